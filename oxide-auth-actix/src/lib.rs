@@ -12,16 +12,19 @@ use actix_web::{
         header::{self, HeaderMap, InvalidHeaderValue},
         StatusCode,
     },
+    web::Bytes,
     web::Form,
     web::Query,
     FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, Responder, ResponseError,
 };
 use futures::future::{self, FutureExt, LocalBoxFuture, Ready};
 use oxide_auth::{
-    endpoint::{Endpoint, NormalizedParameter, OAuthError, QueryParameter, WebRequest, WebResponse},
+    endpoint::{
+        cookie_value, Endpoint, NormalizedParameter, OAuthError, QueryParameter, WebRequest, WebResponse,
+    },
     frontends::simple::endpoint::Error,
 };
-use std::{borrow::Cow, convert::TryFrom, error, fmt};
+use std::{borrow::Cow, convert::TryFrom, error, fmt, time::Duration};
 use url::Url;
 
 mod operations;
@@ -111,11 +114,14 @@ pub struct OAuthMessage<Operation, Extras>(Operation, Extras);
 /// Type implementing `WebRequest` as well as `FromRequest` for use in route handlers
 ///
 /// This type consumes the body of the HttpRequest upon extraction, so be careful not to use it in
-/// places you also expect an application payload
+/// places you also expect an application payload. The unparsed body is kept as well and available
+/// to extensions through `WebRequest::raw_body`.
 pub struct OAuthRequest {
     auth: Option<String>,
+    headers: HeaderMap,
     query: Option<NormalizedParameter>,
     body: Option<NormalizedParameter>,
+    raw_body: Option<Bytes>,
 }
 
 impl OAuthResponse {
@@ -136,6 +142,7 @@ impl OAuthResponse {
 /// request upon extraction
 pub struct OAuthResource {
     auth: Option<String>,
+    headers: HeaderMap,
 }
 
 #[derive(Clone, Debug)]
@@ -187,10 +194,17 @@ impl OAuthRequest {
             .await
             .ok()
             .map(|q: Query<NormalizedParameter>| q.into_inner());
-        let body = Form::from_request(&req, &mut payload)
-            .await
-            .ok()
-            .map(|b: Form<NormalizedParameter>| b.into_inner());
+        // Buffer the body once for both the form and extensions.
+        let (raw_body, body) = match Bytes::from_request(&req, &mut payload).await {
+            Ok(bytes) => {
+                let body = Form::from_request(&req, &mut Payload::from(bytes.clone()))
+                    .await
+                    .ok()
+                    .map(|b: Form<NormalizedParameter>| b.into_inner());
+                (Some(bytes), body)
+            }
+            Err(_) => (None, None),
+        };
 
         let mut all_auth = req.headers().get_all(header::AUTHORIZATION);
         let optional = all_auth.next();
@@ -201,7 +215,13 @@ impl OAuthRequest {
             optional.and_then(|hv| hv.to_str().ok().map(str::to_owned))
         };
 
-        Ok(OAuthRequest { auth, query, body })
+        Ok(OAuthRequest {
+            auth,
+            headers: req.headers().clone(),
+            query,
+            body,
+            raw_body,
+        })
     }
 
     /// Fetch the authorization header from the request
@@ -237,7 +257,10 @@ impl OAuthResource {
             optional.and_then(|hv| hv.to_str().ok().map(str::to_owned))
         };
 
-        Ok(OAuthResource { auth })
+        Ok(OAuthResource {
+            auth,
+            headers: req.headers().clone(),
+        })
     }
 
     /// Turn this OAuthResource into an OAuthRequest for processing
@@ -245,7 +268,9 @@ impl OAuthResource {
         OAuthRequest {
             query: None,
             body: None,
+            raw_body: None,
             auth: self.auth,
+            headers: self.headers,
         }
     }
 }
@@ -302,6 +327,35 @@ impl WebRequest for OAuthRequest {
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
         Ok(self.auth.as_deref().map(Cow::Borrowed))
     }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        for value in self.headers.get_all(header::COOKIE) {
+            let value = value.to_str().map_err(|_| WebError::Encoding)?;
+            if let Some(value) = cookie_value(value, name) {
+                return Ok(Some(Cow::Borrowed(value)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        let mut all = self.headers.get_all(name);
+        let value = match (all.next(), all.next()) {
+            (None, _) => return Ok(None),
+            (Some(value), None) => value,
+            (Some(_), Some(_)) => return Err(WebError::Encoding),
+        };
+
+        value
+            .to_str()
+            .map(|value| Some(Cow::Borrowed(value)))
+            .map_err(|_| WebError::Encoding)
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
+    }
 }
 
 impl WebResponse for OAuthResponse {
@@ -325,6 +379,41 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::INTERNAL_SERVER_ERROR;
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::TOO_MANY_REQUESTS;
+        match retry_after {
+            Some(retry_after) => {
+                let seconds = retry_after.as_secs().to_string();
+                self.headers
+                    .insert(header::RETRY_AFTER, TryFrom::try_from(seconds)?);
+            }
+            None => {
+                self.headers.remove(header::RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::SERVICE_UNAVAILABLE;
+        match retry_after {
+            Some(retry_after) => {
+                let seconds = retry_after.as_secs().to_string();
+                self.headers
+                    .insert(header::RETRY_AFTER, TryFrom::try_from(seconds)?);
+            }
+            None => {
+                self.headers.remove(header::RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
     fn unauthorized(&mut self, kind: &str) -> Result<(), Self::Error> {
         self.status = StatusCode::UNAUTHORIZED;
         self.headers
@@ -332,6 +421,13 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn forbidden(&mut self, kind: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::FORBIDDEN;
+        self.headers
+            .insert(header::WWW_AUTHENTICATE, TryFrom::try_from(kind)?);
+        Ok(())
+    }
+
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.body = Some(text.to_owned());
         self.headers
@@ -345,6 +441,14 @@ impl WebResponse for OAuthResponse {
             .insert(header::CONTENT_TYPE, TryFrom::try_from("application/json")?);
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.headers
+            .insert(header::CACHE_CONTROL, TryFrom::try_from("no-store")?);
+        self.headers
+            .insert(header::PRAGMA, TryFrom::try_from("no-cache")?);
+        Ok(())
+    }
 }
 
 impl<Operation, Extras> Message for OAuthMessage<Operation, Extras>
@@ -471,5 +575,96 @@ impl error::Error for WebError {
 }
 
 impl ResponseError for WebError {
-    // Default to 500 for now
+    // Default to 500 for now, transient failures of the endpoint are answered with 503
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebError::Endpoint(OAuthError::TemporarilyUnavailable(_)) => StatusCode::SERVICE_UNAVAILABLE,
+            WebError::Authorization => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut builder = HttpResponseBuilder::new(self.status_code());
+        builder.content_type("text/plain; charset=utf-8");
+        if let WebError::Endpoint(OAuthError::TemporarilyUnavailable(retry_after)) = self {
+            builder.insert_header((header::RETRY_AFTER, retry_after.as_secs()));
+        }
+        builder.body(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = OAuthResponse::ok();
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.get_headers().get(header::RETRY_AFTER).unwrap(), "30");
+    }
+
+    #[test]
+    fn custom_header() {
+        let request = actix_web::test::TestRequest::default()
+            .insert_header(("X-Custom", "value"))
+            .append_header(("X-Twice", "first"))
+            .append_header(("X-Twice", "second"))
+            .to_http_request();
+        let mut request = OAuthResource::new(&request).unwrap().into_request();
+
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        assert!(request.header("X-Twice").is_err());
+    }
+
+    #[test]
+    fn cookie() {
+        let request = actix_web::test::TestRequest::default()
+            .append_header((header::COOKIE, "theme=dark"))
+            .append_header((header::COOKIE, "session=abc; token=xyz"))
+            .to_http_request();
+        let mut request = OAuthResource::new(&request).unwrap().into_request();
+
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        let request = actix_web::test::TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer token"))
+            .to_http_request();
+        let resource = OAuthResource::new(&request).unwrap();
+        assert_eq!(
+            resource.into_request().authorization_header(),
+            Some("Bearer token")
+        );
+
+        let request = actix_web::test::TestRequest::default()
+            .append_header((header::AUTHORIZATION, "Bearer token"))
+            .append_header((header::AUTHORIZATION, "Bearer other"))
+            .to_http_request();
+        match OAuthResource::new(&request) {
+            Err(error @ WebError::Authorization) => {
+                assert_eq!(error.status_code(), StatusCode::BAD_REQUEST)
+            }
+            _ => panic!("duplicate authorization headers should be rejected"),
+        }
+    }
+
+    #[test]
+    fn temporarily_unavailable() {
+        let error = WebError::from(OAuthError::TemporarilyUnavailable(Duration::from_secs(30)));
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "30");
+
+        let response = WebError::from(OAuthError::PrimitiveError).error_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 }
@@ -1,6 +1,31 @@
 pub mod refresh {
+    use async_trait::async_trait;
     use oxide_auth::code_grant::refresh::{BearerToken, Error, Input, Output, Refresh, Request};
-    use oxide_auth::primitives::{grant::Grant, registrar::RegistrarError};
+    use oxide_auth::primitives::grant::{Extensions, Grant};
+    use oxide_auth::primitives::registrar::{GrantType, RegistrarError};
+
+    /// A system of addons recomputing the extension data of refreshed grants.
+    ///
+    /// An endpoint not having any extension may use `&mut ()`, which keeps the data unchanged.
+    #[async_trait]
+    pub trait Extension {
+        /// Inspect the request and the extension data of the grant to produce the refreshed data.
+        ///
+        /// The returned data replaces the data of the grant entirely. Failing rejects the refresh
+        /// with an `invalid_grant` error.
+        async fn extend(
+            &mut self, request: &(dyn Request + Sync), data: Extensions,
+        ) -> std::result::Result<Extensions, ()>;
+    }
+
+    #[async_trait]
+    impl Extension for () {
+        async fn extend(
+            &mut self, _: &(dyn Request + Sync), data: Extensions,
+        ) -> std::result::Result<Extensions, ()> {
+            Ok(data)
+        }
+    }
 
     pub trait Endpoint {
         /// Authenticate the requesting confidential client.
@@ -8,6 +33,13 @@ pub mod refresh {
 
         /// Recover and test the provided refresh token then issue new tokens.
         fn issuer(&mut self) -> &mut (dyn crate::primitives::Issuer + Send);
+
+        /// An extension recomputing the data of the refreshed grant.
+        ///
+        /// The data of the grant is copied unchanged if there is none.
+        fn extension(&mut self) -> Option<&mut (dyn Extension + Send)> {
+            None
+        }
     }
 
     pub async fn refresh(
@@ -24,7 +56,24 @@ pub mod refresh {
         loop {
             let input = match requested {
                 Requested::None => Input::None,
-                Requested::Refresh { token, grant } => {
+                Requested::Refresh { token, mut grant } => {
+                    // The grant belongs to the client, whether it authenticated or not.
+                    handler
+                        .registrar()
+                        .check_grant_type(&grant.client_id, GrantType::RefreshToken)
+                        .await
+                        .map_err(|err| match err {
+                            RegistrarError::PrimitiveError => Error::Primitive,
+                            RegistrarError::Unspecified => Error::unauthorized_client(),
+                        })?;
+                    if let Some(extension) = handler.extension() {
+                        let extensions = std::mem::take(&mut grant.extensions);
+                        grant.extensions = extension
+                            .extend(request, extensions)
+                            .await
+                            .map_err(|()| Error::invalid_grant())?;
+                    }
+
                     let refreshed = handler
                         .issuer()
                         .refresh(&token, *grant)
@@ -78,16 +127,48 @@ pub mod refresh {
 }
 
 pub mod resource {
-    use oxide_auth::code_grant::resource::{Error, Input, Output, Request, Resource};
-    use oxide_auth::primitives::grant::Grant;
+    use async_trait::async_trait;
+    use oxide_auth::code_grant::resource::{
+        AccessFailure, Authenticate, Error, ErrorCode, Input, Output, Request, Resource,
+    };
+    use oxide_auth::primitives::grant::{Extensions, Grant};
     use oxide_auth::primitives::scope::Scope;
 
+    /// A system of addons checking the extension data of grants presented to a resource.
+    ///
+    /// An endpoint not having any extension may use `&mut ()`, which accepts every grant.
+    #[async_trait]
+    pub trait Extension {
+        /// Inspect the request and the extension data of the grant of a valid token.
+        ///
+        /// Failing denies access with an `invalid_token` error.
+        async fn check(
+            &mut self, request: &(dyn Request + Sync), data: &Extensions,
+        ) -> std::result::Result<(), ()>;
+    }
+
+    #[async_trait]
+    impl Extension for () {
+        async fn check(
+            &mut self, _: &(dyn Request + Sync), _: &Extensions,
+        ) -> std::result::Result<(), ()> {
+            Ok(())
+        }
+    }
+
     pub trait Endpoint {
         /// The list of possible scopes required by the resource endpoint.
         fn scopes(&mut self) -> &[Scope];
 
         /// Recover and test the provided refresh token then issue new tokens.
         fn issuer(&mut self) -> &mut (dyn crate::primitives::Issuer + Send);
+
+        /// An extension checking the data of the grant.
+        ///
+        /// Every grant is accepted if there is none.
+        fn extension(&mut self) -> Option<&mut (dyn Extension + Send)> {
+            None
+        }
     }
 
     pub async fn protect(
@@ -119,13 +200,34 @@ pub mod resource {
 
             requested = match resource.advance(input) {
                 Output::Err(error) => return Err(error),
-                Output::Ok(grant) => return Ok(*grant),
+                Output::Ok(grant) => return checked(handler, req, *grant).await,
                 Output::GetRequest => Requested::Request,
                 Output::DetermineScopes => Requested::Scopes,
                 Output::Recover { token } => Requested::Grant(token.to_string()),
             };
         }
     }
+
+    async fn checked(
+        handler: &mut (dyn Endpoint + Send + Sync), req: &(dyn Request + Sync), grant: Grant,
+    ) -> Result<Grant, Error> {
+        if let Some(extension) = handler.extension() {
+            if extension.check(req, &grant.extensions).await.is_err() {
+                return Err(Error::AccessDenied {
+                    failure: AccessFailure {
+                        code: Some(ErrorCode::InvalidToken),
+                    },
+                    authenticate: Authenticate {
+                        realm: None,
+                        scope: None,
+                        description: None,
+                    },
+                });
+            }
+        }
+
+        Ok(grant)
+    }
 }
 
 pub mod client_credentials {
@@ -141,8 +243,9 @@ pub mod client_credentials {
         endpoint::{PreGrant, Scope, Solicitation},
         primitives::{
             grant::{Extensions, Grant},
+            issuer::RefreshIssuance,
             prelude::ClientUrl,
-            registrar::{BoundClient, RegistrarError},
+            registrar::{BoundClient, GrantType, RegistrarError},
         },
     };
 
@@ -201,6 +304,19 @@ pub mod client_credentials {
             Solicitation::new(&self.pre_grant)
         }
 
+        /// Restrict the grant to the part of the negotiated scope approved by the resource owner.
+        ///
+        /// Fails without changing the grant if `scope` is empty or not contained in the negotiated
+        /// scope, the owner can only narrow what the registrar has chosen.
+        pub fn restrict_scope(&mut self, scope: Scope) -> Result<(), ()> {
+            if scope.is_empty() || !self.pre_grant.scope.priviledged_to(&scope) {
+                return Err(());
+            }
+
+            self.pre_grant.scope = scope;
+            Ok(())
+        }
+
         /// Inform the backend about consent from a resource owner.
         ///
         /// Use negotiated parameters to authorize a client for an owner. The endpoint SHOULD be the
@@ -210,23 +326,28 @@ pub mod client_credentials {
         ) -> Result<BearerToken, Error> {
             let pre_grant = self.pre_grant.clone();
 
-            let mut token = handler
+            let refresh = if allow_refresh_token {
+                RefreshIssuance::Default
+            } else {
+                RefreshIssuance::Never
+            };
+
+            let token = handler
                 .issuer()
-                .issue(Grant {
-                    owner_id,
-                    client_id: pre_grant.client_id,
-                    redirect_uri: pre_grant.redirect_uri.into_url(),
-                    scope: pre_grant.scope.clone(),
-                    until: Utc::now() + Duration::minutes(10),
-                    extensions: self.extensions,
-                })
+                .issue_with(
+                    Grant {
+                        owner_id,
+                        client_id: pre_grant.client_id,
+                        redirect_uri: pre_grant.redirect_uri.into_url(),
+                        scope: pre_grant.scope.clone(),
+                        until: Utc::now() + Duration::minutes(10),
+                        extensions: self.extensions,
+                    },
+                    refresh,
+                )
                 .await
                 .map_err(|()| Error::Primitive(Box::new(PrimitiveError::empty())))?;
 
-            if !allow_refresh_token {
-                token.refresh = None;
-            }
-
             Ok(token.convert_bearer_token(self.pre_grant))
         }
     }
@@ -270,6 +391,19 @@ pub mod client_credentials {
                                 }))
                             }
                         })?;
+                    handler
+                        .registrar()
+                        .check_grant_type(&client, GrantType::ClientCredentials)
+                        .await
+                        .map_err(|err| match err {
+                            RegistrarError::Unspecified => Error::unauthorized_client(),
+                            RegistrarError::PrimitiveError => {
+                                Error::Primitive(Box::new(PrimitiveError {
+                                    grant: None,
+                                    extensions: None,
+                                }))
+                            }
+                        })?;
                     Input::Authenticated
                 }
                 Requested::Bind { client_id } => {
@@ -351,7 +485,7 @@ pub mod access_token {
         },
         primitives::{
             grant::{Extensions, Grant},
-            registrar::RegistrarError,
+            registrar::{GrantType, RegistrarError},
         },
     };
     // use crate::endpoint::access_token::WrappedRequest;
@@ -401,6 +535,14 @@ pub mod access_token {
                 client: &'a str,
                 passdata: Option<&'a [u8]>,
             },
+            AuthenticateAssertion {
+                client: &'a str,
+                assertion: &'a str,
+            },
+            AuthenticateCertificate {
+                client: &'a str,
+                certificate: &'a [u8],
+            },
             Recover(&'a str),
             Extend {
                 extensions: &'a mut Extensions,
@@ -430,6 +572,41 @@ pub mod access_token {
                                 }))
                             }
                         })?;
+                    check_grant_type(handler.registrar(), client).await?;
+                    Input::Authenticated
+                }
+                Requested::AuthenticateAssertion { client, assertion } => {
+                    handler
+                        .registrar()
+                        .check_assertion(client, assertion)
+                        .await
+                        .map_err(|err| match err {
+                            RegistrarError::Unspecified => Error::unauthorized("basic"),
+                            RegistrarError::PrimitiveError => {
+                                Error::Primitive(Box::new(PrimitiveError {
+                                    grant: None,
+                                    extensions: None,
+                                }))
+                            }
+                        })?;
+                    check_grant_type(handler.registrar(), client).await?;
+                    Input::Authenticated
+                }
+                Requested::AuthenticateCertificate { client, certificate } => {
+                    handler
+                        .registrar()
+                        .check_certificate(client, certificate)
+                        .await
+                        .map_err(|err| match err {
+                            RegistrarError::Unspecified => Error::unauthorized("basic"),
+                            RegistrarError::PrimitiveError => {
+                                Error::Primitive(Box::new(PrimitiveError {
+                                    grant: None,
+                                    extensions: None,
+                                }))
+                            }
+                        })?;
+                    check_grant_type(handler.registrar(), client).await?;
                     Input::Authenticated
                 }
                 Requested::Recover(code) => {
@@ -466,6 +643,12 @@ pub mod access_token {
                 Output::Authenticate { client, passdata } => {
                     Requested::Authenticate { client, passdata }
                 }
+                Output::AuthenticateAssertion { client, assertion } => {
+                    Requested::AuthenticateAssertion { client, assertion }
+                }
+                Output::AuthenticateCertificate { client, certificate } => {
+                    Requested::AuthenticateCertificate { client, certificate }
+                }
                 Output::Recover { code } => Requested::Recover(code),
                 Output::Extend { extensions, .. } => Requested::Extend { extensions },
                 Output::Issue { grant } => Requested::Issue { grant },
@@ -474,21 +657,41 @@ pub mod access_token {
             };
         }
     }
+
+    /// Check that the authenticated client may redeem authorization codes.
+    async fn check_grant_type(
+        registrar: &(dyn crate::primitives::Registrar + Sync), client_id: &str,
+    ) -> Result<(), Error> {
+        registrar
+            .check_grant_type(client_id, GrantType::AuthorizationCode)
+            .await
+            .map_err(|err| match err {
+                RegistrarError::Unspecified => Error::unauthorized_client(),
+                RegistrarError::PrimitiveError => Error::Primitive(Box::new(PrimitiveError {
+                    grant: None,
+                    extensions: None,
+                })),
+            })
+    }
 }
 
 pub mod authorization {
     use async_trait::async_trait;
-    use chrono::{Duration, Utc};
+    use chrono::{DateTime, Duration, Utc};
     use oxide_auth::{
         code_grant::{
-            authorization::{Authorization, Error, ErrorUrl, Input, Output, Request},
+            authorization::{
+                excess_scope, required_pkce, Authorization, AuthorizationResponse, Error, ErrorUrl,
+                ImplicitResponse, Input, Output, Request,
+            },
             error::{AuthorizationError, AuthorizationErrorType},
         },
         endpoint::{PreGrant, Scope, Solicitation},
         primitives::{
             grant::{Extensions, Grant},
+            issuer::RefreshIssuance,
             prelude::ClientUrl,
-            registrar::{BoundClient, ExactUrl, RegistrarError},
+            registrar::{BoundClient, ExactUrl, GrantType, RegistrarError},
         },
     };
     use url::Url;
@@ -538,27 +741,97 @@ pub mod authorization {
         pre_grant: PreGrant,
         state: Option<String>,
         extensions: Extensions,
+        implicit: bool,
+        requested_scope: Option<Scope>,
+    }
+
+    /// Whether the request asks for an implicit grant that the endpoint allows.
+    fn implicit(request: &dyn Request) -> bool {
+        request.allow_implicit() && request.response_type().as_deref() == Some("token")
     }
 
     impl Pending {
         /// Reference this pending state as a solicitation.
         pub fn as_solicitation(&self) -> Solicitation<'_> {
-            let base = Solicitation::new(&self.pre_grant);
+            let mut base = Solicitation::new(&self.pre_grant);
+            if let Some(scope) = &self.requested_scope {
+                base = base.with_requested_scope(scope);
+            }
             match self.state {
                 None => base,
                 Some(ref state) => base.with_state(state),
             }
         }
 
+        /// Restrict the grant to the part of the negotiated scope approved by the resource owner.
+        ///
+        /// Fails without changing the grant if `scope` is empty or not contained in the negotiated
+        /// scope, the owner can only narrow what the registrar has chosen.
+        pub fn restrict_scope(&mut self, scope: Scope) -> Result<(), ()> {
+            if scope.is_empty() || !self.pre_grant.scope.priviledged_to(&scope) {
+                return Err(());
+            }
+
+            self.pre_grant.scope = scope;
+            Ok(())
+        }
+
         /// Denies the request, which redirects to the client for which the request originated.
         pub fn deny(self) -> Result<Url, Error> {
             let url = self.pre_grant.redirect_uri;
             let mut error = AuthorizationError::default();
             error.set_type(AuthorizationErrorType::AccessDenied);
-            let error = ErrorUrl::new(url.into(), self.state.as_deref(), error);
+            let mut error = ErrorUrl::new(url.into(), self.state.as_deref(), error);
+            if self.implicit {
+                error = error.in_fragment();
+            }
             Err(Error::Redirect(error))
         }
 
+        /// Whether the request asked for an implicit grant.
+        ///
+        /// Such requests must be answered with [`implicit_response`] instead of an authorization
+        /// code.
+        ///
+        /// [`implicit_response`]: #method.implicit_response
+        pub fn is_implicit(&self) -> bool {
+            self.implicit
+        }
+
+        /// Inform the backend about consent from a resource owner, issuing an access token directly.
+        ///
+        /// This completes an implicit grant request. The token is issued without a refresh token
+        /// and its expiry is reported relative to `now`, the current time of the endpoint.
+        pub async fn implicit_response(
+            self, issuer: &mut (dyn crate::primitives::Issuer + Send), owner_id: Cow<'_, str>,
+            now: DateTime<Utc>,
+        ) -> Result<ImplicitResponse, Error> {
+            let redirect_uri = self.pre_grant.redirect_uri.to_url();
+            let scope = self.pre_grant.scope.clone();
+
+            let grant = Grant {
+                owner_id: owner_id.into_owned(),
+                client_id: self.pre_grant.client_id,
+                redirect_uri: self.pre_grant.redirect_uri.into(),
+                scope: self.pre_grant.scope,
+                until: now + Duration::minutes(10),
+                extensions: self.extensions,
+            };
+            let token = issuer
+                .issue_with(grant, RefreshIssuance::Never)
+                .await
+                .map_err(|()| Error::PrimitiveError)?;
+
+            Ok(ImplicitResponse {
+                access_token: token.token,
+                token_type: token.token_type,
+                expires_in: token.until.signed_duration_since(now).num_seconds(),
+                scope,
+                state: self.state,
+                redirect_uri,
+            })
+        }
+
         /// Inform the backend about consent from a resource owner.
         ///
         /// Use negotiated parameters to authorize a client for an owner. The endpoint SHOULD be the
@@ -566,9 +839,23 @@ pub mod authorization {
         pub async fn authorize(
             self, handler: &mut (dyn Endpoint + Send), owner_id: Cow<'_, str>,
         ) -> Result<Url, Error> {
-            let mut url = self.pre_grant.redirect_uri.to_url();
+            self.authorization_response(handler, owner_id)
+                .await
+                .map(|response| response.redirect_url())
+        }
+
+        /// Inform the backend about consent from a resource owner, without rendering the response.
+        ///
+        /// This is the same as [`authorize`] but returns the structured response. Its `iss` is not
+        /// set, since the issuer identifier is not known to the pending request.
+        ///
+        /// [`authorize`]: #method.authorize
+        pub async fn authorization_response(
+            self, handler: &mut (dyn Endpoint + Send), owner_id: Cow<'_, str>,
+        ) -> Result<AuthorizationResponse, Error> {
+            let redirect_uri = self.pre_grant.redirect_uri.to_url();
 
-            let grant = handler
+            let code = handler
                 .authorizer()
                 .authorize(Grant {
                     owner_id: owner_id.into_owned(),
@@ -581,11 +868,12 @@ pub mod authorization {
                 .await
                 .map_err(|()| Error::PrimitiveError)?;
 
-            url.query_pairs_mut()
-                .append_pair("code", grant.as_str())
-                .extend_pairs(self.state.map(|v| ("state", v)))
-                .finish();
-            Ok(url)
+            Ok(AuthorizationResponse {
+                code,
+                state: self.state,
+                redirect_uri,
+                iss: None,
+            })
         }
 
         /// Retrieve a reference to the negotiated parameters (e.g. scope). These should be displayed
@@ -624,6 +912,8 @@ pub mod authorization {
         let mut authorization = Authorization::new(request);
         let mut requested = Requested::None;
         let mut the_redirect_uri = None;
+        let mut require_pkce = false;
+        let mut requested_scope = None;
 
         loop {
             let input = match requested {
@@ -641,6 +931,53 @@ pub mod authorization {
                         Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
                         Ok(pre_grant) => pre_grant,
                     };
+                    let grant_type = if implicit(request) {
+                        GrantType::Implicit
+                    } else {
+                        GrantType::AuthorizationCode
+                    };
+                    let registrar = handler.registrar();
+                    match registrar
+                        .check_grant_type(&bound_client.client_id, grant_type)
+                        .await
+                    {
+                        Ok(()) => (),
+                        Err(RegistrarError::Unspecified) => {
+                            let prepared_error = ErrorUrl::with_request(
+                                request,
+                                bound_client.redirect_uri.to_url(),
+                                AuthorizationErrorType::UnauthorizedClient,
+                            );
+                            return Err(Error::Redirect(prepared_error));
+                        }
+                        Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
+                    }
+                    if implicit(request) {
+                        // Tokens in the fragment are visible to the user agent, confidential
+                        // clients must use the code grant instead.
+                        let registrar = handler.registrar();
+                        match registrar.check(&bound_client.client_id, None).await {
+                            Ok(()) => (),
+                            Err(RegistrarError::Unspecified) => {
+                                let prepared_error = ErrorUrl::with_request(
+                                    request,
+                                    bound_client.redirect_uri.to_url(),
+                                    AuthorizationErrorType::UnauthorizedClient,
+                                );
+                                return Err(Error::Redirect(prepared_error));
+                            }
+                            Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
+                        }
+                    }
+                    if request.require_pkce_for_public_clients() {
+                        // Public clients are those that authenticate without any passphrase.
+                        let registrar = handler.registrar();
+                        require_pkce = match registrar.check(&bound_client.client_id, None).await {
+                            Ok(()) => true,
+                            Err(RegistrarError::Unspecified) => false,
+                            Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
+                        };
+                    }
                     the_redirect_uri = Some(bound_client.redirect_uri.clone().into_owned());
                     Input::Bound {
                         request,
@@ -648,6 +985,9 @@ pub mod authorization {
                     }
                 }
                 Requested::Extend => {
+                    if require_pkce {
+                        required_pkce(request, the_redirect_uri.as_ref().unwrap().to_url())?;
+                    }
                     let grant_extension = match handler.extension().extend(request).await {
                         Ok(extension_data) => extension_data,
                         Err(()) => {
@@ -670,19 +1010,23 @@ pub mod authorization {
                         client_id: Cow::Owned(client_id),
                         redirect_uri: Cow::Owned(redirect_uri.clone().into()),
                     };
-                    let pre_grant = handler.registrar().negotiate(bound_client, scope).await.map_err(
-                        |err| match err {
+                    let pre_grant = handler
+                        .registrar()
+                        .negotiate(bound_client, scope.clone())
+                        .await
+                        .map_err(|err| match err {
                             RegistrarError::PrimitiveError => Error::PrimitiveError,
                             RegistrarError::Unspecified => {
                                 let prepared_error = ErrorUrl::with_request(
                                     request,
-                                    redirect_uri,
+                                    redirect_uri.clone(),
                                     AuthorizationErrorType::InvalidScope,
                                 );
                                 Error::Redirect(prepared_error)
                             }
-                        },
-                    )?;
+                        })?;
+                    excess_scope(request, scope.as_ref(), &pre_grant, redirect_uri)?;
+                    requested_scope = scope;
                     Input::Negotiated {
                         pre_grant,
                         state: request.state().map(|s| s.into_owned()),
@@ -713,6 +1057,8 @@ pub mod authorization {
                         pre_grant,
                         state,
                         extensions,
+                        implicit: implicit(request),
+                        requested_scope,
                     })
                 }
                 Output::Err(e) => return Err(e),
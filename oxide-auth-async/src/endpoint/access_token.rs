@@ -4,15 +4,19 @@ use std::{borrow::Cow, marker::PhantomData};
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use oxide_auth::{
-    endpoint::{QueryParameter, WebRequest, OAuthError, WebResponse, Template, NormalizedParameter},
+    endpoint::{
+        QueryParameter, WebRequest, OAuthError, WebResponse, Template, NormalizedParameter,
+        exceeds_parameter_limit,
+    },
     code_grant::{
         accesstoken::{
-            Error as TokenError, Request as TokenRequest, Authorization as TokenAuthorization,
+            BearerToken, Error as TokenError, Request as TokenRequest,
+            Authorization as TokenAuthorization,
         },
     },
 };
 
-use super::Endpoint;
+use super::{Endpoint, FlowErrorDetail, primitive_error};
 use crate::{
     code_grant::access_token::{Extension, Endpoint as TokenEndpoint, access_token},
     primitives::{Issuer, Registrar, Authorizer},
@@ -37,6 +41,9 @@ where
 {
     endpoint: WrappedToken<E, R>,
     allow_credentials_in_body: bool,
+    require_pkce_verifier: bool,
+    report_refresh_expiry: bool,
+    advertise_expiry: Option<Box<dyn Fn(i64) -> i64 + Send + Sync>>,
 }
 
 struct WrappedToken<E, R>
@@ -62,6 +69,18 @@ pub struct WrappedRequest<R: WebRequest> {
 
     /// The credentials-in-body flag from the flow.
     allow_credentials_in_body: bool,
+
+    /// Whether codes with a PKCE challenge require a verifier.
+    require_pkce_verifier: bool,
+
+    /// A copy of the unparsed body, if the request had one.
+    raw_body: Option<Vec<u8>>,
+
+    /// The `DPoP` header, if the request had one.
+    dpop: Option<String>,
+
+    /// The client certificate of the connection.
+    certificate: Option<Vec<u8>>,
 }
 
 #[derive(Debug)]
@@ -112,6 +131,9 @@ where
                 r_type: PhantomData,
             },
             allow_credentials_in_body: false,
+            require_pkce_verifier: false,
+            report_refresh_expiry: false,
+            advertise_expiry: None,
         })
     }
 
@@ -126,6 +148,39 @@ where
         self.allow_credentials_in_body = allow;
     }
 
+    /// Reject codes issued with a PKCE challenge when the request has no `code_verifier`.
+    ///
+    /// Single-page applications and other public clients rely on PKCE to protect their codes. With
+    /// this option, a stored challenge is enforced with an `invalid_grant` error even if the
+    /// endpoint does not use the `Pkce` extension. Verifiers that are present are still checked by
+    /// the extension only.
+    pub fn require_pkce_verifier(&mut self) {
+        self.require_pkce_verifier = true;
+    }
+
+    /// Report the remaining lifetime of the refresh token in the response.
+    ///
+    /// The lifetime is sent as `refresh_token_expires_in`, next to `expires_in` of the access
+    /// token. This parameter is not part of RFC 6749 and only sent if the issuer limits the
+    /// lifetime of its refresh tokens, e.g. with `TokenMap::refresh_valid_for`. Disabled by default.
+    pub fn report_refresh_expiry(&mut self, report: bool) {
+        self.report_refresh_expiry = report;
+    }
+
+    /// Post-process the `expires_in` advertised in the response.
+    ///
+    /// The function receives the remaining lifetime of the access token in seconds and returns the
+    /// value sent to the client, for example rounded down or capped at a maximum. This only changes
+    /// the response. The grant stored by the issuer keeps its expiry, so the token is recovered
+    /// until then regardless of the advertised value. Advertising more than the remaining lifetime
+    /// makes clients use tokens that have already expired.
+    pub fn advertise_expiry<F>(&mut self, expires_in: F)
+    where
+        F: Fn(i64) -> i64 + Send + Sync + 'static,
+    {
+        self.advertise_expiry = Some(Box::new(expires_in));
+    }
+
     /// Use the checked endpoint to check for authorization for a resource.
     ///
     /// ## Panics
@@ -133,11 +188,10 @@ where
     /// When the registrar, authorizer, or issuer returned by the endpoint is suddenly
     /// `None` when previously it was `Some(_)`.
     pub async fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
-        let issued = access_token(
-            &mut self.endpoint,
-            &WrappedRequest::new(&mut request, self.allow_credentials_in_body),
-        )
-        .await;
+        let limit = self.endpoint.inner.parameter_limit();
+        let mut wrapped = WrappedRequest::new(&mut request, self.allow_credentials_in_body, limit);
+        wrapped.require_pkce_verifier = self.require_pkce_verifier;
+        let issued = access_token(&mut self.endpoint, &wrapped).await;
 
         let token = match issued {
             Err(error) => return token_error(&mut self.endpoint.inner, &mut request, error),
@@ -146,12 +200,32 @@ where
 
         let mut response = self.endpoint.inner.response(&mut request, Template::new_ok())?;
         response
-            .body_json(&token.to_json())
+            .no_store()
+            .map_err(|err| self.endpoint.inner.web_error(err))?;
+        response
+            .body_json(&token_json(
+                &token,
+                self.report_refresh_expiry,
+                self.advertise_expiry.as_deref(),
+            ))
             .map_err(|err| self.endpoint.inner.web_error(err))?;
         Ok(response)
     }
 }
 
+fn token_json(
+    token: &BearerToken, report_refresh_expiry: bool,
+    advertise_expiry: Option<&(dyn Fn(i64) -> i64 + Send + Sync)>,
+) -> String {
+    if let Some(expires_in) = advertise_expiry {
+        token.to_json_with_expiry(report_refresh_expiry, expires_in)
+    } else if report_refresh_expiry {
+        token.to_json_with_refresh_expiry()
+    } else {
+        token.to_json()
+    }
+}
+
 fn token_error<E, R>(
     endpoint: &mut E, request: &mut R, error: TokenError,
 ) -> Result<R::Response, E::Error>
@@ -161,15 +235,24 @@ where
 {
     Ok(match error {
         TokenError::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response =
                 endpoint.response(request, Template::new_bad(Some(json.description())))?;
             response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         TokenError::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response = endpoint.response(
                 request,
                 Template::new_unauthorized(None, Some(json.description())),
@@ -177,6 +260,7 @@ where
             response
                 .unauthorized(&scheme)
                 .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
@@ -184,7 +268,11 @@ where
         }
         TokenError::Primitive(_) => {
             // FIXME: give the context for restoration.
-            return Err(endpoint.error(OAuthError::PrimitiveError));
+            return primitive_error(
+                endpoint,
+                request,
+                "access token: registrar, authorizer or issuer failed",
+            );
         }
     })
 }
@@ -215,11 +303,13 @@ where
 }
 
 impl<R: WebRequest> WrappedRequest<R> {
-    pub fn new(request: &mut R, credentials: bool) -> Self {
-        Self::new_or_fail(request, credentials).unwrap_or_else(Self::from_err)
+    pub fn new(request: &mut R, credentials: bool, limit: Option<usize>) -> Self {
+        Self::new_or_fail(request, credentials, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &mut R, credentials: bool) -> Result<Self, FailParse<R::Error>> {
+    fn new_or_fail(
+        request: &mut R, credentials: bool, limit: Option<usize>,
+    ) -> Result<Self, FailParse<R::Error>> {
         // If there is a header, it must parse correctly.
         let authorization = match request.authheader() {
             Err(err) => return Err(FailParse::Err(err)),
@@ -227,11 +317,30 @@ impl<R: WebRequest> WrappedRequest<R> {
             Ok(None) => None,
         };
 
+        let dpop = match request.header("DPoP") {
+            Err(err) => return Err(FailParse::Err(err)),
+            Ok(header) => header.map(Cow::into_owned),
+        };
+
+        let certificate = match request.client_certificate() {
+            Err(err) => return Err(FailParse::Err(err)),
+            Ok(certificate) => certificate.map(Cow::into_owned),
+        };
+
+        let body = request.urlbody().map_err(FailParse::Err)?;
+        if exceeds_parameter_limit(&*body, limit) {
+            return Err(FailParse::Invalid);
+        }
+
         Ok(WrappedRequest {
-            body: request.urlbody().map_err(FailParse::Err)?.into_owned(),
+            body: body.into_owned(),
             authorization,
             error: None,
             allow_credentials_in_body: credentials,
+            require_pkce_verifier: false,
+            raw_body: request.raw_body().map(<[u8]>::to_vec),
+            dpop,
+            certificate,
         })
     }
 
@@ -241,6 +350,10 @@ impl<R: WebRequest> WrappedRequest<R> {
             authorization: None,
             error: Some(err),
             allow_credentials_in_body: false,
+            require_pkce_verifier: false,
+            raw_body: None,
+            dpop: None,
+            certificate: None,
         }
     }
 
@@ -316,6 +429,22 @@ impl<R: WebRequest> TokenRequest for WrappedRequest<R> {
     fn allow_credentials_in_body(&self) -> bool {
         self.allow_credentials_in_body
     }
+
+    fn require_pkce_verifier(&self) -> bool {
+        self.require_pkce_verifier
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
+    }
+
+    fn dpop_proof(&self) -> Option<Cow<str>> {
+        self.dpop.as_deref().map(Cow::Borrowed)
+    }
+
+    fn client_certificate(&self) -> Option<Cow<[u8]>> {
+        self.certificate.as_deref().map(Cow::Borrowed)
+    }
 }
 
 impl<E> From<Invalid> for FailParse<E> {
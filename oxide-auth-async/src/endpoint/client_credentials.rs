@@ -6,6 +6,7 @@ use base64::{engine::general_purpose::STANDARD, Engine};
 use oxide_auth::{
     endpoint::{
         NormalizedParameter, QueryParameter, WebResponse, WebRequest, Template, is_authorization_method,
+        exceeds_parameter_limit,
     },
     code_grant::{
         accesstoken::ErrorDescription,
@@ -14,7 +15,7 @@ use oxide_auth::{
     },
 };
 
-use super::{Endpoint, OAuthError, OwnerConsent};
+use super::{primitive_error, Endpoint, FlowError, FlowErrorDetail, OAuthError, OwnerConsent};
 use crate::{
     primitives::{Issuer, Registrar, Authorizer},
     code_grant::client_credentials::{
@@ -26,6 +27,11 @@ use crate::{
 ///
 /// A client may request a token that provides access to their own resources.
 ///
+/// There is no resource owner to interact with, so an owner solicitor is optional. When the
+/// endpoint has one, it is asked to assign an owner for the grant without user interaction.
+/// Otherwise the token is issued right after authenticating the client, with the client
+/// itself as the owner.
+///
 /// Client credentials can be allowed to appear in the request body instead of being
 /// required to be passed as HTTP Basic authorization. This is not recommended and must be
 /// enabled explicitely. See [`allow_credentials_in_body`] for details.
@@ -39,6 +45,7 @@ where
     endpoint: WrappedToken<E, R>,
     allow_credentials_in_body: bool,
     allow_refresh_token: bool,
+    solicit_owner: bool,
 }
 
 struct WrappedToken<E: Endpoint<R>, R: WebRequest> {
@@ -106,6 +113,7 @@ where
             },
             allow_credentials_in_body: false,
             allow_refresh_token: false,
+            solicit_owner: true,
         })
     }
 
@@ -124,7 +132,7 @@ where
     ///
     /// According to [RFC-6749 Section 4.4.3][4.4.3] "A refresh token SHOULD NOT be included" in
     /// the response for the client credentials grant. Following that recommendation, the default
-    /// behaviour of this flow is to discard any refresh token that is returned from the issuer.
+    /// behaviour of this flow is to request a token without a refresh token from the issuer.
     ///
     /// If this behaviour is not what you want (it is possible that your particular application
     /// does have a use for a client credentials refresh token), you may enable this feature.
@@ -134,6 +142,14 @@ where
         self.allow_refresh_token = allow;
     }
 
+    /// Control whether the owner solicitor of the endpoint is consulted.
+    ///
+    /// Enabled by default. When disabled, the solicitor is never called and the client is always
+    /// treated as its own resource owner.
+    pub fn solicit_owner(&mut self, solicit: bool) {
+        self.solicit_owner = solicit;
+    }
+
     /// Use the checked endpoint to check for authorization for a resource.
     ///
     /// ## Panics
@@ -141,39 +157,66 @@ where
     /// When the registrar, authorizer, or issuer returned by the endpoint is suddenly
     /// `None` when previously it was `Some(_)`.
     pub async fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        let limit = self.endpoint.inner.parameter_limit();
         let pending = client_credentials(
             &mut self.endpoint,
-            &WrappedRequest::new(&mut request, self.allow_credentials_in_body),
+            &WrappedRequest::new(&mut request, self.allow_credentials_in_body, limit),
         )
         .await;
 
-        let pending = match pending {
+        let mut pending = match pending {
             Err(error) => {
                 return client_credentials_error(&mut self.endpoint.inner, &mut request, error)
             }
             Ok(pending) => pending,
         };
 
-        let consent = self
-            .endpoint
-            .inner
-            .owner_solicitor()
-            .unwrap()
-            .check_consent(&mut request, pending.as_solicitation())
-            .await;
+        let solicitor = if self.solicit_owner {
+            self.endpoint.inner.owner_solicitor()
+        } else {
+            None
+        };
+
+        let consent = match solicitor {
+            Some(solicitor) => {
+                solicitor
+                    .check_consent(&mut request, pending.as_solicitation())
+                    .await
+            }
+            // The client acts on its own behalf.
+            None => OwnerConsent::Authorized(pending.as_solicitation().pre_grant().client_id.clone()),
+        };
+
+        // A restricted scope that can not be granted is treated as a denial.
+        let consent = match consent {
+            OwnerConsent::AuthorizedScope(owner_id, scope) => match pending.restrict_scope(scope) {
+                Ok(()) => OwnerConsent::Authorized(owner_id),
+                Err(()) => OwnerConsent::Denied,
+            },
+            consent => consent,
+        };
 
         let owner_id = match consent {
             OwnerConsent::Authorized(owner_id) => owner_id,
+            OwnerConsent::AuthorizedScope(..) => unreachable!("Restricted scopes were resolved above"),
             OwnerConsent::Error(error) => return Err(self.endpoint.inner.web_error(error)),
             OwnerConsent::InProgress(..) => {
                 // User interaction is not permitted in the client credentials flow, so
                 // an InProgress response is invalid.
-                return Err(self.endpoint.inner.error(OAuthError::PrimitiveError));
+                return primitive_error(
+                    &mut self.endpoint.inner,
+                    &mut request,
+                    "client credentials: owner solicitor requested user interaction",
+                );
             }
             OwnerConsent::Denied => {
                 let mut error = AccessTokenError::default();
                 error.set_type(AccessTokenErrorType::InvalidClient);
                 let mut json = ErrorDescription::new(error);
+                self.endpoint.inner.on_flow_error(
+                    FlowError::AccessDenied,
+                    &mut FlowErrorDetail::AccessToken(json.description()),
+                );
                 let mut response = self.endpoint.inner.response(
                     &mut request,
                     Template::new_unauthorized(None, Some(json.description())).into(),
@@ -182,6 +225,9 @@ where
                 response
                     .client_error()
                     .map_err(|err| self.endpoint.inner.web_error(err))?;
+                response
+                    .no_store()
+                    .map_err(|err| self.endpoint.inner.web_error(err))?;
                 response
                     .body_json(&json.to_json())
                     .map_err(|err| self.endpoint.inner.web_error(err))?;
@@ -203,6 +249,9 @@ where
             .endpoint
             .inner
             .response(&mut request, Template::new_ok().into())?;
+        response
+            .no_store()
+            .map_err(|err| self.endpoint.inner.web_error(err))?;
         response
             .body_json(&token.to_json())
             .map_err(|err| self.endpoint.inner.web_error(err))?;
@@ -214,18 +263,30 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
     endpoint: &mut E, request: &mut R, error: ClientCredentialsError,
 ) -> Result<R::Response, E::Error> {
     Ok(match error {
-        ClientCredentialsError::Ignore => return Err(endpoint.error(OAuthError::DenySilently)),
+        ClientCredentialsError::Ignore => {
+            endpoint.on_flow_error(FlowError::UnknownClient, &mut FlowErrorDetail::DeniedSilently);
+            return Err(endpoint.error(OAuthError::DenySilently));
+        }
         ClientCredentialsError::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response =
                 endpoint.response(request, Template::new_bad(Some(json.description())).into())?;
 
             response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         ClientCredentialsError::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response = endpoint.response(
                 request,
                 Template::new_unauthorized(None, Some(json.description())).into(),
@@ -234,6 +295,7 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
             response
                 .unauthorized(&scheme)
                 .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
@@ -241,7 +303,11 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
         }
         ClientCredentialsError::Primitive(_) => {
             // FIXME: give the context for restoration.
-            return Err(endpoint.error(OAuthError::PrimitiveError));
+            return primitive_error(
+                endpoint,
+                request,
+                "client credentials: registrar or issuer failed",
+            );
         }
     })
 }
@@ -268,11 +334,13 @@ impl<E: Endpoint<R>, R: WebRequest> ClientCredentialsEndpoint for WrappedToken<E
 }
 
 impl<R: WebRequest> WrappedRequest<R> {
-    pub fn new(request: &mut R, credentials: bool) -> Self {
-        Self::new_or_fail(request, credentials).unwrap_or_else(Self::from_err)
+    pub fn new(request: &mut R, credentials: bool, limit: Option<usize>) -> Self {
+        Self::new_or_fail(request, credentials, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &mut R, credentials: bool) -> Result<Self, FailParse<R::Error>> {
+    fn new_or_fail(
+        request: &mut R, credentials: bool, limit: Option<usize>,
+    ) -> Result<Self, FailParse<R::Error>> {
         // If there is a header, it must parse correctly.
         let authorization = match request.authheader() {
             Err(err) => return Err(FailParse::Err(err)),
@@ -280,12 +348,14 @@ impl<R: WebRequest> WrappedRequest<R> {
             Ok(None) => None,
         };
 
+        let body = request.urlbody().map_err(FailParse::Err)?;
+        if exceeds_parameter_limit(&*body, limit) {
+            return Err(FailParse::Invalid);
+        }
+
         Ok(WrappedRequest {
             request: PhantomData,
-            body: request
-                .urlbody()
-                .map(|body| body.into_owned())
-                .map_err(FailParse::Err)?,
+            body: body.into_owned(),
             authorization,
             error: None,
             allow_credentials_in_body: credentials,
@@ -0,0 +1,87 @@
+use std::marker::PhantomData;
+
+use chrono::Utc;
+use oxide_auth::{
+    code_grant::resource::TokenInfo,
+    endpoint::{OAuthError, Template, WebRequest, WebResponse},
+};
+
+use super::{Endpoint, primitive_error};
+use super::token_request::{authenticated, token_error};
+
+/// Answers token introspection requests of authenticated clients.
+///
+/// Reports the claims of an access token as described in [RFC 7662]. The client must authenticate
+/// like in an access token request. Tokens that are unknown, expired or not access tokens are
+/// reported as inactive, with no further claims. Only confidential clients, those authenticating
+/// with a passphrase, can introspect the tokens of other clients. Tokens of other clients are
+/// reported as inactive to public clients, as anyone can claim to be a public client.
+///
+/// [RFC 7662]: https://tools.ietf.org/html/rfc7662
+pub struct IntrospectionFlow<E, R>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    endpoint: E,
+    r_type: PhantomData<R>,
+}
+
+impl<E, R> IntrospectionFlow<E, R>
+where
+    E: Endpoint<R> + Send + Sync,
+    R: WebRequest + Send + Sync,
+    <R as WebRequest>::Error: Send + Sync,
+{
+    /// Wrap the endpoint if it supports handling introspection requests.
+    ///
+    /// The endpoint needs to provide (return `Some`):
+    ///
+    /// * a `Registrar` from `registrar`
+    /// * an `Issuer` from `issuer_mut`
+    pub fn prepare(mut endpoint: E) -> Result<Self, E::Error> {
+        if endpoint.registrar().is_none() {
+            return Err(endpoint.error(OAuthError::PrimitiveError));
+        }
+
+        if endpoint.issuer_mut().is_none() {
+            return Err(endpoint.error(OAuthError::PrimitiveError));
+        }
+
+        Ok(IntrospectionFlow {
+            endpoint,
+            r_type: PhantomData,
+        })
+    }
+
+    /// Answer an introspection request.
+    pub async fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        const DETAIL: &str = "introspection: registrar or issuer failed";
+
+        let token = match authenticated(&self.endpoint, &mut request).await {
+            Err(error) => return token_error(&mut self.endpoint, &mut request, error, DETAIL),
+            Ok(token) => token,
+        };
+
+        let recovered = match self.endpoint.issuer_mut() {
+            None => Err(()),
+            Some(issuer) => issuer.recover_token(&token.token).await,
+        };
+
+        let info = match recovered {
+            Err(()) => return primitive_error(&mut self.endpoint, &mut request, DETAIL),
+            Ok(Some(grant)) if !token.confidential && grant.client_id != token.client_id => {
+                TokenInfo::inactive()
+            }
+            Ok(Some(grant)) if grant.until >= Utc::now() => TokenInfo::active(&grant),
+            Ok(_) => TokenInfo::inactive(),
+        };
+
+        let mut response = self.endpoint.response(&mut request, Template::new_ok())?;
+        response.no_store().map_err(|err| self.endpoint.web_error(err))?;
+        response
+            .body_json(&info.to_json())
+            .map_err(|err| self.endpoint.web_error(err))?;
+        Ok(response)
+    }
+}
@@ -1,16 +1,31 @@
 use async_trait::async_trait;
-use oxide_auth::endpoint::{OAuthError, Template, WebRequest, OwnerConsent, Solicitation, Scopes};
+use oxide_auth::endpoint::{
+    ClientTrust, FlowError, FlowErrorDetail, OAuthError, Template, WebRequest, WebResponse,
+    OwnerConsent, ProofVerifier, Solicitation, Scopes,
+};
+use oxide_auth::endpoint;
+use oxide_auth::frontends::simple::endpoint as simple;
+use oxide_auth::primitives::clock::Clock;
+use oxide_auth::primitives::grant::Grant;
+use oxide_auth::primitives::scope::ScopeDescriptions;
+
+use std::time::Duration;
 
 pub use crate::code_grant::access_token::{Extension as AccessTokenExtension};
 pub use crate::code_grant::authorization::Extension as AuthorizationExtension;
 pub use crate::code_grant::client_credentials::{Extension as ClientCredentialsExtension};
-use crate::primitives::{Authorizer, Registrar, Issuer};
+pub use crate::code_grant::refresh::Extension as RefreshExtension;
+pub use crate::code_grant::resource::Extension as ResourceExtension;
+use crate::primitives::{Authorizer, FromSync, Registrar, Issuer};
 
 pub mod authorization;
 pub mod access_token;
 pub mod client_credentials;
+pub mod introspection;
 pub mod refresh;
 pub mod resource;
+pub mod revocation;
+mod token_request;
 
 pub trait Endpoint<Request>
 where
@@ -69,6 +84,104 @@ where
     fn extension(&mut self) -> Option<&mut (dyn Extension + Send)> {
         None
     }
+
+    /// Check the sessions of otherwise valid tokens in the resource flow.
+    ///
+    /// Returning `None` is the default implementation and accepts every valid token.
+    fn session_validator(&mut self) -> Option<&mut (dyn SessionValidator + Send)> {
+        None
+    }
+
+    /// Verify the proof of possession accompanying bound tokens in the resource flow.
+    ///
+    /// Returning `None` is the default implementation. Access with tokens that are bound to a key
+    /// is then always denied, while other tokens are unaffected.
+    fn proof_verifier(&mut self) -> Option<&mut (dyn ProofVerifier<Request> + Send)> {
+        None
+    }
+
+    /// Descriptions of scope tokens presented to the owner solicitor.
+    ///
+    /// Returning `None` is the default implementation and describes each token by itself.
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        None
+    }
+
+    /// Inspect the detailed cause of a failed flow.
+    ///
+    /// Called like in the synchronous flows, see `oxide_auth::endpoint::Endpoint::on_flow_error`.
+    /// The default implementation does nothing.
+    fn on_flow_error(&mut self, _error: FlowError, _detail: &mut FlowErrorDetail<'_>) {}
+
+    /// Answer failed primitives with a generic response instead of an error.
+    ///
+    /// When this returns `true`, a flow whose primitive failed produces a response with status 500
+    /// and the body `{"error":"server_error"}`. Otherwise, the flow returns the result of `error`
+    /// for `OAuthError::PrimitiveError`. Returning `false` is the default implementation.
+    fn opaque_errors(&self) -> bool {
+        false
+    }
+
+    /// Whether the failure of a primitive was only transient.
+    ///
+    /// Failures are then reported like in the synchronous flows, see
+    /// `oxide_auth::endpoint::Endpoint::temporarily_unavailable`. Returning `None` is the default
+    /// implementation and treats all failures as permanent.
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        None
+    }
+
+    /// The maximum number of query or body parameters accepted in a request.
+    ///
+    /// Requests with more parameters are rejected like in the synchronous flows, see
+    /// `oxide_auth::endpoint::Endpoint::parameter_limit`. Returning `None` is the default
+    /// implementation and accepts any number of parameters.
+    fn parameter_limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// The source of the current time for flows, such as the expiry of implicitly issued tokens.
+    ///
+    /// See `oxide_auth::endpoint::Endpoint::clock`. Returning `None` is the default
+    /// implementation and uses the system time.
+    fn clock(&self) -> Option<&(dyn Clock + Sync)> {
+        None
+    }
+}
+
+/// Handle the failure of a primitive during a flow.
+///
+/// Reports the failure to `on_flow_error` and then either fails the flow or, for endpoints with opaque
+/// errors, answers with a generic server error. Transient failures are reported and answered as
+/// such.
+fn primitive_error<E: Endpoint<R>, R: WebRequest>(
+    endpoint: &mut E, request: &mut R, detail: &str,
+) -> Result<R::Response, E::Error> {
+    let error = match endpoint.temporarily_unavailable() {
+        Some(retry_after) => OAuthError::TemporarilyUnavailable(retry_after),
+        None => OAuthError::PrimitiveError,
+    };
+
+    endpoint.on_flow_error(FlowError::Primitive, &mut FlowErrorDetail::Primitive(detail));
+    if !endpoint.opaque_errors() {
+        return Err(endpoint.error(error));
+    }
+
+    let mut response = endpoint.response(request, Template::new_server_error())?;
+    let body = match error {
+        OAuthError::TemporarilyUnavailable(retry_after) => {
+            response
+                .service_unavailable(Some(retry_after))
+                .map_err(|err| endpoint.web_error(err))?;
+            r#"{"error":"temporarily_unavailable"}"#
+        }
+        _ => {
+            response.server_error().map_err(|err| endpoint.web_error(err))?;
+            r#"{"error":"server_error"}"#
+        }
+    };
+    response.body_json(body).map_err(|err| endpoint.web_error(err))?;
+    Ok(response)
 }
 
 pub trait Extension {
@@ -86,13 +199,26 @@ pub trait Extension {
     fn client_credentials(&mut self) -> Option<&mut (dyn ClientCredentialsExtension + Send)> {
         None
     }
+
+    /// The handler for refresh extensions.
+    fn refresh(&mut self) -> Option<&mut (dyn RefreshExtension + Send)> {
+        None
+    }
+
+    /// The handler for resource extensions.
+    fn resource(&mut self) -> Option<&mut (dyn ResourceExtension + Send)> {
+        None
+    }
 }
 
 /// Checks consent with the owner of a resource, identified in a request.
 ///
-/// See [`frontends::simple`] for an implementation that permits arbitrary functions.
+/// See [`frontends::simple`] for an implementation that permits functions returning futures. The
+/// simple solicitors of `oxide-auth`, its `FnSolicitor`, `TieredSolicitor` and `Vacant`, can be
+/// used as well. Any other synchronous solicitor is wrapped in [`FromSync`].
 ///
 /// [`frontends::simple`]: ../frontends/simple/endpoint/struct.FnSolicitor.html
+/// [`FromSync`]: ../primitives/struct.FromSync.html
 #[async_trait]
 pub trait OwnerSolicitor<Request: WebRequest> {
     /// Ensure that a user (resource owner) is currently authenticated (for example via a session
@@ -103,14 +229,78 @@ pub trait OwnerSolicitor<Request: WebRequest> {
 }
 
 #[async_trait]
-impl<T, Request: WebRequest> OwnerSolicitor<Request> for T
+impl<F, Request> OwnerSolicitor<Request> for simple::FnSolicitor<F>
+where
+    F: FnMut(&mut Request, Solicitation) -> OwnerConsent<Request::Response> + Send,
+    Request: WebRequest + Send,
+{
+    async fn check_consent(
+        &mut self, req: &mut Request, solicitation: Solicitation<'_>,
+    ) -> OwnerConsent<Request::Response> {
+        (self.0)(req, solicitation)
+    }
+}
+
+#[async_trait]
+impl<F, T, Request> OwnerSolicitor<Request> for simple::TieredSolicitor<F, T>
+where
+    F: OwnerSolicitor<Request> + Send,
+    T: OwnerSolicitor<Request> + Send,
+    Request: WebRequest + Send,
+{
+    async fn check_consent(
+        &mut self, req: &mut Request, solicitation: Solicitation<'_>,
+    ) -> OwnerConsent<Request::Response> {
+        match solicitation.client_trust() {
+            ClientTrust::FirstParty => self.first_party.check_consent(req, solicitation).await,
+            ClientTrust::ThirdParty => self.third_party.check_consent(req, solicitation).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<S, Request> OwnerSolicitor<Request> for FromSync<S>
 where
-    T: oxide_auth::endpoint::OwnerSolicitor<Request> + ?Sized + Send,
-    Request: Send,
+    S: endpoint::OwnerSolicitor<Request> + Send,
+    Request: WebRequest + Send,
 {
     async fn check_consent(
         &mut self, req: &mut Request, solicitation: Solicitation<'_>,
     ) -> OwnerConsent<Request::Response> {
-        oxide_auth::endpoint::OwnerSolicitor::check_consent(self, req, solicitation)
+        endpoint::OwnerSolicitor::check_consent(&mut self.0, req, solicitation)
+    }
+}
+
+#[async_trait]
+impl<Request> OwnerSolicitor<Request> for simple::Vacant
+where
+    Request: WebRequest + Send,
+{
+    async fn check_consent(
+        &mut self, _: &mut Request, _: Solicitation<'_>,
+    ) -> OwnerConsent<Request::Response> {
+        OwnerConsent::Denied
+    }
+}
+
+/// Checks that the session in which a token was issued is still active.
+///
+/// The asynchronous counterpart of the [`SessionValidator`] of `oxide-auth`, which it is
+/// implemented for, so that the session store can be queried without blocking.
+///
+/// [`SessionValidator`]: ../../oxide_auth/endpoint/trait.SessionValidator.html
+#[async_trait]
+pub trait SessionValidator {
+    /// Determine whether the session of a grant has not been revoked.
+    async fn session_active(&mut self, grant: &Grant) -> Result<bool, ()>;
+}
+
+#[async_trait]
+impl<T> SessionValidator for T
+where
+    T: oxide_auth::endpoint::SessionValidator + ?Sized + Send,
+{
+    async fn session_active(&mut self, grant: &Grant) -> Result<bool, ()> {
+        oxide_auth::endpoint::SessionValidator::session_active(self, grant)
     }
 }
@@ -2,13 +2,16 @@ use std::{borrow::Cow, marker::PhantomData, str::from_utf8};
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 use oxide_auth::{
-    code_grant::refresh::{Error, Request},
-    endpoint::{WebRequest, WebResponse, OAuthError, QueryParameter, Template, NormalizedParameter},
+    code_grant::refresh::{BearerToken, Error, Request},
+    endpoint::{
+        WebRequest, WebResponse, OAuthError, QueryParameter, Template, NormalizedParameter,
+        exceeds_parameter_limit,
+    },
 };
 
-use super::Endpoint;
+use super::{Endpoint, FlowErrorDetail, primitive_error};
 use crate::{
-    code_grant::refresh::{refresh, Endpoint as RefreshEndpoint},
+    code_grant::refresh::{refresh, Endpoint as RefreshEndpoint, Extension},
     primitives::{Issuer, Registrar},
 };
 
@@ -19,6 +22,7 @@ where
     R: WebRequest,
 {
     endpoint: WrappedRefresh<E, R>,
+    report_refresh_expiry: bool,
 }
 
 struct WrappedRefresh<E, R>
@@ -76,11 +80,21 @@ where
                 inner: endpoint,
                 r_type: PhantomData,
             },
+            report_refresh_expiry: false,
         })
     }
 
+    /// Report the remaining lifetime of the new refresh token in the response.
+    ///
+    /// See `AccessTokenFlow::report_refresh_expiry`, the non-standard `refresh_token_expires_in`
+    /// parameter is only sent if the issuer limits the lifetime of its refresh tokens.
+    pub fn report_refresh_expiry(&mut self, report: bool) {
+        self.report_refresh_expiry = report;
+    }
+
     pub async fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
-        let refreshed = refresh(&mut self.endpoint, &WrappedRequest::new(&mut request)).await;
+        let limit = self.endpoint.inner.parameter_limit();
+        let refreshed = refresh(&mut self.endpoint, &WrappedRequest::new(&mut request, limit)).await;
 
         let token = match refreshed {
             Err(error) => return token_error(&mut self.endpoint.inner, &mut request, error),
@@ -89,12 +103,23 @@ where
 
         let mut response = self.endpoint.inner.response(&mut request, Template::new_ok())?;
         response
-            .body_json(&token.to_json())
+            .no_store()
+            .map_err(|err| self.endpoint.inner.web_error(err))?;
+        response
+            .body_json(&token_json(&token, self.report_refresh_expiry))
             .map_err(|err| self.endpoint.inner.web_error(err))?;
         Ok(response)
     }
 }
 
+fn token_json(token: &BearerToken, report_refresh_expiry: bool) -> String {
+    if report_refresh_expiry {
+        token.to_json_with_refresh_expiry()
+    } else {
+        token.to_json()
+    }
+}
+
 fn token_error<E, R>(endpoint: &mut E, request: &mut R, error: Error) -> Result<R::Response, E::Error>
 where
     E: Endpoint<R>,
@@ -102,15 +127,24 @@ where
 {
     Ok(match error {
         Error::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.description().kind().into(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response =
                 endpoint.response(request, Template::new_bad(Some(json.description())))?;
             response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         Error::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.description().kind().into(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response = endpoint.response(
                 request,
                 Template::new_unauthorized(None, Some(json.description())),
@@ -118,6 +152,7 @@ where
             response
                 .unauthorized(&scheme)
                 .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
@@ -125,17 +160,17 @@ where
         }
         Error::Primitive => {
             // FIXME: give the context for restoration.
-            return Err(endpoint.error(OAuthError::PrimitiveError));
+            return primitive_error(endpoint, request, "refresh: registrar or issuer failed");
         }
     })
 }
 
 impl<'a, R: WebRequest> WrappedRequest<R> {
-    pub fn new(request: &'a mut R) -> Self {
-        Self::new_or_fail(request).unwrap_or_else(Self::from_err)
+    pub fn new(request: &'a mut R, limit: Option<usize>) -> Self {
+        Self::new_or_fail(request, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &'a mut R) -> Result<Self, Option<R::Error>> {
+    fn new_or_fail(request: &'a mut R, limit: Option<usize>) -> Result<Self, Option<R::Error>> {
         // If there is a header, it must parse correctly.
         let authorization = match request.authheader() {
             Err(err) => return Err(Some(err)),
@@ -143,8 +178,13 @@ impl<'a, R: WebRequest> WrappedRequest<R> {
             Ok(None) => None,
         };
 
+        let body = request.urlbody()?;
+        if exceeds_parameter_limit(&*body, limit) {
+            return Err(None);
+        }
+
         Ok(WrappedRequest {
-            body: request.urlbody()?.into_owned(),
+            body: body.into_owned(),
             authorization,
             error: None,
         })
@@ -203,6 +243,10 @@ where
     fn issuer(&mut self) -> &mut (dyn Issuer + Send) {
         self.inner.issuer_mut().unwrap()
     }
+
+    fn extension(&mut self) -> Option<&mut (dyn Extension + Send)> {
+        self.inner.extension().and_then(super::Extension::refresh)
+    }
 }
 
 impl<R: WebRequest> Request for WrappedRequest<R> {
@@ -1,16 +1,26 @@
 use std::{marker::PhantomData, borrow::Cow};
 
-use oxide_auth::code_grant::resource::{Error as ResourceError, Request as ResourceRequest};
+use oxide_auth::code_grant::resource::{
+    split_scheme, AccessFailure, Authenticate, Error as ResourceError, ErrorCode,
+    Request as ResourceRequest,
+};
 use oxide_auth::{
     endpoint::{Scope, WebResponse},
-    primitives::grant::Grant,
+    primitives::{binding::Binding, grant::Grant},
 };
 
-use crate::code_grant::resource::{protect, Endpoint as ResourceEndpoint};
+use crate::code_grant::resource::{protect, Endpoint as ResourceEndpoint, Extension as ResourceExtension};
 
 use super::*;
 
 /// Guards resources by requiring OAuth authorization.
+///
+/// The session of each valid token is additionally checked with the [`SessionValidator`] of the
+/// endpoint, if it has one. Tokens bound to a key of the client must be presented with the scheme
+/// of their type and are only accepted when the `ProofVerifier` of the endpoint approves the
+/// proof of possession.
+///
+/// [`SessionValidator`]: ../trait.SessionValidator.html
 pub struct ResourceFlow<E, R>
 where
     E: Endpoint<R>,
@@ -79,9 +89,8 @@ where
     /// When the issuer returned by the endpoint is suddenly `None` when previously it
     /// was `Some(_)`.
     pub async fn execute(&mut self, mut request: R) -> Result<Grant, Result<R::Response, E::Error>> {
+        let wrapped = WrappedRequest::new(&mut request);
         let protected = {
-            let wrapped = WrappedRequest::new(&mut request);
-
             let mut scoped = Scoped {
                 request: &mut request,
                 endpoint: &mut self.endpoint.0,
@@ -90,28 +99,104 @@ where
             protect(&mut scoped, &wrapped).await
         };
 
-        protected.map_err(|err| self.denied(&mut request, err))
+        let protected = match protected {
+            Err(mut error) if error.insufficient_scope() => {
+                let scopes = self.endpoint.0.scopes().unwrap();
+                if let Some(reason) = scopes.deny_reason(&mut request) {
+                    error.describe(reason);
+                }
+                Err(error)
+            }
+            protected => protected,
+        };
+
+        let validated = match protected {
+            Ok(grant) => self.validate_session(grant).await,
+            Err(err) => Err(err),
+        };
+
+        let verified = match validated {
+            Ok(grant) => self.verify_proof(&mut request, &wrapped, grant),
+            Err(err) => Err(err),
+        };
+
+        verified.map_err(|err| self.denied(&mut request, err))
+    }
+
+    async fn validate_session(&mut self, grant: Grant) -> Result<Grant, ResourceError> {
+        let validator = match self.endpoint.0.session_validator() {
+            Some(validator) => validator,
+            None => return Ok(grant),
+        };
+
+        match validator.session_active(&grant).await {
+            Ok(true) => Ok(grant),
+            Ok(false) => Err(invalid_token()),
+            Err(()) => Err(ResourceError::PrimitiveError),
+        }
+    }
+
+    fn verify_proof(
+        &mut self, request: &mut R, wrapped: &WrappedRequest<R>, grant: Grant,
+    ) -> Result<Grant, ResourceError> {
+        let binding = match Binding::of(&grant) {
+            Some(binding) => binding,
+            None => return Ok(grant),
+        };
+
+        // A successfully validated authorization always has a known scheme.
+        let (_, token) = split_scheme(wrapped.authorization.as_deref().unwrap()).unwrap();
+        let verified = match self.endpoint.0.proof_verifier() {
+            Some(verifier) => verifier.verify(request, token, &binding),
+            None => Ok(false),
+        };
+
+        match verified {
+            Ok(true) => Ok(grant),
+            Ok(false) => Err(invalid_token()),
+            Err(()) => Err(ResourceError::PrimitiveError),
+        }
     }
 
     fn denied(&mut self, request: &mut R, error: ResourceError) -> Result<R::Response, E::Error> {
         let template = match &error {
-            ResourceError::AccessDenied { .. } => Template::new_unauthorized(None, None),
-            ResourceError::NoAuthentication { .. } => Template::new_unauthorized(None, None),
+            ResourceError::AccessDenied { .. } | ResourceError::NoAuthentication { .. } => {
+                Template::new_unauthorized(Some(error.clone()), None)
+            }
             ResourceError::InvalidRequest { .. } => Template::new_bad(None),
             ResourceError::PrimitiveError => {
-                return Err(self.endpoint.0.error(OAuthError::PrimitiveError))
+                return primitive_error(&mut self.endpoint.0, request, "resource: issuer failed")
             }
         };
 
         let mut response = self.endpoint.0.response(request, template)?;
-        response
-            .unauthorized(&error.www_authenticate())
-            .map_err(|err| self.endpoint.0.web_error(err))?;
+        // A valid token lacking the scope is forbidden, any other denial is unauthorized.
+        let forbidden = error.insufficient_scope();
+        let header = error.www_authenticate();
+        if forbidden {
+            response.forbidden(&header)
+        } else {
+            response.unauthorized(&header)
+        }
+        .map_err(|err| self.endpoint.0.web_error(err))?;
 
         Ok(response)
     }
 }
 
+fn invalid_token() -> ResourceError {
+    ResourceError::AccessDenied {
+        failure: AccessFailure {
+            code: Some(ErrorCode::InvalidToken),
+        },
+        authenticate: Authenticate {
+            realm: None,
+            scope: None,
+            description: None,
+        },
+    }
+}
+
 impl<R: WebRequest> WrappedRequest<R> {
     fn new(request: &mut R) -> Self {
         let token = match request.authheader() {
@@ -149,6 +234,10 @@ where
     fn issuer(&mut self) -> &mut (dyn Issuer + Send) {
         self.endpoint.issuer_mut().unwrap()
     }
+
+    fn extension(&mut self) -> Option<&mut (dyn ResourceExtension + Send)> {
+        self.endpoint.extension().and_then(Extension::resource)
+    }
 }
 
 impl<R: WebRequest> ResourceRequest for WrappedRequest<R> {
@@ -0,0 +1,84 @@
+use std::marker::PhantomData;
+
+use oxide_auth::{
+    code_grant::accesstoken::Error,
+    endpoint::{OAuthError, Template, WebRequest, WebResponse},
+};
+
+use super::{Endpoint, primitive_error};
+use super::token_request::{authenticated, recover, token_error};
+
+/// Revokes access and refresh tokens on request of their client.
+///
+/// Implements the revocation endpoint of [RFC 7009]. The client must authenticate like in an
+/// access token request and can only revoke its own tokens. Requests for unknown tokens succeed,
+/// as the token is invalid either way. The `Issuer` must support `revoke_token`.
+///
+/// [RFC 7009]: https://tools.ietf.org/html/rfc7009
+pub struct RevocationFlow<E, R>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    endpoint: E,
+    r_type: PhantomData<R>,
+}
+
+impl<E, R> RevocationFlow<E, R>
+where
+    E: Endpoint<R> + Send + Sync,
+    R: WebRequest + Send + Sync,
+    <R as WebRequest>::Error: Send + Sync,
+{
+    /// Wrap the endpoint if it supports handling revocation requests.
+    ///
+    /// The endpoint needs to provide (return `Some`):
+    ///
+    /// * a `Registrar` from `registrar`
+    /// * an `Issuer` from `issuer_mut`
+    pub fn prepare(mut endpoint: E) -> Result<Self, E::Error> {
+        if endpoint.registrar().is_none() {
+            return Err(endpoint.error(OAuthError::PrimitiveError));
+        }
+
+        if endpoint.issuer_mut().is_none() {
+            return Err(endpoint.error(OAuthError::PrimitiveError));
+        }
+
+        Ok(RevocationFlow {
+            endpoint,
+            r_type: PhantomData,
+        })
+    }
+
+    /// Answer a revocation request.
+    pub async fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        const DETAIL: &str = "revocation: registrar or issuer failed";
+
+        let token = match authenticated(&self.endpoint, &mut request).await {
+            Err(error) => return token_error(&mut self.endpoint, &mut request, error, DETAIL),
+            Ok(token) => token,
+        };
+
+        let revoked = match recover(&mut self.endpoint, &token).await {
+            Err(()) => Err(()),
+            Ok(None) => Ok(()),
+            Ok(Some(grant)) if grant.client_id != token.client_id => {
+                let error = Error::unauthorized_client();
+                return token_error(&mut self.endpoint, &mut request, error, DETAIL);
+            }
+            Ok(Some(_)) => match self.endpoint.issuer_mut() {
+                None => Err(()),
+                Some(issuer) => issuer.revoke_token(&token.token).await,
+            },
+        };
+
+        if revoked.is_err() {
+            return primitive_error(&mut self.endpoint, &mut request, DETAIL);
+        }
+
+        let mut response = self.endpoint.response(&mut request, Template::new_ok())?;
+        response.no_store().map_err(|err| self.endpoint.web_error(err))?;
+        Ok(response)
+    }
+}
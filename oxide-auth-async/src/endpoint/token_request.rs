@@ -0,0 +1,162 @@
+//! Parsing and error responses shared by the revocation and introspection flows.
+use std::{borrow::Cow, str::from_utf8};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use oxide_auth::{
+    code_grant::accesstoken::{Error, PrimitiveError},
+    endpoint::{
+        NormalizedParameter, QueryParameter, Template, WebRequest, WebResponse, exceeds_parameter_limit,
+    },
+    primitives::{grant::Grant, registrar::RegistrarError},
+};
+
+use super::{Endpoint, FlowErrorDetail, primitive_error};
+
+/// A token named by an authenticated client.
+pub(super) struct TokenRequest {
+    /// The authenticated client.
+    pub client_id: String,
+
+    /// The token in question.
+    pub token: String,
+
+    /// The `token_type_hint` of the request, if any.
+    pub hint: Option<String>,
+
+    /// Whether the client authenticated with a passphrase.
+    pub confidential: bool,
+}
+
+/// Parse the request and authenticate its client.
+///
+/// Confidential clients authenticate with HTTP Basic authorization while public clients name
+/// themselves with a `client_id` in the body, like in the access token request.
+pub(super) async fn authenticated<E, R>(endpoint: &E, request: &mut R) -> Result<TokenRequest, Error>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    let authorization = match request.authheader() {
+        Err(_) => return Err(Error::invalid()),
+        Ok(None) => None,
+        Ok(Some(header)) => Some(parse_header(header).ok_or_else(Error::invalid)?),
+    };
+
+    let body: NormalizedParameter = match request.urlbody() {
+        Err(_) => return Err(Error::invalid()),
+        Ok(body) if exceeds_parameter_limit(&*body, endpoint.parameter_limit()) => {
+            return Err(Error::invalid())
+        }
+        Ok(body) => body.into_owned(),
+    };
+
+    let token = body
+        .unique_value("token")
+        .ok_or_else(Error::invalid)?
+        .into_owned();
+    let hint = body.unique_value("token_type_hint").map(Cow::into_owned);
+
+    let (client_id, passphrase) = match authorization {
+        Some(authorization) => authorization,
+        None => match body.unique_value("client_id") {
+            Some(client_id) => (client_id.into_owned(), None),
+            None => return Err(Error::unauthorized("basic")),
+        },
+    };
+
+    let confidential = passphrase.is_some();
+    let registrar = endpoint.registrar().ok_or_else(primitive)?;
+    match registrar.check(&client_id, passphrase.as_deref()).await {
+        Ok(()) => (),
+        Err(RegistrarError::PrimitiveError) => return Err(primitive()),
+        Err(RegistrarError::Unspecified) => return Err(Error::unauthorized("basic")),
+    }
+
+    Ok(TokenRequest {
+        client_id,
+        token,
+        hint,
+        confidential,
+    })
+}
+
+/// Find the grant of an access or refresh token, trying the hinted kind of token first.
+pub(super) async fn recover<E, R>(endpoint: &mut E, request: &TokenRequest) -> Result<Option<Grant>, ()>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    let issuer = endpoint.issuer_mut().ok_or(())?;
+    if request.hint.as_deref() == Some("refresh_token") {
+        match issuer.recover_refresh(&request.token).await? {
+            Some(grant) => Ok(Some(grant)),
+            None => issuer.recover_token(&request.token).await,
+        }
+    } else {
+        match issuer.recover_token(&request.token).await? {
+            Some(grant) => Ok(Some(grant)),
+            None => issuer.recover_refresh(&request.token).await,
+        }
+    }
+}
+
+/// Answer a request that failed with an error of the access token endpoint.
+pub(super) fn token_error<E, R>(
+    endpoint: &mut E, request: &mut R, error: Error, detail: &str,
+) -> Result<R::Response, E::Error>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    Ok(match error {
+        Error::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
+            let mut response =
+                endpoint.response(request, Template::new_bad(Some(json.description())))?;
+            response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
+            response
+                .body_json(&json.to_json())
+                .map_err(|err| endpoint.web_error(err))?;
+            response
+        }
+        Error::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
+            let mut response = endpoint.response(
+                request,
+                Template::new_unauthorized(None, Some(json.description())),
+            )?;
+            response
+                .unauthorized(&scheme)
+                .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
+            response
+                .body_json(&json.to_json())
+                .map_err(|err| endpoint.web_error(err))?;
+            response
+        }
+        Error::Primitive(_) => return primitive_error(endpoint, request, detail),
+    })
+}
+
+fn primitive() -> Error {
+    Error::Primitive(Box::new(PrimitiveError::empty()))
+}
+
+fn parse_header(header: Cow<str>) -> Option<(String, Option<Vec<u8>>)> {
+    let encoded = header.strip_prefix("Basic ")?;
+    let combined = STANDARD.decode(encoded).ok()?;
+    let mut split = combined.splitn(2, |&c| c == b':');
+    let client = from_utf8(split.next()?).ok()?;
+    let passphrase = match split.next()? {
+        [] => None,
+        passphrase => Some(passphrase.to_vec()),
+    };
+    Some((client.to_string(), passphrase))
+}
@@ -0,0 +1,84 @@
+//! Sharing an endpoint between the handlers of an async web framework.
+use std::sync::{Arc, Mutex};
+
+use oxide_auth::endpoint::OAuthError;
+
+/// An endpoint shared between handlers that keeps their futures `Send`
+///
+/// Locking a `std::sync::Mutex` in a handler and keeping the guard alive across an `.await` makes
+/// the future of the handler non-`Send`, and multi-threaded executors then refuse to spawn it. This
+/// wrapper only hands out the endpoint within a synchronous closure, so the lock is always released
+/// before the handler awaits anything else. The synchronous flows of `oxide-auth` never block on io
+/// themselves, they only call into the primitives of the endpoint, so they can run in the closure.
+///
+/// The wrapper is cheap to clone and meant to be used as the state of the router. Frontends may
+/// wrap it to offer the flows directly, as `oxide-auth-axum` does.
+///
+/// ```
+/// use oxide_auth::endpoint::ResourceFlow;
+/// use oxide_auth::frontends::simple::endpoint::{Generic, Vacant};
+/// use oxide_auth::frontends::simple::request::Request;
+/// use oxide_auth::primitives::prelude::*;
+/// use oxide_auth_async::frontends::shared::SharedEndpoint;
+///
+/// type Endpoint = Generic<Vacant, Vacant, TokenMap<RandomGenerator>, Vacant, Vec<Scope>>;
+///
+/// async fn load_profile(owner: &str) -> String {
+///     format!("profile of {}", owner)
+/// }
+///
+/// async fn profile(endpoint: SharedEndpoint<Endpoint>, request: Request) -> Option<String> {
+///     // The lock is released again when the flow returns.
+///     let grant = endpoint
+///         .with(|endpoint| ResourceFlow::prepare(endpoint).ok()?.execute(request).ok())
+///         .ok()??;
+///     Some(load_profile(&grant.owner_id).await)
+/// }
+///
+/// fn assert_send<F: Send>(_: F) {}
+///
+/// let endpoint = SharedEndpoint::new(Generic {
+///     registrar: Vacant,
+///     authorizer: Vacant,
+///     issuer: TokenMap::new(RandomGenerator::new(16)),
+///     solicitor: Vacant,
+///     scopes: vec!["profile".parse().unwrap()],
+///     response: Vacant,
+/// });
+/// assert_send(profile(endpoint.clone(), Request::default()));
+/// ```
+pub struct SharedEndpoint<E> {
+    endpoint: Arc<Mutex<E>>,
+}
+
+impl<E> SharedEndpoint<E> {
+    /// Share an endpoint between handlers
+    pub fn new(endpoint: E) -> Self {
+        SharedEndpoint {
+            endpoint: Arc::new(Mutex::new(endpoint)),
+        }
+    }
+
+    /// Run a closure with exclusive access to the endpoint
+    ///
+    /// The lock is only held while the closure runs. Fails with `OAuthError::PrimitiveError` if
+    /// another user of the endpoint panicked while holding the lock.
+    pub fn with<R>(&self, f: impl FnOnce(&mut E) -> R) -> Result<R, OAuthError> {
+        let mut endpoint = self.endpoint.lock().map_err(|_| OAuthError::PrimitiveError)?;
+        Ok(f(&mut endpoint))
+    }
+}
+
+impl<E> Clone for SharedEndpoint<E> {
+    fn clone(&self) -> Self {
+        SharedEndpoint {
+            endpoint: self.endpoint.clone(),
+        }
+    }
+}
+
+impl<E> From<Arc<Mutex<E>>> for SharedEndpoint<E> {
+    fn from(endpoint: Arc<Mutex<E>>) -> Self {
+        SharedEndpoint { endpoint }
+    }
+}
@@ -0,0 +1,61 @@
+//! Simple implementations of the endpoint primitives for asynchronous flows.
+use std::future::Future;
+
+use async_trait::async_trait;
+use oxide_auth::endpoint::{OwnerConsent, Solicitation, WebRequest};
+
+use crate::endpoint::OwnerSolicitor;
+
+/// A wrapper for functions returning futures to be used as solicitors.
+///
+/// The function is called with the request and the solicitation and its future decides on the
+/// consent of the owner, so it can await a session store or a database. Since the future may not
+/// borrow the arguments of the function, read what is needed from them first and move it into an
+/// `async move` block. Functions that do not need to await anything can instead use the
+/// `FnSolicitor` of `oxide-auth`, which is also an asynchronous solicitor.
+///
+/// ## Example
+///
+/// ```
+/// # use oxide_auth::endpoint::{OwnerConsent, Solicitation, WebRequest};
+/// use oxide_auth_async::endpoint::OwnerSolicitor;
+/// use oxide_auth_async::frontends::simple::endpoint::FnSolicitor;
+///
+/// # #[derive(Clone)] struct Sessions;
+/// # impl Sessions {
+/// #     async fn owner(&self, _: &str) -> Option<String> { Some("owner".into()) }
+/// # }
+/// fn solicitor<R: WebRequest + Send>(sessions: Sessions) -> impl OwnerSolicitor<R> {
+///     FnSolicitor(move |request: &mut R, _: Solicitation| {
+///         let session = request.cookie("session").ok().flatten().map(|id| id.into_owned());
+///         let sessions = sessions.clone();
+///         async move {
+///             // For example a lookup in a database shared by the login pages.
+///             let owner = match session {
+///                 Some(session) => sessions.owner(&session).await,
+///                 None => None,
+///             };
+///
+///             match owner {
+///                 Some(owner) => OwnerConsent::Authorized(owner),
+///                 None => OwnerConsent::Denied,
+///             }
+///         }
+///     })
+/// }
+/// ```
+pub struct FnSolicitor<F>(pub F);
+
+#[async_trait]
+impl<W, F, Fut> OwnerSolicitor<W> for FnSolicitor<F>
+where
+    W: WebRequest + Send,
+    F: FnMut(&mut W, Solicitation) -> Fut + Send,
+    Fut: Future<Output = OwnerConsent<W::Response>> + Send,
+{
+    async fn check_consent(
+        &mut self, request: &mut W, solicitation: Solicitation<'_>,
+    ) -> OwnerConsent<W::Response> {
+        (self.0)(request, solicitation).await
+    }
+}
@@ -1,13 +1,16 @@
 use oxide_auth::{
     frontends::simple::extensions::Extended,
-    endpoint::{WebRequest, Scopes, Template, OAuthError},
+    endpoint::{WebRequest, FlowError, FlowErrorDetail, ProofVerifier, Scopes, Template, OAuthError},
+    primitives::{clock::Clock, scope::ScopeDescriptions},
 };
 
 use crate::{
-    endpoint::{Endpoint, Extension, OwnerSolicitor},
+    endpoint::{Endpoint, Extension, OwnerSolicitor, SessionValidator},
     primitives::{Registrar, Authorizer, Issuer},
 };
 
+use std::time::Duration;
+
 impl<Request, Inner, Ext> Endpoint<Request> for Extended<Inner, Ext>
 where
     Request: WebRequest,
@@ -53,4 +56,36 @@ where
     fn extension(&mut self) -> Option<&mut (dyn Extension + Send)> {
         Some(&mut self.addons)
     }
+
+    fn session_validator(&mut self) -> Option<&mut (dyn SessionValidator + Send)> {
+        self.inner.session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut (dyn ProofVerifier<Request> + Send)> {
+        self.inner.proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.inner.scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.inner.on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.inner.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        self.inner.temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.inner.parameter_limit()
+    }
+
+    fn clock(&self) -> Option<&(dyn Clock + Sync)> {
+        self.inner.clock()
+    }
 }
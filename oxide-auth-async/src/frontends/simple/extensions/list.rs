@@ -2,6 +2,8 @@ use async_trait::async_trait;
 use oxide_auth::code_grant::authorization::Request;
 use oxide_auth::code_grant::accesstoken::Request as TokenRequest;
 use oxide_auth::code_grant::client_credentials::Request as ClientCredentialsRequest;
+use oxide_auth::code_grant::refresh::Request as RefreshRequest;
+use oxide_auth::code_grant::resource::Request as ResourceRequest;
 use oxide_auth::frontends::simple::extensions::{AddonList, AddonResult};
 use oxide_auth::primitives::grant::Extensions;
 
@@ -9,6 +11,8 @@ use crate::endpoint::Extension;
 use crate::code_grant::access_token::{Extension as AccessTokenExtension};
 use crate::code_grant::authorization::Extension as AuthorizationExtension;
 use crate::code_grant::client_credentials::{Extension as ClientCredentialsExtension};
+use crate::code_grant::refresh::Extension as RefreshExtension;
+use crate::code_grant::resource::Extension as ResourceExtension;
 
 impl Extension for AddonList {
     fn authorization(&mut self) -> Option<&mut (dyn AuthorizationExtension + Send)> {
@@ -22,6 +26,14 @@ impl Extension for AddonList {
     fn client_credentials(&mut self) -> Option<&mut (dyn ClientCredentialsExtension + Send)> {
         Some(self)
     }
+
+    fn refresh(&mut self) -> Option<&mut (dyn RefreshExtension + Send)> {
+        Some(self)
+    }
+
+    fn resource(&mut self) -> Option<&mut (dyn ResourceExtension + Send)> {
+        Some(self)
+    }
 }
 
 #[async_trait]
@@ -85,3 +97,46 @@ impl ClientCredentialsExtension for AddonList {
         Ok(result_data)
     }
 }
+
+#[async_trait]
+impl RefreshExtension for AddonList {
+    async fn extend(
+        &mut self, request: &(dyn RefreshRequest + Sync), mut data: Extensions,
+    ) -> std::result::Result<Extensions, ()> {
+        for ext in self.refresh.iter() {
+            let ext_data = data.remove(ext);
+            let result = ext.execute(request, ext_data.clone());
+
+            match result {
+                // Keep the previous data, as well as that of extensions without an addon.
+                AddonResult::Ok => {
+                    if let Some(ext_data) = ext_data {
+                        data.set(ext, ext_data)
+                    }
+                }
+                AddonResult::Data(new_data) => data.set(ext, new_data),
+                AddonResult::Err => return Err(()),
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+#[async_trait]
+impl ResourceExtension for AddonList {
+    async fn check(
+        &mut self, request: &(dyn ResourceRequest + Sync), data: &Extensions,
+    ) -> std::result::Result<(), ()> {
+        for ext in self.resource.iter() {
+            let ext_data = data.get(ext.identifier()).cloned();
+
+            match ext.execute(request, ext_data) {
+                AddonResult::Ok | AddonResult::Data(_) => (),
+                AddonResult::Err => return Err(()),
+            }
+        }
+
+        Ok(())
+    }
+}
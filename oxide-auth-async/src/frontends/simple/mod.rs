@@ -1 +1,2 @@
+pub mod endpoint;
 pub mod extensions;
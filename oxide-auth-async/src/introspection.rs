@@ -0,0 +1,178 @@
+//! Recovers tokens from a remote authorization server through token introspection.
+//!
+//! A resource server which does not issue tokens itself can not recover their grants from its own
+//! storage or verify them with its own keys. Instead it asks the authorization server about each
+//! presented token, as specified by [RFC 7662]. The [`Introspection`] issuer does so and can be
+//! used in the resource flow like any other issuer.
+//!
+//! [RFC 7662]: https://tools.ietf.org/html/rfc7662
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{Duration, TimeZone, Utc};
+use oxide_auth::code_grant::resource::TokenInfo;
+use oxide_auth::primitives::grant::{Extensions, Grant};
+use oxide_auth::primitives::issuer::{IssuedToken, RefreshedToken};
+use url::{form_urlencoded, Url};
+
+use crate::primitives::Issuer;
+
+/// Sends requests to the introspection endpoint of the authorization server.
+///
+/// This is the http client of the resource server, for example a `reqwest::Client`:
+///
+/// ```
+/// # use async_trait::async_trait;
+/// # use url::Url;
+/// use oxide_auth_async::introspection::IntrospectionClient;
+///
+/// # struct Client;
+/// # impl Client {
+/// #     async fn post(&self, _: &Url, _: &str, _: &str) -> Result<String, ()> { Err(()) }
+/// # }
+/// struct Remote {
+///     /// Shared connection pool, for example a `reqwest::Client`.
+///     client: Client,
+/// }
+///
+/// #[async_trait]
+/// impl IntrospectionClient for Remote {
+///     async fn post(&mut self, url: &Url, authorization: &str, body: &str) -> Result<String, ()> {
+///         // With reqwest, roughly `client.post(url).header(AUTHORIZATION, authorization)
+///         //     .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+///         //     .body(body).send().await?.text().await`.
+///         self.client.post(url, authorization, body).await
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait IntrospectionClient {
+    /// Post an introspection request and return the body of the response.
+    ///
+    /// The `body` is `application/x-www-form-urlencoded` and `authorization` the value of the
+    /// `Authorization` header, authenticating the resource server. Failing to reach the server or
+    /// a response status other than 200 should return an error.
+    async fn post(&mut self, url: &Url, authorization: &str, body: &str) -> Result<String, ()>;
+}
+
+/// An issuer asking a remote authorization server about the tokens presented to a resource.
+///
+/// Each token is introspected at most once for its lifetime, the grant of an active token is
+/// cached until it expires. Inactive tokens are not cached, they are asked for again when they
+/// are presented next. As the authorization server does not reveal the redirect uri of the client,
+/// the recovered grants carry the introspection url in its place.
+///
+/// The issuer can only recover access tokens, issuing and refreshing tokens always fails.
+///
+/// ```
+/// # use oxide_auth_async::introspection::{Introspection, IntrospectionClient};
+/// # fn with<C: IntrospectionClient>(client: C) {
+/// let issuer = Introspection::new(
+///     client,
+///     "https://auth.example.com/introspect".parse().unwrap(),
+///     "resource-server",
+///     "secret",
+/// );
+/// # }
+/// ```
+pub struct Introspection<C> {
+    client: C,
+    url: Url,
+    authorization: String,
+    default_lifetime: Duration,
+    cache: HashMap<String, Grant>,
+}
+
+impl<C: IntrospectionClient> Introspection<C> {
+    /// Introspect tokens at `url`, authenticating with the credentials of the resource server.
+    pub fn new(client: C, url: Url, client_id: &str, client_secret: &str) -> Self {
+        // The credentials are form encoded before their use in basic authentication, RFC 6749.
+        let encode = |value: &str| form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+        let credentials = format!("{}:{}", encode(client_id), encode(client_secret));
+
+        Introspection {
+            client,
+            url,
+            authorization: format!("Basic {}", STANDARD.encode(credentials)),
+            default_lifetime: Duration::minutes(1),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Set how long a token is considered valid when the server does not name its expiry.
+    ///
+    /// Defaults to one minute. Such tokens are introspected again after this duration.
+    pub fn default_lifetime(&mut self, lifetime: Duration) {
+        self.default_lifetime = lifetime;
+    }
+
+    /// Forget all cached grants, for example when tokens were revoked at the server.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    async fn introspect(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("token", token)
+            .append_pair("token_type_hint", "access_token")
+            .finish();
+        let response = self.client.post(&self.url, &self.authorization, &body).await?;
+        let info: TokenInfo = serde_json::from_str(&response).map_err(|_| ())?;
+
+        if !info.active {
+            return Ok(None);
+        }
+
+        let until = match info.exp {
+            Some(exp) => Utc.timestamp_opt(exp, 0).single().ok_or(())?,
+            None => Utc::now() + self.default_lifetime,
+        };
+        let client_id = info.client_id.unwrap_or_default();
+
+        Ok(Some(Grant {
+            owner_id: info.sub.unwrap_or_else(|| client_id.clone()),
+            client_id,
+            scope: info.scope.as_deref().unwrap_or("").parse().map_err(|_| ())?,
+            redirect_uri: self.url.clone(),
+            until,
+            extensions: Extensions::new(),
+        }))
+    }
+}
+
+#[async_trait]
+impl<C: IntrospectionClient + Send> Issuer for Introspection<C> {
+    async fn issue(&mut self, _: Grant) -> Result<IssuedToken, ()> {
+        Err(())
+    }
+
+    async fn refresh(&mut self, _: &str, _: Grant) -> Result<RefreshedToken, ()> {
+        Err(())
+    }
+
+    async fn recover_token(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        let now = Utc::now();
+        if let Some(grant) = self.cache.get(token) {
+            if grant.until > now {
+                return Ok(Some(grant.clone()));
+            }
+        }
+
+        // Drop the expired grants so that the cache does not grow without bounds.
+        self.cache.retain(|_, grant| grant.until > now);
+
+        let grant = self.introspect(token).await?;
+        if let Some(grant) = &grant {
+            if grant.until > now {
+                self.cache.insert(token.to_owned(), grant.clone());
+            }
+        }
+
+        Ok(grant)
+    }
+
+    async fn recover_refresh(&mut self, _: &str) -> Result<Option<Grant>, ()> {
+        Ok(None)
+    }
+}
@@ -2,6 +2,55 @@ pub mod code_grant;
 pub mod endpoint;
 pub mod primitives;
 pub mod frontends;
+pub mod introspection;
+
+/// Commonly used traits and types of async endpoints, for a single glob import.
+///
+/// Contains the async primitive traits, the flows and the `oxide-auth` types they are used with,
+/// such as the synchronous primitives which also implement the async traits.
+///
+/// The `Authorizer`, `Issuer` and `Registrar` traits of this prelude are the async versions. They
+/// share their names with the synchronous traits of `oxide_auth::primitives::prelude` and a name
+/// imported by both globs is ambiguous. Everything else is the same item in both preludes. Import
+/// the synchronous traits under an alias when both are needed, for example to implement one:
+///
+/// ```
+/// use oxide_auth_async::prelude::*;
+/// use oxide_auth::primitives::prelude::{Issuer as SyncIssuer, Registrar as SyncRegistrar};
+///
+/// async fn recover(issuer: &mut (dyn Issuer + Send), token: &str) -> bool {
+///     matches!(issuer.recover_token(token).await, Ok(Some(_)))
+/// }
+///
+/// fn recover_sync(issuer: &dyn SyncIssuer, token: &str) -> bool {
+///     matches!(issuer.recover_token(token), Ok(Some(_)))
+/// }
+///
+/// let mut issuer = TokenMap::new(RandomGenerator::new(16));
+/// # let _ = recover(&mut issuer, "token");
+/// # assert!(!recover_sync(&issuer, "token"));
+/// # let _: &dyn SyncRegistrar = &ClientMap::new();
+/// ```
+pub mod prelude {
+    pub use crate::endpoint::{Endpoint, OwnerSolicitor, SessionValidator};
+    pub use crate::endpoint::access_token::AccessTokenFlow;
+    pub use crate::endpoint::authorization::AuthorizationFlow;
+    pub use crate::endpoint::client_credentials::ClientCredentialsFlow;
+    pub use crate::endpoint::introspection::IntrospectionFlow;
+    pub use crate::endpoint::refresh::RefreshFlow;
+    pub use crate::endpoint::resource::ResourceFlow;
+    pub use crate::endpoint::revocation::RevocationFlow;
+    pub use crate::frontends::simple::endpoint::FnSolicitor;
+    pub use crate::primitives::{Authorizer, FromSync, Issuer, Registrar};
+
+    pub use oxide_auth::endpoint::{
+        OAuthError, OwnerConsent, Scopes, Solicitation, Template, WebRequest, WebResponse,
+    };
+    pub use oxide_auth::primitives::prelude::{
+        Assertion, AuthMap, ChainRegistrar, Client, ClientMap, ClientUrl, CodeSigner, GrantQuery,
+        IssuedToken, PreGrant, RandomGenerator, Scope, TagGrant, TokenMap, TokenSigner,
+    };
+}
 
 #[cfg(test)]
 mod tests;
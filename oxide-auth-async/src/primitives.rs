@@ -1,12 +1,35 @@
 //! Async versions of all primitives traits.
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use oxide_auth::primitives::{grant::Grant, scope::Scope};
-use oxide_auth::primitives::issuer::{IssuedToken, RefreshedToken};
+use oxide_auth::primitives::issuer::{IssuedToken, RefreshIssuance, RefreshedToken};
 use oxide_auth::primitives::{
     authorizer, registrar, issuer,
-    registrar::{ClientUrl, BoundClient, RegistrarError, PreGrant},
+    registrar::{ClientUrl, BoundClient, ClientTrust, GrantType, RegistrarError, PreGrant},
 };
 
+/// Adapts a synchronous primitive of `oxide-auth` to the async traits of this crate.
+///
+/// Each operation delegates to the wrapped primitive and completes immediately, without ever
+/// suspending. Synchronous registrars, authorizers and issuers such as `ClientMap`, `AuthMap` and
+/// `TokenMap` also implement the async traits on their own, the wrapper makes that choice explicit
+/// in the type of an endpoint. For owner solicitors, which have no such blanket implementation,
+/// it is the way to use any synchronous solicitor in an async flow.
+///
+/// ```
+/// # use oxide_auth::primitives::prelude::*;
+/// use oxide_auth_async::primitives::{FromSync, Issuer};
+///
+/// async fn recover(issuer: &mut FromSync<TokenMap<RandomGenerator>>) -> bool {
+///     issuer.recover_token("token").await.unwrap().is_some()
+/// }
+///
+/// let mut issuer = FromSync(TokenMap::new(RandomGenerator::new(16)));
+/// # let _ = recover(&mut issuer);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FromSync<T>(pub T);
+
 #[async_trait]
 pub trait Authorizer {
     async fn authorize(&mut self, _: Grant) -> Result<String, ()>;
@@ -28,15 +51,104 @@ where
     }
 }
 
+/// Issues and recovers tokens, possibly through I/O such as a separate token service.
+///
+/// Every synchronous `Issuer` of `oxide-auth` also implements this trait. Issuers which need to
+/// await a database or a remote service implement it directly instead, the flows of this crate
+/// then await each operation without blocking the executor. The methods have the same meaning as
+/// those of the synchronous [`Issuer`], refer to it for details.
+///
+/// An issuer storing its tokens in a service reached over HTTP, here with a `reqwest::Client`:
+///
+/// ```
+/// # use async_trait::async_trait;
+/// # use oxide_auth::primitives::grant::Grant;
+/// # use oxide_auth::primitives::issuer::{IssuedToken, RefreshedToken};
+/// use oxide_auth_async::primitives::Issuer;
+///
+/// # #[derive(Clone)] struct Client;
+/// # impl Client {
+/// #     async fn post<T>(&self, _: String, _: &impl std::fmt::Debug) -> Result<T, ()> { Err(()) }
+/// # }
+/// struct RemoteIssuer {
+///     /// Shared connection pool, for example a `reqwest::Client`.
+///     client: Client,
+///     base: String,
+/// }
+///
+/// #[async_trait]
+/// impl Issuer for RemoteIssuer {
+///     async fn issue(&mut self, grant: Grant) -> Result<IssuedToken, ()> {
+///         // With reqwest, roughly `client.post(url).json(&grant).send().await?.json().await`.
+///         self.client.post(format!("{}/issue", self.base), &grant).await
+///     }
+///
+///     async fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
+///         self.client.post(format!("{}/refresh", self.base), &(token, grant)).await
+///     }
+///
+///     async fn recover_token(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+///         self.client.post(format!("{}/token", self.base), &token).await
+///     }
+///
+///     async fn recover_refresh(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+///         self.client.post(format!("{}/refresh_token", self.base), &token).await
+///     }
+/// }
+/// ```
+///
+/// [`Issuer`]: ../../oxide_auth/primitives/issuer/trait.Issuer.html
 #[async_trait]
 pub trait Issuer {
+    /// Create a token authorizing the request parameters.
     async fn issue(&mut self, _: Grant) -> Result<IssuedToken, ()>;
 
+    /// Create a token, choosing whether it gets a refresh token and for how long that is valid.
+    ///
+    /// See the synchronous [`Issuer::issue_with`], the default implementation behaves the same.
+    ///
+    /// [`Issuer::issue_with`]: ../../oxide_auth/primitives/issuer/trait.Issuer.html#method.issue_with
+    async fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        match refresh {
+            RefreshIssuance::Default => self.issue(grant).await,
+            RefreshIssuance::Never => {
+                let issued = self.issue(grant).await?;
+                Ok(IssuedToken {
+                    refresh: None,
+                    refresh_until: None,
+                    ..issued
+                })
+            }
+            RefreshIssuance::ValidFor(_) => Err(()),
+        }
+    }
+
+    /// Refresh a token, using the grant recovered from the refresh token.
     async fn refresh(&mut self, _: &str, _: Grant) -> Result<RefreshedToken, ()>;
 
+    /// Get the values corresponding to a bearer token, if it is valid.
     async fn recover_token(&mut self, _: &str) -> Result<Option<Grant>, ()>;
 
+    /// Get the values corresponding to a refresh token, if it is valid.
     async fn recover_refresh(&mut self, _: &str) -> Result<Option<Grant>, ()>;
+
+    /// Extend the expiry of an access token in place, for sliding sessions.
+    ///
+    /// See the synchronous [`Issuer::touch`], failing is the default implementation.
+    ///
+    /// [`Issuer::touch`]: ../../oxide_auth/primitives/issuer/trait.Issuer.html#method.touch
+    async fn touch(&mut self, _: &str, _: DateTime<Utc>) -> Result<DateTime<Utc>, ()> {
+        Err(())
+    }
+
+    /// Revoke an access or a refresh token before it expires.
+    ///
+    /// See the synchronous [`Issuer::revoke_token`], failing is the default implementation.
+    ///
+    /// [`Issuer::revoke_token`]: ../../oxide_auth/primitives/issuer/trait.Issuer.html#method.revoke_token
+    async fn revoke_token(&mut self, _: &str) -> Result<(), ()> {
+        Err(())
+    }
 }
 
 #[async_trait]
@@ -48,6 +160,10 @@ where
         issuer::Issuer::issue(self, grant)
     }
 
+    async fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        issuer::Issuer::issue_with(self, grant, refresh)
+    }
+
     async fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
         issuer::Issuer::refresh(self, token, grant)
     }
@@ -59,6 +175,14 @@ where
     async fn recover_refresh(&mut self, token: &str) -> Result<Option<Grant>, ()> {
         issuer::Issuer::recover_refresh(self, token)
     }
+
+    async fn touch(&mut self, token: &str, until: DateTime<Utc>) -> Result<DateTime<Utc>, ()> {
+        issuer::Issuer::touch(self, token, until)
+    }
+
+    async fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        issuer::Issuer::revoke_token(self, token)
+    }
 }
 
 #[async_trait]
@@ -70,6 +194,38 @@ pub trait Registrar {
     ) -> Result<PreGrant, RegistrarError>;
 
     async fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError>;
+
+    /// Try to login as client with a JWT assertion.
+    ///
+    /// Rejecting all assertions is the default implementation.
+    async fn check_assertion(&self, _client_id: &str, _assertion: &str) -> Result<(), RegistrarError> {
+        Err(RegistrarError::Unspecified)
+    }
+
+    /// Try to login as client with the certificate of its TLS connection.
+    ///
+    /// Authenticating the client as if no credentials were sent is the default implementation.
+    async fn check_certificate(
+        &self, client_id: &str, _certificate: &[u8],
+    ) -> Result<(), RegistrarError> {
+        self.check(client_id, None).await
+    }
+
+    /// Determine how far the server trusts a client.
+    ///
+    /// Returning `ClientTrust::ThirdParty` is the default implementation.
+    async fn client_trust(&self, _client_id: &str) -> ClientTrust {
+        ClientTrust::ThirdParty
+    }
+
+    /// Check that the client may use a grant type.
+    ///
+    /// Permitting all grant types is the default implementation.
+    async fn check_grant_type(
+        &self, _client_id: &str, _grant_type: GrantType,
+    ) -> Result<(), RegistrarError> {
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -90,4 +246,112 @@ where
     async fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         registrar::Registrar::check(self, client_id, passphrase)
     }
+
+    async fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        registrar::Registrar::check_assertion(self, client_id, assertion)
+    }
+
+    async fn check_certificate(
+        &self, client_id: &str, certificate: &[u8],
+    ) -> Result<(), RegistrarError> {
+        registrar::Registrar::check_certificate(self, client_id, certificate)
+    }
+
+    async fn client_trust(&self, client_id: &str) -> ClientTrust {
+        registrar::Registrar::client_trust(self, client_id)
+    }
+
+    async fn check_grant_type(
+        &self, client_id: &str, grant_type: GrantType,
+    ) -> Result<(), RegistrarError> {
+        registrar::Registrar::check_grant_type(self, client_id, grant_type)
+    }
+}
+
+#[async_trait]
+impl<T> Authorizer for FromSync<T>
+where
+    T: authorizer::Authorizer + Send,
+{
+    async fn authorize(&mut self, grant: Grant) -> Result<String, ()> {
+        authorizer::Authorizer::authorize(&mut self.0, grant)
+    }
+
+    async fn extract(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        authorizer::Authorizer::extract(&mut self.0, token)
+    }
+}
+
+#[async_trait]
+impl<T> Issuer for FromSync<T>
+where
+    T: issuer::Issuer + Send,
+{
+    async fn issue(&mut self, grant: Grant) -> Result<IssuedToken, ()> {
+        issuer::Issuer::issue(&mut self.0, grant)
+    }
+
+    async fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        issuer::Issuer::issue_with(&mut self.0, grant, refresh)
+    }
+
+    async fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
+        issuer::Issuer::refresh(&mut self.0, token, grant)
+    }
+
+    async fn recover_token(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        issuer::Issuer::recover_token(&self.0, token)
+    }
+
+    async fn recover_refresh(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        issuer::Issuer::recover_refresh(&self.0, token)
+    }
+
+    async fn touch(&mut self, token: &str, until: DateTime<Utc>) -> Result<DateTime<Utc>, ()> {
+        issuer::Issuer::touch(&mut self.0, token, until)
+    }
+
+    async fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        issuer::Issuer::revoke_token(&mut self.0, token)
+    }
+}
+
+#[async_trait]
+impl<T> Registrar for FromSync<T>
+where
+    T: registrar::Registrar + Send + Sync,
+{
+    async fn bound_redirect<'a>(&self, bound: ClientUrl<'a>) -> Result<BoundClient<'a>, RegistrarError> {
+        registrar::Registrar::bound_redirect(&self.0, bound)
+    }
+
+    async fn negotiate<'a>(
+        &self, client: BoundClient<'a>, scope: Option<Scope>,
+    ) -> Result<PreGrant, RegistrarError> {
+        registrar::Registrar::negotiate(&self.0, client, scope)
+    }
+
+    async fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
+        registrar::Registrar::check(&self.0, client_id, passphrase)
+    }
+
+    async fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        registrar::Registrar::check_assertion(&self.0, client_id, assertion)
+    }
+
+    async fn check_certificate(
+        &self, client_id: &str, certificate: &[u8],
+    ) -> Result<(), RegistrarError> {
+        registrar::Registrar::check_certificate(&self.0, client_id, certificate)
+    }
+
+    async fn client_trust(&self, client_id: &str) -> ClientTrust {
+        registrar::Registrar::client_trust(&self.0, client_id)
+    }
+
+    async fn check_grant_type(
+        &self, client_id: &str, grant_type: GrantType,
+    ) -> Result<(), RegistrarError> {
+        registrar::Registrar::check_grant_type(&self.0, client_id, grant_type)
+    }
 }
@@ -2,24 +2,29 @@ use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use oxide_auth::primitives::authorizer::AuthMap;
 use oxide_auth::primitives::issuer::TokenMap;
-use oxide_auth::primitives::grant::{Grant, Extensions};
+use oxide_auth::primitives::grant::{Grant, Extensions, Value};
 use oxide_auth::{
     frontends::simple::endpoint::Error,
     primitives::registrar::{Client, ClientMap, RegisteredUrl},
-    endpoint::WebRequest,
+    code_grant::{error::AccessTokenErrorType, extensions::Pkce},
+    endpoint::{ErrorMapping, FlowError, FlowErrorDetail, WebRequest},
 };
 
+use oxide_auth::primitives::issuer::{IssuedToken, RefreshedToken, TokenType};
+use oxide_auth::primitives::registrar::{BoundClient, ClientUrl, PreGrant, RegistrarError};
+use oxide_auth::primitives::scope::Scope;
 use crate::{
     endpoint::{access_token::AccessTokenFlow, Endpoint},
-    primitives::Authorizer,
+    primitives::{Authorizer, FromSync, Issuer, Registrar},
 };
 //use crate::frontends::simple::endpoint::access_token_flow;
 
 use std::collections::HashMap;
 
+use async_trait::async_trait;
 use chrono::{Utc, Duration};
 
-use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::{Body, CraftedRequest, CraftedResponse, RoundTrip, Status, TestGenerator, ToSingleValueQuery};
 use super::defaults::*;
 
 struct AccessTokenSetup {
@@ -33,18 +38,22 @@ struct AccessTokenSetup {
 struct AccessTokenEndpoint<'a> {
     registrar: &'a ClientMap,
     authorizer: &'a mut AuthMap<TestGenerator>,
-    issuer: &'a mut TokenMap<TestGenerator>,
+    issuer: &'a mut (dyn crate::primitives::Issuer + Send + Sync),
+    error_mapping: Option<ErrorMapping>,
+    parameter_limit: Option<usize>,
 }
 
 impl<'a> AccessTokenEndpoint<'a> {
     pub fn new(
         registrar: &'a ClientMap, authorizer: &'a mut AuthMap<TestGenerator>,
-        issuer: &'a mut TokenMap<TestGenerator>,
+        issuer: &'a mut (dyn crate::primitives::Issuer + Send + Sync),
     ) -> Self {
         AccessTokenEndpoint {
             registrar,
             authorizer,
             issuer,
+            error_mapping: None,
+            parameter_limit: None,
         }
     }
 }
@@ -80,6 +89,102 @@ impl<'a> Endpoint<CraftedRequest> for AccessTokenEndpoint<'a> {
     ) -> Option<&mut (dyn crate::endpoint::OwnerSolicitor<CraftedRequest> + Send)> {
         None
     }
+    fn on_flow_error(&mut self, _: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        if let Some(mapping) = &self.error_mapping {
+            mapping.apply(detail);
+        }
+    }
+    fn parameter_limit(&self) -> Option<usize> {
+        self.parameter_limit
+    }
+}
+
+/// An endpoint built from synchronous primitives, the registrar may be replaced.
+struct SyncEndpoint<R = FromSync<ClientMap>> {
+    registrar: R,
+    authorizer: FromSync<AuthMap<TestGenerator>>,
+    issuer: FromSync<TokenMap<TestGenerator>>,
+}
+
+impl<R: Registrar + Sync> Endpoint<CraftedRequest> for SyncEndpoint<R> {
+    type Error = Error<CraftedRequest>;
+
+    fn registrar(&self) -> Option<&(dyn crate::primitives::Registrar + Sync)> {
+        Some(&self.registrar)
+    }
+    fn authorizer_mut(&mut self) -> Option<&mut (dyn crate::primitives::Authorizer + Send)> {
+        Some(&mut self.authorizer)
+    }
+    fn issuer_mut(&mut self) -> Option<&mut (dyn crate::primitives::Issuer + Send)> {
+        Some(&mut self.issuer)
+    }
+    fn response(
+        &mut self, _: &mut CraftedRequest, _: oxide_auth::endpoint::Template,
+    ) -> Result<<CraftedRequest as WebRequest>::Response, Self::Error> {
+        Ok(Default::default())
+    }
+    fn error(&mut self, _err: oxide_auth::endpoint::OAuthError) -> Self::Error {
+        unimplemented!()
+    }
+    fn web_error(&mut self, _err: <CraftedRequest as WebRequest>::Error) -> Self::Error {
+        unimplemented!()
+    }
+    fn scopes(&mut self) -> Option<&mut dyn oxide_auth::endpoint::Scopes<CraftedRequest>> {
+        None
+    }
+    fn owner_solicitor(
+        &mut self,
+    ) -> Option<&mut (dyn crate::endpoint::OwnerSolicitor<CraftedRequest> + Send)> {
+        None
+    }
+}
+
+/// Stands in for an issuer reached over the network.
+///
+/// Every operation is suspended once before it completes, as a request to a remote service would.
+#[derive(Default)]
+struct RemoteIssuer {
+    tokens: HashMap<String, Grant>,
+    round_trips: usize,
+}
+
+impl RemoteIssuer {
+    async fn round_trip(&mut self) {
+        RoundTrip(false).await;
+        self.round_trips += 1;
+    }
+}
+
+#[async_trait]
+impl Issuer for RemoteIssuer {
+    async fn issue(&mut self, grant: Grant) -> Result<IssuedToken, ()> {
+        self.round_trip().await;
+        let token = format!("RemoteToken{}", self.tokens.len());
+        let until = grant.until;
+        self.tokens.insert(token.clone(), grant);
+        Ok(IssuedToken {
+            token,
+            refresh: None,
+            until,
+            token_type: TokenType::Bearer,
+            refresh_until: None,
+        })
+    }
+
+    async fn refresh(&mut self, _: &str, _: Grant) -> Result<RefreshedToken, ()> {
+        self.round_trip().await;
+        Err(())
+    }
+
+    async fn recover_token(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        self.round_trip().await;
+        Ok(self.tokens.get(token).cloned())
+    }
+
+    async fn recover_refresh(&mut self, _: &str) -> Result<Option<Grant>, ()> {
+        self.round_trip().await;
+        Ok(None)
+    }
 }
 
 impl AccessTokenSetup {
@@ -168,6 +273,7 @@ impl AccessTokenSetup {
             Status::BadRequest => (),
             _ => panic!("Expected error status, got {:?}", response),
         }
+        assert!(response.no_store, "Token responses must not be cached");
     }
 
     fn test_simple_error(&mut self, request: CraftedRequest) {
@@ -210,6 +316,7 @@ impl AccessTokenSetup {
 
     fn assert_ok_access_token(&mut self, response: CraftedResponse) {
         assert_eq!(response.status, Status::Ok);
+        assert!(response.no_store, "Token responses must not be cached");
     }
 }
 
@@ -345,6 +452,92 @@ fn access_request_unknown_client() {
     setup.test_simple_error(unknown_client);
 }
 
+#[test]
+fn access_request_unknown_client_mapped() {
+    let mut setup = AccessTokenSetup::private_client();
+    let mut error_code = |mapping: Option<ErrorMapping>| {
+        let unknown_client = CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("code", &setup.authtoken),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: Some(
+                "Basic ".to_string()
+                    + &STANDARD.encode(format!("{}:{}", "SomeOtherClient", EXAMPLE_PASSPHRASE)),
+            ),
+        };
+
+        let mut endpoint =
+            AccessTokenEndpoint::new(&setup.registrar, &mut setup.authorizer, &mut setup.issuer);
+        endpoint.error_mapping = mapping;
+        let mut flow = AccessTokenFlow::prepare(endpoint).unwrap();
+        let response = smol::block_on(flow.execute(unknown_client)).expect("Expected an error response");
+        assert_eq!(response.status, Status::Unauthorized);
+
+        match response.body {
+            Some(Body::Json(ref json)) => {
+                let mut content: HashMap<String, String> = serde_json::from_str(json).unwrap();
+                content.remove("error").expect("Error not set in json response")
+            }
+            other => panic!("Expected json encoded body, got {:?}", other),
+        }
+    };
+
+    assert_eq!(error_code(None), "invalid_client");
+
+    let mut mapping = ErrorMapping::new();
+    mapping.map_access_token(
+        AccessTokenErrorType::InvalidClient,
+        AccessTokenErrorType::InvalidGrant,
+    );
+    assert_eq!(error_code(Some(mapping)), "invalid_grant");
+}
+
+#[test]
+fn access_request_parameter_limit() {
+    let mut setup = AccessTokenSetup::public_client();
+    let mut execute = |padding: usize| {
+        let mut body = vec![
+            ("grant_type".to_string(), "authorization_code".to_string()),
+            ("client_id".to_string(), EXAMPLE_CLIENT_ID.to_string()),
+            ("code".to_string(), setup.authtoken.clone()),
+            ("redirect_uri".to_string(), EXAMPLE_REDIRECT_URI.to_string()),
+        ];
+        body.extend((0..padding).map(|i| (format!("padding{}", i), "x".to_string())));
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(body.iter().to_single_value_query()),
+            auth: None,
+        };
+
+        let mut endpoint =
+            AccessTokenEndpoint::new(&setup.registrar, &mut setup.authorizer, &mut setup.issuer);
+        endpoint.parameter_limit = Some(4);
+        let mut flow = AccessTokenFlow::prepare(endpoint).unwrap();
+        smol::block_on(flow.execute(request)).expect("Expected a response")
+    };
+
+    let response = execute(100);
+    assert_eq!(response.status, Status::BadRequest);
+    match response.body {
+        Some(Body::Json(ref json)) => {
+            let content: HashMap<String, String> = serde_json::from_str(json).unwrap();
+            assert_eq!(content.get("error").map(String::as_str), Some("invalid_request"));
+        }
+        other => panic!("Expected json encoded body, got {:?}", other),
+    }
+
+    // The code was not consumed by the rejected request.
+    let response = execute(0);
+    assert_eq!(response.status, Status::Ok);
+}
+
 #[test]
 fn access_request_wrong_authentication() {
     let mut setup = AccessTokenSetup::private_client();
@@ -603,6 +796,77 @@ fn access_request_wrong_grant_type() {
     setup.test_simple_error(wrong_grant_type);
 }
 
+#[test]
+fn access_remote_issuer() {
+    let mut setup = AccessTokenSetup::private_client();
+    let mut issuer = RemoteIssuer::default();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let endpoint = AccessTokenEndpoint::new(&setup.registrar, &mut setup.authorizer, &mut issuer);
+    let mut flow = AccessTokenFlow::prepare(endpoint).unwrap();
+    let response = smol::block_on(flow.execute(request)).expect("Expected non-error response");
+
+    let content: HashMap<String, serde_json::Value> = match response.body {
+        Some(Body::Json(ref json)) => serde_json::from_str(json).unwrap(),
+        ref other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    setup.assert_ok_access_token(response);
+    assert_eq!(content.get("access_token"), Some(&"RemoteToken0".into()));
+    assert_eq!(issuer.round_trips, 1);
+
+    let grant = smol::block_on(issuer.recover_token("RemoteToken0"))
+        .unwrap()
+        .expect("Issued token should be recoverable");
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+    assert_eq!(grant.client_id, EXAMPLE_CLIENT_ID);
+}
+
+#[test]
+fn access_from_sync() {
+    let setup = AccessTokenSetup::private_client();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let endpoint = SyncEndpoint {
+        registrar: FromSync(setup.registrar),
+        authorizer: FromSync(setup.authorizer),
+        issuer: FromSync(setup.issuer),
+    };
+    let mut flow = AccessTokenFlow::prepare(endpoint).unwrap();
+    let response = smol::block_on(flow.execute(request)).expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    let content: HashMap<String, serde_json::Value> = match response.body {
+        Some(Body::Json(ref json)) => serde_json::from_str(json).unwrap(),
+        ref other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    assert_eq!(content.get("access_token"), Some(&"AccessToken".into()));
+}
+
 #[test]
 fn assert_send() {
     let mut setup = AccessTokenSetup::public_client();
@@ -681,3 +945,126 @@ fn private_duplicate_authentication() {
 
     setup.test_simple_error(valid_public);
 }
+
+/// Accepts a single, fixed client assertion, as a remote registrar verifying it would.
+struct AssertingRegistrar {
+    clients: ClientMap,
+    assertion: &'static str,
+}
+
+#[async_trait]
+impl Registrar for AssertingRegistrar {
+    async fn bound_redirect<'a>(&self, bound: ClientUrl<'a>) -> Result<BoundClient<'a>, RegistrarError> {
+        Registrar::bound_redirect(&self.clients, bound).await
+    }
+
+    async fn negotiate<'a>(
+        &self, client: BoundClient<'a>, scope: Option<Scope>,
+    ) -> Result<PreGrant, RegistrarError> {
+        Registrar::negotiate(&self.clients, client, scope).await
+    }
+
+    async fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
+        Registrar::check(&self.clients, client_id, passphrase).await
+    }
+
+    async fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        match client_id == EXAMPLE_CLIENT_ID && assertion == self.assertion {
+            true => Ok(()),
+            false => Err(RegistrarError::Unspecified),
+        }
+    }
+}
+
+#[test]
+fn access_client_assertion() {
+    fn redeem(assertion: &str) -> CraftedResponse {
+        let setup = AccessTokenSetup::private_client();
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(
+                vec![
+                    ("grant_type", "authorization_code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("code", &setup.authtoken),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                    (
+                        "client_assertion_type",
+                        "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+                    ),
+                    ("client_assertion", assertion),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: None,
+        };
+
+        let endpoint = SyncEndpoint {
+            registrar: AssertingRegistrar {
+                clients: setup.registrar,
+                assertion: "signed.client.assertion",
+            },
+            authorizer: FromSync(setup.authorizer),
+            issuer: FromSync(setup.issuer),
+        };
+        let mut flow = AccessTokenFlow::prepare(endpoint).unwrap();
+        smol::block_on(flow.execute(request)).expect("Expected non-error response")
+    }
+
+    assert_eq!(redeem("signed.client.assertion").status, Status::Ok);
+
+    let response = redeem("tampered.client.assertion");
+    assert_eq!(response.status, Status::Unauthorized);
+    AccessTokenSetup::assert_json_error_set(&response);
+}
+
+#[test]
+fn access_require_pkce_verifier() {
+    let mut setup = AccessTokenSetup::public_client();
+
+    // Replaces the code of the setup, the endpoint has no extension to verify the challenge.
+    let mut extensions = Extensions::new();
+    extensions.set(&Pkce::required(), Value::private(Some("challengeS".into())));
+    let grant = Grant {
+        client_id: EXAMPLE_CLIENT_ID.to_string(),
+        owner_id: EXAMPLE_OWNER_ID.to_string(),
+        redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+        scope: EXAMPLE_SCOPE.parse().unwrap(),
+        until: Utc::now() + Duration::hours(1),
+        extensions,
+    };
+    let code = smol::block_on(setup.authorizer.authorize(grant)).unwrap();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("code", &code),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    let mut flow = AccessTokenFlow::prepare(AccessTokenEndpoint::new(
+        &setup.registrar,
+        &mut setup.authorizer,
+        &mut setup.issuer,
+    ))
+    .unwrap();
+    flow.require_pkce_verifier();
+    let response = smol::block_on(flow.execute(request)).expect("Expected non-error response");
+    assert_eq!(response.status, Status::BadRequest);
+
+    let content: HashMap<String, serde_json::Value> = match response.body {
+        Some(Body::Json(ref json)) => serde_json::from_str(json).unwrap(),
+        ref other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    assert_eq!(content.get("error"), Some(&"invalid_grant".into()));
+    assert!(!content.contains_key("access_token"));
+}
@@ -1,15 +1,18 @@
 use std::collections::HashMap;
 
 use oxide_auth::primitives::authorizer::AuthMap;
+use oxide_auth::primitives::issuer::TokenMap;
 use oxide_auth::{
     primitives::registrar::{Client, ClientMap, RegisteredUrl},
     frontends::simple::endpoint::Error,
-    endpoint::WebRequest,
+    endpoint::{FlowError, FlowErrorDetail, OwnerConsent, Scope, Solicitation, WebRequest},
 };
 
 use crate::endpoint::{Endpoint, OwnerSolicitor, authorization::AuthorizationFlow};
+use crate::frontends::simple::endpoint::FnSolicitor;
+use crate::primitives::FromSync;
 
-use super::{CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::{CraftedRequest, CraftedResponse, RoundTrip, Status, TestGenerator, ToSingleValueQuery};
 use super::{Allow, Deny};
 use super::defaults::*;
 
@@ -17,6 +20,8 @@ struct AuthorizationEndpoint<'a> {
     registrar: &'a ClientMap,
     authorizer: &'a mut AuthMap<TestGenerator>,
     solicitor: &'a mut (dyn OwnerSolicitor<CraftedRequest> + Send + Sync),
+    issuer: Option<&'a mut TokenMap<TestGenerator>>,
+    causes: Option<&'a mut Vec<FlowError>>,
 }
 
 impl<'a> AuthorizationEndpoint<'a> {
@@ -28,6 +33,22 @@ impl<'a> AuthorizationEndpoint<'a> {
             registrar,
             authorizer,
             solicitor,
+            issuer: None,
+            causes: None,
+        }
+    }
+
+    fn with_issuer(self, issuer: &'a mut TokenMap<TestGenerator>) -> Self {
+        Self {
+            issuer: Some(issuer),
+            ..self
+        }
+    }
+
+    fn with_causes(self, causes: &'a mut Vec<FlowError>) -> Self {
+        Self {
+            causes: Some(causes),
+            ..self
         }
     }
 }
@@ -42,7 +63,10 @@ impl<'a> Endpoint<CraftedRequest> for AuthorizationEndpoint<'a> {
         Some(self.authorizer)
     }
     fn issuer_mut(&mut self) -> Option<&mut (dyn crate::primitives::Issuer + Send)> {
-        None
+        match &mut self.issuer {
+            Some(issuer) => Some(*issuer),
+            None => None,
+        }
     }
     fn scopes(&mut self) -> Option<&mut dyn oxide_auth::endpoint::Scopes<CraftedRequest>> {
         None
@@ -61,6 +85,11 @@ impl<'a> Endpoint<CraftedRequest> for AuthorizationEndpoint<'a> {
     fn owner_solicitor(&mut self) -> Option<&mut (dyn OwnerSolicitor<CraftedRequest> + Send)> {
         Some(self.solicitor)
     }
+    fn on_flow_error(&mut self, error: FlowError, _: &mut FlowErrorDetail<'_>) {
+        if let Some(causes) = &mut self.causes {
+            causes.push(error);
+        }
+    }
 }
 
 struct AuthorizationSetup {
@@ -104,19 +133,18 @@ impl AuthorizationSetup {
         }
     }
 
-    fn test_silent_error(&mut self, request: CraftedRequest) {
+    fn test_silent_error(&mut self, request: CraftedRequest, cause: FlowError) {
         let mut solicitor = Allow(EXAMPLE_OWNER_ID.to_string());
-        let mut authorization_flow = AuthorizationFlow::prepare(AuthorizationEndpoint::new(
-            &self.registrar,
-            &mut self.authorizer,
-            &mut solicitor,
-        ))
-        .unwrap();
+        let mut causes = Vec::new();
+        let endpoint = AuthorizationEndpoint::new(&self.registrar, &mut self.authorizer, &mut solicitor)
+            .with_causes(&mut causes);
+        let mut authorization_flow = AuthorizationFlow::prepare(endpoint).unwrap();
         match smol::block_on(authorization_flow.execute(request)) {
             Ok(ref resp) if resp.location.is_some() => panic!("Redirect without client id {:?}", resp),
             Ok(resp) => panic!("Response without client id {:?}", resp),
             Err(_) => (),
         }
+        assert_eq!(causes, [cause]);
     }
 
     fn test_error_redirect<P: Send + Sync>(&mut self, request: CraftedRequest, mut pagehandler: P)
@@ -185,7 +213,7 @@ fn auth_request_silent_missing_client() {
         auth: None,
     };
 
-    AuthorizationSetup::new().test_silent_error(missing_client);
+    AuthorizationSetup::new().test_silent_error(missing_client, FlowError::MalformedRequest);
 }
 
 #[test]
@@ -205,7 +233,7 @@ fn auth_request_silent_unknown_client() {
         auth: None,
     };
 
-    AuthorizationSetup::new().test_silent_error(unknown_client);
+    AuthorizationSetup::new().test_silent_error(unknown_client, FlowError::UnknownClient);
 }
 
 #[test]
@@ -225,7 +253,7 @@ fn auth_request_silent_mismatching_redirect() {
         auth: None,
     };
 
-    AuthorizationSetup::new().test_silent_error(mismatching_redirect);
+    AuthorizationSetup::new().test_silent_error(mismatching_redirect, FlowError::InvalidRedirectUri);
 }
 
 #[test]
@@ -245,7 +273,7 @@ fn auth_request_silent_invalid_redirect() {
         auth: None,
     };
 
-    AuthorizationSetup::new().test_silent_error(invalid_redirect);
+    AuthorizationSetup::new().test_silent_error(invalid_redirect, FlowError::InvalidRedirectUri);
 }
 
 #[test]
@@ -309,3 +337,353 @@ fn auth_request_error_malformed_scope() {
 
     AuthorizationSetup::new().test_error_redirect(malformed_scope, Allow(EXAMPLE_OWNER_ID.to_string()));
 }
+
+/// Records the requested and the negotiated scope of the solicitation.
+#[derive(Default)]
+struct RecordScope(Option<(Option<Scope>, Scope)>);
+
+#[async_trait::async_trait]
+impl OwnerSolicitor<CraftedRequest> for RecordScope {
+    async fn check_consent(
+        &mut self, _: &mut CraftedRequest, solicitation: Solicitation<'_>,
+    ) -> OwnerConsent<CraftedResponse> {
+        self.0 = Some((
+            solicitation.requested_scope().cloned(),
+            solicitation.pre_grant().scope.clone(),
+        ));
+        OwnerConsent::Denied
+    }
+}
+
+#[test]
+fn auth_excess_scope_solicitation() {
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("scope", "example default admin"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let mut setup = AuthorizationSetup::new();
+    let mut solicitor = RecordScope::default();
+    let mut authorization_flow = AuthorizationFlow::prepare(AuthorizationEndpoint::new(
+        &setup.registrar,
+        &mut setup.authorizer,
+        &mut solicitor,
+    ))
+    .unwrap();
+    smol::block_on(authorization_flow.execute(request)).expect("Should not error");
+    drop(authorization_flow);
+
+    let (requested, negotiated) = solicitor.0.expect("Should have solicited");
+    assert_eq!(requested, Some("example default admin".parse().unwrap()));
+    assert_eq!(negotiated, EXAMPLE_SCOPE.parse().unwrap());
+}
+
+/// Approves only part of the requested scope.
+struct ApproveScope(Scope);
+
+#[async_trait::async_trait]
+impl OwnerSolicitor<CraftedRequest> for ApproveScope {
+    async fn check_consent(
+        &mut self, _: &mut CraftedRequest, _: Solicitation<'_>,
+    ) -> OwnerConsent<CraftedResponse> {
+        OwnerConsent::AuthorizedScope(EXAMPLE_OWNER_ID.to_string(), self.0.clone())
+    }
+}
+
+#[test]
+fn auth_owner_restricted_scope() {
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let mut setup = AuthorizationSetup::new();
+    let mut solicitor = ApproveScope("example".parse().unwrap());
+    let mut authorization_flow = AuthorizationFlow::prepare(AuthorizationEndpoint::new(
+        &setup.registrar,
+        &mut setup.authorizer,
+        &mut solicitor,
+    ))
+    .unwrap();
+    let response = smol::block_on(authorization_flow.execute(request)).expect("Should not error");
+    assert_eq!(response.status, Status::Redirect);
+
+    let code = response
+        .location
+        .expect("Expected a redirect location")
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Expected an authorization code");
+    let grant = oxide_auth::primitives::authorizer::Authorizer::extract(&mut setup.authorizer, &code)
+        .unwrap()
+        .expect("Code should be valid");
+    assert_eq!(grant.scope, "example".parse().unwrap());
+}
+
+#[test]
+fn auth_owner_widened_scope_denied() {
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let widened = ApproveScope("example default admin".parse().unwrap());
+    AuthorizationSetup::new().test_error_redirect(request, widened);
+}
+
+#[test]
+fn auth_implicit_fragment() {
+    const PUBLIC_CLIENT_ID: &str = "PublicClient";
+    let mut setup = AuthorizationSetup::new();
+    setup.registrar.register_client(Client::public(
+        PUBLIC_CLIENT_ID,
+        RegisteredUrl::Exact(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+    ));
+    let mut issuer = TokenMap::new(TestGenerator("AccessToken".to_string()));
+
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "token"),
+                ("client_id", PUBLIC_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("state", "OpaqueState"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let mut solicitor = Allow(EXAMPLE_OWNER_ID.to_string());
+    let endpoint = AuthorizationEndpoint::new(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .with_issuer(&mut issuer);
+    let mut authorization_flow = AuthorizationFlow::prepare(endpoint).unwrap();
+    authorization_flow.allow_implicit();
+    let response = smol::block_on(authorization_flow.execute(request)).expect("Should not error");
+    assert_eq!(response.status, Status::Redirect);
+
+    let location = response.location.expect("Expected a redirect location");
+    assert_eq!(location.query(), None);
+    let parameters: HashMap<String, String> =
+        url::form_urlencoded::parse(location.fragment().unwrap().as_bytes())
+            .into_owned()
+            .collect();
+    assert_eq!(parameters["access_token"], "AccessToken");
+    assert_eq!(parameters["token_type"], "bearer");
+    assert_eq!(parameters["state"], "OpaqueState");
+    assert!(parameters["expires_in"].parse::<i64>().unwrap() > 0);
+    assert!(!parameters.contains_key("refresh_token"));
+    assert!(!parameters.contains_key("code"));
+}
+
+#[test]
+fn auth_implicit_confidential_rejected() {
+    let mut setup = AuthorizationSetup::new();
+    let mut issuer = TokenMap::new(TestGenerator("AccessToken".to_string()));
+
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "token"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let mut solicitor = Allow(EXAMPLE_OWNER_ID.to_string());
+    let endpoint = AuthorizationEndpoint::new(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .with_issuer(&mut issuer);
+    let mut authorization_flow = AuthorizationFlow::prepare(endpoint).unwrap();
+    authorization_flow.allow_implicit();
+    let response = smol::block_on(authorization_flow.execute(request)).expect("Should not error");
+
+    let location = response.location.expect("Expected a redirect location");
+    assert_eq!(location.fragment(), Some("error=unauthorized_client"));
+}
+
+#[test]
+fn auth_excess_scope_rejected() {
+    let request = || CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("scope", "example default admin"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    for reject in [false, true] {
+        let mut setup = AuthorizationSetup::new();
+        let mut solicitor = Allow(EXAMPLE_OWNER_ID.to_string());
+        let mut authorization_flow = AuthorizationFlow::prepare(AuthorizationEndpoint::new(
+            &setup.registrar,
+            &mut setup.authorizer,
+            &mut solicitor,
+        ))
+        .unwrap();
+        if reject {
+            authorization_flow.reject_excess_scope();
+        }
+        let response = smol::block_on(authorization_flow.execute(request())).expect("Should not error");
+
+        let location = response.location.expect("Expected a redirect location");
+        let error = location
+            .query_pairs()
+            .find(|(key, _)| key == "error")
+            .map(|(_, value)| value.into_owned());
+        if reject {
+            assert_eq!(error.as_deref(), Some("invalid_scope"));
+        } else {
+            // The request is narrowed to the registered scope instead.
+            assert_eq!(error, None);
+        }
+    }
+}
+
+impl AuthorizationSetup {
+    /// Run a code request with the given solicitor, returning the error code of the redirect.
+    fn solicit<P>(&mut self, solicitor: &mut P, session: Option<&str>) -> Option<String>
+    where
+        P: OwnerSolicitor<CraftedRequest> + Send + Sync,
+    {
+        let mut query = vec![
+            ("response_type", "code"),
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+        ];
+        query.extend(session.map(|session| ("session", session)));
+        let request = CraftedRequest {
+            query: Some(query.iter().to_single_value_query()),
+            urlbody: None,
+            auth: None,
+        };
+
+        let mut authorization_flow = AuthorizationFlow::prepare(AuthorizationEndpoint::new(
+            &self.registrar,
+            &mut self.authorizer,
+            solicitor,
+        ))
+        .unwrap();
+        let response = smol::block_on(authorization_flow.execute(request)).expect("Should not error");
+        assert_eq!(response.status, Status::Redirect);
+
+        let location = response.location.expect("Expected a redirect location");
+        let pairs = location.query_pairs().collect::<HashMap<_, _>>();
+        match pairs.get("error") {
+            Some(error) => Some(error.to_string()),
+            None => {
+                assert!(pairs.contains_key("code"), "Expected a code in {}", location);
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn auth_async_fn_solicitor() {
+    let sessions: HashMap<String, String> = vec![("abc".to_string(), EXAMPLE_OWNER_ID.to_string())]
+        .into_iter()
+        .collect();
+    let mut solicitor = FnSolicitor(move |request: &mut CraftedRequest, _: Solicitation| {
+        let session = request
+            .query
+            .as_ref()
+            .and_then(|query| query.get("session"))
+            .and_then(|values| values.first().cloned());
+        let sessions = sessions.clone();
+        async move {
+            // Lookup in a session store that is not immediately ready.
+            RoundTrip(false).await;
+            match session.and_then(|session| sessions.get(&session).cloned()) {
+                Some(owner) => OwnerConsent::Authorized(owner),
+                None => OwnerConsent::Denied,
+            }
+        }
+    });
+
+    let mut setup = AuthorizationSetup::new();
+    assert_eq!(setup.solicit(&mut solicitor, Some("abc")), None);
+    assert_eq!(
+        setup.solicit(&mut solicitor, Some("unknown")).as_deref(),
+        Some("access_denied")
+    );
+    assert_eq!(
+        setup.solicit(&mut solicitor, None).as_deref(),
+        Some("access_denied")
+    );
+}
+
+#[test]
+fn auth_sync_solicitors() {
+    use oxide_auth::frontends::simple::endpoint::{self as simple, TieredSolicitor, Vacant};
+
+    let mut setup = AuthorizationSetup::new();
+    let mut allow = simple::FnSolicitor(|_: &mut CraftedRequest, _: Solicitation| {
+        OwnerConsent::Authorized(EXAMPLE_OWNER_ID.to_string())
+    });
+    assert_eq!(setup.solicit(&mut allow, None), None);
+    assert_eq!(setup.solicit(&mut Vacant, None).as_deref(), Some("access_denied"));
+
+    // The example client is not trusted as a first party.
+    let mut tiered = TieredSolicitor {
+        first_party: Vacant,
+        third_party: allow,
+    };
+    assert_eq!(setup.solicit(&mut tiered, None), None);
+
+    // Any other synchronous solicitor is adapted with `FromSync`.
+    struct AllowAll;
+
+    impl oxide_auth::endpoint::OwnerSolicitor<CraftedRequest> for AllowAll {
+        fn check_consent(
+            &mut self, _: &mut CraftedRequest, _: Solicitation,
+        ) -> OwnerConsent<CraftedResponse> {
+            OwnerConsent::Authorized(EXAMPLE_OWNER_ID.to_string())
+        }
+    }
+
+    assert_eq!(setup.solicit(&mut FromSync(AllowAll), None), None);
+}
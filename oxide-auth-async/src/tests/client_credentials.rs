@@ -1,7 +1,7 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
 use oxide_auth::primitives::authorizer::AuthMap;
-use oxide_auth::primitives::registrar::{Client, ClientMap, RegisteredUrl};
-use oxide_auth::primitives::issuer::TokenMap;
+use oxide_auth::primitives::registrar::{Client, ClientMap, GrantType, RegisteredUrl};
+use oxide_auth::primitives::issuer::{Issuer, TokenMap};
 use oxide_auth::{frontends::simple::endpoint::Error, endpoint::WebRequest};
 
 use crate::{
@@ -16,7 +16,7 @@ struct ClientCredentialsEndpoint<'a> {
     registrar: &'a ClientMap,
     authorizer: &'a mut AuthMap<TestGenerator>,
     issuer: &'a mut TokenMap<TestGenerator>,
-    solicitor: &'a mut (dyn OwnerSolicitor<CraftedRequest> + Send + Sync),
+    solicitor: Option<&'a mut (dyn OwnerSolicitor<CraftedRequest> + Send + Sync)>,
 }
 
 impl<'a> ClientCredentialsEndpoint<'a> {
@@ -29,7 +29,19 @@ impl<'a> ClientCredentialsEndpoint<'a> {
             registrar,
             authorizer,
             issuer,
-            solicitor,
+            solicitor: Some(solicitor),
+        }
+    }
+
+    fn without_solicitor(
+        registrar: &'a ClientMap, authorizer: &'a mut AuthMap<TestGenerator>,
+        issuer: &'a mut TokenMap<TestGenerator>,
+    ) -> Self {
+        Self {
+            registrar,
+            authorizer,
+            issuer,
+            solicitor: None,
         }
     }
 }
@@ -61,7 +73,10 @@ impl<'a> Endpoint<CraftedRequest> for ClientCredentialsEndpoint<'a> {
         Error::Web(err)
     }
     fn owner_solicitor(&mut self) -> Option<&mut (dyn OwnerSolicitor<CraftedRequest> + Send)> {
-        Some(self.solicitor)
+        match &mut self.solicitor {
+            Some(solicitor) => Some(&mut **solicitor),
+            None => None,
+        }
     }
 }
 
@@ -240,6 +255,31 @@ fn client_credentials_deny_public_client() {
     setup.test_bad_request(public_client, Deny);
 }
 
+#[test]
+fn client_credentials_deny_restricted_grant_type() {
+    let mut setup = ClientCredentialsSetup::new();
+    setup.registrar.register_client(
+        Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        )
+        .with_grant_types(vec![GrantType::AuthorizationCode]),
+    );
+    let restricted = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    setup.test_bad_request(restricted, Allow(EXAMPLE_CLIENT_ID.to_owned()));
+}
+
 #[test]
 fn client_credentials_deny_incorrect_credentials() {
     let mut setup = ClientCredentialsSetup::new();
@@ -400,3 +440,30 @@ fn client_credentials_request_error_malformed_scope() {
 
     setup.test_bad_request(malformed_scope, Allow(EXAMPLE_OWNER_ID.to_owned()));
 }
+
+#[test]
+fn client_credentials_without_solicitor() {
+    let mut setup = ClientCredentialsSetup::new();
+    let success = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    let mut flow = ClientCredentialsFlow::prepare(ClientCredentialsEndpoint::without_solicitor(
+        &setup.registrar,
+        &mut setup.authorizer,
+        &mut setup.issuer,
+    ))
+    .unwrap();
+    let response = smol::block_on(flow.execute(success)).expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    // The client is the owner of the grant.
+    let grant = setup.issuer.recover_token("AuthToken").unwrap().unwrap();
+    assert_eq!(grant.owner_id, EXAMPLE_CLIENT_ID);
+}
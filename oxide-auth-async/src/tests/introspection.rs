@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{Duration, Utc};
+use url::{form_urlencoded, Url};
+
+use super::defaults::*;
+use crate::introspection::{Introspection, IntrospectionClient};
+use crate::primitives::Issuer;
+
+const INTROSPECTION_URL: &str = "https://auth.example/introspect";
+const RESOURCE_ID: &str = "resource";
+const RESOURCE_SECRET: &str = "resource secret";
+
+/// Answers introspection requests with the stored responses, as a remote server would.
+#[derive(Clone, Default)]
+struct MockServer {
+    responses: Arc<Mutex<HashMap<String, String>>>,
+    calls: Arc<Mutex<usize>>,
+}
+
+impl MockServer {
+    fn answer(&self, token: &str, response: String) {
+        self.responses.lock().unwrap().insert(token.to_string(), response);
+    }
+
+    fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl IntrospectionClient for MockServer {
+    async fn post(&mut self, url: &Url, authorization: &str, body: &str) -> Result<String, ()> {
+        *self.calls.lock().unwrap() += 1;
+        assert_eq!(url.as_str(), INTROSPECTION_URL);
+
+        let credentials = STANDARD
+            .decode(authorization.strip_prefix("Basic ").unwrap())
+            .unwrap();
+        assert_eq!(credentials, b"resource:resource+secret");
+
+        let form: HashMap<_, _> = form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+        assert_eq!(
+            form.get("token_type_hint").map(String::as_str),
+            Some("access_token")
+        );
+        let token = form.get("token").ok_or(())?;
+
+        match self.responses.lock().unwrap().get(token) {
+            Some(response) => Ok(response.clone()),
+            None => Ok(r#"{"active":false}"#.to_string()),
+        }
+    }
+}
+
+fn active(exp: i64) -> String {
+    format!(
+        r#"{{"active":true,"sub":"{}","client_id":"{}","scope":"{}","exp":{}}}"#,
+        EXAMPLE_OWNER_ID, EXAMPLE_CLIENT_ID, EXAMPLE_SCOPE, exp
+    )
+}
+
+fn setup() -> (MockServer, Introspection<MockServer>) {
+    let server = MockServer::default();
+    let issuer = Introspection::new(
+        server.clone(),
+        INTROSPECTION_URL.parse().unwrap(),
+        RESOURCE_ID,
+        RESOURCE_SECRET,
+    );
+    (server, issuer)
+}
+
+#[test]
+fn introspection_active() {
+    let (server, mut issuer) = setup();
+    let exp = (Utc::now() + Duration::hours(1)).timestamp();
+    server.answer("token", active(exp));
+
+    let grant = smol::block_on(issuer.recover_token("token"))
+        .unwrap()
+        .expect("Should recover an active token");
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+    assert_eq!(grant.client_id, EXAMPLE_CLIENT_ID);
+    assert_eq!(grant.scope, EXAMPLE_SCOPE.parse().unwrap());
+    assert_eq!(grant.until.timestamp(), exp);
+
+    // The grant is cached for the lifetime of the token.
+    let cached = smol::block_on(issuer.recover_token("token")).unwrap();
+    assert_eq!(cached, Some(grant));
+    assert_eq!(server.calls(), 1);
+}
+
+#[test]
+fn introspection_inactive() {
+    let (server, mut issuer) = setup();
+    server.answer("revoked", r#"{"active":false}"#.to_string());
+
+    assert_eq!(smol::block_on(issuer.recover_token("revoked")), Ok(None));
+    assert_eq!(smol::block_on(issuer.recover_token("unknown")), Ok(None));
+
+    // Inactive tokens are asked for again, they could have been issued in the meantime.
+    assert_eq!(smol::block_on(issuer.recover_token("revoked")), Ok(None));
+    assert_eq!(server.calls(), 3);
+}
+
+#[test]
+fn introspection_cache_expiry() {
+    let (server, mut issuer) = setup();
+    let exp = (Utc::now() + Duration::seconds(2)).timestamp();
+    server.answer("token", active(exp));
+
+    assert!(smol::block_on(issuer.recover_token("token")).unwrap().is_some());
+    assert!(smol::block_on(issuer.recover_token("token")).unwrap().is_some());
+    assert_eq!(server.calls(), 1);
+
+    // After its expiry the token is introspected again, and the server may no longer know it.
+    std::thread::sleep(std::time::Duration::from_millis(2100));
+    server.answer("token", r#"{"active":false}"#.to_string());
+    assert_eq!(smol::block_on(issuer.recover_token("token")), Ok(None));
+    assert_eq!(server.calls(), 2);
+}
+
+#[test]
+fn introspection_default_lifetime() {
+    let (server, mut issuer) = setup();
+    issuer.default_lifetime(Duration::minutes(5));
+    server.answer(
+        "token",
+        format!(r#"{{"active":true,"client_id":"{}"}}"#, EXAMPLE_CLIENT_ID),
+    );
+
+    let grant = smol::block_on(issuer.recover_token("token"))
+        .unwrap()
+        .expect("Should recover an active token");
+    // Without an owner the token was issued to the client itself.
+    assert_eq!(grant.owner_id, EXAMPLE_CLIENT_ID);
+    assert!(grant.scope.iter().next().is_none());
+    assert!(grant.until > Utc::now() + Duration::minutes(4));
+}
+
+#[test]
+fn introspection_malformed() {
+    let (server, mut issuer) = setup();
+    server.answer("token", "not json".to_string());
+
+    assert_eq!(smol::block_on(issuer.recover_token("token")), Err(()));
+}
+
+#[test]
+fn introspection_issues_nothing() {
+    let (_, mut issuer) = setup();
+    assert!(smol::block_on(issuer.recover_refresh("token")).unwrap().is_none());
+}
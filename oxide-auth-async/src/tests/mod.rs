@@ -1,5 +1,8 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use oxide_auth::{
     primitives::generator::TagGrant,
@@ -41,6 +44,9 @@ struct CraftedResponse {
     ///
     /// One variant for each possible encoding type.
     pub body: Option<Body>,
+
+    /// Whether caches must not store the response.
+    pub no_store: bool,
 }
 
 /// An enum containing the necessary HTTP status codes.
@@ -58,6 +64,12 @@ enum Status {
 
     /// Http status code 401.
     Unauthorized,
+
+    /// Http status code 403.
+    Forbidden,
+
+    /// Http status code 500.
+    InternalServerError,
 }
 
 /// Models the necessary body contents.
@@ -134,6 +146,21 @@ impl WebResponse for CraftedResponse {
         Ok(())
     }
 
+    /// Set the response status to 403 and add a `WWW-Authenticate` header.
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.status = Status::Forbidden;
+        self.location = None;
+        self.www_authenticate = Some(header_value.to_owned());
+        Ok(())
+    }
+
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = Status::InternalServerError;
+        self.location = None;
+        self.www_authenticate = None;
+        Ok(())
+    }
+
     /// A pure text response with no special media type set.
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.body = Some(Body::Text(text.to_owned()));
@@ -145,6 +172,11 @@ impl WebResponse for CraftedResponse {
         self.body = Some(Body::Json(data.to_owned()));
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.no_store = true;
+        Ok(())
+    }
 }
 
 struct TestGenerator(String);
@@ -212,6 +244,23 @@ where
 
 fn assert_send<T: Send>(_val: &T) {}
 
+/// Returns `Pending` on its first poll, waking the task immediately.
+struct RoundTrip(bool);
+
+impl Future for RoundTrip {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
 pub mod defaults {
     pub const EXAMPLE_CLIENT_ID: &str = "ClientId";
     pub const EXAMPLE_OWNER_ID: &str = "Owner";
@@ -226,4 +275,7 @@ mod client_credentials;
 mod type_properties;
 mod resource;
 mod refresh;
+mod introspection;
+mod revocation;
+mod shared;
 // mod pkce;
@@ -155,6 +155,7 @@ impl RefreshTokenSetup {
         let response =
             smol::block_on(refresh_flow.execute(request)).expect("Expected non-failed reponse");
         assert_eq!(response.status, Status::Ok);
+        assert!(response.no_store, "Token responses must not be cached");
         let body = match response.body {
             Some(Body::Json(body)) => body,
             _ => panic!("Expect json body"),
@@ -166,6 +167,7 @@ impl RefreshTokenSetup {
             refresh: body.refresh_token,
             until: Utc::now() + Duration::seconds(duration),
             token_type: TokenType::Bearer,
+            refresh_until: None,
         }
     }
 
@@ -442,3 +444,39 @@ fn private_invalid_token() {
 
     setup.assert_invalid_grant(valid_private);
 }
+
+#[test]
+fn refresh_reports_refresh_expiry() {
+    let mut setup = RefreshTokenSetup::private_client();
+    setup.issuer.refresh_valid_for(Duration::days(30));
+
+    let valid_private = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &setup.refresh_token),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(setup.basic_authorization.clone()),
+    };
+
+    let mut refresh_flow =
+        RefreshFlow::prepare(RefreshTokenEndpoint::new(&setup.registrar, &mut setup.issuer)).unwrap();
+    refresh_flow.report_refresh_expiry(true);
+    let response =
+        smol::block_on(refresh_flow.execute(valid_private)).expect("Expected non-failed reponse");
+    assert_eq!(response.status, Status::Ok);
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        _ => panic!("Expect json body"),
+    };
+
+    let lifetime = Duration::days(30).num_seconds();
+    let expires_in = body
+        .refresh_token_expires_in
+        .expect("Expected refresh token lifetime");
+    assert!(expires_in <= lifetime && expires_in > lifetime - 5);
+}
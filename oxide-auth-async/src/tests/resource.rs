@@ -1,17 +1,55 @@
+use oxide_auth::primitives::binding::Binding;
 use oxide_auth::primitives::issuer::TokenMap;
 use oxide_auth::primitives::generator::RandomGenerator;
-use oxide_auth::primitives::grant::{Grant, Extensions};
-use oxide_auth::{frontends::simple::endpoint::Error, primitives::scope::Scope, endpoint::WebRequest};
+use oxide_auth::primitives::grant::{Grant, Extensions, Value};
+use oxide_auth::{
+    frontends::simple::endpoint::Error,
+    frontends::simple::extensions::{AddonList, AudienceAddon},
+    primitives::scope::Scope,
+    endpoint::{ProofVerifier, WebRequest},
+};
 
+use async_trait::async_trait;
 use chrono::{Utc, Duration};
 
-use super::CraftedRequest;
+use super::{CraftedRequest, Status};
 use super::defaults::*;
-use crate::endpoint::{resource::ResourceFlow, Endpoint};
+use crate::endpoint::{resource::ResourceFlow, Endpoint, Extension, SessionValidator};
 
 pub struct ResourceEndpoint<'a> {
     issuer: &'a mut TokenMap<RandomGenerator>,
     scopes: &'a mut [Scope],
+    sessions: Option<LoggedOut>,
+    proofs: Option<DpopKey>,
+    addons: Option<AddonList>,
+}
+
+/// Owners who logged out everywhere, as would be queried from a shared session store.
+struct LoggedOut(Vec<String>);
+
+#[async_trait]
+impl SessionValidator for LoggedOut {
+    async fn session_active(&mut self, grant: &Grant) -> Result<bool, ()> {
+        Ok(!self.0.contains(&grant.owner_id))
+    }
+}
+
+/// Accepts DPoP proofs of a single key, passed as the `dpop` query parameter.
+struct DpopKey(String);
+
+impl ProofVerifier<CraftedRequest> for DpopKey {
+    fn verify(&mut self, request: &mut CraftedRequest, _: &str, binding: &Binding) -> Result<bool, ()> {
+        let proof = request
+            .query
+            .as_ref()
+            .and_then(|query| query.get("dpop"))
+            .and_then(|values| values.first());
+
+        match binding {
+            Binding::Dpop { jkt } => Ok(proof == Some(jkt) && *jkt == self.0),
+            Binding::Certificate { .. } => Ok(false),
+        }
+    }
 }
 
 impl<'a> Endpoint<CraftedRequest> for ResourceEndpoint<'a> {
@@ -40,6 +78,15 @@ impl<'a> Endpoint<CraftedRequest> for ResourceEndpoint<'a> {
     fn scopes(&mut self) -> Option<&mut dyn oxide_auth::endpoint::Scopes<CraftedRequest>> {
         Some(&mut self.scopes)
     }
+    fn session_validator(&mut self) -> Option<&mut (dyn SessionValidator + Send)> {
+        self.sessions.as_mut().map(|sessions| sessions as _)
+    }
+    fn proof_verifier(&mut self) -> Option<&mut (dyn ProofVerifier<CraftedRequest> + Send)> {
+        self.proofs.as_mut().map(|proofs| proofs as _)
+    }
+    fn extension(&mut self) -> Option<&mut (dyn Extension + Send)> {
+        self.addons.as_mut().map(|addons| addons as _)
+    }
     fn owner_solicitor(
         &mut self,
     ) -> Option<&mut (dyn crate::endpoint::OwnerSolicitor<CraftedRequest> + Send)> {
@@ -49,7 +96,13 @@ impl<'a> Endpoint<CraftedRequest> for ResourceEndpoint<'a> {
 
 impl<'a> ResourceEndpoint<'a> {
     pub fn new(issuer: &'a mut TokenMap<RandomGenerator>, scopes: &'a mut [Scope]) -> Self {
-        Self { issuer, scopes }
+        Self {
+            issuer,
+            scopes,
+            sessions: None,
+            proofs: None,
+            addons: None,
+        }
     }
 }
 
@@ -210,3 +263,189 @@ fn resource_wrong_scope() {
 
     setup.test_access_error(wrong_scope);
 }
+
+#[test]
+fn resource_insufficient_scope_forbidden() {
+    let mut setup = ResourceSetup::new();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &setup.small_scope_token),
+    };
+
+    let endpoint = ResourceEndpoint::new(&mut setup.issuer, &mut setup.resource_scope);
+    let mut flow = ResourceFlow::prepare(endpoint).unwrap();
+    let response = match smol::block_on(flow.execute(request)) {
+        Err(Ok(response)) => response,
+        other => panic!("Expected an error response instead of {:?}", other),
+    };
+    assert_eq!(response.status, Status::Forbidden);
+    let authenticate = response.www_authenticate.expect("Should name the missing scope");
+    assert!(
+        authenticate.contains("error=\"insufficient_scope\""),
+        "{}",
+        authenticate
+    );
+}
+
+#[test]
+fn resource_session_revoked() {
+    let mut setup = ResourceSetup::new();
+    let auth = "Bearer ".to_string() + &setup.authtoken;
+    let request = || CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some(auth.clone()),
+    };
+
+    let mut endpoint = ResourceEndpoint::new(&mut setup.issuer, &mut setup.resource_scope);
+    endpoint.sessions = Some(LoggedOut(vec!["SomeoneElse".to_string()]));
+    let mut flow = ResourceFlow::prepare(endpoint).unwrap();
+    smol::block_on(flow.execute(request())).expect("Should allow access");
+
+    let mut endpoint = ResourceEndpoint::new(&mut setup.issuer, &mut setup.resource_scope);
+    endpoint.sessions = Some(LoggedOut(vec![EXAMPLE_OWNER_ID.to_string()]));
+    let mut flow = ResourceFlow::prepare(endpoint).unwrap();
+    let response = match smol::block_on(flow.execute(request())) {
+        Err(Ok(response)) => response,
+        other => panic!("Expected an error response instead of {:?}", other),
+    };
+    assert_eq!(response.status, Status::Unauthorized);
+    let authenticate = response.www_authenticate.expect("Should ask for authentication");
+    assert!(
+        authenticate.contains("error=\"invalid_token\""),
+        "{}",
+        authenticate
+    );
+}
+
+#[test]
+fn resource_dpop_bound() {
+    use crate::primitives::Issuer;
+
+    let mut setup = ResourceSetup::new();
+    let jkt = "0ZcOCORZNYy-DWpqq30jZyJGHTN0d2HglBV3uiguA4I";
+    let mut extensions = Extensions::new();
+    Binding::Dpop { jkt: jkt.to_string() }.attach(&mut extensions);
+    let token = smol::block_on(setup.issuer.issue(Grant {
+        client_id: EXAMPLE_CLIENT_ID.to_string(),
+        owner_id: EXAMPLE_OWNER_ID.to_string(),
+        redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+        scope: "legit needed".parse().unwrap(),
+        until: Utc::now() + Duration::hours(1),
+        extensions,
+    }))
+    .unwrap()
+    .token;
+
+    let request = |scheme: &str| CraftedRequest {
+        query: Some(
+            vec![("dpop".to_string(), vec![jkt.to_string()])]
+                .into_iter()
+                .collect(),
+        ),
+        urlbody: None,
+        auth: Some(format!("{} {}", scheme, token)),
+    };
+
+    let mut endpoint = ResourceEndpoint::new(&mut setup.issuer, &mut setup.resource_scope);
+    endpoint.proofs = Some(DpopKey(jkt.to_string()));
+    let mut flow = ResourceFlow::prepare(endpoint).unwrap();
+    smol::block_on(flow.execute(request("DPoP"))).expect("Should allow access");
+
+    let response = match smol::block_on(flow.execute(request("Bearer"))) {
+        Err(Ok(response)) => response,
+        other => panic!("Expected an error response instead of {:?}", other),
+    };
+    assert_eq!(response.status, Status::Unauthorized);
+    let authenticate = response.www_authenticate.expect("Should ask for authentication");
+    assert!(
+        authenticate.contains("error=\"invalid_token\""),
+        "{}",
+        authenticate
+    );
+
+    // Without a verifier the proof can not be checked.
+    setup.test_access_error(request("DPoP"));
+}
+
+#[test]
+fn resource_audience_restricted() {
+    use crate::primitives::Issuer;
+
+    let mut setup = ResourceSetup::new();
+    let audience = AudienceAddon::new(vec!["https://a.example"]);
+    let mut extensions = Extensions::new();
+    extensions.set(&audience, Value::public(Some("https://b.example".to_string())));
+    let foreign = smol::block_on(setup.issuer.issue(Grant {
+        client_id: EXAMPLE_CLIENT_ID.to_string(),
+        owner_id: EXAMPLE_OWNER_ID.to_string(),
+        redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+        scope: "legit needed".parse().unwrap(),
+        until: Utc::now() + Duration::hours(1),
+        extensions,
+    }))
+    .unwrap()
+    .token;
+
+    let request = |token: &str| CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some(format!("Bearer {}", token)),
+    };
+
+    let mut addons = AddonList::new();
+    addons.push_resource(audience);
+    let authtoken = setup.authtoken.clone();
+    let mut endpoint = ResourceEndpoint::new(&mut setup.issuer, &mut setup.resource_scope);
+    endpoint.addons = Some(addons);
+    let mut flow = ResourceFlow::prepare(endpoint).unwrap();
+
+    // Tokens without an audience are not restricted.
+    smol::block_on(flow.execute(request(&authtoken))).expect("Should allow access");
+
+    let response = match smol::block_on(flow.execute(request(&foreign))) {
+        Err(Ok(response)) => response,
+        other => panic!("Expected an error response instead of {:?}", other),
+    };
+    assert_eq!(response.status, Status::Unauthorized);
+    let authenticate = response.www_authenticate.expect("Should ask for authentication");
+    assert!(
+        authenticate.contains("error=\"invalid_token\""),
+        "{}",
+        authenticate
+    );
+
+    // Without the addon the audience is not checked.
+    setup.test_access_success(request(&foreign));
+}
+
+#[test]
+fn resource_touch_extends_token() {
+    use crate::primitives::{FromSync, Issuer};
+    use oxide_auth::primitives::issuer::TokenSigner;
+
+    let grant = || Grant {
+        client_id: EXAMPLE_CLIENT_ID.to_string(),
+        owner_id: EXAMPLE_OWNER_ID.to_string(),
+        redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+        scope: "legit".parse().unwrap(),
+        until: Utc::now() + Duration::minutes(10),
+        extensions: Extensions::new(),
+    };
+
+    let mut map = TokenMap::new(RandomGenerator::new(16));
+    map.sliding_expiry(Duration::hours(1));
+    let mut issuer = FromSync(map);
+    let token = smol::block_on(issuer.issue(grant())).unwrap().token;
+
+    let until = Utc::now() + Duration::minutes(30);
+    assert_eq!(smol::block_on(issuer.touch(&token, until)), Ok(until));
+    let recovered = smol::block_on(issuer.recover_token(&token)).unwrap().unwrap();
+    assert_eq!(recovered.until, until);
+
+    // Issuers that can not change their tokens keep failing.
+    let mut signer = TokenSigner::ephemeral();
+    let token = smol::block_on(Issuer::issue(&mut signer, grant())).unwrap().token;
+    assert!(smol::block_on(Issuer::touch(&mut signer, &token, until)).is_err());
+}
@@ -0,0 +1,290 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use oxide_auth::primitives::issuer::{IssuedToken, TokenMap};
+use oxide_auth::primitives::generator::RandomGenerator;
+use oxide_auth::primitives::grant::{Grant, Extensions};
+use oxide_auth::{
+    code_grant::resource::TokenInfo,
+    endpoint::WebRequest,
+    primitives::registrar::{Client, ClientMap, RegisteredUrl},
+    frontends::simple::endpoint::Error,
+};
+
+use crate::{
+    endpoint::{introspection::IntrospectionFlow, revocation::RevocationFlow, Endpoint},
+    primitives::Issuer,
+};
+
+use std::collections::HashMap;
+
+use chrono::{Utc, Duration};
+
+use super::{Body, CraftedRequest, CraftedResponse, Status, ToSingleValueQuery};
+use super::defaults::*;
+
+struct TokenEndpoint<'a> {
+    registrar: &'a ClientMap,
+    issuer: &'a mut TokenMap<RandomGenerator>,
+}
+
+impl<'a> Endpoint<CraftedRequest> for TokenEndpoint<'a> {
+    type Error = Error<CraftedRequest>;
+
+    fn registrar(&self) -> Option<&(dyn crate::primitives::Registrar + Sync)> {
+        Some(self.registrar)
+    }
+    fn authorizer_mut(&mut self) -> Option<&mut (dyn crate::primitives::Authorizer + Send)> {
+        None
+    }
+    fn issuer_mut(&mut self) -> Option<&mut (dyn crate::primitives::Issuer + Send)> {
+        Some(self.issuer)
+    }
+    fn response(
+        &mut self, _: &mut CraftedRequest, _: oxide_auth::endpoint::Template,
+    ) -> Result<<CraftedRequest as WebRequest>::Response, Self::Error> {
+        Ok(Default::default())
+    }
+    fn error(&mut self, _err: oxide_auth::endpoint::OAuthError) -> Self::Error {
+        unimplemented!()
+    }
+    fn web_error(&mut self, _err: <CraftedRequest as WebRequest>::Error) -> Self::Error {
+        unimplemented!()
+    }
+    fn scopes(&mut self) -> Option<&mut dyn oxide_auth::endpoint::Scopes<CraftedRequest>> {
+        None
+    }
+    fn owner_solicitor(
+        &mut self,
+    ) -> Option<&mut (dyn crate::endpoint::OwnerSolicitor<CraftedRequest> + Send)> {
+        None
+    }
+}
+
+struct RevocationSetup {
+    registrar: ClientMap,
+    issuer: TokenMap<RandomGenerator>,
+    issued: IssuedToken,
+    basic_authorization: String,
+}
+
+impl RevocationSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        let mut issuer = TokenMap::new(RandomGenerator::new(16));
+
+        registrar.register_client(Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        ));
+        registrar.register_client(Client::public(
+            "OtherClient",
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        let grant = Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions: Extensions::new(),
+        };
+        let issued = smol::block_on(issuer.issue(grant)).unwrap();
+
+        let basic_authorization =
+            STANDARD.encode(format!("{}:{}", EXAMPLE_CLIENT_ID, EXAMPLE_PASSPHRASE));
+        let basic_authorization = format!("Basic {}", basic_authorization);
+
+        RevocationSetup {
+            registrar,
+            issuer,
+            issued,
+            basic_authorization,
+        }
+    }
+
+    fn request(&self, token: &str) -> CraftedRequest {
+        CraftedRequest {
+            query: None,
+            urlbody: Some([("token", token)].iter().to_single_value_query()),
+            auth: Some(self.basic_authorization.clone()),
+        }
+    }
+
+    fn endpoint(&mut self) -> TokenEndpoint<'_> {
+        TokenEndpoint {
+            registrar: &self.registrar,
+            issuer: &mut self.issuer,
+        }
+    }
+
+    fn introspect(&mut self, request: CraftedRequest) -> TokenInfo {
+        let mut flow = IntrospectionFlow::prepare(self.endpoint()).unwrap();
+        let response = smol::block_on(flow.execute(request)).expect("Expected non-failed reponse");
+        assert_eq!(response.status, Status::Ok);
+        assert!(response.no_store, "Introspection responses must not be cached");
+        match response.body {
+            Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+            _ => panic!("Expect json body"),
+        }
+    }
+
+    fn revoke(&mut self, request: CraftedRequest) -> CraftedResponse {
+        let mut flow = RevocationFlow::prepare(self.endpoint()).unwrap();
+        smol::block_on(flow.execute(request)).expect("Expected non-failed reponse")
+    }
+
+    fn assert_error(response: &CraftedResponse, status: Status, error: &str) {
+        assert_eq!(response.status, status);
+        let body: HashMap<String, String> = match &response.body {
+            Some(Body::Json(body)) => serde_json::from_str(body).expect("Expected valid json body"),
+            _ => panic!("Expect json body"),
+        };
+        assert_eq!(body.get("error").map(String::as_str), Some(error));
+    }
+}
+
+#[test]
+fn introspect_active_token() {
+    let mut setup = RevocationSetup::new();
+    let request = setup.request(&setup.issued.token);
+    let info = setup.introspect(request);
+
+    assert!(info.active);
+    assert_eq!(info.sub.as_deref(), Some(EXAMPLE_OWNER_ID));
+    assert_eq!(info.client_id.as_deref(), Some(EXAMPLE_CLIENT_ID));
+}
+
+#[test]
+fn introspect_unknown_token() {
+    let mut setup = RevocationSetup::new();
+    let info = setup.introspect(setup.request("NotAToken"));
+
+    assert_eq!(info, TokenInfo::inactive());
+}
+
+#[test]
+fn introspect_foreign_token_as_public_client() {
+    let mut setup = RevocationSetup::new();
+    let token = setup.issued.token.clone();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [("token", token.as_str()), ("client_id", "OtherClient")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    assert_eq!(setup.introspect(request), TokenInfo::inactive());
+    assert!(setup.introspect(setup.request(&token)).active);
+}
+
+#[test]
+fn revoke_access_token() {
+    let mut setup = RevocationSetup::new();
+    let token = setup.issued.token.clone();
+
+    let response = setup.revoke(setup.request(&token));
+    assert_eq!(response.status, Status::Ok);
+    assert!(response.no_store);
+
+    assert_eq!(setup.introspect(setup.request(&token)), TokenInfo::inactive());
+}
+
+#[test]
+fn revoke_refresh_token() {
+    let mut setup = RevocationSetup::new();
+    let token = setup.issued.token.clone();
+    let refresh = setup.issued.refresh.clone().unwrap();
+
+    let mut request = setup.request(&refresh);
+    request
+        .urlbody
+        .as_mut()
+        .unwrap()
+        .insert("token_type_hint".to_string(), vec!["refresh_token".to_string()]);
+    assert_eq!(setup.revoke(request).status, Status::Ok);
+
+    assert_eq!(smol::block_on(setup.issuer.recover_refresh(&refresh)), Ok(None));
+    assert_eq!(setup.introspect(setup.request(&token)), TokenInfo::inactive());
+}
+
+#[test]
+fn revoke_unknown_token() {
+    let mut setup = RevocationSetup::new();
+    let response = setup.revoke(setup.request("NotAToken"));
+
+    assert_eq!(response.status, Status::Ok);
+}
+
+#[test]
+fn revoke_foreign_token() {
+    let mut setup = RevocationSetup::new();
+    let token = setup.issued.token.clone();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [("token", token.as_str()), ("client_id", "OtherClient")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    let response = setup.revoke(request);
+    RevocationSetup::assert_error(&response, Status::BadRequest, "unauthorized_client");
+    assert!(setup.introspect(setup.request(&token)).active);
+}
+
+#[test]
+fn unauthenticated_rejected() {
+    let mut setup = RevocationSetup::new();
+    let mut request = setup.request(&setup.issued.token);
+    request.auth = None;
+
+    let response = setup.revoke(request.clone());
+    RevocationSetup::assert_error(&response, Status::Unauthorized, "invalid_client");
+    assert!(response.www_authenticate.is_some());
+
+    let mut flow = IntrospectionFlow::prepare(setup.endpoint()).unwrap();
+    let response = smol::block_on(flow.execute(request)).unwrap();
+    RevocationSetup::assert_error(&response, Status::Unauthorized, "invalid_client");
+}
+
+#[test]
+fn wrong_passphrase_rejected() {
+    let mut setup = RevocationSetup::new();
+    let mut request = setup.request(&setup.issued.token);
+    let wrong = STANDARD.encode(format!("{}:{}", EXAMPLE_CLIENT_ID, "wrong"));
+    request.auth = Some(format!("Basic {}", wrong));
+
+    let response = setup.revoke(request);
+    RevocationSetup::assert_error(&response, Status::Unauthorized, "invalid_client");
+    assert!(setup.introspect(setup.request(&setup.issued.token)).active);
+}
+
+#[test]
+fn missing_token_rejected() {
+    let mut setup = RevocationSetup::new();
+    let mut request = setup.request("");
+    request.urlbody = Some(HashMap::new());
+
+    let response = setup.revoke(request);
+    RevocationSetup::assert_error(&response, Status::BadRequest, "invalid_request");
+}
+
+#[test]
+fn assert_send() {
+    let mut setup = RevocationSetup::new();
+    {
+        let mut flow = RevocationFlow::prepare(setup.endpoint()).unwrap();
+        super::assert_send(&flow.execute(CraftedRequest::default()));
+    }
+
+    let mut flow = IntrospectionFlow::prepare(setup.endpoint()).unwrap();
+    super::assert_send(&flow.execute(CraftedRequest::default()));
+}
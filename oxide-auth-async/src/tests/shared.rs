@@ -0,0 +1,23 @@
+use std::thread;
+
+use oxide_auth::endpoint::OAuthError;
+
+use crate::frontends::shared::SharedEndpoint;
+
+#[test]
+fn shared_between_clones() {
+    let endpoint = SharedEndpoint::new(0u32);
+    let other = endpoint.clone();
+    other.with(|count| *count += 1).unwrap();
+
+    assert_eq!(endpoint.with(|count| *count).unwrap(), 1);
+}
+
+#[test]
+fn poisoned_lock() {
+    let endpoint = SharedEndpoint::new(());
+    let poison = endpoint.clone();
+    let _ = thread::spawn(move || poison.with(|_| panic!("poison the lock"))).join();
+
+    assert!(matches!(endpoint.with(|_| ()), Err(OAuthError::PrimitiveError)));
+}
@@ -1,6 +1,9 @@
 use crate::OAuthRequest;
 use axum::{
-    http::{header::InvalidHeaderValue, StatusCode},
+    http::{
+        header::{self, InvalidHeaderValue, HeaderValue},
+        StatusCode,
+    },
     response::{IntoResponse, Response},
 };
 use oxide_auth::frontends::{dev::OAuthError, simple::endpoint::Error};
@@ -59,9 +62,38 @@ impl std::error::Error for WebError {
     }
 }
 
+impl WebError {
+    /// The status code of the response to this error
+    ///
+    /// Malformed requests are answered with a client error, only failures of the server itself
+    /// are internal server errors. Transient failures are answered with service unavailable.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            WebError::Endpoint(OAuthError::DenySilently) => StatusCode::BAD_REQUEST,
+            WebError::Endpoint(OAuthError::BadRequest) => StatusCode::BAD_REQUEST,
+            WebError::Endpoint(OAuthError::PrimitiveError) => StatusCode::INTERNAL_SERVER_ERROR,
+            WebError::Endpoint(OAuthError::TemporarilyUnavailable(_)) => StatusCode::SERVICE_UNAVAILABLE,
+            WebError::Header(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            WebError::Encoding => StatusCode::BAD_REQUEST,
+            WebError::Form => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            WebError::Query => StatusCode::BAD_REQUEST,
+            WebError::Body => StatusCode::BAD_REQUEST,
+            WebError::Authorization => StatusCode::BAD_REQUEST,
+            WebError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl IntoResponse for WebError {
     fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+        let status = self.status_code();
+        let mut response = (status, self.to_string()).into_response();
+        if let WebError::Endpoint(OAuthError::TemporarilyUnavailable(retry_after)) = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        response
     }
 }
 
@@ -85,3 +117,51 @@ impl From<InvalidHeaderValue> for WebError {
         Self::Header(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn status(error: WebError) -> StatusCode {
+        error.into_response().status()
+    }
+
+    #[test]
+    fn status_codes() {
+        let invalid_header = HeaderValue::from_str("\n").unwrap_err();
+
+        assert_eq!(status(OAuthError::DenySilently.into()), StatusCode::BAD_REQUEST);
+        assert_eq!(status(OAuthError::BadRequest.into()), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            status(OAuthError::PrimitiveError.into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(status(invalid_header.into()), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(status(WebError::Encoding), StatusCode::BAD_REQUEST);
+        assert_eq!(status(WebError::Form), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(status(WebError::Query), StatusCode::BAD_REQUEST);
+        assert_eq!(status(WebError::Body), StatusCode::BAD_REQUEST);
+        assert_eq!(status(WebError::Authorization), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            status(WebError::InternalError(None)),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn temporarily_unavailable() {
+        let error = OAuthError::TemporarilyUnavailable(Duration::from_secs(30));
+        let response = WebError::from(error).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        // A malformed request, not a missing authentication, so no challenge is sent.
+        let response = WebError::Authorization.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().get(header::WWW_AUTHENTICATE).is_none());
+    }
+}
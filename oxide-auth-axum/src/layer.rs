@@ -0,0 +1,212 @@
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use axum::{
+    http::Request,
+    response::{IntoResponse, Response},
+};
+use oxide_auth::frontends::simple::endpoint::resource_flow;
+use oxide_auth::primitives::{issuer::Issuer, scope::Scope};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{OAuthResource, WebError};
+
+/// A layer guarding all routes of the inner service with the resource flow
+///
+/// The bearer token of each request is checked against the issuer. If it grants any one of the
+/// required scopes, the recovered [`Grant`] is inserted into the request extensions, where handlers
+/// can find it with the `Extension<Grant>` extractor. Other requests are answered by the flow,
+/// usually with `401 Unauthorized`, and never reach the inner service. The request body is not
+/// touched in either case.
+///
+/// [`Grant`]: oxide_auth::primitives::grant::Grant
+pub struct ResourceLayer<I> {
+    issuer: Arc<Mutex<I>>,
+    scopes: Arc<[Scope]>,
+}
+
+/// The service created by a [ResourceLayer]
+pub struct ResourceService<S, I> {
+    inner: S,
+    issuer: Arc<Mutex<I>>,
+    scopes: Arc<[Scope]>,
+}
+
+impl<I> ResourceLayer<I> {
+    /// Guard with tokens of an issuer, which is usually shared with the token endpoint
+    pub fn new(issuer: Arc<Mutex<I>>, scopes: Vec<Scope>) -> Self {
+        ResourceLayer {
+            issuer,
+            scopes: scopes.into(),
+        }
+    }
+}
+
+impl<I> Clone for ResourceLayer<I> {
+    fn clone(&self) -> Self {
+        ResourceLayer {
+            issuer: self.issuer.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<S, I> Layer<S> for ResourceLayer<I> {
+    type Service = ResourceService<S, I>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResourceService {
+            inner,
+            issuer: self.issuer.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<S: Clone, I> Clone for ResourceService<S, I> {
+    fn clone(&self) -> Self {
+        ResourceService {
+            inner: self.inner.clone(),
+            issuer: self.issuer.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<S, I: Issuer> ResourceService<S, I> {
+    /// Run the resource flow, returning the response to any denied request.
+    ///
+    /// The grant of an accepted request is inserted into its extensions.
+    fn protect<B>(&self, request: &mut Request<B>) -> Option<Response> {
+        let resource = match OAuthResource::from_headers(request.headers()) {
+            Ok(resource) => resource,
+            Err(error) => return Some(error.into_response()),
+        };
+        let mut issuer = match self.issuer.lock() {
+            Ok(issuer) => issuer,
+            Err(_) => {
+                let error = WebError::InternalError(Some("issuer lock poisoned".to_owned()));
+                return Some(error.into_response());
+            }
+        };
+
+        match resource_flow(&mut *issuer, &self.scopes).execute(resource.into_request()) {
+            Ok(grant) => {
+                request.extensions_mut().insert(grant);
+                None
+            }
+            Err(Ok(response)) => Some(response.into_response()),
+            Err(Err(error)) => Some(WebError::from(error).into_response()),
+        }
+    }
+}
+
+impl<S, I, B> Service<Request<B>> for ResourceService<S, I>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    I: Issuer,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        match self.protect(&mut request) {
+            None => Box::pin(self.inner.call(request)),
+            Some(response) => Box::pin(future::ready(Ok(response))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::post, Extension, Router};
+    use chrono::{Duration, Utc};
+    use oxide_auth::primitives::generator::RandomGenerator;
+    use oxide_auth::primitives::grant::{Extensions, Grant};
+    use oxide_auth::primitives::issuer::TokenMap;
+    use tower::ServiceExt;
+
+    fn setup() -> (Router, String) {
+        let mut issuer = TokenMap::new(RandomGenerator::new(16));
+        let token = issuer
+            .issue(Grant {
+                owner_id: "Owner".to_owned(),
+                client_id: "Client".to_owned(),
+                scope: "read".parse().unwrap(),
+                redirect_uri: "https://client.example/endpoint".parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap()
+            .token;
+
+        let layer = ResourceLayer::new(Arc::new(Mutex::new(issuer)), vec!["read".parse().unwrap()]);
+        let router = Router::new()
+            .route(
+                "/",
+                post(|Extension(grant): Extension<Grant>, body: String| async move {
+                    format!("{}: {}", grant.owner_id, body)
+                }),
+            )
+            .layer(layer);
+        (router, token)
+    }
+
+    fn request(auth: &str) -> Request<Body> {
+        Request::post("/")
+            .header("Authorization", auth)
+            .body(Body::from("payload"))
+            .unwrap()
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn valid_token_reaches_service() {
+        let (router, token) = setup();
+        let response = router
+            .oneshot(request(&format!("Bearer {}", token)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "Owner: payload");
+    }
+
+    #[tokio::test]
+    async fn invalid_token_rejected() {
+        let (router, _) = setup();
+        let response = router.oneshot(request("Bearer invalid")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response
+            .headers()
+            .get("WWW-Authenticate")
+            .is_some_and(|value| value.to_str().unwrap().starts_with("Bearer")));
+    }
+
+    #[tokio::test]
+    async fn missing_token_rejected() {
+        let (router, _) = setup();
+        let request = Request::post("/").body(Body::from("payload")).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -4,8 +4,14 @@
 mod error;
 pub use error::WebError;
 
+mod layer;
+pub use layer::{ResourceLayer, ResourceService};
+
 mod request;
 pub use request::{OAuthResource, OAuthRequest};
 
 mod response;
 pub use response::OAuthResponse;
+
+mod shared;
+pub use shared::SharedEndpoint;
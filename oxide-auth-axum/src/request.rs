@@ -1,7 +1,10 @@
+use oxide_auth::endpoint::cookie_value;
 use oxide_auth::frontends::dev::{NormalizedParameter, QueryParameter, WebRequest};
 use axum::{
+    body::{Body, Bytes},
     extract::{Query, Form, FromRequest, FromRequestParts, Request},
-    http::{header, request::Parts},
+    RequestExt,
+    http::{header, request::Parts, HeaderMap},
 };
 use crate::{OAuthResponse, WebError};
 use std::borrow::Cow;
@@ -9,20 +12,26 @@ use std::borrow::Cow;
 #[derive(Clone, Debug, Default)]
 /// Type implementing `WebRequest` as well as `FromRequest` for use in route handlers
 ///
-/// This type consumes the body of the Request upon extraction, so be careful not to use it in
-/// places you also expect an application payload
+/// This type consumes the body of the Request upon extraction, buffering it as an urlencoded form,
+/// so be careful not to use it in places you also expect an application payload. Use
+/// [OAuthResource] to only extract the authorization header. The unparsed body is kept as well and
+/// available to extensions through `WebRequest::raw_body`.
 pub struct OAuthRequest {
     auth: Option<String>,
+    headers: HeaderMap,
     query: Option<NormalizedParameter>,
     body: Option<NormalizedParameter>,
+    raw_body: Option<Bytes>,
 }
 
 /// Type implementing `WebRequest` as well as `FromRequest` for use in guarding resources
 ///
 /// This is useful over [OAuthRequest] since [OAuthResource] doesn't consume the body of the
-/// request upon extraction
+/// request upon extraction. It implements `FromRequestParts` and can therefore precede any other
+/// extractor, including one reading the body.
 pub struct OAuthResource {
     auth: Option<String>,
+    headers: HeaderMap,
 }
 
 impl OAuthRequest {
@@ -49,10 +58,7 @@ impl OAuthRequest {
 
 impl From<OAuthResource> for OAuthRequest {
     fn from(r: OAuthResource) -> OAuthRequest {
-        OAuthRequest {
-            auth: r.auth,
-            ..Default::default()
-        }
+        r.into_request()
     }
 }
 
@@ -77,6 +83,35 @@ impl WebRequest for OAuthRequest {
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
         Ok(self.auth.as_deref().map(Cow::Borrowed))
     }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        for value in self.headers.get_all(header::COOKIE) {
+            let value = value.to_str().map_err(|_| WebError::Encoding)?;
+            if let Some(value) = cookie_value(value, name) {
+                return Ok(Some(Cow::Borrowed(value)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        let mut all = self.headers.get_all(name).iter();
+        let value = match (all.next(), all.next()) {
+            (None, _) => return Ok(None),
+            (Some(value), None) => value,
+            (Some(_), Some(_)) => return Err(WebError::Encoding),
+        };
+
+        value
+            .to_str()
+            .map(|value| Some(Cow::Borrowed(value)))
+            .map_err(|_| WebError::Encoding)
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
+    }
 }
 
 impl<S> FromRequest<S> for OAuthRequest
@@ -95,19 +130,33 @@ where
             optional.and_then(|hv| hv.to_str().ok().map(str::to_owned))
         };
 
-        let (mut parts, body) = req.into_parts();
+        let headers = req.headers().clone();
+        let (mut parts, body) = req.with_limited_body().into_parts();
         let query = Query::from_request_parts(&mut parts, state)
             .await
             .ok()
             .map(|q: Query<NormalizedParameter>| q.0);
 
-        let req = Request::from_parts(parts, body);
-        let body = Form::from_request(req, state)
-            .await
-            .ok()
-            .map(|b: Form<NormalizedParameter>| b.0);
+        // The body was limited above, buffer it once for both the form and extensions.
+        let (raw_body, body) = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(bytes) => {
+                let req = Request::from_parts(parts, Body::from(bytes.clone()));
+                let body = Form::from_request(req, state)
+                    .await
+                    .ok()
+                    .map(|b: Form<NormalizedParameter>| b.0);
+                (Some(bytes), body)
+            }
+            Err(_) => (None, None),
+        };
 
-        Ok(Self { auth, query, body })
+        Ok(Self {
+            auth,
+            headers,
+            query,
+            body,
+            raw_body,
+        })
     }
 }
 
@@ -118,7 +167,14 @@ where
     type Rejection = WebError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let mut all_auth = parts.headers.get_all(header::AUTHORIZATION).iter();
+        Self::from_headers(&parts.headers)
+    }
+}
+
+impl OAuthResource {
+    /// Extract the authorization header, rejecting requests with more than one.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Result<Self, WebError> {
+        let mut all_auth = headers.get_all(header::AUTHORIZATION).iter();
         let optional = all_auth.next();
 
         let auth = if all_auth.next().is_some() {
@@ -127,13 +183,125 @@ where
             optional.and_then(|hv| hv.to_str().ok().map(str::to_owned))
         };
 
-        Ok(Self { auth })
+        Ok(Self {
+            auth,
+            headers: headers.clone(),
+        })
     }
-}
 
-impl OAuthResource {
     /// Fetch the authorization header from the request
     pub fn authorization_header(&self) -> Option<&str> {
         self.auth.as_deref()
     }
+
+    /// Turn this OAuthResource into an OAuthRequest for processing
+    pub fn into_request(self) -> OAuthRequest {
+        OAuthRequest {
+            query: None,
+            body: None,
+            raw_body: None,
+            auth: self.auth,
+            headers: self.headers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Router};
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "Bearer token";
+    const PAYLOAD: &str = "application=payload";
+
+    async fn guarded(resource: OAuthResource, body: String) -> String {
+        let request = resource.into_request();
+        assert_eq!(request.authorization_header(), Some(TOKEN));
+        assert!(request.body().is_none());
+        body
+    }
+
+    #[tokio::test]
+    async fn resource_leaves_body() {
+        let app = Router::new().route("/", post(guarded));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::AUTHORIZATION, TOKEN)
+            .body(Body::from(PAYLOAD))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], PAYLOAD.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn request_buffers_body() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/?client_id=example")
+            .header(header::AUTHORIZATION, TOKEN)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("grant_type=refresh_token"))
+            .unwrap();
+
+        let request = OAuthRequest::from_request(request, &()).await.unwrap();
+        assert_eq!(request.authorization_header(), Some(TOKEN));
+        let query = request.query().unwrap();
+        assert_eq!(query.unique_value("client_id").as_deref(), Some("example"));
+        let body = request.body().unwrap();
+        assert_eq!(body.unique_value("grant_type").as_deref(), Some("refresh_token"));
+        assert_eq!(request.raw_body(), Some(&b"grant_type=refresh_token"[..]));
+    }
+
+    #[tokio::test]
+    async fn request_custom_header() {
+        let request = Request::builder()
+            .uri("/")
+            .header("X-Custom", "value")
+            .header("X-Twice", "first")
+            .header("X-Twice", "second")
+            .body(Body::empty())
+            .unwrap();
+
+        let mut request = OAuthRequest::from_request(request, &()).await.unwrap();
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        assert!(request.header("X-Twice").is_err());
+    }
+
+    #[tokio::test]
+    async fn request_raw_json_body() {
+        const JSON: &str = r#"{"grant_type":"urn:example:json"}"#;
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(JSON))
+            .unwrap();
+
+        let request = OAuthRequest::from_request(request, &()).await.unwrap();
+        assert!(request.body().is_none());
+        assert_eq!(request.raw_body(), Some(JSON.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn request_cookie() {
+        let request = Request::builder()
+            .uri("/")
+            .header(header::COOKIE, "theme=dark")
+            .header(header::COOKIE, "session=abc; token=xyz")
+            .body(Body::empty())
+            .unwrap();
+
+        let mut request = OAuthRequest::from_request(request, &()).await.unwrap();
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
+    }
 }
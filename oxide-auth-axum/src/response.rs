@@ -7,6 +7,7 @@ use axum::{
     },
 };
 use oxide_auth::frontends::dev::{WebResponse, Url};
+use std::time::Duration;
 
 #[derive(Default, Clone, Debug)]
 /// Type implementing `WebResponse` and `IntoResponse` for use in route handlers
@@ -50,12 +51,51 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::INTERNAL_SERVER_ERROR;
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::TOO_MANY_REQUESTS;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(header::RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::SERVICE_UNAVAILABLE;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(header::RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
     fn unauthorized(&mut self, kind: &str) -> Result<(), Self::Error> {
         self.status = StatusCode::UNAUTHORIZED;
         self.headers.insert(header::WWW_AUTHENTICATE, kind.try_into()?);
         Ok(())
     }
 
+    fn forbidden(&mut self, kind: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::FORBIDDEN;
+        self.headers.insert(header::WWW_AUTHENTICATE, kind.try_into()?);
+        Ok(())
+    }
+
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.body = Some(text.to_owned());
         self.headers
@@ -69,6 +109,14 @@ impl WebResponse for OAuthResponse {
             .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.headers
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        self.headers
+            .insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+        Ok(())
+    }
 }
 
 impl IntoResponse for OAuthResponse {
@@ -76,3 +124,29 @@ impl IntoResponse for OAuthResponse {
         (self.status, self.headers, self.body.unwrap_or_default()).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_store_headers() {
+        let mut response = OAuthResponse::default();
+        response.body_json("{}").unwrap();
+        response.no_store().unwrap();
+
+        let response = response.into_response();
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        assert_eq!(response.headers()[header::PRAGMA], "no-cache");
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = OAuthResponse::default();
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        let response = response.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    }
+}
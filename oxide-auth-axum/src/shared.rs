@@ -0,0 +1,226 @@
+use std::sync::{Arc, Mutex};
+
+use axum::response::{IntoResponse, Response};
+use oxide_auth::endpoint::{AccessTokenFlow, Endpoint, RefreshFlow, ResourceFlow};
+use oxide_auth::primitives::grant::Grant;
+use oxide_auth_async::frontends::shared;
+
+use crate::{OAuthRequest, OAuthResource, OAuthResponse, WebError};
+
+/// An endpoint shared between handlers that keeps their futures `Send`
+///
+/// Locking a `std::sync::Mutex` in a handler and keeping the guard alive across an `.await` makes
+/// the future of the handler non-`Send`, and axum then refuses it as a handler. This wraps the
+/// `SharedEndpoint` of `oxide-auth-async`, which only hands out the endpoint within a synchronous
+/// closure, and runs the flows with axum requests and responses.
+///
+/// The wrapper is cheap to clone and meant to be used as the state of the router.
+///
+/// ```
+/// use axum::{extract::State, response::Response, routing::get, Router};
+/// use oxide_auth::frontends::simple::endpoint::{Generic, Vacant};
+/// use oxide_auth::primitives::prelude::*;
+/// use oxide_auth_axum::{OAuthResource, SharedEndpoint};
+///
+/// type Endpoint = Generic<Vacant, Vacant, TokenMap<RandomGenerator>, Vacant, Vec<Scope>>;
+///
+/// async fn load_profile(owner: &str) -> String {
+///     format!("profile of {}", owner)
+/// }
+///
+/// async fn profile(
+///     State(endpoint): State<SharedEndpoint<Endpoint>>, resource: OAuthResource,
+/// ) -> Result<String, Response> {
+///     // The lock is released again when the flow returns.
+///     let grant = endpoint.resource(resource)?;
+///     Ok(load_profile(&grant.owner_id).await)
+/// }
+///
+/// let endpoint = Generic {
+///     registrar: Vacant,
+///     authorizer: Vacant,
+///     issuer: TokenMap::new(RandomGenerator::new(16)),
+///     solicitor: Vacant,
+///     scopes: vec!["profile".parse().unwrap()],
+///     response: Vacant,
+/// };
+/// let app: Router = Router::new()
+///     .route("/profile", get(profile))
+///     .with_state(SharedEndpoint::new(endpoint));
+/// ```
+pub struct SharedEndpoint<E> {
+    inner: shared::SharedEndpoint<E>,
+}
+
+impl<E> SharedEndpoint<E> {
+    /// Share an endpoint between handlers
+    pub fn new(endpoint: E) -> Self {
+        SharedEndpoint {
+            inner: shared::SharedEndpoint::new(endpoint),
+        }
+    }
+
+    /// Run a closure with exclusive access to the endpoint
+    ///
+    /// The lock is only held while the closure runs. Fails if another user of the endpoint
+    /// panicked while holding the lock.
+    pub fn with<R>(&self, f: impl FnOnce(&mut E) -> R) -> Result<R, WebError> {
+        Ok(self.inner.with(f)?)
+    }
+}
+
+impl<E> SharedEndpoint<E>
+where
+    E: Endpoint<OAuthRequest>,
+    WebError: From<E::Error>,
+{
+    /// Run the resource flow, returning the grant of the bearer token
+    ///
+    /// Denied requests are answered with the response of the flow. The error is a complete
+    /// response, so that handlers can return it with `?`.
+    #[allow(clippy::result_large_err)]
+    pub fn resource(&self, resource: OAuthResource) -> Result<Grant, Response> {
+        self.with(|endpoint| {
+            let mut flow = ResourceFlow::prepare(endpoint)
+                .map_err(|error| WebError::from(error).into_response())?;
+            flow.execute(resource.into_request())
+                .map_err(|error| match error {
+                    Ok(response) => response.into_response(),
+                    Err(error) => WebError::from(error).into_response(),
+                })
+        })
+        .map_err(IntoResponse::into_response)?
+    }
+
+    /// Run the access token flow
+    pub fn access_token(&self, request: OAuthRequest) -> Result<OAuthResponse, WebError> {
+        self.with(|endpoint| {
+            AccessTokenFlow::prepare(endpoint)?
+                .execute(request)
+                .map_err(WebError::from)
+        })?
+    }
+
+    /// Run the refresh flow
+    pub fn refresh(&self, request: OAuthRequest) -> Result<OAuthResponse, WebError> {
+        self.with(|endpoint| {
+            RefreshFlow::prepare(endpoint)?
+                .execute(request)
+                .map_err(WebError::from)
+        })?
+    }
+}
+
+impl<E> Clone for SharedEndpoint<E> {
+    fn clone(&self) -> Self {
+        SharedEndpoint {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<E> From<Arc<Mutex<E>>> for SharedEndpoint<E> {
+    fn from(endpoint: Arc<Mutex<E>>) -> Self {
+        SharedEndpoint {
+            inner: endpoint.into(),
+        }
+    }
+}
+
+impl<E> From<shared::SharedEndpoint<E>> for SharedEndpoint<E> {
+    fn from(inner: shared::SharedEndpoint<E>) -> Self {
+        SharedEndpoint { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::State, http::Request, http::StatusCode, routing::get, Router};
+    use chrono::{Duration, Utc};
+    use oxide_auth::frontends::simple::endpoint::{Generic, Vacant};
+    use oxide_auth::primitives::generator::RandomGenerator;
+    use oxide_auth::primitives::grant::Extensions;
+    use oxide_auth::primitives::issuer::{Issuer, TokenMap};
+    use oxide_auth::primitives::scope::Scope;
+    use tower::ServiceExt;
+
+    type TestEndpoint = Generic<Vacant, Vacant, TokenMap<RandomGenerator>, Vacant, Vec<Scope>>;
+
+    async fn handler(
+        State(endpoint): State<SharedEndpoint<TestEndpoint>>, resource: OAuthResource,
+    ) -> Result<String, Response> {
+        let grant = endpoint.resource(resource)?;
+        // Awaiting after the flow requires the lock to be released already.
+        tokio::task::yield_now().await;
+        Ok(grant.owner_id)
+    }
+
+    fn setup() -> (Router, String) {
+        let mut issuer = TokenMap::new(RandomGenerator::new(16));
+        let token = issuer
+            .issue(Grant {
+                owner_id: "Owner".to_owned(),
+                client_id: "Client".to_owned(),
+                scope: "read".parse().unwrap(),
+                redirect_uri: "https://client.example/endpoint".parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap()
+            .token;
+
+        let endpoint = Generic {
+            registrar: Vacant,
+            authorizer: Vacant,
+            issuer,
+            solicitor: Vacant,
+            scopes: vec!["read".parse().unwrap()],
+            response: Vacant,
+        };
+        let router = Router::new()
+            .route("/", get(handler))
+            .with_state(SharedEndpoint::new(endpoint));
+        (router, token)
+    }
+
+    fn request(auth: &str) -> Request<Body> {
+        Request::get("/")
+            .header("Authorization", auth)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn valid_token_accepted() {
+        let (router, token) = setup();
+        let response = router
+            .oneshot(request(&format!("Bearer {}", token)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"Owner");
+    }
+
+    #[tokio::test]
+    async fn invalid_token_rejected() {
+        let (router, _) = setup();
+        let response = router.oneshot(request("Bearer invalid")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn poisoned_lock() {
+        let endpoint = SharedEndpoint::new(());
+        let poison = endpoint.clone();
+        let _ = std::thread::spawn(move || poison.with(|_| panic!("poison the lock"))).join();
+
+        let error = endpoint.with(|_| ()).unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,28 @@
+-- Counter shared by all nodes, handed to the `TagGrant` generators.
+--
+-- Each issued token pair consumes two values, the access token is tagged with `usage` and the
+-- refresh token with `usage + 1`.
+CREATE SEQUENCE IF NOT EXISTS oxide_auth_usage INCREMENT BY 2 MINVALUE 0 START WITH 0;
+
+CREATE TABLE IF NOT EXISTS oxide_auth_codes (
+    code TEXT PRIMARY KEY,
+    usage BIGINT NOT NULL,
+    client_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    until TIMESTAMPTZ NOT NULL,
+    extensions JSONB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS oxide_auth_tokens (
+    access_token TEXT PRIMARY KEY,
+    refresh_token TEXT UNIQUE,
+    usage BIGINT NOT NULL,
+    client_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    until TIMESTAMPTZ NOT NULL,
+    extensions JSONB NOT NULL
+);
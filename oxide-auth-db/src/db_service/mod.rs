@@ -1,6 +1,9 @@
 #[cfg(feature = "with-redis")]
 pub mod redis;
 
+#[cfg(feature = "sqlx")]
+pub mod postgres;
+
 #[cfg(feature = "with-redis")]
 use redis::RedisDataSource;
 
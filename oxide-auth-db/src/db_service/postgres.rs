@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use oxide_auth::primitives::grant::{Extensions, Grant, Value};
+use oxide_auth::primitives::prelude::Scope;
+use serde::{Serialize, Deserialize};
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::Row;
+use url::Url;
+
+/// The schema migrations for the code and token tables.
+///
+/// Run these with [`PostgresDataSource::migrate`] or embed them in the migrations of your own
+/// application.
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+/// postgres datasource to code and token entries.
+#[derive(Debug, Clone)]
+pub struct PostgresDataSource {
+    pool: PgPool,
+}
+
+/// The extension data of a grant as it is stored in the `extensions` column.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StoredValue {
+    Public(Option<String>),
+    Private(Option<String>),
+}
+
+impl PostgresDataSource {
+    pub async fn new(url: &str, max_pool_size: u32) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_pool_size)
+            .connect(url)
+            .await?;
+        Ok(PostgresDataSource { pool })
+    }
+
+    /// Use an existing pool, for example one shared with the rest of the application.
+    pub fn from_pool(pool: PgPool) -> Self {
+        PostgresDataSource { pool }
+    }
+
+    /// Create the tables and the usage counter if they do not exist yet.
+    pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    pub fn get_pool(&self) -> PgPool {
+        self.pool.clone()
+    }
+
+    /// Reserve the next value of the usage counter shared by all nodes.
+    pub(crate) async fn next_usage(&self) -> Result<u64, sqlx::Error> {
+        let usage: i64 = sqlx::query_scalar("SELECT nextval('oxide_auth_usage')")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(usage as u64)
+    }
+}
+
+/// Encode the extensions of a grant into the json stored alongside it.
+pub(crate) fn encode_extensions(extensions: &Extensions) -> sqlx::types::JsonValue {
+    let public = extensions
+        .public()
+        .map(|(key, value)| (key.to_owned(), StoredValue::Public(value.map(str::to_owned))));
+    let private = extensions
+        .private()
+        .map(|(key, value)| (key.to_owned(), StoredValue::Private(value.map(str::to_owned))));
+    let stored: HashMap<String, StoredValue> = public.chain(private).collect();
+    // Serializing a map of strings can not fail.
+    serde_json::to_value(stored).unwrap()
+}
+
+/// Restore a grant from a row of the code or the token table.
+pub(crate) fn decode_grant(row: &PgRow) -> anyhow::Result<Grant> {
+    let redirect_uri: String = row.try_get("redirect_uri")?;
+    let scope: String = row.try_get("scope")?;
+    let until: DateTime<Utc> = row.try_get("until")?;
+    let stored: sqlx::types::JsonValue = row.try_get("extensions")?;
+    let stored: HashMap<String, StoredValue> = serde_json::from_value(stored)?;
+
+    let mut extensions = Extensions::new();
+    for (key, value) in stored {
+        let value = match value {
+            StoredValue::Public(content) => Value::public(content),
+            StoredValue::Private(content) => Value::private(content),
+        };
+        extensions.set_raw(key, value);
+    }
+
+    Ok(Grant {
+        owner_id: row.try_get("owner_id")?,
+        client_id: row.try_get("client_id")?,
+        scope: Scope::from_str(&scope).map_err(|_| anyhow::anyhow!("Invalid stored scope"))?,
+        redirect_uri: Url::parse(&redirect_uri)?,
+        until,
+        extensions,
+    })
+}
@@ -1,7 +1,9 @@
 use crate::primitives::db_registrar::OauthClientDBRepository;
 
 use oxide_auth::primitives::prelude::Scope;
-use oxide_auth::primitives::registrar::{ClientType, EncodedClient, RegisteredUrl, ExactUrl};
+use oxide_auth::primitives::registrar::{
+    ClientKey, ClientTrust, ClientType, EncodedClient, GrantType, RegisteredUrl, ExactUrl,
+};
 
 use r2d2_redis::r2d2::Pool;
 use r2d2_redis::redis::{Commands, RedisError, ErrorKind};
@@ -45,6 +47,26 @@ pub struct StringfiedEncodedClient {
 
     /// client_secret, for authentication.
     pub client_secret: Option<String>,
+
+    /// How far the server trusts the client.
+    #[serde(default)]
+    pub trust: ClientTrust,
+
+    /// Whether redirect uris on a loopback host match regardless of their port.
+    #[serde(default)]
+    pub loopback_port_variance: bool,
+
+    /// The key verifying assertions the client authenticates with.
+    #[serde(default)]
+    pub assertion_key: Option<ClientKey>,
+
+    /// The `x5t#S256` thumbprint of the certificate the client authenticates with.
+    #[serde(default)]
+    pub certificate: Option<String>,
+
+    /// The grant types the client may use, all of them if this is `None`.
+    #[serde(default)]
+    pub grant_types: Option<Vec<GrantType>>,
 }
 
 impl StringfiedEncodedClient {
@@ -72,6 +94,11 @@ impl StringfiedEncodedClient {
             )
             .unwrap(),
             encoded_client: client_type,
+            trust: self.trust,
+            loopback_port_variance: self.loopback_port_variance,
+            assertion_key: self.assertion_key.clone(),
+            certificate: self.certificate.clone(),
+            grant_types: self.grant_types.clone(),
         })
     }
 
@@ -92,6 +119,11 @@ impl StringfiedEncodedClient {
             additional_redirect_uris,
             default_scope,
             client_secret,
+            trust: encoded_client.trust,
+            loopback_port_variance: encoded_client.loopback_port_variance,
+            assertion_key: encoded_client.assertion_key.clone(),
+            certificate: encoded_client.certificate.clone(),
+            grant_types: encoded_client.grant_types.clone(),
         }
     }
 }
@@ -1,7 +1,7 @@
 pub mod db_service;
 pub mod primitives;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "with-redis"))]
 fn requires_redis_and_should_skip() -> bool {
     match std::env::var("OXIDE_AUTH_SKIP_REDIS") {
         Err(_) => false,
@@ -11,3 +11,18 @@ fn requires_redis_and_should_skip() -> bool {
         },
     }
 }
+
+#[cfg(all(test, feature = "sqlx"))]
+fn requires_postgres_and_should_skip() -> bool {
+    match std::env::var("OXIDE_AUTH_SKIP_POSTGRES") {
+        Err(_) => false,
+        Ok(st) => matches!(st.as_str(), "1" | "yes"),
+    }
+}
+
+/// The disposable database used by the postgres tests.
+#[cfg(all(test, feature = "sqlx"))]
+fn postgres_url() -> String {
+    std::env::var("OXIDE_AUTH_POSTGRES_URL")
+        .unwrap_or_else(|_| "postgres://postgres@localhost/oxide_auth".to_string())
+}
@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use oxide_auth::primitives::generator::TagGrant;
+use oxide_auth::primitives::grant::Grant;
+use oxide_auth_async::primitives::Authorizer;
+
+use crate::db_service::postgres::{decode_grant, encode_extensions, PostgresDataSource};
+
+/// An authorizer storing its codes in a postgres database.
+///
+/// The codes are still generated by the `tagger`, the database only provides persistence and the
+/// usage counter shared between all nodes. Each code is removed from the database when it is
+/// extracted, so it can only be used once even when several nodes access the same table.
+pub struct DBAuthorizer<I: TagGrant> {
+    pub repo: PostgresDataSource,
+    tagger: I,
+}
+
+impl<I: TagGrant> DBAuthorizer<I> {
+    /// Create an authorizer generating codes with the `tagger`.
+    ///
+    /// The tables must have already been created, see [`PostgresDataSource::migrate`].
+    pub fn new(repo: PostgresDataSource, tagger: I) -> Self {
+        DBAuthorizer { repo, tagger }
+    }
+}
+
+#[async_trait]
+impl<I: TagGrant + Send> Authorizer for DBAuthorizer<I> {
+    async fn authorize(&mut self, grant: Grant) -> Result<String, ()> {
+        let usage = self.repo.next_usage().await.map_err(|_| ())?;
+        let code = self.tagger.tag(usage, &grant)?;
+
+        sqlx::query(
+            "INSERT INTO oxide_auth_codes \
+             (code, usage, client_id, owner_id, redirect_uri, scope, until, extensions) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(&code)
+        .bind(usage as i64)
+        .bind(&grant.client_id)
+        .bind(&grant.owner_id)
+        .bind(grant.redirect_uri.as_str())
+        .bind(grant.scope.to_string())
+        .bind(grant.until)
+        .bind(encode_extensions(&grant.extensions))
+        .execute(&self.repo.get_pool())
+        .await
+        .map_err(|_| ())?;
+
+        Ok(code)
+    }
+
+    async fn extract(&mut self, code: &str) -> Result<Option<Grant>, ()> {
+        let row = sqlx::query(
+            "DELETE FROM oxide_auth_codes WHERE code = $1 \
+             RETURNING client_id, owner_id, redirect_uri, scope, until, extensions",
+        )
+        .bind(code)
+        .fetch_optional(&self.repo.get_pool())
+        .await
+        .map_err(|_| ())?;
+
+        match row {
+            None => Ok(None),
+            Some(row) => decode_grant(&row).map(Some).map_err(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DurationRound, Utc};
+    use oxide_auth::primitives::generator::RandomGenerator;
+    use oxide_auth::primitives::grant::{Extensions, Value};
+
+    #[tokio::test]
+    async fn postgres_authorizer() {
+        if crate::requires_postgres_and_should_skip() {
+            return;
+        }
+
+        let repo = PostgresDataSource::new(&crate::postgres_url(), 4).await.unwrap();
+        repo.migrate().await.unwrap();
+        let mut authorizer = DBAuthorizer::new(repo, RandomGenerator::new(16));
+
+        let mut extensions = Extensions::new();
+        extensions.set_raw("public".into(), Value::public(Some("content".into())));
+        extensions.set_raw("private".into(), Value::private(None));
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "One two three scopes".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            // Postgres stores timestamps with microsecond precision only.
+            until: Utc::now().duration_trunc(chrono::Duration::seconds(1)).unwrap(),
+            extensions,
+        };
+
+        let code = authorizer.authorize(grant.clone()).await.unwrap();
+        let recovered = authorizer.extract(&code).await.unwrap();
+        assert_eq!(recovered, Some(grant.clone()));
+        assert_eq!(authorizer.extract(&code).await.unwrap(), None);
+
+        let code_again = authorizer.authorize(grant).await.unwrap();
+        assert_ne!(code, code_again);
+    }
+}
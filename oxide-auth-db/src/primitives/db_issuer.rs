@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use oxide_auth::primitives::generator::TagGrant;
+use oxide_auth::primitives::grant::Grant;
+use oxide_auth::primitives::issuer::{IssuedToken, RefreshedToken, TokenType};
+use oxide_auth_async::primitives::Issuer;
+use sqlx::{Postgres, Transaction};
+
+use crate::db_service::postgres::{decode_grant, encode_extensions, PostgresDataSource};
+
+/// An issuer storing its tokens in a postgres database.
+///
+/// Like `TokenMap` the access and refresh tokens are generated by the `generator`, the database
+/// only provides persistence and the usage counter shared between all nodes. Refreshing a token
+/// replaces the old access and refresh token within a single transaction.
+pub struct DBIssuer<G: TagGrant> {
+    pub repo: PostgresDataSource,
+    generator: G,
+    duration: Option<Duration>,
+}
+
+impl<G: TagGrant> DBIssuer<G> {
+    /// Create an issuer generating tokens with the `generator`.
+    ///
+    /// The tables must have already been created, see [`PostgresDataSource::migrate`].
+    pub fn new(repo: PostgresDataSource, generator: G) -> Self {
+        DBIssuer {
+            repo,
+            generator,
+            duration: None,
+        }
+    }
+
+    /// Set the validity of all issued grants to the specified duration.
+    pub fn valid_for(&mut self, duration: Duration) {
+        self.duration = Some(duration);
+    }
+
+    /// All grants are valid for their default duration.
+    pub fn valid_for_default(&mut self) {
+        self.duration = None;
+    }
+
+    fn set_duration(&self, grant: &mut Grant) {
+        if let Some(duration) = &self.duration {
+            grant.until = Utc::now() + *duration;
+        }
+    }
+
+    fn tag_pair(&mut self, usage: u64, grant: &Grant) -> Result<(String, String), ()> {
+        let access = self.generator.tag(usage, grant)?;
+        let refresh = self.generator.tag(usage.wrapping_add(1), grant)?;
+        Ok((access, refresh))
+    }
+
+    async fn insert(
+        transaction: &mut Transaction<'_, Postgres>, usage: u64, access: &str, refresh: &str,
+        grant: &Grant,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO oxide_auth_tokens \
+             (access_token, refresh_token, usage, client_id, owner_id, redirect_uri, scope, until, \
+             extensions) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(access)
+        .bind(refresh)
+        .bind(usage as i64)
+        .bind(&grant.client_id)
+        .bind(&grant.owner_id)
+        .bind(grant.redirect_uri.as_str())
+        .bind(grant.scope.to_string())
+        .bind(grant.until)
+        .bind(encode_extensions(&grant.extensions))
+        .execute(&mut **transaction)
+        .await?;
+        Ok(())
+    }
+
+    async fn find(&self, column: &str, token: &str) -> Result<Option<Grant>, ()> {
+        let query = format!(
+            "SELECT client_id, owner_id, redirect_uri, scope, until, extensions \
+             FROM oxide_auth_tokens WHERE {} = $1",
+            column
+        );
+
+        let row = sqlx::query(&query)
+            .bind(token)
+            .fetch_optional(&self.repo.get_pool())
+            .await
+            .map_err(|_| ())?;
+
+        match row {
+            None => Ok(None),
+            Some(row) => decode_grant(&row).map(Some).map_err(|_| ()),
+        }
+    }
+}
+
+#[async_trait]
+impl<G: TagGrant + Send + Sync> Issuer for DBIssuer<G> {
+    async fn issue(&mut self, mut grant: Grant) -> Result<IssuedToken, ()> {
+        self.set_duration(&mut grant);
+        let usage = self.repo.next_usage().await.map_err(|_| ())?;
+        let (access, refresh) = self.tag_pair(usage, &grant)?;
+
+        let mut transaction = self.repo.get_pool().begin().await.map_err(|_| ())?;
+        Self::insert(&mut transaction, usage, &access, &refresh, &grant)
+            .await
+            .map_err(|_| ())?;
+        transaction.commit().await.map_err(|_| ())?;
+
+        Ok(IssuedToken {
+            token: access,
+            refresh: Some(refresh),
+            until: grant.until,
+            token_type: TokenType::Bearer,
+            refresh_until: None,
+        })
+    }
+
+    async fn refresh(&mut self, refresh: &str, mut grant: Grant) -> Result<RefreshedToken, ()> {
+        self.set_duration(&mut grant);
+        let usage = self.repo.next_usage().await.map_err(|_| ())?;
+        let (new_access, new_refresh) = self.tag_pair(usage, &grant)?;
+
+        let mut transaction = self.repo.get_pool().begin().await.map_err(|_| ())?;
+        // Should only be called on valid refresh tokens.
+        let removed = sqlx::query("DELETE FROM oxide_auth_tokens WHERE refresh_token = $1")
+            .bind(refresh)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| ())?;
+        if removed.rows_affected() == 0 {
+            return Err(());
+        }
+
+        Self::insert(&mut transaction, usage, &new_access, &new_refresh, &grant)
+            .await
+            .map_err(|_| ())?;
+        transaction.commit().await.map_err(|_| ())?;
+
+        Ok(RefreshedToken {
+            token: new_access,
+            refresh: Some(new_refresh),
+            until: grant.until,
+            token_type: TokenType::Bearer,
+            refresh_until: None,
+        })
+    }
+
+    async fn recover_token(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        self.find("access_token", token).await
+    }
+
+    async fn recover_refresh(&mut self, token: &str) -> Result<Option<Grant>, ()> {
+        self.find("refresh_token", token).await
+    }
+
+    async fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        // Both tokens of a grant share a row, revoking either revokes the grant.
+        sqlx::query("DELETE FROM oxide_auth_tokens WHERE access_token = $1 OR refresh_token = $1")
+            .bind(token)
+            .execute(&self.repo.get_pool())
+            .await
+            .map_err(|_| ())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DurationRound;
+    use oxide_auth::primitives::generator::RandomGenerator;
+    use oxide_auth::primitives::grant::Extensions;
+
+    #[tokio::test]
+    async fn postgres_issuer() {
+        if crate::requires_postgres_and_should_skip() {
+            return;
+        }
+
+        let repo = PostgresDataSource::new(&crate::postgres_url(), 4).await.unwrap();
+        repo.migrate().await.unwrap();
+        let mut issuer = DBIssuer::new(repo, RandomGenerator::new(16));
+
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "One two three scopes".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            // Postgres stores timestamps with microsecond precision only.
+            until: (Utc::now() + Duration::hours(1))
+                .duration_trunc(Duration::seconds(1))
+                .unwrap(),
+            extensions: Extensions::new(),
+        };
+
+        let issued = issuer.issue(grant.clone()).await.unwrap();
+        let refresh = issued.refresh.clone().unwrap();
+        assert_eq!(
+            issuer.recover_token(&issued.token).await.unwrap(),
+            Some(grant.clone())
+        );
+        assert_eq!(
+            issuer.recover_refresh(&refresh).await.unwrap(),
+            Some(grant.clone())
+        );
+
+        let refreshed = issuer.refresh(&refresh, grant.clone()).await.unwrap();
+        assert_eq!(issuer.recover_token(&issued.token).await.unwrap(), None);
+        assert_eq!(issuer.recover_refresh(&refresh).await.unwrap(), None);
+        assert_eq!(
+            issuer.recover_token(&refreshed.token).await.unwrap(),
+            Some(grant.clone())
+        );
+        // The old refresh token can not be used a second time.
+        assert!(issuer.refresh(&refresh, grant).await.is_err());
+    }
+}
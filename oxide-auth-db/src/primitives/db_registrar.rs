@@ -2,8 +2,8 @@ use std::borrow::Cow;
 use std::iter::Extend;
 use once_cell::sync::Lazy;
 use oxide_auth::primitives::registrar::{
-    Argon2, BoundClient, Client, EncodedClient, PasswordPolicy, RegisteredClient, Registrar,
-    RegistrarError,
+    Argon2, BoundClient, Client, ClientTrust, EncodedClient, GrantType, PasswordPolicy,
+    RegisteredClient, RegisteredUrl, Registrar, RegistrarError,
 };
 use oxide_auth::primitives::prelude::{ClientUrl, PreGrant, Scope};
 use crate::db_service::DataSource;
@@ -85,20 +85,13 @@ impl Registrar for DBRegistrar {
             _ => return Err(RegistrarError::Unspecified),
         };
         // Perform exact matching as motivated in the rfc
-        let registered_url = match bound.redirect_uri {
-            None => client.redirect_uri.clone(),
-            Some(ref url) => {
-                let original = std::iter::once(&client.redirect_uri);
-                let alternatives = client.additional_redirect_uris.iter();
-                if let Some(registered) = original
-                    .chain(alternatives)
-                    .find(|&registered| *registered == *url.as_ref())
-                {
-                    registered.clone()
-                } else {
-                    return Err(RegistrarError::Unspecified);
-                }
-            }
+        let registered_url = match client.bound_redirect_uri(bound.redirect_uri.as_deref()) {
+            None => return Err(RegistrarError::Unspecified),
+            Some(registered) => match bound.redirect_uri {
+                // Keep the requested url, it may differ from the registered one in its local port.
+                Some(url) => RegisteredUrl::Exact(url.into_owned()),
+                None => registered.clone(),
+            },
         };
         Ok(BoundClient {
             client_id: bound.client_id,
@@ -132,6 +125,19 @@ impl Registrar for DBRegistrar {
         })?;
         Ok(())
     }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        self.repo
+            .find_client_by_id(client_id)
+            .map_or(ClientTrust::ThirdParty, |client| client.trust)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        match self.repo.find_client_by_id(client_id) {
+            Ok(client) if client.allows_grant_type(grant_type) => Ok(()),
+            _ => Err(RegistrarError::Unspecified),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1 +1,8 @@
+#[cfg(feature = "with-redis")]
 pub mod db_registrar;
+
+#[cfg(feature = "sqlx")]
+pub mod db_authorizer;
+
+#[cfg(feature = "sqlx")]
+pub mod db_issuer;
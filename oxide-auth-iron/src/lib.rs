@@ -5,8 +5,11 @@
 #![warn(missing_docs)]
 
 use std::borrow::Cow;
+use std::time::Duration;
 
-use oxide_auth::endpoint::{OAuthError as EndpointError, QueryParameter, WebRequest, WebResponse};
+use oxide_auth::endpoint::{
+    cookie_value, OAuthError as EndpointError, QueryParameter, WebRequest, WebResponse,
+};
 use oxide_auth::frontends::simple::endpoint::Error as SimpleError;
 
 use iron::{Request, Response};
@@ -19,6 +22,9 @@ use url::Url;
 pub enum Error {
     /// Generally describes a malformed request.
     BadRequest,
+
+    /// The request had more than one `Authorization` header.
+    Authorization,
 }
 
 #[derive(Debug)]
@@ -132,7 +138,50 @@ impl<'a, 'b, 'c: 'b> WebRequest for OAuthRequest<'a, 'b, 'c> {
     }
 
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
-        Ok(self.authorization_header())
+        authorization(&self.0.headers)
+    }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        cookie(&self.0.headers, name)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        raw_header(&self.0.headers, name)
+    }
+}
+
+/// Find the value of a cookie in any of the `Cookie` headers.
+fn cookie<'h>(headers: &'h headers::Headers, name: &str) -> Result<Option<Cow<'h, str>>, Error> {
+    for value in headers.get_raw("Cookie").unwrap_or(&[]) {
+        let value = std::str::from_utf8(value).map_err(|_| Error::BadRequest)?;
+        if let Some(value) = cookie_value(value, name) {
+            return Ok(Some(Cow::Borrowed(value)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Find the single value of a header, rejecting repeated or non-utf8 ones.
+fn raw_header<'h>(headers: &'h headers::Headers, name: &str) -> Result<Option<Cow<'h, str>>, Error> {
+    match headers.get_raw(name) {
+        None => Ok(None),
+        Some([value]) => std::str::from_utf8(value)
+            .map(|value| Some(Cow::Borrowed(value)))
+            .map_err(|_| Error::BadRequest),
+        Some(_) => Err(Error::BadRequest),
+    }
+}
+
+/// Find the authorization header, rejecting requests with more than one.
+///
+/// Ignoring the duplicates would silently turn an authenticated request into an anonymous one.
+fn authorization(headers: &headers::Headers) -> Result<Option<Cow<str>>, Error> {
+    match headers.get_raw("Authorization") {
+        Some(values) if values.len() > 1 => Err(Error::Authorization),
+        _ => Ok(headers
+            .get::<headers::Authorization<String>>()
+            .map(|h| Cow::Borrowed(h.0.as_ref()))),
     }
 }
 
@@ -155,6 +204,29 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.set_status(Status::InternalServerError);
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.set_status(Status::TooManyRequests);
+        if let Some(retry_after) = retry_after {
+            let seconds = retry_after.as_secs().to_string().into_bytes();
+            self.set_raw_header("Retry-After".into(), vec![seconds]);
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.set_status(Status::ServiceUnavailable);
+        if let Some(retry_after) = retry_after {
+            let seconds = retry_after.as_secs().to_string().into_bytes();
+            self.set_raw_header("Retry-After".into(), vec![seconds]);
+        }
+        Ok(())
+    }
+
     fn unauthorized(&mut self, header_value: &str) -> Result<(), Self::Error> {
         self.set_status(Status::Unauthorized);
         let value_owned = header_value.as_bytes().to_vec();
@@ -162,6 +234,13 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.set_status(Status::Forbidden);
+        let value_owned = header_value.as_bytes().to_vec();
+        self.set_raw_header("WWW-Authenticate".into(), vec![value_owned]);
+        Ok(())
+    }
+
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.set_header(headers::ContentType::plaintext());
         self.set_body(text);
@@ -173,6 +252,12 @@ impl WebResponse for OAuthResponse {
         self.set_body(data);
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.set_raw_header("Cache-Control".into(), vec![b"no-store".to_vec()]);
+        self.set_raw_header("Pragma".into(), vec![b"no-cache".to_vec()]);
+        Ok(())
+    }
 }
 
 impl<'a, 'b, 'c: 'b> From<&'a mut Request<'b, 'c>> for OAuthRequest<'a, 'b, 'c> {
@@ -202,7 +287,9 @@ impl Into<Response> for OAuthResponse {
 impl<'a, 'b, 'c: 'b> From<SimpleError<OAuthRequest<'a, 'b, 'c>>> for OAuthError {
     fn from(error: SimpleError<OAuthRequest<'a, 'b, 'c>>) -> Self {
         let as_oauth = match error {
-            SimpleError::Web(Error::BadRequest) => EndpointError::BadRequest,
+            SimpleError::Web(Error::BadRequest) | SimpleError::Web(Error::Authorization) => {
+                EndpointError::BadRequest
+            }
             SimpleError::OAuth(oauth) => oauth,
         };
 
@@ -210,9 +297,15 @@ impl<'a, 'b, 'c: 'b> From<SimpleError<OAuthRequest<'a, 'b, 'c>>> for OAuthError
             EndpointError::BadRequest => Status::BadRequest,
             EndpointError::DenySilently => Status::BadRequest,
             EndpointError::PrimitiveError => Status::InternalServerError,
+            EndpointError::TemporarilyUnavailable(_) => Status::ServiceUnavailable,
         };
 
-        OAuthError(IronError::new(as_oauth, status))
+        let mut error = IronError::new(as_oauth, status);
+        if let EndpointError::TemporarilyUnavailable(retry_after) = as_oauth {
+            let seconds = retry_after.as_secs().to_string().into_bytes();
+            error.response.headers.set_raw("Retry-After", vec![seconds]);
+        }
+        OAuthError(error)
     }
 }
 
@@ -227,3 +320,60 @@ impl Into<IronError> for OAuthError {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_header() {
+        let mut headers = headers::Headers::new();
+        headers.set_raw("X-Custom", vec![b"value".to_vec()]);
+        headers.set_raw("X-Twice", vec![b"first".to_vec(), b"second".to_vec()]);
+
+        assert_eq!(raw_header(&headers, "x-custom").ok(), Some(Some("value".into())));
+        assert_eq!(raw_header(&headers, "X-Missing").ok(), Some(None));
+        assert!(raw_header(&headers, "X-Twice").is_err());
+    }
+
+    #[test]
+    fn cookies() {
+        let mut headers = headers::Headers::new();
+        headers.set_raw(
+            "Cookie",
+            vec![b"theme=dark".to_vec(), b"session=abc; token=xyz".to_vec()],
+        );
+
+        assert_eq!(cookie(&headers, "token").ok(), Some(Some("xyz".into())));
+        assert_eq!(cookie(&headers, "theme").ok(), Some(Some("dark".into())));
+        assert_eq!(cookie(&headers, "missing").ok(), Some(None));
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        let mut headers = headers::Headers::new();
+        assert_eq!(authorization(&headers).ok(), Some(None));
+
+        headers.set_raw("Authorization", vec![b"Bearer token".to_vec()]);
+        assert_eq!(authorization(&headers).ok(), Some(Some("Bearer token".into())));
+
+        headers.set_raw(
+            "Authorization",
+            vec![b"Bearer token".to_vec(), b"Bearer other".to_vec()],
+        );
+        assert!(matches!(authorization(&headers), Err(Error::Authorization)));
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = OAuthResponse::new();
+        assert!(response.too_many_requests(Some(Duration::from_secs(30))).is_ok());
+
+        let response: Response = response.into();
+        assert_eq!(response.status, Some(Status::TooManyRequests));
+        assert_eq!(
+            response.headers.get_raw("Retry-After"),
+            Some(&[b"30".to_vec()][..])
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use poem::error::{BadRequest, InternalServerError, Unauthorized};
+use poem::error::{BadRequest, InternalServerError};
 use thiserror::Error;
 
 /// Errors that may be generated by this crate.
@@ -26,8 +26,7 @@ pub enum OxidePoemError {
 impl From<OxidePoemError> for poem::Error {
     fn from(ox_err: OxidePoemError) -> Self {
         match &ox_err {
-            OxidePoemError::Request => BadRequest(ox_err),
-            OxidePoemError::Authorization => Unauthorized(ox_err),
+            OxidePoemError::Request | OxidePoemError::Authorization => BadRequest(ox_err),
             OxidePoemError::Header(_) | OxidePoemError::Server => InternalServerError(ox_err),
         }
     }
@@ -2,9 +2,10 @@ use std::borrow::Cow;
 use poem::{
     FromRequest, Request, RequestBody,
     error::BadRequest,
+    http::{header, HeaderMap},
     web::{Form},
 };
-use oxide_auth::endpoint::{NormalizedParameter, QueryParameter, WebRequest};
+use oxide_auth::endpoint::{cookie_value, NormalizedParameter, QueryParameter, WebRequest};
 use crate::{error::OxidePoemError, response::OAuthResponse};
 
 #[derive(Clone, Debug, Default)]
@@ -14,6 +15,7 @@ use crate::{error::OxidePoemError, response::OAuthResponse};
 /// places you also expect an application payload
 pub struct OAuthRequest {
     auth: Option<String>,
+    headers: HeaderMap,
     query: Option<NormalizedParameter>,
     body: Option<NormalizedParameter>,
 }
@@ -64,6 +66,31 @@ impl WebRequest for OAuthRequest {
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
         Ok(self.auth.as_deref().map(Cow::Borrowed))
     }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        for value in self.headers.get_all(header::COOKIE) {
+            let value = value.to_str().map_err(|_| OxidePoemError::Request)?;
+            if let Some(value) = cookie_value(value, name) {
+                return Ok(Some(Cow::Borrowed(value)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        let mut all = self.headers.get_all(name).into_iter();
+        let value = match (all.next(), all.next()) {
+            (None, _) => return Ok(None),
+            (Some(value), None) => value,
+            (Some(_), Some(_)) => return Err(OxidePoemError::Request),
+        };
+
+        value
+            .to_str()
+            .map(|value| Some(Cow::Borrowed(value)))
+            .map_err(|_| OxidePoemError::Request)
+    }
 }
 
 #[poem::async_trait]
@@ -76,14 +103,86 @@ impl<'a> FromRequest<'a> for OAuthRequest {
             .ok()
             .map(|f| f.0);
 
-        let mut all_auth = req.headers().get_all("Authorization").into_iter();
-        let optional = all_auth.next();
+        let auth = authorization(req.headers()).map_err(BadRequest)?;
+
+        Ok(Self {
+            auth,
+            headers: req.headers().clone(),
+            query,
+            body,
+        })
+    }
+}
+
+/// Find the authorization header, rejecting requests with more than one.
+///
+/// Ignoring the duplicates would silently turn an authenticated request into an anonymous one.
+fn authorization(headers: &HeaderMap) -> Result<Option<String>, OxidePoemError> {
+    let mut all_auth = headers.get_all("Authorization").into_iter();
+    let optional = all_auth.next();
+
+    match all_auth.next() {
+        Some(_) => Err(OxidePoemError::Authorization),
+        None => Ok(optional.and_then(|header| header.to_str().ok().map(str::to_owned))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_header() {
+        let request = Request::builder()
+            .header("X-Custom", "value")
+            .header("X-Twice", "first")
+            .header("X-Twice", "second")
+            .finish();
+        let mut request = OAuthRequest {
+            headers: request.headers().clone(),
+            ..OAuthRequest::default()
+        };
+
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        assert!(request.header("X-Twice").is_err());
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        let request = Request::builder()
+            .header("Authorization", "Bearer token")
+            .finish();
+        assert_eq!(
+            authorization(request.headers()).unwrap().as_deref(),
+            Some("Bearer token")
+        );
+
+        let request = Request::builder()
+            .header("Authorization", "Bearer token")
+            .header("Authorization", "Bearer other")
+            .finish();
+        let err = authorization(request.headers()).unwrap_err();
+        assert!(matches!(err, OxidePoemError::Authorization));
+        assert_eq!(
+            poem::Error::from(err).status(),
+            poem::http::StatusCode::BAD_REQUEST
+        );
+    }
 
-        let auth = match all_auth.next() {
-            Some(_) => return Err(BadRequest(OxidePoemError::Authorization)),
-            None => optional.and_then(|header| header.to_str().ok().map(str::to_owned)),
+    #[test]
+    fn cookie() {
+        let request = Request::builder()
+            .header(header::COOKIE, "theme=dark")
+            .header(header::COOKIE, "session=abc; token=xyz")
+            .finish();
+        let mut request = OAuthRequest {
+            headers: request.headers().clone(),
+            ..OAuthRequest::default()
         };
 
-        Ok(Self { auth, query, body })
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
     }
 }
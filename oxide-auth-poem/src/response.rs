@@ -1,13 +1,17 @@
 use poem::{
     http::{
         Extensions,
-        header::{InvalidHeaderValue, CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE},
+        header::{
+            InvalidHeaderValue, CACHE_CONTROL, CONTENT_TYPE, LOCATION, PRAGMA, RETRY_AFTER,
+            WWW_AUTHENTICATE,
+        },
         HeaderMap, HeaderValue, StatusCode, Version,
     },
     Body, IntoResponse, Response, ResponseParts,
 };
 use oxide_auth::{endpoint::WebResponse, frontends::dev::Url};
 use crate::error::OxidePoemError;
+use std::time::Duration;
 
 #[derive(Default, Clone, Debug)]
 /// Type implementing `WebResponse` and `IntoResponse` for use in route handlers
@@ -63,6 +67,39 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::INTERNAL_SERVER_ERROR;
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::TOO_MANY_REQUESTS;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::SERVICE_UNAVAILABLE;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
     fn unauthorized(&mut self, header_value: &str) -> Result<(), Self::Error> {
         self.status = StatusCode::UNAUTHORIZED;
         self.headers.insert(
@@ -74,6 +111,17 @@ impl WebResponse for OAuthResponse {
         Ok(())
     }
 
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::FORBIDDEN;
+        self.headers.insert(
+            WWW_AUTHENTICATE,
+            header_value
+                .parse()
+                .map_err(|err: InvalidHeaderValue| OxidePoemError::Header(err.to_string()))?,
+        );
+        Ok(())
+    }
+
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.body = Some(text.to_owned());
         self.headers
@@ -87,6 +135,13 @@ impl WebResponse for OAuthResponse {
             .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.headers
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        self.headers.insert(PRAGMA, HeaderValue::from_static("no-cache"));
+        Ok(())
+    }
 }
 
 impl IntoResponse for OAuthResponse {
@@ -105,3 +160,18 @@ impl IntoResponse for OAuthResponse {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = OAuthResponse::default();
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        let response = response.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+    }
+}
@@ -47,6 +47,7 @@ impl<'r> Responder<'r> for OAuthFailure {
         match self.inner {
             Web(_) | OAuth(DenySilently) | OAuth(BadRequest) => Err(Status::BadRequest),
             OAuth(PrimitiveError) => Err(Status::InternalServerError),
+            OAuth(TemporarilyUnavailable(_)) => Err(Status::ServiceUnavailable),
         }
     }
 }
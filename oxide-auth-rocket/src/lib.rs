@@ -3,8 +3,9 @@
 
 mod failure;
 
-use std::io::Cursor;
+use std::io::{Cursor, Read};
 use std::marker::PhantomData;
+use std::time::Duration;
 
 use rocket::{Data, Request, Response};
 use rocket::http::{ContentType, Status};
@@ -13,7 +14,7 @@ use rocket::request::FromRequest;
 use rocket::response::{self, Responder};
 use rocket::outcome::Outcome;
 
-use oxide_auth::endpoint::{NormalizedParameter, WebRequest, WebResponse};
+use oxide_auth::endpoint::{cookie_value, NormalizedParameter, WebRequest, WebResponse};
 use oxide_auth::frontends::dev::*;
 
 pub use oxide_auth::frontends::simple::endpoint::Generic;
@@ -22,9 +23,11 @@ pub use self::failure::OAuthFailure;
 
 /// Request guard that also buffers OAuth data internally.
 pub struct OAuthRequest<'r> {
-    auth: Option<String>,
+    auth: Result<Option<String>, WebError>,
+    headers: Vec<(String, String)>,
     query: Result<NormalizedParameter, WebError>,
     body: Result<Option<NormalizedParameter>, WebError>,
+    raw_body: Option<Vec<u8>>,
     lifetime: PhantomData<&'r ()>,
 }
 
@@ -52,6 +55,9 @@ pub enum WebError {
 
     /// Form data was requested but the request was not a form.
     NotAForm,
+
+    /// The request had more than one `Authorization` header.
+    Authorization,
 }
 
 impl<'r> OAuthRequest<'r> {
@@ -70,20 +76,20 @@ impl<'r> OAuthRequest<'r> {
             _ => Err(WebError::NotAForm),
         };
 
-        let mut all_auth = request.headers().get("Authorization");
-        let optional = all_auth.next();
+        let auth = authorization(request.headers().get("Authorization"));
 
-        // Duplicate auth header, just treat it as no authorization.
-        let auth = if let Some(_) = all_auth.next() {
-            None
-        } else {
-            optional.map(str::to_owned)
-        };
+        let headers = request
+            .headers()
+            .iter()
+            .map(|header| (header.name().to_owned(), header.value().to_owned()))
+            .collect();
 
         OAuthRequest {
             auth,
+            headers,
             query,
             body,
+            raw_body: None,
             lifetime: PhantomData,
         }
     }
@@ -94,19 +100,46 @@ impl<'r> OAuthRequest<'r> {
     /// simplify the implementation of primitives and handlers, this type is the central request
     /// type for both these use cases. When you forget to provide the body to a request, the oauth
     /// system will return an error the moment the request is used.
+    ///
+    /// The unparsed body is kept as well, also for content types other than forms, and available
+    /// to extensions through `WebRequest::raw_body`.
     pub fn add_body(&mut self, data: Data) {
-        // Nothing to do if we already have a body, or already generated an error. This includes
-        // the case where the content type does not indicate a form, as the error is silent until a
-        // body is explicitely requested.
+        // Nothing to do if we already have a body.
+        if self.raw_body.is_some() {
+            return;
+        }
+
+        let mut raw_body = Vec::new();
+        let read = data.open().read_to_end(&mut raw_body).is_ok();
+
+        // Nothing to parse if we already generated an error. This includes the case where the
+        // content type does not indicate a form, as the error is silent until a body is
+        // explicitely requested.
         if let Ok(None) = self.body {
-            match serde_urlencoded::from_reader(data.open()) {
-                Ok(query) => self.body = Ok(Some(query)),
-                Err(_) => self.body = Err(WebError::Encoding),
-            }
+            self.body = match serde_urlencoded::from_bytes(&raw_body) {
+                Ok(query) if read => Ok(Some(query)),
+                _ => Err(WebError::Encoding),
+            };
+        }
+
+        if read {
+            self.raw_body = Some(raw_body);
         }
     }
 }
 
+/// Find the authorization header, rejecting requests with more than one.
+///
+/// Ignoring the duplicates would silently turn an authenticated request into an anonymous one.
+fn authorization<'h>(mut all_auth: impl Iterator<Item = &'h str>) -> Result<Option<String>, WebError> {
+    let optional = all_auth.next();
+
+    match all_auth.next() {
+        Some(_) => Err(WebError::Authorization),
+        None => Ok(optional.map(str::to_owned)),
+    }
+}
+
 impl<'r> OAuthResponse<'r> {
     /// Create a new `OAuthResponse<'r>`
     pub fn new() -> Self {
@@ -139,7 +172,37 @@ impl<'r> WebRequest for OAuthRequest<'r> {
     }
 
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
-        Ok(self.auth.as_ref().map(String::as_str).map(Cow::Borrowed))
+        match self.auth.as_ref() {
+            Ok(auth) => Ok(auth.as_deref().map(Cow::Borrowed)),
+            Err(err) => Err(*err),
+        }
+    }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        Ok(self
+            .headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Cookie"))
+            .find_map(|(_, value)| cookie_value(value, name))
+            .map(Cow::Borrowed))
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        let mut all = self
+            .headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str());
+
+        match (all.next(), all.next()) {
+            (None, _) => Ok(None),
+            (Some(value), None) => Ok(Some(Cow::Borrowed(value))),
+            (Some(_), Some(_)) => Err(WebError::Encoding),
+        }
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
     }
 }
 
@@ -162,12 +225,47 @@ impl<'r> WebResponse for OAuthResponse<'r> {
         Ok(())
     }
 
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.0.set_status(Status::InternalServerError);
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.0.set_status(Status::TooManyRequests);
+        match retry_after {
+            Some(retry_after) => {
+                self.0
+                    .set_raw_header("Retry-After", retry_after.as_secs().to_string());
+            }
+            None => self.0.remove_header("Retry-After"),
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.0.set_status(Status::ServiceUnavailable);
+        match retry_after {
+            Some(retry_after) => {
+                self.0
+                    .set_raw_header("Retry-After", retry_after.as_secs().to_string());
+            }
+            None => self.0.remove_header("Retry-After"),
+        }
+        Ok(())
+    }
+
     fn unauthorized(&mut self, kind: &str) -> Result<(), Self::Error> {
         self.0.set_status(Status::Unauthorized);
         self.0.set_raw_header("WWW-Authenticate", kind.to_owned());
         Ok(())
     }
 
+    fn forbidden(&mut self, kind: &str) -> Result<(), Self::Error> {
+        self.0.set_status(Status::Forbidden);
+        self.0.set_raw_header("WWW-Authenticate", kind.to_owned());
+        Ok(())
+    }
+
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.0.set_sized_body(Cursor::new(text.to_owned()));
         self.0.set_header(ContentType::Plain);
@@ -179,6 +277,12 @@ impl<'r> WebResponse for OAuthResponse<'r> {
         self.0.set_header(ContentType::JSON);
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.0.set_raw_header("Cache-Control", "no-store");
+        self.0.set_raw_header("Pragma", "no-cache");
+        Ok(())
+    }
 }
 
 impl<'a, 'r> FromRequest<'a, 'r> for OAuthRequest<'r> {
@@ -200,6 +304,7 @@ impl<'r> Responder<'r> for WebError {
         match self {
             WebError::Encoding => Err(Status::BadRequest),
             WebError::NotAForm => Err(Status::BadRequest),
+            WebError::Authorization => Err(Status::BadRequest),
             WebError::BodyNeeded => Err(Status::InternalServerError),
         }
     }
@@ -222,3 +327,74 @@ impl<'r> Into<Response<'r>> for OAuthResponse<'r> {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_header() {
+        let header = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+        let mut request = OAuthRequest {
+            auth: Ok(None),
+            headers: vec![
+                header("X-Custom", "value"),
+                header("X-Twice", "first"),
+                header("X-Twice", "second"),
+            ],
+            query: Err(WebError::Encoding),
+            body: Err(WebError::NotAForm),
+            raw_body: None,
+            lifetime: PhantomData,
+        };
+
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        assert!(request.header("X-Twice").is_err());
+    }
+
+    #[test]
+    fn cookie() {
+        let header = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+        let mut request = OAuthRequest {
+            auth: Ok(None),
+            headers: vec![
+                header("Cookie", "theme=dark"),
+                header("Cookie", "session=abc; token=xyz"),
+            ],
+            query: Err(WebError::Encoding),
+            body: Err(WebError::NotAForm),
+            raw_body: None,
+            lifetime: PhantomData,
+        };
+
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        assert_eq!(authorization(vec![].into_iter()).unwrap(), None);
+        assert_eq!(
+            authorization(vec!["Bearer token"].into_iter())
+                .unwrap()
+                .as_deref(),
+            Some("Bearer token")
+        );
+        assert!(matches!(
+            authorization(vec!["Bearer token", "Bearer other"].into_iter()),
+            Err(WebError::Authorization)
+        ));
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = OAuthResponse::new();
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        let response: Response = response.into();
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("30"));
+    }
+}
@@ -6,8 +6,9 @@
 
 use core::ops::Deref;
 use std::borrow::Cow;
+use std::time::Duration;
 
-use oxide_auth::endpoint::{QueryParameter, WebRequest, WebResponse};
+use oxide_auth::endpoint::{cookie_value, NormalizedParameter, QueryParameter, WebRequest, WebResponse};
 
 use rouille;
 use url::Url;
@@ -24,12 +25,19 @@ pub enum WebError {
     /// This may happen for example due to a query parameter that is not valid utf8 when the query
     /// parameters are necessary for OAuth processing.
     Encoding,
+
+    /// The request had more than one `Authorization` header.
+    Authorization,
 }
 
 #[derive(Debug)]
 /// The Request type used by Oxide Auth to extract required information
+///
+/// A form body is buffered when the request is created, as the body of a `rouille::Request` can
+/// only be read once.
 pub struct Request<'a> {
     inner: &'a rouille::Request,
+    body: Option<NormalizedParameter>,
 }
 
 #[derive(Debug)]
@@ -40,11 +48,31 @@ pub struct Response {
 
 impl<'a> Request<'a> {
     /// Create a new Request from a `rouille::Request`
+    ///
+    /// This consumes the body of the request if it is an urlencoded form, or has no content type.
     pub fn new(inner: &'a rouille::Request) -> Self {
-        Request { inner }
+        let body = match inner.header("Content-Type") {
+            None => Self::read_form(inner),
+            Some(content_type) if is_form(content_type) => Self::read_form(inner),
+            Some(_) => None,
+        };
+
+        Request { inner, body }
+    }
+
+    fn read_form(inner: &rouille::Request) -> Option<NormalizedParameter> {
+        let body = inner.data()?;
+        serde_urlencoded::from_reader(body).ok()
     }
 }
 
+fn is_form(content_type: &str) -> bool {
+    content_type.split(';').next().is_some_and(|mime| {
+        mime.trim()
+            .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+    })
+}
+
 impl Response {
     /// Produce a `rouille::Response` from a `Response`
     pub fn into_inner(self) -> rouille::Response {
@@ -75,18 +103,48 @@ impl<'a> WebRequest for Request<'a> {
     }
 
     fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
-        match self.inner.header("Content-Type") {
-            None | Some("application/x-www-form-urlencoded") => (),
-            _ => return Err(WebError::Encoding),
+        self.body
+            .as_ref()
+            .map(|body| Cow::Borrowed(body as &dyn QueryParameter))
+            .ok_or(WebError::Encoding)
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+        // Ignoring duplicates would silently turn an authenticated request into an anonymous one.
+        let mut all = self
+            .inner
+            .headers()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Authorization"))
+            .map(|(_, value)| value);
+
+        match (all.next(), all.next()) {
+            (None, _) => Ok(None),
+            (Some(value), None) => Ok(Some(value.into())),
+            (Some(_), Some(_)) => Err(WebError::Authorization),
         }
+    }
 
-        let body = self.inner.data().ok_or(WebError::Encoding)?;
-        let data = serde_urlencoded::from_reader(body).map_err(|_| WebError::Encoding)?;
-        Ok(Cow::Owned(data))
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        Ok(self
+            .inner
+            .headers()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Cookie"))
+            .find_map(|(_, value)| cookie_value(value, name))
+            .map(Cow::Borrowed))
     }
 
-    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
-        Ok(self.inner.header("Authorization").map(|st| st.into()))
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        let mut all = self
+            .inner
+            .headers()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value);
+
+        match (all.next(), all.next()) {
+            (None, _) => Ok(None),
+            (Some(value), None) => Ok(Some(value.into())),
+            (Some(_), Some(_)) => Err(WebError::Encoding),
+        }
     }
 }
 
@@ -114,6 +172,37 @@ impl WebResponse for Response {
         Ok(())
     }
 
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.inner.status_code = 500;
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.inner.status_code = 429;
+        self.inner
+            .headers
+            .retain(|header| !header.0.eq_ignore_ascii_case("Retry-After"));
+        if let Some(retry_after) = retry_after {
+            self.inner
+                .headers
+                .push(("Retry-After".into(), retry_after.as_secs().to_string().into()));
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.inner.status_code = 503;
+        self.inner
+            .headers
+            .retain(|header| !header.0.eq_ignore_ascii_case("Retry-After"));
+        if let Some(retry_after) = retry_after {
+            self.inner
+                .headers
+                .push(("Retry-After".into(), retry_after.as_secs().to_string().into()));
+        }
+        Ok(())
+    }
+
     fn unauthorized(&mut self, kind: &str) -> Result<(), Self::Error> {
         self.inner.status_code = 401;
         self.inner
@@ -125,6 +214,17 @@ impl WebResponse for Response {
         Ok(())
     }
 
+    fn forbidden(&mut self, kind: &str) -> Result<(), Self::Error> {
+        self.inner.status_code = 403;
+        self.inner
+            .headers
+            .retain(|header| !header.0.eq_ignore_ascii_case("www-authenticate"));
+        self.inner
+            .headers
+            .push(("WWW-Authenticate".into(), kind.to_string().into()));
+        Ok(())
+    }
+
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.inner
             .headers
@@ -146,6 +246,17 @@ impl WebResponse for Response {
         self.inner.data = rouille::ResponseBody::from_string(data);
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.inner.headers.retain(|header| {
+            !header.0.eq_ignore_ascii_case("Cache-Control") && !header.0.eq_ignore_ascii_case("Pragma")
+        });
+        self.inner
+            .headers
+            .push(("Cache-Control".into(), "no-store".into()));
+        self.inner.headers.push(("Pragma".into(), "no-cache".into()));
+        Ok(())
+    }
 }
 
 impl Deref for Request<'_> {
@@ -170,4 +281,138 @@ mod tests {
         assert_eq!(Some(Cow::Borrowed("val")), query.unique_value("fine"));
         assert_eq!(None, query.unique_value("param"));
     }
+
+    #[test]
+    fn urlbody_twice() {
+        let request = &rouille::Request::fake_http(
+            "POST",
+            "/token?state=abc",
+            vec![("Content-Type".into(), "application/x-www-form-urlencoded".into())],
+            b"grant_type=authorization_code&code=xyz".to_vec(),
+        );
+        let mut request = Request::new(request);
+
+        let body = WebRequest::urlbody(&mut request).unwrap();
+        assert_eq!(Some(Cow::Borrowed("xyz")), body.unique_value("code"));
+        let query = WebRequest::query(&mut request).unwrap();
+        assert_eq!(Some(Cow::Borrowed("abc")), query.unique_value("state"));
+        let body = WebRequest::urlbody(&mut request).unwrap();
+        assert_eq!(
+            Some(Cow::Borrowed("authorization_code")),
+            body.unique_value("grant_type")
+        );
+    }
+
+    #[test]
+    fn urlbody_not_a_form() {
+        let request = &rouille::Request::fake_http(
+            "POST",
+            "/token",
+            vec![("Content-Type".into(), "application/json".into())],
+            br#"{"grant_type":"authorization_code"}"#.to_vec(),
+        );
+        let mut request = Request::new(request);
+
+        assert!(matches!(
+            WebRequest::urlbody(&mut request),
+            Err(WebError::Encoding)
+        ));
+        // The body was not consumed.
+        assert!(request.data().is_some());
+    }
+
+    #[test]
+    fn custom_header() {
+        let request = &rouille::Request::fake_http(
+            "GET",
+            "/resource",
+            vec![
+                ("X-Custom".into(), "value".into()),
+                ("X-Twice".into(), "first".into()),
+                ("X-Twice".into(), "second".into()),
+            ],
+            vec![],
+        );
+        let mut request = Request::new(request);
+
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        assert!(request.header("X-Twice").is_err());
+    }
+
+    #[test]
+    fn cookie() {
+        let request = &rouille::Request::fake_http(
+            "GET",
+            "/resource",
+            vec![
+                ("Cookie".into(), "theme=dark".into()),
+                ("Cookie".into(), "session=abc; token=xyz".into()),
+            ],
+            vec![],
+        );
+        let mut request = Request::new(request);
+
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        let request = &rouille::Request::fake_http(
+            "GET",
+            "/resource",
+            vec![("Authorization".into(), "Bearer token".into())],
+            vec![],
+        );
+        let mut request = Request::new(request);
+        assert_eq!(request.authheader().unwrap().as_deref(), Some("Bearer token"));
+
+        let request = &rouille::Request::fake_http(
+            "GET",
+            "/resource",
+            vec![
+                ("Authorization".into(), "Bearer token".into()),
+                ("authorization".into(), "Bearer other".into()),
+            ],
+            vec![],
+        );
+        let mut request = Request::new(request);
+        assert!(matches!(request.authheader(), Err(WebError::Authorization)));
+    }
+
+    #[test]
+    fn no_store_headers() {
+        let mut response = Response::from(rouille::Response::text(""));
+        response.no_store().unwrap();
+        response.no_store().unwrap();
+
+        let headers = response.into_inner().headers;
+        let header = |name: &str| {
+            headers
+                .iter()
+                .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(header("Cache-Control"), ["no-store"]);
+        assert_eq!(header("Pragma"), ["no-cache"]);
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = Response::from(rouille::Response::text(""));
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        let response = response.into_inner();
+        assert_eq!(response.status_code, 429);
+        let retry_after = response
+            .headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Retry-After"))
+            .map(|(_, value)| value.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(retry_after, ["30"]);
+    }
 }
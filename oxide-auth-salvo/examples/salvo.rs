@@ -0,0 +1,132 @@
+#[rustfmt::skip]
+#[path = "../../examples/support/generic.rs"]
+mod support;
+
+use std::sync::{Arc, Mutex};
+
+use oxide_auth::endpoint::{
+    AccessTokenFlow, AuthorizationFlow, OwnerConsent, QueryParameter, ResourceFlow, Solicitation,
+    WebResponse,
+};
+use oxide_auth::frontends::dev::Url;
+use oxide_auth::frontends::simple::endpoint::{FnSolicitor, Generic};
+use oxide_auth::primitives::prelude::*;
+use oxide_auth_salvo::{error::OxideSalvoError, request::OAuthRequest, response::OAuthResponse};
+use salvo::prelude::*;
+
+type Solicitor = FnSolicitor<fn(&mut OAuthRequest, Solicitation) -> OwnerConsent<OAuthResponse>>;
+
+type Endpoint = Generic<
+    ClientMap,
+    AuthMap<RandomGenerator>,
+    TokenMap<RandomGenerator>,
+    Solicitor,
+    Vec<Scope>,
+    fn() -> OAuthResponse,
+>;
+
+type State = Arc<Mutex<Endpoint>>;
+
+/// Example of a salvo server supporting oauth.
+#[tokio::main]
+async fn main() {
+    let mut clients = ClientMap::new();
+    // Register a dummy client instance
+    clients.register_client(Client::public(
+        "LocalClient",                                                   // Client id
+        "http://localhost:8021/endpoint".parse::<Url>().unwrap().into(), // Redirection url
+        "default".parse().unwrap(),
+    )); // Allowed client scope
+
+    let state: State = Arc::new(Mutex::new(Generic {
+        registrar: clients,
+        // Authorization tokens are 16 byte random keys to a memory hash map.
+        authorizer: AuthMap::new(RandomGenerator::new(16)),
+        // Bearer tokens are also random generated but 256-bit tokens, since they live longer.
+        issuer: TokenMap::new(RandomGenerator::new(32)),
+        solicitor: FnSolicitor(consent),
+        scopes: vec!["default".parse().unwrap()],
+        response: OAuthResponse::default,
+    }));
+
+    let router = Router::new()
+        .hoop(affix_state::inject(state))
+        .get(protected)
+        .push(Router::with_path("authorize").get(authorize).post(authorize))
+        .push(Router::with_path("token").post(token));
+
+    support::open_in_browser();
+    let acceptor = TcpListener::new("127.0.0.1:8020").bind().await;
+    Server::new(acceptor).serve(router).await;
+}
+
+fn endpoint(depot: &Depot) -> State {
+    depot.get_typed::<State>().expect("state is injected").clone()
+}
+
+#[handler]
+async fn authorize(request: OAuthRequest, depot: &mut Depot) -> Result<OAuthResponse, OxideSalvoError> {
+    let state = endpoint(depot);
+    let mut endpoint = state.lock().unwrap();
+    let response = AuthorizationFlow::prepare(&mut *endpoint).and_then(|mut flow| flow.execute(request));
+    Ok(response?)
+}
+
+#[handler]
+async fn token(request: OAuthRequest, depot: &mut Depot) -> Result<OAuthResponse, OxideSalvoError> {
+    let state = endpoint(depot);
+    let mut endpoint = state.lock().unwrap();
+    let response = AccessTokenFlow::prepare(&mut *endpoint).and_then(|mut flow| flow.execute(request));
+    Ok(response?)
+}
+
+/// A handler only accessible with a valid token.
+#[handler]
+async fn protected(request: OAuthRequest, depot: &mut Depot) -> Result<OAuthResponse, OxideSalvoError> {
+    let state = endpoint(depot);
+    let mut endpoint = state.lock().unwrap();
+    let mut flow = ResourceFlow::prepare(&mut *endpoint)?;
+
+    match flow.execute(request) {
+        Ok(grant) => Ok(OAuthResponse::default()
+            .content_type("text/plain")?
+            .body(&format!("Hello, {}", grant.owner_id))),
+        Err(Ok(response)) => {
+            let text = "<html>
+This page should be accessed via an oauth token from the client in the example. Click
+<a href=\"http://localhost:8020/authorize?response_type=code&client_id=LocalClient\">
+here</a> to begin the authorization process.
+</html>";
+            Ok(response.content_type("text/html")?.body(text))
+        }
+        Err(Err(error)) => Err(error.into()),
+    }
+}
+
+/// A simple implementation of an 'owner solicitor'.
+///
+/// Displays a page to the user asking for their permission to proceed. The submitted form adds
+/// either an `allow` or a `deny` parameter and actually completes the flow.
+fn consent(request: &mut OAuthRequest, solicitation: Solicitation) -> OwnerConsent<OAuthResponse> {
+    let query = request.query();
+    if query.and_then(|query| query.unique_value("allow")).is_some() {
+        // No real user authentication is done here, in production you MUST use session keys or
+        // equivalent.
+        return OwnerConsent::Authorized("dummy user".to_string());
+    }
+
+    if query.and_then(|query| query.unique_value("deny")).is_some() {
+        return OwnerConsent::Denied;
+    }
+
+    let mut response = OAuthResponse::default();
+    let page = support::consent_page_html("/authorize", solicitation);
+    let html = response
+        .ok()
+        .and_then(|()| response.body_text(&page))
+        .and_then(|()| response.content_type("text/html"));
+    match html {
+        Ok(response) => OwnerConsent::InProgress(response),
+        Err(error) => OwnerConsent::Error(error),
+    }
+}
@@ -0,0 +1,88 @@
+use oxide_auth::frontends::{dev::OAuthError, simple::endpoint::Error};
+use salvo::{
+    async_trait,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    writing::Text,
+    Depot, Request, Response, Writer,
+};
+use thiserror::Error;
+
+use crate::request::OAuthRequest;
+
+/// Errors that may be generated by this crate.
+/// - `Request`
+/// - `ContentType`
+/// - `Authorization`
+/// - `Header`
+/// - `Endpoint`
+/// - `Server`
+///    - This is the general server error.
+#[derive(Clone, Debug, Error)]
+pub enum OxideSalvoError {
+    /// This is returned when the client request cannot be properly parsed
+    #[error("There was a problem with the request")]
+    Request,
+    /// This is returned when the request has a body that is not an urlencoded form
+    #[error("Unsupported content type, expected application/x-www-form-urlencoded")]
+    ContentType,
+    /// This is returned when the client request contains an invalid "Authorization" header
+    #[error("Invalid Authorization Header")]
+    Authorization,
+    /// This is for header parsing related errors (server side)
+    #[error("Error while parsing header: {0}")]
+    Header(String),
+    /// This is returned when an endpoint operation failed
+    #[error("Endpoint error: {0}")]
+    Endpoint(OAuthError),
+    #[error("There was a problem with the server")]
+    /// This is the general server error.
+    Server,
+}
+
+impl OxideSalvoError {
+    /// The status code of the response to this error
+    #[must_use]
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            OxideSalvoError::Request
+            | OxideSalvoError::ContentType
+            | OxideSalvoError::Authorization
+            | OxideSalvoError::Endpoint(OAuthError::DenySilently | OAuthError::BadRequest) => {
+                StatusCode::BAD_REQUEST
+            }
+            OxideSalvoError::Endpoint(OAuthError::TemporarilyUnavailable(_)) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            OxideSalvoError::Header(_)
+            | OxideSalvoError::Endpoint(OAuthError::PrimitiveError)
+            | OxideSalvoError::Server => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<OAuthError> for OxideSalvoError {
+    fn from(err: OAuthError) -> Self {
+        OxideSalvoError::Endpoint(err)
+    }
+}
+
+impl From<Error<OAuthRequest>> for OxideSalvoError {
+    fn from(err: Error<OAuthRequest>) -> Self {
+        match err {
+            Error::Web(err) => err,
+            Error::OAuth(err) => err.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Writer for OxideSalvoError {
+    async fn write(self, _: &mut Request, _: &mut Depot, res: &mut Response) {
+        if let OxideSalvoError::Endpoint(OAuthError::TemporarilyUnavailable(retry_after)) = &self {
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        res.status_code(self.status_code());
+        res.render(Text::Plain(self.to_string()));
+    }
+}
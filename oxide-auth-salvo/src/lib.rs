@@ -0,0 +1,11 @@
+//! Adaptations and integration for Salvo.
+#![warn(missing_docs)]
+#![deny(clippy::pedantic)]
+#![allow(clippy::module_name_repetitions)]
+
+/// Things related to Requests (from the client)
+pub mod request;
+/// Errors for this crate.
+pub mod error;
+/// Things related to Responses (from the server)
+pub mod response;
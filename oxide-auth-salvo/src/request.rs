@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+
+use oxide_auth::endpoint::{cookie_value, NormalizedParameter, QueryParameter, WebRequest};
+use salvo::{
+    extract::{Extractible, Metadata},
+    http::{
+        header::{self, CONTENT_TYPE},
+        HeaderMap,
+    },
+    Depot, Request,
+};
+
+use crate::{error::OxideSalvoError, response::OAuthResponse};
+
+#[derive(Clone, Debug, Default)]
+/// Type implementing `WebRequest` as well as `Extractible` for use in route handlers
+///
+/// This type consumes the body of the Request upon extraction, so be careful not to use it in
+/// places you also expect an application payload. A body is only accepted as an
+/// `application/x-www-form-urlencoded` form, requests with any other content type are rejected
+/// with a `400 Bad Request`.
+pub struct OAuthRequest {
+    auth: Option<String>,
+    headers: HeaderMap,
+    query: Option<NormalizedParameter>,
+    body: Option<NormalizedParameter>,
+}
+
+impl OAuthRequest {
+    /// Extract the parts relevant to the flows from a salvo request
+    ///
+    /// # Errors
+    /// Fails with [`OxideSalvoError::Authorization`] if there is more than one `Authorization`
+    /// header, with [`OxideSalvoError::ContentType`] if the request has a content type other than
+    /// an urlencoded form and with [`OxideSalvoError::Request`] if the body can not be read.
+    pub async fn from_request(req: &mut Request) -> Result<Self, OxideSalvoError> {
+        let query = serde_urlencoded::from_str(req.uri().query().unwrap_or("")).ok();
+
+        let mut all_auth = req.headers().get_all("Authorization").into_iter();
+        let optional = all_auth.next();
+
+        let auth = match all_auth.next() {
+            Some(_) => return Err(OxideSalvoError::Authorization),
+            None => optional.and_then(|header| header.to_str().ok().map(str::to_owned)),
+        };
+
+        let body = match req.headers().get(CONTENT_TYPE) {
+            None => None,
+            Some(content_type) if is_form(content_type.to_str().unwrap_or("")) => {
+                let payload = req.payload().await.map_err(|_| OxideSalvoError::Request)?;
+                serde_urlencoded::from_bytes(payload).ok()
+            }
+            Some(_) => return Err(OxideSalvoError::ContentType),
+        };
+
+        Ok(Self {
+            auth,
+            headers: req.headers().clone(),
+            query,
+            body,
+        })
+    }
+
+    /// Fetch the authorization header from the request
+    #[must_use]
+    pub fn authorization_header(&self) -> Option<&str> {
+        self.auth.as_deref()
+    }
+
+    /// Fetch the query for this request
+    #[must_use]
+    pub fn query(&self) -> Option<&NormalizedParameter> {
+        self.query.as_ref()
+    }
+
+    /// Fetch the query mutably
+    pub fn query_mut(&mut self) -> Option<&mut NormalizedParameter> {
+        self.query.as_mut()
+    }
+
+    /// Fetch the body of the request
+    #[must_use]
+    pub fn body(&self) -> Option<&NormalizedParameter> {
+        self.body.as_ref()
+    }
+}
+
+fn is_form(content_type: &str) -> bool {
+    content_type.split(';').next().is_some_and(|mime| {
+        mime.trim()
+            .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+    })
+}
+
+impl WebRequest for OAuthRequest {
+    type Error = OxideSalvoError;
+    type Response = OAuthResponse;
+
+    fn query(&mut self) -> Result<Cow<'_, dyn QueryParameter + 'static>, Self::Error> {
+        self.query
+            .as_ref()
+            .map(|q| Cow::Borrowed(q as &dyn QueryParameter))
+            .ok_or(OxideSalvoError::Request)
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<'_, dyn QueryParameter + 'static>, Self::Error> {
+        self.body
+            .as_ref()
+            .map(|b| Cow::Borrowed(b as &dyn QueryParameter))
+            .ok_or(OxideSalvoError::Request)
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        Ok(self.auth.as_deref().map(Cow::Borrowed))
+    }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        for value in self.headers.get_all(header::COOKIE) {
+            let value = value.to_str().map_err(|_| OxideSalvoError::Request)?;
+            if let Some(value) = cookie_value(value, name) {
+                return Ok(Some(Cow::Borrowed(value)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        let mut all = self.headers.get_all(name).into_iter();
+        let value = match (all.next(), all.next()) {
+            (None, _) => return Ok(None),
+            (Some(value), None) => value,
+            (Some(_), Some(_)) => return Err(OxideSalvoError::Request),
+        };
+
+        value
+            .to_str()
+            .map(|value| Some(Cow::Borrowed(value)))
+            .map_err(|_| OxideSalvoError::Request)
+    }
+}
+
+impl<'ex> Extractible<'ex> for OAuthRequest {
+    fn metadata() -> &'static Metadata {
+        static METADATA: Metadata = Metadata::new("OAuthRequest");
+        &METADATA
+    }
+
+    #[allow(refining_impl_trait)]
+    async fn extract(req: &'ex mut Request, _: &'ex mut Depot) -> Result<Self, OxideSalvoError> {
+        Self::from_request(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use salvo::test::TestClient;
+
+    async fn extract(mut req: Request) -> Result<OAuthRequest, OxideSalvoError> {
+        OAuthRequest::extract(&mut req, &mut Depot::new()).await
+    }
+
+    #[tokio::test]
+    async fn form_body() {
+        let req = TestClient::post("http://localhost/token?state=abc")
+            .raw_form("grant_type=authorization_code&code=xyz")
+            .build();
+        let mut request = extract(req).await.unwrap();
+
+        let body = request.urlbody().unwrap();
+        assert_eq!(body.unique_value("code").as_deref(), Some("xyz"));
+        let query = request.query().unwrap();
+        assert_eq!(query.unique_value("state").as_deref(), Some("abc"));
+    }
+
+    #[tokio::test]
+    async fn other_content_type() {
+        let req = TestClient::post("http://localhost/token")
+            .raw_json(r#"{"grant_type":"authorization_code"}"#)
+            .build();
+        let err = extract(req).await.unwrap_err();
+        assert!(matches!(err, OxideSalvoError::ContentType));
+        assert_eq!(err.status_code(), salvo::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn no_body() {
+        let req = TestClient::get("http://localhost/").bearer_auth("token").build();
+        let mut request = extract(req).await.unwrap();
+
+        assert!(request.urlbody().is_err());
+        assert_eq!(request.authheader().unwrap().as_deref(), Some("Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn duplicate_authorization() {
+        let req = TestClient::get("http://localhost/")
+            .add_header("Authorization", "Bearer first", false)
+            .add_header("Authorization", "Bearer second", false)
+            .build();
+        let err = extract(req).await.unwrap_err();
+        assert!(matches!(err, OxideSalvoError::Authorization));
+        assert_eq!(err.status_code(), salvo::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn custom_header() {
+        let req = TestClient::get("http://localhost/")
+            .add_header("X-Custom", "value", true)
+            .add_header("X-Twice", "first", false)
+            .add_header("X-Twice", "second", false)
+            .build();
+        let mut request = OAuthRequest {
+            headers: req.headers().clone(),
+            ..OAuthRequest::default()
+        };
+
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        assert!(request.header("X-Twice").is_err());
+    }
+
+    #[test]
+    fn cookie() {
+        let req = TestClient::get("http://localhost/")
+            .add_header(header::COOKIE, "theme=dark", false)
+            .add_header(header::COOKIE, "session=abc; token=xyz", false)
+            .build();
+        let mut request = OAuthRequest {
+            headers: req.headers().clone(),
+            ..OAuthRequest::default()
+        };
+
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
+    }
+}
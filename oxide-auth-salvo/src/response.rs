@@ -0,0 +1,178 @@
+use oxide_auth::{endpoint::WebResponse, frontends::dev::Url};
+use salvo::{
+    async_trait,
+    http::{
+        header::{
+            InvalidHeaderValue, CACHE_CONTROL, CONTENT_TYPE, LOCATION, PRAGMA, RETRY_AFTER,
+            WWW_AUTHENTICATE,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    Depot, Request, Response, Writer,
+};
+use crate::error::OxideSalvoError;
+use std::time::Duration;
+
+#[derive(Default, Clone, Debug)]
+/// Type implementing `WebResponse` and `Writer` for use in route handlers
+pub struct OAuthResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Option<String>,
+}
+
+impl OAuthResponse {
+    /// Set the `ContentType` header on a response
+    /// # Errors
+    /// In case the `content_type` cannot be parsed, this will return an [`OxideSalvoError::Header(_)`]
+    pub fn content_type(mut self, content_type: &str) -> Result<Self, OxideSalvoError> {
+        self.headers.insert(
+            CONTENT_TYPE,
+            content_type
+                .parse()
+                .map_err(|err: InvalidHeaderValue| OxideSalvoError::Header(err.to_string()))?,
+        );
+        Ok(self)
+    }
+
+    /// Set the body for the response
+    #[must_use]
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_owned());
+        self
+    }
+}
+
+impl WebResponse for OAuthResponse {
+    type Error = OxideSalvoError;
+
+    fn ok(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::OK;
+        Ok(())
+    }
+
+    fn redirect(&mut self, url: Url) -> Result<(), Self::Error> {
+        self.status = StatusCode::FOUND;
+        self.headers.insert(
+            LOCATION,
+            HeaderValue::from_str(url.as_str())
+                .map_err(|header_err| OxideSalvoError::Header(header_err.to_string()))?,
+        );
+        Ok(())
+    }
+
+    fn client_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::BAD_REQUEST;
+        Ok(())
+    }
+
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::INTERNAL_SERVER_ERROR;
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::TOO_MANY_REQUESTS;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::SERVICE_UNAVAILABLE;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn unauthorized(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::UNAUTHORIZED;
+        self.headers.insert(
+            WWW_AUTHENTICATE,
+            header_value
+                .parse()
+                .map_err(|err: InvalidHeaderValue| OxideSalvoError::Header(err.to_string()))?,
+        );
+        Ok(())
+    }
+
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::FORBIDDEN;
+        self.headers.insert(
+            WWW_AUTHENTICATE,
+            header_value
+                .parse()
+                .map_err(|err: InvalidHeaderValue| OxideSalvoError::Header(err.to_string()))?,
+        );
+        Ok(())
+    }
+
+    fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
+        self.body = Some(text.to_owned());
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        Ok(())
+    }
+
+    fn body_json(&mut self, json: &str) -> Result<(), Self::Error> {
+        self.body = Some(json.to_owned());
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(())
+    }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.headers
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        self.headers.insert(PRAGMA, HeaderValue::from_static("no-cache"));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Writer for OAuthResponse {
+    async fn write(self, _: &mut Request, _: &mut Depot, res: &mut Response) {
+        res.status_code(self.status);
+        res.headers_mut().extend(self.headers);
+        if let Some(body) = self.body {
+            res.body(body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn write(response: OAuthResponse) -> Response {
+        let mut res = Response::new();
+        response
+            .write(&mut Request::new(), &mut Depot::new(), &mut res)
+            .await;
+        res
+    }
+
+    #[tokio::test]
+    async fn too_many_requests() {
+        let mut response = OAuthResponse::default();
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        let response = write(response).await;
+        assert_eq!(response.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+    }
+}
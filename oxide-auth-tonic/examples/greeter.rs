@@ -0,0 +1,79 @@
+//! A gRPC service guarded by a bearer token.
+//!
+//! The example issues a token on startup and prints it. Call the service with it, for example:
+//!
+//! ```text
+//! grpcurl -plaintext -H 'authorization: Bearer <token>' \
+//!     -import-path . -proto greeter.proto 127.0.0.1:50051 greeter.Greeter/SayHello
+//! ```
+//!
+//! The service answers every call with a status greeting the owner of the token. Calls without
+//! a valid token are rejected as unauthenticated.
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use chrono::{Duration, Utc};
+use oxide_auth::primitives::generator::RandomGenerator;
+use oxide_auth::primitives::grant::{Extensions, Grant};
+use oxide_auth::primitives::issuer::{Issuer, TokenMap};
+use oxide_auth_tonic::ResourceInterceptor;
+use tonic::body::Body;
+use tonic::server::NamedService;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::Server;
+use tonic::{Code, Status};
+
+/// A service without messages, the greeting is the message of the status.
+#[derive(Clone)]
+struct Greeter;
+
+impl NamedService for Greeter {
+    const NAME: &'static str = "greeter.Greeter";
+}
+
+impl tower_service::Service<http::Request<Body>> for Greeter {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        // The interceptor has checked the token and inserted its grant.
+        let status = match request.extensions().get::<Grant>() {
+            Some(grant) => Status::new(Code::Ok, format!("Hello, {}", grant.owner_id)),
+            None => Status::internal("not guarded"),
+        };
+        ready(Ok(status.into_http()))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let token = issuer
+        .issue(Grant {
+            owner_id: "dummy".into(),
+            client_id: "LocalClient".into(),
+            scope: "greeting".parse().unwrap(),
+            redirect_uri: "http://localhost:8021/endpoint".parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions: Extensions::new(),
+        })
+        .expect("Issuing to a map does not fail");
+    println!("Use the token: Bearer {}", token.token);
+
+    let issuer = Arc::new(Mutex::new(issuer));
+    let interceptor = ResourceInterceptor::new(issuer, vec!["greeting".parse().unwrap()]);
+
+    Server::builder()
+        .add_service(InterceptedService::new(Greeter, interceptor))
+        .serve("127.0.0.1:50051".parse()?)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+use oxide_auth::frontends::dev::OAuthError;
+use tonic::Status;
+
+#[derive(Debug)]
+/// The error type for Oxide Auth operations
+pub enum WebError {
+    /// Errors occuring in Endpoint operations
+    Endpoint(OAuthError),
+
+    /// The `authorization` metadata was not valid ascii or present more than once
+    Authorization,
+
+    /// A gRPC call carries neither a query nor an urlencoded body
+    NoParameters,
+}
+
+impl fmt::Display for WebError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebError::Endpoint(e) => write!(f, "Endpoint, {}", e),
+            WebError::Authorization => write!(f, "Invalid authorization metadata"),
+            WebError::NoParameters => write!(f, "No query or body parameters in a gRPC call"),
+        }
+    }
+}
+
+impl std::error::Error for WebError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WebError::Endpoint(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<OAuthError> for WebError {
+    fn from(e: OAuthError) -> Self {
+        WebError::Endpoint(e)
+    }
+}
+
+impl From<WebError> for Status {
+    /// Malformed calls are rejected as unauthenticated, only failures of the server itself are
+    /// internal errors. Transient failures are answered with unavailable.
+    fn from(e: WebError) -> Self {
+        match e {
+            WebError::Endpoint(OAuthError::DenySilently)
+            | WebError::Endpoint(OAuthError::BadRequest) => Status::unauthenticated(e.to_string()),
+            WebError::Endpoint(OAuthError::TemporarilyUnavailable(_)) => {
+                Status::unavailable(e.to_string())
+            }
+            WebError::Endpoint(OAuthError::PrimitiveError) => Status::internal(e.to_string()),
+            WebError::Authorization => Status::unauthenticated(e.to_string()),
+            WebError::NoParameters => Status::internal(e.to_string()),
+        }
+    }
+}
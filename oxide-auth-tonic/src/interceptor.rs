@@ -0,0 +1,157 @@
+use std::sync::{Arc, Mutex};
+
+use oxide_auth::frontends::simple::endpoint::{resource_flow, Error};
+use oxide_auth::primitives::issuer::Issuer;
+use oxide_auth::primitives::scope::Scope;
+use tonic::{service::Interceptor, Request, Status};
+
+use crate::{OAuthRequest, WebError};
+
+/// Guards a gRPC service with the resource flow
+///
+/// Each call must carry a bearer token in its `authorization` metadata that the issuer recognizes
+/// and that grants one of the scopes. The [`Grant`] of the token is then inserted into the
+/// extensions of the request. Calls without a valid token are rejected as `unauthenticated`,
+/// tokens with insufficient scope as `permission_denied`.
+///
+/// The issuer is shared so that tokens issued by the authorization server are visible to all
+/// clones of the interceptor.
+///
+/// ```no_run
+/// # use std::sync::{Arc, Mutex};
+/// # use oxide_auth::primitives::issuer::TokenMap;
+/// # use oxide_auth::primitives::generator::RandomGenerator;
+/// # use oxide_auth_tonic::ResourceInterceptor;
+/// let issuer = Arc::new(Mutex::new(TokenMap::new(RandomGenerator::new(16))));
+/// let interceptor = ResourceInterceptor::new(issuer, vec!["greeting".parse().unwrap()]);
+/// // Wrap a service, e.g. `GreeterServer::with_interceptor(greeter, interceptor)`.
+/// # let _ = interceptor;
+/// ```
+///
+/// [`Grant`]: oxide_auth::primitives::grant::Grant
+pub struct ResourceInterceptor<I> {
+    issuer: Arc<Mutex<I>>,
+    scopes: Arc<[Scope]>,
+}
+
+impl<I: Issuer> ResourceInterceptor<I> {
+    /// Guard a service with tokens of the issuer that grant any of the scopes
+    pub fn new(issuer: Arc<Mutex<I>>, scopes: Vec<Scope>) -> Self {
+        ResourceInterceptor {
+            issuer,
+            scopes: scopes.into(),
+        }
+    }
+
+    /// The issuer validating the tokens
+    pub fn issuer(&self) -> &Arc<Mutex<I>> {
+        &self.issuer
+    }
+}
+
+impl<I> Clone for ResourceInterceptor<I> {
+    fn clone(&self) -> Self {
+        ResourceInterceptor {
+            issuer: self.issuer.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<I: Issuer> Interceptor for ResourceInterceptor<I> {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let oauth = OAuthRequest::from_metadata(request.metadata())?;
+        let mut issuer = self
+            .issuer
+            .lock()
+            .map_err(|_| Status::internal("issuer lock poisoned"))?;
+
+        let grant = match resource_flow(&mut *issuer, &self.scopes).execute(oauth) {
+            Ok(grant) => grant,
+            Err(Ok(response)) => return Err(response.into_status()),
+            Err(Err(Error::Web(err))) => return Err(err.into()),
+            Err(Err(Error::OAuth(err))) => return Err(WebError::from(err).into()),
+        };
+
+        drop(issuer);
+        request.extensions_mut().insert(grant);
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{Duration, Utc};
+    use oxide_auth::primitives::generator::RandomGenerator;
+    use oxide_auth::primitives::grant::{Extensions, Grant};
+    use oxide_auth::primitives::issuer::TokenMap;
+    use tonic::Code;
+
+    fn setup() -> (ResourceInterceptor<TokenMap<RandomGenerator>>, String) {
+        let mut issuer = TokenMap::new(RandomGenerator::new(16));
+        let token = issuer
+            .issue(Grant {
+                owner_id: "owner".into(),
+                client_id: "client".into(),
+                scope: "greeting".parse().unwrap(),
+                redirect_uri: "https://client.example/endpoint".parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap()
+            .token;
+
+        let interceptor =
+            ResourceInterceptor::new(Arc::new(Mutex::new(issuer)), vec!["greeting".parse().unwrap()]);
+        (interceptor, token)
+    }
+
+    fn call(
+        interceptor: &mut ResourceInterceptor<TokenMap<RandomGenerator>>, auth: Option<&str>,
+    ) -> Result<Request<()>, Status> {
+        let mut request = Request::new(());
+        if let Some(auth) = auth {
+            request
+                .metadata_mut()
+                .insert("authorization", auth.parse().unwrap());
+        }
+        interceptor.call(request)
+    }
+
+    #[test]
+    fn missing_token() {
+        let (mut interceptor, _) = setup();
+        let status = call(&mut interceptor, None).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        assert!(status.metadata().get("www-authenticate").is_some());
+    }
+
+    #[test]
+    fn invalid_token() {
+        let (mut interceptor, _) = setup();
+        let status = call(&mut interceptor, Some("Bearer NotAToken")).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn insufficient_scope() {
+        let (interceptor, token) = setup();
+        let mut interceptor =
+            ResourceInterceptor::new(interceptor.issuer().clone(), vec!["admin".parse().unwrap()]);
+        let status = call(&mut interceptor, Some(&format!("Bearer {}", token))).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    #[test]
+    fn valid_token() {
+        let (mut interceptor, token) = setup();
+        let request = call(&mut interceptor, Some(&format!("Bearer {}", token))).unwrap();
+        let grant = request
+            .extensions()
+            .get::<Grant>()
+            .expect("Grant should be inserted");
+        assert_eq!(grant.owner_id, "owner");
+    }
+}
@@ -0,0 +1,21 @@
+//! Adaptations and integration for tonic.
+//!
+//! gRPC services do not take part in the authorization flows themselves, they only serve
+//! resources. The [`ResourceInterceptor`] guards a service by validating the bearer token in the
+//! `authorization` metadata of each call and makes the [`Grant`] available to the handler in the
+//! request extensions.
+//!
+//! [`Grant`]: oxide_auth::primitives::grant::Grant
+#![warn(missing_docs)]
+
+mod error;
+pub use error::WebError;
+
+mod interceptor;
+pub use interceptor::ResourceInterceptor;
+
+mod request;
+pub use request::OAuthRequest;
+
+mod response;
+pub use response::OAuthResponse;
@@ -0,0 +1,58 @@
+use std::borrow::Cow;
+
+use oxide_auth::frontends::dev::{QueryParameter, WebRequest};
+use tonic::metadata::MetadataMap;
+
+use crate::{OAuthResponse, WebError};
+
+#[derive(Clone, Debug, Default)]
+/// Type implementing `WebRequest` for the metadata of a gRPC call
+///
+/// A call has no query and its body is a protobuf message, so only the `authorization` metadata is
+/// carried. It is therefore only suitable for the resource flow.
+pub struct OAuthRequest {
+    auth: Option<String>,
+}
+
+impl OAuthRequest {
+    /// Extract the `authorization` metadata of a call
+    ///
+    /// Fails if the value is not ascii or if there is more than one value.
+    pub fn from_metadata(metadata: &MetadataMap) -> Result<Self, WebError> {
+        let mut all_auth = metadata.get_all("authorization").iter();
+        let optional = all_auth.next();
+
+        if all_auth.next().is_some() {
+            return Err(WebError::Authorization);
+        }
+
+        let auth = optional
+            .map(|value| value.to_str().map(str::to_owned))
+            .transpose()
+            .map_err(|_| WebError::Authorization)?;
+
+        Ok(OAuthRequest { auth })
+    }
+
+    /// Fetch the authorization metadata of the call
+    pub fn authorization_header(&self) -> Option<&str> {
+        self.auth.as_deref()
+    }
+}
+
+impl WebRequest for OAuthRequest {
+    type Error = WebError;
+    type Response = OAuthResponse;
+
+    fn query(&mut self) -> Result<Cow<'_, dyn QueryParameter + 'static>, Self::Error> {
+        Err(WebError::NoParameters)
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<'_, dyn QueryParameter + 'static>, Self::Error> {
+        Err(WebError::NoParameters)
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        Ok(self.auth.as_deref().map(Cow::Borrowed))
+    }
+}
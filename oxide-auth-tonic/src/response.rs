@@ -0,0 +1,106 @@
+use oxide_auth::frontends::dev::{Url, WebResponse};
+use tonic::{metadata::MetadataValue, Code, Status};
+
+use crate::WebError;
+
+#[derive(Clone, Debug)]
+/// Type implementing `WebResponse` that is converted into a gRPC `Status`
+///
+/// gRPC has no redirects or response bodies outside of messages, so only the outcome and the
+/// `www-authenticate` challenge of a response are kept.
+pub struct OAuthResponse {
+    code: Code,
+    www_authenticate: Option<String>,
+    message: Option<String>,
+}
+
+impl Default for OAuthResponse {
+    fn default() -> Self {
+        OAuthResponse {
+            code: Code::Ok,
+            www_authenticate: None,
+            message: None,
+        }
+    }
+}
+
+impl OAuthResponse {
+    /// The `www-authenticate` challenge of an unauthorized response
+    pub fn www_authenticate(&self) -> Option<&str> {
+        self.www_authenticate.as_deref()
+    }
+
+    /// Convert the response into a gRPC status
+    ///
+    /// Requests lacking a valid token are `unauthenticated` while tokens without the required
+    /// scope are `permission_denied`. The challenge is passed along in the `www-authenticate`
+    /// metadata of the status.
+    pub fn into_status(self) -> Status {
+        let code = match self.www_authenticate.as_deref() {
+            Some(challenge) if challenge.contains("insufficient_scope") => Code::PermissionDenied,
+            Some(_) => Code::Unauthenticated,
+            None => self.code,
+        };
+
+        let message = self.message.unwrap_or_else(|| match code {
+            Code::PermissionDenied => "insufficient scope".to_owned(),
+            Code::Unauthenticated => "missing or invalid access token".to_owned(),
+            _ => String::new(),
+        });
+
+        let mut status = Status::new(code, message);
+        if let Some(value) = self
+            .www_authenticate
+            .and_then(|challenge| MetadataValue::try_from(challenge).ok())
+        {
+            status.metadata_mut().insert("www-authenticate", value);
+        }
+        status
+    }
+}
+
+impl WebResponse for OAuthResponse {
+    type Error = WebError;
+
+    fn ok(&mut self) -> Result<(), Self::Error> {
+        self.code = Code::Ok;
+        Ok(())
+    }
+
+    fn redirect(&mut self, _: Url) -> Result<(), Self::Error> {
+        self.code = Code::Internal;
+        Ok(())
+    }
+
+    fn client_error(&mut self) -> Result<(), Self::Error> {
+        self.code = Code::InvalidArgument;
+        Ok(())
+    }
+
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.code = Code::Internal;
+        Ok(())
+    }
+
+    fn unauthorized(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.code = Code::Unauthenticated;
+        self.www_authenticate = Some(header_value.to_owned());
+        Ok(())
+    }
+
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.code = Code::PermissionDenied;
+        self.www_authenticate = Some(header_value.to_owned());
+        Ok(())
+    }
+
+    fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
+        self.message = Some(text.to_owned());
+        Ok(())
+    }
+
+    fn body_json(&mut self, data: &str) -> Result<(), Self::Error> {
+        self.message = Some(data.to_owned());
+        Ok(())
+    }
+}
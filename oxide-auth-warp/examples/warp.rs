@@ -0,0 +1,148 @@
+#[rustfmt::skip]
+#[path = "../../examples/support/generic.rs"]
+mod support;
+
+use std::sync::{Arc, Mutex};
+
+use oxide_auth::endpoint::{
+    AccessTokenFlow, AuthorizationFlow, OwnerConsent, ResourceFlow, QueryParameter, Solicitation,
+    WebResponse,
+};
+use oxide_auth::frontends::dev::Url;
+use oxide_auth::frontends::simple::endpoint::{FnSolicitor, Generic};
+use oxide_auth::primitives::prelude::*;
+use oxide_auth_warp::{oauth_request, recover, OAuthRequest, OAuthResponse, WebError};
+use warp::{reply::Response, Filter, Reply};
+
+type Solicitor = FnSolicitor<fn(&mut OAuthRequest, Solicitation) -> OwnerConsent<OAuthResponse>>;
+
+type State = Arc<
+    Mutex<
+        Generic<
+            ClientMap,
+            AuthMap<RandomGenerator>,
+            TokenMap<RandomGenerator>,
+            Solicitor,
+            Vec<Scope>,
+            fn() -> OAuthResponse,
+        >,
+    >,
+>;
+
+/// Example of a warp server supporting oauth.
+#[tokio::main]
+async fn main() {
+    let mut clients = ClientMap::new();
+    // Register a dummy client instance
+    clients.register_client(Client::public(
+        "LocalClient",                                                   // Client id
+        "http://localhost:8021/endpoint".parse::<Url>().unwrap().into(), // Redirection url
+        "default".parse().unwrap(),
+    )); // Allowed client scope
+
+    let state: State = Arc::new(Mutex::new(Generic {
+        registrar: clients,
+        // Authorization tokens are 16 byte random keys to a memory hash map.
+        authorizer: AuthMap::new(RandomGenerator::new(16)),
+        // Bearer tokens are also random generated but 256-bit tokens, since they live longer.
+        issuer: TokenMap::new(RandomGenerator::new(32)),
+        solicitor: FnSolicitor(consent),
+        scopes: vec!["default".parse().unwrap()],
+        response: OAuthResponse::default,
+    }));
+    let with_state = warp::any().map(move || state.clone());
+
+    let authorize = warp::path("authorize")
+        .and(warp::get().or(warp::post()).unify())
+        .and(oauth_request())
+        .and(with_state.clone())
+        .map(authorize);
+
+    let token = warp::path("token")
+        .and(warp::post())
+        .and(oauth_request())
+        .and(with_state.clone())
+        .map(token);
+
+    let protected = warp::path::end()
+        .and(oauth_request())
+        .and(with_state)
+        .map(protected);
+
+    let routes = authorize.or(token).or(protected).recover(recover);
+
+    support::open_in_browser();
+    warp::serve(routes).run(([127, 0, 0, 1], 8020)).await;
+}
+
+fn authorize(request: OAuthRequest, state: State) -> Response {
+    let mut endpoint = state.lock().unwrap();
+    let response = AuthorizationFlow::prepare(&mut *endpoint).and_then(|mut flow| flow.execute(request));
+    reply(response.map_err(WebError::from))
+}
+
+fn token(request: OAuthRequest, state: State) -> Response {
+    let mut endpoint = state.lock().unwrap();
+    let response = AccessTokenFlow::prepare(&mut *endpoint).and_then(|mut flow| flow.execute(request));
+    reply(response.map_err(WebError::from))
+}
+
+fn protected(request: OAuthRequest, state: State) -> Response {
+    let mut endpoint = state.lock().unwrap();
+    let mut flow = match ResourceFlow::prepare(&mut *endpoint) {
+        Ok(flow) => flow,
+        Err(error) => return WebError::from(error).into_response(),
+    };
+
+    match flow.execute(request) {
+        Ok(_grant) => "Hello, world".into_response(),
+        Err(Ok(response)) => {
+            let text = "<html>
+This page should be accessed via an oauth token from the client in the example. Click
+<a href=\"http://localhost:8020/authorize?response_type=code&client_id=LocalClient\">
+here</a> to begin the authorization process.
+</html>";
+            reply(
+                response
+                    .content_type("text/html")
+                    .map(|response| response.body(text)),
+            )
+        }
+        Err(Err(error)) => WebError::from(error).into_response(),
+    }
+}
+
+fn reply(response: Result<OAuthResponse, WebError>) -> Response {
+    match response {
+        Ok(response) => response.into_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
+/// A simple implementation of an 'owner solicitor'.
+///
+/// Displays a page to the user asking for their permission to proceed. The submitted form adds
+/// either an `allow` or a `deny` parameter and actually completes the flow.
+fn consent(request: &mut OAuthRequest, solicitation: Solicitation) -> OwnerConsent<OAuthResponse> {
+    let query = request.query();
+    if query.and_then(|query| query.unique_value("allow")).is_some() {
+        // No real user authentication is done here, in production you MUST use session keys or
+        // equivalent.
+        return OwnerConsent::Authorized("dummy user".to_string());
+    }
+
+    if query.and_then(|query| query.unique_value("deny")).is_some() {
+        return OwnerConsent::Denied;
+    }
+
+    let mut response = OAuthResponse::default();
+    let page = support::consent_page_html("/authorize", solicitation);
+    let html = response
+        .ok()
+        .and_then(|()| response.body_text(&page))
+        .and_then(|()| response.content_type("text/html"));
+    match html {
+        Ok(response) => OwnerConsent::InProgress(response),
+        Err(error) => OwnerConsent::Error(error),
+    }
+}
@@ -0,0 +1,154 @@
+use oxide_auth::frontends::{dev::OAuthError, simple::endpoint::Error};
+use warp::{
+    http::{
+        header::{self, HeaderValue, InvalidHeaderValue},
+        StatusCode,
+    },
+    reject::{PayloadTooLarge, Reject, Rejection},
+    reply::{self, Reply, Response},
+};
+
+use crate::OAuthRequest;
+
+#[derive(Debug)]
+/// The error type for Oxide Auth operations
+pub enum WebError {
+    /// Errors occuring in Endpoint operations
+    Endpoint(OAuthError),
+
+    /// A header of the response could not be set
+    Header(InvalidHeaderValue),
+
+    /// Request query was absent or could not be parsed
+    Query,
+
+    /// Request body was absent or could not be parsed as a form
+    Body,
+
+    /// The Authorization header was invalid
+    Authorization,
+
+    /// A header of the request was repeated or not valid text
+    Encoding,
+}
+
+impl WebError {
+    /// The status code of the response to this error
+    ///
+    /// Malformed requests are answered with a client error, only failures of the server itself
+    /// are internal server errors. Transient failures are answered with service unavailable.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            WebError::Endpoint(OAuthError::DenySilently) => StatusCode::BAD_REQUEST,
+            WebError::Endpoint(OAuthError::BadRequest) => StatusCode::BAD_REQUEST,
+            WebError::Endpoint(OAuthError::PrimitiveError) => StatusCode::INTERNAL_SERVER_ERROR,
+            WebError::Endpoint(OAuthError::TemporarilyUnavailable(_)) => StatusCode::SERVICE_UNAVAILABLE,
+            WebError::Header(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            WebError::Query => StatusCode::BAD_REQUEST,
+            WebError::Body => StatusCode::BAD_REQUEST,
+            WebError::Authorization => StatusCode::BAD_REQUEST,
+            WebError::Encoding => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn reply(&self) -> Response {
+        let mut response = reply::with_status(self.to_string(), self.status_code()).into_response();
+        if let WebError::Endpoint(OAuthError::TemporarilyUnavailable(retry_after)) = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+        response
+    }
+}
+
+impl std::fmt::Display for WebError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            WebError::Endpoint(ref e) => write!(f, "Endpoint, {}", e),
+            WebError::Header(ref e) => write!(f, "Couldn't set header, {}", e),
+            WebError::Query => write!(f, "No query present"),
+            WebError::Body => write!(f, "No body present"),
+            WebError::Authorization => write!(f, "Request has invalid Authorization headers"),
+            WebError::Encoding => write!(f, "Request has an invalid header"),
+        }
+    }
+}
+
+impl std::error::Error for WebError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            WebError::Endpoint(ref e) => e.source(),
+            WebError::Header(ref e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl Reject for WebError {}
+
+impl Reply for WebError {
+    fn into_response(self) -> Response {
+        self.reply()
+    }
+}
+
+/// Turn rejections by the filters of this crate into error responses
+///
+/// Use this with [`Filter::recover`] so that malformed requests are answered with a client error
+/// instead of falling through to the next route. Bodies over the limit of
+/// [`oauth_request_with_limit`](crate::oauth_request_with_limit) are answered with `413`. Other
+/// rejections are passed on unchanged.
+///
+/// [`Filter::recover`]: https://docs.rs/warp/0.3/warp/trait.Filter.html#method.recover
+pub async fn recover(rejection: Rejection) -> Result<Response, Rejection> {
+    if let Some(error) = rejection.find::<WebError>() {
+        return Ok(error.reply());
+    }
+
+    match rejection.find::<PayloadTooLarge>() {
+        Some(error) => {
+            Ok(reply::with_status(error.to_string(), StatusCode::PAYLOAD_TOO_LARGE).into_response())
+        }
+        None => Err(rejection),
+    }
+}
+
+impl From<Error<OAuthRequest>> for WebError {
+    fn from(e: Error<OAuthRequest>) -> Self {
+        match e {
+            Error::Web(e) => e,
+            Error::OAuth(e) => e.into(),
+        }
+    }
+}
+
+impl From<OAuthError> for WebError {
+    fn from(e: OAuthError) -> Self {
+        WebError::Endpoint(e)
+    }
+}
+
+impl From<InvalidHeaderValue> for WebError {
+    fn from(e: InvalidHeaderValue) -> Self {
+        Self::Header(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn temporarily_unavailable() {
+        let error = WebError::from(OAuthError::TemporarilyUnavailable(Duration::from_secs(30)));
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+
+        let response = WebError::from(OAuthError::PrimitiveError).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+    }
+}
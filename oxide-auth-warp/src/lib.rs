@@ -0,0 +1,11 @@
+//! Adaptations and integration for Warp.
+#![warn(missing_docs)]
+
+mod error;
+pub use error::{recover, WebError};
+
+mod request;
+pub use request::{oauth_request, oauth_request_with_limit, OAuthRequest, DEFAULT_BODY_LIMIT};
+
+mod response;
+pub use response::OAuthResponse;
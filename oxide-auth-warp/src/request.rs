@@ -0,0 +1,342 @@
+use std::borrow::Cow;
+
+use oxide_auth::endpoint::cookie_value;
+use oxide_auth::frontends::dev::{NormalizedParameter, QueryParameter, WebRequest};
+use warp::{
+    http::{header, HeaderMap},
+    hyper::body::Bytes,
+    Filter, Rejection,
+};
+
+use crate::{OAuthResponse, WebError};
+
+#[derive(Clone, Debug, Default)]
+/// Type implementing `WebRequest`, extracted in route handlers with [oauth_request]
+///
+/// The extraction buffers the body of the request as an urlencoded form, so be careful not to
+/// use it in places you also expect an application payload. The unparsed body is kept as well
+/// and available to extensions through `WebRequest::raw_body`.
+pub struct OAuthRequest {
+    auth: Option<String>,
+    headers: HeaderMap,
+    query: Option<NormalizedParameter>,
+    body: Option<NormalizedParameter>,
+    raw_body: Option<Bytes>,
+}
+
+/// The default limit of the body size in bytes used by [oauth_request]
+///
+/// The forms of the OAuth flows are small, this leaves plenty of room for extensions.
+pub const DEFAULT_BODY_LIMIT: u64 = 64 * 1024;
+
+/// A filter extracting an [OAuthRequest]
+///
+/// The query and the form body are optional, they are only required by the flows that read
+/// them. The body is only read as a form when its content type is
+/// `application/x-www-form-urlencoded`. A request with more than one `Authorization` header is
+/// rejected with [WebError::Authorization], which [recover](crate::recover) turns into a `400`
+/// response.
+///
+/// Bodies larger than [DEFAULT_BODY_LIMIT] are rejected, see [oauth_request_with_limit].
+pub fn oauth_request() -> impl Filter<Extract = (OAuthRequest,), Error = Rejection> + Clone {
+    oauth_request_with_limit(DEFAULT_BODY_LIMIT)
+}
+
+/// A filter extracting an [OAuthRequest] whose body is at most `limit` bytes
+///
+/// The body is buffered in memory, so it is bounded by its `Content-Length`. Requests declaring a
+/// larger body are rejected before it is read, which [recover](crate::recover) turns into a `413`
+/// response. The body of a request without `Content-Length` is not read at all.
+pub fn oauth_request_with_limit(
+    limit: u64,
+) -> impl Filter<Extract = (OAuthRequest,), Error = Rejection> + Clone {
+    let body = warp::body::content_length_limit(limit)
+        .and(warp::body::bytes())
+        .or(warp::header::optional::<String>("content-length").and_then(
+            |length: Option<String>| async move {
+                match length {
+                    None => Ok(Bytes::new()),
+                    // Already rejected by the limit.
+                    Some(_) => Err(warp::reject()),
+                }
+            },
+        ))
+        .unify();
+
+    let query = warp::query::raw()
+        .map(|query: String| serde_urlencoded::from_str(&query).ok())
+        .or(warp::any().map(|| None))
+        .unify();
+
+    warp::header::headers_cloned().and(query).and(body).and_then(
+        |headers: HeaderMap, query, raw_body: Bytes| async move {
+            let auth = authorization(&headers).map_err(warp::reject::custom)?;
+            let body = if is_form(&headers) {
+                serde_urlencoded::from_bytes(&raw_body).ok()
+            } else {
+                None
+            };
+
+            Ok::<_, Rejection>(OAuthRequest {
+                auth,
+                headers,
+                query,
+                body,
+                raw_body: Some(raw_body),
+            })
+        },
+    )
+}
+
+fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| {
+            mime.trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+fn authorization(headers: &HeaderMap) -> Result<Option<String>, WebError> {
+    let mut all_auth = headers.get_all(header::AUTHORIZATION).iter();
+    let optional = all_auth.next();
+
+    if all_auth.next().is_some() {
+        return Err(WebError::Authorization);
+    }
+
+    Ok(optional.and_then(|hv| hv.to_str().ok().map(str::to_owned)))
+}
+
+impl OAuthRequest {
+    /// Fetch the authorization header from the request
+    pub fn authorization_header(&self) -> Option<&str> {
+        self.auth.as_deref()
+    }
+
+    /// Fetch the query for this request
+    pub fn query(&self) -> Option<&NormalizedParameter> {
+        self.query.as_ref()
+    }
+
+    /// Fetch the query mutably
+    pub fn query_mut(&mut self) -> Option<&mut NormalizedParameter> {
+        self.query.as_mut()
+    }
+
+    /// Fetch the body of the request
+    pub fn body(&self) -> Option<&NormalizedParameter> {
+        self.body.as_ref()
+    }
+}
+
+impl WebRequest for OAuthRequest {
+    type Error = WebError;
+    type Response = OAuthResponse;
+
+    fn query(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.query
+            .as_ref()
+            .map(|q| Cow::Borrowed(q as &dyn QueryParameter))
+            .ok_or(WebError::Query)
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.body
+            .as_ref()
+            .map(|b| Cow::Borrowed(b as &dyn QueryParameter))
+            .ok_or(WebError::Body)
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+        Ok(self.auth.as_deref().map(Cow::Borrowed))
+    }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        for value in self.headers.get_all(header::COOKIE) {
+            let value = value.to_str().map_err(|_| WebError::Encoding)?;
+            if let Some(value) = cookie_value(value, name) {
+                return Ok(Some(Cow::Borrowed(value)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        let mut all = self.headers.get_all(name).iter();
+        let value = match (all.next(), all.next()) {
+            (None, _) => return Ok(None),
+            (Some(value), None) => value,
+            (Some(_), Some(_)) => return Err(WebError::Encoding),
+        };
+
+        value
+            .to_str()
+            .map(|value| Some(Cow::Borrowed(value)))
+            .map_err(|_| WebError::Encoding)
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxide_auth::frontends::dev::QueryParameter;
+    use warp::http::StatusCode;
+
+    #[tokio::test]
+    async fn extracts_request() {
+        let request = warp::test::request()
+            .method("POST")
+            .path("/token?client_id=example")
+            .header(header::AUTHORIZATION, "Basic secret")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body("grant_type=authorization_code")
+            .filter(&oauth_request())
+            .await
+            .unwrap();
+
+        assert_eq!(request.authorization_header(), Some("Basic secret"));
+        let query = request.query().unwrap();
+        assert_eq!(query.unique_value("client_id").as_deref(), Some("example"));
+        let body = request.body().unwrap();
+        assert_eq!(
+            body.unique_value("grant_type").as_deref(),
+            Some("authorization_code")
+        );
+        assert_eq!(request.raw_body(), Some(&b"grant_type=authorization_code"[..]));
+    }
+
+    #[tokio::test]
+    async fn optional_parts() {
+        let request = warp::test::request()
+            .path("/")
+            .filter(&oauth_request())
+            .await
+            .unwrap();
+
+        assert!(request.authorization_header().is_none());
+        assert!(request.query().is_none());
+        assert!(request.body().is_none());
+    }
+
+    #[tokio::test]
+    async fn not_a_form() {
+        let mut request = warp::test::request()
+            .method("POST")
+            .path("/token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(r#"{"grant_type":"authorization_code"}"#)
+            .filter(&oauth_request())
+            .await
+            .unwrap();
+
+        assert!(request.body().is_none());
+        assert_eq!(
+            request.raw_body(),
+            Some(&br#"{"grant_type":"authorization_code"}"#[..])
+        );
+        let error = request.urlbody().err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn request_custom_header() {
+        let mut request = warp::test::request()
+            .path("/")
+            .header("X-Custom", "value")
+            .filter(&oauth_request())
+            .await
+            .unwrap();
+        // The test request builder replaces repeated headers.
+        request.headers.append("X-Twice", "first".parse().unwrap());
+        request.headers.append("X-Twice", "second".parse().unwrap());
+
+        assert_eq!(request.header("x-custom").unwrap().as_deref(), Some("value"));
+        assert_eq!(request.header("X-Missing").unwrap(), None);
+        let error = request.header("X-Twice").err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn duplicate_authorization() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::AUTHORIZATION, "Bearer first".parse().unwrap());
+        assert_eq!(authorization(&headers).unwrap().as_deref(), Some("Bearer first"));
+
+        headers.append(header::AUTHORIZATION, "Bearer second".parse().unwrap());
+        assert!(matches!(authorization(&headers), Err(WebError::Authorization)));
+    }
+
+    #[tokio::test]
+    async fn rejection_recovered() {
+        let route = warp::any()
+            .and_then(|| async { Err::<String, _>(warp::reject::custom(WebError::Authorization)) })
+            .recover(crate::recover);
+        let response = warp::test::request().path("/").reply(&route).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn body_limit() {
+        let form = format!("grant_type=authorization_code&code={}", "a".repeat(64));
+        let filter = oauth_request_with_limit(32);
+
+        let request = warp::test::request()
+            .method("POST")
+            .path("/token")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body("grant_type=authorization_code")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert!(request.body().is_some());
+
+        let route = filter.map(|_| "accepted").recover(crate::recover);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/token")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(form)
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn default_body_limit() {
+        let route = oauth_request().map(|_| "accepted").recover(crate::recover);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/token")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(vec![b'a'; DEFAULT_BODY_LIMIT as usize + 1])
+            .reply(&route)
+            .await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn request_cookie() {
+        let mut request = warp::test::request()
+            .path("/")
+            .header(header::COOKIE, "session=abc; token=xyz")
+            .filter(&oauth_request())
+            .await
+            .unwrap();
+        request
+            .headers
+            .append(header::COOKIE, "theme=dark".parse().unwrap());
+
+        assert_eq!(request.cookie("token").unwrap().as_deref(), Some("xyz"));
+        assert_eq!(request.cookie("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(request.cookie("missing").unwrap(), None);
+    }
+}
@@ -0,0 +1,145 @@
+use oxide_auth::frontends::dev::{Url, WebResponse};
+use std::time::Duration;
+use warp::{
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        StatusCode,
+    },
+    reply::{Reply, Response},
+};
+
+use crate::WebError;
+
+#[derive(Default, Clone, Debug)]
+/// Type implementing `WebResponse` and `Reply` for use in route handlers
+pub struct OAuthResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Option<String>,
+}
+
+impl OAuthResponse {
+    /// Set the `ContentType` header on a response
+    pub fn content_type(mut self, content_type: &str) -> Result<Self, WebError> {
+        self.headers
+            .insert(header::CONTENT_TYPE, content_type.try_into()?);
+        Ok(self)
+    }
+
+    /// Set the body for the response
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_owned());
+        self
+    }
+}
+
+impl WebResponse for OAuthResponse {
+    type Error = WebError;
+
+    fn ok(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::OK;
+        Ok(())
+    }
+
+    fn redirect(&mut self, url: Url) -> Result<(), Self::Error> {
+        self.status = StatusCode::FOUND;
+        self.headers.insert(header::LOCATION, url.as_str().try_into()?);
+        Ok(())
+    }
+
+    fn client_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::BAD_REQUEST;
+        Ok(())
+    }
+
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = StatusCode::INTERNAL_SERVER_ERROR;
+        Ok(())
+    }
+
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::TOO_MANY_REQUESTS;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(header::RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = StatusCode::SERVICE_UNAVAILABLE;
+        match retry_after {
+            Some(retry_after) => {
+                self.headers
+                    .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+            }
+            None => {
+                self.headers.remove(header::RETRY_AFTER);
+            }
+        }
+        Ok(())
+    }
+
+    fn unauthorized(&mut self, kind: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::UNAUTHORIZED;
+        self.headers.insert(header::WWW_AUTHENTICATE, kind.try_into()?);
+        Ok(())
+    }
+
+    fn forbidden(&mut self, kind: &str) -> Result<(), Self::Error> {
+        self.status = StatusCode::FORBIDDEN;
+        self.headers.insert(header::WWW_AUTHENTICATE, kind.try_into()?);
+        Ok(())
+    }
+
+    fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
+        self.body = Some(text.to_owned());
+        self.headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        Ok(())
+    }
+
+    fn body_json(&mut self, json: &str) -> Result<(), Self::Error> {
+        self.body = Some(json.to_owned());
+        self.headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(())
+    }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.headers
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        self.headers
+            .insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+        Ok(())
+    }
+}
+
+impl Reply for OAuthResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(self.body.unwrap_or_default().into());
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_requests() {
+        let mut response = OAuthResponse::default();
+        response.too_many_requests(Some(Duration::from_secs(30))).unwrap();
+
+        let response = response.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    }
+}
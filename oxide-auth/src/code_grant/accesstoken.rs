@@ -8,10 +8,13 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 
 use crate::code_grant::error::{AccessTokenError, AccessTokenErrorType};
+use crate::code_grant::extensions::Pkce;
+use crate::endpoint::FlowError;
 use crate::primitives::authorizer::Authorizer;
 use crate::primitives::issuer::{IssuedToken, Issuer};
 use crate::primitives::grant::{Extensions, Grant};
-use crate::primitives::registrar::{Registrar, RegistrarError};
+use crate::primitives::jwt::ClientAssertion;
+use crate::primitives::registrar::{GrantType, Registrar, RegistrarError};
 use crate::primitives::scope::Scope;
 
 /// Token Response
@@ -33,6 +36,12 @@ pub struct TokenResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expires_in: Option<i64>,
 
+    /// The remaining lifetime in seconds of the refresh token.
+    ///
+    /// This is not a standardized parameter and only sent when explicitly enabled on the flow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_expires_in: Option<i64>,
+
     /// The scope, which limits the permissions on the access token.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scope: Option<String>,
@@ -89,6 +98,37 @@ pub trait Request {
     fn allow_credentials_in_body(&self) -> bool {
         false
     }
+
+    /// The unparsed body of the request, if the frontend provided it.
+    ///
+    /// Returning `None` is the default implementation.
+    fn raw_body(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// The proof of possession in the `DPoP` header, see `DpopValidator`.
+    ///
+    /// Returning `None` is the default implementation.
+    fn dpop_proof(&self) -> Option<Cow<'_, str>> {
+        None
+    }
+
+    /// The DER encoded certificate the client authenticated its TLS connection with.
+    ///
+    /// Clients that send no other credentials authenticate with it, see
+    /// `Registrar::check_certificate`. Returning `None` is the default implementation.
+    fn client_certificate(&self) -> Option<Cow<'_, [u8]>> {
+        None
+    }
+
+    /// Whether a code issued with a PKCE challenge can only be redeemed with a `code_verifier`.
+    ///
+    /// The verifier itself is checked by the `Pkce` extension. This only ensures that a stored
+    /// challenge is not ignored when the extension is missing from the endpoint. Returning `false`
+    /// is the default implementation.
+    fn require_pkce_verifier(&self) -> bool {
+        false
+    }
 }
 
 /// A system of addons provided additional data.
@@ -147,8 +187,13 @@ enum Credentials<'a> {
     ///
     /// This is a security issue, only one attempt must be made per request.
     Duplicate,
+    /// A JWT assertion was offered, see RFC 7523.
+    Asserted { client_id: &'a str, assertion: &'a str },
 }
 
+/// The `client_assertion_type` of JWT assertions authenticating a client.
+const JWT_BEARER_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
 /// Access token issuing process
 ///
 /// This state machine will go through four phases. On creation, the request will be validated and
@@ -174,10 +219,12 @@ enum Credentials<'a> {
 ///     2.1. Authenticate the client
 ///     2.2. If there was no authentication, assert token does not require authentication
 ///     2.3. Recover the current grant corresponding to the `code`
-///     2.4. Check the intrinsic validity (scope)
+///     2.4. Check the intrinsic validity (scope), including an answer to a PKCE challenge
 /// 3. Query the backend for a new (bearer) token
 pub struct AccessToken {
     state: AccessTokenState,
+    /// Whether a stored PKCE challenge is unanswered by the request.
+    missing_verifier: bool,
 }
 
 /// Inner state machine for access token
@@ -190,6 +237,20 @@ enum AccessTokenState {
         // TODO: parsing here is unnecessary if we compare a string representation.
         redirect_uri: url::Url,
     },
+    /// State after a request with a client assertion has been validated.
+    AuthenticateAssertion {
+        client: String,
+        assertion: String,
+        code: String,
+        redirect_uri: url::Url,
+    },
+    /// State after a request authenticated only by its client certificate has been validated.
+    AuthenticateCertificate {
+        client: String,
+        certificate: Vec<u8>,
+        code: String,
+        redirect_uri: url::Url,
+    },
     Recover {
         client: String,
         code: String,
@@ -209,7 +270,7 @@ enum AccessTokenState {
 pub enum Input<'req> {
     /// The request to be processed.
     Request(&'req dyn Request),
-    /// Positively answer an authentication query.
+    /// Positively answer an authentication query, with passdata or an assertion.
     Authenticated,
     /// Provide the queried refresh token.
     Recovered(Option<Box<Grant>>),
@@ -240,6 +301,26 @@ pub enum Output<'machine> {
         /// The supplied passdata/password.
         passdata: Option<&'machine [u8]>,
     },
+    /// The registrar should authenticate a client with a JWT assertion.
+    ///
+    /// Fulfilled by `Input::Authenticated`. In an unsuccessful case, the executor should not
+    /// continue and discard the flow.
+    AuthenticateAssertion {
+        /// The to-be-authenticated client.
+        client: &'machine str,
+        /// The supplied `client_assertion`.
+        assertion: &'machine str,
+    },
+    /// The registrar should authenticate a client with its TLS client certificate.
+    ///
+    /// Fulfilled by `Input::Authenticated`. In an unsuccessful case, the executor should not
+    /// continue and discard the flow.
+    AuthenticateCertificate {
+        /// The to-be-authenticated client.
+        client: &'machine str,
+        /// The DER encoded client certificate.
+        certificate: &'machine [u8],
+    },
     /// The issuer should try to recover the grant for this `code`
     ///
     /// Fulfilled by `Input::Recovered`.
@@ -275,8 +356,11 @@ pub enum Output<'machine> {
 impl AccessToken {
     /// Create the state machine. validating the request in the process
     pub fn new(request: &dyn Request) -> Self {
+        let (state, missing_verifier) =
+            Self::validate(request).unwrap_or_else(|err| (AccessTokenState::Err(err), false));
         AccessToken {
-            state: Self::validate(request).unwrap_or_else(AccessTokenState::Err),
+            state,
+            missing_verifier,
         }
     }
 
@@ -293,12 +377,31 @@ impl AccessToken {
                 },
                 Input::Authenticated,
             ) => Self::authenticated(client, code, redirect_uri),
+            (
+                AccessTokenState::AuthenticateAssertion {
+                    client,
+                    code,
+                    redirect_uri,
+                    ..
+                },
+                Input::Authenticated,
+            ) => Self::authenticated(client, code, redirect_uri),
+            (
+                AccessTokenState::AuthenticateCertificate {
+                    client,
+                    code,
+                    redirect_uri,
+                    ..
+                },
+                Input::Authenticated,
+            ) => Self::authenticated(client, code, redirect_uri),
             (
                 AccessTokenState::Recover {
                     client, redirect_uri, ..
                 },
                 Input::Recovered(grant),
-            ) => Self::recovered(client, redirect_uri, grant).unwrap_or_else(AccessTokenState::Err),
+            ) => Self::recovered(client, redirect_uri, grant, self.missing_verifier)
+                .unwrap_or_else(AccessTokenState::Err),
             (AccessTokenState::Extend { saved_params, .. }, Input::Extended { access_extensions }) => {
                 Self::issue(saved_params, access_extensions)
             }
@@ -319,6 +422,12 @@ impl AccessToken {
                 client,
                 passdata: passdata.as_ref().map(Vec::as_slice),
             },
+            AccessTokenState::AuthenticateAssertion {
+                client, assertion, ..
+            } => Output::AuthenticateAssertion { client, assertion },
+            AccessTokenState::AuthenticateCertificate {
+                client, certificate, ..
+            } => Output::AuthenticateCertificate { client, certificate },
             AccessTokenState::Recover { code, .. } => Output::Recover { code },
             AccessTokenState::Extend { extensions, .. } => Output::Extend { extensions },
             AccessTokenState::Issue { grant } => Output::Issue { grant },
@@ -332,7 +441,7 @@ impl AccessToken {
         )
     }
 
-    fn validate(request: &dyn Request) -> Result<AccessTokenState> {
+    fn validate(request: &dyn Request) -> Result<(AccessTokenState, bool)> {
         if !request.valid() {
             return Err(Error::invalid());
         }
@@ -340,6 +449,20 @@ impl AccessToken {
         let authorization = request.authorization();
         let client_id = request.client_id();
         let client_secret = request.extension("client_secret");
+        let assertion = match (
+            request.extension("client_assertion_type"),
+            request.extension("client_assertion"),
+        ) {
+            (None, None) => None,
+            (Some(kind), Some(assertion)) if kind == JWT_BEARER_ASSERTION => Some(assertion),
+            _ => return Err(Error::invalid()),
+        };
+
+        // Without a `client_id` the client is identified by the subject of its assertion.
+        let subject = match (&client_id, &assertion) {
+            (None, Some(assertion)) => ClientAssertion::subject(assertion),
+            _ => None,
+        };
 
         let mut credentials = Credentials::None;
 
@@ -356,19 +479,63 @@ impl AccessToken {
                 Some(auth) if request.allow_credentials_in_body() => {
                     credentials.authenticate(client_id.as_ref(), auth.as_ref().as_bytes())
                 }
+                // The assertion authenticates the client named by the parameter.
+                Some(_) | None if assertion.is_some() => {}
                 // Ignore parameter if not allowed.
                 Some(_) | None => credentials.unauthenticated(client_id.as_ref()),
             }
         }
 
+        if let Some(assertion) = &assertion {
+            let client_id = client_id.as_deref().or(subject.as_deref());
+            let client_id = client_id.ok_or_else(Error::invalid)?;
+            credentials.assert(client_id, assertion.as_ref());
+        }
+
         match request.grant_type() {
             Some(ref cow) if cow == "authorization_code" => (),
             None => return Err(Error::invalid()),
             Some(_) => return Err(Error::invalid_with(AccessTokenErrorType::UnsupportedGrantType)),
         };
 
+        let missing_verifier =
+            request.require_pkce_verifier() && request.extension("code_verifier").is_none();
+
+        if let Credentials::Asserted { client_id, assertion } = credentials {
+            let (redirect_uri, code) = Self::grant_parameters(request)?;
+            let state = AccessTokenState::AuthenticateAssertion {
+                client: client_id.to_string(),
+                assertion: assertion.to_string(),
+                redirect_uri,
+                code,
+            };
+            return Ok((state, missing_verifier));
+        }
+
         let (client_id, passdata) = credentials.into_client().ok_or_else(Error::invalid)?;
+        let (redirect_uri, code) = Self::grant_parameters(request)?;
+
+        // A client without other credentials may be authenticated by its certificate.
+        if let (None, Some(certificate)) = (passdata, request.client_certificate()) {
+            let state = AccessTokenState::AuthenticateCertificate {
+                client: client_id.to_string(),
+                certificate: certificate.into_owned(),
+                redirect_uri,
+                code,
+            };
+            return Ok((state, missing_verifier));
+        }
+
+        let state = AccessTokenState::Authenticate {
+            client: client_id.to_string(),
+            passdata: passdata.map(Vec::from),
+            redirect_uri,
+            code,
+        };
+        Ok((state, missing_verifier))
+    }
 
+    fn grant_parameters(request: &dyn Request) -> Result<(url::Url, String)> {
         let redirect_uri = request
             .redirect_uri()
             .ok_or_else(Error::invalid)?
@@ -377,12 +544,7 @@ impl AccessToken {
 
         let code = request.code().ok_or_else(Error::invalid)?;
 
-        Ok(AccessTokenState::Authenticate {
-            client: client_id.to_string(),
-            passdata: passdata.map(Vec::from),
-            redirect_uri,
-            code: code.into_owned(),
-        })
+        Ok((redirect_uri, code.into_owned()))
     }
 
     fn authenticated(client: String, code: String, redirect_uri: url::Url) -> AccessTokenState {
@@ -394,10 +556,15 @@ impl AccessToken {
     }
 
     fn recovered(
-        client_id: String, redirect_uri: url::Url, grant: Option<Box<Grant>>,
+        client_id: String, redirect_uri: url::Url, grant: Option<Box<Grant>>, missing_verifier: bool,
     ) -> Result<AccessTokenState> {
         let mut saved_params = match grant {
-            None => return Err(Error::invalid()),
+            None => {
+                return Err(Error::invalid_because(
+                    AccessTokenErrorType::InvalidGrant,
+                    FlowError::InvalidGrant,
+                ))
+            }
             Some(v) => v,
         };
 
@@ -406,6 +573,13 @@ impl AccessToken {
         }
 
         if saved_params.until < Utc::now() {
+            return Err(Error::invalid_because(
+                AccessTokenErrorType::InvalidGrant,
+                FlowError::ExpiredCode,
+            ));
+        }
+
+        if missing_verifier && Pkce::challenged(&saved_params.extensions) {
             return Err(Error::invalid_with(AccessTokenErrorType::InvalidGrant));
         }
 
@@ -436,6 +610,14 @@ pub fn access_token(handler: &mut dyn Endpoint, request: &dyn Request) -> Result
             client: &'a str,
             passdata: Option<&'a [u8]>,
         },
+        AuthenticateAssertion {
+            client: &'a str,
+            assertion: &'a str,
+        },
+        AuthenticateCertificate {
+            client: &'a str,
+            certificate: &'a [u8],
+        },
         Recover(&'a str),
         Extend {
             extensions: &'a mut Extensions,
@@ -462,6 +644,35 @@ pub fn access_token(handler: &mut dyn Endpoint, request: &dyn Request) -> Result
                             extensions: None,
                         })),
                     })?;
+                check_grant_type(handler.registrar(), client)?;
+                Input::Authenticated
+            }
+            Requested::AuthenticateAssertion { client, assertion } => {
+                handler
+                    .registrar()
+                    .check_assertion(client, assertion)
+                    .map_err(|err| match err {
+                        RegistrarError::Unspecified => Error::unauthorized("basic"),
+                        RegistrarError::PrimitiveError => Error::Primitive(Box::new(PrimitiveError {
+                            grant: None,
+                            extensions: None,
+                        })),
+                    })?;
+                check_grant_type(handler.registrar(), client)?;
+                Input::Authenticated
+            }
+            Requested::AuthenticateCertificate { client, certificate } => {
+                handler
+                    .registrar()
+                    .check_certificate(client, certificate)
+                    .map_err(|err| match err {
+                        RegistrarError::Unspecified => Error::unauthorized("basic"),
+                        RegistrarError::PrimitiveError => Error::Primitive(Box::new(PrimitiveError {
+                            grant: None,
+                            extensions: None,
+                        })),
+                    })?;
+                check_grant_type(handler.registrar(), client)?;
                 Input::Authenticated
             }
             Requested::Recover(code) => {
@@ -494,6 +705,12 @@ pub fn access_token(handler: &mut dyn Endpoint, request: &dyn Request) -> Result
 
         requested = match access_token.advance(input) {
             Output::Authenticate { client, passdata } => Requested::Authenticate { client, passdata },
+            Output::AuthenticateAssertion { client, assertion } => {
+                Requested::AuthenticateAssertion { client, assertion }
+            }
+            Output::AuthenticateCertificate { client, certificate } => {
+                Requested::AuthenticateCertificate { client, certificate }
+            }
             Output::Recover { code } => Requested::Recover(code),
             Output::Extend { extensions } => Requested::Extend { extensions },
             Output::Issue { grant } => Requested::Issue { grant },
@@ -503,6 +720,19 @@ pub fn access_token(handler: &mut dyn Endpoint, request: &dyn Request) -> Result
     }
 }
 
+/// Check that the authenticated client may redeem authorization codes.
+fn check_grant_type(registrar: &dyn Registrar, client_id: &str) -> Result<()> {
+    registrar
+        .check_grant_type(client_id, GrantType::AuthorizationCode)
+        .map_err(|err| match err {
+            RegistrarError::Unspecified => Error::unauthorized_client(),
+            RegistrarError::PrimitiveError => Error::Primitive(Box::new(PrimitiveError {
+                grant: None,
+                extensions: None,
+            })),
+        })
+}
+
 impl<'a> Credentials<'a> {
     pub fn authenticate(&mut self, client_id: &'a str, passphrase: &'a [u8]) {
         self.add(Credentials::Authenticated {
@@ -515,6 +745,10 @@ impl<'a> Credentials<'a> {
         self.add(Credentials::Unauthenticated { client_id })
     }
 
+    pub fn assert(&mut self, client_id: &'a str, assertion: &'a str) {
+        self.add(Credentials::Asserted { client_id, assertion })
+    }
+
     pub fn into_client(self) -> Option<(&'a str, Option<&'a [u8]>)> {
         match self {
             Credentials::Authenticated {
@@ -581,6 +815,7 @@ pub struct PrimitiveError {
 #[derive(Clone)]
 pub struct ErrorDescription {
     pub(crate) error: AccessTokenError,
+    pub(crate) cause: Option<FlowError>,
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -593,6 +828,7 @@ impl Error {
     pub fn invalid() -> Self {
         Error::Invalid(ErrorDescription {
             error: AccessTokenError::default(),
+            cause: None,
         })
     }
 
@@ -603,9 +839,24 @@ impl Error {
                 error.set_type(with_type);
                 error
             },
+            cause: None,
         })
     }
 
+    /// Create an invalid error type and record a more specific cause for the server.
+    pub(crate) fn invalid_because(with_type: AccessTokenErrorType, cause: FlowError) -> Self {
+        let mut error = Error::invalid_with(with_type);
+        if let Error::Invalid(description) = &mut error {
+            description.cause = Some(cause);
+        }
+        error
+    }
+
+    /// Create an error for a client that may not use the grant type
+    pub fn unauthorized_client() -> Self {
+        Error::invalid_with(AccessTokenErrorType::UnauthorizedClient)
+    }
+
     /// Create unauthorized error type
     pub fn unauthorized(authtype: &str) -> Error {
         Error::Unauthorized(
@@ -615,6 +866,7 @@ impl Error {
                     error.set_type(AccessTokenErrorType::InvalidClient);
                     error
                 },
+                cause: None,
             },
             authtype.to_string(),
         )
@@ -646,7 +898,18 @@ impl PrimitiveError {
 impl ErrorDescription {
     /// Create this from an access token error
     pub fn new(error: AccessTokenError) -> Self {
-        Self { error }
+        Self { error, cause: None }
+    }
+
+    /// The detailed cause of the error, for the server only.
+    ///
+    /// When the flow did not record a more specific cause, this is derived from the error code
+    /// that the client receives.
+    pub fn cause(&mut self) -> FlowError {
+        match self.cause {
+            Some(cause) => cause,
+            None => self.error.kind().into(),
+        }
     }
 
     /// Convert the error into a json string, viable for being sent over a network with
@@ -667,15 +930,54 @@ impl ErrorDescription {
 }
 
 impl BearerToken {
+    /// The issued access token, its refresh token and their expiry.
+    pub fn token(&self) -> &IssuedToken {
+        &self.0
+    }
+
+    /// The scope granted to the token.
+    pub fn scope(&self) -> &Scope {
+        &self.1
+    }
+
     /// Convert the token into a json string, viable for being sent over a network with
     /// `application/json` encoding.
     pub fn to_json(&self) -> String {
-        let remaining = self.0.until.signed_duration_since(Utc::now());
+        self.encode(false, &|remaining| remaining)
+    }
+
+    /// Convert the token into a json string, including the lifetime of the refresh token.
+    ///
+    /// The remaining lifetime is sent as the non-standard `refresh_token_expires_in` parameter
+    /// when the issuer limited it. Otherwise this is the same as `to_json`.
+    pub fn to_json_with_refresh_expiry(&self) -> String {
+        self.encode(true, &|remaining| remaining)
+    }
+
+    /// Convert the token into a json string, adjusting the advertised `expires_in`.
+    ///
+    /// The function receives the remaining lifetime of the access token in seconds and returns the
+    /// value sent to the client, for example to round it or to cap it at a maximum. Only the
+    /// response is affected, the token itself stays valid until the expiry of its grant. Clients
+    /// should therefore not be told that a token lives longer than it does.
+    pub fn to_json_with_expiry(&self, refresh_expiry: bool, expires_in: &dyn Fn(i64) -> i64) -> String {
+        self.encode(refresh_expiry, expires_in)
+    }
+
+    fn encode(&self, refresh_expiry: bool, expires_in: &dyn Fn(i64) -> i64) -> String {
+        let now = Utc::now();
+        let remaining = self.0.until.signed_duration_since(now);
+        let refresh_remaining = self
+            .0
+            .refresh_until
+            .filter(|_| refresh_expiry && self.0.refresh.is_some())
+            .map(|until| until.signed_duration_since(now).num_seconds());
         let token_response = TokenResponse {
             access_token: Some(self.0.token.clone()),
             refresh_token: self.0.refresh.clone(),
-            token_type: Some("bearer".to_owned()),
-            expires_in: Some(remaining.num_seconds()),
+            token_type: Some(self.0.token_type.as_str().to_owned()),
+            expires_in: Some(expires_in(remaining.num_seconds())),
+            refresh_token_expires_in: refresh_remaining,
             scope: Some(self.1.to_string()),
             error: None,
         };
@@ -697,6 +999,7 @@ mod tests {
                 refresh: Some("refresh".into()),
                 until: Utc::now(),
                 token_type: TokenType::Bearer,
+                refresh_until: None,
             },
             "scope".parse().unwrap(),
         );
@@ -711,6 +1014,26 @@ mod tests {
         assert!(token.expires_in.is_some());
     }
 
+    #[test]
+    fn dpop_token_encoding() {
+        let token = BearerToken(
+            IssuedToken {
+                token: "access".into(),
+                refresh: None,
+                until: Utc::now(),
+                token_type: TokenType::DPoP,
+                refresh_until: None,
+            },
+            "scope".parse().unwrap(),
+        );
+
+        let json = token.to_json();
+        let token = serde_json::from_str::<TokenResponse>(&json).unwrap();
+
+        assert_eq!(token.access_token, Some("access".to_owned()));
+        assert_eq!(token.token_type, Some("DPoP".to_owned()));
+    }
+
     #[test]
     fn no_refresh_encoding() {
         let token = BearerToken(
@@ -727,4 +1050,18 @@ mod tests {
         assert_eq!(token.token_type, Some("bearer".to_owned()));
         assert!(token.expires_in.is_some());
     }
+
+    #[test]
+    fn capped_expiry_encoding() {
+        let token = BearerToken(
+            IssuedToken::without_refresh("access".into(), Utc::now() + chrono::Duration::hours(1)),
+            "scope".parse().unwrap(),
+        );
+
+        let json = token.to_json_with_expiry(false, &|remaining| remaining.min(300));
+        let response = serde_json::from_str::<TokenResponse>(&json).unwrap();
+
+        assert_eq!(response.expires_in, Some(300));
+        assert!(token.token().until > Utc::now() + chrono::Duration::minutes(59));
+    }
 }
@@ -2,12 +2,15 @@
 use std::borrow::Cow;
 use std::result::Result as StdResult;
 
-use url::Url;
-use chrono::{Duration, Utc};
+use url::{form_urlencoded, Url};
+use chrono::{DateTime, Duration, Utc};
 
 use crate::code_grant::error::{AuthorizationError, AuthorizationErrorType};
 use crate::primitives::authorizer::Authorizer;
-use crate::primitives::registrar::{ClientUrl, ExactUrl, Registrar, RegistrarError, PreGrant};
+use crate::primitives::issuer::{Issuer, RefreshIssuance, TokenType};
+use crate::primitives::registrar::{
+    ClientTrust, ClientUrl, ExactUrl, GrantType, Registrar, RegistrarError, PreGrant,
+};
 use crate::primitives::grant::{Extensions, Grant};
 use crate::{endpoint::Scope, endpoint::Solicitation, primitives::registrar::BoundClient};
 
@@ -36,6 +39,52 @@ pub trait Request {
 
     /// Retrieve an additional parameter used in an extension
     fn extension(&self, key: &str) -> Option<Cow<str>>;
+
+    /// Whether the endpoint accepts the requested `response_type`.
+    ///
+    /// Requests with any other value are answered with `unsupported_response_type`. Only `code` is
+    /// accepted by default.
+    fn allow_response_type(&self, response_type: &str) -> bool {
+        response_type == "code"
+    }
+
+    /// Whether public clients must present a PKCE `code_challenge`.
+    ///
+    /// Requests of public clients without a challenge are answered with `invalid_request`.
+    /// Confidential clients are unaffected. Not required by default.
+    fn require_pkce_for_public_clients(&self) -> bool {
+        false
+    }
+
+    /// Whether a required PKCE challenge must use the `S256` method.
+    ///
+    /// Only has an effect on clients that are required to use PKCE.
+    fn require_pkce_s256(&self) -> bool {
+        false
+    }
+
+    /// Whether requests with `response_type=token` are answered with an implicit grant.
+    ///
+    /// The implicit grant issues the access token directly and is only available to public
+    /// clients. When this is `false` but `token` is an accepted response type, such requests are
+    /// answered with an authorization code instead. Not allowed by default.
+    fn allow_implicit(&self) -> bool {
+        false
+    }
+
+    /// Whether requests for more than the negotiated scope are rejected.
+    ///
+    /// When this is `true`, a request whose scope is not contained in the scope negotiated by the
+    /// registrar is answered with `invalid_scope` instead of silently narrowing it. Requests
+    /// without a scope are unaffected. Narrowing is the default.
+    fn reject_excess_scope(&self) -> bool {
+        false
+    }
+}
+
+/// Whether the request asks for an implicit grant that the endpoint allows.
+fn implicit(request: &dyn Request) -> bool {
+    request.allow_implicit() && request.response_type().as_deref() == Some("token")
 }
 
 /// A system of addons provided additional data.
@@ -228,7 +277,7 @@ impl Authorization {
         // It's done here rather than in `validate` because we need bound_client to be sure
         // `redirect_uri` has a value
         match request.response_type() {
-            Some(ref method) if method.as_ref() == "code" => (),
+            Some(ref method) if request.allow_response_type(method) => (),
             _ => {
                 let prepared_error = ErrorUrl::with_request(
                     request,
@@ -327,6 +376,8 @@ pub fn authorization_code(handler: &mut dyn Endpoint, request: &dyn Request) ->
     let mut authorization = Authorization::new(request);
     let mut requested = Requested::None;
     let mut the_redirect_uri = None;
+    let mut require_pkce = false;
+    let mut requested_scope = None;
 
     loop {
         let input = match requested {
@@ -344,6 +395,50 @@ pub fn authorization_code(handler: &mut dyn Endpoint, request: &dyn Request) ->
                     Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
                     Ok(pre_grant) => pre_grant,
                 };
+                let grant_type = if implicit(request) {
+                    GrantType::Implicit
+                } else {
+                    GrantType::AuthorizationCode
+                };
+                match handler
+                    .registrar()
+                    .check_grant_type(&bound_client.client_id, grant_type)
+                {
+                    Ok(()) => (),
+                    Err(RegistrarError::Unspecified) => {
+                        let prepared_error = ErrorUrl::with_request(
+                            request,
+                            bound_client.redirect_uri.to_url(),
+                            AuthorizationErrorType::UnauthorizedClient,
+                        );
+                        return Err(Error::Redirect(prepared_error));
+                    }
+                    Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
+                }
+                if implicit(request) {
+                    // Tokens in the fragment are visible to the user agent, confidential clients
+                    // must use the code grant instead.
+                    match handler.registrar().check(&bound_client.client_id, None) {
+                        Ok(()) => (),
+                        Err(RegistrarError::Unspecified) => {
+                            let prepared_error = ErrorUrl::with_request(
+                                request,
+                                bound_client.redirect_uri.to_url(),
+                                AuthorizationErrorType::UnauthorizedClient,
+                            );
+                            return Err(Error::Redirect(prepared_error));
+                        }
+                        Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
+                    }
+                }
+                if request.require_pkce_for_public_clients() {
+                    // Public clients are those that authenticate without any passphrase.
+                    require_pkce = match handler.registrar().check(&bound_client.client_id, None) {
+                        Ok(()) => true,
+                        Err(RegistrarError::Unspecified) => false,
+                        Err(RegistrarError::PrimitiveError) => return Err(Error::PrimitiveError),
+                    };
+                }
                 the_redirect_uri = Some(bound_client.redirect_uri.clone().into_owned());
                 Input::Bound {
                     request,
@@ -351,6 +446,9 @@ pub fn authorization_code(handler: &mut dyn Endpoint, request: &dyn Request) ->
                 }
             }
             Requested::Extend => {
+                if require_pkce {
+                    required_pkce(request, the_redirect_uri.as_ref().unwrap().to_url())?;
+                }
                 let grant_extension = match handler.extension().extend(request) {
                     Ok(extension_data) => extension_data,
                     Err(()) => {
@@ -375,18 +473,20 @@ pub fn authorization_code(handler: &mut dyn Endpoint, request: &dyn Request) ->
                 };
                 let pre_grant = handler
                     .registrar()
-                    .negotiate(bound_client, scope)
+                    .negotiate(bound_client, scope.clone())
                     .map_err(|err| match err {
                         RegistrarError::PrimitiveError => Error::PrimitiveError,
                         RegistrarError::Unspecified => {
                             let prepared_error = ErrorUrl::with_request(
                                 request,
-                                redirect_uri,
+                                redirect_uri.clone(),
                                 AuthorizationErrorType::InvalidScope,
                             );
                             Error::Redirect(prepared_error)
                         }
                     })?;
+                excess_scope(request, scope.as_ref(), &pre_grant, redirect_uri)?;
+                requested_scope = scope;
                 Input::Negotiated {
                     pre_grant,
                     state: request.state().map(|s| s.into_owned()),
@@ -417,6 +517,8 @@ pub fn authorization_code(handler: &mut dyn Endpoint, request: &dyn Request) ->
                     pre_grant,
                     state,
                     extensions,
+                    implicit: implicit(request),
+                    requested_scope,
                 })
             }
             Output::Err(e) => return Err(e),
@@ -424,6 +526,51 @@ pub fn authorization_code(handler: &mut dyn Endpoint, request: &dyn Request) ->
     }
 }
 
+/// Check that a request carries the PKCE challenge required from public clients.
+///
+/// The challenge itself is only checked for presence and its method, it is bound to the grant and
+/// verified by the [`Pkce`] extension which should also be used by the endpoint.
+///
+/// [`Pkce`]: ../extensions/struct.Pkce.html
+pub fn required_pkce(request: &dyn Request, redirect_uri: Url) -> self::Result<()> {
+    let explanation = match request.extension("code_challenge_method") {
+        _ if request.extension("code_challenge").is_none() => "code_challenge is required",
+        Some(method) if method == "S256" => return Ok(()),
+        // The method defaults to `plain` if it is not given.
+        _ if request.require_pkce_s256() => "code_challenge_method must be S256",
+        _ => return Ok(()),
+    };
+
+    let mut prepared_error =
+        ErrorUrl::with_request(request, redirect_uri, AuthorizationErrorType::InvalidRequest);
+    prepared_error.description().explain(explanation);
+    Err(Error::Redirect(prepared_error))
+}
+
+/// Check that the negotiated grant covers the requested scope, if the request demands it.
+///
+/// Only has an effect on requests with [`reject_excess_scope`], all others keep the scope chosen
+/// by the registrar.
+///
+/// [`reject_excess_scope`]: trait.Request.html#method.reject_excess_scope
+pub fn excess_scope(
+    request: &dyn Request, requested: Option<&Scope>, pre_grant: &PreGrant, redirect_uri: Url,
+) -> self::Result<()> {
+    match requested {
+        Some(requested)
+            if request.reject_excess_scope() && !pre_grant.scope.priviledged_to(requested) =>
+        {
+            let mut prepared_error =
+                ErrorUrl::with_request(request, redirect_uri, AuthorizationErrorType::InvalidScope);
+            prepared_error
+                .description()
+                .explain("requested scope exceeds the scope of the client");
+            Err(Error::Redirect(prepared_error))
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Represents a valid, currently pending authorization request not bound to an owner. The frontend
 /// can signal a reponse using this object.
 // Don't ever implement `Clone` here. It's to make it very
@@ -433,6 +580,59 @@ pub struct Pending {
     pre_grant: PreGrant,
     state: Option<String>,
     extensions: Extensions,
+    implicit: bool,
+    requested_scope: Option<Scope>,
+}
+
+/// The successful result of an authorization request, before it is rendered to the client.
+///
+/// By default this is sent as a redirect to the `redirect_uri` with the parameters in its query,
+/// see [`redirect_url`]. Frontends that deliver the response differently, for example with a
+/// form post or through an api for a consent page, can instead use the fields directly.
+///
+/// [`redirect_url`]: #method.redirect_url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizationResponse {
+    /// The authorization code issued for the grant.
+    pub code: String,
+
+    /// The `state` of the request which must be echoed to the client.
+    pub state: Option<String>,
+
+    /// The redirect uri of the client, without any response parameters.
+    pub redirect_uri: Url,
+
+    /// The issuer identifier of this server, as defined in [RFC 9207].
+    ///
+    /// [RFC 9207]: https://tools.ietf.org/html/rfc9207
+    pub iss: Option<String>,
+}
+
+/// The successful result of an implicit grant request, before it is rendered to the client.
+///
+/// The access token is sent in the fragment of the `redirect_uri`, see [`redirect_url`]. No
+/// refresh token is ever issued for an implicit grant.
+///
+/// [`redirect_url`]: #method.redirect_url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImplicitResponse {
+    /// The access token issued for the grant.
+    pub access_token: String,
+
+    /// The type of the access token.
+    pub token_type: TokenType,
+
+    /// The number of seconds until the access token expires.
+    pub expires_in: i64,
+
+    /// The scope of the access token.
+    pub scope: Scope,
+
+    /// The `state` of the request which must be echoed to the client.
+    pub state: Option<String>,
+
+    /// The redirect uri of the client, without any response parameters.
+    pub redirect_uri: Url,
 }
 
 impl Pending {
@@ -441,26 +641,97 @@ impl Pending {
         Solicitation {
             grant: Cow::Borrowed(&self.pre_grant),
             state: self.state.as_ref().map(|s| Cow::Borrowed(&**s)),
+            descriptions: None,
+            trust: ClientTrust::ThirdParty,
+            requested_scope: self.requested_scope.as_ref().map(Cow::Borrowed),
         }
     }
 
+    /// Restrict the grant to the part of the negotiated scope approved by the resource owner.
+    ///
+    /// Fails without changing the grant if `scope` is empty or not contained in the negotiated
+    /// scope, the owner can only narrow what the registrar has chosen.
+    pub fn restrict_scope(&mut self, scope: Scope) -> StdResult<(), ()> {
+        if scope.is_empty() || !self.pre_grant.scope.priviledged_to(&scope) {
+            return Err(());
+        }
+
+        self.pre_grant.scope = scope;
+        Ok(())
+    }
+
     /// Denies the request, which redirects to the client for which the request originated.
     pub fn deny(self) -> Result<Url> {
         let url = self.pre_grant.redirect_uri;
         let mut error = AuthorizationError::default();
         error.set_type(AuthorizationErrorType::AccessDenied);
-        let error = ErrorUrl::new_generic(url.into_url(), self.state, error);
+        let mut error = ErrorUrl::new_generic(url.into_url(), self.state, error);
+        error.fragment = self.implicit;
         Err(Error::Redirect(error))
     }
 
+    /// Whether the request asked for an implicit grant.
+    ///
+    /// Such requests must be answered with [`implicit_response`] instead of an authorization code.
+    ///
+    /// [`implicit_response`]: #method.implicit_response
+    pub fn is_implicit(&self) -> bool {
+        self.implicit
+    }
+
+    /// Inform the backend about consent from a resource owner, issuing an access token directly.
+    ///
+    /// This completes an implicit grant request. The token is issued without a refresh token and
+    /// its expiry is reported relative to `now`, the current time of the endpoint.
+    pub fn implicit_response(
+        self, issuer: &mut dyn Issuer, owner_id: Cow<str>, now: DateTime<Utc>,
+    ) -> Result<ImplicitResponse> {
+        let redirect_uri = self.pre_grant.redirect_uri.to_url();
+        let scope = self.pre_grant.scope.clone();
+
+        let grant = Grant {
+            owner_id: owner_id.into_owned(),
+            client_id: self.pre_grant.client_id,
+            redirect_uri: self.pre_grant.redirect_uri.into_url(),
+            scope: self.pre_grant.scope,
+            until: now + Duration::minutes(10),
+            extensions: self.extensions,
+        };
+        let token = issuer
+            .issue_with(grant, RefreshIssuance::Never)
+            .map_err(|()| Error::PrimitiveError)?;
+
+        Ok(ImplicitResponse {
+            access_token: token.token,
+            token_type: token.token_type,
+            expires_in: token.until.signed_duration_since(now).num_seconds(),
+            scope,
+            state: self.state,
+            redirect_uri,
+        })
+    }
+
     /// Inform the backend about consent from a resource owner.
     ///
     /// Use negotiated parameters to authorize a client for an owner. The endpoint SHOULD be the
     /// same endpoint as was used to create the pending request.
     pub fn authorize(self, handler: &mut dyn Endpoint, owner_id: Cow<str>) -> Result<Url> {
-        let mut url = self.pre_grant.redirect_uri.to_url();
+        self.authorization_response(handler, owner_id)
+            .map(|response| response.redirect_url())
+    }
 
-        let grant = handler
+    /// Inform the backend about consent from a resource owner, without rendering the response.
+    ///
+    /// This is the same as [`authorize`] but returns the structured response. Its `iss` is not
+    /// set, since the issuer identifier is not known to the pending request.
+    ///
+    /// [`authorize`]: #method.authorize
+    pub fn authorization_response(
+        self, handler: &mut dyn Endpoint, owner_id: Cow<str>,
+    ) -> Result<AuthorizationResponse> {
+        let redirect_uri = self.pre_grant.redirect_uri.to_url();
+
+        let code = handler
             .authorizer()
             .authorize(Grant {
                 owner_id: owner_id.into_owned(),
@@ -472,11 +743,12 @@ impl Pending {
             })
             .map_err(|()| Error::PrimitiveError)?;
 
-        url.query_pairs_mut()
-            .append_pair("code", grant.as_str())
-            .extend_pairs(self.state.map(|v| ("state", v)))
-            .finish();
-        Ok(url)
+        Ok(AuthorizationResponse {
+            code,
+            state: self.state,
+            redirect_uri,
+            iss: None,
+        })
     }
 
     /// Retrieve a reference to the negotiated parameters (e.g. scope). These should be displayed
@@ -486,6 +758,53 @@ impl Pending {
     }
 }
 
+impl AuthorizationResponse {
+    /// The response parameters, in the order in which they are sent to the client.
+    pub fn parameters(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        let code = Some(("code", self.code.as_str()));
+        let state = self.state.as_deref().map(|state| ("state", state));
+        let iss = self.iss.as_deref().map(|iss| ("iss", iss));
+        code.into_iter().chain(state).chain(iss)
+    }
+
+    /// The redirect uri with all response parameters appended to its query.
+    pub fn redirect_url(&self) -> Url {
+        let mut url = self.redirect_uri.clone();
+        url.query_pairs_mut().extend_pairs(self.parameters()).finish();
+        url
+    }
+}
+
+impl ImplicitResponse {
+    /// The response parameters, in the order in which they are sent to the client.
+    pub fn parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![
+            ("access_token", self.access_token.clone()),
+            ("token_type", self.token_type.as_str().to_owned()),
+            ("expires_in", self.expires_in.to_string()),
+            ("scope", self.scope.to_string()),
+        ];
+        parameters.extend(self.state.clone().map(|state| ("state", state)));
+        parameters
+    }
+
+    /// The redirect uri with all response parameters in its fragment.
+    pub fn redirect_url(&self) -> Url {
+        let mut url = self.redirect_uri.clone();
+        let fragment = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(self.parameters())
+            .finish();
+        url.set_fragment(Some(&fragment));
+        url
+    }
+}
+
+impl From<AuthorizationResponse> for Url {
+    fn from(response: AuthorizationResponse) -> Url {
+        response.redirect_url()
+    }
+}
+
 /// Defines the correct treatment of the error.
 /// Not all errors are signalled to the requesting party, especially when impersonation is possible
 /// it is integral for security to resolve the error internally instead of redirecting the user
@@ -511,20 +830,26 @@ pub enum Error {
 #[derive(Clone)]
 pub struct ErrorUrl {
     base_uri: Url,
+    state: Option<String>,
     error: AuthorizationError,
+    /// Send the parameters in the fragment, as for implicit grant requests.
+    fragment: bool,
 }
 
 type Result<T> = StdResult<T, Error>;
 
 impl ErrorUrl {
     /// Construct a new error, already fixing the state parameter if it exists.
-    fn new_generic<S>(mut url: Url, state: Option<S>, error: AuthorizationError) -> ErrorUrl
+    fn new_generic<S>(url: Url, state: Option<S>, error: AuthorizationError) -> ErrorUrl
     where
         S: AsRef<str>,
     {
-        url.query_pairs_mut()
-            .extend_pairs(state.as_ref().map(|st| ("state", st.as_ref())));
-        ErrorUrl { base_uri: url, error }
+        ErrorUrl {
+            base_uri: url,
+            state: state.map(|st| st.as_ref().to_owned()),
+            error,
+            fragment: false,
+        }
     }
 
     /// Construct a new error, already fixing the state parameter if it exists.
@@ -542,9 +867,16 @@ impl ErrorUrl {
             AuthorizationError::default(),
         );
         err.description().set_type(err_type);
+        err.fragment = implicit(request);
         err
     }
 
+    /// Send the error in the fragment of the redirect uri, as required for implicit grants.
+    pub fn in_fragment(mut self) -> Self {
+        self.fragment = true;
+        self
+    }
+
     /// Get a handle to the description the client will receive.
     pub fn description(&mut self) -> &mut AuthorizationError {
         &mut self.error
@@ -566,10 +898,20 @@ impl Error {
 }
 
 impl Into<Url> for ErrorUrl {
-    /// Finalize the error url by saving its parameters in the query part of the redirect_uri
+    /// Finalize the error url by saving its parameters in the query part of the redirect_uri, or
+    /// in its fragment for implicit grant requests.
     fn into(self) -> Url {
         let mut url = self.base_uri;
-        url.query_pairs_mut().extend_pairs(self.error.into_iter());
+        let state = self.state.map(|st| ("state", Cow::Owned(st)));
+        let pairs = state.into_iter().chain(self.error);
+        if self.fragment {
+            let fragment = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish();
+            url.set_fragment(Some(&fragment));
+        } else {
+            url.query_pairs_mut().extend_pairs(pairs);
+        }
         url
     }
 }
@@ -7,9 +7,11 @@ use chrono::{Utc, Duration};
 use crate::code_grant::accesstoken::BearerToken;
 use crate::code_grant::error::{AccessTokenError, AccessTokenErrorType};
 use crate::endpoint::{Scope, Solicitation};
-use crate::primitives::issuer::Issuer;
+use crate::primitives::issuer::{Issuer, RefreshIssuance};
 use crate::primitives::grant::{Extensions, Grant};
-use crate::primitives::registrar::{Registrar, RegistrarError, BoundClient, PreGrant, ClientUrl};
+use crate::primitives::registrar::{
+    Registrar, RegistrarError, BoundClient, ClientTrust, GrantType, PreGrant, ClientUrl,
+};
 
 use super::accesstoken::{ErrorDescription, PrimitiveError};
 
@@ -48,7 +50,7 @@ pub trait Request {
     ///
     /// According to [RFC-6749 Section 4.4.3][4.4.3] "A refresh token SHOULD NOT be included" in
     /// the response for the client credentials grant. Following that recommendation, the default
-    /// behaviour of this flow is to discard any refresh token that is returned from the issuer.
+    /// behaviour of this flow is to request a token without a refresh token from the issuer.
     ///
     /// If this behaviour is not what you want (it is possible that your particular application
     /// does have a use for a client credentials refresh token), you may enable this feature.
@@ -388,9 +390,25 @@ impl Pending {
         Solicitation {
             grant: Cow::Borrowed(&self.pre_grant),
             state: None,
+            descriptions: None,
+            trust: ClientTrust::ThirdParty,
+            requested_scope: None,
         }
     }
 
+    /// Restrict the grant to the part of the negotiated scope approved by the resource owner.
+    ///
+    /// Fails without changing the grant if `scope` is empty or not contained in the negotiated
+    /// scope, the owner can only narrow what the registrar has chosen.
+    pub fn restrict_scope(&mut self, scope: Scope) -> std::result::Result<(), ()> {
+        if scope.is_empty() || !self.pre_grant.scope.priviledged_to(&scope) {
+            return Err(());
+        }
+
+        self.pre_grant.scope = scope;
+        Ok(())
+    }
+
     /// Inform the backend about consent from a resource owner.
     ///
     /// Use negotiated parameters to authorize a client for an owner. The endpoint SHOULD be the
@@ -398,22 +416,27 @@ impl Pending {
     pub fn issue(
         self, handler: &mut dyn Endpoint, owner_id: String, allow_refresh_token: bool,
     ) -> Result<BearerToken> {
-        let mut token = handler
+        let refresh = if allow_refresh_token {
+            RefreshIssuance::Default
+        } else {
+            RefreshIssuance::Never
+        };
+
+        let token = handler
             .issuer()
-            .issue(Grant {
-                owner_id,
-                client_id: self.pre_grant.client_id,
-                redirect_uri: self.pre_grant.redirect_uri.into_url(),
-                scope: self.pre_grant.scope.clone(),
-                until: Utc::now() + Duration::minutes(10),
-                extensions: self.extensions,
-            })
+            .issue_with(
+                Grant {
+                    owner_id,
+                    client_id: self.pre_grant.client_id,
+                    redirect_uri: self.pre_grant.redirect_uri.into_url(),
+                    scope: self.pre_grant.scope.clone(),
+                    until: Utc::now() + Duration::minutes(10),
+                    extensions: self.extensions,
+                },
+                refresh,
+            )
             .map_err(|()| Error::Primitive(Box::new(PrimitiveError::empty())))?;
 
-        if !allow_refresh_token {
-            token.refresh = None;
-        }
-
         Ok(BearerToken(token, self.pre_grant.scope.clone()))
     }
 }
@@ -454,6 +477,16 @@ pub fn client_credentials(handler: &mut dyn Endpoint, request: &dyn Request) ->
                             extensions: None,
                         })),
                     })?;
+                handler
+                    .registrar()
+                    .check_grant_type(&client, GrantType::ClientCredentials)
+                    .map_err(|err| match err {
+                        RegistrarError::Unspecified => Error::unauthorized_client(),
+                        RegistrarError::PrimitiveError => Error::Primitive(Box::new(PrimitiveError {
+                            grant: None,
+                            extensions: None,
+                        })),
+                    })?;
                 Input::Authenticated
             }
             Requested::Bind { client_id } => {
@@ -579,6 +612,7 @@ impl Error {
     pub fn invalid() -> Self {
         Error::Invalid(ErrorDescription {
             error: AccessTokenError::default(),
+            cause: None,
         })
     }
 
@@ -589,9 +623,15 @@ impl Error {
                 error.set_type(with_type);
                 error
             },
+            cause: None,
         })
     }
 
+    /// Create an error for a client that may not use the grant type
+    pub fn unauthorized_client() -> Self {
+        Error::invalid_with(AccessTokenErrorType::UnauthorizedClient)
+    }
+
     /// Create unauthorized error type
     pub fn unauthorized(authtype: &str) -> Error {
         Error::Unauthorized(
@@ -601,6 +641,7 @@ impl Error {
                     error.set_type(AccessTokenErrorType::InvalidClient);
                     error
                 },
+                cause: None,
             },
             authtype.to_string(),
         )
@@ -4,11 +4,14 @@
 
 use std::fmt;
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::vec;
 use url::Url;
 
+use crate::endpoint::FlowErrorDetail;
+
 /// Error codes returned from an authorization code request.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum AuthorizationErrorType {
     /// The request is missing a required parameter, includes an invalid parameter value, includes
     /// a parameter more than once, or is otherwise malformed.
@@ -38,7 +41,7 @@ pub enum AuthorizationErrorType {
 }
 
 impl AuthorizationErrorType {
-    fn description(self) -> &'static str {
+    pub(crate) fn description(self) -> &'static str {
         match self {
             AuthorizationErrorType::InvalidRequest => "invalid_request",
             AuthorizationErrorType::UnauthorizedClient => "unauthorized_client",
@@ -56,7 +59,7 @@ impl AuthorizationErrorType {
 /// [Authorization Error]: https://tools.ietf.org/html/rfc6749#section-4.2.2.1
 #[derive(Clone, Debug)]
 pub struct AuthorizationError {
-    error: AuthorizationErrorType,
+    pub(crate) error: AuthorizationErrorType,
     description: Option<Cow<'static, str>>,
     uri: Option<Cow<'static, str>>,
 }
@@ -106,7 +109,7 @@ impl AuthorizationError {
 /// All defined error codes
 ///
 /// Details also found in <https://tools.ietf.org/html/rfc6749#section-5.2>.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum AccessTokenErrorType {
     /// The request is missing a required parameter, includes an unsupported parameter value (other
     // than grant type), repeats a parameter, includes multiple credentials, utilizes more than one
@@ -139,7 +142,7 @@ pub enum AccessTokenErrorType {
 }
 
 impl AccessTokenErrorType {
-    fn description(self) -> &'static str {
+    pub(crate) fn description(self) -> &'static str {
         match self {
             AccessTokenErrorType::InvalidRequest => "invalid_request",
             AccessTokenErrorType::InvalidClient => "invalid_client",
@@ -160,7 +163,7 @@ impl AccessTokenErrorType {
 /// [token refresh requests]: https://tools.ietf.org/html/rfc6749#section-7
 #[derive(Clone, Debug)]
 pub struct AccessTokenError {
-    error: AccessTokenErrorType,
+    pub(crate) error: AccessTokenErrorType,
     description: Option<Cow<'static, str>>,
     uri: Option<Cow<'static, str>>,
 }
@@ -205,6 +208,82 @@ impl AccessTokenError {
     }
 }
 
+/// Replaces the error codes chosen by a flow before they are sent to the client.
+///
+/// The same condition can often be reported with different codes and operators may prefer to
+/// reveal less about the cause of a failure. Codes without a replacement are sent unchanged. Only
+/// the code is replaced, the status and headers of the response still follow from the condition.
+///
+/// ```
+/// # use oxide_auth::code_grant::error::{AccessTokenErrorType, ErrorMapping};
+/// let mut mapping = ErrorMapping::new();
+/// // Don't tell clients whether they are registered at all.
+/// mapping.map_access_token(AccessTokenErrorType::InvalidClient, AccessTokenErrorType::InvalidGrant);
+///
+/// assert_eq!(
+///     mapping.access_token(AccessTokenErrorType::InvalidClient),
+///     AccessTokenErrorType::InvalidGrant);
+/// assert_eq!(
+///     mapping.access_token(AccessTokenErrorType::InvalidScope),
+///     AccessTokenErrorType::InvalidScope);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ErrorMapping {
+    authorization: HashMap<AuthorizationErrorType, AuthorizationErrorType>,
+    access_token: HashMap<AccessTokenErrorType, AccessTokenErrorType>,
+}
+
+impl ErrorMapping {
+    /// Create a mapping that keeps all error codes.
+    pub fn new() -> Self {
+        ErrorMapping::default()
+    }
+
+    /// Report authorization errors of type `from` as `to` instead.
+    pub fn map_authorization(&mut self, from: AuthorizationErrorType, to: AuthorizationErrorType) {
+        self.authorization.insert(from, to);
+    }
+
+    /// Report access token errors of type `from` as `to` instead.
+    ///
+    /// This applies to all flows of the token endpoint, including refresh and client credentials.
+    pub fn map_access_token(&mut self, from: AccessTokenErrorType, to: AccessTokenErrorType) {
+        self.access_token.insert(from, to);
+    }
+
+    /// The code that is reported for an authorization error type.
+    pub fn authorization(&self, kind: AuthorizationErrorType) -> AuthorizationErrorType {
+        self.authorization.get(&kind).copied().unwrap_or(kind)
+    }
+
+    /// The code that is reported for an access token error type.
+    pub fn access_token(&self, kind: AccessTokenErrorType) -> AccessTokenErrorType {
+        self.access_token.get(&kind).copied().unwrap_or(kind)
+    }
+
+    /// Replace the type of an authorization error.
+    pub fn apply_authorization(&self, error: &mut AuthorizationError) {
+        error.error = self.authorization(error.error);
+    }
+
+    /// Replace the type of an access token error.
+    pub fn apply_access_token(&self, error: &mut AccessTokenError) {
+        error.error = self.access_token(error.error);
+    }
+
+    /// Replace the type of the error that a failed flow sends to the client.
+    ///
+    /// Meant to be called from `Endpoint::on_flow_error`, details without an error for the client
+    /// are left unchanged.
+    pub fn apply(&self, detail: &mut FlowErrorDetail<'_>) {
+        match detail {
+            FlowErrorDetail::Authorization(error) => self.apply_authorization(error),
+            FlowErrorDetail::AccessToken(error) => self.apply_access_token(error),
+            _ => (),
+        }
+    }
+}
+
 impl Default for AuthorizationError {
     /// Construct a `AuthorizationError` with no extra information.
     ///
@@ -265,10 +344,10 @@ impl IntoIterator for AuthorizationError {
     fn into_iter(self) -> Self::IntoIter {
         let mut vec = vec![("error", Cow::Borrowed(self.error.description()))];
         if let Some(description) = self.description {
-            vec.push(("description", description));
+            vec.push(("error_description", description));
         }
         if let Some(uri) = self.uri {
-            vec.push(("uri", uri));
+            vec.push(("error_uri", uri));
         }
         vec.into_iter()
     }
@@ -281,10 +360,10 @@ impl IntoIterator for &'_ AuthorizationError {
     fn into_iter(self) -> Self::IntoIter {
         let mut vec = vec![("error", Cow::Borrowed(self.error.description()))];
         if let Some(description) = &self.description {
-            vec.push(("description", description.clone().to_owned()));
+            vec.push(("error_description", description.clone().to_owned()));
         }
         if let Some(uri) = &self.uri {
-            vec.push(("uri", uri.clone().to_owned()));
+            vec.push(("error_uri", uri.clone().to_owned()));
         }
         vec.into_iter()
     }
@@ -298,10 +377,10 @@ impl IntoIterator for AccessTokenError {
     fn into_iter(self) -> Self::IntoIter {
         let mut vec = vec![("error", Cow::Borrowed(self.error.description()))];
         if let Some(description) = self.description {
-            vec.push(("description", description));
+            vec.push(("error_description", description));
         }
         if let Some(uri) = self.uri {
-            vec.push(("uri", uri));
+            vec.push(("error_uri", uri));
         }
         vec.into_iter()
     }
@@ -314,10 +393,10 @@ impl IntoIterator for &'_ AccessTokenError {
     fn into_iter(self) -> Self::IntoIter {
         let mut vec = vec![("error", Cow::Borrowed(self.error.description()))];
         if let Some(description) = &self.description {
-            vec.push(("description", description.clone().to_owned()));
+            vec.push(("error_description", description.clone().to_owned()));
         }
         if let Some(uri) = &self.uri {
-            vec.push(("uri", uri.clone().to_owned()));
+            vec.push(("error_uri", uri.clone().to_owned()));
         }
         vec.into_iter()
     }
@@ -0,0 +1,379 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::primitives::binding::Binding;
+use crate::primitives::clock::{Clock, SystemClock};
+use crate::primitives::generator::Assertion;
+use crate::primitives::grant::GrantExtension;
+
+/// Demonstrating Proof of Possession
+///
+/// > This document describes a mechanism for sender-constraining OAuth 2.0 tokens via a
+/// > proof-of-possession mechanism on the application level. This mechanism allows for the
+/// > detection of replay attacks with access and refresh tokens.
+///
+/// (from the respective [RFC 9449])
+///
+/// The client signs a short-lived proof with a key of its own for each request and sends it in
+/// the `DPoP` header. At the token endpoint, the validator checks the proof and binds the issued
+/// tokens to the thumbprint of the key, see [`Binding::Dpop`]. A resource server then requires a
+/// fresh proof signed by the same key, which additionally carries the hash of the token, on each
+/// request presenting the token.
+///
+/// Each validator checks proofs for a single target, the http method and url the proofs must
+/// name. Use one instance for the token endpoint and one for each protected resource. Only proofs
+/// signed with an `Ed25519` key (the `EdDSA` algorithm) are supported, which requires the
+/// `ed25519` feature. Without it all proofs are rejected.
+///
+/// The unique identifier `jti` of each accepted proof is remembered until the proof would be too
+/// old anyways, so that a proof can not be replayed to the same target.
+///
+/// [RFC 9449]: https://tools.ietf.org/html/rfc9449
+/// [`Binding::Dpop`]: ../../primitives/binding/enum.Binding.html#variant.Dpop
+pub struct DpopValidator {
+    method: String,
+    target: Url,
+    required: bool,
+    max_age: Duration,
+    nonce: Option<String>,
+    seen: Mutex<HashMap<String, DateTime<Utc>>>,
+    clock: Arc<dyn Clock + Send + Sync>,
+}
+
+/// The media type of proofs, in the `typ` header.
+const PROOF_TYPE: &str = "dpop+jwt";
+
+#[derive(Deserialize)]
+struct ProofHeader {
+    typ: String,
+    alg: String,
+    jwk: ProofKey,
+}
+
+#[derive(Deserialize)]
+struct ProofKey {
+    kty: String,
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: i64,
+    #[serde(default)]
+    ath: Option<String>,
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+impl DpopValidator {
+    /// A validator which requires clients to send a proof to the target.
+    ///
+    /// Token requests without a proof are rejected instead of being issued unbound tokens.
+    pub fn required<M: Into<String>>(method: M, target: Url) -> DpopValidator {
+        DpopValidator {
+            required: true,
+            ..DpopValidator::optional(method, target)
+        }
+    }
+
+    /// A validator which will check proofs sent to the target if present but not require them.
+    pub fn optional<M: Into<String>>(method: M, target: Url) -> DpopValidator {
+        DpopValidator {
+            method: method.into(),
+            target,
+            required: false,
+            max_age: Duration::seconds(60),
+            nonce: None,
+            seen: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use a different clock to determine the age of proofs, instead of the system time.
+    ///
+    /// This is mainly useful in tests, to advance time deterministically with a [`ManualClock`].
+    ///
+    /// [`ManualClock`]: ../../primitives/clock/struct.ManualClock.html
+    pub fn with_clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Accept proofs whose `iat` is at most this far from the current time, one minute by default.
+    pub fn max_age(&mut self, max_age: Duration) {
+        self.max_age = max_age;
+    }
+
+    /// Require proofs to carry the nonce provided by the server, or no nonce with `None`.
+    ///
+    /// Clients learn the nonce from the `DPoP-Nonce` header of a previous response, which the
+    /// frontend has to send on its own.
+    pub fn set_nonce(&mut self, nonce: Option<String>) {
+        self.nonce = nonce;
+    }
+
+    /// Check a proof and return the JWK thumbprint of its key.
+    ///
+    /// The proof must be signed by the key in its header, name the method and url of this
+    /// validator, not be too old and not have been accepted before. When presented together with
+    /// an access token, the proof must also carry the hash of that token.
+    pub fn validate(&self, proof: &str, access_token: Option<&str>) -> Result<String, ()> {
+        let (signing_input, signature) = proof.rsplit_once('.').ok_or(())?;
+        let (header, claims) = signing_input.split_once('.').ok_or(())?;
+
+        let header = URL_SAFE_NO_PAD.decode(header).map_err(|_| ())?;
+        let header: ProofHeader = serde_json::from_slice(&header).map_err(|_| ())?;
+        if !header.typ.eq_ignore_ascii_case(PROOF_TYPE) {
+            return Err(());
+        }
+
+        let key = Self::public_key(&header.jwk)?;
+        if header.alg != key.jws_algorithm() {
+            return Err(());
+        }
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ())?;
+        key.verify(signing_input.as_bytes(), &signature)?;
+
+        let claims = URL_SAFE_NO_PAD.decode(claims).map_err(|_| ())?;
+        let claims: ProofClaims = serde_json::from_slice(&claims).map_err(|_| ())?;
+        self.check_claims(&claims, access_token)?;
+
+        let jkt = key.key_id().ok_or(())?;
+        self.remember(claims)?;
+        Ok(jkt)
+    }
+
+    /// Check the proof of a request presenting a token bound to the key with thumbprint `jkt`.
+    pub fn verify(&self, proof: &str, access_token: &str, jkt: &str) -> Result<(), ()> {
+        match self.validate(proof, Some(access_token))? {
+            ref key if key == jkt => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    /// Determine the binding of the tokens issued for a token request with the proof.
+    ///
+    /// Fails if the proof is invalid or if it is missing while being required.
+    pub fn bind(&self, proof: Option<Cow<str>>) -> Result<Option<Binding>, ()> {
+        match proof {
+            None if self.required => Err(()),
+            None => Ok(None),
+            Some(proof) => {
+                let jkt = self.validate(&proof, None)?;
+                Ok(Some(Binding::Dpop { jkt }))
+            }
+        }
+    }
+
+    fn public_key(key: &ProofKey) -> Result<Assertion, ()> {
+        if key.kty != "OKP" || key.crv.as_deref() != Some("Ed25519") {
+            return Err(());
+        }
+
+        let x = URL_SAFE_NO_PAD
+            .decode(key.x.as_deref().ok_or(())?)
+            .map_err(|_| ())?;
+        let x: [u8; 32] = x.as_slice().try_into().map_err(|_| ())?;
+        Self::ed25519_key(&x)
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn ed25519_key(x: &[u8; 32]) -> Result<Assertion, ()> {
+        Assertion::ed25519_verifier(x)
+    }
+
+    #[cfg(not(feature = "ed25519"))]
+    fn ed25519_key(_: &[u8; 32]) -> Result<Assertion, ()> {
+        Err(())
+    }
+
+    fn check_claims(&self, claims: &ProofClaims, access_token: Option<&str>) -> Result<(), ()> {
+        if claims.htm != self.method {
+            return Err(());
+        }
+
+        // The url is compared without its query and fragment.
+        let mut htu: Url = claims.htu.parse().map_err(|_| ())?;
+        let mut target = self.target.clone();
+        for url in [&mut htu, &mut target] {
+            url.set_query(None);
+            url.set_fragment(None);
+        }
+
+        if htu != target {
+            return Err(());
+        }
+
+        let now = self.clock.now();
+        let iat = Utc.timestamp_opt(claims.iat, 0).single().ok_or(())?;
+        if iat < now - self.max_age || iat > now + self.max_age {
+            return Err(());
+        }
+
+        if self.nonce.is_some() && claims.nonce != self.nonce {
+            return Err(());
+        }
+
+        match access_token {
+            None => Ok(()),
+            Some(token) => {
+                let ath = URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()));
+                match &claims.ath {
+                    Some(claimed) if *claimed == ath => Ok(()),
+                    _ => Err(()),
+                }
+            }
+        }
+    }
+
+    fn remember(&self, claims: ProofClaims) -> Result<(), ()> {
+        let now = self.clock.now();
+        let mut seen = self.seen.lock().map_err(|_| ())?;
+        seen.retain(|_, until| *until > now);
+
+        if seen.contains_key(&claims.jti) {
+            return Err(());
+        }
+
+        // The proof is rejected as too old afterwards, no need to remember it for longer.
+        let until = Utc.timestamp_opt(claims.iat, 0).single().ok_or(())? + self.max_age;
+        seen.insert(claims.jti, until);
+        Ok(())
+    }
+}
+
+impl GrantExtension for DpopValidator {
+    fn identifier(&self) -> &'static str {
+        // The validator produces the binding of the grant.
+        "cnf"
+    }
+}
+
+#[cfg(all(test, feature = "ed25519"))]
+mod tests {
+    use super::*;
+    use crate::primitives::clock::ManualClock;
+    use serde_json::json;
+
+    const TARGET: &str = "https://resource.example/photos";
+
+    fn sign(key: &Assertion, claims: serde_json::Value) -> String {
+        let header = json!({
+            "typ": "dpop+jwt",
+            "alg": "EdDSA",
+            "jwk": {
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": URL_SAFE_NO_PAD.encode(key.public_key().unwrap()),
+            },
+        });
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = key.signature(input.as_bytes()).unwrap();
+        format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn claims(jti: &str, iat: DateTime<Utc>) -> serde_json::Value {
+        json!({
+            "jti": jti,
+            "htm": "GET",
+            "htu": TARGET,
+            "iat": iat.timestamp(),
+        })
+    }
+
+    #[test]
+    fn proof_claims() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let key = Assertion::ed25519(&[1; 32]);
+        let mut validator =
+            DpopValidator::optional("GET", TARGET.parse().unwrap()).with_clock(ManualClock::new(now));
+
+        let proof = sign(&key, claims("a", now));
+        assert_eq!(validator.validate(&proof, None), Ok(key.key_id().unwrap()));
+        assert!(validator.validate(&proof, None).is_err());
+
+        // The query of the target is ignored.
+        let mut other = claims("b", now);
+        other["htu"] = json!("https://resource.example/photos?id=1");
+        assert!(validator.validate(&sign(&key, other), None).is_ok());
+
+        let mut other = claims("c", now);
+        other["htm"] = json!("POST");
+        assert!(validator.validate(&sign(&key, other), None).is_err());
+
+        let mut other = claims("d", now);
+        other["htu"] = json!("https://resource.example/other");
+        assert!(validator.validate(&sign(&key, other), None).is_err());
+
+        let stale = claims("e", now - Duration::minutes(5));
+        assert!(validator.validate(&sign(&key, stale), None).is_err());
+
+        validator.set_nonce(Some("server-nonce".into()));
+        assert!(validator.validate(&sign(&key, claims("f", now)), None).is_err());
+        let mut other = claims("g", now);
+        other["nonce"] = json!("server-nonce");
+        assert!(validator.validate(&sign(&key, other), None).is_ok());
+    }
+
+    #[test]
+    fn proof_token_hash() {
+        let now = Utc::now();
+        let key = Assertion::ed25519(&[1; 32]);
+        let validator = DpopValidator::optional("GET", TARGET.parse().unwrap());
+        let jkt = key.key_id().unwrap();
+
+        let mut bound = claims("a", now);
+        bound["ath"] = json!(URL_SAFE_NO_PAD.encode(Sha256::digest(b"AccessToken")));
+        assert!(validator
+            .verify(&sign(&key, bound.clone()), "AccessToken", &jkt)
+            .is_ok());
+
+        bound["jti"] = json!("b");
+        assert!(validator.verify(&sign(&key, bound), "OtherToken", &jkt).is_err());
+        assert!(validator
+            .verify(&sign(&key, claims("c", now)), "AccessToken", &jkt)
+            .is_err());
+    }
+
+    #[test]
+    fn proof_forged() {
+        let now = Utc::now();
+        let key = Assertion::ed25519(&[1; 32]);
+        let other = Assertion::ed25519(&[2; 32]);
+        let validator = DpopValidator::required("GET", TARGET.parse().unwrap());
+
+        // The signature of another key than the one in the header.
+        let proof = sign(&key, claims("a", now));
+        let (input, _) = proof.rsplit_once('.').unwrap();
+        let signature = other.signature(input.as_bytes()).unwrap();
+        let forged = format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature));
+        assert!(validator.validate(&forged, None).is_err());
+
+        assert!(validator.bind(None).is_err());
+        assert_eq!(
+            validator.bind(Some(proof.into())),
+            Ok(Some(Binding::Dpop {
+                jkt: key.key_id().unwrap()
+            }))
+        );
+    }
+}
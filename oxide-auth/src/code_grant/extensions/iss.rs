@@ -0,0 +1,86 @@
+use std::borrow::Cow;
+
+use crate::primitives::grant::GrantExtension;
+
+/// Authorization Server Issuer Identification
+///
+/// > This document specifies a new parameter called iss. This parameter is used to explicitly
+/// > include the issuer identifier of the authorization server in the authorization response of
+/// > an OAuth authorization flow. If implemented correctly, the iss parameter serves as an
+/// > effective countermeasure to "mix-up attacks".
+///
+/// (from the respective [RFC 9207])
+///
+/// Clients which compare the `iss` of the authorization response against the server they sent
+/// the user to can additionally echo it in the following access token request. This extension
+/// checks that the echoed value is the identifier of this server, so that a client which was
+/// confused about the server it talked to is detected before a code is ever redeemed.
+///
+/// Echoing the parameter is not part of the RFC and must be explicitely required by the server.
+/// Otherwise, the parameter is only compared when present.
+///
+/// [RFC 9207]: https://tools.ietf.org/html/rfc9207
+pub struct IssuerIdentifier {
+    issuer: String,
+    required: bool,
+}
+
+impl IssuerIdentifier {
+    /// An extension which requires clients to echo the issuer identifier.
+    pub fn required<I: Into<String>>(issuer: I) -> IssuerIdentifier {
+        IssuerIdentifier {
+            issuer: issuer.into(),
+            required: true,
+        }
+    }
+
+    /// An extension which will check the issuer identifier if present but not require it.
+    pub fn optional<I: Into<String>>(issuer: I) -> IssuerIdentifier {
+        IssuerIdentifier {
+            issuer: issuer.into(),
+            required: false,
+        }
+    }
+
+    /// The issuer identifier of this server.
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Verify the issuer identifier echoed by the client.
+    ///
+    /// Fails if the identifier does not match or if it is missing while being required. The
+    /// comparison is a simple string comparison, as prescribed for issuer identifiers.
+    pub fn verify(&self, iss: Option<Cow<str>>) -> Result<(), ()> {
+        match iss {
+            None if self.required => Err(()),
+            None => Ok(()),
+            Some(iss) if iss == self.issuer => Ok(()),
+            Some(_) => Err(()),
+        }
+    }
+}
+
+impl GrantExtension for IssuerIdentifier {
+    fn identifier(&self) -> &'static str {
+        "iss"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_issuer() {
+        let required = IssuerIdentifier::required("https://auth.example");
+        assert!(required.verify(Some("https://auth.example".into())).is_ok());
+        assert!(required.verify(Some("https://auth.example/".into())).is_err());
+        assert!(required.verify(None).is_err());
+
+        let optional = IssuerIdentifier::optional("https://auth.example");
+        assert!(optional.verify(Some("https://auth.example".into())).is_ok());
+        assert!(optional.verify(Some("https://evil.example".into())).is_err());
+        assert!(optional.verify(None).is_ok());
+    }
+}
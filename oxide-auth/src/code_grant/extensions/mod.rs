@@ -1,4 +1,10 @@
 //! Provides standard extensions to the OAuth process.
+mod dpop;
+mod iss;
+mod mtls;
 mod pkce;
 
+pub use self::dpop::DpopValidator;
+pub use self::iss::IssuerIdentifier;
+pub use self::mtls::CertificateBinding;
 pub use self::pkce::Pkce;
@@ -0,0 +1,80 @@
+use crate::primitives::binding::Binding;
+use crate::primitives::grant::GrantExtension;
+
+/// Certificate-Bound Access Tokens
+///
+/// > The access token is bound to the client certificate [..] such that the resource server can
+/// > verify that the client presenting the token is in possession of the corresponding private
+/// > key.
+///
+/// (from the respective [RFC 8705])
+///
+/// At the token endpoint, the tokens issued to a client that authenticated its TLS connection
+/// with a certificate are bound to the thumbprint of that certificate, see
+/// [`Binding::Certificate`]. A resource server then only accepts such a token on connections
+/// authenticated with the same certificate. The frontend provides the certificate through
+/// `WebRequest::client_certificate`.
+///
+/// [RFC 8705]: https://tools.ietf.org/html/rfc8705#section-3
+/// [`Binding::Certificate`]: ../../primitives/binding/enum.Binding.html#variant.Certificate
+pub struct CertificateBinding {
+    required: bool,
+}
+
+impl CertificateBinding {
+    /// Bind tokens to the client certificate and reject token requests without one.
+    pub fn required() -> CertificateBinding {
+        CertificateBinding { required: true }
+    }
+
+    /// Bind tokens to the client certificate if there is one, or issue unbound tokens otherwise.
+    pub fn optional() -> CertificateBinding {
+        CertificateBinding { required: false }
+    }
+
+    /// Determine the binding of the tokens issued for a token request with the certificate.
+    ///
+    /// Fails if the certificate is missing while being required.
+    pub fn bind(&self, certificate: Option<&[u8]>) -> Result<Option<Binding>, ()> {
+        match certificate {
+            None if self.required => Err(()),
+            None => Ok(None),
+            Some(certificate) => Ok(Some(Binding::certificate(certificate))),
+        }
+    }
+
+    /// Check that a certificate has the thumbprint of a bound token.
+    pub fn verify(&self, certificate: &[u8], x5t_s256: &str) -> bool {
+        match Binding::certificate(certificate) {
+            Binding::Certificate { x5t_s256: presented } => presented == x5t_s256,
+            _ => false,
+        }
+    }
+}
+
+impl GrantExtension for CertificateBinding {
+    fn identifier(&self) -> &'static str {
+        // The extension produces the binding of the grant.
+        "cnf"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificate_thumbprint() {
+        let binding = CertificateBinding::required();
+        assert!(binding.bind(None).is_err());
+        assert_eq!(CertificateBinding::optional().bind(None), Ok(None));
+
+        let thumbprint = match binding.bind(Some(b"certificate")) {
+            Ok(Some(Binding::Certificate { x5t_s256 })) => x5t_s256,
+            other => panic!("Expected a certificate binding, got {:?}", other),
+        };
+
+        assert!(binding.verify(b"certificate", &thumbprint));
+        assert!(!binding.verify(b"other certificate", &thumbprint));
+    }
+}
@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use crate::primitives::grant::{GrantExtension, Value};
+use crate::primitives::grant::{Extensions, GrantExtension, Value};
 
 use base64::{self, engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use sha2::{Digest, Sha256};
@@ -114,6 +114,21 @@ impl Pkce {
 
         method.verify(&verifier)
     }
+
+    /// Whether an encoded challenge uses the `S256` method.
+    ///
+    /// Unlike a `plain` challenge, the hash reveals nothing about the verifier. Such a challenge
+    /// can be embedded in a code that the client is able to read.
+    pub(crate) fn is_hashed(encoded: &str) -> bool {
+        encoded.ends_with('S')
+    }
+
+    /// Whether a challenge was stored in the extensions of a grant.
+    pub(crate) fn challenged(extensions: &Extensions) -> bool {
+        extensions
+            .private()
+            .any(|(identifier, _)| identifier == Pkce::optional().identifier())
+    }
 }
 
 impl GrantExtension for Pkce {
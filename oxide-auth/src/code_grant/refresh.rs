@@ -8,9 +8,9 @@ use crate::code_grant::{
     accesstoken::TokenResponse,
     error::{AccessTokenError, AccessTokenErrorType},
 };
-use crate::primitives::grant::Grant;
+use crate::primitives::grant::{Extensions, Grant};
 use crate::primitives::issuer::{RefreshedToken, Issuer};
-use crate::primitives::registrar::{Registrar, RegistrarError};
+use crate::primitives::registrar::{GrantType, Registrar, RegistrarError};
 
 /// Required content of a refresh request.
 ///
@@ -40,6 +40,24 @@ pub trait Request {
     fn extension(&self, key: &str) -> Option<Cow<str>>;
 }
 
+/// A system of addons recomputing the extension data of refreshed grants.
+///
+/// An endpoint not having any extension may use `&mut ()`, which keeps the data unchanged.
+pub trait Extension {
+    /// Inspect the request and the extension data of the grant to produce the refreshed data.
+    ///
+    /// The returned data replaces the data of the grant entirely. Failing rejects the refresh with
+    /// an `invalid_grant` error.
+    fn extend(&mut self, request: &dyn Request, data: Extensions)
+        -> std::result::Result<Extensions, ()>;
+}
+
+impl Extension for () {
+    fn extend(&mut self, _: &dyn Request, data: Extensions) -> std::result::Result<Extensions, ()> {
+        Ok(data)
+    }
+}
+
 /// The specific endpoint trait for refreshing.
 ///
 /// Each method will only be invoked exactly once when processing a correct and authorized request,
@@ -57,11 +75,20 @@ pub trait Endpoint {
 
     /// Recover and test the provided refresh token then issue new tokens.
     fn issuer(&mut self) -> &mut dyn Issuer;
+
+    /// An extension recomputing the data of the refreshed grant.
+    ///
+    /// The data of the grant is copied unchanged if there is none.
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        None
+    }
 }
 
 /// Represents a bearer token, optional refresh token and the associated scope for serialization.
+///
+/// Also carries the client of the grant, which is not serialized.
 #[derive(Debug)]
-pub struct BearerToken(RefreshedToken, String);
+pub struct BearerToken(pub(crate) RefreshedToken, pub(crate) String, pub(crate) String);
 
 /// An ongoing refresh request.
 ///
@@ -340,7 +367,22 @@ pub fn refresh(handler: &mut dyn Endpoint, request: &dyn Request) -> Result<Bear
     loop {
         let input = match requested {
             Requested::None => Input::None,
-            Requested::Refresh { token, grant } => {
+            Requested::Refresh { token, mut grant } => {
+                // The grant belongs to the client, whether it authenticated or not.
+                handler
+                    .registrar()
+                    .check_grant_type(&grant.client_id, GrantType::RefreshToken)
+                    .map_err(|err| match err {
+                        RegistrarError::PrimitiveError => Error::Primitive,
+                        RegistrarError::Unspecified => Error::unauthorized_client(),
+                    })?;
+                if let Some(extension) = handler.extension() {
+                    let extensions = std::mem::take(&mut grant.extensions);
+                    grant.extensions = extension
+                        .extend(request, extensions)
+                        .map_err(|()| Error::invalid_grant())?;
+                }
+
                 let refreshed = handler
                     .issuer()
                     .refresh(&token, *grant)
@@ -497,7 +539,7 @@ fn validate(scope: Option<Cow<str>>, grant: Box<Grant>, token: String) -> Result
 }
 
 fn issued(grant: Box<Grant>, token: RefreshedToken) -> BearerToken {
-    BearerToken(token, grant.scope.to_string())
+    BearerToken(token, grant.scope.to_string(), grant.client_id)
 }
 
 impl Error {
@@ -507,6 +549,16 @@ impl Error {
         })
     }
 
+    /// Create an error for a grant that can not be refreshed
+    pub fn invalid_grant() -> Self {
+        Error::invalid(AccessTokenErrorType::InvalidGrant)
+    }
+
+    /// Create an error for a client that may not use the grant type
+    pub fn unauthorized_client() -> Self {
+        Error::invalid(AccessTokenErrorType::UnauthorizedClient)
+    }
+
     /// Create unauthorized error type
     pub fn unauthorized(authtype: &str) -> Self {
         Error::Unauthorized(
@@ -554,12 +606,31 @@ impl BearerToken {
     /// Convert the token into a json string, viable for being sent over a network with
     /// `application/json` encoding.
     pub fn to_json(&self) -> String {
-        let remaining = self.0.until.signed_duration_since(Utc::now());
+        self.encode(false)
+    }
+
+    /// Convert the token into a json string, including the lifetime of the new refresh token.
+    ///
+    /// Sends the non-standard `refresh_token_expires_in` parameter when the issuer limited the
+    /// lifetime of the refresh token. Otherwise this is the same as `to_json`.
+    pub fn to_json_with_refresh_expiry(&self) -> String {
+        self.encode(true)
+    }
+
+    fn encode(&self, refresh_expiry: bool) -> String {
+        let now = Utc::now();
+        let remaining = self.0.until.signed_duration_since(now);
+        let refresh_remaining = self
+            .0
+            .refresh_until
+            .filter(|_| refresh_expiry && self.0.refresh.is_some())
+            .map(|until| until.signed_duration_since(now).num_seconds());
         let token_response = TokenResponse {
             access_token: Some(self.0.token.clone()),
             refresh_token: self.0.refresh.clone(),
-            token_type: Some("bearer".to_owned()),
+            token_type: Some(self.0.token_type.as_str().to_owned()),
             expires_in: Some(remaining.num_seconds()),
+            refresh_token_expires_in: refresh_remaining,
             scope: Some(self.1.clone()),
             error: None,
         };
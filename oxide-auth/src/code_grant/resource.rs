@@ -3,9 +3,11 @@ use std::{fmt, mem};
 use std::borrow::Cow;
 
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
 
-use crate::primitives::issuer::Issuer;
-use crate::primitives::grant::Grant;
+use crate::primitives::binding::Binding;
+use crate::primitives::issuer::{Issuer, TokenType};
+use crate::primitives::grant::{Extensions, Grant};
 use crate::primitives::scope::Scope;
 
 /// Gives additional information about the reason for an access failure.
@@ -41,6 +43,12 @@ pub struct Authenticate {
 
     /// The required scope to access the resource.
     pub scope: Option<Scope>,
+
+    /// A human readable explanation of the error, the `error_description`.
+    ///
+    /// Only sent together with an error code. Characters that are not allowed in the header by
+    /// RFC 6750, such as quotes, backslashes or non-ascii characters, are dropped.
+    pub description: Option<String>,
 }
 
 /// An error signalling the resource access was not permitted.
@@ -71,7 +79,36 @@ pub enum Error {
     PrimitiveError,
 }
 
+/// The claims of a bearer token, as answered to a token info request.
+///
+/// Similar to an introspection response of [RFC 7662] but determined by the bearer token of the
+/// request alone. Only `active` is present for tokens that were not accepted.
+///
+/// [RFC 7662]: https://tools.ietf.org/html/rfc7662#section-2.2
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TokenInfo {
+    /// Whether the token was accepted.
+    pub active: bool,
+
+    /// The resource owner who authorized the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+
+    /// The scope granted by the token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+
+    /// The client to which the token was issued.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+
+    /// The expiry of the token in seconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+}
+
 const BEARER_START: &str = "Bearer ";
+const DPOP_START: &str = "DPoP ";
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -85,12 +122,29 @@ pub trait Request {
 
     /// The authorization used in the request.
     ///
-    /// Expects the complete `Authorization` HTTP-header, including the qualification as `Bearer`.
+    /// Expects the complete `Authorization` HTTP-header, including the qualification as `Bearer`
+    /// or, for tokens bound to a DPoP key, as `DPoP`.
     /// In case the client included multiple forms of authorization, this method MUST return None
     /// and the request SHOULD be marked as invalid.
     fn token(&self) -> Option<Cow<str>>;
 }
 
+/// A system of addons checking the extension data of grants presented to a resource.
+///
+/// An endpoint not having any extension may use `&mut ()`, which accepts every grant.
+pub trait Extension {
+    /// Inspect the request and the extension data of the grant of a valid token.
+    ///
+    /// Failing denies access with an `invalid_token` error.
+    fn check(&mut self, request: &dyn Request, data: &Extensions) -> std::result::Result<(), ()>;
+}
+
+impl Extension for () {
+    fn check(&mut self, _: &dyn Request, _: &Extensions) -> std::result::Result<(), ()> {
+        Ok(())
+    }
+}
+
 /// Required functionality to respond to resource requests.
 ///
 /// Each method will only be invoked exactly once when processing a correct and authorized request,
@@ -102,20 +156,39 @@ pub trait Endpoint {
 
     /// Issuer which provides the tokens used for authorization by the client.
     fn issuer(&mut self) -> &dyn Issuer;
+
+    /// An extension checking the data of the grant.
+    ///
+    /// Every grant is accepted if there is none.
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        None
+    }
+
+    /// The type of the tokens not bound to a key, as advertised by the issuer.
+    ///
+    /// Such tokens are only accepted when presented with the scheme of this type.
+    fn token_type(&self) -> TokenType {
+        TokenType::Bearer
+    }
 }
 
 /// The result will indicate whether the resource access should be allowed or not.
 pub struct Resource {
     state: ResourceState,
+    unbound: TokenType,
 }
 
 enum ResourceState {
     /// The initial state.
     New,
     /// State after request has been validated.
-    Internalized { token: String },
+    Internalized { token: String, scheme: TokenType },
     /// State after scopes have been determined.
-    Recovering { token: String, scopes: Vec<Scope> },
+    Recovering {
+        token: String,
+        scheme: TokenType,
+        scopes: Vec<Scope>,
+    },
     /// State after an error occurred.
     Err(Error),
 }
@@ -176,8 +249,14 @@ pub enum Output<'machine> {
 impl Resource {
     /// Create a Resource state machine at `ResourceState::New` state
     pub fn new() -> Self {
+        Resource::with_token_type(TokenType::Bearer)
+    }
+
+    /// Create a Resource state machine accepting tokens not bound to a key with another type.
+    pub fn with_token_type(unbound: TokenType) -> Self {
         Resource {
             state: ResourceState::New,
+            unbound,
         }
     }
 
@@ -186,11 +265,13 @@ impl Resource {
         self.state = match (self.take(), input) {
             (any, Input::None) => any,
             (ResourceState::New, Input::Request { request }) => {
-                validate(request).unwrap_or_else(ResourceState::Err)
+                validate(request, self.unbound).unwrap_or_else(ResourceState::Err)
             }
-            (ResourceState::Internalized { token }, Input::Scopes(scopes)) => get_scopes(token, scopes),
-            (ResourceState::Recovering { token: _, scopes }, Input::Recovered(grant)) => {
-                match recovered(grant, scopes) {
+            (ResourceState::Internalized { token, scheme }, Input::Scopes(scopes)) => {
+                get_scopes(token, scheme, scopes)
+            }
+            (ResourceState::Recovering { scheme, scopes, .. }, Input::Recovered(grant)) => {
+                match recovered(grant, scheme, self.unbound, scopes) {
                     Ok(grant) => return Output::Ok(Box::new(grant)),
                     Err(err) => ResourceState::Err(err),
                 }
@@ -224,7 +305,7 @@ pub fn protect(handler: &mut dyn Endpoint, req: &dyn Request) -> Result<Grant> {
         Grant(String),
     }
 
-    let mut resource = Resource::new();
+    let mut resource = Resource::with_token_type(handler.token_type());
     let mut requested = Requested::None;
     loop {
         let input = match requested {
@@ -242,7 +323,7 @@ pub fn protect(handler: &mut dyn Endpoint, req: &dyn Request) -> Result<Grant> {
 
         requested = match resource.advance(input) {
             Output::Err(error) => return Err(error),
-            Output::Ok(grant) => return Ok(*grant),
+            Output::Ok(grant) => return checked(handler, req, *grant),
             Output::GetRequest => Requested::Request,
             Output::DetermineScopes => Requested::Scopes,
             Output::Recover { token } => Requested::Grant(token.to_string()),
@@ -250,7 +331,22 @@ pub fn protect(handler: &mut dyn Endpoint, req: &dyn Request) -> Result<Grant> {
     }
 }
 
-fn validate(request: &'_ dyn Request) -> Result<ResourceState> {
+fn checked(handler: &mut dyn Endpoint, req: &dyn Request, grant: Grant) -> Result<Grant> {
+    if let Some(extension) = handler.extension() {
+        if extension.check(req, &grant.extensions).is_err() {
+            return Err(Error::AccessDenied {
+                failure: AccessFailure {
+                    code: Some(ErrorCode::InvalidToken),
+                },
+                authenticate: Authenticate::empty(),
+            });
+        }
+    }
+
+    Ok(grant)
+}
+
+fn validate(request: &'_ dyn Request, unbound: TokenType) -> Result<ResourceState> {
     if !request.valid() {
         return Err(Error::InvalidRequest {
             authenticate: Authenticate::empty(),
@@ -266,31 +362,58 @@ fn validate(request: &'_ dyn Request) -> Result<ResourceState> {
         }
     };
 
-    if !client_token
-        .to_uppercase()
-        .starts_with(&BEARER_START.to_uppercase())
-    {
-        return Err(Error::InvalidRequest {
-            authenticate: Authenticate::empty(),
-        });
-    }
-
-    let token = match client_token {
-        Cow::Borrowed(token) => token[BEARER_START.len()..].to_string(),
-        Cow::Owned(mut token) => token.split_off(BEARER_START.len()),
+    let (scheme, token) = match split_scheme_with(&client_token, unbound) {
+        Some((scheme, token)) => (scheme, token.to_string()),
+        None => {
+            return Err(Error::InvalidRequest {
+                authenticate: Authenticate::empty(),
+            })
+        }
     };
 
-    Ok(ResourceState::Internalized { token })
+    Ok(ResourceState::Internalized { token, scheme })
 }
 
-fn get_scopes(token: String, scopes: &'_ [Scope]) -> ResourceState {
+/// Split an authorization into the token type of its scheme and the token itself.
+///
+/// The scheme is matched case-insensitively. Returns `None` for any scheme other than `Bearer` and
+/// `DPoP`.
+pub fn split_scheme(authorization: &str) -> Option<(TokenType, &str)> {
+    split_scheme_with(authorization, TokenType::Bearer)
+}
+
+/// Split an authorization into the token type of its scheme and the token itself.
+///
+/// Like `split_scheme` but additionally recognizes the scheme of a `TokenType::Custom` type, the
+/// type of tokens not bound to a key.
+pub fn split_scheme_with(authorization: &str, unbound: TokenType) -> Option<(TokenType, &str)> {
+    let starts_with = |start: &str| matches!(authorization.get(..start.len()), Some(prefix) if prefix.eq_ignore_ascii_case(start));
+
+    if starts_with(BEARER_START) {
+        Some((TokenType::Bearer, &authorization[BEARER_START.len()..]))
+    } else if starts_with(DPOP_START) {
+        Some((TokenType::DPoP, &authorization[DPOP_START.len()..]))
+    } else if let TokenType::Custom(name) = unbound {
+        match authorization.get(name.len()..) {
+            Some(rest) if starts_with(name) => rest.strip_prefix(' ').map(|token| (unbound, token)),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+fn get_scopes(token: String, scheme: TokenType, scopes: &'_ [Scope]) -> ResourceState {
     ResourceState::Recovering {
         token,
+        scheme,
         scopes: scopes.to_owned(),
     }
 }
 
-fn recovered(grant: Option<Grant>, mut scopes: Vec<Scope>) -> Result<Grant> {
+fn recovered(
+    grant: Option<Grant>, scheme: TokenType, unbound: TokenType, mut scopes: Vec<Scope>,
+) -> Result<Grant> {
     let grant = match grant {
         Some(grant) => grant,
         None => {
@@ -302,6 +425,7 @@ fn recovered(grant: Option<Grant>, mut scopes: Vec<Scope>) -> Result<Grant> {
                     realm: None,
                     // TODO. Don't drop the other scopes?
                     scope: scopes.drain(..).next(),
+                    description: None,
                 },
             });
         }
@@ -316,9 +440,20 @@ fn recovered(grant: Option<Grant>, mut scopes: Vec<Scope>) -> Result<Grant> {
         });
     }
 
+    // A token must be presented with the scheme of its type, so that a DPoP bound token is never
+    // accepted as a plain bearer token and the other way around.
+    if Binding::token_type_or(&grant, unbound) != scheme {
+        return Err(Error::AccessDenied {
+            failure: AccessFailure {
+                code: Some(ErrorCode::InvalidToken),
+            },
+            authenticate: Authenticate::empty(),
+        });
+    }
+
     let allowing = scopes
         .iter()
-        .find(|resource_scope| resource_scope.allow_access(&grant.scope));
+        .find(|resource_scope| fulfills(&grant.scope, resource_scope));
 
     if allowing.is_none() {
         return Err(Error::AccessDenied {
@@ -328,6 +463,7 @@ fn recovered(grant: Option<Grant>, mut scopes: Vec<Scope>) -> Result<Grant> {
             authenticate: Authenticate {
                 realm: None,
                 scope: scopes.drain(..).next(),
+                description: None,
             },
         });
     }
@@ -336,8 +472,49 @@ fn recovered(grant: Option<Grant>, mut scopes: Vec<Scope>) -> Result<Grant> {
     Ok(grant)
 }
 
+/// Check if a granted scope fulfills a scope required by the resource.
+///
+/// A grant with an empty scope only fulfills a resource requiring the empty scope and is denied
+/// for any non-empty requirement.
+fn fulfills(granted: &Scope, required: &Scope) -> bool {
+    if granted.is_empty() {
+        return required.is_empty();
+    }
+
+    required.allow_access(granted)
+}
+
+impl TokenInfo {
+    /// The claims of an accepted grant.
+    pub fn active(grant: &Grant) -> Self {
+        TokenInfo {
+            active: true,
+            sub: Some(grant.owner_id.clone()),
+            scope: Some(grant.scope.to_string()),
+            client_id: Some(grant.client_id.clone()),
+            exp: Some(grant.until.timestamp()),
+        }
+    }
+
+    /// The answer for a token that was not accepted.
+    pub fn inactive() -> Self {
+        TokenInfo {
+            active: false,
+            sub: None,
+            scope: None,
+            client_id: None,
+            exp: None,
+        }
+    }
+
+    /// Serialize the claims as a json object.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
 impl ErrorCode {
-    fn description(self) -> &'static str {
+    pub(crate) fn description(self) -> &'static str {
         match self {
             ErrorCode::InvalidRequest => "invalid_request",
             ErrorCode::InsufficientScope => "insufficient_scope",
@@ -362,6 +539,7 @@ impl BearerHeader {
     fn add_option(&mut self, args: fmt::Arguments) {
         if self.first_option {
             self.content.push(' ');
+            self.first_option = false;
         } else {
             self.content.push(',');
         }
@@ -379,11 +557,20 @@ impl BearerHeader {
     }
 }
 
+/// Drop the characters not allowed in the `error_description` attribute by RFC 6750.
+fn header_safe(description: String) -> String {
+    description
+        .chars()
+        .filter(|&c| matches!(c, ' ' | '!' | '#'..='[' | ']'..='~'))
+        .collect()
+}
+
 impl Authenticate {
     fn empty() -> Self {
         Authenticate {
             realm: None,
             scope: None,
+            description: None,
         }
     }
 
@@ -400,16 +587,41 @@ impl AccessFailure {
 }
 
 impl Error {
+    /// Whether access was denied because the token did not grant the required scope.
+    pub fn insufficient_scope(&self) -> bool {
+        matches!(
+            self,
+            Error::AccessDenied {
+                failure: AccessFailure {
+                    code: Some(ErrorCode::InsufficientScope),
+                },
+                ..
+            }
+        )
+    }
+
+    /// Explain why access was denied, in the `error_description` of the header.
+    ///
+    /// Has no effect on errors other than a denied access.
+    pub fn describe(&mut self, description: String) {
+        if let Error::AccessDenied { authenticate, .. } = self {
+            authenticate.description = Some(description);
+        }
+    }
+
     /// Convert the guard error into the content used in an WWW-Authenticate header.
     pub fn www_authenticate(self) -> String {
         let mut header = BearerHeader::new();
         match self {
             Error::AccessDenied {
                 failure,
-                authenticate,
+                mut authenticate,
             } => {
+                // A description is only meaningful together with an error code.
+                let description = authenticate.description.take().filter(|_| failure.code.is_some());
                 failure.extend_header(&mut header);
                 authenticate.extend_header(&mut header);
+                header.add_kvp("error_description", description.map(header_safe));
             }
             Error::NoAuthentication { authenticate } => {
                 authenticate.extend_header(&mut header);
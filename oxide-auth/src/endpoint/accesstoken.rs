@@ -6,14 +6,15 @@ use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 
 use crate::code_grant::accesstoken::{
-    access_token, Error as TokenError, Extension, Endpoint as TokenEndpoint, Request as TokenRequest,
-    Authorization as TokenAuthorization,
+    access_token, BearerToken, Error as TokenError, Extension, Endpoint as TokenEndpoint,
+    Request as TokenRequest, Authorization as TokenAuthorization,
 };
 use crate::primitives::{authorizer::Authorizer, registrar::Registrar, issuer::Issuer};
 use super::{
-    Endpoint, InnerTemplate, OAuthError, QueryParameter, WebRequest, WebResponse,
-    is_authorization_method,
+    Endpoint, FlowErrorDetail, InnerTemplate, OAuthError, QueryParameter, WebRequest, WebResponse,
+    exceeds_parameter_limit, is_authorization_method, primitive_error,
 };
+use super::telemetry::{self, FlowSpan};
 
 /// Offers access tokens to authenticated third parties.
 ///
@@ -34,6 +35,9 @@ where
 {
     endpoint: WrappedToken<E, R>,
     allow_credentials_in_body: bool,
+    require_pkce_verifier: bool,
+    report_refresh_expiry: bool,
+    advertise_expiry: Option<Box<dyn Fn(i64) -> i64 + Send + Sync>>,
 }
 
 struct WrappedToken<E: Endpoint<R>, R: WebRequest> {
@@ -57,6 +61,18 @@ struct WrappedRequest<'a, R: WebRequest + 'a> {
 
     /// The credentials-in-body flag from the flow.
     allow_credentials_in_body: bool,
+
+    /// Whether codes with a PKCE challenge require a verifier.
+    require_pkce_verifier: bool,
+
+    /// A copy of the unparsed body, if the request had one.
+    raw_body: Option<Vec<u8>>,
+
+    /// The `DPoP` header, if the request had one.
+    dpop: Option<String>,
+
+    /// The client certificate of the connection.
+    certificate: Option<Vec<u8>>,
 }
 
 #[derive(Debug)]
@@ -105,6 +121,9 @@ where
                 r_type: PhantomData,
             },
             allow_credentials_in_body: false,
+            require_pkce_verifier: false,
+            report_refresh_expiry: false,
+            advertise_expiry: None,
         })
     }
 
@@ -119,31 +138,123 @@ where
         self.allow_credentials_in_body = allow;
     }
 
+    /// Reject codes issued with a PKCE challenge when the request has no `code_verifier`.
+    ///
+    /// Single-page applications and other public clients rely on PKCE to protect their codes. With
+    /// this option, a stored challenge is enforced with an `invalid_grant` error even if the
+    /// endpoint does not use the `Pkce` extension. Verifiers that are present are still checked by
+    /// the extension only.
+    pub fn require_pkce_verifier(&mut self) {
+        self.require_pkce_verifier = true;
+    }
+
+    /// Report the remaining lifetime of the refresh token in the response.
+    ///
+    /// The lifetime is sent as `refresh_token_expires_in`, next to `expires_in` of the access
+    /// token. This parameter is not part of RFC 6749 and only sent if the issuer limits the
+    /// lifetime of its refresh tokens, e.g. with `TokenMap::refresh_valid_for`. Disabled by default.
+    pub fn report_refresh_expiry(&mut self, report: bool) {
+        self.report_refresh_expiry = report;
+    }
+
+    /// Post-process the `expires_in` advertised in the response.
+    ///
+    /// The function receives the remaining lifetime of the access token in seconds and returns the
+    /// value sent to the client, for example rounded down or capped at a maximum. This only changes
+    /// the response. The grant stored by the issuer keeps its expiry, so the token is recovered
+    /// until then regardless of the advertised value. Advertising more than the remaining lifetime
+    /// makes clients use tokens that have already expired.
+    pub fn advertise_expiry<F>(&mut self, expires_in: F)
+    where
+        F: Fn(i64) -> i64 + Send + Sync + 'static,
+    {
+        self.advertise_expiry = Some(Box::new(expires_in));
+    }
+
     /// Use the checked endpoint to check for authorization for a resource.
     ///
     /// ## Panics
     ///
     /// When the registrar, authorizer, or issuer returned by the endpoint is suddenly
     /// `None` when previously it was `Some(_)`.
-    pub fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
-        let issued = access_token(
-            &mut self.endpoint,
-            &WrappedRequest::new(&mut request, self.allow_credentials_in_body),
-        );
+    pub fn execute(&mut self, request: R) -> Result<R::Response, E::Error> {
+        self.execute_with_token(request).map(|(response, _)| response)
+    }
+
+    /// Use the checked endpoint to check for authorization, also returning the issued token.
+    ///
+    /// The response is the same as from [`execute`]. When a token was issued, it is returned
+    /// alongside so that the caller can act on its expiry or scope, for example to set a cookie,
+    /// without parsing the response body.
+    ///
+    /// ## Panics
+    ///
+    /// Under the same conditions as [`execute`].
+    ///
+    /// [`execute`]: #method.execute
+    pub fn execute_with_token(
+        &mut self, mut request: R,
+    ) -> Result<(R::Response, Option<BearerToken>), E::Error> {
+        let _span = FlowSpan::enter("access_token", Some("authorization_code"));
+        let limit = self.endpoint.inner.parameter_limit();
+        let mut wrapped = WrappedRequest::new(&mut request, self.allow_credentials_in_body, limit);
+        wrapped.require_pkce_verifier = self.require_pkce_verifier;
+        let client_id = match wrapped.authorization() {
+            TokenAuthorization::Username(id) | TokenAuthorization::UsernamePassword(id, _) => Some(id),
+            TokenAuthorization::None => wrapped.client_id(),
+        };
+        if let Some(id) = &client_id {
+            telemetry::client_id(id);
+        }
+        // Only keep the client for the metrics, if there are any.
+        let client_id = client_id
+            .filter(|_| self.endpoint.inner.metrics().is_some())
+            .map(Cow::into_owned);
+        let issued = access_token(&mut self.endpoint, &wrapped);
 
         let token = match issued {
-            Err(error) => return token_error(&mut self.endpoint.inner, &mut request, error),
+            Err(error) => {
+                return token_error(&mut self.endpoint.inner, &mut request, error)
+                    .map(|response| (response, None))
+            }
             Ok(token) => token,
         };
+        telemetry::scope(&token.1);
+        telemetry::token(&token.0.token);
+        telemetry::success();
+        if let (Some(metrics), Some(client_id)) = (self.endpoint.inner.metrics(), &client_id) {
+            metrics.on_code_redeemed(client_id);
+            metrics.on_token_issued(client_id);
+        }
 
         let mut response = self
             .endpoint
             .inner
             .response(&mut request, InnerTemplate::Ok.into())?;
         response
-            .body_json(&token.to_json())
+            .no_store()
+            .map_err(|err| self.endpoint.inner.web_error(err))?;
+        response
+            .body_json(&token_json(
+                &token,
+                self.report_refresh_expiry,
+                self.advertise_expiry.as_deref(),
+            ))
             .map_err(|err| self.endpoint.inner.web_error(err))?;
-        Ok(response)
+        Ok((response, Some(token)))
+    }
+}
+
+fn token_json(
+    token: &BearerToken, report_refresh_expiry: bool,
+    advertise_expiry: Option<&(dyn Fn(i64) -> i64 + Send + Sync)>,
+) -> String {
+    if let Some(expires_in) = advertise_expiry {
+        token.to_json_with_expiry(report_refresh_expiry, expires_in)
+    } else if report_refresh_expiry {
+        token.to_json_with_refresh_expiry()
+    } else {
+        token.to_json()
     }
 }
 
@@ -152,6 +263,11 @@ fn token_error<E: Endpoint<R>, R: WebRequest>(
 ) -> Result<R::Response, E::Error> {
     Ok(match error {
         TokenError::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
+            telemetry::failure(json.description().kind().description());
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::BadRequest {
@@ -160,12 +276,18 @@ fn token_error<E: Endpoint<R>, R: WebRequest>(
                 .into(),
             )?;
             response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         TokenError::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
+            telemetry::failure(json.description().kind().description());
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::Unauthorized {
@@ -177,14 +299,20 @@ fn token_error<E: Endpoint<R>, R: WebRequest>(
             response
                 .unauthorized(&scheme)
                 .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         TokenError::Primitive(_) => {
+            telemetry::failure("server_error");
             // FIXME: give the context for restoration.
-            return Err(endpoint.error(OAuthError::PrimitiveError));
+            return primitive_error(
+                endpoint,
+                request,
+                "access token: registrar, authorizer or issuer failed",
+            );
         }
     })
 }
@@ -211,11 +339,13 @@ impl<E: Endpoint<R>, R: WebRequest> TokenEndpoint for WrappedToken<E, R> {
 }
 
 impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
-    pub fn new(request: &'a mut R, credentials: bool) -> Self {
-        Self::new_or_fail(request, credentials).unwrap_or_else(Self::from_err)
+    pub fn new(request: &'a mut R, credentials: bool, limit: Option<usize>) -> Self {
+        Self::new_or_fail(request, credentials, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &'a mut R, credentials: bool) -> Result<Self, FailParse<R::Error>> {
+    fn new_or_fail(
+        request: &'a mut R, credentials: bool, limit: Option<usize>,
+    ) -> Result<Self, FailParse<R::Error>> {
         // If there is a header, it must parse correctly.
         let authorization = match request.authheader() {
             Err(err) => return Err(FailParse::Err(err)),
@@ -223,12 +353,32 @@ impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
             Ok(None) => None,
         };
 
+        // Copied as the parsed body borrows the request for the whole flow.
+        let raw_body = request.raw_body().map(<[u8]>::to_vec);
+        let dpop = match request.header("DPoP") {
+            Err(err) => return Err(FailParse::Err(err)),
+            Ok(header) => header.map(Cow::into_owned),
+        };
+        let certificate = match request.client_certificate() {
+            Err(err) => return Err(FailParse::Err(err)),
+            Ok(certificate) => certificate.map(Cow::into_owned),
+        };
+
+        let body = request.urlbody().map_err(FailParse::Err)?;
+        if exceeds_parameter_limit(&*body, limit) {
+            return Err(FailParse::Invalid);
+        }
+
         Ok(WrappedRequest {
             request: PhantomData,
-            body: request.urlbody().map_err(FailParse::Err)?,
+            body,
             authorization,
             error: None,
             allow_credentials_in_body: credentials,
+            require_pkce_verifier: false,
+            raw_body,
+            dpop,
+            certificate,
         })
     }
 
@@ -239,6 +389,10 @@ impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
             authorization: None,
             error: Some(err),
             allow_credentials_in_body: false,
+            require_pkce_verifier: false,
+            raw_body: None,
+            dpop: None,
+            certificate: None,
         }
     }
 
@@ -315,6 +469,22 @@ impl<'a, R: WebRequest> TokenRequest for WrappedRequest<'a, R> {
     fn allow_credentials_in_body(&self) -> bool {
         self.allow_credentials_in_body
     }
+
+    fn require_pkce_verifier(&self) -> bool {
+        self.require_pkce_verifier
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
+    }
+
+    fn dpop_proof(&self) -> Option<Cow<'_, str>> {
+        self.dpop.as_deref().map(Cow::Borrowed)
+    }
+
+    fn client_certificate(&self) -> Option<Cow<'_, [u8]>> {
+        self.certificate.as_deref().map(Cow::Borrowed)
+    }
 }
 
 impl<E> From<Invalid> for FailParse<E> {
@@ -1,9 +1,13 @@
 use crate::code_grant::authorization::{
-    authorization_code, Error as AuthorizationError, Extension, Endpoint as AuthorizationEndpoint,
-    Request as AuthorizationRequest, Pending,
+    authorization_code, AuthorizationResponse, Error as AuthorizationError, Extension,
+    Endpoint as AuthorizationEndpoint, ImplicitResponse, Request as AuthorizationRequest, Pending,
 };
 
+use crate::primitives::clock::SystemClock;
+use crate::primitives::registrar::{ClientUrl, ExactUrl, RegistrarError};
+
 use super::*;
+use super::telemetry::{self, FlowSpan};
 
 /// All relevant methods for handling authorization code requests.
 pub struct AuthorizationFlow<E, R>
@@ -12,6 +16,18 @@ where
     R: WebRequest,
 {
     endpoint: WrappedAuthorization<E, R>,
+    response_types: Vec<String>,
+    pkce: PublicPkce,
+    implicit: bool,
+    reject_excess_scope: bool,
+    issuer: Option<String>,
+}
+
+/// The PKCE requirements on public clients.
+#[derive(Clone, Copy, Default)]
+struct PublicPkce {
+    required: bool,
+    s256: bool,
 }
 
 struct WrappedAuthorization<E: Endpoint<R>, R: WebRequest> {
@@ -27,8 +43,25 @@ struct WrappedRequest<'a, R: WebRequest + 'a> {
     /// The query in the url.
     query: Cow<'a, dyn QueryParameter + 'static>,
 
+    /// The accepted values of `response_type`.
+    response_types: &'a [String],
+
+    /// The PKCE requirements on public clients.
+    pkce: PublicPkce,
+
+    /// Whether `response_type=token` is answered with an implicit grant.
+    implicit: bool,
+
+    /// Whether requests for more than the negotiated scope are rejected.
+    reject_excess_scope: bool,
+
     /// An error if one occurred.
-    error: Option<R::Error>,
+    error: Option<FailParse<R::Error>>,
+}
+
+enum FailParse<E> {
+    Invalid,
+    Err(E),
 }
 
 struct AuthorizationPending<'a, E: 'a, R: 'a>
@@ -39,6 +72,7 @@ where
     endpoint: &'a mut WrappedAuthorization<E, R>,
     pending: Pending,
     request: R,
+    issuer: Option<&'a str>,
 }
 
 /// A processed authentication request that may be waiting for authorization by the resource owner.
@@ -117,9 +151,93 @@ where
                 extension_fallback: (),
                 r_type: PhantomData,
             },
+            response_types: vec!["code".to_string()],
+            pkce: PublicPkce::default(),
+            implicit: false,
+            reject_excess_scope: false,
+            issuer: None,
         })
     }
 
+    /// Restrict the accepted values of `response_type`.
+    ///
+    /// Requests with any other value are redirected to the client with an
+    /// `unsupported_response_type` error before the grant is negotiated. Only `code` is accepted by
+    /// default. Only `code`, and `token` once the implicit grant is enabled with
+    /// [`allow_implicit`], can be served by this flow. Other values, such as `code id_token`, are
+    /// answered with `unsupported_response_type` as well, even when they are listed here.
+    ///
+    /// [`allow_implicit`]: #method.allow_implicit
+    pub fn allow_response_types<I>(&mut self, response_types: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.response_types = response_types.into_iter().map(Into::into).collect();
+    }
+
+    /// Require a PKCE `code_challenge` in requests of public clients.
+    ///
+    /// Public clients are those the registrar accepts without any passphrase. Their requests
+    /// without a challenge are redirected with an `invalid_request` error, while confidential
+    /// clients are unaffected. The challenge still needs to be bound to the grant and verified by
+    /// the [`Pkce`] extension, so the endpoint should use it as well.
+    ///
+    /// [`Pkce`]: ../frontends/simple/extensions/struct.Pkce.html
+    pub fn require_pkce_for_public_clients(&mut self) {
+        self.pkce.required = true;
+    }
+
+    /// Require public clients to use the `S256` PKCE method.
+    ///
+    /// This implies [`require_pkce_for_public_clients`] and additionally rejects challenges using
+    /// the `plain` method, which is also assumed when no method is given.
+    ///
+    /// [`require_pkce_for_public_clients`]: #method.require_pkce_for_public_clients
+    pub fn require_pkce_s256(&mut self) {
+        self.pkce.required = true;
+        self.pkce.s256 = true;
+    }
+
+    /// Answer requests with `response_type=token` with an implicit grant.
+    ///
+    /// The implicit grant is deprecated by current security best practices, new clients should
+    /// use the code grant with PKCE instead. This adds `token` to the accepted response types.
+    /// After consent, an access token is issued directly by the issuer of the endpoint, which must
+    /// be available, and sent in the fragment of the redirect uri without a refresh token.
+    /// Requests of confidential clients are answered with an `unauthorized_client` error.
+    pub fn allow_implicit(&mut self) {
+        self.implicit = true;
+        if !self
+            .response_types
+            .iter()
+            .any(|response_type| response_type == "token")
+        {
+            self.response_types.push("token".to_string());
+        }
+    }
+
+    /// Reject requests for more than the scope negotiated by the registrar.
+    ///
+    /// By default a request for a scope that the registrar does not grant to the client is
+    /// narrowed to the negotiated scope, for example its registered default scope. With this, such
+    /// requests are instead redirected to the client with an `invalid_scope` error before the owner
+    /// is asked for consent. Requests without a scope still receive the negotiated scope.
+    pub fn reject_excess_scope(&mut self) {
+        self.reject_excess_scope = true;
+    }
+
+    /// Include the issuer identifier of this server in successful responses.
+    ///
+    /// This adds the `iss` parameter of [RFC 9207] which lets clients detect mix-up attacks. It
+    /// should be the same identifier as verified by an [`IssuerIdentifier`] extension, if any.
+    ///
+    /// [RFC 9207]: https://tools.ietf.org/html/rfc9207
+    /// [`IssuerIdentifier`]: ../frontends/simple/extensions/struct.IssuerIdentifier.html
+    pub fn issuer_identifier<I: Into<String>>(&mut self, issuer: I) {
+        self.issuer = Some(issuer.into());
+    }
+
     /// Use the checked endpoint to execute the authorization flow for a request.
     ///
     /// In almost all cases this is followed by executing `finish` on the result but some users may
@@ -130,20 +248,47 @@ where
     /// When the registrar or the authorizer returned by the endpoint is suddenly `None` when
     /// previously it was `Some(_)`.
     pub fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
-        let negotiated = authorization_code(&mut self.endpoint, &WrappedRequest::new(&mut request));
+        let _span = FlowSpan::enter("authorization", None);
+        let limit = self.endpoint.inner.parameter_limit();
+        let mut wrapped = WrappedRequest::new(
+            &mut request,
+            &self.response_types,
+            self.pkce,
+            self.implicit,
+            limit,
+        );
+        wrapped.reject_excess_scope = self.reject_excess_scope;
+        if let Some(client_id) = AuthorizationRequest::client_id(&wrapped) {
+            telemetry::client_id(&client_id);
+        }
+        let negotiated = authorization_code(&mut self.endpoint, &wrapped);
+        if let Err(AuthorizationError::Ignore) = negotiated {
+            let cause = ignored_cause(&self.endpoint.inner, &wrapped);
+            self.endpoint
+                .inner
+                .on_flow_error(cause, &mut FlowErrorDetail::DeniedSilently);
+        }
 
         let inner = match negotiated {
             Err(err) => match authorization_error(&mut self.endpoint.inner, &mut request, err) {
                 Ok(response) => AuthorizationPartialInner::Failed { request, response },
                 Err(error) => AuthorizationPartialInner::Error { request, error },
             },
-            Ok(negotiated) => AuthorizationPartialInner::Pending {
-                pending: AuthorizationPending {
-                    endpoint: &mut self.endpoint,
-                    pending: negotiated,
-                    request,
-                },
-            },
+            Ok(negotiated) => {
+                telemetry::grant_type(match negotiated.is_implicit() {
+                    true => "implicit",
+                    false => "authorization_code",
+                });
+                telemetry::scope(&negotiated.pre_grant().scope);
+                AuthorizationPartialInner::Pending {
+                    pending: AuthorizationPending {
+                        endpoint: &mut self.endpoint,
+                        pending: negotiated,
+                        request,
+                        issuer: self.issuer.as_deref(),
+                    },
+                }
+            }
         };
 
         let partial = AuthorizationPartial { inner };
@@ -168,16 +313,59 @@ impl<'a, E: Endpoint<R>, R: WebRequest> AuthorizationPartial<'a, E, R> {
     }
 }
 
+/// Determine why the request was ignored.
+///
+/// The flow does not distinguish these cases since all of them must be answered in the same way,
+/// without redirecting to the client. The registrar is asked again whether the client exists.
+fn ignored_cause<E: Endpoint<R>, R: WebRequest>(endpoint: &E, request: &WrappedRequest<R>) -> FlowError {
+    let client_id = match AuthorizationRequest::client_id(request) {
+        Some(client_id) if request.valid() => client_id,
+        _ => return FlowError::MalformedRequest,
+    };
+
+    if let Some(uri) = AuthorizationRequest::redirect_uri(request) {
+        if uri.parse::<ExactUrl>().is_err() {
+            return FlowError::InvalidRedirectUri;
+        }
+    }
+
+    let registrar = match endpoint.registrar() {
+        Some(registrar) => registrar,
+        None => return FlowError::Primitive,
+    };
+
+    let client_url = ClientUrl {
+        client_id,
+        redirect_uri: None,
+    };
+
+    match registrar.bound_redirect(client_url) {
+        Ok(_) => FlowError::InvalidRedirectUri,
+        Err(RegistrarError::Unspecified) => FlowError::UnknownClient,
+        Err(RegistrarError::PrimitiveError) => FlowError::Primitive,
+    }
+}
+
 fn authorization_error<E: Endpoint<R>, R: WebRequest>(
     endpoint: &mut E, request: &mut R, error: AuthorizationError,
 ) -> Result<R::Response, E::Error> {
     match error {
-        AuthorizationError::Ignore => Err(endpoint.error(OAuthError::DenySilently)),
+        // The cause has already been reported by `execute`, which still has the parsed request.
+        AuthorizationError::Ignore => {
+            telemetry::failure("ignored");
+            Err(endpoint.error(OAuthError::DenySilently))
+        }
         AuthorizationError::Redirect(mut target) => {
+            endpoint.on_flow_error(
+                target.description().kind().into(),
+                &mut FlowErrorDetail::Authorization(target.description()),
+            );
+            telemetry::failure(target.description().kind().description());
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::Redirect {
                     authorization_error: Some(target.description()),
+                    authorization_response: None,
                 }
                 .into(),
             )?;
@@ -186,26 +374,61 @@ fn authorization_error<E: Endpoint<R>, R: WebRequest>(
                 .map_err(|err| endpoint.web_error(err))?;
             Ok(response)
         }
-        AuthorizationError::PrimitiveError => Err(endpoint.error(OAuthError::PrimitiveError)),
+        AuthorizationError::PrimitiveError => {
+            telemetry::failure("server_error");
+            primitive_error(endpoint, request, "authorization: registrar or authorizer failed")
+        }
     }
 }
 
 impl<'a, E: Endpoint<R>, R: WebRequest> AuthorizationPending<'a, E, R> {
     /// Resolve the pending status using the endpoint to query owner consent.
     fn finish(mut self) -> (R, Result<R::Response, E::Error>) {
+        if let Some(who) = self.remembered_consent() {
+            return self.authorize(who);
+        }
+
+        let trust = self
+            .endpoint
+            .inner
+            .registrar()
+            .unwrap()
+            .client_trust(&self.pending.pre_grant().client_id);
+        let mut solicitation = self.pending.as_solicitation().with_client_trust(trust);
+        if let Some(descriptions) = self.endpoint.inner.scope_descriptions() {
+            solicitation = solicitation.with_scope_descriptions(descriptions);
+        }
+
         let checked = self
             .endpoint
             .owner_solicitor()
-            .check_consent(&mut self.request, self.pending.as_solicitation());
+            .check_consent(&mut self.request, solicitation);
 
         match checked {
             OwnerConsent::Denied => self.deny(),
             OwnerConsent::InProgress(resp) => self.in_progress(resp),
             OwnerConsent::Authorized(who) => self.authorize(who),
+            OwnerConsent::AuthorizedScope(who, scope) => self.authorize_scope(who, scope),
             OwnerConsent::Error(err) => (self.request, Err(self.endpoint.inner.web_error(err))),
         }
     }
 
+    /// The owner of the request, if they have consented to the same grant before.
+    fn remembered_consent(&mut self) -> Option<String> {
+        self.endpoint.inner.consent_store()?;
+        let owner = self
+            .endpoint
+            .owner_solicitor()
+            .authenticated_owner(&mut self.request)?;
+        let grant = self.pending.pre_grant();
+        let store = self.endpoint.inner.consent_store()?;
+        if store.has_consent(&owner, &grant.client_id, &grant.scope) {
+            Some(owner)
+        } else {
+            None
+        }
+    }
+
     /// Postpones the decision over the request, to display data to the resource owner.
     ///
     /// This should happen at least once for each request unless the resource owner has already
@@ -213,38 +436,125 @@ impl<'a, E: Endpoint<R>, R: WebRequest> AuthorizationPending<'a, E, R> {
     /// the resource owner, if no login has been detected or if multiple accounts are allowed to be
     /// logged in at the same time.
     fn in_progress(self, response: R::Response) -> (R, Result<R::Response, E::Error>) {
+        telemetry::in_progress();
         (self.request, Ok(response))
     }
 
     /// Denies the request, the client is not allowed access.
     fn deny(mut self) -> (R, Result<R::Response, E::Error>) {
-        let result = self.pending.deny();
-        let result = Self::convert_result(result, &mut self.endpoint.inner, &mut self.request);
+        let result = match self.pending.deny() {
+            Ok(_) => unreachable!("Denying a request never succeeds"),
+            Err(err) => authorization_error(&mut self.endpoint.inner, &mut self.request, err),
+        };
 
         (self.request, result)
     }
 
+    /// Tells the system that the resource owner has approved only part of the grant.
+    fn authorize_scope(mut self, who: String, scope: Scope) -> (R, Result<R::Response, E::Error>) {
+        match self.pending.restrict_scope(scope) {
+            Ok(()) => self.authorize(who),
+            Err(()) => self.deny(),
+        }
+    }
+
     /// Tells the system that the resource owner with the given id has approved the grant.
     fn authorize(mut self, who: String) -> (R, Result<R::Response, E::Error>) {
-        let result = self.pending.authorize(self.endpoint, who.into());
-        let result = Self::convert_result(result, &mut self.endpoint.inner, &mut self.request);
+        // The owner may have consented to less than requested.
+        telemetry::scope(&self.pending.pre_grant().scope);
+        if let Some(store) = self.endpoint.inner.consent_store() {
+            let grant = self.pending.pre_grant();
+            store.remember(&who, &grant.client_id, &grant.scope);
+        }
+
+        if self.pending.is_implicit() {
+            return self.authorize_implicit(who);
+        }
+
+        let pre_grant = self.pending.pre_grant();
+        let client_id = self.endpoint.inner.metrics().map(|_| pre_grant.client_id.clone());
+        let result = self.pending.authorization_response(self.endpoint, who.into());
+        if let (Ok(_), Some(metrics), Some(client_id)) =
+            (&result, self.endpoint.inner.metrics(), &client_id)
+        {
+            metrics.on_code_issued(client_id);
+        }
+        let result =
+            Self::convert_result(result, self.issuer, &mut self.endpoint.inner, &mut self.request);
 
         (self.request, result)
     }
 
+    /// Issues the access token of an implicit grant directly.
+    fn authorize_implicit(mut self, who: String) -> (R, Result<R::Response, E::Error>) {
+        let endpoint = &mut self.endpoint.inner;
+        let pre_grant = self.pending.pre_grant();
+        let client_id = endpoint.metrics().map(|_| pre_grant.client_id.clone());
+        let now = endpoint.clock().unwrap_or(&SystemClock).now();
+        let result = match endpoint.issuer_mut() {
+            Some(issuer) => self.pending.implicit_response(issuer, who.into(), now),
+            None => {
+                let result = primitive_error(
+                    endpoint,
+                    &mut self.request,
+                    "authorization: implicit grant requires an issuer",
+                );
+                return (self.request, result);
+            }
+        };
+
+        let result = match result {
+            Ok(implicit) => {
+                telemetry::scope(&implicit.scope);
+                telemetry::token(&implicit.access_token);
+                telemetry::success();
+                if let (Some(metrics), Some(client_id)) = (endpoint.metrics(), &client_id) {
+                    metrics.on_token_issued(client_id);
+                }
+                Self::implicit_redirect(implicit, endpoint, &mut self.request)
+            }
+            Err(err) => authorization_error(endpoint, &mut self.request, err),
+        };
+
+        (self.request, result)
+    }
+
+    fn implicit_redirect(
+        implicit: ImplicitResponse, endpoint: &mut E, request: &mut R,
+    ) -> Result<R::Response, E::Error> {
+        let mut response = endpoint.response(
+            request,
+            InnerTemplate::Redirect {
+                authorization_error: None,
+                authorization_response: None,
+            }
+            .into(),
+        )?;
+        response
+            .redirect(implicit.redirect_url())
+            .map_err(|err| endpoint.web_error(err))?;
+        Ok(response)
+    }
+
     fn convert_result(
-        result: Result<Url, AuthorizationError>, endpoint: &mut E, request: &mut R,
+        result: Result<AuthorizationResponse, AuthorizationError>, issuer: Option<&str>,
+        endpoint: &mut E, request: &mut R,
     ) -> Result<R::Response, E::Error> {
         match result {
-            Ok(url) => {
+            Ok(mut authorization) => {
+                telemetry::success();
+                authorization.iss = issuer.map(str::to_string);
                 let mut response = endpoint.response(
                     request,
                     InnerTemplate::Redirect {
                         authorization_error: None,
+                        authorization_response: Some(&authorization),
                     }
                     .into(),
                 )?;
-                response.redirect(url).map_err(|err| endpoint.web_error(err))?;
+                response
+                    .redirect(authorization.redirect_url())
+                    .map_err(|err| endpoint.web_error(err))?;
                 Ok(response)
             }
             Err(err) => authorization_error(endpoint, request, err),
@@ -276,22 +586,41 @@ impl<E: Endpoint<R>, R: WebRequest> AuthorizationEndpoint for WrappedAuthorizati
 }
 
 impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
-    pub fn new(request: &'a mut R) -> Self {
-        Self::new_or_fail(request).unwrap_or_else(Self::from_err)
+    pub fn new(
+        request: &'a mut R, response_types: &'a [String], pkce: PublicPkce, implicit: bool,
+        limit: Option<usize>,
+    ) -> Self {
+        Self::new_or_fail(request, response_types, pkce, implicit, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &'a mut R) -> Result<Self, R::Error> {
+    fn new_or_fail(
+        request: &'a mut R, response_types: &'a [String], pkce: PublicPkce, implicit: bool,
+        limit: Option<usize>,
+    ) -> Result<Self, FailParse<R::Error>> {
+        let query = request.query().map_err(FailParse::Err)?;
+        if exceeds_parameter_limit(&*query, limit) {
+            return Err(FailParse::Invalid);
+        }
+
         Ok(WrappedRequest {
             request: PhantomData,
-            query: request.query()?,
+            query,
+            response_types,
+            pkce,
+            implicit,
+            reject_excess_scope: false,
             error: None,
         })
     }
 
-    fn from_err(err: R::Error) -> Self {
+    fn from_err(err: FailParse<R::Error>) -> Self {
         WrappedRequest {
             request: PhantomData,
             query: Cow::Owned(Default::default()),
+            response_types: &[],
+            pkce: PublicPkce::default(),
+            implicit: false,
+            reject_excess_scope: false,
             error: Some(err),
         }
     }
@@ -325,4 +654,25 @@ impl<'a, R: WebRequest + 'a> AuthorizationRequest for WrappedRequest<'a, R> {
     fn extension(&self, key: &str) -> Option<Cow<str>> {
         self.query.unique_value(key)
     }
+
+    fn allow_response_type(&self, response_type: &str) -> bool {
+        let servable = response_type == "code" || (self.implicit && response_type == "token");
+        servable && self.response_types.iter().any(|allowed| allowed == response_type)
+    }
+
+    fn require_pkce_for_public_clients(&self) -> bool {
+        self.pkce.required
+    }
+
+    fn require_pkce_s256(&self) -> bool {
+        self.pkce.s256
+    }
+
+    fn allow_implicit(&self) -> bool {
+        self.implicit
+    }
+
+    fn reject_excess_scope(&self) -> bool {
+        self.reject_excess_scope
+    }
 }
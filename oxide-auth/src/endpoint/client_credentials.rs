@@ -13,14 +13,19 @@ use crate::code_grant::error::{AccessTokenError, AccessTokenErrorType};
 use crate::code_grant::refresh::ErrorDescription;
 use crate::primitives::{registrar::Registrar, issuer::Issuer};
 use super::{
-    Endpoint, InnerTemplate, OAuthError, QueryParameter, WebRequest, WebResponse,
-    is_authorization_method, OwnerConsent,
+    Endpoint, FlowError, FlowErrorDetail, InnerTemplate, OAuthError, QueryParameter, WebRequest,
+    WebResponse, exceeds_parameter_limit, is_authorization_method, primitive_error, OwnerConsent,
 };
 
 /// Offers access tokens to authenticated third parties.
 ///
 /// A client may request a token that provides access to their own resources.
 ///
+/// There is no resource owner to interact with, so an owner solicitor is optional. When the
+/// endpoint has one, it is asked to assign an owner for the grant without user interaction.
+/// Otherwise the token is issued right after authenticating the client, with the client
+/// itself as the owner.
+///
 /// Client credentials can be allowed to appear in the request body instead of being
 /// required to be passed as HTTP Basic authorization. This is not recommended and must be
 /// enabled explicitely. See [`allow_credentials_in_body`] for details.
@@ -34,6 +39,7 @@ where
     endpoint: WrappedToken<E, R>,
     allow_credentials_in_body: bool,
     allow_refresh_token: bool,
+    solicit_owner: bool,
 }
 
 struct WrappedToken<E: Endpoint<R>, R: WebRequest> {
@@ -100,6 +106,7 @@ where
             },
             allow_credentials_in_body: false,
             allow_refresh_token: false,
+            solicit_owner: true,
         })
     }
 
@@ -118,7 +125,7 @@ where
     ///
     /// According to [RFC-6749 Section 4.4.3][4.4.3] "A refresh token SHOULD NOT be included" in
     /// the response for the client credentials grant. Following that recommendation, the default
-    /// behaviour of this flow is to discard any refresh token that is returned from the issuer.
+    /// behaviour of this flow is to request a token without a refresh token from the issuer.
     ///
     /// If this behaviour is not what you want (it is possible that your particular application
     /// does have a use for a client credentials refresh token), you may enable this feature.
@@ -128,6 +135,16 @@ where
         self.allow_refresh_token = allow;
     }
 
+    /// Control whether the owner solicitor of the endpoint is consulted.
+    ///
+    /// Enabled by default, in which case a solicitor provided by the endpoint may assign an owner
+    /// or restrict the scope of the grant. When disabled, the solicitor is never called and the
+    /// client is always treated as its own resource owner, receiving its registered scope or the
+    /// requested restriction of it.
+    pub fn solicit_owner(&mut self, solicit: bool) {
+        self.solicit_owner = solicit;
+    }
+
     /// Use the checked endpoint to check for authorization for a resource.
     ///
     /// ## Panics
@@ -135,36 +152,60 @@ where
     /// When the registrar, authorizer, or issuer returned by the endpoint is suddenly
     /// `None` when previously it was `Some(_)`.
     pub fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        let limit = self.endpoint.inner.parameter_limit();
         let pending = client_credentials(
             &mut self.endpoint,
-            &WrappedRequest::new(&mut request, self.allow_credentials_in_body),
+            &WrappedRequest::new(&mut request, self.allow_credentials_in_body, limit),
         );
-        let pending = match pending {
+        let mut pending = match pending {
             Err(error) => {
                 return client_credentials_error(&mut self.endpoint.inner, &mut request, error)
             }
             Ok(pending) => pending,
         };
 
-        let consent = self
-            .endpoint
-            .inner
-            .owner_solicitor()
-            .unwrap()
-            .check_consent(&mut request, pending.as_solicitation());
+        let solicitor = if self.solicit_owner {
+            self.endpoint.inner.owner_solicitor()
+        } else {
+            None
+        };
+
+        let consent = match solicitor {
+            Some(solicitor) => solicitor.check_consent(&mut request, pending.as_solicitation()),
+            // The client acts on its own behalf.
+            None => OwnerConsent::Authorized(pending.as_solicitation().pre_grant().client_id.clone()),
+        };
+
+        // A restricted scope that can not be granted is treated as a denial.
+        let consent = match consent {
+            OwnerConsent::AuthorizedScope(owner_id, scope) => match pending.restrict_scope(scope) {
+                Ok(()) => OwnerConsent::Authorized(owner_id),
+                Err(()) => OwnerConsent::Denied,
+            },
+            consent => consent,
+        };
 
         let owner_id = match consent {
             OwnerConsent::Authorized(owner_id) => owner_id,
+            OwnerConsent::AuthorizedScope(..) => unreachable!("Restricted scopes were resolved above"),
             OwnerConsent::Error(error) => return Err(self.endpoint.inner.web_error(error)),
             OwnerConsent::InProgress(..) => {
                 // User interaction is not permitted in the client credentials flow, so
                 // an InProgress response is invalid.
-                return Err(self.endpoint.inner.error(OAuthError::PrimitiveError));
+                return primitive_error(
+                    &mut self.endpoint.inner,
+                    &mut request,
+                    "client credentials: owner solicitor requested user interaction",
+                );
             }
             OwnerConsent::Denied => {
                 let mut error = AccessTokenError::default();
                 error.set_type(AccessTokenErrorType::InvalidClient);
                 let mut json = ErrorDescription { error };
+                self.endpoint.inner.on_flow_error(
+                    FlowError::AccessDenied,
+                    &mut FlowErrorDetail::AccessToken(json.description()),
+                );
                 let mut response = self.endpoint.inner.response(
                     &mut request,
                     InnerTemplate::Unauthorized {
@@ -176,6 +217,9 @@ where
                 response
                     .client_error()
                     .map_err(|err| self.endpoint.inner.web_error(err))?;
+                response
+                    .no_store()
+                    .map_err(|err| self.endpoint.inner.web_error(err))?;
                 response
                     .body_json(&json.to_json())
                     .map_err(|err| self.endpoint.inner.web_error(err))?;
@@ -183,17 +227,28 @@ where
             }
         };
 
+        let client_id = self
+            .endpoint
+            .inner
+            .metrics()
+            .map(|_| pending.as_solicitation().pre_grant().client_id.clone());
         let token = match pending.issue(&mut self.endpoint, owner_id, self.allow_refresh_token) {
             Err(error) => {
                 return client_credentials_error(&mut self.endpoint.inner, &mut request, error)
             }
             Ok(token) => token,
         };
+        if let (Some(metrics), Some(client_id)) = (self.endpoint.inner.metrics(), &client_id) {
+            metrics.on_token_issued(client_id);
+        }
 
         let mut response = self
             .endpoint
             .inner
             .response(&mut request, InnerTemplate::Ok.into())?;
+        response
+            .no_store()
+            .map_err(|err| self.endpoint.inner.web_error(err))?;
         response
             .body_json(&token.to_json())
             .map_err(|err| self.endpoint.inner.web_error(err))?;
@@ -205,8 +260,15 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
     endpoint: &mut E, request: &mut R, error: ClientCredentialsError,
 ) -> Result<R::Response, E::Error> {
     Ok(match error {
-        ClientCredentialsError::Ignore => return Err(endpoint.error(OAuthError::DenySilently)),
+        ClientCredentialsError::Ignore => {
+            endpoint.on_flow_error(FlowError::UnknownClient, &mut FlowErrorDetail::DeniedSilently);
+            return Err(endpoint.error(OAuthError::DenySilently));
+        }
         ClientCredentialsError::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::BadRequest {
@@ -215,12 +277,17 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
                 .into(),
             )?;
             response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         ClientCredentialsError::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.cause(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::Unauthorized {
@@ -232,6 +299,7 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
             response
                 .unauthorized(&scheme)
                 .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
@@ -239,7 +307,11 @@ fn client_credentials_error<E: Endpoint<R>, R: WebRequest>(
         }
         ClientCredentialsError::Primitive(_) => {
             // FIXME: give the context for restoration.
-            return Err(endpoint.error(OAuthError::PrimitiveError));
+            return primitive_error(
+                endpoint,
+                request,
+                "client credentials: registrar or issuer failed",
+            );
         }
     })
 }
@@ -262,11 +334,13 @@ impl<E: Endpoint<R>, R: WebRequest> ClientCredentialsEndpoint for WrappedToken<E
 }
 
 impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
-    pub fn new(request: &'a mut R, credentials: bool) -> Self {
-        Self::new_or_fail(request, credentials).unwrap_or_else(Self::from_err)
+    pub fn new(request: &'a mut R, credentials: bool, limit: Option<usize>) -> Self {
+        Self::new_or_fail(request, credentials, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &'a mut R, credentials: bool) -> Result<Self, FailParse<R::Error>> {
+    fn new_or_fail(
+        request: &'a mut R, credentials: bool, limit: Option<usize>,
+    ) -> Result<Self, FailParse<R::Error>> {
         // If there is a header, it must parse correctly.
         let authorization = match request.authheader() {
             Err(err) => return Err(FailParse::Err(err)),
@@ -274,9 +348,14 @@ impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
             Ok(None) => None,
         };
 
+        let body = request.urlbody().map_err(FailParse::Err)?;
+        if exceeds_parameter_limit(&*body, limit) {
+            return Err(FailParse::Invalid);
+        }
+
         Ok(WrappedRequest {
             request: PhantomData,
-            body: request.urlbody().map_err(FailParse::Err)?,
+            body,
             authorization,
             error: None,
             allow_credentials_in_body: credentials,
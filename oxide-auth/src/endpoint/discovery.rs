@@ -0,0 +1,211 @@
+//! Documents describing the authorization server to clients and resource servers.
+use std::marker::PhantomData;
+
+use serde::Serialize;
+use url::Url;
+
+use super::{Endpoint, InnerTemplate, OAuthError, WebRequest, WebResponse};
+
+/// The authorization server metadata, as described in [RFC 8414].
+///
+/// Serve the result of [`to_json`] with the content type `application/json` under
+/// `/.well-known/oauth-authorization-server`, for example with a [`MetadataFlow`]. Only the
+/// `issuer` and the supported response types are required, all other members are omitted from the
+/// document while they are empty.
+///
+/// ```
+/// # use oxide_auth::endpoint::ServerMetadata;
+/// let mut metadata = ServerMetadata::new("https://auth.example");
+/// metadata.token_endpoint = Some("https://auth.example/token".parse().unwrap());
+/// metadata.grant_types_supported = vec!["authorization_code".into(), "refresh_token".into()];
+/// assert!(metadata.to_json().contains(r#""issuer":"https://auth.example""#));
+/// ```
+///
+/// [RFC 8414]: https://tools.ietf.org/html/rfc8414
+/// [`to_json`]: #method.to_json
+/// [`MetadataFlow`]: struct.MetadataFlow.html
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct ServerMetadata {
+    /// The issuer identifier of the server, the same as in the `iss` claim of its tokens.
+    pub issuer: String,
+
+    /// The url of the authorization endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorization_endpoint: Option<Url>,
+
+    /// The url of the token endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_endpoint: Option<Url>,
+
+    /// The url of the key set verifying the signed tokens, see [`JwksFlow`].
+    ///
+    /// [`JwksFlow`]: struct.JwksFlow.html
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_uri: Option<Url>,
+
+    /// The scope tokens clients may request.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes_supported: Vec<String>,
+
+    /// The values of the `response_type` parameter, `code` by default.
+    pub response_types_supported: Vec<String>,
+
+    /// The values of the `grant_type` parameter of the token endpoint.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub grant_types_supported: Vec<String>,
+
+    /// The methods with which clients authenticate to the token endpoint.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+
+    /// The PKCE code challenge methods, such as `S256`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub code_challenge_methods_supported: Vec<String>,
+
+    /// Whether authorization responses carry the `iss` parameter, see `IssuerIdentifier`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub authorization_response_iss_parameter_supported: bool,
+}
+
+/// Answers requests for the authorization server metadata of an endpoint.
+///
+/// The document is the [`ServerMetadata`] of [`Endpoint::server_metadata`]. The flow does not
+/// look at the request, so it should only be routed `GET` requests of the well-known path.
+///
+/// [`ServerMetadata`]: struct.ServerMetadata.html
+/// [`Endpoint::server_metadata`]: trait.Endpoint.html#method.server_metadata
+pub struct MetadataFlow<E, R>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    endpoint: E,
+    request: PhantomData<R>,
+}
+
+/// Answers requests for the key set verifying the tokens of an endpoint.
+///
+/// The document is the [`JwkSet`] of [`Endpoint::jwks`]. The flow does not look at the request,
+/// so it should only be routed `GET` requests of the path announced as `jwks_uri`.
+///
+/// [`JwkSet`]: struct.JwkSet.html
+/// [`Endpoint::jwks`]: trait.Endpoint.html#method.jwks
+#[cfg(feature = "ed25519")]
+pub struct JwksFlow<E, R>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    endpoint: E,
+    request: PhantomData<R>,
+}
+
+impl ServerMetadata {
+    /// Describe the server with the given issuer identifier, supporting the `code` response type.
+    pub fn new<I: Into<String>>(issuer: I) -> Self {
+        ServerMetadata {
+            issuer: issuer.into(),
+            authorization_endpoint: None,
+            token_endpoint: None,
+            jwks_uri: None,
+            scopes_supported: Vec::new(),
+            response_types_supported: vec!["code".to_string()],
+            grant_types_supported: Vec::new(),
+            token_endpoint_auth_methods_supported: Vec::new(),
+            code_challenge_methods_supported: Vec::new(),
+            authorization_response_iss_parameter_supported: false,
+        }
+    }
+
+    /// Serialize the metadata as a JSON document.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl<E, R> MetadataFlow<E, R>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    /// Wrap the endpoint if it has server metadata.
+    ///
+    /// The endpoint needs to provide (return `Some`):
+    ///
+    /// * a `ServerMetadata` from `server_metadata`
+    pub fn prepare(mut endpoint: E) -> Result<Self, E::Error> {
+        if endpoint.server_metadata().is_none() {
+            return Err(endpoint.error(OAuthError::PrimitiveError));
+        }
+
+        Ok(MetadataFlow {
+            endpoint,
+            request: PhantomData,
+        })
+    }
+
+    /// Answer the request with the metadata document.
+    ///
+    /// ## Panics
+    ///
+    /// When the metadata returned by the endpoint is suddenly `None` when previously it was
+    /// `Some(_)`.
+    pub fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        let document = self
+            .endpoint
+            .server_metadata()
+            .expect("Server metadata was checked when preparing the flow")
+            .to_json();
+        json_response(&mut self.endpoint, &mut request, &document)
+    }
+}
+
+#[cfg(feature = "ed25519")]
+impl<E, R> JwksFlow<E, R>
+where
+    E: Endpoint<R>,
+    R: WebRequest,
+{
+    /// Wrap the endpoint if it has a key set.
+    ///
+    /// The endpoint needs to provide (return `Some`):
+    ///
+    /// * a `JwkSet` from `jwks`
+    pub fn prepare(mut endpoint: E) -> Result<Self, E::Error> {
+        if endpoint.jwks().is_none() {
+            return Err(endpoint.error(OAuthError::PrimitiveError));
+        }
+
+        Ok(JwksFlow {
+            endpoint,
+            request: PhantomData,
+        })
+    }
+
+    /// Answer the request with the key set document.
+    ///
+    /// ## Panics
+    ///
+    /// When the key set returned by the endpoint is suddenly `None` when previously it was
+    /// `Some(_)`.
+    pub fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        let document = self
+            .endpoint
+            .jwks()
+            .expect("Key set was checked when preparing the flow")
+            .to_json();
+        json_response(&mut self.endpoint, &mut request, &document)
+    }
+}
+
+fn json_response<E: Endpoint<R>, R: WebRequest>(
+    endpoint: &mut E, request: &mut R, document: &str,
+) -> Result<R::Response, E::Error> {
+    let mut response = endpoint.response(request, InnerTemplate::Ok.into())?;
+    response.ok().map_err(|err| endpoint.web_error(err))?;
+    response
+        .body_json(document)
+        .map_err(|err| endpoint.web_error(err))?;
+    Ok(response)
+}
@@ -1,5 +1,10 @@
 use std::error;
 use std::fmt;
+use std::time::Duration;
+
+use crate::code_grant::error::{
+    AccessTokenError, AccessTokenErrorType, AuthorizationError, AuthorizationErrorType,
+};
 
 /// Errors which should not or need not be communicated to the requesting party but which are of
 /// interest to the server. See the documentation for each enum variant for more documentation on
@@ -19,6 +24,13 @@ pub enum OAuthError {
     /// implementation of the primitive underlying those two.
     PrimitiveError,
 
+    /// One of the primitives used to complete the operation is temporarily unavailable.
+    ///
+    /// Unlike a `PrimitiveError` the failure is transient, for example when the storage backing a
+    /// primitive can not be reached. Clients should retry the request after the given duration,
+    /// frontends answer with status 503 and a `Retry-After` header.
+    TemporarilyUnavailable(Duration),
+
     /// The incoming request was malformed.
     ///
     /// This implies that it did not change any internal state. Note that this differs from an
@@ -34,9 +46,156 @@ impl fmt::Display for OAuthError {
         match self {
             OAuthError::DenySilently => fmt.write_str("OAuthError: Request should be silently denied"),
             OAuthError::PrimitiveError => fmt.write_str("OAuthError: Server component failed"),
+            OAuthError::TemporarilyUnavailable(_) => {
+                fmt.write_str("OAuthError: Server component temporarily unavailable")
+            }
             OAuthError::BadRequest => fmt.write_str("OAuthError: Bad request"),
         }
     }
 }
 
 impl error::Error for OAuthError {}
+
+/// The detailed cause of a failed flow.
+///
+/// Flows report this to `Endpoint::on_flow_error`, along with a `FlowErrorDetail`, so that a
+/// frontend can tell, for example, an unknown client from a mismatched redirect uri. These details
+/// are meant for logs, metrics or an audit trail and must not be forwarded to the client. In
+/// particular an authorization request with an unknown client or an invalid redirect uri is
+/// answered without any indication of which of the two failed, and telling them apart in a
+/// response would leak which clients are registered.
+///
+/// Each cause converts into the coarser `OAuthError` that is used to answer the request.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowError {
+    /// The request was missing a required parameter or was otherwise malformed.
+    MalformedRequest,
+
+    /// The request named a client that is not registered.
+    UnknownClient,
+
+    /// The redirect uri was malformed or not registered for the client.
+    InvalidRedirectUri,
+
+    /// The client failed to authenticate.
+    InvalidClient,
+
+    /// The client is not allowed to use the requested flow.
+    UnauthorizedClient,
+
+    /// The requested scope was rejected by the registrar.
+    ScopeDenied,
+
+    /// The resource owner denied the request.
+    AccessDenied,
+
+    /// The grant was unknown, was issued to another client, or did not match the request.
+    InvalidGrant,
+
+    /// The authorization code was found but had already expired.
+    ExpiredCode,
+
+    /// The grant type is not supported by the server.
+    UnsupportedGrantType,
+
+    /// The response type is not supported by the server.
+    UnsupportedResponseType,
+
+    /// One of the primitives used to complete the operation failed.
+    Primitive,
+}
+
+impl fmt::Display for FlowError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let text = match self {
+            FlowError::MalformedRequest => "malformed request",
+            FlowError::UnknownClient => "unknown client",
+            FlowError::InvalidRedirectUri => "invalid redirect uri",
+            FlowError::InvalidClient => "client authentication failed",
+            FlowError::UnauthorizedClient => "client not authorized for this flow",
+            FlowError::ScopeDenied => "scope denied",
+            FlowError::AccessDenied => "access denied by resource owner",
+            FlowError::InvalidGrant => "invalid grant",
+            FlowError::ExpiredCode => "authorization code expired",
+            FlowError::UnsupportedGrantType => "unsupported grant type",
+            FlowError::UnsupportedResponseType => "unsupported response type",
+            FlowError::Primitive => "server component failed",
+        };
+
+        write!(fmt, "FlowError: {}", text)
+    }
+}
+
+impl error::Error for FlowError {}
+
+impl From<FlowError> for OAuthError {
+    fn from(error: FlowError) -> Self {
+        match error {
+            FlowError::UnknownClient | FlowError::InvalidRedirectUri => OAuthError::DenySilently,
+            FlowError::Primitive => OAuthError::PrimitiveError,
+            _ => OAuthError::BadRequest,
+        }
+    }
+}
+
+/// The detail accompanying the cause of a failed flow.
+///
+/// Passed to `Endpoint::on_flow_error` together with the `FlowError`. Where the client is sent an
+/// error, the detail holds it so that the endpoint can still adjust it, for example with an
+/// `ErrorMapping`. Displaying the detail gives a short text for server logs.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum FlowErrorDetail<'a> {
+    /// The error redirected to the client of an authorization request.
+    Authorization(&'a mut AuthorizationError),
+
+    /// The error sent by the token endpoint, for access token, refresh and client credentials
+    /// requests alike.
+    AccessToken(&'a mut AccessTokenError),
+
+    /// The request is denied without sending an error to the client.
+    DeniedSilently,
+
+    /// A primitive failed, with a description that is never sent to the client.
+    Primitive(&'a str),
+}
+
+impl fmt::Display for FlowErrorDetail<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            FlowErrorDetail::Authorization(error) => write!(fmt, "{}", error.error),
+            FlowErrorDetail::AccessToken(error) => write!(fmt, "{}", error.error),
+            FlowErrorDetail::DeniedSilently => fmt.write_str("denied silently"),
+            FlowErrorDetail::Primitive(detail) => fmt.write_str(detail),
+        }
+    }
+}
+
+impl From<AuthorizationErrorType> for FlowError {
+    fn from(kind: AuthorizationErrorType) -> Self {
+        match kind {
+            AuthorizationErrorType::InvalidRequest => FlowError::MalformedRequest,
+            AuthorizationErrorType::UnauthorizedClient => FlowError::UnauthorizedClient,
+            AuthorizationErrorType::AccessDenied => FlowError::AccessDenied,
+            AuthorizationErrorType::UnsupportedResponseType => FlowError::UnsupportedResponseType,
+            AuthorizationErrorType::InvalidScope => FlowError::ScopeDenied,
+            AuthorizationErrorType::ServerError | AuthorizationErrorType::TemporarilyUnavailable => {
+                FlowError::Primitive
+            }
+        }
+    }
+}
+
+impl From<AccessTokenErrorType> for FlowError {
+    fn from(kind: AccessTokenErrorType) -> Self {
+        match kind {
+            AccessTokenErrorType::InvalidRequest => FlowError::MalformedRequest,
+            AccessTokenErrorType::InvalidClient => FlowError::InvalidClient,
+            AccessTokenErrorType::InvalidGrant => FlowError::InvalidGrant,
+            AccessTokenErrorType::UnauthorizedClient => FlowError::UnauthorizedClient,
+            AccessTokenErrorType::UnsupportedGrantType => FlowError::UnsupportedGrantType,
+            AccessTokenErrorType::InvalidScope => FlowError::ScopeDenied,
+        }
+    }
+}
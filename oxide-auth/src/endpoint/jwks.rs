@@ -0,0 +1,125 @@
+//! Distribution of the public keys verifying signed tokens.
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::Serialize;
+
+use crate::primitives::generator::Assertion;
+
+/// A JSON Web Key Set, as described in [RFC 7517].
+///
+/// Serve the result of [`to_json`] with the content type `application/json`, usually under
+/// `/.well-known/jwks.json`, so that resource servers can verify JWT access tokens on their own.
+/// During a key rotation, add both the new and the old key such that tokens issued with the old
+/// key remain verifiable until they expire. Each key is identified by the same `kid` that is
+/// placed in the header of the tokens it signed.
+///
+/// ```
+/// # use oxide_auth::endpoint::JwkSet;
+/// # use oxide_auth::primitives::generator::Assertion;
+/// let current = Assertion::ed25519(&[1; 32]);
+/// let previous = Assertion::ed25519(&[2; 32]);
+///
+/// let mut jwks = JwkSet::new();
+/// jwks.push(&current).unwrap();
+/// jwks.push(&previous).unwrap();
+/// assert_eq!(jwks.keys().len(), 2);
+/// ```
+///
+/// [RFC 7517]: https://tools.ietf.org/html/rfc7517
+/// [`to_json`]: #method.to_json
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+/// A single public signing key of a `JwkSet`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Jwk {
+    kty: &'static str,
+    #[serde(rename = "use")]
+    usage: &'static str,
+    kid: String,
+    alg: &'static str,
+    crv: &'static str,
+    x: String,
+}
+
+impl JwkSet {
+    /// Create an empty key set.
+    pub fn new() -> Self {
+        JwkSet::default()
+    }
+
+    /// Add the public key verifying the tokens of an assertion.
+    ///
+    /// Fails for assertions with a symmetric key, as the key must never be published.
+    pub fn push(&mut self, assertion: &Assertion) -> Result<(), ()> {
+        let public_key = assertion.public_key().ok_or(())?;
+        let kid = assertion.key_id().ok_or(())?;
+
+        if self.keys.iter().any(|key| key.kid == kid) {
+            return Ok(());
+        }
+
+        self.keys.push(Jwk {
+            kty: "OKP",
+            usage: "sig",
+            kid,
+            alg: assertion.jws_algorithm(),
+            crv: "Ed25519",
+            x: URL_SAFE_NO_PAD.encode(public_key),
+        });
+
+        Ok(())
+    }
+
+    /// The keys in this set.
+    pub fn keys(&self) -> &[Jwk] {
+        &self.keys
+    }
+
+    /// Serialize the set as a JWKS document.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl Jwk {
+    /// The identifier of this key.
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::generator::AssertionKind;
+
+    #[test]
+    fn jwks_document() {
+        let signer = Assertion::ed25519(&[1; 32]);
+        let verifier = Assertion::ed25519_verifier(&signer.public_key().unwrap()).unwrap();
+        let other = Assertion::ed25519(&[2; 32]);
+
+        let mut jwks = JwkSet::new();
+        jwks.push(&signer).unwrap();
+        // The same key is only published once.
+        jwks.push(&verifier).unwrap();
+        jwks.push(&other).unwrap();
+        assert!(jwks
+            .push(&Assertion::new(AssertionKind::HmacSha256, &[0; 32]))
+            .is_err());
+
+        let document: serde_json::Value = serde_json::from_str(&jwks.to_json()).unwrap();
+        let keys = document["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0]["kty"], "OKP");
+        assert_eq!(keys[0]["crv"], "Ed25519");
+        assert_eq!(keys[0]["use"], "sig");
+        assert_eq!(keys[0]["alg"], "EdDSA");
+        assert_eq!(keys[0]["kid"], signer.key_id().unwrap());
+        assert_eq!(keys[0]["x"], URL_SAFE_NO_PAD.encode(signer.public_key().unwrap()));
+        assert_ne!(keys[0]["kid"], keys[1]["kid"]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Counting the outcomes of flows, for example for dashboards.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Receives the outcomes of flows.
+///
+/// Flows call the endpoint's [`Endpoint::metrics`] at the points where a token or code is issued
+/// or access to a resource is denied. All methods do nothing by default, so an implementation only
+/// needs to provide the counters it is interested in. The methods are called synchronously within
+/// the flow and should not block, for example by only incrementing counters or sending to a
+/// channel.
+///
+/// [`Endpoint::metrics`]: trait.Endpoint.html#method.metrics
+pub trait Metrics {
+    /// An access token was issued to the client, by any grant.
+    fn on_token_issued(&self, _client_id: &str) {}
+
+    /// An access token was refreshed for the client.
+    fn on_token_refreshed(&self, _client_id: &str) {}
+
+    /// An authorization code was issued to the client.
+    fn on_code_issued(&self, _client_id: &str) {}
+
+    /// The client redeemed an authorization code for an access token.
+    fn on_code_redeemed(&self, _client_id: &str) {}
+
+    /// A request to a resource was denied.
+    ///
+    /// The reason is the error code of the response, such as `invalid_token` or
+    /// `insufficient_scope`, or `no_authentication` when the request carried no token at all.
+    fn on_resource_denied(&self, _reason: &str) {}
+}
+
+/// Counts the outcomes of flows with atomic counters.
+///
+/// The counters are not labelled by client or reason. Share the counters with the endpoint through
+/// an `Arc` to read them while the endpoint is in use.
+///
+/// ```
+/// # use oxide_auth::endpoint::{MetricCounters, Metrics};
+/// use std::sync::Arc;
+///
+/// let counters = Arc::new(MetricCounters::new());
+/// let metrics: Arc<dyn Metrics> = counters.clone();
+/// metrics.on_token_issued("client");
+/// assert_eq!(counters.tokens_issued(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct MetricCounters {
+    tokens_issued: AtomicU64,
+    tokens_refreshed: AtomicU64,
+    codes_issued: AtomicU64,
+    codes_redeemed: AtomicU64,
+    resources_denied: AtomicU64,
+}
+
+impl MetricCounters {
+    /// Create counters starting at zero.
+    pub fn new() -> Self {
+        MetricCounters::default()
+    }
+
+    /// The number of issued access tokens.
+    pub fn tokens_issued(&self) -> u64 {
+        self.tokens_issued.load(Ordering::Relaxed)
+    }
+
+    /// The number of refreshed access tokens.
+    pub fn tokens_refreshed(&self) -> u64 {
+        self.tokens_refreshed.load(Ordering::Relaxed)
+    }
+
+    /// The number of issued authorization codes.
+    pub fn codes_issued(&self) -> u64 {
+        self.codes_issued.load(Ordering::Relaxed)
+    }
+
+    /// The number of authorization codes redeemed for an access token.
+    pub fn codes_redeemed(&self) -> u64 {
+        self.codes_redeemed.load(Ordering::Relaxed)
+    }
+
+    /// The number of denied resource requests.
+    pub fn resources_denied(&self) -> u64 {
+        self.resources_denied.load(Ordering::Relaxed)
+    }
+}
+
+impl Metrics for MetricCounters {
+    fn on_token_issued(&self, _: &str) {
+        self.tokens_issued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_token_refreshed(&self, _: &str) {
+        self.tokens_refreshed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_code_issued(&self, _: &str) {
+        self.codes_issued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_code_redeemed(&self, _: &str) {
+        self.codes_redeemed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_resource_denied(&self, _: &str) {
+        self.resources_denied.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn on_token_issued(&self, client_id: &str) {
+        (**self).on_token_issued(client_id)
+    }
+
+    fn on_token_refreshed(&self, client_id: &str) {
+        (**self).on_token_refreshed(client_id)
+    }
+
+    fn on_code_issued(&self, client_id: &str) {
+        (**self).on_code_issued(client_id)
+    }
+
+    fn on_code_redeemed(&self, client_id: &str) {
+        (**self).on_code_redeemed(client_id)
+    }
+
+    fn on_resource_denied(&self, reason: &str) {
+        (**self).on_resource_denied(reason)
+    }
+}
@@ -31,37 +31,57 @@
 mod authorization;
 mod accesstoken;
 mod client_credentials;
+mod discovery;
 mod error;
+#[cfg(feature = "ed25519")]
+mod jwks;
+mod metrics;
 mod refresh;
 mod resource;
 mod query;
+mod telemetry;
 
 #[cfg(test)]
 mod tests;
 
 use std::borrow::Cow;
 use std::marker::PhantomData;
+use std::time::Duration;
 
 pub use crate::primitives::authorizer::Authorizer;
 pub use crate::primitives::issuer::Issuer;
 pub use crate::primitives::registrar::Registrar;
-pub use crate::primitives::scope::Scope;
+pub use crate::primitives::scope::{Scope, ScopeDescription, ScopeDescriptions};
+pub use crate::code_grant::error::ErrorMapping;
 
 use crate::code_grant::resource::{Error as ResourceError};
 use crate::code_grant::error::{AuthorizationError, AccessTokenError};
+use crate::primitives::binding::Binding;
+use crate::primitives::clock::Clock;
+use crate::primitives::consent::ConsentStore;
+use crate::primitives::grant::Grant;
 
 use url::Url;
 
 // Re-export the extension traits under prefixed names.
+pub use crate::code_grant::authorization::AuthorizationResponse;
 pub use crate::code_grant::authorization::Extension as AuthorizationExtension;
 pub use crate::code_grant::accesstoken::Extension as AccessTokenExtension;
 pub use crate::code_grant::client_credentials::Extension as ClientCredentialsExtension;
+pub use crate::code_grant::refresh::Extension as RefreshExtension;
+pub use crate::code_grant::resource::Extension as ResourceExtension;
 
-pub use crate::primitives::registrar::PreGrant;
+pub use crate::primitives::registrar::{ClientTrust, PreGrant};
 pub use self::authorization::*;
 pub use self::accesstoken::*;
 pub use self::client_credentials::ClientCredentialsFlow;
-pub use self::error::OAuthError;
+pub use self::discovery::{MetadataFlow, ServerMetadata};
+#[cfg(feature = "ed25519")]
+pub use self::discovery::JwksFlow;
+pub use self::error::{FlowError, FlowErrorDetail, OAuthError};
+#[cfg(feature = "ed25519")]
+pub use self::jwks::{Jwk, JwkSet};
+pub use self::metrics::{MetricCounters, Metrics};
 pub use self::refresh::RefreshFlow;
 pub use self::resource::*;
 pub use self::query::*;
@@ -77,6 +97,16 @@ pub enum OwnerConsent<Response: WebResponse> {
     /// Authorization was granted by the specified user.
     Authorized(String),
 
+    /// Authorization was granted by the specified user for only part of the requested scope.
+    ///
+    /// The solicitation presents the scope chosen by [`Registrar::negotiate`], which has already
+    /// run when the owner is asked. The scope given here must be a non-empty subset of it and
+    /// replaces it for the issued grant, so that the client learns the reduced scope with its
+    /// token. Anything else denies the request, the owner can never widen the negotiated scope.
+    ///
+    /// [`Registrar::negotiate`]: ../primitives/registrar/trait.Registrar.html#tymethod.negotiate
+    AuthorizedScope(String, Scope),
+
     /// An error occurred while checking authorization.
     Error(Response::Error),
 }
@@ -102,6 +132,9 @@ pub enum ResponseStatus {
     /// The response is issued because the requesting party was not authorized.
     Unauthorized,
 
+    /// The requesting party was authorized but its token lacks the scope for the resource.
+    Forbidden,
+
     /// The response redirects in the code grant flow.
     Redirect,
 
@@ -110,6 +143,9 @@ pub enum ResponseStatus {
 
     /// This response is normal and expected.
     Ok,
+
+    /// The server failed to handle the request.
+    ServerError,
 }
 
 /// Encapsulated different types of responses reasons.
@@ -127,8 +163,8 @@ enum InnerTemplate<'a> {
     Unauthorized {
         /// The underlying cause for denying access.
         ///
-        /// The http authorization header is to be set according to this field.
-        #[allow(dead_code)]
+        /// The http authorization header is to be set according to this field. A token with
+        /// insufficient scope is answered with `403 Forbidden` instead of `401 Unauthorized`.
         error: Option<ResourceError>,
 
         /// Information on an access token error.
@@ -149,6 +185,9 @@ enum InnerTemplate<'a> {
         /// Endpoints may modify this description to add additional explanatory text or a reference
         /// uri for clients or resource owners seeking explanation.
         authorization_error: Option<&'a mut AuthorizationError>,
+
+        /// The successful response to an authorization request, as encoded in the redirect.
+        authorization_response: Option<&'a AuthorizationResponse>,
     },
 
     /// The request did not conform to specification or was otheriwse invalid.
@@ -171,6 +210,12 @@ enum InnerTemplate<'a> {
     /// therefore it is constructed using the `WebResponse` trait methods. Try not to tamper with
     /// the format too much, such as unsetting a body etc. after the flow has finished.
     Ok,
+
+    /// An internal error that is answered with a generic description.
+    ///
+    /// Only used by endpoints that enable `opaque_errors`. The details of the failure are not
+    /// part of the response, they have been passed to `Endpoint::on_error` instead.
+    ServerError,
 }
 
 /// A pending solicitation to a resource owner.
@@ -182,6 +227,9 @@ enum InnerTemplate<'a> {
 pub struct Solicitation<'flow> {
     pub(crate) grant: Cow<'flow, PreGrant>,
     pub(crate) state: Option<Cow<'flow, str>>,
+    pub(crate) descriptions: Option<Vec<ScopeDescription>>,
+    pub(crate) trust: ClientTrust,
+    pub(crate) requested_scope: Option<Cow<'flow, Scope>>,
 }
 
 impl<'flow> Solicitation<'flow> {
@@ -192,6 +240,9 @@ impl<'flow> Solicitation<'flow> {
         Solicitation {
             grant: Cow::Owned(self.grant.into_owned()),
             state: self.state.map(|state| Cow::Owned(state.into_owned())),
+            descriptions: self.descriptions,
+            trust: self.trust,
+            requested_scope: self.requested_scope.map(|scope| Cow::Owned(scope.into_owned())),
         }
     }
 
@@ -204,6 +255,18 @@ impl<'flow> Solicitation<'flow> {
         self.grant.as_ref()
     }
 
+    /// The scope as originally requested by the client.
+    ///
+    /// The registrar may have negotiated it down, or chosen another scope entirely, so this can
+    /// differ from the scope of the [`pre_grant`]. Only the latter is granted. A consent page can
+    /// show both, to explain that the client asked for more than it will receive. This is `None`
+    /// when the request did not name a scope and the registrar chose the default of the client.
+    ///
+    /// [`pre_grant`]: #method.pre_grant
+    pub fn requested_scope(&self) -> Option<&Scope> {
+        self.requested_scope.as_deref()
+    }
+
     /// The state provided by the client request.
     ///
     /// This will need to be provided to the response back to the client so it must be preserved
@@ -215,6 +278,31 @@ impl<'flow> Solicitation<'flow> {
         }
     }
 
+    /// Human readable descriptions of each token in the requested scope.
+    ///
+    /// Uses the [`ScopeDescriptions`] of the endpoint, if it has any. Tokens without a registered
+    /// description are described by the raw token. The result is ordered by token.
+    ///
+    /// [`ScopeDescriptions`]: ../primitives/scope/struct.ScopeDescriptions.html
+    pub fn scope_descriptions(&self) -> Cow<'_, [ScopeDescription]> {
+        match &self.descriptions {
+            Some(descriptions) => Cow::Borrowed(descriptions),
+            None => Cow::Owned(
+                ScopeDescriptions::new().describe_scope(&self.grant.client_id, &self.grant.scope),
+            ),
+        }
+    }
+
+    /// How far the server trusts the requesting client.
+    ///
+    /// Solicitors may use this to skip the consent page for first-party clients, see
+    /// [`TieredSolicitor`]. This is `ClientTrust::ThirdParty` unless the registrar reports otherwise.
+    ///
+    /// [`TieredSolicitor`]: ../frontends/simple/endpoint/struct.TieredSolicitor.html
+    pub fn client_trust(&self) -> ClientTrust {
+        self.trust
+    }
+
     /// Create a new solicitation request from a pre grant.
     ///
     /// You usually wouldn't need to call this manually as it is called by the endpoint's flow and
@@ -223,6 +311,9 @@ impl<'flow> Solicitation<'flow> {
         Solicitation {
             grant: Cow::Borrowed(grant),
             state: None,
+            descriptions: None,
+            trust: ClientTrust::ThirdParty,
+            requested_scope: None,
         }
     }
 
@@ -233,6 +324,28 @@ impl<'flow> Solicitation<'flow> {
             ..self
         }
     }
+
+    /// Add the scope originally requested by the client to the solicitation.
+    pub fn with_requested_scope(self, scope: &'flow Scope) -> Self {
+        Solicitation {
+            requested_scope: Some(Cow::Borrowed(scope)),
+            ..self
+        }
+    }
+
+    /// Set how far the server trusts the requesting client.
+    pub fn with_client_trust(self, trust: ClientTrust) -> Self {
+        Solicitation { trust, ..self }
+    }
+
+    /// Describe the requested scope with the given descriptions.
+    pub fn with_scope_descriptions(self, descriptions: &ScopeDescriptions) -> Self {
+        let described = descriptions.describe_scope(&self.grant.client_id, &self.grant.scope);
+        Solicitation {
+            descriptions: Some(described),
+            ..self
+        }
+    }
 }
 
 /// Checks consent with the owner of a resource, identified in a request.
@@ -243,7 +356,24 @@ impl<'flow> Solicitation<'flow> {
 pub trait OwnerSolicitor<Request: WebRequest> {
     /// Ensure that a user (resource owner) is currently authenticated (for example via a session
     /// cookie) and determine if he has agreed to the presented grants.
+    ///
+    /// Request-scoped data such as the session or the submitted consent form is read from the
+    /// request itself. An owner approving only some of the requested scopes is answered with
+    /// [`OwnerConsent::AuthorizedScope`].
+    ///
+    /// [`OwnerConsent::AuthorizedScope`]: enum.OwnerConsent.html#variant.AuthorizedScope
     fn check_consent(&mut self, _: &mut Request, _: Solicitation) -> OwnerConsent<Request::Response>;
+
+    /// The owner authenticated by the request, if known without interacting with them.
+    ///
+    /// Consulted before `check_consent` by endpoints with a [`ConsentStore`]. An owner who already
+    /// consented to the client and scope is then authorized without being asked again. Returning
+    /// `None` is the default implementation and always asks the owner.
+    ///
+    /// [`ConsentStore`]: ../primitives/consent/trait.ConsentStore.html
+    fn authenticated_owner(&mut self, _: &mut Request) -> Option<String> {
+        None
+    }
 }
 
 /// Determine the scopes applying to a request of a resource.
@@ -285,8 +415,59 @@ pub trait Scopes<Request: WebRequest> {
     ///
     /// One of the scopes needs to be fulfilled by the access token in the request to grant access.
     /// A scope is fulfilled if the set of its part is a subset of the parts in the grant. If the
-    /// slice is empty, then no scope can be fulfilled and the request is always blocked.
+    /// slice is empty, then no scope can be fulfilled and the request is always blocked. An empty
+    /// scope within the slice is fulfilled by every grant, including grants with an empty scope.
+    /// A grant with an empty scope fulfills no other scope.
     fn scopes(&mut self, request: &mut Request) -> &[Scope];
+
+    /// Explain why a request was denied for insufficient scope.
+    ///
+    /// Consulted when the token does not fulfill any of the scopes, including when `scopes`
+    /// returned an empty slice. The explanation is sent to the client as the `error_description`
+    /// of the `WWW-Authenticate` header, next to `error="insufficient_scope"` and the required
+    /// `scope`, see [RFC 6750, Section 3.1]. Returning `None` is the default implementation.
+    ///
+    /// [RFC 6750, Section 3.1]: https://tools.ietf.org/html/rfc6750#section-3.1
+    fn deny_reason(&mut self, _request: &mut Request) -> Option<String> {
+        None
+    }
+}
+
+/// Checks that the session in which a token was issued is still active.
+///
+/// Consulted by the resource flow after the token itself was validated, see
+/// [`Endpoint::session_validator`]. This allows revoking the access of tokens that can not be
+/// revoked individually, such as self-contained signed tokens, for example when an owner logs out
+/// everywhere. The session can be identified by the owner of the grant or by an extension that
+/// was attached when the token was issued.
+///
+/// [`Endpoint::session_validator`]: trait.Endpoint.html#method.session_validator
+pub trait SessionValidator {
+    /// Determine whether the session of a grant has not been revoked.
+    ///
+    /// Access is denied with `invalid_token` when this returns `Ok(false)`. An error indicates
+    /// that the state of the session could not be determined, and fails the flow like any other
+    /// failing primitive.
+    fn session_active(&mut self, grant: &Grant) -> Result<bool, ()>;
+}
+
+/// Checks that the client presenting a bound token is in possession of its key.
+///
+/// Consulted by the resource flow for every valid token whose grant has a [`Binding`], see
+/// [`Endpoint::proof_verifier`]. The authorization scheme of the request was already checked to
+/// agree with the type of the token, so a DPoP bound token reaches this only with the `DPoP`
+/// scheme. The verifier is responsible for the proof itself, for example the DPoP proof in the
+/// header of the same name, or the client certificate of the connection.
+///
+/// [`Binding`]: ../primitives/binding/enum.Binding.html
+/// [`Endpoint::proof_verifier`]: trait.Endpoint.html#method.proof_verifier
+pub trait ProofVerifier<Request: WebRequest> {
+    /// Determine whether the request proves possession of the key of a bound token.
+    ///
+    /// The `token` is the access token as presented by the client. Access is denied with
+    /// `invalid_token` when this returns `Ok(false)`, an error fails the flow like any other
+    /// failing primitive.
+    fn verify(&mut self, request: &mut Request, token: &str, binding: &Binding) -> Result<bool, ()>;
 }
 
 /// Abstraction of web requests with several different abstractions and constructors needed by an
@@ -313,6 +494,49 @@ pub trait WebRequest {
     /// Contents of the authorization header or none if none exists. An Err value indicates a
     /// malformed header or request.
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error>;
+
+    /// The value of the cookie with the given name or none if no such cookie was sent.
+    ///
+    /// Only consulted by flows which have been explicitely configured to accept credentials from
+    /// cookies, such as [`ResourceFlow::token_cookie`]. The default implementation never finds any
+    /// cookie. An Err value indicates a malformed `Cookie` header or request.
+    ///
+    /// [`ResourceFlow::token_cookie`]: struct.ResourceFlow.html#method.token_cookie
+    fn cookie(&mut self, _name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// The value of the header with the given name or none if no such header was sent.
+    ///
+    /// Header names are case-insensitive. Only consulted by extensions relying on headers other
+    /// than `Authorization`, such as the `DPoP` proof of a [`DpopValidator`]. The default
+    /// implementation never finds any header. An Err value indicates a malformed header, for
+    /// example one that is not valid utf-8 or that was sent several times.
+    ///
+    /// [`DpopValidator`]: ../code_grant/extensions/struct.DpopValidator.html
+    fn header(&mut self, _name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// The DER encoded certificate the client authenticated its TLS connection with.
+    ///
+    /// Frontends terminating mutual TLS, or running behind a proxy that forwards the certificate,
+    /// provide it so that clients can authenticate with it and tokens can be bound to it, see
+    /// [RFC 8705]. The frontend must only return a certificate whose private key the client proved
+    /// to possess in the handshake. The default implementation never finds any certificate.
+    ///
+    /// [RFC 8705]: https://tools.ietf.org/html/rfc8705
+    fn client_certificate(&mut self) -> Result<Option<Cow<'_, [u8]>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// The unparsed body of the request, if the frontend has buffered it.
+    ///
+    /// Extensions can use this to read bodies that are not urlencoded forms, for example json
+    /// encoded ones. Returning `None` is the default implementation.
+    fn raw_body(&self) -> Option<&[u8]> {
+        None
+    }
 }
 
 /// Response representation into which the Request is transformed by the code_grant types.
@@ -335,11 +559,57 @@ pub trait WebResponse {
     /// Set the response status to 401 and add a `WWW-Authenticate` header.
     fn unauthorized(&mut self, header_value: &str) -> Result<(), Self::Error>;
 
+    /// Set the response status to 403 and add a `WWW-Authenticate` header.
+    ///
+    /// Used when the request was authenticated but its token lacks the required scope. The
+    /// default implementation falls back to `unauthorized` so that existing responses keep
+    /// working, frontends should override it.
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.unauthorized(header_value)
+    }
+
+    /// Set the response status to 500.
+    ///
+    /// The default implementation falls back to `client_error` so that existing responses keep
+    /// working, frontends should override it.
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.client_error()
+    }
+
+    /// Set the response status to 429, asking the client to slow down.
+    ///
+    /// When `retry_after` is given the response should carry a `Retry-After` header with the
+    /// duration in whole seconds. The default implementation falls back to `client_error` so that
+    /// existing responses keep working, frontends should override it.
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        let _ = retry_after;
+        self.client_error()
+    }
+
+    /// Set the response status to 503, the server is temporarily unable to handle the request.
+    ///
+    /// When `retry_after` is given the response should carry a `Retry-After` header with the
+    /// duration in whole seconds. The default implementation falls back to `server_error` so that
+    /// existing responses keep working, frontends should override it.
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        let _ = retry_after;
+        self.server_error()
+    }
+
     /// A pure text response with no special media type set.
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error>;
 
     /// Json repsonse data, with media type `aplication/json.
     fn body_json(&mut self, data: &str) -> Result<(), Self::Error>;
+
+    /// Forbid caches from storing the response.
+    ///
+    /// Called on all responses of the token endpoint, which must carry `Cache-Control: no-store`
+    /// and `Pragma: no-cache` headers. The default implementation does nothing so that existing
+    /// responses keep working, frontends should override it.
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 /// Intermediate trait to flow specific extensions.
@@ -364,6 +634,16 @@ pub trait Extension {
     fn client_credentials(&mut self) -> Option<&mut dyn ClientCredentialsExtension> {
         None
     }
+
+    /// The handler for refresh extensions.
+    fn refresh(&mut self) -> Option<&mut dyn RefreshExtension> {
+        None
+    }
+
+    /// The handler for resource extensions.
+    fn resource(&mut self) -> Option<&mut dyn ResourceExtension> {
+        None
+    }
 }
 
 /// Fuses requests and primitives into a coherent system to give a response.
@@ -438,6 +718,116 @@ pub trait Endpoint<Request: WebRequest> {
     fn extension(&mut self) -> Option<&mut dyn Extension> {
         None
     }
+
+    /// Check the sessions of otherwise valid tokens in the resource flow.
+    ///
+    /// Returning `None` is the default implementation and accepts every valid token.
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        None
+    }
+
+    /// Verify the proof of possession accompanying bound tokens in the resource flow.
+    ///
+    /// Returning `None` is the default implementation. Access with tokens that are bound to a key
+    /// is then always denied, while other tokens are unaffected.
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<Request>> {
+        None
+    }
+
+    /// Descriptions of scope tokens presented to the owner solicitor.
+    ///
+    /// Returning `None` is the default implementation and describes each token by itself.
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        None
+    }
+
+    /// Inspect the detailed cause of a failed flow.
+    ///
+    /// Called once for each request that a flow rejects or that fails due to a primitive, before
+    /// the response is created and regardless of `opaque_errors`. The cause is finer than the
+    /// `OAuthError` or the error code the client receives and must not be revealed to the client.
+    /// The detail holds the error that is sent to the client, which may still be replaced here for
+    /// example with an `ErrorMapping`, or the description of a failed primitive for server logs.
+    /// The default implementation does nothing.
+    fn on_flow_error(&mut self, _error: FlowError, _detail: &mut FlowErrorDetail<'_>) {}
+
+    /// Answer failed primitives with a generic response instead of an error.
+    ///
+    /// When this returns `true`, a flow whose primitive failed produces a response with status 500
+    /// and the body `{"error":"server_error"}`. Otherwise, the flow returns the result of `error`
+    /// for `OAuthError::PrimitiveError`. Returning `false` is the default implementation.
+    fn opaque_errors(&self) -> bool {
+        false
+    }
+
+    /// Whether the failure of a primitive was only transient.
+    ///
+    /// Called after a primitive failed during a flow. The primitive traits have no way to report
+    /// the cause of their failure, so an endpoint backed by remote storage should track whether the
+    /// storage is reachable and return the duration after which clients should retry. The flow then
+    /// fails with `OAuthError::TemporarilyUnavailable` instead of `OAuthError::PrimitiveError`, or
+    /// answers with status 503 for endpoints with opaque errors. Returning `None` is the default
+    /// implementation and treats all failures as permanent.
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        None
+    }
+
+    /// The maximum number of query or body parameters accepted in a request.
+    ///
+    /// The token flows answer requests with more parameters with `invalid_request` before looking
+    /// at any of them. The authorization flow can not trust the redirect uri of such a request and
+    /// rejects it with `OAuthError::DenySilently` instead. Since frontends parse the parameters
+    /// before the flow runs, they should also bound the size of the body. Returning `None` is the
+    /// default implementation and accepts any number of parameters.
+    fn parameter_limit(&self) -> Option<usize> {
+        None
+    }
+
+    /// The authorization server metadata served by the `MetadataFlow`.
+    ///
+    /// Returning `None` is the default implementation and fails the preparation of that flow but
+    /// has no effect on any other flow.
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        None
+    }
+
+    /// The key set verifying the signed tokens of this endpoint, served by the `JwksFlow`.
+    ///
+    /// This is usually the result of `TokenSigner::jwks`. Returning `None` is the default
+    /// implementation and fails the preparation of that flow but has no effect on any other flow.
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        None
+    }
+
+    /// Remembers the consent of owners, so that they are not asked again for the same grant.
+    ///
+    /// When this returns a store, the authorization flow first asks the solicitor for the
+    /// [`authenticated_owner`] of the request and authorizes them right away if they have consented
+    /// to the client and scope before. Every authorization by the solicitor is then remembered.
+    /// Returning `None` is the default implementation and asks the owner every time.
+    ///
+    /// [`authenticated_owner`]: trait.OwnerSolicitor.html#method.authenticated_owner
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        None
+    }
+
+    /// Counts the outcomes of flows, such as issued tokens and denied resource requests.
+    ///
+    /// Returning `None` is the default implementation and records nothing.
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        None
+    }
+
+    /// The source of the current time for flows, such as the expiry of implicitly issued tokens.
+    ///
+    /// This should be the same clock as the one of the primitives, see [`Clock`]. Returning `None`
+    /// is the default implementation and uses the system time.
+    ///
+    /// [`Clock`]: ../primitives/clock/trait.Clock.html
+    fn clock(&self) -> Option<&dyn Clock> {
+        None
+    }
 }
 
 impl<'a> Template<'a> {
@@ -464,16 +854,39 @@ impl<'a> Template<'a> {
 
     /// Create a redirect template
     pub fn new_redirect(authorization_error: Option<&'a mut AuthorizationError>) -> Self {
-        InnerTemplate::Redirect { authorization_error }.into()
+        InnerTemplate::Redirect {
+            authorization_error,
+            authorization_response: None,
+        }
+        .into()
+    }
+
+    /// Create a redirect template for a successful authorization request
+    pub fn new_authorization_response(authorization_response: &'a AuthorizationResponse) -> Self {
+        InnerTemplate::Redirect {
+            authorization_error: None,
+            authorization_response: Some(authorization_response),
+        }
+        .into()
+    }
+
+    /// Create a server error template
+    pub fn new_server_error() -> Self {
+        InnerTemplate::ServerError.into()
     }
 
     /// The corresponding status code.
     pub fn status(&self) -> ResponseStatus {
         match self.inner {
+            InnerTemplate::Unauthorized {
+                error: Some(ref error),
+                ..
+            } if error.insufficient_scope() => ResponseStatus::Forbidden,
             InnerTemplate::Unauthorized { .. } => ResponseStatus::Unauthorized,
             InnerTemplate::Redirect { .. } => ResponseStatus::Redirect,
             InnerTemplate::BadRequest { .. } => ResponseStatus::BadRequest,
             InnerTemplate::Ok => ResponseStatus::Ok,
+            InnerTemplate::ServerError => ResponseStatus::ServerError,
         }
     }
 
@@ -502,11 +915,39 @@ impl<'a> Template<'a> {
         }
     }
 
+    /// The successful response of the authorization code flow.
+    ///
+    /// The same response is afterwards sent as a redirect through [`WebResponse::redirect`]. It
+    /// can be used to render it in a different form instead, for example as an auto-submitting
+    /// form for the `form_post` response mode.
+    ///
+    /// ```
+    /// # use oxide_auth::endpoint::Template;
+    /// fn inspect(template: Template) {
+    ///     if let Some(response) = template.authorization_response() {
+    ///         eprintln!("[authorization] Issued a code to {}", response.redirect_uri);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`WebResponse::redirect`]: trait.WebResponse.html#tymethod.redirect
+    pub fn authorization_response(&self) -> Option<&AuthorizationResponse> {
+        match &self.inner {
+            InnerTemplate::Redirect {
+                authorization_response,
+                ..
+            } => *authorization_response,
+            _ => None,
+        }
+    }
+
     /// Supplementary information about an error in the access token flow.
     ///
     /// The referenced object can be inspected and manipulated to provided additional information
     /// that is specific to this server or endpoint. Such information could be an error page with
-    /// explanatory information or a customized message.
+    /// explanatory information or a customized message. The error is serialized afterwards, with
+    /// the explanation and its uri as the `error_description` and `error_uri` fields of the json
+    /// body.
     ///
     /// ```
     /// # use oxide_auth::endpoint::Template;
@@ -529,6 +970,26 @@ impl<'a> Template<'a> {
             _ => None,
         }
     }
+
+    /// The cause for denying access to a protected resource.
+    ///
+    /// Distinguishes a token that was rejected outright, answered with `401 Unauthorized`, from a
+    /// valid token lacking the required scope, answered with `403 Forbidden`.
+    ///
+    /// ```
+    /// # use oxide_auth::endpoint::Template;
+    /// fn inspect(template: Template) {
+    ///     if let Some(error) = template.resource_error() {
+    ///         eprintln!("[resource] Access denied, insufficient scope: {}", error.insufficient_scope());
+    ///     }
+    /// }
+    /// ```
+    pub fn resource_error(&self) -> Option<&ResourceError> {
+        match &self.inner {
+            InnerTemplate::Unauthorized { error, .. } => error.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 /// Reborrow contained optional reference.
@@ -557,6 +1018,22 @@ impl<'a, W: WebRequest> WebRequest for &'a mut W {
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
         (**self).authheader()
     }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        (**self).cookie(name)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        (**self).header(name)
+    }
+
+    fn client_certificate(&mut self) -> Result<Option<Cow<'_, [u8]>>, Self::Error> {
+        (**self).client_certificate()
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        (**self).raw_body()
+    }
 }
 
 impl<'a, R: WebRequest, E: Endpoint<R>> Endpoint<R> for &'a mut E {
@@ -597,6 +1074,55 @@ impl<'a, R: WebRequest, E: Endpoint<R>> Endpoint<R> for &'a mut E {
     fn extension(&mut self) -> Option<&mut dyn Extension> {
         (**self).extension()
     }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        (**self).session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<R>> {
+        (**self).proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        (**self).scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        (**self).on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        (**self).opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        (**self).temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        (**self).parameter_limit()
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        (**self).server_metadata()
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        (**self).jwks()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        (**self).consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        (**self).metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        (**self).clock()
+    }
 }
 
 impl<'a, R: WebRequest, E: Endpoint<R> + 'a> Endpoint<R> for Box<E> {
@@ -637,16 +1163,104 @@ impl<'a, R: WebRequest, E: Endpoint<R> + 'a> Endpoint<R> for Box<E> {
     fn extension(&mut self) -> Option<&mut dyn Extension> {
         (**self).extension()
     }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        (**self).session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<R>> {
+        (**self).proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        (**self).scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        (**self).on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        (**self).opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        (**self).temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        (**self).parameter_limit()
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        (**self).server_metadata()
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        (**self).jwks()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        (**self).consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        (**self).metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        (**self).clock()
+    }
 }
 
 impl Extension for () {}
 
+/// Handle the failure of a primitive during a flow.
+///
+/// Reports the failure to `on_flow_error` and then either fails the flow or, for endpoints with opaque
+/// errors, answers with a generic server error. Transient failures are reported and answered as
+/// such.
+fn primitive_error<E: Endpoint<R>, R: WebRequest>(
+    endpoint: &mut E, request: &mut R, detail: &str,
+) -> Result<R::Response, E::Error> {
+    let error = match endpoint.temporarily_unavailable() {
+        Some(retry_after) => OAuthError::TemporarilyUnavailable(retry_after),
+        None => OAuthError::PrimitiveError,
+    };
+
+    endpoint.on_flow_error(FlowError::Primitive, &mut FlowErrorDetail::Primitive(detail));
+    if !endpoint.opaque_errors() {
+        return Err(endpoint.error(error));
+    }
+
+    let mut response = endpoint.response(request, InnerTemplate::ServerError.into())?;
+    let body = match error {
+        OAuthError::TemporarilyUnavailable(retry_after) => {
+            response
+                .service_unavailable(Some(retry_after))
+                .map_err(|err| endpoint.web_error(err))?;
+            r#"{"error":"temporarily_unavailable"}"#
+        }
+        _ => {
+            response.server_error().map_err(|err| endpoint.web_error(err))?;
+            r#"{"error":"server_error"}"#
+        }
+    };
+    response.body_json(body).map_err(|err| endpoint.web_error(err))?;
+    Ok(response)
+}
+
 impl<'a, W: WebRequest, S: OwnerSolicitor<W> + 'a + ?Sized> OwnerSolicitor<W> for &'a mut S {
     fn check_consent(
         &mut self, request: &mut W, solicitation: Solicitation,
     ) -> OwnerConsent<W::Response> {
         (**self).check_consent(request, solicitation)
     }
+
+    fn authenticated_owner(&mut self, request: &mut W) -> Option<String> {
+        (**self).authenticated_owner(request)
+    }
 }
 
 impl<'a, W: WebRequest, S: OwnerSolicitor<W> + 'a + ?Sized> OwnerSolicitor<W> for Box<S> {
@@ -655,6 +1269,10 @@ impl<'a, W: WebRequest, S: OwnerSolicitor<W> + 'a + ?Sized> OwnerSolicitor<W> fo
     ) -> OwnerConsent<W::Response> {
         (**self).check_consent(request, solicitation)
     }
+
+    fn authenticated_owner(&mut self, request: &mut W) -> Option<String> {
+        (**self).authenticated_owner(request)
+    }
 }
 
 impl<W: WebRequest> Scopes<W> for [Scope] {
@@ -679,12 +1297,20 @@ impl<'a, W: WebRequest, S: Scopes<W> + 'a + ?Sized> Scopes<W> for &'a mut S {
     fn scopes(&mut self, request: &mut W) -> &[Scope] {
         (**self).scopes(request)
     }
+
+    fn deny_reason(&mut self, request: &mut W) -> Option<String> {
+        (**self).deny_reason(request)
+    }
 }
 
 impl<'a, W: WebRequest, S: Scopes<W> + 'a + ?Sized> Scopes<W> for Box<S> {
     fn scopes(&mut self, request: &mut W) -> &[Scope] {
         (**self).scopes(request)
     }
+
+    fn deny_reason(&mut self, request: &mut W) -> Option<String> {
+        (**self).deny_reason(request)
+    }
 }
 
 impl<'a> From<InnerTemplate<'a>> for Template<'a> {
@@ -693,6 +1319,11 @@ impl<'a> From<InnerTemplate<'a>> for Template<'a> {
     }
 }
 
+/// Check if the parameters of a request exceed the `parameter_limit` of an endpoint.
+pub fn exceeds_parameter_limit(parameters: &dyn QueryParameter, limit: Option<usize>) -> bool {
+    matches!(limit, Some(limit) if parameters.count() > limit)
+}
+
 /// Check if the header is an authorization method
 pub fn is_authorization_method<'h>(header: &'h str, method: &'static str) -> Option<&'h str> {
     let header_method = header.get(..method.len())?;
@@ -702,3 +1333,70 @@ pub fn is_authorization_method<'h>(header: &'h str, method: &'static str) -> Opt
         None
     }
 }
+
+/// Extract the token of a `Bearer` authorization header of a request.
+///
+/// This is meant for custom resource handlers that check tokens without a [`ResourceFlow`]. The
+/// scheme is matched case-insensitively and whitespace around the token is ignored. A request
+/// without an authorization header, with another scheme, without a token, or with whitespace
+/// within the token yields `None`. Only errors of the request itself are returned.
+///
+/// ```
+/// # use oxide_auth::endpoint::{bearer_token, WebRequest};
+/// fn token_of<W: WebRequest>(request: &mut W) -> Option<String> {
+///     bearer_token(request).ok().flatten()
+/// }
+/// ```
+///
+/// [`ResourceFlow`]: struct.ResourceFlow.html
+pub fn bearer_token<W: WebRequest>(request: &mut W) -> Result<Option<String>, W::Error> {
+    let header = match request.authheader()? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let token = match is_authorization_method(header.trim_start(), "Bearer") {
+        // The scheme must be separated from the token, `Bearerfoo` is no bearer token.
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim(),
+        _ => return Ok(None),
+    };
+
+    if token.is_empty() || token.contains(char::is_whitespace) {
+        return Ok(None);
+    }
+
+    Ok(Some(token.to_owned()))
+}
+
+/// Find the value of the cookie with the given name in the value of a `Cookie` header.
+///
+/// This is meant for frontends implementing [`WebRequest::cookie`]. Pairs are separated by
+/// semicolons as described in [RFC 6265], the name is matched exactly and the double quotes
+/// around a value are removed. A request may carry several `Cookie` headers, each of which should
+/// be searched.
+///
+/// ```
+/// # use oxide_auth::endpoint::cookie_value;
+/// let header = "theme=dark; access_token=\"abc\"";
+/// assert_eq!(cookie_value(header, "access_token"), Some("abc"));
+/// assert_eq!(cookie_value(header, "session"), None);
+/// ```
+///
+/// [`WebRequest::cookie`]: trait.WebRequest.html#method.cookie
+/// [RFC 6265]: https://tools.ietf.org/html/rfc6265#section-4.2.1
+pub fn cookie_value<'h>(header: &'h str, name: &str) -> Option<&'h str> {
+    header.split(';').find_map(|pair| {
+        let (key, value) = pair.trim().split_once('=')?;
+        if key != name {
+            return None;
+        }
+
+        let value = value.trim();
+        Some(
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value),
+        )
+    })
+}
@@ -30,8 +30,31 @@ pub unsafe trait QueryParameter {
     /// query string from different ends.
     fn unique_value(&self, key: &str) -> Option<Cow<str>>;
 
+    /// Get all values associated with a key, in the order they appeared.
+    ///
+    /// Some extensions accept a parameter several times, such as `resource` of [RFC 8707]. Never
+    /// use this for the parameters of OAuth itself which must only appear once, those are read with
+    /// `unique_value`. The default implementation reads the values of the normalized copy.
+    ///
+    /// [RFC 8707]: https://www.rfc-editor.org/rfc/rfc8707
+    fn values(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.normalize()
+            .values_of(key)
+            .map(|value| Cow::Owned(value.to_string()))
+            .collect()
+    }
+
     /// Guarantees that one can grab an owned copy.
     fn normalize(&self) -> NormalizedParameter;
+
+    /// The number of parameters, counting each occurrence of a repeated key.
+    ///
+    /// Maps that collect the values of a key into one entry count each of its values. Used to
+    /// enforce `Endpoint::parameter_limit`. The default implementation counts the parameters of the
+    /// normalized copy.
+    fn count(&self) -> usize {
+        self.normalize().count()
+    }
 }
 
 /// The query parameter normal form.
@@ -49,20 +72,32 @@ pub unsafe trait QueryParameter {
 /// Internally a hashmap but this may change due to optimizations.
 #[derive(Clone, Debug, Default)]
 pub struct NormalizedParameter {
-    /// The value is `None` if the key appeared at least twice.
-    inner: HashMap<Cow<'static, str>, Option<Cow<'static, str>>>,
+    /// All values of a key, the key has a unique value only if there is exactly one.
+    inner: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>,
+
+    /// The number of inserted pairs, including those with a repeated key.
+    pairs: usize,
 }
 
 unsafe impl QueryParameter for NormalizedParameter {
     fn unique_value(&self, key: &str) -> Option<Cow<str>> {
-        self.inner
-            .get(key)
-            .and_then(|val| val.as_ref().map(Cow::as_ref).map(Cow::Borrowed))
+        match self.inner.get(key).map(Vec::as_slice) {
+            Some([value]) => Some(Cow::Borrowed(value.as_ref())),
+            _ => None,
+        }
+    }
+
+    fn values(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.values_of(key).map(Cow::Borrowed).collect()
     }
 
     fn normalize(&self) -> NormalizedParameter {
         self.clone()
     }
+
+    fn count(&self) -> usize {
+        self.pairs
+    }
 }
 
 impl NormalizedParameter {
@@ -74,13 +109,18 @@ impl NormalizedParameter {
     /// Insert a key-value-pair or mark key as dead if already present.
     ///
     /// Since each key must appear at most once, we do not remove it from the map but instead mark
-    /// the key as having a duplicate entry.
+    /// the key as having a duplicate entry. All values are kept and available through `values`.
     pub fn insert_or_poison(&mut self, key: Cow<'static, str>, val: Cow<'static, str>) {
-        let unique_val = Some(val);
+        self.pairs += 1;
+        self.inner.entry(key).or_default().push(val);
+    }
+
+    /// Iterate over all values of a key, in the order they were inserted.
+    pub fn values_of(&self, key: &str) -> impl Iterator<Item = &str> {
         self.inner
-            .entry(key)
-            .and_modify(|val| *val = None)
-            .or_insert(unique_val);
+            .get(key)
+            .into_iter()
+            .flat_map(|values| values.iter().map(Cow::as_ref))
     }
 }
 
@@ -169,6 +209,14 @@ impl ToOwned for dyn QueryParameter + Send {
 pub unsafe trait UniqueValue {
     /// Borrow the unique value reference.
     fn get_unique(&self) -> Option<&str>;
+
+    /// Borrow all values of the collection.
+    ///
+    /// The default implementation returns the unique value, if any. Collections of several values
+    /// override this.
+    fn get_all(&self) -> Vec<&str> {
+        self.get_unique().into_iter().collect()
+    }
 }
 
 unsafe impl<K, V, S: BuildHasher> QueryParameter for HashMap<K, V, S>
@@ -180,20 +228,32 @@ where
         self.get(key).and_then(V::get_unique).map(Cow::Borrowed)
     }
 
+    fn values(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.get(key)
+            .map(|val| val.get_all().into_iter().map(Cow::Borrowed).collect())
+            .unwrap_or_default()
+    }
+
     fn normalize(&self) -> NormalizedParameter {
+        let pairs = QueryParameter::count(self);
         let inner = self
             .iter()
-            .filter_map(|(key, val)| {
-                val.get_unique().map(|value| {
-                    (
-                        Cow::Owned(key.borrow().to_string()),
-                        Some(Cow::Owned(value.to_string())),
-                    )
-                })
+            .map(|(key, val)| {
+                let values = val
+                    .get_all()
+                    .into_iter()
+                    .map(|value| Cow::Owned(value.to_string()))
+                    .collect();
+                (Cow::Owned(key.borrow().to_string()), values)
             })
-            .collect();
+            .filter(|(_, values): &(_, Vec<_>)| !values.is_empty())
+            .collect::<HashMap<_, _>>();
 
-        NormalizedParameter { inner }
+        NormalizedParameter { pairs, inner }
+    }
+
+    fn count(&self) -> usize {
+        self.values().map(|val| val.get_all().len()).sum()
     }
 }
 
@@ -217,6 +277,13 @@ where
         value
     }
 
+    fn values(&self, key: &str) -> Vec<Cow<'_, str>> {
+        self.iter()
+            .filter(|entry| entry.0.borrow() == key)
+            .map(|entry| Cow::Borrowed(entry.1.borrow()))
+            .collect()
+    }
+
     fn normalize(&self) -> NormalizedParameter {
         let mut params = NormalizedParameter::default();
         self.iter()
@@ -229,6 +296,10 @@ where
             .for_each(|(key, val)| params.insert_or_poison(key, val));
         params
     }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
 }
 
 unsafe impl<'a, Q: QueryParameter + 'a + ?Sized> QueryParameter for &'a Q {
@@ -236,9 +307,17 @@ unsafe impl<'a, Q: QueryParameter + 'a + ?Sized> QueryParameter for &'a Q {
         (**self).unique_value(key)
     }
 
+    fn values(&self, key: &str) -> Vec<Cow<'_, str>> {
+        (**self).values(key)
+    }
+
     fn normalize(&self) -> NormalizedParameter {
         (**self).normalize()
     }
+
+    fn count(&self) -> usize {
+        (**self).count()
+    }
 }
 
 unsafe impl<'a, Q: QueryParameter + 'a + ?Sized> QueryParameter for &'a mut Q {
@@ -246,9 +325,17 @@ unsafe impl<'a, Q: QueryParameter + 'a + ?Sized> QueryParameter for &'a mut Q {
         (**self).unique_value(key)
     }
 
+    fn values(&self, key: &str) -> Vec<Cow<'_, str>> {
+        (**self).values(key)
+    }
+
     fn normalize(&self) -> NormalizedParameter {
         (**self).normalize()
     }
+
+    fn count(&self) -> usize {
+        (**self).count()
+    }
 }
 
 unsafe impl UniqueValue for str {
@@ -282,6 +369,10 @@ unsafe impl<V: UniqueValue> UniqueValue for Option<V> {
     fn get_unique(&self) -> Option<&str> {
         self.as_ref().and_then(V::get_unique)
     }
+
+    fn get_all(&self) -> Vec<&str> {
+        self.as_ref().map(V::get_all).unwrap_or_default()
+    }
 }
 
 unsafe impl<V: UniqueValue> UniqueValue for [V] {
@@ -292,24 +383,40 @@ unsafe impl<V: UniqueValue> UniqueValue for [V] {
             self.get(0).and_then(V::get_unique)
         }
     }
+
+    fn get_all(&self) -> Vec<&str> {
+        self.iter().flat_map(V::get_all).collect()
+    }
 }
 
 unsafe impl<V: UniqueValue + ?Sized> UniqueValue for Box<V> {
     fn get_unique(&self) -> Option<&str> {
         (**self).get_unique()
     }
+
+    fn get_all(&self) -> Vec<&str> {
+        (**self).get_all()
+    }
 }
 
 unsafe impl<V: UniqueValue + ?Sized> UniqueValue for Rc<V> {
     fn get_unique(&self) -> Option<&str> {
         (**self).get_unique()
     }
+
+    fn get_all(&self) -> Vec<&str> {
+        (**self).get_all()
+    }
 }
 
 unsafe impl<V: UniqueValue + ?Sized> UniqueValue for Arc<V> {
     fn get_unique(&self) -> Option<&str> {
         (**self).get_unique()
     }
+
+    fn get_all(&self) -> Vec<&str> {
+        (**self).get_all()
+    }
 }
 
 unsafe impl<V: UniqueValue> UniqueValue for Vec<V> {
@@ -320,6 +427,10 @@ unsafe impl<V: UniqueValue> UniqueValue for Vec<V> {
             self.get(0).and_then(V::get_unique)
         }
     }
+
+    fn get_all(&self) -> Vec<&str> {
+        self.iter().flat_map(V::get_all).collect()
+    }
 }
 
 mod test {
@@ -337,4 +448,56 @@ mod test {
         let _ = (&HashMap::<String, Box<String>>::new()) as &dyn QueryParameter;
         let _ = (&HashMap::<String, Box<[Cow<'static, str>]>>::new()) as &dyn QueryParameter;
     }
+
+    #[test]
+    fn count_parameters() {
+        let pairs = vec![("a", "1"), ("b", "2"), ("a", "3")];
+        assert_eq!(QueryParameter::count(&pairs), 3);
+
+        let normalized = pairs.normalize();
+        assert_eq!(normalized.count(), 3);
+        assert_eq!(normalized.unique_value("a"), None);
+
+        let collected: NormalizedParameter = pairs.into_iter().collect();
+        assert_eq!(collected.count(), 3);
+        assert_eq!((&collected as &dyn QueryParameter).count(), 3);
+
+        let map: HashMap<&str, &str> = vec![("a", "1"), ("b", "2")].into_iter().collect();
+        assert_eq!(QueryParameter::count(&map), 2);
+        assert_eq!(map.normalize().count(), 2);
+
+        // Repeating a key in a single entry counts each of its values.
+        let mut repeated: HashMap<String, Vec<String>> = HashMap::new();
+        repeated.insert("a".to_string(), vec!["1".to_string(); 3]);
+        repeated.insert("b".to_string(), vec!["2".to_string()]);
+        assert_eq!(QueryParameter::count(&repeated), 4);
+        assert_eq!(repeated.normalize().count(), 4);
+    }
+
+    #[test]
+    fn repeated_parameter_values() {
+        let pairs = vec![
+            ("resource", "https://a.example"),
+            ("scope", "default"),
+            ("resource", "https://b.example"),
+        ];
+        let resources = ["https://a.example", "https://b.example"];
+        assert_eq!(pairs.values("resource"), resources);
+        assert_eq!(pairs.values("scope"), ["default"]);
+        assert!(pairs.values("missing").is_empty());
+
+        let normalized = pairs.normalize();
+        assert_eq!(normalized.unique_value("resource"), None);
+        assert_eq!(normalized.values("resource"), resources);
+        assert!(normalized.values_of("resource").eq(resources.iter().copied()));
+        let query: &dyn QueryParameter = &normalized;
+        assert_eq!(query.values("resource"), resources);
+        assert_eq!(query.unique_value("scope").as_deref(), Some("default"));
+
+        let mut map: HashMap<&str, Vec<&str>> = HashMap::new();
+        map.insert("resource", resources.to_vec());
+        assert_eq!(map.unique_value("resource"), None);
+        assert_eq!(QueryParameter::values(&map, "resource"), resources);
+        assert_eq!(map.normalize().values("resource"), resources);
+    }
 }
@@ -5,12 +5,15 @@ use std::str::from_utf8;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 
-use crate::code_grant::refresh::{refresh, Error, Endpoint as RefreshEndpoint, Request};
+use crate::code_grant::refresh::{
+    refresh, BearerToken, Error, Endpoint as RefreshEndpoint, Extension, Request,
+};
 use crate::primitives::{registrar::Registrar, issuer::Issuer};
 use super::{
-    Endpoint, InnerTemplate, OAuthError, QueryParameter, WebRequest, WebResponse,
-    is_authorization_method,
+    Endpoint, FlowErrorDetail, InnerTemplate, OAuthError, QueryParameter, WebRequest, WebResponse,
+    exceeds_parameter_limit, is_authorization_method, primitive_error,
 };
+use super::telemetry::{self, FlowSpan};
 
 /// Takes requests from clients to refresh their access tokens.
 pub struct RefreshFlow<E, R>
@@ -19,6 +22,7 @@ where
     R: WebRequest,
 {
     endpoint: WrappedRefresh<E, R>,
+    report_refresh_expiry: bool,
 }
 
 struct WrappedRefresh<E: Endpoint<R>, R: WebRequest> {
@@ -79,9 +83,18 @@ where
                 inner: endpoint,
                 r_type: PhantomData,
             },
+            report_refresh_expiry: false,
         })
     }
 
+    /// Report the remaining lifetime of the new refresh token in the response.
+    ///
+    /// See `AccessTokenFlow::report_refresh_expiry`, the non-standard `refresh_token_expires_in`
+    /// parameter is only sent if the issuer limits the lifetime of its refresh tokens.
+    pub fn report_refresh_expiry(&mut self, report: bool) {
+        self.report_refresh_expiry = report;
+    }
+
     /// Use the checked endpoint to refresh a token.
     ///
     /// ## Panics
@@ -89,29 +102,57 @@ where
     /// When the registrar, authorizer, or issuer returned by the endpoint is suddenly
     /// `None` when previously it was `Some(_)`.
     pub fn execute(&mut self, mut request: R) -> Result<R::Response, E::Error> {
-        let refreshed = refresh(&mut self.endpoint, &WrappedRequest::new(&mut request));
+        let _span = FlowSpan::enter("refresh", Some("refresh_token"));
+        let limit = self.endpoint.inner.parameter_limit();
+        let wrapped = WrappedRequest::new(&mut request, limit);
+        if let Some((id, _)) = wrapped.authorization() {
+            telemetry::client_id(&id);
+        }
+        let refreshed = refresh(&mut self.endpoint, &wrapped);
 
         let token = match refreshed {
             Err(error) => return token_error(&mut self.endpoint.inner, &mut request, error),
             Ok(token) => token,
         };
+        telemetry::scope(&token.1);
+        telemetry::token(&token.0.token);
+        telemetry::success();
+        if let Some(metrics) = self.endpoint.inner.metrics() {
+            metrics.on_token_refreshed(&token.2);
+        }
 
         let mut response = self
             .endpoint
             .inner
             .response(&mut request, InnerTemplate::Ok.into())?;
         response
-            .body_json(&token.to_json())
+            .no_store()
+            .map_err(|err| self.endpoint.inner.web_error(err))?;
+        response
+            .body_json(&token_json(&token, self.report_refresh_expiry))
             .map_err(|err| self.endpoint.inner.web_error(err))?;
         Ok(response)
     }
 }
 
+fn token_json(token: &BearerToken, report_refresh_expiry: bool) -> String {
+    if report_refresh_expiry {
+        token.to_json_with_refresh_expiry()
+    } else {
+        token.to_json()
+    }
+}
+
 fn token_error<E: Endpoint<R>, R: WebRequest>(
     endpoint: &mut E, request: &mut R, error: Error,
 ) -> Result<R::Response, E::Error> {
     Ok(match error {
         Error::Invalid(mut json) => {
+            endpoint.on_flow_error(
+                json.description().kind().into(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
+            telemetry::failure(json.description().kind().description());
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::BadRequest {
@@ -120,12 +161,18 @@ fn token_error<E: Endpoint<R>, R: WebRequest>(
                 .into(),
             )?;
             response.client_error().map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         Error::Unauthorized(mut json, scheme) => {
+            endpoint.on_flow_error(
+                json.description().kind().into(),
+                &mut FlowErrorDetail::AccessToken(json.description()),
+            );
+            telemetry::failure(json.description().kind().description());
             let mut response = endpoint.response(
                 request,
                 InnerTemplate::Unauthorized {
@@ -137,24 +184,26 @@ fn token_error<E: Endpoint<R>, R: WebRequest>(
             response
                 .unauthorized(&scheme)
                 .map_err(|err| endpoint.web_error(err))?;
+            response.no_store().map_err(|err| endpoint.web_error(err))?;
             response
                 .body_json(&json.to_json())
                 .map_err(|err| endpoint.web_error(err))?;
             response
         }
         Error::Primitive => {
+            telemetry::failure("server_error");
             // FIXME: give the context for restoration.
-            return Err(endpoint.error(OAuthError::PrimitiveError));
+            return primitive_error(endpoint, request, "refresh: registrar or issuer failed");
         }
     })
 }
 
 impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
-    pub fn new(request: &'a mut R) -> Self {
-        Self::new_or_fail(request).unwrap_or_else(Self::from_err)
+    pub fn new(request: &'a mut R, limit: Option<usize>) -> Self {
+        Self::new_or_fail(request, limit).unwrap_or_else(Self::from_err)
     }
 
-    fn new_or_fail(request: &'a mut R) -> Result<Self, InitError<R::Error>> {
+    fn new_or_fail(request: &'a mut R, limit: Option<usize>) -> Result<Self, InitError<R::Error>> {
         // If there is a header, it must parse correctly.
         let authorization = match request.authheader() {
             Err(err) => return Err(InitError::Internal(err)),
@@ -162,9 +211,14 @@ impl<'a, R: WebRequest + 'a> WrappedRequest<'a, R> {
             Ok(None) => None,
         };
 
+        let body = request.urlbody().map_err(InitError::Internal)?;
+        if exceeds_parameter_limit(&*body, limit) {
+            return Err(InitError::Malformed);
+        }
+
         Ok(WrappedRequest {
             request: PhantomData,
-            body: request.urlbody().map_err(InitError::Internal)?,
+            body,
             authorization,
             error: None,
         })
@@ -221,6 +275,10 @@ impl<E: Endpoint<R>, R: WebRequest> RefreshEndpoint for WrappedRefresh<E, R> {
     fn issuer(&mut self) -> &mut dyn Issuer {
         self.inner.issuer_mut().unwrap()
     }
+
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        self.inner.extension().and_then(super::Extension::refresh)
+    }
 }
 
 impl<'a, R: WebRequest> Request for WrappedRequest<'a, R> {
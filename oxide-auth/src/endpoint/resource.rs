@@ -1,19 +1,44 @@
 use std::borrow::Cow;
 
+use chrono::{Duration, Utc};
+
 use crate::code_grant::resource::{
-    protect, Error as ResourceError, Endpoint as ResourceEndpoint, Request as ResourceRequest,
+    protect, AccessFailure, Authenticate, Error as ResourceError, ErrorCode,
+    Endpoint as ResourceEndpoint, Extension as ResourceExtension, Request as ResourceRequest, TokenInfo,
+    split_scheme_with,
 };
+use crate::primitives::binding::Binding;
 use crate::primitives::grant::Grant;
+use crate::primitives::issuer::TokenType;
 
 use super::*;
+use super::telemetry::{self, FlowSpan};
 
 /// Guards resources by requiring OAuth authorization.
+///
+/// By default the token is only read from the `Authorization` header. Browser applications that
+/// keep their token in an `HttpOnly` cookie can opt into reading it from a cookie instead, see
+/// [`token_cookie`] for details.
+///
+/// Tokens of sliding sessions are extended on each successful access, see [`sliding_expiry`].
+/// The session of each valid token is additionally checked with the [`SessionValidator`] of the
+/// endpoint, if it has one. Tokens bound to a key of the client must be presented with the scheme
+/// of their type, `DPoP` for DPoP bound tokens, and are only accepted when the [`ProofVerifier`]
+/// of the endpoint approves the proof of possession.
+///
+/// [`SessionValidator`]: trait.SessionValidator.html
+/// [`ProofVerifier`]: trait.ProofVerifier.html
+/// [`token_cookie`]: #method.token_cookie
+/// [`sliding_expiry`]: #method.sliding_expiry
 pub struct ResourceFlow<E, R>
 where
     E: Endpoint<R>,
     R: WebRequest,
 {
     endpoint: WrappedResource<E, R>,
+    token_cookie: Option<String>,
+    sliding_expiry: Option<Duration>,
+    token_type: TokenType,
 }
 
 struct WrappedResource<E: Endpoint<R>, R: WebRequest>(E, PhantomData<R>);
@@ -29,11 +54,15 @@ struct WrappedRequest<R: WebRequest> {
     ///
     /// Actual parsing of the authorization header is done in the lower level.
     error: Option<R::Error>,
+
+    /// The token was presented both in the header and in the cookie.
+    ambiguous: bool,
 }
 
 struct Scoped<'a, E: 'a, R: 'a> {
     request: &'a mut R,
     endpoint: &'a mut E,
+    token_type: TokenType,
 }
 
 impl<E, R> ResourceFlow<E, R>
@@ -62,9 +91,53 @@ where
 
         Ok(ResourceFlow {
             endpoint: WrappedResource(endpoint, PhantomData),
+            token_cookie: None,
+            sliding_expiry: None,
+            token_type: TokenType::Bearer,
         })
     }
 
+    /// Additionally accept the bearer token from the cookie with the given name.
+    ///
+    /// The cookie value is the plain token, without any `Bearer` prefix, and is retrieved through
+    /// [`WebRequest::cookie`]. A request presenting a token both in the `Authorization` header and
+    /// in the cookie is rejected as malformed, as a client must not use more than one method to
+    /// transmit the token. Pass `None` to disable this again.
+    ///
+    /// This is disabled by default and should only be enabled for browser applications. The
+    /// cookie is sent by the user-agent automatically, so it should be set with the `HttpOnly`,
+    /// `Secure` and `SameSite=Strict` (or at least `Lax`) attributes. Since `SameSite` alone is not
+    /// sufficient for all user-agents and subdomain setups, state changing resources protected in
+    /// this way additionally need their own CSRF protection such as a synchronizer token or a
+    /// check of the `Origin` header.
+    ///
+    /// [`WebRequest::cookie`]: trait.WebRequest.html#method.cookie
+    pub fn token_cookie<N: Into<String>>(&mut self, name: Option<N>) {
+        self.token_cookie = name.map(Into::into);
+    }
+
+    /// Extend the expiry of the token to the given duration from now on each successful access.
+    ///
+    /// The token is extended with [`Issuer::touch`], the issuer may limit it to an absolute
+    /// maximum. Access is still granted when the issuer fails to extend the token, for example
+    /// because it does not support it. Pass `None` to disable this again, which is the default.
+    ///
+    /// [`Issuer::touch`]: ../primitives/issuer/trait.Issuer.html#method.touch
+    pub fn sliding_expiry(&mut self, window: Option<Duration>) {
+        self.sliding_expiry = window;
+    }
+
+    /// Accept tokens not bound to a key only with the scheme of the given type.
+    ///
+    /// This must agree with the type advertised by the issuer, for example as configured with
+    /// [`TokenMap::token_type`]. The default is `TokenType::Bearer`. Tokens bound to a key are
+    /// always presented with the scheme of their binding.
+    ///
+    /// [`TokenMap::token_type`]: ../primitives/issuer/struct.TokenMap.html#method.token_type
+    pub fn token_type(&mut self, token_type: TokenType) {
+        self.token_type = token_type;
+    }
+
     /// Use the checked endpoint to check for authorization for a resource.
     ///
     /// ## Panics
@@ -72,49 +145,176 @@ where
     /// When the issuer returned by the endpoint is suddenly `None` when previously it
     /// was `Some(_)`.
     pub fn execute(&mut self, mut request: R) -> Result<Grant, Result<R::Response, E::Error>> {
-        let protected = {
-            let wrapped = WrappedRequest::new(&mut request);
+        self.guard(&mut request)
+            .map_err(|err| self.denied(&mut request, err))
+    }
+
+    /// Check the authorization of the request and respond with the claims of its bearer token.
+    ///
+    /// This is intended for gateways that authorize requests by forwarding their `Authorization`
+    /// header. The token is validated exactly as in [`execute`] and its [`TokenInfo`] is the json
+    /// body of the response. A rejected token yields the same error response as in `execute`,
+    /// with a body containing only `{"active":false}`.
+    ///
+    /// [`execute`]: #method.execute
+    /// [`TokenInfo`]: ../code_grant/resource/struct.TokenInfo.html
+    pub fn to_token_info(&mut self, mut request: R) -> Result<R::Response, E::Error> {
+        let (mut response, info) = match self.guard(&mut request) {
+            Ok(grant) => {
+                let response = self.endpoint.0.response(&mut request, InnerTemplate::Ok.into())?;
+                (response, TokenInfo::active(&grant))
+            }
+            Err(ResourceError::PrimitiveError) => {
+                return primitive_error(&mut self.endpoint.0, &mut request, "resource: issuer failed")
+            }
+            Err(error) => (self.denied(&mut request, error)?, TokenInfo::inactive()),
+        };
 
+        response
+            .body_json(&info.to_json())
+            .map_err(|err| self.endpoint.0.web_error(err))?;
+        Ok(response)
+    }
+
+    fn guard(&mut self, request: &mut R) -> Result<Grant, ResourceError> {
+        let _span = FlowSpan::enter("resource", None);
+        let checked = self.check(request);
+        match &checked {
+            Ok(grant) => {
+                telemetry::client_id(&grant.client_id);
+                telemetry::scope(&grant.scope);
+                telemetry::success();
+            }
+            Err(error) => {
+                telemetry::failure(error_kind(error));
+                match (self.endpoint.0.metrics(), error) {
+                    // A failed issuer did not deny the request.
+                    (_, ResourceError::PrimitiveError) | (None, _) => (),
+                    (Some(metrics), error) => metrics.on_resource_denied(error_kind(error)),
+                }
+            }
+        }
+        checked
+    }
+
+    fn check(&mut self, request: &mut R) -> Result<Grant, ResourceError> {
+        let wrapped = WrappedRequest::new(request, self.token_cookie.as_deref(), self.token_type);
+        let mut grant = {
             let mut scoped = Scoped {
-                request: &mut request,
+                request,
                 endpoint: &mut self.endpoint.0,
+                token_type: self.token_type,
             };
 
-            protect(&mut scoped, &wrapped)
+            match protect(&mut scoped, &wrapped) {
+                Ok(grant) => grant,
+                Err(mut error) => {
+                    if error.insufficient_scope() {
+                        let scopes = self.endpoint.0.scopes().unwrap();
+                        if let Some(reason) = scopes.deny_reason(request) {
+                            error.describe(reason);
+                        }
+                    }
+                    return Err(error);
+                }
+            }
         };
 
-        protected.map_err(|err| self.denied(&mut request, err))
+        if let Some(validator) = self.endpoint.0.session_validator() {
+            match validator.session_active(&grant) {
+                Ok(true) => (),
+                Ok(false) => return Err(invalid_token()),
+                Err(()) => return Err(ResourceError::PrimitiveError),
+            }
+        }
+
+        // A successfully validated authorization always has a known scheme.
+        let (_, token) =
+            split_scheme_with(wrapped.authorization.as_deref().unwrap(), self.token_type).unwrap();
+        telemetry::token(token);
+
+        if let Some(binding) = Binding::of(&grant) {
+            let verified = match self.endpoint.0.proof_verifier() {
+                Some(verifier) => verifier.verify(request, token, &binding),
+                None => Ok(false),
+            };
+
+            match verified {
+                Ok(true) => (),
+                Ok(false) => return Err(invalid_token()),
+                Err(()) => return Err(ResourceError::PrimitiveError),
+            }
+        }
+
+        if let Some(window) = self.sliding_expiry {
+            let issuer = self.endpoint.0.issuer_mut().unwrap();
+            if let Ok(until) = issuer.touch(token, Utc::now() + window) {
+                grant.until = until;
+            }
+        }
+
+        Ok(grant)
     }
 
     fn denied(&mut self, request: &mut R, error: ResourceError) -> Result<R::Response, E::Error> {
         let template = match &error {
-            ResourceError::AccessDenied { .. } => InnerTemplate::Unauthorized {
-                error: None,
-                access_token_error: None,
-            },
-            ResourceError::NoAuthentication { .. } => InnerTemplate::Unauthorized {
-                error: None,
-                access_token_error: None,
-            },
+            ResourceError::AccessDenied { .. } | ResourceError::NoAuthentication { .. } => {
+                InnerTemplate::Unauthorized {
+                    error: Some(error.clone()),
+                    access_token_error: None,
+                }
+            }
             ResourceError::InvalidRequest { .. } => InnerTemplate::BadRequest {
                 access_token_error: None,
             },
             ResourceError::PrimitiveError => {
-                return Err(self.endpoint.0.error(OAuthError::PrimitiveError))
+                return primitive_error(&mut self.endpoint.0, request, "resource: issuer failed")
             }
         };
 
         let mut response = self.endpoint.0.response(request, template.into())?;
-        response
-            .unauthorized(&error.www_authenticate())
-            .map_err(|err| self.endpoint.0.web_error(err))?;
+        // A valid token lacking the scope is forbidden, any other denial is unauthorized.
+        let forbidden = error.insufficient_scope();
+        let header = error.www_authenticate();
+        if forbidden {
+            response.forbidden(&header)
+        } else {
+            response.unauthorized(&header)
+        }
+        .map_err(|err| self.endpoint.0.web_error(err))?;
 
         Ok(response)
     }
 }
 
+/// The kind of a failed access, as recorded by telemetry.
+fn error_kind(error: &ResourceError) -> &'static str {
+    match error {
+        ResourceError::AccessDenied { failure, .. } => match failure.code {
+            Some(code) => code.description(),
+            None => "access_denied",
+        },
+        ResourceError::NoAuthentication { .. } => "no_authentication",
+        ResourceError::InvalidRequest { .. } => "invalid_request",
+        ResourceError::PrimitiveError => "server_error",
+    }
+}
+
+fn invalid_token() -> ResourceError {
+    ResourceError::AccessDenied {
+        failure: AccessFailure {
+            code: Some(ErrorCode::InvalidToken),
+        },
+        authenticate: Authenticate {
+            realm: None,
+            scope: None,
+            description: None,
+        },
+    }
+}
+
 impl<R: WebRequest> WrappedRequest<R> {
-    fn new(request: &mut R) -> Self {
+    fn new(request: &mut R, token_cookie: Option<&str>, token_type: TokenType) -> Self {
         let token = match request.authheader() {
             // TODO: this is unecessarily wasteful, we always clone.
             Ok(Some(token)) => Some(token.into_owned()),
@@ -122,10 +322,19 @@ impl<R: WebRequest> WrappedRequest<R> {
             Err(error) => return Self::from_error(error),
         };
 
+        let cookie = match token_cookie.map(|name| request.cookie(name)) {
+            Some(Ok(Some(cookie))) => Some(format!("{} {}", token_type.as_str(), cookie)),
+            Some(Ok(None)) | None => None,
+            Some(Err(error)) => return Self::from_error(error),
+        };
+
+        let ambiguous = token.is_some() && cookie.is_some();
+
         WrappedRequest {
             request: PhantomData,
-            authorization: token,
+            authorization: token.or(cookie),
             error: None,
+            ambiguous,
         }
     }
 
@@ -134,6 +343,7 @@ impl<R: WebRequest> WrappedRequest<R> {
             request: PhantomData,
             authorization: None,
             error: Some(error),
+            ambiguous: false,
         }
     }
 }
@@ -146,14 +356,26 @@ impl<'a, E: Endpoint<R> + 'a, R: WebRequest + 'a> ResourceEndpoint for Scoped<'a
     fn issuer(&mut self) -> &dyn Issuer {
         self.endpoint.issuer_mut().unwrap()
     }
+
+    fn extension(&mut self) -> Option<&mut dyn ResourceExtension> {
+        self.endpoint.extension().and_then(Extension::resource)
+    }
+
+    fn token_type(&self) -> TokenType {
+        self.token_type
+    }
 }
 
 impl<R: WebRequest> ResourceRequest for WrappedRequest<R> {
     fn valid(&self) -> bool {
-        self.error.is_none()
+        self.error.is_none() && !self.ambiguous
     }
 
     fn token(&self) -> Option<Cow<str>> {
+        if self.ambiguous {
+            return None;
+        }
+
         self.authorization.as_deref().map(Cow::Borrowed)
     }
 }
@@ -0,0 +1,106 @@
+//! Optional `tracing` instrumentation of the flows.
+//!
+//! Each flow enters a span for its whole execution and records what it learns about the request on
+//! the current span. The span carries the fields `flow`, `grant_type`, `client_id`, `scope`,
+//! `token_id` and `outcome`, as well as the `error` kind of failed requests. Tokens are never
+//! recorded, only the first bytes of their hash to correlate requests.
+//!
+//! Without the `tracing` feature all of these functions do nothing.
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+
+use std::fmt;
+
+/// The entered span of a flow, exited when dropped.
+pub(crate) struct FlowSpan {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl FlowSpan {
+    /// Enter the span of a flow, for the grant type if the flow is restricted to one.
+    pub(crate) fn enter(flow: &'static str, grant_type: Option<&'static str>) -> Self {
+        FlowSpan {
+            #[cfg(feature = "tracing")]
+            _span: tracing::info_span!(
+                "oxide_auth",
+                flow,
+                grant_type,
+                client_id = tracing::field::Empty,
+                scope = tracing::field::Empty,
+                token_id = tracing::field::Empty,
+                outcome = tracing::field::Empty,
+                error = tracing::field::Empty,
+            )
+            .entered(),
+        }
+    }
+}
+
+/// Record the grant type once the flow has determined it.
+pub(crate) fn grant_type(grant_type: &'static str) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("grant_type", grant_type);
+}
+
+/// Record the id of the client, as claimed by the request.
+pub(crate) fn client_id(client_id: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("client_id", client_id);
+}
+
+/// Record the requested or granted scope.
+pub(crate) fn scope(scope: &dyn fmt::Display) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("scope", tracing::field::display(scope));
+}
+
+/// Record an identifier of a token that does not reveal the token itself.
+pub(crate) fn token(token: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("token_id", token_id(token).as_str());
+}
+
+/// The flow completed successfully.
+pub(crate) fn success() {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::Span::current().record("outcome", "success");
+        tracing::debug!(outcome = "success", "flow succeeded");
+    }
+}
+
+/// The flow awaits a decision of the resource owner.
+pub(crate) fn in_progress() {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("outcome", "in_progress");
+}
+
+/// The flow failed with an error of the given kind.
+///
+/// Only failures of the server itself are logged as errors. Denied requests are routine, a user
+/// declining consent or a client with a wrong secret is no fault of the server, and are logged at
+/// the info level instead.
+pub(crate) fn failure(kind: &str) {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::Span::current()
+            .record("outcome", "error")
+            .record("error", kind);
+        match kind {
+            "server_error" | "temporarily_unavailable" => {
+                tracing::error!(outcome = "error", error = kind, "flow failed")
+            }
+            _ => tracing::info!(outcome = "error", error = kind, "flow denied"),
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn token_id(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(token.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -1,18 +1,33 @@
-use crate::primitives::authorizer::{AuthMap, Authorizer};
-use crate::primitives::issuer::TokenMap;
-use crate::primitives::grant::{Grant, Extensions};
-use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
-
-use crate::frontends::simple::endpoint::access_token_flow;
-
+use crate::primitives::authorizer::{AuthMap, Authorizer, CodeSigner};
+use crate::primitives::clock::ManualClock;
+use crate::primitives::generator::{Assertion, AssertionKind, RandomGenerator};
+use crate::primitives::issuer::{Issuer, TokenMap, TokenSigner, TokenType};
+use crate::primitives::grant::{Grant, GrantExtension, Extensions, Value};
+use crate::primitives::registrar::{Client, ClientKey, ClientMap, RegisteredUrl};
+
+use crate::code_grant::accesstoken::TokenResponse;
+use crate::endpoint::{
+    AccessTokenFlow, AuthorizationFlow, QueryParameter, ResponseStatus, Template, WebRequest,
+};
+use crate::frontends::simple::endpoint::{
+    access_token_flow, resource_flow, FnResponse, Generic, ResponseCreator, Vacant,
+};
+use crate::frontends::simple::extensions::{
+    AccessTokenAddon, AccessTokenRequest, AddonList, AddonResult, Extended,
+};
+
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 use base64::{self, Engine};
-use base64::engine::general_purpose::STANDARD;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
 use chrono::{Utc, Duration};
 use serde_json;
 
-use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::{
+    Allow, Body, CraftedError, CraftedRequest, CraftedResponse, Status, TestGenerator,
+    ToSingleValueQuery,
+};
 use super::defaults::*;
 
 struct AccessTokenSetup {
@@ -143,6 +158,7 @@ impl AccessTokenSetup {
             Status::BadRequest => (),
             _ => panic!("Expected error status, got {:?}", response),
         }
+        assert!(response.no_store, "Token responses must not be cached");
     }
 
     fn test_simple_error(&mut self, request: CraftedRequest) {
@@ -170,6 +186,7 @@ impl AccessTokenSetup {
 
     fn assert_ok_access_token(&mut self, response: CraftedResponse) {
         assert_eq!(response.status, Status::Ok);
+        assert!(response.no_store, "Token responses must not be cached");
     }
 }
 
@@ -195,6 +212,64 @@ fn access_valid_public() {
     setup.test_success(valid_public);
 }
 
+#[test]
+fn access_token_code_ttl() {
+    let clock = ManualClock::new(Utc::now());
+    let mut setup = AccessTokenSetup::public_client();
+    setup.authorizer = AuthMap::new(TestGenerator("AuthToken".to_string())).with_clock(clock.clone());
+    setup.authorizer.code_ttl(Duration::minutes(1));
+
+    let grant = Grant {
+        client_id: EXAMPLE_CLIENT_ID.to_string(),
+        owner_id: EXAMPLE_OWNER_ID.to_string(),
+        redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+        scope: EXAMPLE_SCOPE.parse().unwrap(),
+        until: Utc::now() + Duration::hours(1),
+        extensions: Extensions::new(),
+    };
+    let request = || CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("code", "AuthToken"),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    // A code presented after its ttl is no longer valid.
+    setup.authorizer.authorize(grant.clone()).unwrap();
+    clock.advance(Duration::minutes(2));
+    let response = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+        .execute(request())
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::BadRequest);
+    match response.body {
+        Some(Body::Json(ref body)) => {
+            let content: HashMap<String, String> = serde_json::from_str(body).unwrap();
+            assert_eq!(content.get("error").map(String::as_str), Some("invalid_grant"));
+        }
+        other => panic!("Expected json encoded body, got {:?}", other),
+    }
+
+    // A fresh code works and the token keeps the lifetime of the grant.
+    setup.authorizer.authorize(grant).unwrap();
+    let response = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+        .execute(request())
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(ref body)) => serde_json::from_str(body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    assert!(body.expires_in.unwrap() > 50 * 60);
+}
+
 #[test]
 fn access_valid_public_empty_secret() {
     let mut setup = AccessTokenSetup::public_client_empty_secret();
@@ -260,6 +335,71 @@ fn access_valid_private() {
     setup.test_success(valid_public);
 }
 
+#[test]
+fn access_issued_token() {
+    let mut setup = AccessTokenSetup::private_client();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let (response, token) =
+        access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+            .execute_with_token(request)
+            .expect("Should not error");
+    let token = token.expect("Should have issued a token");
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(ref body)) => serde_json::from_str(body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+
+    assert_eq!(body.access_token.as_ref(), Some(&token.token().token));
+    assert_eq!(body.refresh_token, token.token().refresh);
+    assert_eq!(body.scope, Some(token.scope().to_string()));
+    assert_eq!(
+        body.token_type.as_deref(),
+        Some(token.token().token_type.as_str())
+    );
+    let expires_in = (token.token().until - Utc::now()).num_seconds();
+    assert!((body.expires_in.unwrap() - expires_in).abs() <= 1);
+}
+
+#[test]
+fn access_issued_token_error() {
+    let mut setup = AccessTokenSetup::private_client();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("code", "NotTheAuthToken"),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let (response, token) =
+        access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+            .execute_with_token(request)
+            .expect("Should not error");
+    AccessTokenSetup::assert_json_error_set(&response);
+    assert!(token.is_none());
+}
+
 #[test]
 fn regression_case_insensitive_basic() {
     let mut setup = AccessTokenSetup::private_client();
@@ -346,6 +486,87 @@ fn access_equivalent_url() {
     });
 }
 
+fn refresh_expiry_response(report: bool) -> TokenResponse {
+    let mut setup = AccessTokenSetup::private_client();
+    setup.issuer.refresh_valid_for(Duration::days(30));
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let mut flow = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer);
+    flow.report_refresh_expiry(report);
+    let response = flow.execute(request).expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+    match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    }
+}
+
+#[test]
+fn access_refresh_expiry() {
+    // Not part of the standard response unless requested.
+    let body = refresh_expiry_response(false);
+    assert!(body.refresh_token.is_some());
+    assert_eq!(body.refresh_token_expires_in, None);
+
+    let body = refresh_expiry_response(true);
+    let lifetime = Duration::days(30).num_seconds();
+    let expires_in = body
+        .refresh_token_expires_in
+        .expect("Expected refresh token lifetime");
+    assert!(expires_in <= lifetime && expires_in > lifetime - 5);
+}
+
+#[test]
+fn access_advertised_expiry() {
+    let mut setup = AccessTokenSetup::private_client();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let mut flow = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer);
+    flow.advertise_expiry(|remaining| remaining.min(60));
+    let (response, token) = flow.execute_with_token(request).expect("Should not error");
+    let token = token.expect("Should have issued a token");
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(ref body)) => serde_json::from_str(body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+
+    // Only the advertised value is capped, the grant keeps its lifetime of ten minutes.
+    assert_eq!(body.expires_in, Some(60));
+    assert!(token.token().until > Utc::now() + Duration::minutes(9));
+    let grant = setup
+        .issuer
+        .recover_token(&token.token().token)
+        .unwrap()
+        .expect("Token should be recoverable");
+    assert_eq!(grant.until, token.token().until);
+}
+
 #[test]
 fn access_request_unknown_client() {
     let mut setup = AccessTokenSetup::private_client();
@@ -697,3 +918,737 @@ fn private_duplicate_authentication() {
 
     setup.test_simple_error(valid_public);
 }
+
+/// A request that also provides its unparsed body, like buffering frontends do.
+#[derive(Debug)]
+struct RawBodyRequest(CraftedRequest, Vec<u8>);
+
+impl WebRequest for RawBodyRequest {
+    type Response = CraftedResponse;
+    type Error = CraftedError;
+
+    fn query(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.0.query()
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.0.urlbody()
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+        self.0.authheader()
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        Some(&self.1)
+    }
+}
+
+/// Stores the raw body of the token request in the grant.
+struct RawBody;
+
+impl GrantExtension for RawBody {
+    fn identifier(&self) -> &'static str {
+        "raw_body"
+    }
+}
+
+impl AccessTokenAddon for RawBody {
+    fn execute(&self, request: &dyn AccessTokenRequest, _: Option<Value>) -> AddonResult {
+        match request.raw_body() {
+            Some(body) => AddonResult::Data(Value::public(Some(String::from_utf8_lossy(body).into()))),
+            None => AddonResult::Err,
+        }
+    }
+}
+
+#[test]
+fn extension_raw_body() {
+    let mut setup = AccessTokenSetup::private_client();
+    let raw = format!(
+        "grant_type=authorization_code&code={}&redirect_uri={}",
+        setup.authtoken, EXAMPLE_REDIRECT_URI
+    );
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let mut addons = AddonList::new();
+    addons.push_access_token(RawBody);
+    let mut endpoint = Extended::extend_with(
+        Generic {
+            registrar: &setup.registrar,
+            authorizer: &mut setup.authorizer,
+            issuer: &mut setup.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        },
+        addons,
+    );
+
+    let response = AccessTokenFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+        .execute(RawBodyRequest(request, raw.clone().into_bytes()))
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    let grant = setup.issuer.recover_token("AccessToken").unwrap().unwrap();
+    let stored = grant.extensions.public().find(|(name, _)| *name == "raw_body");
+    assert_eq!(stored, Some(("raw_body", Some(raw.as_str()))));
+}
+
+/// Carries the tenant chosen during authorization over to the access token.
+struct Tenant;
+
+impl GrantExtension for Tenant {
+    fn identifier(&self) -> &'static str {
+        "tenant"
+    }
+}
+
+impl AccessTokenAddon for Tenant {
+    fn execute(&self, _: &dyn AccessTokenRequest, code_data: Option<Value>) -> AddonResult {
+        match code_data {
+            Some(data) => AddonResult::Data(data),
+            None => AddonResult::Ok,
+        }
+    }
+}
+
+#[test]
+fn extension_reaches_resource() {
+    let mut setup = AccessTokenSetup::private_client();
+    let mut extensions = Extensions::new();
+    extensions.set(&Tenant, Value::public(Some("acme".into())));
+    extensions.set_raw("unforwarded".into(), Value::public(Some("dropped".into())));
+
+    // Replace the code of the setup with one carrying extension data.
+    setup.authorizer.extract(&setup.authtoken).unwrap();
+    let code = setup
+        .authorizer
+        .authorize(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions,
+        })
+        .unwrap();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let mut addons = AddonList::new();
+    addons.push_access_token(Tenant);
+    let mut endpoint = Extended::extend_with(
+        Generic {
+            registrar: &setup.registrar,
+            authorizer: &mut setup.authorizer,
+            issuer: &mut setup.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        },
+        addons,
+    );
+
+    let response = AccessTokenFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+        .execute(request)
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    let scope = [EXAMPLE_SCOPE.parse().unwrap()];
+    let resource = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer AccessToken".to_string()),
+    };
+    let grant = resource_flow(&mut setup.issuer, &scope)
+        .execute(resource)
+        .unwrap_or_else(|_| panic!("Expected access to the resource"));
+
+    // Only values returned by an access token addon are kept.
+    assert_eq!(grant.extensions.public_value("tenant"), Some("acme"));
+    assert_eq!(grant.extensions.get("unforwarded"), None);
+}
+
+/// Points clients to documentation of each token error, as an endpoint would in its responses.
+struct ExplainErrors;
+
+impl ResponseCreator<CraftedRequest> for ExplainErrors {
+    fn create(&mut self, _: &mut CraftedRequest, mut kind: Template) -> CraftedResponse {
+        if let Some(error) = kind.access_token_error() {
+            error.explain("See the documentation");
+            error.explain_uri("https://auth.example/errors".parse().unwrap());
+        }
+        CraftedResponse::default()
+    }
+}
+
+impl AccessTokenSetup {
+    fn explained_error(&mut self, request: CraftedRequest) -> (Status, serde_json::Value) {
+        let mut endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: ExplainErrors,
+        };
+
+        let response = AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .expect("Expected an error response");
+        let body = match response.body {
+            Some(Body::Json(body)) => serde_json::from_str(&body).unwrap(),
+            other => panic!("Expected json encoded body, got {:?}", other),
+        };
+        (response.status, body)
+    }
+}
+
+fn explained_error_json(error: &str) -> serde_json::Value {
+    serde_json::json!({
+        "error": error,
+        "error_description": "See the documentation",
+        "error_uri": "https://auth.example/errors",
+    })
+}
+
+#[test]
+fn error_json_invalid_grant() {
+    let mut setup = AccessTokenSetup::private_client();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                // The code was issued for a different redirect uri.
+                ("redirect_uri", "https://client.example/other"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let (status, body) = setup.explained_error(request);
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body, explained_error_json("invalid_grant"));
+}
+
+#[test]
+fn error_json_invalid_client() {
+    let mut setup = AccessTokenSetup::private_client();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(
+            "Basic ".to_string()
+                + &STANDARD.encode(format!("{}:{}", "SomeOtherClient", EXAMPLE_PASSPHRASE)),
+        ),
+    };
+
+    let (status, body) = setup.explained_error(request);
+    assert_eq!(status, Status::Unauthorized);
+    assert_eq!(body, explained_error_json("invalid_client"));
+}
+
+#[test]
+fn error_json_without_explanation() {
+    let mut setup = AccessTokenSetup::private_client();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", "https://client.example/other"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some("Basic ".to_string() + &setup.basic_authorization),
+    };
+
+    let response = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+        .execute(request)
+        .expect("Expected an error response");
+    let body: serde_json::Value = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    // Optional fields are left out instead of being null.
+    assert_eq!(body, serde_json::json!({ "error": "invalid_grant" }));
+}
+
+fn public_registrar() -> ClientMap {
+    let mut registrar = ClientMap::new();
+    registrar.register_client(Client::public(
+        EXAMPLE_CLIENT_ID,
+        RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+    ));
+    registrar
+}
+
+fn authorize_code(authorizer: &mut dyn Authorizer) -> String {
+    authorizer
+        .authorize(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        })
+        .unwrap()
+}
+
+/// Redeem a code at the token endpoint, returning the json body of the response.
+fn redeem_code(
+    registrar: &ClientMap, authorizer: &mut dyn Authorizer, issuer: &mut dyn Issuer, code: &str,
+) -> (Status, HashMap<String, serde_json::Value>) {
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("code", code),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    let response = access_token_flow(registrar, authorizer, issuer)
+        .execute(request)
+        .expect("Expected non-error response");
+    match response.body {
+        Some(Body::Json(json)) => (response.status, serde_json::from_str(&json).unwrap()),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    }
+}
+
+/// The flow is the same for every authorizer, whether it stores its codes or signs them.
+fn redeem_code_once(authorizer: &mut dyn Authorizer, issuer: &mut dyn Issuer) {
+    let registrar = public_registrar();
+    let code = authorize_code(authorizer);
+
+    let (status, body) = redeem_code(&registrar, authorizer, issuer, &code);
+    assert_eq!(status, Status::Ok);
+    let token = body["access_token"].as_str().unwrap();
+    let grant = issuer.recover_token(token).unwrap().expect("Token was issued");
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+
+    let (status, body) = redeem_code(&registrar, authorizer, issuer, &code);
+    assert_eq!(status, Status::BadRequest);
+    assert!(!body.contains_key("access_token"));
+}
+
+#[test]
+fn access_stored_code() {
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    redeem_code_once(&mut authorizer, &mut TokenMap::new(RandomGenerator::new(16)));
+
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    redeem_code_once(&mut authorizer, &mut TokenSigner::ephemeral());
+}
+
+#[test]
+fn access_reused_code_revokes() {
+    use std::sync::{mpsc, Mutex};
+
+    let (sender, reused) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    authorizer.on_code_reuse(move |reuse| sender.lock().unwrap().send(reuse.clone()).unwrap());
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let registrar = public_registrar();
+    let code = authorize_code(&mut authorizer);
+
+    let (status, body) = redeem_code(&registrar, &mut authorizer, &mut issuer, &code);
+    assert_eq!(status, Status::Ok);
+    let token = body["access_token"].as_str().unwrap().to_owned();
+    assert!(reused.try_recv().is_err());
+
+    let (status, body) = redeem_code(&registrar, &mut authorizer, &mut issuer, &code);
+    assert_eq!(status, Status::BadRequest);
+    assert!(!body.contains_key("access_token"));
+
+    // The hook learns of the reuse and revokes the token of the first redemption.
+    let reuse = reused.try_recv().expect("Reuse should have been reported");
+    assert_eq!(reuse.client_id, EXAMPLE_CLIENT_ID);
+    assert_eq!(reuse.owner_id, EXAMPLE_OWNER_ID);
+    assert!(issuer.recover_token(&token).unwrap().is_some());
+    issuer.revoke_client(&reuse.owner_id, &reuse.client_id);
+    assert!(issuer.recover_token(&token).unwrap().is_none());
+}
+
+#[test]
+fn access_signed_code() {
+    let mut authorizer = CodeSigner::ephemeral();
+    redeem_code_once(&mut authorizer, &mut TokenMap::new(RandomGenerator::new(16)));
+
+    let mut authorizer = CodeSigner::ephemeral();
+    redeem_code_once(&mut authorizer, &mut TokenSigner::ephemeral());
+}
+
+#[test]
+fn access_signed_code_shared_key() {
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+    let registrar = public_registrar();
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+
+    // The instance redeeming the code has never seen it before.
+    let code = authorize_code(&mut CodeSigner::new(Assertion::new(
+        AssertionKind::HmacSha256,
+        KEY,
+    )));
+    let mut redeeming = CodeSigner::new(Assertion::new(AssertionKind::HmacSha256, KEY));
+    let (status, _) = redeem_code(&registrar, &mut redeeming, &mut issuer, &code);
+    assert_eq!(status, Status::Ok);
+
+    let mut other_key = CodeSigner::ephemeral();
+    let code = authorize_code(&mut CodeSigner::new(Assertion::new(
+        AssertionKind::HmacSha256,
+        KEY,
+    )));
+    let (status, body) = redeem_code(&registrar, &mut other_key, &mut issuer, &code);
+    assert_eq!(status, Status::BadRequest);
+    assert!(!body.contains_key("access_token"));
+}
+
+#[test]
+fn access_custom_token_type() {
+    const EXAMPLE_TYPE: TokenType = TokenType::Custom("Example");
+    let registrar = public_registrar();
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    issuer.token_type(EXAMPLE_TYPE);
+
+    let code = authorize_code(&mut authorizer);
+    let (status, body) = redeem_code(&registrar, &mut authorizer, &mut issuer, &code);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["token_type"], "Example");
+    let token = body["access_token"].as_str().unwrap();
+
+    let scope = [EXAMPLE_SCOPE.parse().unwrap()];
+    let resource = |scheme: &str| CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some(format!("{} {}", scheme, token)),
+    };
+
+    let mut flow = resource_flow(&mut issuer, &scope);
+    flow.token_type(EXAMPLE_TYPE);
+    assert!(flow.execute(resource("Example")).is_ok());
+    // The token is only accepted with the scheme of its type.
+    assert!(flow.execute(resource("Bearer")).is_err());
+    assert!(flow.execute(resource("Other")).is_err());
+
+    // A flow not configured with the type does not know its scheme.
+    let mut flow = resource_flow(&mut issuer, &scope);
+    assert!(flow.execute(resource("Example")).is_err());
+}
+
+const TOKEN_ENDPOINT: &str = "https://auth.example/token";
+const ASSERTION_SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+/// A public client which authenticates with signed assertions.
+fn asserting_registrar(key: ClientKey) -> ClientMap {
+    let mut registrar = ClientMap::new();
+    registrar.set_assertion_audience(TOKEN_ENDPOINT);
+    registrar.register_client(
+        Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        )
+        .with_assertion_key(key),
+    );
+    registrar
+}
+
+fn sign_assertion(key: &Assertion, claims: &serde_json::Value) -> String {
+    let header = serde_json::json!({ "alg": key.jws_algorithm(), "typ": "JWT" });
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = key.signature(signing_input.as_bytes()).unwrap();
+    format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))
+}
+
+fn assertion_claims(jti: &str) -> serde_json::Value {
+    serde_json::json!({
+        "iss": EXAMPLE_CLIENT_ID,
+        "sub": EXAMPLE_CLIENT_ID,
+        "aud": TOKEN_ENDPOINT,
+        "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+        "jti": jti,
+    })
+}
+
+/// Redeem a code, authenticating with the assertion instead of a `client_id`.
+fn redeem_with_assertion(
+    registrar: &ClientMap, authorizer: &mut dyn Authorizer, assertion: &str,
+) -> (Status, HashMap<String, serde_json::Value>) {
+    let code = authorize_code(authorizer);
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                (
+                    "client_assertion_type",
+                    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+                ),
+                ("client_assertion", assertion),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let response = access_token_flow(registrar, authorizer, &mut issuer)
+        .execute(request)
+        .expect("Expected non-error response");
+    match response.body {
+        Some(Body::Json(json)) => (response.status, serde_json::from_str(&json).unwrap()),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    }
+}
+
+#[test]
+fn access_client_assertion() {
+    let registrar = asserting_registrar(ClientKey::HmacSha256(ASSERTION_SECRET.to_vec()));
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let key = Assertion::new(AssertionKind::HmacSha256, ASSERTION_SECRET);
+    let assertion = sign_assertion(&key, &assertion_claims("first"));
+
+    let (status, body) = redeem_with_assertion(&registrar, &mut authorizer, &assertion);
+    assert_eq!(status, Status::Ok);
+    assert!(body.contains_key("access_token"));
+
+    // Each assertion authenticates a single request.
+    let (status, body) = redeem_with_assertion(&registrar, &mut authorizer, &assertion);
+    assert_eq!(status, Status::Unauthorized);
+    assert!(!body.contains_key("access_token"));
+
+    let assertion = sign_assertion(&key, &assertion_claims("second"));
+    let (status, _) = redeem_with_assertion(&registrar, &mut authorizer, &assertion);
+    assert_eq!(status, Status::Ok);
+}
+
+#[test]
+fn access_client_assertion_tampered() {
+    let registrar = asserting_registrar(ClientKey::HmacSha256(ASSERTION_SECRET.to_vec()));
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let key = Assertion::new(AssertionKind::HmacSha256, ASSERTION_SECRET);
+    let assertion = sign_assertion(&key, &assertion_claims("tampered"));
+
+    // Replace the claims while keeping the signature.
+    let mut claims = assertion_claims("tampered");
+    claims["exp"] = (Utc::now() + Duration::minutes(50)).timestamp().into();
+    let mut parts: Vec<_> = assertion.split('.').map(str::to_string).collect();
+    parts[1] = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let tampered = parts.join(".");
+
+    let (status, body) = redeem_with_assertion(&registrar, &mut authorizer, &tampered);
+    assert_eq!(status, Status::Unauthorized);
+    assert!(!body.contains_key("access_token"));
+
+    let other_key = Assertion::new(AssertionKind::HmacSha256, b"another secret of the attacker");
+    let forged = sign_assertion(&other_key, &assertion_claims("forged"));
+    let (status, _) = redeem_with_assertion(&registrar, &mut authorizer, &forged);
+    assert_eq!(status, Status::Unauthorized);
+}
+
+#[test]
+fn access_client_assertion_claims() {
+    let registrar = asserting_registrar(ClientKey::HmacSha256(ASSERTION_SECRET.to_vec()));
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let key = Assertion::new(AssertionKind::HmacSha256, ASSERTION_SECRET);
+
+    let mut wrong_audience = assertion_claims("audience");
+    wrong_audience["aud"] = "https://other.example/token".into();
+    let mut wrong_issuer = assertion_claims("issuer");
+    wrong_issuer["iss"] = "SomeOtherClient".into();
+    let mut expired = assertion_claims("expired");
+    expired["exp"] = (Utc::now() - Duration::minutes(1)).timestamp().into();
+    let mut long_lived = assertion_claims("long_lived");
+    long_lived["exp"] = (Utc::now() + Duration::days(1)).timestamp().into();
+
+    for claims in &[wrong_audience, wrong_issuer, expired, long_lived] {
+        let assertion = sign_assertion(&key, claims);
+        let (status, _) = redeem_with_assertion(&registrar, &mut authorizer, &assertion);
+        assert_eq!(status, Status::Unauthorized, "Accepted assertion {}", claims);
+    }
+}
+
+#[test]
+fn access_client_assertion_required() {
+    let registrar = asserting_registrar(ClientKey::HmacSha256(ASSERTION_SECRET.to_vec()));
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let code = authorize_code(&mut authorizer);
+
+    // Registering a key turns the public client into one that must authenticate.
+    let (status, body) = redeem_code(
+        &registrar,
+        &mut authorizer,
+        &mut TokenMap::new(RandomGenerator::new(16)),
+        &code,
+    );
+    assert_eq!(status, Status::Unauthorized);
+    assert!(!body.contains_key("access_token"));
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn access_private_key_jwt() {
+    let key = Assertion::ed25519(&[7; 32]);
+    let jwk = format!(
+        r#"{{"kty":"OKP","crv":"Ed25519","x":"{}"}}"#,
+        URL_SAFE_NO_PAD.encode(key.public_key().unwrap())
+    );
+    let registrar = asserting_registrar(ClientKey::from_jwk(&jwk).unwrap());
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+
+    let assertion = sign_assertion(&key, &assertion_claims("private_key_jwt"));
+    let (status, _) = redeem_with_assertion(&registrar, &mut authorizer, &assertion);
+    assert_eq!(status, Status::Ok);
+
+    let other_key = Assertion::ed25519(&[8; 32]);
+    let forged = sign_assertion(&other_key, &assertion_claims("forged"));
+    let (status, _) = redeem_with_assertion(&registrar, &mut authorizer, &forged);
+    assert_eq!(status, Status::Unauthorized);
+}
+
+#[test]
+fn access_token_custom_response() {
+    let mut registrar = ClientMap::new();
+    registrar.register_client(Client::public(
+        EXAMPLE_CLIENT_ID,
+        RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+    ));
+    let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+    let mut issuer = TokenMap::new(TestGenerator("AccessToken".to_string()));
+
+    // Only successful responses, such as the token response, are marked private.
+    let mut created = 0;
+    let mut builder = |_: &mut CraftedRequest, kind: Template| {
+        created += 1;
+        let mut response = CraftedResponse::default();
+        if kind.status() == ResponseStatus::Ok {
+            response
+                .headers
+                .push(("Cache-Control".to_string(), "private".to_string()));
+        }
+        response
+    };
+
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        ..CraftedRequest::default()
+    };
+    let mut endpoint = Generic {
+        registrar: &registrar,
+        authorizer: &mut authorizer,
+        issuer: Vacant,
+        solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+        scopes: Vacant,
+        response: FnResponse(&mut builder),
+    };
+    let response = AuthorizationFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Expected no flow execution error"));
+    assert_eq!(response.status, Status::Redirect);
+    assert!(response.headers.is_empty());
+
+    let request = CraftedRequest {
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("code", "AuthToken"),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        ..CraftedRequest::default()
+    };
+    let mut endpoint = Generic {
+        registrar: &registrar,
+        authorizer: &mut authorizer,
+        issuer: &mut issuer,
+        solicitor: Vacant,
+        scopes: Vacant,
+        response: FnResponse(&mut builder),
+    };
+    let response = AccessTokenFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Expected no flow execution error"));
+    assert_eq!(response.status, Status::Ok);
+    assert!(response.no_store);
+    assert_eq!(
+        response.headers,
+        [("Cache-Control".to_string(), "private".to_string())]
+    );
+
+    assert_eq!(created, 2);
+}
@@ -0,0 +1,152 @@
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::grant::{GrantExtension, Value};
+use crate::primitives::issuer::{Issuer, TokenMap};
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::code_grant::accesstoken::TokenResponse;
+use crate::endpoint::{AuthorizationFlow, AccessTokenFlow};
+use crate::frontends::simple::extensions::{
+    AccessTokenAddon, AccessTokenRequest, Addon, AddonList, AddonResult, AudienceAddon,
+    AuthorizationAddon, AuthorizationRequest, Extended,
+};
+use crate::frontends::simple::endpoint::{Generic, Vacant};
+
+use super::{Allow, Body, CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+const SERVER_A: &str = "https://a.example";
+
+/// Remembers the tenant of the owner in the grant.
+struct Tenant(&'static str);
+
+impl GrantExtension for Tenant {
+    fn identifier(&self) -> &'static str {
+        "tenant"
+    }
+}
+
+impl AuthorizationAddon for Tenant {
+    fn execute(&self, _: &dyn AuthorizationRequest) -> AddonResult {
+        AddonResult::Data(Value::public(Some(self.0.to_string())))
+    }
+}
+
+impl AccessTokenAddon for Tenant {
+    fn execute(&self, _: &dyn AccessTokenRequest, code_data: Option<Value>) -> AddonResult {
+        match code_data {
+            Some(data) => AddonResult::Data(data),
+            None => AddonResult::Err,
+        }
+    }
+}
+
+impl Addon for Tenant {
+    fn register(self, addons: &mut AddonList) {
+        addons.push_code(self)
+    }
+}
+
+fn addons() -> AddonList {
+    AddonList::new()
+        .with(AudienceAddon::new(vec![SERVER_A]))
+        .with(Tenant("acme"))
+}
+
+#[test]
+fn addons_registered_by_name() {
+    let addons = addons();
+    assert_eq!(addons.identifiers(), ["audience", "tenant"]);
+    assert_eq!(addons.authorization.len(), 2);
+    assert_eq!(addons.access_token.len(), 2);
+    // Only the audience takes part in the client credentials flow.
+    assert_eq!(addons.client_credentials.len(), 1);
+    assert!(addons.resource.is_empty());
+}
+
+#[test]
+fn addons_data_in_issued_grant() {
+    let mut registrar = ClientMap::new();
+    registrar.register_client(Client::public(
+        EXAMPLE_CLIENT_ID,
+        RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+    ));
+    let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+
+    let request = CraftedRequest {
+        query: Some(
+            [
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("response_type", "code"),
+                ("resource", SERVER_A),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+    let endpoint = Generic {
+        registrar: &registrar,
+        authorizer: &mut authorizer,
+        issuer: Vacant,
+        scopes: Vacant,
+        solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+        response: Vacant,
+    };
+    let mut endpoint = Extended::extend_with(endpoint, addons());
+    let response = AuthorizationFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Expected no flow execution error"));
+    assert_eq!(response.status, Status::Redirect);
+    assert!(!response.location.unwrap().as_str().contains("error"));
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("code", "AuthToken"),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: None,
+    };
+    let endpoint = Generic {
+        registrar: &registrar,
+        authorizer: &mut authorizer,
+        issuer: &mut issuer,
+        scopes: Vacant,
+        solicitor: Vacant,
+        response: Vacant,
+    };
+    let mut endpoint = Extended::extend_with(endpoint, addons());
+    let response = AccessTokenFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+        .execute(request)
+        .expect("Expected no flow execution error");
+    assert_eq!(response.status, Status::Ok, "Expected access token in response");
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    let token = body.access_token.expect("Expected an access token");
+
+    let grant = issuer
+        .recover_token(&token)
+        .unwrap()
+        .expect("Expected the grant of the token");
+    assert_eq!(AudienceAddon::audience(&grant), Some(SERVER_A));
+    let tenant = grant
+        .extensions
+        .value_of(&Tenant("acme"))
+        .expect("Expected the data of the tenant addon");
+    assert_eq!(tenant.public_value(), Ok(Some("acme")));
+}
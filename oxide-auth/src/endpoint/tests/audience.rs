@@ -0,0 +1,195 @@
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::grant::Grant;
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::code_grant::accesstoken::TokenResponse;
+use crate::endpoint::{AuthorizationFlow, AccessTokenFlow, ResourceFlow};
+use crate::frontends::simple::extensions::{AddonList, AudienceAddon, Extended};
+use crate::frontends::simple::endpoint::{Generic, Vacant};
+
+use super::{Allow, Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+use serde_json;
+
+const SERVER_A: &str = "https://a.example";
+const SERVER_B: &str = "https://b.example";
+
+struct AudienceSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<RandomGenerator>,
+}
+
+impl AudienceSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        AudienceSetup {
+            registrar,
+            authorizer: AuthMap::new(TestGenerator("AuthToken".to_string())),
+            issuer: TokenMap::new(RandomGenerator::new(16)),
+        }
+    }
+
+    /// The addons of the authorization server, which knows both resource servers.
+    fn server_addons() -> AddonList {
+        let mut addons = AddonList::new();
+        addons.push_code(AudienceAddon::new(vec![SERVER_A, SERVER_B]));
+        addons
+    }
+
+    fn authorize(&mut self, resource: Option<&str>) -> CraftedResponse {
+        let mut query = vec![
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ("response_type", "code"),
+        ];
+        query.extend(resource.map(|resource| ("resource", resource)));
+        let request = CraftedRequest {
+            query: Some(query.iter().to_single_value_query()),
+            urlbody: None,
+            auth: None,
+        };
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: Vacant,
+            scopes: Vacant,
+            solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, Self::server_addons());
+        AuthorizationFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+            .execute(request)
+            .unwrap_or_else(|_| panic!("Expected no flow execution error"))
+    }
+
+    fn token(&mut self, resource: Option<&str>) -> CraftedResponse {
+        let mut body = vec![
+            ("grant_type", "authorization_code"),
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("code", "AuthToken"),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+        ];
+        body.extend(resource.map(|resource| ("resource", resource)));
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(body.iter().to_single_value_query()),
+            auth: None,
+        };
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, Self::server_addons());
+        AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .expect("Expected no flow execution error")
+    }
+
+    /// Authorize and redeem the code, returning the access token.
+    fn access_token(&mut self, resource: Option<&str>) -> String {
+        let response = self.authorize(resource);
+        assert_eq!(response.status, Status::Redirect);
+        assert!(!response.location.unwrap().as_str().contains("error"));
+
+        let response = self.token(None);
+        assert_eq!(response.status, Status::Ok, "Expected access token in response");
+        let body: TokenResponse = match response.body {
+            Some(Body::Json(body)) => serde_json::from_str(&body).unwrap(),
+            other => panic!("Expected json encoded body, got {:?}", other),
+        };
+        body.access_token.expect("Expected an access token")
+    }
+
+    /// Present the token to the resource server with the given audience.
+    fn access(&mut self, server: &str, token: &str) -> Result<Grant, CraftedResponse> {
+        let mut addons = AddonList::new();
+        addons.push_resource(AudienceAddon::new(vec![server]));
+
+        let scopes: [Scope; 1] = [EXAMPLE_SCOPE.parse().unwrap()];
+        let endpoint = Generic {
+            registrar: Vacant,
+            authorizer: Vacant,
+            issuer: &mut self.issuer,
+            scopes: &scopes[..],
+            solicitor: Vacant,
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, addons);
+        let request = CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("Bearer {}", token)),
+        };
+
+        ResourceFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on resource flow."))
+            .execute(request)
+            .map_err(|err| err.unwrap_or_else(|_| panic!("Expected an error response")))
+    }
+}
+
+#[test]
+fn audience_restricts_resource() {
+    let mut setup = AudienceSetup::new();
+    let token = setup.access_token(Some(SERVER_A));
+
+    let grant = setup
+        .access(SERVER_A, &token)
+        .expect("Expected access to server A");
+    assert_eq!(grant.extensions.public_value("audience"), Some(SERVER_A));
+
+    let response = setup
+        .access(SERVER_B, &token)
+        .expect_err("Expected server B to deny the token");
+    assert_eq!(response.status, Status::Unauthorized);
+    let authenticate = response.www_authenticate.expect("Should ask for authentication");
+    assert!(authenticate.contains("invalid_token"), "{}", authenticate);
+}
+
+#[test]
+fn audience_unrestricted() {
+    let mut setup = AudienceSetup::new();
+    let token = setup.access_token(None);
+
+    let grant = setup
+        .access(SERVER_B, &token)
+        .expect("Expected access to server B");
+    assert_eq!(grant.extensions.get("audience"), None);
+}
+
+#[test]
+fn audience_unknown() {
+    let mut setup = AudienceSetup::new();
+    let response = setup.authorize(Some("https://c.example"));
+    assert_eq!(response.status, Status::Redirect);
+    assert!(response.location.unwrap().as_str().contains("error"));
+}
+
+#[test]
+fn audience_changed_in_token_request() {
+    let mut setup = AudienceSetup::new();
+    let response = setup.authorize(Some(SERVER_A));
+    assert!(!response.location.unwrap().as_str().contains("error"));
+
+    let response = setup.token(Some(SERVER_B));
+    assert_eq!(response.status, Status::BadRequest);
+}
@@ -1,13 +1,27 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use url::Url;
+
 use crate::primitives::authorizer::AuthMap;
-use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::registrar::{Client, ClientMap, ClientTrust, GrantType, RegisteredUrl};
+
+use crate::endpoint::{AuthorizationFlow, AuthorizationResponse, Authorizer, Issuer};
+use crate::endpoint::OwnerConsent;
+use crate::endpoint::{OwnerSolicitor, ScopeDescriptions, Solicitation, Template};
 
-use crate::endpoint::{OwnerSolicitor};
+use crate::frontends::simple::endpoint::{
+    access_token_flow, authorization_flow, FnSolicitor, Generic, Hooks, TieredSolicitor, Vacant,
+};
+use crate::frontends::simple::endpoint::ResponseCreator;
 
-use crate::frontends::simple::endpoint::authorization_flow;
+use crate::code_grant::accesstoken::TokenResponse;
 
-use super::{CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
 use super::{Allow, Deny};
 use super::defaults::*;
 
@@ -211,6 +225,39 @@ fn auth_request_silent_mismatching_literal_redirect() {
     setup.test_success(valid_redirect);
 }
 
+#[test]
+fn auth_request_multiple_redirects() {
+    let mut setup = AuthorizationSetup::new();
+    const UNIQUE_CLIENT: &str = "client_auth_request_multiple_redirects";
+    const FIRST_URL: &str = "https://right.client.example/first";
+    const SECOND_URL: &str = "https://right.client.example/second";
+
+    let client = Client::confidential(
+        UNIQUE_CLIENT,
+        RegisteredUrl::Exact(FIRST_URL.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+        EXAMPLE_PASSPHRASE.as_bytes(),
+    )
+    .with_additional_redirect_uris(vec![RegisteredUrl::Exact(SECOND_URL.parse().unwrap())]);
+    setup.registrar.register_client(client);
+
+    let request_with = |redirect_uri: Option<&str>| {
+        let mut query = vec![("response_type", "code"), ("client_id", UNIQUE_CLIENT)];
+        query.extend(redirect_uri.map(|uri| ("redirect_uri", uri)));
+        CraftedRequest {
+            query: Some(query.iter().to_single_value_query()),
+            urlbody: None,
+            auth: None,
+        }
+    };
+
+    setup.test_success(request_with(Some(FIRST_URL)));
+    setup.test_success(request_with(Some(SECOND_URL)));
+    setup.test_silent_error(request_with(Some("https://right.client.example/third")));
+    // With multiple registered uris there is no default to fall back to.
+    setup.test_silent_error(request_with(None));
+}
+
 #[test]
 fn auth_request_silent_invalid_redirect() {
     // The redirect_uri is not an uri ('\' is not allowed to appear in the scheme)
@@ -251,6 +298,49 @@ fn auth_request_error_denied() {
     AuthorizationSetup::new().test_error_redirect(denied_request, Deny);
 }
 
+#[test]
+fn auth_request_error_restricted_grant_type() {
+    let mut setup = AuthorizationSetup::new();
+    setup.registrar.register_client(
+        Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        )
+        .with_grant_types(vec![GrantType::ClientCredentials]),
+    );
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let response = authorization_flow(
+        &mut setup.registrar,
+        &mut setup.authorizer,
+        &mut Allow(EXAMPLE_OWNER_ID.to_string()),
+    )
+    .execute(request)
+    .expect("Should not error");
+
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Should redirect to the client");
+    let query: HashMap<_, _> = location.query_pairs().into_owned().collect();
+    assert_eq!(
+        query.get("error").map(String::as_str),
+        Some("unauthorized_client")
+    );
+}
+
 #[test]
 fn auth_request_error_unsupported_method() {
     // Requesting an authorization token for a method other than code
@@ -272,6 +362,67 @@ fn auth_request_error_unsupported_method() {
         .test_error_redirect(unsupported_method, Allow(EXAMPLE_OWNER_ID.to_string()));
 }
 
+fn response_type_request(response_type: &str) -> CraftedRequest {
+    CraftedRequest {
+        query: Some(
+            [
+                ("response_type", response_type),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    }
+}
+
+impl AuthorizationSetup {
+    fn restricted_error(&mut self, allowed: &[&str], request: CraftedRequest) -> Option<String> {
+        let mut solicitor = Allow(EXAMPLE_OWNER_ID.to_string());
+        let mut flow = authorization_flow(&self.registrar, &mut self.authorizer, &mut solicitor);
+        flow.allow_response_types(allowed.iter().copied());
+        let response = flow.execute(request).expect("Should not error");
+
+        assert_eq!(response.status, Status::Redirect);
+        let location = response.location.expect("Expected a redirect location");
+        location
+            .query_pairs()
+            .find(|(key, _)| key == "error")
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+#[test]
+fn auth_response_type_allowed() {
+    let mut setup = AuthorizationSetup::new();
+    let error = setup.restricted_error(&["code"], response_type_request("code"));
+    assert_eq!(error, None);
+}
+
+#[test]
+fn auth_response_type_disallowed() {
+    let mut setup = AuthorizationSetup::new();
+    let error = setup.restricted_error(&["code"], response_type_request("token"));
+    assert_eq!(error.as_deref(), Some("unsupported_response_type"));
+
+    // Removing `code` from the allow-list also rejects the default response type.
+    let error = setup.restricted_error(&[], response_type_request("code"));
+    assert_eq!(error.as_deref(), Some("unsupported_response_type"));
+}
+
+#[test]
+fn auth_response_type_unservable() {
+    let mut setup = AuthorizationSetup::new();
+    // Without the implicit grant enabled, a listed `token` must not be answered with a code.
+    let error = setup.restricted_error(&["code", "token"], response_type_request("token"));
+    assert_eq!(error.as_deref(), Some("unsupported_response_type"));
+
+    let error = setup.restricted_error(&["code id_token"], response_type_request("code id_token"));
+    assert_eq!(error.as_deref(), Some("unsupported_response_type"));
+}
+
 #[test]
 fn auth_request_error_malformed_scope() {
     // A scope with malformed formatting
@@ -292,3 +443,427 @@ fn auth_request_error_malformed_scope() {
 
     AuthorizationSetup::new().test_error_redirect(malformed_scope, Allow(EXAMPLE_OWNER_ID.to_string()));
 }
+
+fn scope_request(scope: &str) -> CraftedRequest {
+    CraftedRequest {
+        query: Some(
+            [
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("scope", scope),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    }
+}
+
+impl AuthorizationSetup {
+    /// Run a request for `scope`, returning the redirect location.
+    fn excess_scope(&mut self, reject: bool, scope: &str) -> Url {
+        let mut solicitor = Allow(EXAMPLE_OWNER_ID.to_string());
+        let mut flow = authorization_flow(&self.registrar, &mut self.authorizer, &mut solicitor);
+        if reject {
+            flow.reject_excess_scope();
+        }
+        let response = flow.execute(scope_request(scope)).expect("Should not error");
+
+        assert_eq!(response.status, Status::Redirect);
+        response.location.expect("Expected a redirect location")
+    }
+}
+
+#[test]
+fn auth_excess_scope_narrowed() {
+    let mut setup = AuthorizationSetup::new();
+    let location = setup.excess_scope(false, "example default admin");
+    let code = location
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Expected an authorization code");
+
+    let grant = setup.authorizer.extract(&code).unwrap().unwrap();
+    assert_eq!(grant.scope, EXAMPLE_SCOPE.parse().unwrap());
+}
+
+#[test]
+fn auth_excess_scope_rejected() {
+    let mut setup = AuthorizationSetup::new();
+    let location = setup.excess_scope(true, "example default admin");
+    let pairs = location.query_pairs().into_owned().collect::<HashMap<_, _>>();
+    assert_eq!(pairs.get("error").map(String::as_str), Some("invalid_scope"));
+    assert!(pairs.contains_key("error_description"));
+    assert!(!pairs.contains_key("code"));
+
+    // Requests within the registered scope are still granted.
+    let location = setup.excess_scope(true, "example");
+    assert!(location.query_pairs().any(|(key, _)| key == "code"));
+}
+
+#[test]
+fn auth_duplicate_scope_not_excess() {
+    let mut setup = AuthorizationSetup::new();
+    let location = setup.excess_scope(true, "default example default");
+    let code = location
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Expected an authorization code");
+
+    let grant = setup.authorizer.extract(&code).unwrap().unwrap();
+    assert_eq!(grant.scope, EXAMPLE_SCOPE.parse().unwrap());
+}
+
+#[test]
+fn auth_excess_scope_solicitation() {
+    let mut setup = AuthorizationSetup::new();
+    let seen = RefCell::new(None);
+    let mut solicitor = FnSolicitor(|_: &mut CraftedRequest, solicitation: Solicitation| {
+        *seen.borrow_mut() = Some((
+            solicitation.requested_scope().cloned(),
+            solicitation.pre_grant().scope.clone(),
+        ));
+        OwnerConsent::Denied
+    });
+    authorization_flow(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .execute(scope_request("example default admin"))
+        .expect("Should not error");
+
+    // The client asked for more than it is registered for, and is offered the narrowed scope.
+    let (requested, negotiated) = seen.borrow_mut().take().expect("Should have solicited");
+    assert_eq!(requested, Some("example default admin".parse().unwrap()));
+    assert_eq!(negotiated, EXAMPLE_SCOPE.parse().unwrap());
+
+    // Without a scope in the request the registrar picks the default on its own.
+    let mut request = scope_request("");
+    request.query.as_mut().unwrap().remove("scope");
+    authorization_flow(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .execute(request)
+        .expect("Should not error");
+    let (requested, negotiated) = seen.borrow_mut().take().expect("Should have solicited");
+    assert_eq!(requested, None);
+    assert_eq!(negotiated, EXAMPLE_SCOPE.parse().unwrap());
+}
+
+#[test]
+fn auth_scope_descriptions() {
+    let setup = AuthorizationSetup::new();
+    let mut descriptions = ScopeDescriptions::new();
+    descriptions.insert("example", "Access any example");
+    descriptions.insert_for_client(EXAMPLE_CLIENT_ID, "example", "Access the example");
+
+    let described = RefCell::new(Vec::new());
+    let solicitor = FnSolicitor(|_: &mut CraftedRequest, solicitation: Solicitation| {
+        *described.borrow_mut() = solicitation.scope_descriptions().into_owned();
+        OwnerConsent::Denied
+    });
+
+    let mut endpoint = Generic {
+        registrar: setup.registrar,
+        authorizer: setup.authorizer,
+        issuer: Vacant,
+        solicitor,
+        scopes: Vacant,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().scope_descriptions(descriptions));
+
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("scope", "example default"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    AuthorizationFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Endpoint should support authorization"))
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Denying should not error"));
+
+    let pairs = described
+        .borrow()
+        .iter()
+        .map(|desc| (desc.token.clone(), desc.description.clone()))
+        .collect::<Vec<_>>();
+    // The `default` token has no description and falls back to the raw token.
+    assert_eq!(
+        pairs,
+        [
+            ("default".to_string(), "default".to_string()),
+            ("example".to_string(), "Access the example".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn auth_tiered_consent() {
+    const FIRST_PARTY_ID: &str = "FirstPartyClient";
+    let mut setup = AuthorizationSetup::new();
+    setup.registrar.register_client(
+        Client::public(
+            FIRST_PARTY_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        )
+        .with_trust(ClientTrust::FirstParty),
+    );
+
+    let consent_form = FnSolicitor(|_: &mut CraftedRequest, _: Solicitation| {
+        OwnerConsent::InProgress(CraftedResponse {
+            status: Status::Ok,
+            body: Some(Body::Text("Consent form".to_string())),
+            ..Default::default()
+        })
+    });
+    let mut solicitor = TieredSolicitor {
+        first_party: Allow(EXAMPLE_OWNER_ID.to_string()),
+        third_party: consent_form,
+    };
+
+    let request = |client_id: &str| CraftedRequest {
+        query: Some(
+            [
+                ("response_type", "code"),
+                ("client_id", client_id),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    // The first-party client is redirected with a code without asking the owner.
+    let response = authorization_flow(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .execute(request(FIRST_PARTY_ID))
+        .expect("Should not error");
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Expected a redirect location");
+    assert!(location.query_pairs().any(|(key, _)| key == "code"));
+
+    // The third-party client gets the consent form.
+    let response = authorization_flow(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .execute(request(EXAMPLE_CLIENT_ID))
+        .expect("Should not error");
+    assert_eq!(response.status, Status::Ok);
+    assert!(response.location.is_none());
+    match response.body {
+        Some(Body::Text(ref body)) if body == "Consent form" => (),
+        other => panic!("Expected the consent form, got {:?}", other),
+    }
+}
+
+/// Records the successful authorization response passed to the template.
+#[derive(Default)]
+struct RecordResponse(Option<AuthorizationResponse>);
+
+impl ResponseCreator<CraftedRequest> for RecordResponse {
+    fn create(&mut self, _: &mut CraftedRequest, kind: Template) -> CraftedResponse {
+        self.0 = kind.authorization_response().cloned();
+        CraftedResponse::default()
+    }
+}
+
+fn structured_response(issuer: Option<&str>) -> (AuthorizationResponse, CraftedResponse) {
+    let setup = AuthorizationSetup::new();
+    let mut endpoint = Generic {
+        registrar: setup.registrar,
+        authorizer: setup.authorizer,
+        issuer: Vacant,
+        solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+        scopes: Vacant,
+        response: RecordResponse::default(),
+    };
+
+    let request = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ("state", "OpaqueState"),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        ..CraftedRequest::default()
+    };
+
+    let mut flow = AuthorizationFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Endpoint should support authorization"));
+    if let Some(issuer) = issuer {
+        flow.issuer_identifier(issuer);
+    }
+
+    let response = flow
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Authorizing should not error"));
+    let recorded = endpoint
+        .response
+        .0
+        .take()
+        .expect("No structured response recorded");
+    (recorded, response)
+}
+
+#[test]
+fn auth_structured_response() {
+    let (recorded, response) = structured_response(Some("https://auth.example"));
+
+    assert_eq!(recorded.code, "AuthToken");
+    assert_eq!(recorded.state.as_deref(), Some("OpaqueState"));
+    assert_eq!(recorded.iss.as_deref(), Some("https://auth.example"));
+    assert_eq!(recorded.redirect_uri.as_str(), EXAMPLE_REDIRECT_URI);
+    assert_eq!(response.location, Some(recorded.redirect_url()));
+
+    let location = response.location.unwrap();
+    let pairs = location.query_pairs().into_owned().collect::<Vec<_>>();
+    assert_eq!(
+        pairs,
+        [
+            ("code".to_string(), "AuthToken".to_string()),
+            ("state".to_string(), "OpaqueState".to_string()),
+            ("iss".to_string(), "https://auth.example".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn auth_structured_response_default_redirect() {
+    let (recorded, response) = structured_response(None);
+    assert_eq!(recorded.iss, None);
+
+    let mut expected: url::Url = EXAMPLE_REDIRECT_URI.parse().unwrap();
+    expected
+        .query_pairs_mut()
+        .append_pair("code", "AuthToken")
+        .append_pair("state", "OpaqueState");
+    assert_eq!(response.location, Some(expected));
+}
+
+/// Run a request where the owner approves the scope in the `approved` form field.
+fn downscoped(setup: &mut AuthorizationSetup, approved: &str) -> CraftedResponse {
+    let mut solicitor = FnSolicitor(|request: &mut CraftedRequest, solicitation: Solicitation| {
+        assert_eq!(solicitation.pre_grant().scope, EXAMPLE_SCOPE.parse().unwrap());
+        let approved = &request.urlbody.as_ref().unwrap()["approved"][0];
+        OwnerConsent::AuthorizedScope(EXAMPLE_OWNER_ID.to_string(), approved.parse().unwrap())
+    });
+
+    let request = CraftedRequest {
+        query: Some(
+            [
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: Some([("approved", approved)].iter().to_single_value_query()),
+        auth: None,
+    };
+
+    authorization_flow(&setup.registrar, &mut setup.authorizer, &mut solicitor)
+        .execute(request)
+        .expect("Should not error")
+}
+
+#[test]
+fn auth_owner_restricted_scope() {
+    let mut setup = AuthorizationSetup::new();
+    let response = downscoped(&mut setup, "example");
+    assert_eq!(response.status, Status::Redirect);
+
+    let location = response.location.expect("Expected a redirect location");
+    let code = location
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Expected an authorization code");
+    let grant = setup
+        .authorizer
+        .extract(&code)
+        .unwrap()
+        .expect("Code should be valid");
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+    assert_eq!(grant.scope, "example".parse().unwrap());
+}
+
+#[test]
+fn auth_owner_widened_scope_denied() {
+    for approved in ["example default admin", ""] {
+        let mut setup = AuthorizationSetup::new();
+        let response = downscoped(&mut setup, approved);
+        assert_eq!(response.status, Status::Redirect);
+
+        let location = response.location.expect("Expected a redirect location");
+        let error = location
+            .query_pairs()
+            .find(|(key, _)| key == "error")
+            .map(|(_, value)| value.into_owned());
+        assert_eq!(error.as_deref(), Some("access_denied"));
+    }
+}
+
+#[test]
+fn auth_owner_restricted_scope_token() {
+    let mut setup = AuthorizationSetup::new();
+    let response = downscoped(&mut setup, "example");
+    let code = response
+        .location
+        .expect("Expected a redirect location")
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Expected an authorization code");
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(
+            "Basic ".to_string()
+                + &STANDARD.encode(format!("{}:{}", EXAMPLE_CLIENT_ID, EXAMPLE_PASSPHRASE)),
+        ),
+    };
+
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let response = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut issuer)
+        .execute(request)
+        .expect("Should not error");
+    assert_eq!(response.status, Status::Ok);
+
+    let body = match response.body {
+        Some(Body::Json(body)) => body,
+        other => panic!("Expected json body, got {:?}", other),
+    };
+    let body: TokenResponse = serde_json::from_str(&body).expect("Expected valid json body");
+    assert_eq!(body.scope.as_deref(), Some("example"));
+
+    let token = body.access_token.expect("Expected an access token");
+    let grant = issuer
+        .recover_token(&token)
+        .unwrap()
+        .expect("Token should be valid");
+    assert_eq!(grant.scope, "example".parse().unwrap());
+}
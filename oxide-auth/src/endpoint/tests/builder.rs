@@ -0,0 +1,143 @@
+use crate::endpoint::{AccessTokenFlow, AuthorizationFlow, Endpoint, ResourceFlow};
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::generator::{Assertion, AssertionKind, RandomGenerator};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::code_grant::accesstoken::TokenResponse;
+use crate::frontends::simple::endpoint::{EndpointBuilder, Hooks};
+
+use serde_json;
+
+use super::{Allow, Body, CraftedRequest, Status, ToSingleValueQuery};
+use super::defaults::*;
+
+fn registrar() -> ClientMap {
+    let mut registrar = ClientMap::new();
+    registrar.register_client(Client::public(
+        EXAMPLE_CLIENT_ID,
+        RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+    ));
+    registrar
+}
+
+/// Authorize the example client, redeem the code and access a resource with the token.
+fn round_trip<E: Endpoint<CraftedRequest>>(endpoint: &mut E) {
+    let authorization = CraftedRequest {
+        query: Some(
+            vec![
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        ..CraftedRequest::default()
+    };
+
+    let response = AuthorizationFlow::prepare(&mut *endpoint)
+        .unwrap_or_else(|_| panic!("Endpoint should support authorization"))
+        .execute(authorization)
+        .unwrap_or_else(|_| panic!("Authorization should not error"));
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Expected a redirect");
+    let (_, code) = location
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .expect("Expected an authorization code");
+
+    let access_token = CraftedRequest {
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("code", &code),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        ..CraftedRequest::default()
+    };
+
+    let response = AccessTokenFlow::prepare(&mut *endpoint)
+        .unwrap_or_else(|_| panic!("Endpoint should support access tokens"))
+        .execute(access_token)
+        .unwrap_or_else(|_| panic!("Access token request should not error"));
+    assert_eq!(response.status, Status::Ok);
+    let token = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str::<TokenResponse>(&body).unwrap(),
+        _ => panic!("Expected json body"),
+    };
+    let token = token.access_token.expect("Expected an access token");
+
+    let resource = CraftedRequest {
+        auth: Some(format!("Bearer {}", token)),
+        ..CraftedRequest::default()
+    };
+
+    ResourceFlow::prepare(&mut *endpoint)
+        .unwrap_or_else(|_| panic!("Endpoint should support resources"))
+        .execute(resource)
+        .unwrap_or_else(|_| panic!("Expected access allowed"));
+}
+
+#[test]
+fn builder_random_issuer() {
+    let mut endpoint = EndpointBuilder::new()
+        .registrar(registrar())
+        .authorizer(AuthMap::new(RandomGenerator::new(16)))
+        .with_random_issuer(16)
+        .solicitor(Allow(EXAMPLE_OWNER_ID.to_string()))
+        .scope("example".parse().unwrap())
+        .build();
+
+    round_trip(&mut endpoint);
+}
+
+#[test]
+fn builder_signer() {
+    let key = Assertion::new(AssertionKind::HmacSha256, b"0123456789abcdef0123456789abcdef");
+    let mut endpoint = EndpointBuilder::new()
+        .registrar(registrar())
+        .authorizer(AuthMap::new(RandomGenerator::new(16)))
+        .with_signer(key)
+        .solicitor(Allow(EXAMPLE_OWNER_ID.to_string()))
+        .scope("example".parse().unwrap())
+        .build();
+
+    round_trip(&mut endpoint);
+}
+
+#[test]
+fn builder_default_scopes() {
+    let mut endpoint = EndpointBuilder::new().with_random_issuer(16).build();
+
+    let resource = CraftedRequest {
+        auth: Some("Bearer unknown".to_string()),
+        ..CraftedRequest::default()
+    };
+
+    // Without any scopes no resource is accessible.
+    let response = ResourceFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Endpoint should support resources"))
+        .execute(resource);
+    assert!(response.is_err());
+}
+
+#[test]
+fn builder_hooks() {
+    let mut endpoint = EndpointBuilder::new()
+        .registrar(registrar())
+        .authorizer(AuthMap::new(RandomGenerator::new(16)))
+        .with_random_issuer(16)
+        .solicitor(Allow(EXAMPLE_OWNER_ID.to_string()))
+        .scope("example".parse().unwrap())
+        .hooks(Hooks::new().parameter_limit(8))
+        .build();
+    assert_eq!(Endpoint::<CraftedRequest>::parameter_limit(&endpoint), Some(8));
+
+    // The hooked endpoint still serves all flows of the wrapped one.
+    round_trip(&mut endpoint);
+}
@@ -1,17 +1,64 @@
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 
-use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
-use crate::primitives::issuer::TokenMap;
+use crate::primitives::registrar::{Client, ClientMap, GrantType, RegisteredUrl};
+use crate::primitives::authorizer::Authorizer;
+use crate::primitives::issuer::{Issuer, TokenMap};
+use crate::primitives::registrar::Registrar;
+use crate::primitives::scope::Scope;
 
-use crate::endpoint::{OwnerSolicitor};
+use crate::endpoint::{
+    ClientCredentialsFlow, Endpoint, OAuthError, OwnerConsent, OwnerSolicitor, Scopes, Solicitation,
+    Template, WebRequest,
+};
 
-use crate::frontends::simple::endpoint::client_credentials_flow;
+use crate::frontends::simple::endpoint::{client_credentials_flow, Error, FnSolicitor, Generic, Vacant};
 
-use super::{CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
 use super::{Allow, Deny};
 use super::defaults::*;
 
+/// Wraps an endpoint to remove its owner solicitor.
+struct WithoutSolicitor<E>(E);
+
+impl<E: Endpoint<CraftedRequest>> Endpoint<CraftedRequest> for WithoutSolicitor<E> {
+    type Error = E::Error;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.0.registrar()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.0.authorizer_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.0.issuer_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<CraftedRequest>> {
+        None
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<CraftedRequest>> {
+        self.0.scopes()
+    }
+
+    fn response(
+        &mut self, request: &mut CraftedRequest, kind: Template,
+    ) -> Result<CraftedResponse, Self::Error> {
+        self.0.response(request, kind)
+    }
+
+    fn error(&mut self, err: OAuthError) -> Self::Error {
+        self.0.error(err)
+    }
+
+    fn web_error(&mut self, err: <CraftedRequest as WebRequest>::Error) -> Self::Error {
+        self.0.web_error(err)
+    }
+}
+
 struct ClientCredentialsSetup {
     registrar: ClientMap,
     issuer: TokenMap<TestGenerator>,
@@ -70,6 +117,7 @@ impl ClientCredentialsSetup {
         let response = flow.execute(request).expect("Expected non-error reponse");
 
         assert_eq!(response.status, Status::Ok);
+        assert!(response.no_store, "Token responses must not be cached");
     }
 
     fn test_bad_request<S>(&mut self, request: CraftedRequest, mut solicitor: S)
@@ -81,6 +129,7 @@ impl ClientCredentialsSetup {
         let response = flow.execute(request).expect("Expected non-error response");
 
         assert_eq!(response.status, Status::BadRequest);
+        assert!(response.no_store, "Token responses must not be cached");
     }
 
     fn test_unauthorized<S>(&mut self, request: CraftedRequest, mut solicitor: S)
@@ -111,6 +160,77 @@ fn client_credentials_success() {
     setup.test_success(success, Allow(EXAMPLE_CLIENT_ID.to_owned()));
 }
 
+#[test]
+fn client_credentials_without_solicitor() {
+    let mut setup = ClientCredentialsSetup::new();
+    let success = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    let mut flow = ClientCredentialsFlow::prepare(WithoutSolicitor(Generic {
+        registrar: &setup.registrar,
+        authorizer: Vacant,
+        issuer: &mut setup.issuer,
+        solicitor: Vacant,
+        scopes: Vacant,
+        response: Vacant,
+    }))
+    .unwrap_or_else(|_| panic!("A solicitor should not be required"));
+    let response = flow.execute(success).expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    // The client is the owner of the grant.
+    let grant = setup.issuer.recover_token("AuthToken").unwrap().unwrap();
+    assert_eq!(grant.owner_id, EXAMPLE_CLIENT_ID);
+}
+
+#[test]
+fn client_credentials_restricted_scope() {
+    let mut setup = ClientCredentialsSetup::new();
+    let success = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    let solicitor = FnSolicitor(|_: &mut CraftedRequest, _: Solicitation| {
+        OwnerConsent::AuthorizedScope(EXAMPLE_CLIENT_ID.to_owned(), "example".parse().unwrap())
+    });
+    setup.test_success(success, solicitor);
+
+    let grant = setup.issuer.recover_token("AuthToken").unwrap().unwrap();
+    assert_eq!(grant.scope, "example".parse().unwrap());
+}
+
+#[test]
+fn client_credentials_widened_scope_denied() {
+    let mut setup = ClientCredentialsSetup::new();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    let solicitor = FnSolicitor(|_: &mut CraftedRequest, _: Solicitation| {
+        OwnerConsent::AuthorizedScope(EXAMPLE_CLIENT_ID.to_owned(), "example admin".parse().unwrap())
+    });
+    setup.test_bad_request(request, solicitor);
+}
+
 #[test]
 fn client_credentials_success_changed_owner() {
     let mut setup = ClientCredentialsSetup::new();
@@ -146,6 +266,37 @@ fn client_credentials_deny_public_client() {
     setup.test_bad_request(public_client, Deny);
 }
 
+#[test]
+fn client_credentials_deny_restricted_grant_type() {
+    let mut setup = ClientCredentialsSetup::new();
+    setup.registrar.register_client(
+        Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        )
+        .with_grant_types(vec![GrantType::AuthorizationCode]),
+    );
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    let mut solicitor = Allow(EXAMPLE_CLIENT_ID.to_owned());
+    let response = client_credentials_flow(&mut setup.registrar, &mut setup.issuer, &mut solicitor)
+        .execute(request)
+        .expect("Expected non-error response");
+
+    assert_eq!(response.status, Status::BadRequest);
+    assert_eq!(json_body(response)["error"], "unauthorized_client");
+}
+
 #[test]
 fn client_credentials_deny_incorrect_credentials() {
     let mut setup = ClientCredentialsSetup::new();
@@ -362,3 +513,112 @@ fn client_credentials_request_error_malformed_scope() {
 
     setup.test_bad_request(malformed_scope, Allow(EXAMPLE_OWNER_ID.to_owned()));
 }
+
+impl ClientCredentialsSetup {
+    fn generic_flow(
+        &mut self,
+    ) -> ClientCredentialsFlow<
+        impl Endpoint<CraftedRequest, Error = Error<CraftedRequest>> + '_,
+        CraftedRequest,
+    > {
+        Generic {
+            registrar: &self.registrar,
+            authorizer: Vacant,
+            issuer: &mut self.issuer,
+            solicitor: Deny,
+            scopes: Vacant,
+            response: Vacant,
+        }
+        .client_credentials_flow()
+    }
+}
+
+fn json_body(response: CraftedResponse) -> serde_json::Value {
+    match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        other => panic!("Expected json body, got {:?}", other),
+    }
+}
+
+#[test]
+fn client_credentials_generic_flow_omits_refresh_token() {
+    let mut setup = ClientCredentialsSetup::new();
+    let success = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    // The solicitor would deny the request if it were consulted.
+    let response = setup
+        .generic_flow()
+        .execute(success)
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+    assert!(response.no_store, "Token responses must not be cached");
+
+    let body = json_body(response);
+    assert_eq!(body["access_token"], "AuthToken");
+    let scope: Scope = body["scope"].as_str().unwrap().parse().unwrap();
+    assert_eq!(scope, EXAMPLE_SCOPE.parse().unwrap());
+    assert!(
+        body.get("refresh_token").is_none(),
+        "Unexpected refresh token: {}",
+        body
+    );
+
+    let grant = setup.issuer.recover_token("AuthToken").unwrap().unwrap();
+    assert_eq!(grant.owner_id, EXAMPLE_CLIENT_ID);
+    assert_eq!(grant.scope, EXAMPLE_SCOPE.parse().unwrap());
+    assert!(setup.issuer.recover_refresh("AuthToken").unwrap().is_none());
+}
+
+#[test]
+fn client_credentials_generic_flow_allowed_refresh_token() {
+    let mut setup = ClientCredentialsSetup::new();
+    let success = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [("grant_type", "client_credentials")]
+                .iter()
+                .to_single_value_query(),
+        ),
+        auth: Some(format!("Basic {}", setup.basic_authorization)),
+    };
+
+    let mut flow = setup.generic_flow();
+    flow.allow_refresh_token(true);
+    let response = flow.execute(success).expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    let body = json_body(response);
+    assert_eq!(body["refresh_token"], "AuthToken");
+}
+
+#[test]
+fn client_credentials_generic_flow_deny_public_client() {
+    let mut setup = ClientCredentialsSetup::public_client();
+    let public_client = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "client_credentials"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: None,
+    };
+
+    let response = setup
+        .generic_flow()
+        .execute(public_client)
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::BadRequest);
+    assert!(setup.issuer.recover_token("AccessToken").unwrap().is_none());
+}
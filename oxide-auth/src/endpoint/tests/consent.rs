@@ -0,0 +1,152 @@
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::consent::ConsentMap;
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::endpoint::{AuthorizationFlow, Endpoint, OwnerConsent, OwnerSolicitor, Solicitation};
+use crate::frontends::simple::endpoint::{Generic, Hooks, Vacant};
+
+use super::{CraftedRequest, CraftedResponse, Status, ToSingleValueQuery};
+use super::defaults::*;
+
+/// Knows the logged in owner and counts how often they were asked for consent.
+struct Counting {
+    owner: Option<String>,
+    scope: Option<Scope>,
+    asked: usize,
+}
+
+impl OwnerSolicitor<CraftedRequest> for Counting {
+    fn check_consent(
+        &mut self, _: &mut CraftedRequest, _: Solicitation,
+    ) -> OwnerConsent<CraftedResponse> {
+        self.asked += 1;
+        match &self.scope {
+            Some(scope) => OwnerConsent::AuthorizedScope(EXAMPLE_OWNER_ID.into(), scope.clone()),
+            None => OwnerConsent::Authorized(EXAMPLE_OWNER_ID.into()),
+        }
+    }
+
+    fn authenticated_owner(&mut self, _: &mut CraftedRequest) -> Option<String> {
+        self.owner.clone()
+    }
+}
+
+struct ConsentSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<RandomGenerator>,
+    consents: ConsentMap,
+    solicitor: Counting,
+}
+
+impl ConsentSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        ConsentSetup {
+            registrar,
+            authorizer: AuthMap::new(RandomGenerator::new(16)),
+            consents: ConsentMap::new(),
+            solicitor: Counting {
+                owner: Some(EXAMPLE_OWNER_ID.to_string()),
+                scope: None,
+                asked: 0,
+            },
+        }
+    }
+
+    fn authorize(&mut self, remember: bool) {
+        let query = [
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ("response_type", "code"),
+        ];
+        let request = CraftedRequest {
+            query: Some(query.iter().to_single_value_query()),
+            urlbody: None,
+            auth: None,
+        };
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: Vacant,
+            scopes: Vacant,
+            solicitor: &mut self.solicitor,
+            response: Vacant,
+        };
+        let response = if remember {
+            execute(
+                endpoint.with_hooks(Hooks::new().consent_store(&mut self.consents)),
+                request,
+            )
+        } else {
+            execute(endpoint, request)
+        };
+
+        assert_eq!(response.status, Status::Redirect);
+        let location = response.location.expect("Expected a redirect location");
+        assert!(location.query_pairs().any(|(key, _)| key == "code"));
+    }
+}
+
+fn execute(endpoint: impl Endpoint<CraftedRequest>, request: CraftedRequest) -> CraftedResponse {
+    AuthorizationFlow::prepare(endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Expected no flow execution error"))
+}
+
+#[test]
+fn consent_remembered() {
+    let mut setup = ConsentSetup::new();
+    setup.authorize(true);
+    assert_eq!(setup.solicitor.asked, 1);
+
+    // The owner is not asked again for the same client and scope.
+    setup.authorize(true);
+    assert_eq!(setup.solicitor.asked, 1);
+
+    setup.consents.revoke(EXAMPLE_OWNER_ID, EXAMPLE_CLIENT_ID);
+    setup.authorize(true);
+    assert_eq!(setup.solicitor.asked, 2);
+}
+
+#[test]
+fn consent_without_store() {
+    let mut setup = ConsentSetup::new();
+    setup.authorize(false);
+    setup.authorize(false);
+    assert_eq!(setup.solicitor.asked, 2);
+}
+
+#[test]
+fn consent_unknown_owner() {
+    let mut setup = ConsentSetup::new();
+    setup.solicitor.owner = None;
+    setup.authorize(true);
+    setup.authorize(true);
+    assert_eq!(setup.solicitor.asked, 2);
+}
+
+#[test]
+fn consent_partial_scope() {
+    let mut setup = ConsentSetup::new();
+    setup.solicitor.scope = Some("example".parse().unwrap());
+    setup.authorize(true);
+
+    // Only part of the scope was approved, the full scope must be approved again.
+    setup.authorize(true);
+    assert_eq!(setup.solicitor.asked, 2);
+
+    setup.solicitor.scope = None;
+    setup.authorize(true);
+    setup.authorize(true);
+    assert_eq!(setup.solicitor.asked, 3);
+}
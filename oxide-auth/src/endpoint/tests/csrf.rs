@@ -0,0 +1,151 @@
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::endpoint::{OwnerConsent, Solicitation};
+use crate::frontends::simple::csrf::ConsentCsrf;
+use crate::frontends::simple::endpoint::{authorization_flow, FnSolicitor};
+
+use chrono::Duration;
+
+use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+const SESSION: &str = "SessionOfTheOwner";
+
+struct CsrfSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    csrf: ConsentCsrf,
+}
+
+impl CsrfSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        CsrfSetup {
+            registrar,
+            authorizer: AuthMap::new(TestGenerator("AuthToken".to_string())),
+            csrf: ConsentCsrf::ephemeral(),
+        }
+    }
+
+    fn request(state: &str, token: Option<&str>) -> CraftedRequest {
+        CraftedRequest {
+            query: Some(
+                [
+                    ("response_type", "code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                    ("state", state),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            urlbody: token.map(|token| [(ConsentCsrf::FIELD, token)].iter().to_single_value_query()),
+            auth: None,
+        }
+    }
+
+    /// Render the consent form in a session, returning the embedded token.
+    fn form(&mut self, session: &str, state: &str) -> String {
+        let csrf = &self.csrf;
+        let mut solicitor = FnSolicitor(|_: &mut CraftedRequest, solicitation: Solicitation| {
+            let token = csrf.token(session, &solicitation).unwrap();
+            OwnerConsent::InProgress(CraftedResponse {
+                status: Status::Ok,
+                body: Some(Body::Text(token)),
+                ..CraftedResponse::default()
+            })
+        });
+
+        let response = authorization_flow(&self.registrar, &mut self.authorizer, &mut solicitor)
+            .execute(Self::request(state, None))
+            .expect("Should not error");
+        match response.body {
+            Some(Body::Text(token)) => token,
+            other => panic!("Expected the form with a token, got {:?}", other),
+        }
+    }
+
+    /// Submit the consent form in the owner's session.
+    fn submit(&mut self, state: &str, token: Option<&str>) -> CraftedResponse {
+        let csrf = &self.csrf;
+        let mut solicitor = FnSolicitor(|request: &mut CraftedRequest, solicitation: Solicitation| {
+            match csrf.check(request, SESSION, &solicitation) {
+                Ok(()) => OwnerConsent::Authorized(EXAMPLE_OWNER_ID.to_string()),
+                Err(()) => OwnerConsent::Denied,
+            }
+        });
+
+        authorization_flow(&self.registrar, &mut self.authorizer, &mut solicitor)
+            .execute(Self::request(state, token))
+            .expect("Should not error")
+    }
+}
+
+fn assert_denied(response: CraftedResponse) {
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Expected a redirect location");
+    let error = location
+        .query_pairs()
+        .find(|(key, _)| key == "error")
+        .map(|(_, value)| value.into_owned());
+    assert_eq!(error.as_deref(), Some("access_denied"));
+}
+
+#[test]
+fn csrf_valid_token() {
+    let mut setup = CsrfSetup::new();
+    let token = setup.form(SESSION, "OpaqueState");
+    let response = setup.submit("OpaqueState", Some(&token));
+
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Expected a redirect location");
+    assert!(location.query_pairs().any(|(key, _)| key == "code"));
+}
+
+#[test]
+fn csrf_missing_token() {
+    let mut setup = CsrfSetup::new();
+    assert_denied(setup.submit("OpaqueState", None));
+}
+
+#[test]
+fn csrf_forged_token() {
+    let mut setup = CsrfSetup::new();
+    let token = setup.form(SESSION, "OpaqueState");
+
+    // A token of the attacker's own session.
+    let foreign = setup.form("SessionOfTheAttacker", "OpaqueState");
+    assert_denied(setup.submit("OpaqueState", Some(&foreign)));
+
+    // A token for another request.
+    assert_denied(setup.submit("OtherState", Some(&token)));
+
+    // A token with a modified expiry or signature.
+    let (expiry, signature) = token.split_once('.').unwrap();
+    let extended = format!("{}.{}", expiry.parse::<i64>().unwrap() + 3600, signature);
+    assert_denied(setup.submit("OpaqueState", Some(&extended)));
+    assert_denied(setup.submit("OpaqueState", Some(&format!("{}.", expiry))));
+    assert_denied(setup.submit("OpaqueState", Some("garbage")));
+
+    // A token signed with another key.
+    let other = ConsentCsrf::ephemeral();
+    let original = std::mem::replace(&mut setup.csrf, other);
+    let resigned = setup.form(SESSION, "OpaqueState");
+    setup.csrf = original;
+    assert_denied(setup.submit("OpaqueState", Some(&resigned)));
+}
+
+#[test]
+fn csrf_expired_token() {
+    let mut setup = CsrfSetup::new();
+    setup.csrf.valid_for(Duration::seconds(-1));
+    let token = setup.form(SESSION, "OpaqueState");
+    assert_denied(setup.submit("OpaqueState", Some(&token)));
+}
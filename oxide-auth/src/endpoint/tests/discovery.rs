@@ -0,0 +1,116 @@
+use crate::endpoint::{MetadataFlow, ServerMetadata};
+use crate::frontends::simple::endpoint::{Error, Generic, Vacant, WellKnown};
+
+use serde_json::Value;
+
+use super::{Body, CraftedRequest, CraftedResponse, Status};
+
+const ISSUER: &str = "https://auth.example";
+
+fn metadata() -> ServerMetadata {
+    let mut metadata = ServerMetadata::new(ISSUER);
+    metadata.authorization_endpoint = Some("https://auth.example/authorize".parse().unwrap());
+    metadata.token_endpoint = Some("https://auth.example/token".parse().unwrap());
+    metadata.jwks_uri = Some("https://auth.example/.well-known/jwks.json".parse().unwrap());
+    metadata.grant_types_supported = vec!["authorization_code".into(), "refresh_token".into()];
+    metadata.code_challenge_methods_supported = vec!["S256".into()];
+    metadata
+}
+
+fn vacant() -> Generic<Vacant, Vacant, Vacant> {
+    Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer: Vacant,
+        scopes: Vacant,
+        solicitor: Vacant,
+        response: Vacant,
+    }
+}
+
+fn get() -> CraftedRequest {
+    CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: None,
+    }
+}
+
+fn json_body(response: CraftedResponse) -> Value {
+    assert_eq!(response.status, Status::Ok);
+    match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    }
+}
+
+#[test]
+fn metadata_document() {
+    let endpoint = WellKnown::new(vacant(), metadata());
+    let response = endpoint
+        .metadata_flow()
+        .execute(get())
+        .expect("Expected a metadata response");
+
+    let document = json_body(response);
+    assert_eq!(document["issuer"], ISSUER);
+    assert_eq!(
+        document["authorization_endpoint"],
+        "https://auth.example/authorize"
+    );
+    assert_eq!(document["token_endpoint"], "https://auth.example/token");
+    assert_eq!(document["response_types_supported"], serde_json::json!(["code"]));
+    assert_eq!(
+        document["grant_types_supported"],
+        serde_json::json!(["authorization_code", "refresh_token"])
+    );
+    assert_eq!(
+        document["code_challenge_methods_supported"],
+        serde_json::json!(["S256"])
+    );
+    // Empty members are omitted.
+    assert!(document.get("scopes_supported").is_none());
+    assert!(document
+        .get("authorization_response_iss_parameter_supported")
+        .is_none());
+}
+
+#[test]
+fn metadata_missing() {
+    match MetadataFlow::<_, CraftedRequest>::prepare(vacant()) {
+        Err(Error::OAuth(_)) => (),
+        Err(other) => panic!("Expected a primitive error, got {:?}", other),
+        Ok(_) => panic!("Expected the flow to require metadata"),
+    }
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn jwks_document() {
+    use crate::endpoint::JwksFlow;
+    use crate::primitives::generator::Assertion;
+    use crate::primitives::issuer::TokenSigner;
+
+    let signer = TokenSigner::jwt(Assertion::ed25519(&[1; 32]), ISSUER, "https://api.example");
+    let endpoint = WellKnown::new(vacant(), metadata()).with_jwks(signer.jwks());
+    let response = JwksFlow::prepare(endpoint)
+        .unwrap_or_else(|_| panic!("Expected the endpoint to have a key set"))
+        .execute(get())
+        .expect("Expected a key set response");
+
+    let document = json_body(response);
+    let keys = document["keys"].as_array().expect("Expected a list of keys");
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0]["kid"], signer.jwks().keys()[0].kid());
+    assert_eq!(keys[0]["kty"], "OKP");
+    assert_eq!(keys[0]["alg"], "EdDSA");
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn jwks_missing() {
+    use crate::endpoint::JwksFlow;
+
+    let endpoint = WellKnown::new(vacant(), metadata());
+    assert!(JwksFlow::<_, CraftedRequest>::prepare(endpoint).is_err());
+}
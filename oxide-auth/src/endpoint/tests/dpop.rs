@@ -0,0 +1,258 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::binding::Binding;
+use crate::primitives::generator::{Assertion, RandomGenerator};
+use crate::primitives::grant::{Extensions, Grant};
+use crate::primitives::issuer::{Issuer, TokenMap};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::endpoint::{AccessTokenFlow, QueryParameter, ResourceFlow, WebRequest};
+use crate::frontends::simple::endpoint::{Generic, Hooked, Hooks, Vacant};
+use crate::frontends::simple::extensions::{AddonList, DpopValidator, Extended};
+
+use std::borrow::Cow;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{Duration, Utc};
+use rand::{thread_rng, RngCore};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use super::{Body, CraftedError, CraftedRequest, CraftedResponse, Status, ToSingleValueQuery};
+use super::defaults::*;
+
+const TOKEN_ENDPOINT: &str = "https://auth.example/token";
+const RESOURCE: &str = "https://resource.example/photos";
+
+/// A request that additionally carries a `DPoP` header.
+#[derive(Debug)]
+struct DpopRequest {
+    inner: CraftedRequest,
+    proof: Option<String>,
+}
+
+impl WebRequest for DpopRequest {
+    type Response = CraftedResponse;
+    type Error = CraftedError;
+
+    fn query(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.inner.query()
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.inner.urlbody()
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+        self.inner.authheader()
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        match name.eq_ignore_ascii_case("DPoP") {
+            true => Ok(self.proof.as_deref().map(Cow::Borrowed)),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Sign a proof for the target with the key, for the access token if there is one.
+fn proof(key: &Assertion, htm: &str, htu: &str, access_token: Option<&str>) -> String {
+    let mut jti = [0; 16];
+    thread_rng().fill_bytes(&mut jti);
+
+    let header = json!({
+        "typ": "dpop+jwt",
+        "alg": "EdDSA",
+        "jwk": {
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": URL_SAFE_NO_PAD.encode(key.public_key().unwrap()),
+        },
+    });
+    let mut claims = json!({
+        "jti": URL_SAFE_NO_PAD.encode(jti),
+        "htm": htm,
+        "htu": htu,
+        "iat": Utc::now().timestamp(),
+    });
+    if let Some(token) = access_token {
+        claims["ath"] = json!(URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes())));
+    }
+
+    let input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = key.signature(input.as_bytes()).unwrap();
+    format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// Redeem a code with a proof signed by the key, returning the issued access token.
+fn bound_token(issuer: &mut TokenMap<RandomGenerator>, key: &Assertion) -> String {
+    let mut registrar = ClientMap::new();
+    registrar.register_client(Client::public(
+        EXAMPLE_CLIENT_ID,
+        RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+        EXAMPLE_SCOPE.parse().unwrap(),
+    ));
+
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let code = authorizer
+        .authorize(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        })
+        .unwrap();
+
+    let mut addons = AddonList::new();
+    addons.push_access_token(DpopValidator::required("POST", TOKEN_ENDPOINT.parse().unwrap()));
+    let mut endpoint = Extended::extend_with(
+        Generic {
+            registrar: &registrar,
+            authorizer: &mut authorizer,
+            issuer: &mut *issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        },
+        addons,
+    );
+
+    let request = DpopRequest {
+        inner: CraftedRequest {
+            query: None,
+            urlbody: Some(
+                vec![
+                    ("grant_type", "authorization_code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("code", &code),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: None,
+        },
+        proof: Some(proof(key, "POST", TOKEN_ENDPOINT, None)),
+    };
+
+    let response = AccessTokenFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+        .execute(request)
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+
+    let body: serde_json::Value = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    assert_eq!(body["token_type"], "DPoP");
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+type BoundEndpoint<'a> = Hooked<
+    Generic<Vacant, Vacant, &'a mut dyn Issuer, Vacant, &'a [Scope], Vacant>,
+    Hooks<Vacant, DpopValidator>,
+>;
+
+fn bound_flow<'a>(
+    issuer: &'a mut dyn Issuer, scopes: &'a [Scope],
+) -> ResourceFlow<BoundEndpoint<'a>, DpopRequest> {
+    let endpoint = Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer,
+        scopes,
+        solicitor: Vacant,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().proof_verifier(DpopValidator::required("GET", RESOURCE.parse().unwrap())));
+
+    ResourceFlow::prepare(endpoint).unwrap_or_else(|_| panic!("Endpoint has issuer and scopes"))
+}
+
+fn resource_request(token: &str, proof: Option<String>) -> DpopRequest {
+    DpopRequest {
+        inner: CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("DPoP {}", token)),
+        },
+        proof,
+    }
+}
+
+fn assert_denied(
+    result: Result<
+        Grant,
+        Result<CraftedResponse, crate::frontends::simple::endpoint::Error<DpopRequest>>,
+    >,
+) {
+    match result {
+        Err(Ok(response)) => assert_eq!(response.status, Status::Unauthorized),
+        other => panic!("Expected an error response instead of {:?}", other),
+    }
+}
+
+#[test]
+fn dpop_valid_proof() {
+    let key = Assertion::ed25519(&[1; 32]);
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let token = bound_token(&mut issuer, &key);
+
+    let grant = issuer.recover_token(&token).unwrap().unwrap();
+    assert_eq!(
+        Binding::of(&grant),
+        Some(Binding::Dpop {
+            jkt: key.key_id().unwrap()
+        })
+    );
+
+    let scopes = [EXAMPLE_SCOPE.parse().unwrap()];
+    let mut flow = bound_flow(&mut issuer, &scopes);
+    let request = resource_request(&token, Some(proof(&key, "GET", RESOURCE, Some(&token))));
+    flow.execute(request).expect("Should allow access");
+
+    // Every request needs a fresh proof.
+    let request = resource_request(&token, Some(proof(&key, "GET", RESOURCE, Some(&token))));
+    flow.execute(request).expect("Should allow access");
+}
+
+#[test]
+fn dpop_replayed_proof() {
+    let key = Assertion::ed25519(&[1; 32]);
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let token = bound_token(&mut issuer, &key);
+
+    let scopes = [EXAMPLE_SCOPE.parse().unwrap()];
+    let mut flow = bound_flow(&mut issuer, &scopes);
+    let replayed = proof(&key, "GET", RESOURCE, Some(&token));
+    flow.execute(resource_request(&token, Some(replayed.clone())))
+        .expect("Should allow access");
+    assert_denied(flow.execute(resource_request(&token, Some(replayed))));
+
+    // Nor is the proof sent to the token endpoint accepted by the resource.
+    let for_token = proof(&key, "POST", TOKEN_ENDPOINT, Some(&token));
+    assert_denied(flow.execute(resource_request(&token, Some(for_token))));
+    assert_denied(flow.execute(resource_request(&token, None)));
+}
+
+#[test]
+fn dpop_key_mismatch() {
+    let key = Assertion::ed25519(&[1; 32]);
+    let other = Assertion::ed25519(&[2; 32]);
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let token = bound_token(&mut issuer, &key);
+
+    // A valid proof, but of another key than the token is bound to.
+    let scopes = [EXAMPLE_SCOPE.parse().unwrap()];
+    let mut flow = bound_flow(&mut issuer, &scopes);
+    let request = resource_request(&token, Some(proof(&other, "GET", RESOURCE, Some(&token))));
+    assert_denied(flow.execute(request));
+}
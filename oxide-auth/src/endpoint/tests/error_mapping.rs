@@ -0,0 +1,188 @@
+use crate::code_grant::error::{AccessTokenErrorType, AuthorizationErrorType};
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::grant::{Grant, Extensions};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::endpoint::{AccessTokenFlow, AuthorizationFlow, ErrorMapping};
+use crate::frontends::simple::endpoint::{Generic, Hooks, Vacant};
+
+use std::collections::HashMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{Utc, Duration};
+use serde_json;
+
+use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::Deny;
+use super::defaults::*;
+
+struct MappingSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<TestGenerator>,
+    authtoken: String,
+}
+
+impl MappingSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+        let issuer = TokenMap::new(TestGenerator("AccessToken".to_string()));
+
+        registrar.register_client(Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        ));
+
+        let authtoken = authorizer
+            .authorize(Grant {
+                client_id: EXAMPLE_CLIENT_ID.to_string(),
+                owner_id: EXAMPLE_OWNER_ID.to_string(),
+                redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+                scope: EXAMPLE_SCOPE.parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap();
+
+        MappingSetup {
+            registrar,
+            authorizer,
+            issuer,
+            authtoken,
+        }
+    }
+
+    /// Authenticate as a client that was never registered.
+    fn unknown_client(&mut self, mapping: Option<ErrorMapping>) -> CraftedResponse {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("code", &self.authtoken),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: Some(
+                "Basic ".to_string()
+                    + &STANDARD.encode(format!("{}:{}", "SomeOtherClient", EXAMPLE_PASSPHRASE)),
+            ),
+        };
+
+        let mut endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        }
+        .with_hooks(Hooks {
+            error_mapping: mapping,
+            ..Hooks::new()
+        });
+
+        AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .unwrap_or_else(|_| panic!("Expected an error response"))
+    }
+}
+
+fn json_error(response: &CraftedResponse) -> String {
+    let body = match &response.body {
+        Some(Body::Json(content)) => content,
+        other => panic!("Expected json formated error, got {:?}", other),
+    };
+    let mut body: HashMap<String, String> = serde_json::from_str(body).expect("Body not json encoded");
+    body.remove("error").expect("Error not set in json response")
+}
+
+#[test]
+fn unknown_client_unmapped() {
+    let response = MappingSetup::new().unknown_client(None);
+    assert_eq!(response.status, Status::Unauthorized);
+    assert_eq!(json_error(&response), "invalid_client");
+}
+
+#[test]
+fn unknown_client_mapped() {
+    for (code, name) in [
+        (AccessTokenErrorType::InvalidGrant, "invalid_grant"),
+        (AccessTokenErrorType::InvalidRequest, "invalid_request"),
+    ] {
+        let mut mapping = ErrorMapping::new();
+        mapping.map_access_token(AccessTokenErrorType::InvalidClient, code);
+
+        let response = MappingSetup::new().unknown_client(Some(mapping));
+        // Only the code changes, the client is still asked to authenticate.
+        assert_eq!(response.status, Status::Unauthorized);
+        assert_eq!(json_error(&response), name);
+    }
+}
+
+#[test]
+fn unrelated_codes_kept() {
+    let mut mapping = ErrorMapping::new();
+    mapping.map_access_token(
+        AccessTokenErrorType::InvalidGrant,
+        AccessTokenErrorType::InvalidRequest,
+    );
+
+    let response = MappingSetup::new().unknown_client(Some(mapping));
+    assert_eq!(json_error(&response), "invalid_client");
+}
+
+#[test]
+fn authorization_denied_mapped() {
+    let mut setup = MappingSetup::new();
+    let request = CraftedRequest {
+        query: Some(
+            [
+                ("response_type", "code"),
+                ("client_id", EXAMPLE_CLIENT_ID),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        urlbody: None,
+        auth: None,
+    };
+
+    let mut mapping = ErrorMapping::new();
+    mapping.map_authorization(
+        AuthorizationErrorType::AccessDenied,
+        AuthorizationErrorType::InvalidRequest,
+    );
+
+    let mut endpoint = Generic {
+        registrar: &setup.registrar,
+        authorizer: &mut setup.authorizer,
+        issuer: Vacant,
+        scopes: Vacant,
+        solicitor: Deny,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().error_mapping(mapping));
+
+    let response = AuthorizationFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Expected an error redirect"));
+    assert_eq!(response.status, Status::Redirect);
+
+    let location = response.location.expect("Location header should be set");
+    let error = location
+        .query_pairs()
+        .find(|(key, _)| key == "error")
+        .map(|(_, value)| value.into_owned());
+    assert_eq!(error.as_deref(), Some("invalid_request"));
+}
@@ -0,0 +1,163 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::grant::{Grant, Extensions};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::endpoint::{AccessTokenFlow, AuthorizationFlow, FlowError, OAuthError};
+use crate::frontends::simple::endpoint::{Generic, Vacant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{Utc, Duration};
+
+use super::{CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::{Allow, Observed};
+use super::defaults::*;
+
+struct FlowErrorSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<TestGenerator>,
+}
+
+impl FlowErrorSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        let authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+        let issuer = TokenMap::new(TestGenerator("AccessToken".to_string()));
+
+        registrar.register_client(Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        ));
+
+        FlowErrorSetup {
+            registrar,
+            authorizer,
+            issuer,
+        }
+    }
+
+    fn code_until(&mut self, until: chrono::DateTime<Utc>) -> String {
+        self.authorizer
+            .authorize(Grant {
+                client_id: EXAMPLE_CLIENT_ID.to_string(),
+                owner_id: EXAMPLE_OWNER_ID.to_string(),
+                redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+                scope: EXAMPLE_SCOPE.parse().unwrap(),
+                until,
+                extensions: Extensions::new(),
+            })
+            .unwrap()
+    }
+
+    fn authorize(&mut self, client_id: &str, redirect_uri: &str) -> Vec<FlowError> {
+        let request = CraftedRequest {
+            query: Some(
+                [
+                    ("response_type", "code"),
+                    ("client_id", client_id),
+                    ("redirect_uri", redirect_uri),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            urlbody: None,
+            auth: None,
+        };
+
+        let mut endpoint = Observed::new(Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+            response: Vacant,
+        });
+
+        let result = AuthorizationFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+            .execute(request);
+        assert!(result.is_err(), "Expected the request to be denied silently");
+        endpoint.causes()
+    }
+
+    fn redeem(&mut self, code: &str) -> Vec<FlowError> {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("code", code),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: Some(
+                "Basic ".to_string()
+                    + &STANDARD.encode(format!("{}:{}", EXAMPLE_CLIENT_ID, EXAMPLE_PASSPHRASE)),
+            ),
+        };
+
+        let mut endpoint = Observed::new(Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        });
+
+        let response = AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .unwrap_or_else(|_| panic!("Expected an error response"));
+        assert_eq!(response.status, Status::BadRequest);
+        endpoint.causes()
+    }
+}
+
+#[test]
+fn authorization_unknown_client() {
+    let causes = FlowErrorSetup::new().authorize("SomeOtherClient", EXAMPLE_REDIRECT_URI);
+    assert_eq!(causes, [FlowError::UnknownClient]);
+}
+
+#[test]
+fn authorization_invalid_redirect_uri() {
+    let causes = FlowErrorSetup::new().authorize(EXAMPLE_CLIENT_ID, "https://evil.example/");
+    assert_eq!(causes, [FlowError::InvalidRedirectUri]);
+
+    let causes = FlowErrorSetup::new().authorize(EXAMPLE_CLIENT_ID, "not a url");
+    assert_eq!(causes, [FlowError::InvalidRedirectUri]);
+}
+
+#[test]
+fn access_token_expired_code() {
+    let mut setup = FlowErrorSetup::new();
+    let code = setup.code_until(Utc::now() - Duration::minutes(1));
+    assert_eq!(setup.redeem(&code), [FlowError::ExpiredCode]);
+}
+
+#[test]
+fn access_token_invalid_grant() {
+    let mut setup = FlowErrorSetup::new();
+    assert_eq!(setup.redeem("NotAnIssuedCode"), [FlowError::InvalidGrant]);
+}
+
+#[test]
+fn coarse_error_conversion() {
+    assert_eq!(
+        OAuthError::from(FlowError::UnknownClient),
+        OAuthError::DenySilently
+    );
+    assert_eq!(
+        OAuthError::from(FlowError::InvalidRedirectUri),
+        OAuthError::DenySilently
+    );
+    assert_eq!(OAuthError::from(FlowError::ExpiredCode), OAuthError::BadRequest);
+    assert_eq!(OAuthError::from(FlowError::Primitive), OAuthError::PrimitiveError);
+}
@@ -0,0 +1,243 @@
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::clock::{Clock, ManualClock};
+use crate::primitives::issuer::{Issuer, TokenMap};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::endpoint::{AuthorizationFlow, OwnerSolicitor};
+use crate::frontends::simple::endpoint::{Generic, Hooks, Vacant};
+
+use std::collections::HashMap;
+
+use chrono::{Duration, TimeZone, Utc};
+use url::Url;
+
+use super::{CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::{Allow, Deny};
+use super::defaults::*;
+
+const PUBLIC_CLIENT_ID: &str = "PublicClient";
+
+struct ImplicitSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<TestGenerator>,
+}
+
+impl ImplicitSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            PUBLIC_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+        registrar.register_client(Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        ));
+
+        ImplicitSetup {
+            registrar,
+            authorizer: AuthMap::new(TestGenerator("AuthToken".to_string())),
+            issuer: TokenMap::new(TestGenerator("AccessToken".to_string())),
+        }
+    }
+
+    fn request(client_id: &str) -> CraftedRequest {
+        CraftedRequest {
+            query: Some(
+                [
+                    ("response_type", "token"),
+                    ("client_id", client_id),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                    ("state", "OpaqueState"),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            urlbody: None,
+            auth: None,
+        }
+    }
+
+    fn execute<S>(&mut self, solicitor: S, implicit: bool, client_id: &str) -> CraftedResponse
+    where
+        S: OwnerSolicitor<CraftedRequest>,
+    {
+        let mut flow = AuthorizationFlow::prepare(Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            solicitor,
+            scopes: Vacant,
+            response: Vacant,
+        })
+        .unwrap_or_else(|_| panic!("Endpoint should support authorization"));
+        if implicit {
+            flow.allow_implicit();
+        }
+
+        flow.execute(Self::request(client_id))
+            .unwrap_or_else(|_| panic!("Expected a redirect"))
+    }
+}
+
+/// The redirect location and the parameters in its fragment.
+fn fragment(response: CraftedResponse) -> (Url, HashMap<String, String>) {
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Expected a redirect location");
+    let fragment = location.fragment().expect("Expected parameters in the fragment");
+    let parameters = url::form_urlencoded::parse(fragment.as_bytes())
+        .into_owned()
+        .collect();
+    (location, parameters)
+}
+
+#[test]
+fn implicit_fragment_format() {
+    let mut setup = ImplicitSetup::new();
+    let response = setup.execute(Allow(EXAMPLE_OWNER_ID.to_string()), true, PUBLIC_CLIENT_ID);
+    let (location, mut parameters) = fragment(response);
+
+    assert_eq!(location.query(), None);
+    let fragment = location.fragment().unwrap();
+    assert!(
+        fragment.starts_with("access_token=AccessToken&token_type=bearer&expires_in="),
+        "Unexpected fragment {}",
+        fragment
+    );
+    assert!(fragment.ends_with("&state=OpaqueState"));
+
+    let expires_in: i64 = parameters.remove("expires_in").unwrap().parse().unwrap();
+    assert!(expires_in > 0 && expires_in <= 600);
+    assert_eq!(parameters.remove("access_token").as_deref(), Some("AccessToken"));
+    assert_eq!(parameters.remove("token_type").as_deref(), Some("bearer"));
+    let scope: Scope = parameters.remove("scope").unwrap().parse().unwrap();
+    assert_eq!(scope, EXAMPLE_SCOPE.parse().unwrap());
+    assert_eq!(parameters.remove("state").as_deref(), Some("OpaqueState"));
+    // Neither a code nor a refresh token is issued.
+    assert!(parameters.is_empty(), "Unexpected parameters {:?}", parameters);
+
+    let grant = setup.issuer.recover_token("AccessToken").unwrap().unwrap();
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+    assert_eq!(grant.client_id, PUBLIC_CLIENT_ID);
+    // The issuer does not keep a refresh token either.
+    assert_eq!(setup.issuer.recover_refresh("AccessToken"), Ok(None));
+}
+
+#[test]
+fn implicit_endpoint_clock() {
+    let mut setup = ImplicitSetup::new();
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+    let mut flow = AuthorizationFlow::prepare(
+        Generic {
+            registrar: &setup.registrar,
+            authorizer: &mut setup.authorizer,
+            issuer: &mut setup.issuer,
+            solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+            scopes: Vacant,
+            response: Vacant,
+        }
+        .with_hooks(Hooks::new().clock(clock.clone())),
+    )
+    .unwrap_or_else(|_| panic!("Endpoint should support authorization"));
+    flow.allow_implicit();
+
+    let response = flow
+        .execute(ImplicitSetup::request(PUBLIC_CLIENT_ID))
+        .unwrap_or_else(|_| panic!("Expected a redirect"));
+    let (_, parameters) = fragment(response);
+    assert_eq!(parameters["expires_in"], "600");
+
+    let grant = setup.issuer.recover_token("AccessToken").unwrap().unwrap();
+    assert_eq!(grant.until, clock.now() + Duration::minutes(10));
+}
+
+#[test]
+fn implicit_requires_opt_in() {
+    let mut setup = ImplicitSetup::new();
+    let response = setup.execute(Allow(EXAMPLE_OWNER_ID.to_string()), false, PUBLIC_CLIENT_ID);
+    assert_eq!(response.status, Status::Redirect);
+
+    let location = response.location.expect("Expected a redirect location");
+    let error = location
+        .query_pairs()
+        .find(|(key, _)| key == "error")
+        .map(|(_, value)| value.into_owned());
+    assert_eq!(error.as_deref(), Some("unsupported_response_type"));
+    assert!(setup.issuer.recover_token("AccessToken").unwrap().is_none());
+}
+
+#[test]
+fn implicit_confidential_client_rejected() {
+    let mut setup = ImplicitSetup::new();
+    let response = setup.execute(Allow(EXAMPLE_OWNER_ID.to_string()), true, EXAMPLE_CLIENT_ID);
+    let (location, parameters) = fragment(response);
+
+    assert_eq!(location.query(), None);
+    assert_eq!(parameters["error"], "unauthorized_client");
+    assert_eq!(parameters["state"], "OpaqueState");
+    assert!(setup.issuer.recover_token("AccessToken").unwrap().is_none());
+}
+
+#[test]
+fn implicit_denied_in_fragment() {
+    let mut setup = ImplicitSetup::new();
+    let response = setup.execute(Deny, true, PUBLIC_CLIENT_ID);
+    let (location, parameters) = fragment(response);
+
+    assert_eq!(location.query(), None);
+    assert_eq!(parameters["error"], "access_denied");
+    assert_eq!(parameters["state"], "OpaqueState");
+}
+
+#[test]
+fn implicit_code_unaffected() {
+    let mut setup = ImplicitSetup::new();
+    let mut request = ImplicitSetup::request(PUBLIC_CLIENT_ID);
+    request
+        .query
+        .as_mut()
+        .unwrap()
+        .insert("response_type".into(), vec!["code".into()]);
+
+    let mut flow = AuthorizationFlow::prepare(Generic {
+        registrar: &setup.registrar,
+        authorizer: &mut setup.authorizer,
+        issuer: &mut setup.issuer,
+        solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+        scopes: Vacant,
+        response: Vacant,
+    })
+    .unwrap_or_else(|_| panic!("Endpoint should support authorization"));
+    flow.allow_implicit();
+
+    let response = flow
+        .execute(request)
+        .unwrap_or_else(|_| panic!("Expected a redirect"));
+    let location = response.location.expect("Expected a redirect location");
+    assert_eq!(location.fragment(), None);
+    assert!(location
+        .query_pairs()
+        .any(|(key, value)| key == "code" && value == "AuthToken"));
+}
+
+#[test]
+fn implicit_without_issuer() {
+    let mut setup = ImplicitSetup::new();
+    let mut flow = AuthorizationFlow::prepare(Generic {
+        registrar: &setup.registrar,
+        authorizer: &mut setup.authorizer,
+        issuer: Vacant,
+        solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+        scopes: Vacant,
+        response: Vacant,
+    })
+    .unwrap_or_else(|_| panic!("Endpoint should support authorization"));
+    flow.allow_implicit();
+
+    assert!(flow.execute(ImplicitSetup::request(PUBLIC_CLIENT_ID)).is_err());
+}
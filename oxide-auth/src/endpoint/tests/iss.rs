@@ -0,0 +1,144 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::grant::{Grant, Extensions};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::endpoint::AccessTokenFlow;
+use crate::frontends::simple::extensions::{AddonList, Extended, IssuerIdentifier};
+use crate::frontends::simple::endpoint::{Generic, Vacant};
+
+use chrono::{Utc, Duration};
+use serde_json;
+
+use super::{Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+const EXAMPLE_ISSUER: &str = "https://auth.example";
+
+struct IssSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<TestGenerator>,
+    authtoken: String,
+}
+
+impl IssSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+        let issuer = TokenMap::new(TestGenerator("AccessToken".to_string()));
+
+        let client = Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        );
+
+        let authrequest = Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions: Extensions::new(),
+        };
+
+        let authtoken = authorizer.authorize(authrequest).unwrap();
+        registrar.register_client(client);
+
+        IssSetup {
+            registrar,
+            authorizer,
+            issuer,
+            authtoken,
+        }
+    }
+
+    fn access_request(&self, iss: Option<&str>) -> CraftedRequest {
+        let mut body = vec![
+            ("grant_type", "authorization_code"),
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("code", &self.authtoken),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+        ];
+        body.extend(iss.map(|iss| ("iss", iss)));
+
+        CraftedRequest {
+            query: None,
+            urlbody: Some(body.iter().to_single_value_query()),
+            auth: None,
+        }
+    }
+
+    fn execute(&mut self, extension: IssuerIdentifier, request: CraftedRequest) -> CraftedResponse {
+        let mut addons = AddonList::new();
+        addons.push_access_token(extension);
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        };
+
+        let mut endpoint = Extended::extend_with(endpoint, addons);
+        AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .expect("Expected no flow execution error")
+    }
+
+    fn test_success(&mut self, extension: IssuerIdentifier, request: CraftedRequest) {
+        let response = self.execute(extension, request);
+        assert_eq!(response.status, Status::Ok, "Expected access token in response");
+    }
+
+    fn test_rejected(&mut self, extension: IssuerIdentifier, request: CraftedRequest) {
+        let response = self.execute(extension, request);
+        assert_eq!(response.status, Status::BadRequest, "Expected failed request");
+
+        let body = match response.body {
+            Some(Body::Json(content)) => content,
+            other => panic!("Expected json formated error, got {:?}", other),
+        };
+        let body: serde_json::Value = serde_json::from_str(&body).expect("Body not json encoded");
+        assert_eq!(body["error"], "invalid_request");
+    }
+}
+
+#[test]
+fn iss_correct() {
+    let mut setup = IssSetup::new();
+    let request = setup.access_request(Some(EXAMPLE_ISSUER));
+    setup.test_success(IssuerIdentifier::required(EXAMPLE_ISSUER), request);
+}
+
+#[test]
+fn iss_wrong() {
+    let mut setup = IssSetup::new();
+    let request = setup.access_request(Some("https://mixup.example"));
+    setup.test_rejected(IssuerIdentifier::required(EXAMPLE_ISSUER), request);
+}
+
+#[test]
+fn iss_missing_required() {
+    let mut setup = IssSetup::new();
+    let request = setup.access_request(None);
+    setup.test_rejected(IssuerIdentifier::required(EXAMPLE_ISSUER), request);
+}
+
+#[test]
+fn iss_missing_optional() {
+    let mut setup = IssSetup::new();
+    let request = setup.access_request(None);
+    setup.test_success(IssuerIdentifier::optional(EXAMPLE_ISSUER), request);
+}
+
+#[test]
+fn iss_wrong_optional() {
+    let mut setup = IssSetup::new();
+    let request = setup.access_request(Some("https://mixup.example"));
+    setup.test_rejected(IssuerIdentifier::optional(EXAMPLE_ISSUER), request);
+}
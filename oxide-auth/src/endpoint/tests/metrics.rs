@@ -0,0 +1,141 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::grant::{Extensions, Grant};
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::endpoint::{AccessTokenFlow, MetricCounters, ResourceFlow};
+use crate::frontends::simple::endpoint::{Generic, Metered, Vacant};
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+
+use super::{CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+struct MetricsSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<TestGenerator>,
+    counters: Arc<MetricCounters>,
+    code: String,
+}
+
+impl MetricsSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+        let code = authorizer
+            .authorize(Grant {
+                client_id: EXAMPLE_CLIENT_ID.to_string(),
+                owner_id: EXAMPLE_OWNER_ID.to_string(),
+                redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+                scope: EXAMPLE_SCOPE.parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap();
+
+        MetricsSetup {
+            registrar,
+            authorizer,
+            issuer: TokenMap::new(TestGenerator("AccessToken".to_string())),
+            counters: Arc::new(MetricCounters::new()),
+            code,
+        }
+    }
+
+    fn exchange(&mut self, redirect_uri: &str) -> Status {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("code", &self.code),
+                    ("redirect_uri", redirect_uri),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: None,
+        };
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            solicitor: Vacant,
+            scopes: Vacant,
+            response: Vacant,
+        };
+        let endpoint = Metered::new(endpoint, self.counters.clone());
+        AccessTokenFlow::prepare(endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .expect("Should not error")
+            .status
+    }
+
+    fn access(&mut self, token: &str) -> bool {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("Bearer {}", token)),
+        };
+
+        let scopes: Vec<Scope> = vec![EXAMPLE_SCOPE.parse().unwrap()];
+        let endpoint = Generic {
+            registrar: Vacant,
+            authorizer: Vacant,
+            issuer: &mut self.issuer,
+            solicitor: Vacant,
+            scopes,
+            response: Vacant,
+        };
+        let endpoint = Metered::new(endpoint, self.counters.clone());
+        ResourceFlow::prepare(endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on resource flow."))
+            .execute(request)
+            .is_ok()
+    }
+}
+
+#[test]
+fn metrics_token_issued() {
+    let mut setup = MetricsSetup::new();
+    assert_eq!(setup.exchange(EXAMPLE_REDIRECT_URI), Status::Ok);
+
+    assert_eq!(setup.counters.tokens_issued(), 1);
+    assert_eq!(setup.counters.codes_redeemed(), 1);
+    assert_eq!(setup.counters.codes_issued(), 0);
+}
+
+#[test]
+fn metrics_rejected_exchange() {
+    let mut setup = MetricsSetup::new();
+    // The code was issued for a different redirect uri.
+    assert_eq!(setup.exchange("https://client.example/other"), Status::BadRequest);
+
+    assert_eq!(setup.counters.tokens_issued(), 0);
+    assert_eq!(setup.counters.codes_redeemed(), 0);
+}
+
+#[test]
+fn metrics_resource_denied() {
+    let mut setup = MetricsSetup::new();
+    assert_eq!(setup.exchange(EXAMPLE_REDIRECT_URI), Status::Ok);
+
+    assert!(setup.access("AccessToken"));
+    assert_eq!(setup.counters.resources_denied(), 0);
+
+    assert!(!setup.access("NotAToken"));
+    assert_eq!(setup.counters.resources_denied(), 1);
+}
@@ -4,6 +4,7 @@ use crate::primitives::grant::Grant;
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::time::Duration;
 
 use url::Url;
 
@@ -31,13 +32,24 @@ struct CraftedResponse {
 
     /// Indicates how the client should have authenticated.
     ///
-    /// Only set with `Unauthorized` status.
+    /// Only set with `Unauthorized` or `Forbidden` status.
     pub www_authenticate: Option<String>,
 
     /// Encoded body of the response.
     ///
     /// One variant for each possible encoding type.
     pub body: Option<Body>,
+
+    /// Whether caches must not store the response.
+    pub no_store: bool,
+
+    /// When the client should retry, the `Retry-After` header.
+    ///
+    /// Only set with `ServiceUnavailable` status.
+    pub retry_after: Option<Duration>,
+
+    /// Other headers, set by the creator of the response.
+    pub headers: Vec<(String, String)>,
 }
 
 /// An enum containing the necessary HTTP status codes.
@@ -54,6 +66,15 @@ enum Status {
 
     /// Http status code 401.
     Unauthorized,
+
+    /// Http status code 403.
+    Forbidden,
+
+    /// Http status code 500.
+    InternalServerError,
+
+    /// Http status code 503.
+    ServiceUnavailable,
 }
 
 /// Models the necessary body contents.
@@ -130,6 +151,28 @@ impl WebResponse for CraftedResponse {
         Ok(())
     }
 
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.status = Status::Forbidden;
+        self.location = None;
+        self.www_authenticate = Some(header_value.to_owned());
+        Ok(())
+    }
+
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = Status::InternalServerError;
+        self.location = None;
+        self.www_authenticate = None;
+        Ok(())
+    }
+
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.status = Status::ServiceUnavailable;
+        self.location = None;
+        self.www_authenticate = None;
+        self.retry_after = retry_after;
+        Ok(())
+    }
+
     /// A pure text response with no special media type set.
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.body = Some(Body::Text(text.to_owned()));
@@ -141,6 +184,11 @@ impl WebResponse for CraftedResponse {
         self.body = Some(Body::Json(data.to_owned()));
         Ok(())
     }
+
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.no_store = true;
+        Ok(())
+    }
 }
 
 struct TestGenerator(String);
@@ -186,6 +234,109 @@ impl<'l> OwnerSolicitor<CraftedRequest> for &'l Deny {
     }
 }
 
+/// Wraps an endpoint and records what its flows report about errors.
+///
+/// The hooks are those of the wrapped endpoint, usually configured with `Generic::with_hooks`.
+struct Observed<E> {
+    inner: E,
+    failures: Vec<(FlowError, String)>,
+}
+
+impl<E> Observed<E> {
+    fn new(inner: E) -> Self {
+        Observed {
+            inner,
+            failures: Vec::new(),
+        }
+    }
+
+    /// The causes of the reported failures, without their details.
+    fn causes(&self) -> Vec<FlowError> {
+        self.failures.iter().map(|(cause, _)| *cause).collect()
+    }
+}
+
+impl<W: WebRequest, E: Endpoint<W>> Endpoint<W> for Observed<E> {
+    type Error = E::Error;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.inner.registrar()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.inner.authorizer_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.inner.issuer_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
+        self.inner.owner_solicitor()
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
+        self.inner.scopes()
+    }
+
+    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
+        self.inner.response(request, kind)
+    }
+
+    fn error(&mut self, err: OAuthError) -> Self::Error {
+        self.inner.error(err)
+    }
+
+    fn web_error(&mut self, err: W::Error) -> Self::Error {
+        self.inner.web_error(err)
+    }
+
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        self.inner.extension()
+    }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        self.inner.session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<W>> {
+        self.inner.proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.inner.scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.inner.on_flow_error(error, detail);
+        self.failures.push((error, detail.to_string()));
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.inner.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        self.inner.temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.inner.parameter_limit()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        self.inner.consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        self.inner.metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        self.inner.clock()
+    }
+}
+
 trait ToSingleValueQuery {
     fn to_single_value_query(self) -> HashMap<String, Vec<String>>;
 }
@@ -222,3 +373,22 @@ mod client_credentials;
 mod resource;
 mod refresh;
 mod pkce;
+mod iss;
+mod audience;
+mod addons;
+mod snapshot;
+mod builder;
+mod opaque_errors;
+mod error_mapping;
+mod flow_error;
+mod implicit;
+mod parameter_limit;
+mod discovery;
+mod consent;
+mod csrf;
+mod metrics;
+#[cfg(feature = "tracing")]
+mod telemetry;
+#[cfg(feature = "ed25519")]
+mod dpop;
+mod mtls;
@@ -0,0 +1,213 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::binding::Binding;
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::grant::{Extensions, Grant};
+use crate::primitives::issuer::{Issuer, TokenMap};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::endpoint::{AccessTokenFlow, QueryParameter, ResourceFlow, WebRequest};
+use crate::frontends::simple::endpoint::{Generic, Hooked, Hooks, Vacant};
+use crate::frontends::simple::extensions::{AddonList, CertificateBinding, Extended};
+
+use std::borrow::Cow;
+
+use chrono::{Duration, Utc};
+
+use super::{Body, CraftedError, CraftedRequest, CraftedResponse, Status, ToSingleValueQuery};
+use super::defaults::*;
+
+const CERTIFICATE: &[u8] = b"DER encoded client certificate";
+const OTHER_CERTIFICATE: &[u8] = b"DER encoded certificate of another client";
+
+/// A request on a connection that may be authenticated with a client certificate.
+#[derive(Debug)]
+struct TlsRequest {
+    inner: CraftedRequest,
+    certificate: Option<&'static [u8]>,
+}
+
+impl WebRequest for TlsRequest {
+    type Response = CraftedResponse;
+    type Error = CraftedError;
+
+    fn query(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.inner.query()
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.inner.urlbody()
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+        self.inner.authheader()
+    }
+
+    fn client_certificate(&mut self) -> Result<Option<Cow<[u8]>>, Self::Error> {
+        Ok(self.certificate.map(Cow::Borrowed))
+    }
+}
+
+fn thumbprint(certificate: &[u8]) -> String {
+    match Binding::certificate(certificate) {
+        Binding::Certificate { x5t_s256 } => x5t_s256,
+        other => panic!("Expected a certificate binding, got {:?}", other),
+    }
+}
+
+/// Redeem a code on a connection with the certificate, returning the token response.
+fn redeem(
+    issuer: &mut TokenMap<RandomGenerator>, certificate: Option<&'static [u8]>,
+) -> CraftedResponse {
+    let mut registrar = ClientMap::new();
+    registrar.register_client(
+        Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        )
+        .with_certificate_thumbprint(thumbprint(CERTIFICATE)),
+    );
+
+    let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    let code = authorizer
+        .authorize(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        })
+        .unwrap();
+
+    let mut addons = AddonList::new();
+    addons.push_access_token(CertificateBinding::required());
+    let mut endpoint = Extended::extend_with(
+        Generic {
+            registrar: &registrar,
+            authorizer: &mut authorizer,
+            issuer: &mut *issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        },
+        addons,
+    );
+
+    let request = TlsRequest {
+        inner: CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("code", &code),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: None,
+        },
+        certificate,
+    };
+
+    AccessTokenFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+        .execute(request)
+        .expect("Expected non-error response")
+}
+
+/// Redeem a code with the registered certificate, returning the issued access token.
+fn bound_token(issuer: &mut TokenMap<RandomGenerator>) -> String {
+    let response = redeem(issuer, Some(CERTIFICATE));
+    assert_eq!(response.status, Status::Ok);
+
+    let body: serde_json::Value = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).unwrap(),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    // Certificate bound tokens remain bearer tokens.
+    assert_eq!(body["token_type"], "bearer");
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+type BoundEndpoint<'a> = Hooked<
+    Generic<Vacant, Vacant, &'a mut dyn Issuer, Vacant, &'a [Scope], Vacant>,
+    Hooks<Vacant, CertificateBinding>,
+>;
+
+fn bound_flow<'a>(
+    issuer: &'a mut dyn Issuer, scopes: &'a [Scope],
+) -> ResourceFlow<BoundEndpoint<'a>, TlsRequest> {
+    let endpoint = Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer,
+        scopes,
+        solicitor: Vacant,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().proof_verifier(CertificateBinding::required()));
+
+    ResourceFlow::prepare(endpoint).unwrap_or_else(|_| panic!("Endpoint has issuer and scopes"))
+}
+
+fn resource_request(token: &str, certificate: Option<&'static [u8]>) -> TlsRequest {
+    TlsRequest {
+        inner: CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("Bearer {}", token)),
+        },
+        certificate,
+    }
+}
+
+#[test]
+fn mtls_bound_token() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let token = bound_token(&mut issuer);
+
+    let grant = issuer.recover_token(&token).unwrap().unwrap();
+    assert_eq!(
+        Binding::of(&grant),
+        Some(Binding::Certificate {
+            x5t_s256: thumbprint(CERTIFICATE)
+        })
+    );
+
+    let scopes = [EXAMPLE_SCOPE.parse().unwrap()];
+    let mut flow = bound_flow(&mut issuer, &scopes);
+    flow.execute(resource_request(&token, Some(CERTIFICATE)))
+        .expect("Should allow access");
+}
+
+#[test]
+fn mtls_certificate_mismatch() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let token = bound_token(&mut issuer);
+
+    let scopes = [EXAMPLE_SCOPE.parse().unwrap()];
+    let mut flow = bound_flow(&mut issuer, &scopes);
+    for certificate in [Some(OTHER_CERTIFICATE), None] {
+        match flow.execute(resource_request(&token, certificate)) {
+            Err(Ok(response)) => assert_eq!(response.status, Status::Unauthorized),
+            other => panic!("Expected an error response instead of {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn mtls_client_authentication() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+
+    // The client registered a certificate, so it can not authenticate with another one.
+    let response = redeem(&mut issuer, Some(OTHER_CERTIFICATE));
+    assert_eq!(response.status, Status::Unauthorized);
+
+    // Nor without any credentials at all.
+    let response = redeem(&mut issuer, None);
+    assert_eq!(response.status, Status::Unauthorized);
+}
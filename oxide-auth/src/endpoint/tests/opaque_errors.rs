@@ -0,0 +1,286 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::issuer::{IssuedToken, Issuer, RefreshedToken};
+use crate::primitives::grant::{Grant, Extensions};
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+use crate::primitives::scope::Scope;
+
+use crate::endpoint::{AccessTokenFlow, FlowError, OAuthError, ResourceFlow};
+use crate::frontends::simple::endpoint::{Error, Generic, Hooks, Vacant};
+
+use chrono::{Utc, Duration};
+use serde_json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time;
+
+use super::{Body, CraftedRequest, CraftedResponse, Observed, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+/// An issuer whose backing storage is unavailable.
+struct FailingIssuer;
+
+impl Issuer for FailingIssuer {
+    fn issue(&mut self, _: Grant) -> Result<IssuedToken, ()> {
+        Err(())
+    }
+
+    fn refresh(&mut self, _: &str, _: Grant) -> Result<RefreshedToken, ()> {
+        Err(())
+    }
+
+    fn recover_token<'a>(&'a self, _: &'a str) -> Result<Option<Grant>, ()> {
+        Err(())
+    }
+
+    fn recover_refresh<'a>(&'a self, _: &'a str) -> Result<Option<Grant>, ()> {
+        Err(())
+    }
+}
+
+/// The errors reported to the hook of an endpoint.
+type Logged = Vec<(FlowError, String)>;
+
+struct OpaqueSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: FailingIssuer,
+    authtoken: String,
+}
+
+impl OpaqueSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        let authtoken = authorizer
+            .authorize(Grant {
+                client_id: EXAMPLE_CLIENT_ID.to_string(),
+                owner_id: EXAMPLE_OWNER_ID.to_string(),
+                redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+                scope: EXAMPLE_SCOPE.parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap();
+
+        OpaqueSetup {
+            registrar,
+            authorizer,
+            issuer: FailingIssuer,
+            authtoken,
+        }
+    }
+
+    fn access_token(
+        &mut self, opaque: bool, unavailable: Option<time::Duration>,
+    ) -> (Result<CraftedResponse, Error<CraftedRequest>>, Logged) {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("code", &self.authtoken),
+                    ("redirect_uri", EXAMPLE_REDIRECT_URI),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: None,
+        };
+
+        let mut hooks = Hooks::new().opaque_errors(opaque);
+        if let Some(retry_after) = unavailable {
+            hooks = hooks.temporarily_unavailable(move || Some(retry_after));
+        }
+
+        let mut endpoint = Observed::new(
+            Generic {
+                registrar: &self.registrar,
+                authorizer: &mut self.authorizer,
+                issuer: &mut self.issuer,
+                scopes: Vacant,
+                solicitor: Vacant,
+                response: Vacant,
+            }
+            .with_hooks(hooks),
+        );
+
+        let result = AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request);
+        (result, endpoint.failures)
+    }
+}
+
+fn assert_opaque_body(response: &CraftedResponse) {
+    assert_eq!(response.status, Status::InternalServerError);
+    let body = match &response.body {
+        Some(Body::Json(content)) => content,
+        other => panic!("Expected json formated error, got {:?}", other),
+    };
+    let body: serde_json::Value = serde_json::from_str(body).expect("Body not json encoded");
+    assert_eq!(body, serde_json::json!({ "error": "server_error" }));
+}
+
+#[test]
+fn opaque_primitive_error() {
+    let mut setup = OpaqueSetup::new();
+    let (result, logged) = setup.access_token(true, None);
+
+    let response = result.unwrap_or_else(|_| panic!("Expected a generic error response"));
+    assert_opaque_body(&response);
+    assert_eq!(
+        logged,
+        vec![(
+            FlowError::Primitive,
+            "access token: registrar, authorizer or issuer failed".to_owned()
+        )]
+    );
+}
+
+#[test]
+fn transparent_primitive_error() {
+    let mut setup = OpaqueSetup::new();
+    let (result, logged) = setup.access_token(false, None);
+
+    match result {
+        Err(Error::OAuth(OAuthError::PrimitiveError)) => (),
+        Err(Error::Web(err)) => panic!("Expected primitive error, got web error {:?}", err),
+        Err(Error::OAuth(err)) => panic!("Expected primitive error, got {:?}", err),
+        Ok(response) => panic!("Expected flow to fail, got {:?}", response),
+    }
+    // The hook observes the failure independent of the response mode.
+    assert_eq!(logged.len(), 1);
+}
+
+#[test]
+fn opaque_resource_error() {
+    let mut issuer = FailingIssuer;
+    let mut scopes = ["needed".parse::<Scope>().unwrap()];
+    let mut endpoint = Observed::new(
+        Generic {
+            registrar: Vacant,
+            authorizer: Vacant,
+            issuer: &mut issuer,
+            scopes: &mut scopes[..],
+            solicitor: Vacant,
+            response: Vacant,
+        }
+        .with_hooks(Hooks::new().opaque_errors(true)),
+    );
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer AccessToken".to_owned()),
+    };
+
+    let response = match ResourceFlow::prepare(&mut endpoint)
+        .unwrap_or_else(|_| panic!("Not violating any requirements on resource flow."))
+        .execute(request)
+    {
+        Err(Ok(response)) => response,
+        Err(Err(_)) => panic!("Expected a generic error response"),
+        Ok(grant) => panic!("Expected the request to be denied, got {:?}", grant),
+    };
+    assert_opaque_body(&response);
+    assert_eq!(
+        endpoint.failures,
+        vec![(FlowError::Primitive, "resource: issuer failed".to_owned())]
+    );
+}
+
+#[test]
+fn opaque_transient_error() {
+    let retry_after = time::Duration::from_secs(30);
+    let mut setup = OpaqueSetup::new();
+    let (result, logged) = setup.access_token(true, Some(retry_after));
+
+    let response = result.unwrap_or_else(|_| panic!("Expected a generic error response"));
+    assert_eq!(response.status, Status::ServiceUnavailable);
+    assert_eq!(response.retry_after, Some(retry_after));
+    let body = match &response.body {
+        Some(Body::Json(content)) => content,
+        other => panic!("Expected json formated error, got {:?}", other),
+    };
+    let body: serde_json::Value = serde_json::from_str(body).expect("Body not json encoded");
+    assert_eq!(body, serde_json::json!({ "error": "temporarily_unavailable" }));
+    assert_eq!(
+        logged,
+        vec![(
+            FlowError::Primitive,
+            "access token: registrar, authorizer or issuer failed".to_owned()
+        )]
+    );
+}
+
+#[test]
+fn transparent_transient_error() {
+    let retry_after = time::Duration::from_secs(30);
+    let mut setup = OpaqueSetup::new();
+    let (result, _) = setup.access_token(false, Some(retry_after));
+
+    match result {
+        Err(Error::OAuth(OAuthError::TemporarilyUnavailable(duration))) => {
+            assert_eq!(duration, retry_after)
+        }
+        Err(Error::Web(err)) => panic!("Expected transient error, got web error {:?}", err),
+        Err(Error::OAuth(err)) => panic!("Expected transient error, got {:?}", err),
+        Ok(response) => panic!("Expected flow to fail, got {:?}", response),
+    }
+}
+
+#[test]
+fn hooked_transient_error() {
+    let connected = Arc::new(AtomicBool::new(false));
+    let check = connected.clone();
+    let mut issuer = FailingIssuer;
+    let mut scopes = ["needed".parse::<Scope>().unwrap()];
+    let mut endpoint = Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer: &mut issuer,
+        scopes: &mut scopes[..],
+        solicitor: Vacant,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().opaque_errors(true).temporarily_unavailable(move || {
+        if check.load(Ordering::SeqCst) {
+            None
+        } else {
+            Some(time::Duration::from_secs(30))
+        }
+    }));
+
+    let mut resource = || {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some("Bearer AccessToken".to_owned()),
+        };
+        match ResourceFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on resource flow."))
+            .execute(request)
+        {
+            Err(Ok(response)) => response,
+            Err(Err(_)) => panic!("Expected a generic error response"),
+            Ok(grant) => panic!("Expected the request to be denied, got {:?}", grant),
+        }
+    };
+
+    // While the storage is unreachable the failure is transient.
+    let response = resource();
+    assert_eq!(response.status, Status::ServiceUnavailable);
+    assert_eq!(response.retry_after, Some(time::Duration::from_secs(30)));
+
+    // Once connected again, the same failure is permanent.
+    connected.store(true, Ordering::SeqCst);
+    assert_opaque_body(&resource());
+}
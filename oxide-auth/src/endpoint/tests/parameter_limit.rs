@@ -0,0 +1,136 @@
+use crate::primitives::authorizer::AuthMap;
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::endpoint::{AccessTokenFlow, AuthorizationFlow, OAuthError};
+use crate::frontends::simple::endpoint::{Error, Generic, Hooks, Vacant};
+
+use std::collections::HashMap;
+
+use serde_json;
+
+use super::{Allow, Body, CraftedRequest, CraftedResponse, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+/// The number of parameters of a regular request in these tests.
+const LIMIT: usize = 4;
+
+struct LimitSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<RandomGenerator>,
+}
+
+impl LimitSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        LimitSetup {
+            registrar,
+            authorizer: AuthMap::new(TestGenerator("AuthToken".to_string())),
+            issuer: TokenMap::new(RandomGenerator::new(16)),
+        }
+    }
+
+    /// The parameters with `padding` additional unknown parameters.
+    fn padded(parameters: &[(&str, &str)], padding: usize) -> HashMap<String, Vec<String>> {
+        let mut padded = parameters.iter().to_single_value_query();
+        for i in 0..padding {
+            padded.insert(format!("padding{}", i), vec!["x".to_string()]);
+        }
+        padded
+    }
+
+    fn authorize(&mut self, padding: usize) -> Result<CraftedResponse, Error<CraftedRequest>> {
+        let query = [
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ("response_type", "code"),
+        ];
+        let request = CraftedRequest {
+            query: Some(Self::padded(&query, padding)),
+            urlbody: None,
+            auth: None,
+        };
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: Vacant,
+            scopes: Vacant,
+            solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+            response: Vacant,
+        }
+        .with_hooks(Hooks::new().parameter_limit(LIMIT));
+        AuthorizationFlow::prepare(endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+            .execute(request)
+    }
+
+    fn token(&mut self, padding: usize) -> CraftedResponse {
+        let body = [
+            ("grant_type", "authorization_code"),
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("code", "AuthToken"),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+        ];
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(Self::padded(&body, padding)),
+            auth: None,
+        };
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        }
+        .with_hooks(Hooks::new().parameter_limit(LIMIT));
+        AccessTokenFlow::prepare(endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .expect("Expected no flow execution error")
+    }
+}
+
+#[test]
+fn authorization_parameter_limit() {
+    let mut setup = LimitSetup::new();
+
+    match setup.authorize(LIMIT) {
+        Err(Error::OAuth(OAuthError::DenySilently)) => (),
+        other => panic!("Expected the request to be denied, got {:?}", other),
+    }
+
+    let response = setup.authorize(LIMIT - 3).expect("Expected a response");
+    assert_eq!(response.status, Status::Redirect);
+    let location = response.location.expect("Expected a redirect location");
+    assert!(location.query_pairs().any(|(key, _)| key == "code"));
+}
+
+#[test]
+fn token_parameter_limit() {
+    let mut setup = LimitSetup::new();
+    setup.authorize(0).expect("Expected a response");
+
+    let response = setup.token(1);
+    assert_eq!(response.status, Status::BadRequest);
+    let body: HashMap<String, String> = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    assert_eq!(body.get("error").map(String::as_str), Some("invalid_request"));
+
+    // The rejected request did not consume the code.
+    let response = setup.token(0);
+    assert_eq!(response.status, Status::Ok);
+}
@@ -1,6 +1,7 @@
-use crate::primitives::authorizer::AuthMap;
-use crate::primitives::issuer::TokenMap;
-use crate::primitives::generator::RandomGenerator;
+use crate::primitives::authorizer::{AuthMap, Authorizer, CodeSigner};
+use crate::primitives::grant::{Extensions, Grant};
+use crate::primitives::issuer::{Issuer, TokenMap, TokenSigner};
+use crate::primitives::generator::{Assertion, AssertionKind, RandomGenerator};
 use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
 
 use crate::code_grant::accesstoken::TokenResponse;
@@ -11,8 +12,11 @@ use crate::frontends::simple::endpoint::{Generic, Error, Vacant};
 use super::{Allow, Body, CraftedResponse, CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
 use super::defaults::*;
 
+use chrono::{Duration, Utc};
 use serde_json;
 
+const CONFIDENTIAL_CLIENT_ID: &str = "ConfidentialClient";
+
 struct PkceSetup {
     registrar: ClientMap,
     authorizer: AuthMap<TestGenerator>,
@@ -32,6 +36,12 @@ impl PkceSetup {
 
         let mut registrar = ClientMap::new();
         registrar.register_client(client);
+        registrar.register_client(Client::confidential(
+            CONFIDENTIAL_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        ));
 
         let token = "ExampleAuthorizationToken".to_string();
         let authorizer = AuthMap::new(TestGenerator(token.clone()));
@@ -123,6 +133,45 @@ impl PkceSetup {
         }
     }
 
+    /// Authorize with only optional PKCE in the extension but required for public clients.
+    fn authorize_public_pkce(&mut self, request: CraftedRequest, s256: bool) -> CraftedResponse {
+        let mut extensions = AddonList::new();
+        extensions.push_code(Pkce::optional());
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, extensions);
+
+        let mut flow = AuthorizationFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."));
+        if s256 {
+            flow.require_pkce_s256();
+        } else {
+            flow.require_pkce_for_public_clients();
+        }
+        flow.execute(request)
+            .unwrap_or_else(|_| panic!("Expected no flow execution error"))
+    }
+
+    fn assert_invalid_request(response: CraftedResponse, description: &str) {
+        assert_eq!(response.status, Status::Redirect, "Expected redirect to client");
+        let location = response.location.unwrap();
+        let param = |name: &str| {
+            location
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        assert_eq!(param("error").as_deref(), Some("invalid_request"));
+        assert!(location.query_pairs().any(|(_, value)| value == description));
+    }
+
     fn assert_nonerror_redirect(response: CraftedResponse) {
         assert_eq!(response.status, Status::Redirect, "Expected redirect to client");
         assert!(response.location.unwrap().as_str().find("error").is_none());
@@ -215,3 +264,369 @@ fn pkce_failed_verifier() {
 
     setup.test_failed_verification(correct_authorization, correct_access);
 }
+
+fn authorization_request(client_id: &str, challenge: &[(&str, &str)]) -> CraftedRequest {
+    let mut query = vec![
+        ("client_id", client_id),
+        ("redirect_uri", EXAMPLE_REDIRECT_URI),
+        ("response_type", "code"),
+    ];
+    query.extend_from_slice(challenge);
+
+    CraftedRequest {
+        query: Some(query.iter().to_single_value_query()),
+        urlbody: None,
+        auth: None,
+    }
+}
+
+#[test]
+fn pkce_public_client_missing_challenge() {
+    let mut setup = PkceSetup::new();
+
+    let request = authorization_request(EXAMPLE_CLIENT_ID, &[]);
+    PkceSetup::assert_invalid_request(
+        setup.authorize_public_pkce(request, false),
+        "code_challenge is required",
+    );
+
+    let request = authorization_request(EXAMPLE_CLIENT_ID, &[]);
+    PkceSetup::assert_invalid_request(
+        setup.authorize_public_pkce(request, true),
+        "code_challenge is required",
+    );
+}
+
+#[test]
+fn pkce_public_client_plain_rejected() {
+    let mut setup = PkceSetup::new();
+    let challenge = setup.verifier.clone();
+
+    let plain = [
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "plain"),
+    ];
+    let request = authorization_request(EXAMPLE_CLIENT_ID, &plain);
+    PkceSetup::assert_invalid_request(
+        setup.authorize_public_pkce(request, true),
+        "code_challenge_method must be S256",
+    );
+
+    // Without a method the challenge is `plain` as well.
+    let implicit = [("code_challenge", challenge.as_str())];
+    let request = authorization_request(EXAMPLE_CLIENT_ID, &implicit);
+    PkceSetup::assert_invalid_request(
+        setup.authorize_public_pkce(request, true),
+        "code_challenge_method must be S256",
+    );
+}
+
+#[test]
+fn pkce_public_client_s256_accepted() {
+    let mut setup = PkceSetup::new();
+    let challenge = setup.sha256_challenge.clone();
+
+    let s256 = [
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    let request = authorization_request(EXAMPLE_CLIENT_ID, &s256);
+    PkceSetup::assert_nonerror_redirect(setup.authorize_public_pkce(request, true));
+}
+
+#[test]
+fn pkce_confidential_client_unaffected() {
+    let mut setup = PkceSetup::new();
+
+    let request = authorization_request(CONFIDENTIAL_CLIENT_ID, &[]);
+    PkceSetup::assert_nonerror_redirect(setup.authorize_public_pkce(request, true));
+}
+
+impl PkceSetup {
+    /// Redeem the code with a flow requiring verifiers for stored challenges.
+    ///
+    /// The endpoint may lack the `Pkce` extension, which then can not verify the challenge.
+    fn redeem_requiring_verifier(
+        &mut self, verifier: Option<&str>, with_extension: bool,
+    ) -> (Status, TokenResponse) {
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("code", &self.auth_token),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+        ];
+        params.extend(verifier.map(|verifier| ("code_verifier", verifier)));
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(params.iter().to_single_value_query()),
+            auth: None,
+        };
+
+        let mut extensions = AddonList::new();
+        if with_extension {
+            extensions.push_code(Pkce::optional());
+        }
+
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, extensions);
+
+        let mut flow = AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."));
+        flow.require_pkce_verifier();
+        let response = flow
+            .execute(request)
+            .unwrap_or_else(|_| panic!("Expected no flow execution error"));
+        (response.status, Self::json_response(response.body))
+    }
+}
+
+#[test]
+fn pkce_required_verifier_missing() {
+    let mut setup = PkceSetup::new();
+    let challenge = setup.sha256_challenge.clone();
+    let s256 = [
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+
+    // Also rejected when the endpoint forgot to verify challenges with the extension.
+    for &with_extension in &[false, true] {
+        let request = authorization_request(EXAMPLE_CLIENT_ID, &s256);
+        PkceSetup::assert_nonerror_redirect(setup.authorize_public_pkce(request, true));
+
+        let (status, body) = setup.redeem_requiring_verifier(None, with_extension);
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body.error.as_deref(), Some("invalid_grant"));
+        assert!(body.access_token.is_none());
+    }
+}
+
+#[test]
+fn pkce_required_verifier_correct() {
+    let mut setup = PkceSetup::new();
+    let challenge = setup.sha256_challenge.clone();
+    let verifier = setup.verifier.clone();
+    let s256 = [
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+
+    let request = authorization_request(EXAMPLE_CLIENT_ID, &s256);
+    PkceSetup::assert_nonerror_redirect(setup.authorize_public_pkce(request, true));
+
+    let (status, body) = setup.redeem_requiring_verifier(Some(&verifier), true);
+    assert_eq!(status, Status::Ok);
+    assert!(body.error.is_none());
+    assert!(body.access_token.is_some());
+}
+
+#[test]
+fn pkce_required_verifier_without_challenge() {
+    let mut setup = PkceSetup::new();
+
+    setup
+        .authorizer
+        .authorize(Grant {
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            until: Utc::now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        })
+        .unwrap();
+
+    // Only codes with a stored challenge need a verifier.
+    let (status, body) = setup.redeem_requiring_verifier(None, false);
+    assert_eq!(status, Status::Ok);
+    assert!(body.access_token.is_some());
+}
+
+const CODE_KEY: &[u8] = b"stateless-code-key-0123456789abc";
+const TOKEN_KEY: &[u8] = b"stateless-token-key-0123456789ab";
+
+/// An instance of a stateless authorization server, sharing only its keys with other instances.
+struct StatelessInstance {
+    authorizer: CodeSigner,
+    issuer: TokenSigner,
+}
+
+impl StatelessInstance {
+    fn new() -> Self {
+        StatelessInstance {
+            authorizer: CodeSigner::new(Assertion::new(AssertionKind::HmacSha256, CODE_KEY))
+                .embed_pkce(),
+            issuer: TokenSigner::new(Assertion::new(AssertionKind::HmacSha256, TOKEN_KEY)),
+        }
+    }
+
+    fn authorize(&mut self, registrar: &ClientMap, challenge: &[(&str, &str)]) -> CraftedResponse {
+        let mut extensions = AddonList::new();
+        extensions.push_code(Pkce::required());
+
+        let endpoint = Generic {
+            registrar,
+            authorizer: &mut self.authorizer,
+            issuer: Vacant,
+            scopes: Vacant,
+            solicitor: Allow(EXAMPLE_OWNER_ID.to_string()),
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, extensions);
+
+        AuthorizationFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on authorization flow."))
+            .execute(authorization_request(EXAMPLE_CLIENT_ID, challenge))
+            .unwrap_or_else(|_| panic!("Expected no flow execution error"))
+    }
+
+    fn redeem(&mut self, registrar: &ClientMap, code: &str, verifier: &str) -> (Status, TokenResponse) {
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", EXAMPLE_CLIENT_ID),
+            ("code", code),
+            ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ("code_verifier", verifier),
+        ];
+        let request = CraftedRequest {
+            query: None,
+            urlbody: Some(params.iter().to_single_value_query()),
+            auth: None,
+        };
+
+        let mut extensions = AddonList::new();
+        extensions.push_code(Pkce::required());
+
+        let endpoint = Generic {
+            registrar,
+            authorizer: &mut self.authorizer,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        };
+        let mut endpoint = Extended::extend_with(endpoint, extensions);
+
+        let response = AccessTokenFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on access token flow."))
+            .execute(request)
+            .unwrap_or_else(|_| panic!("Expected no flow execution error"));
+        (response.status, PkceSetup::json_response(response.body))
+    }
+}
+
+fn redirected_code(response: CraftedResponse) -> String {
+    assert_eq!(response.status, Status::Redirect, "Expected redirect to client");
+    let location = response.location.unwrap();
+    assert!(location.as_str().find("error").is_none(), "{}", location);
+    location
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .map(|(_, value)| value.into_owned())
+        .expect("Expected a code in the redirect")
+}
+
+#[test]
+fn pkce_stateless_signed_code() {
+    let setup = PkceSetup::new();
+    let s256 = [
+        ("code_challenge", setup.sha256_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+
+    // Authorization, token and resource requests are each served by a different instance.
+    let code = redirected_code(StatelessInstance::new().authorize(&setup.registrar, &s256));
+    let (status, body) = StatelessInstance::new().redeem(&setup.registrar, &code, &setup.verifier);
+    assert_eq!(status, Status::Ok);
+    let token = body.access_token.expect("Expected an access token");
+
+    let grant = StatelessInstance::new()
+        .issuer
+        .recover_token(&token)
+        .unwrap()
+        .expect("Expected the signed token to be valid");
+    assert_eq!(grant.client_id, EXAMPLE_CLIENT_ID);
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+}
+
+#[test]
+fn pkce_stateless_signed_code_verified() {
+    let setup = PkceSetup::new();
+    let s256 = [
+        ("code_challenge", setup.sha256_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+
+    let code = redirected_code(StatelessInstance::new().authorize(&setup.registrar, &s256));
+    let (status, body) =
+        StatelessInstance::new().redeem(&setup.registrar, &code, "Notthecorrectverifier");
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body.error.as_deref(), Some("invalid_request"));
+    assert!(body.access_token.is_none());
+}
+
+#[test]
+fn pkce_stateless_signed_code_tampered() {
+    let setup = PkceSetup::new();
+    let s256 = [
+        ("code_challenge", setup.sha256_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    let code = redirected_code(StatelessInstance::new().authorize(&setup.registrar, &s256));
+
+    // Change a single character anywhere in the encoded content, including the embedded challenge.
+    for position in 0..code.trim_end_matches('=').len() {
+        let mut tampered = code.clone().into_bytes();
+        tampered[position] = if tampered[position] == b'A' { b'B' } else { b'A' };
+        let tampered = String::from_utf8(tampered).unwrap();
+
+        let (status, body) =
+            StatelessInstance::new().redeem(&setup.registrar, &tampered, &setup.verifier);
+        assert_eq!(
+            status,
+            Status::BadRequest,
+            "Accepted code tampered at {}",
+            position
+        );
+        assert!(body.access_token.is_none());
+    }
+
+    // A code signed with another key is rejected as well.
+    let mut other = StatelessInstance::new();
+    other.authorizer = CodeSigner::ephemeral().embed_pkce();
+    let code = redirected_code(other.authorize(&setup.registrar, &s256));
+    let (status, _) = StatelessInstance::new().redeem(&setup.registrar, &code, &setup.verifier);
+    assert_eq!(status, Status::BadRequest);
+}
+
+#[test]
+fn pkce_stateless_plain_not_embedded() {
+    let setup = PkceSetup::new();
+    let mut pkce = Pkce::required();
+    pkce.allow_plain();
+    let challenge = pkce
+        .challenge(Some("plain".into()), Some(setup.verifier.as_str().into()))
+        .unwrap()
+        .unwrap();
+
+    let mut extensions = Extensions::new();
+    extensions.set(&pkce, challenge);
+    let grant = Grant {
+        owner_id: EXAMPLE_OWNER_ID.to_string(),
+        client_id: EXAMPLE_CLIENT_ID.to_string(),
+        scope: EXAMPLE_SCOPE.parse().unwrap(),
+        redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+        until: Utc::now() + Duration::minutes(10),
+        extensions,
+    };
+
+    // The client could read the verifier from the code.
+    assert!(StatelessInstance::new().authorizer.authorize(grant).is_err());
+}
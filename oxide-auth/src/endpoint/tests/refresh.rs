@@ -1,6 +1,6 @@
 use crate::primitives::issuer::{Issuer, IssuedToken, RefreshedToken, TokenMap, TokenType};
 use crate::primitives::generator::RandomGenerator;
-use crate::primitives::grant::{Grant, Extensions};
+use crate::primitives::grant::{Grant, GrantExtension, Extensions, Value};
 use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
 
 use std::collections::HashMap;
@@ -13,13 +13,14 @@ use serde_json;
 use super::{Body, CraftedRequest, CraftedResponse, Status, ToSingleValueQuery};
 use super::defaults::*;
 use crate::code_grant::accesstoken::TokenResponse;
-use crate::frontends::simple::endpoint::{refresh_flow, resource_flow};
+use crate::endpoint::RefreshFlow;
+use crate::frontends::simple::endpoint::{refresh_flow, resource_flow, Generic, Vacant};
+use crate::frontends::simple::extensions::{AddonList, AddonResult, Extended, RefreshAddon, RefreshRequest};
 
 struct RefreshTokenSetup {
     registrar: ClientMap,
     issuer: TokenMap<RandomGenerator>,
-    /// The original issued token. Unused atm.
-    #[allow(unused)]
+    /// The original issued token.
     issued: IssuedToken,
     /// The extract refresh token.
     refresh_token: String,
@@ -29,6 +30,10 @@ struct RefreshTokenSetup {
 
 impl RefreshTokenSetup {
     fn private_client() -> Self {
+        Self::private_client_with(Extensions::new())
+    }
+
+    fn private_client_with(extensions: Extensions) -> Self {
         let mut registrar = ClientMap::new();
         let mut issuer = TokenMap::new(RandomGenerator::new(16));
 
@@ -45,7 +50,7 @@ impl RefreshTokenSetup {
             redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
             scope: EXAMPLE_SCOPE.parse().unwrap(),
             until: Utc::now() + Duration::hours(1),
-            extensions: Extensions::new(),
+            extensions,
         };
 
         registrar.register_client(client);
@@ -106,6 +111,7 @@ impl RefreshTokenSetup {
             .execute(request)
             .expect("Expected non-failed reponse");
         assert_eq!(response.status, Status::Ok);
+        assert!(response.no_store, "Token responses must not be cached");
         let body = match response.body {
             Some(Body::Json(body)) => body,
             _ => panic!("Expect json body"),
@@ -117,6 +123,7 @@ impl RefreshTokenSetup {
             refresh: body.refresh_token,
             until: Utc::now() + Duration::seconds(duration),
             token_type: TokenType::Bearer,
+            refresh_until: None,
         }
     }
 
@@ -172,6 +179,23 @@ impl RefreshTokenSetup {
         self.assert_only_error(body);
     }
 
+    fn refresh_with_addons(&mut self, addons: AddonList, request: CraftedRequest) -> CraftedResponse {
+        let endpoint = Generic {
+            registrar: &self.registrar,
+            authorizer: Vacant,
+            issuer: &mut self.issuer,
+            scopes: Vacant,
+            solicitor: Vacant,
+            response: Vacant,
+        };
+
+        let mut endpoint = Extended::extend_with(endpoint, addons);
+        RefreshFlow::prepare(&mut endpoint)
+            .unwrap_or_else(|_| panic!("Not violating any requirements on refresh flow."))
+            .execute(request)
+            .expect("Expected no flow execution error")
+    }
+
     fn assert_json_body(&mut self, response: &CraftedResponse) -> HashMap<String, String> {
         let body = match &response.body {
             Some(Body::Json(body)) => body,
@@ -200,6 +224,82 @@ impl RefreshTokenSetup {
             .execute(request)
             .expect("Expected access allowed");
     }
+
+    fn assert_access_denied(&mut self, token: String, status: Status) {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("Bearer {}", token)),
+        };
+
+        match resource_flow(&mut self.issuer, &[EXAMPLE_SCOPE.parse().unwrap()]).execute(request) {
+            Err(Ok(response)) => assert_eq!(response.status, status),
+            Ok(_) => panic!("Expected access denied"),
+            Err(Err(err)) => panic!("Expected a response, got {:?}", err),
+        }
+    }
+
+    fn refresh_request(&self, refresh_token: &str) -> CraftedRequest {
+        CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [("grant_type", "refresh_token"), ("refresh_token", refresh_token)]
+                    .iter()
+                    .to_single_value_query(),
+            ),
+            auth: Some(self.basic_authorization.clone()),
+        }
+    }
+
+    fn scoped_refresh_request(&self, refresh_token: &str, scope: &str) -> CraftedRequest {
+        CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", refresh_token),
+                    ("scope", scope),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: Some(self.basic_authorization.clone()),
+        }
+    }
+
+    fn refresh_response(&mut self, request: CraftedRequest) -> (Status, HashMap<String, String>) {
+        let response = refresh_flow(&self.registrar, &mut self.issuer)
+            .execute(request)
+            .expect("Expected non-failed reponse");
+        let body = match response.body {
+            Some(Body::Json(body)) => body,
+            other => panic!("Expected json encoded body, got {:?}", other),
+        };
+        let body: HashMap<String, serde_json::Value> =
+            serde_json::from_str(&body).expect("Expected valid json body");
+        let body = body
+            .into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(value) => (key, value),
+                other => (key, other.to_string()),
+            })
+            .collect();
+        (response.status, body)
+    }
+
+    fn access_with_scope(&mut self, token: &str, scope: &str) -> bool {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("Bearer {}", token)),
+        };
+
+        match resource_flow(&mut self.issuer, &[scope.parse().unwrap()]).execute(request) {
+            Ok(_) => true,
+            Err(Ok(_)) => false,
+            Err(Err(err)) => panic!("Expected a response, got {:?}", err),
+        }
+    }
 }
 
 #[test]
@@ -405,3 +505,273 @@ fn private_invalid_token() {
 
     setup.assert_invalid_grant(valid_private);
 }
+
+/// Replaces the roles stored in a grant with those currently assigned to the owner.
+struct CurrentRoles(Option<&'static str>);
+
+impl GrantExtension for CurrentRoles {
+    fn identifier(&self) -> &'static str {
+        "roles"
+    }
+}
+
+impl RefreshAddon for CurrentRoles {
+    fn execute(&self, _: &dyn RefreshRequest, data: Option<Value>) -> AddonResult {
+        assert_eq!(data, Some(Value::public(Some("reader".to_string()))));
+        match self.0 {
+            Some(roles) => AddonResult::Data(Value::public(Some(roles.to_string()))),
+            // The owner lost all roles, the session ends.
+            None => AddonResult::Err,
+        }
+    }
+}
+
+fn roles_setup() -> RefreshTokenSetup {
+    let mut extensions = Extensions::new();
+    extensions.set(&CurrentRoles(None), Value::public(Some("reader".to_string())));
+    extensions.set_raw("session".to_string(), Value::private(Some("abc".to_string())));
+    RefreshTokenSetup::private_client_with(extensions)
+}
+
+fn roles_request(setup: &RefreshTokenSetup) -> CraftedRequest {
+    CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &setup.refresh_token),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(setup.basic_authorization.clone()),
+    }
+}
+
+#[test]
+fn refresh_addon_updates_extension() {
+    let mut setup = roles_setup();
+    let request = roles_request(&setup);
+
+    let mut addons = AddonList::new();
+    addons.push_refresh(CurrentRoles(Some("reader writer")));
+    let response = setup.refresh_with_addons(addons, request);
+    assert_eq!(response.status, Status::Ok);
+
+    let body = match response.body {
+        Some(Body::Json(body)) => body,
+        _ => panic!("Expect json body"),
+    };
+    let body: TokenResponse = serde_json::from_str(&body).expect("Expected valid json body");
+    let token = body.access_token.expect("Expected a token");
+
+    let mut grant = setup
+        .issuer
+        .recover_token(&token)
+        .unwrap()
+        .expect("Refreshed token should be valid");
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+    assert_eq!(grant.client_id, EXAMPLE_CLIENT_ID);
+    assert_eq!(grant.scope, EXAMPLE_SCOPE.parse().unwrap());
+    assert_eq!(
+        grant.extensions.remove(&CurrentRoles(None)),
+        Some(Value::public(Some("reader writer".to_string())))
+    );
+
+    // Data without a refresh addon is copied unchanged.
+    let private = grant.extensions.private().collect::<Vec<_>>();
+    assert_eq!(private, [("session", Some("abc"))]);
+}
+
+#[test]
+fn refresh_addon_rejects() {
+    let mut setup = roles_setup();
+    let request = roles_request(&setup);
+
+    let mut addons = AddonList::new();
+    addons.push_refresh(CurrentRoles(None));
+    let response = setup.refresh_with_addons(addons, request);
+
+    let body = setup.assert_json_body(&response);
+    assert_eq!(response.status, Status::BadRequest);
+    assert_eq!(body.get("error").map(String::as_str), Some("invalid_grant"));
+}
+
+#[test]
+fn refresh_revokes_previous_access_token() {
+    let mut setup = RefreshTokenSetup::private_client();
+    let original = setup.issued.token.clone();
+    setup.access_resource(original.clone());
+
+    let request = setup.refresh_request(&setup.refresh_token);
+    let first = setup.assert_success(request);
+    setup.assert_access_denied(original.clone(), Status::Unauthorized);
+    setup.access_resource(first.token.clone());
+
+    // Only the newest access token of the grant stays valid.
+    let request = setup.refresh_request(&first.refresh.expect("Expected a new refresh token"));
+    let second = setup.assert_success(request);
+    setup.assert_access_denied(original, Status::Unauthorized);
+    setup.assert_access_denied(first.token, Status::Unauthorized);
+    setup.access_resource(second.token);
+}
+
+#[test]
+fn refresh_reports_refresh_expiry() {
+    let mut setup = RefreshTokenSetup::private_client();
+    setup.issuer.refresh_valid_for(Duration::days(30));
+
+    let request = setup.refresh_request(&setup.refresh_token);
+    let mut flow = refresh_flow(&setup.registrar, &mut setup.issuer);
+    flow.report_refresh_expiry(true);
+    let response = flow.execute(request).expect("Expected non-failed reponse");
+    assert_eq!(response.status, Status::Ok);
+
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    let lifetime = Duration::days(30).num_seconds();
+    let expires_in = body
+        .refresh_token_expires_in
+        .expect("Expected refresh token lifetime");
+    assert!(expires_in <= lifetime && expires_in > lifetime - 5);
+
+    // Omitted by default, even though the lifetime is limited.
+    let request = setup.refresh_request(&body.refresh_token.unwrap());
+    let response = refresh_flow(&setup.registrar, &mut setup.issuer)
+        .execute(request)
+        .expect("Expected non-failed reponse");
+    let body: TokenResponse = match response.body {
+        Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+        other => panic!("Expected json encoded body, got {:?}", other),
+    };
+    assert_eq!(body.refresh_token_expires_in, None);
+}
+
+#[test]
+fn refresh_scope_duplicates() {
+    let mut setup = RefreshTokenSetup::private_client();
+
+    let authorization = setup.basic_authorization.clone();
+    let request = |refresh_token: &str, scope: &str| CraftedRequest {
+        query: None,
+        urlbody: Some(
+            [
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("scope", scope),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(authorization.clone()),
+    };
+
+    let scope_of = |response: CraftedResponse| -> TokenResponse {
+        assert_eq!(response.status, Status::Ok);
+        match response.body {
+            Some(Body::Json(body)) => serde_json::from_str(&body).expect("Expected valid json body"),
+            other => panic!("Expected json encoded body, got {:?}", other),
+        }
+    };
+
+    // Repeated and reordered tokens request exactly the original scope.
+    let refresh = setup.refresh_token.clone();
+    let response = refresh_flow(&setup.registrar, &mut setup.issuer)
+        .execute(request(&refresh, "default example default"))
+        .expect("Expected non-failed reponse");
+    let body = scope_of(response);
+    assert_eq!(body.scope.as_deref(), Some("default example"));
+
+    // A duplicated token is still a subset of the grant.
+    let refresh = body.refresh_token.expect("Expected a new refresh token");
+    let response = refresh_flow(&setup.registrar, &mut setup.issuer)
+        .execute(request(&refresh, "example example"))
+        .expect("Expected non-failed reponse");
+    let body = scope_of(response);
+    assert_eq!(body.scope.as_deref(), Some("example"));
+}
+
+#[test]
+fn refresh_reuse_reported() {
+    use crate::primitives::issuer::{RefreshEvent, RefreshEventKind};
+    use std::sync::{Arc, Mutex};
+
+    let mut setup = RefreshTokenSetup::private_client();
+    let events = Arc::new(Mutex::new(Vec::<RefreshEvent>::new()));
+    let listener = events.clone();
+    setup
+        .issuer
+        .on_refresh_event(move |event| listener.lock().unwrap().push(event.clone()));
+
+    let request = setup.refresh_request(&setup.refresh_token);
+    let refreshed = setup.assert_success(request);
+
+    // A stolen copy of the exchanged token is denied and reported.
+    let request = setup.refresh_request(&setup.refresh_token);
+    setup.assert_invalid_grant(request);
+
+    let kinds = events
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|event| {
+            assert_eq!(event.client_id, EXAMPLE_CLIENT_ID);
+            assert_eq!(event.owner_id, EXAMPLE_OWNER_ID);
+            event.kind
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, [RefreshEventKind::Rotated, RefreshEventKind::Reused]);
+
+    // The event does not revoke the current token on its own.
+    let request = setup.refresh_request(&refreshed.refresh.unwrap());
+    setup.assert_success(request);
+}
+
+#[test]
+fn refresh_scope_narrowing() {
+    let mut setup = RefreshTokenSetup::private_client();
+
+    let request = setup.scoped_refresh_request(&setup.refresh_token, "example");
+    let (status, body) = setup.refresh_response(request);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body.get("scope").map(String::as_str), Some("example"));
+
+    let token = body.get("access_token").expect("Expected a token").clone();
+    assert!(setup.access_with_scope(&token, "example"));
+    assert!(!setup.access_with_scope(&token, "default"));
+    // The narrowed token is still valid, it only lacks the scope.
+    setup.assert_access_denied(token, Status::Forbidden);
+}
+
+#[test]
+fn refresh_scope_superset_rejected() {
+    let mut setup = RefreshTokenSetup::private_client();
+
+    for scope in ["example default admin", "admin", "example \"quoted\""] {
+        let request = setup.scoped_refresh_request(&setup.refresh_token, scope);
+        let (status, body) = setup.refresh_response(request);
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(body.get("error").map(String::as_str), Some("invalid_scope"));
+        setup.assert_only_error(body);
+    }
+
+    // The rejected requests did not consume the refresh token.
+    setup.access_resource(setup.issued.token.clone());
+    let request = setup.refresh_request(&setup.refresh_token);
+    setup.assert_success(request);
+}
+
+#[test]
+fn refresh_scope_default_unchanged() {
+    let mut setup = RefreshTokenSetup::private_client();
+
+    let request = setup.refresh_request(&setup.refresh_token);
+    let (status, body) = setup.refresh_response(request);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body.get("scope").map(String::as_str), Some("default example"));
+
+    let token = body.get("access_token").expect("Expected a token").clone();
+    setup.access_resource(token);
+}
@@ -1,20 +1,103 @@
-use crate::primitives::issuer::TokenMap;
+use crate::primitives::binding::Binding;
+use crate::primitives::issuer::{Issuer, TokenMap, TokenSigner, TokenType};
 use crate::primitives::generator::RandomGenerator;
 use crate::primitives::grant::{Grant, Extensions};
 use crate::primitives::scope::Scope;
+use crate::code_grant::resource::TokenInfo;
 
-use crate::frontends::simple::endpoint::resource_flow;
+use crate::frontends::simple::endpoint::{resource_flow, Generic, Hooked, Hooks, Vacant};
 
 use chrono::{Utc, Duration};
 
-use super::CraftedRequest;
+use crate::endpoint::{
+    bearer_token, cookie_value, ProofVerifier, QueryParameter, ResourceFlow, Scopes, SessionValidator,
+    WebRequest,
+};
+
+use std::borrow::Cow;
+
+use super::{Body, CraftedError, CraftedRequest, CraftedResponse, Status, ToSingleValueQuery};
 use super::defaults::*;
 
+const TOKEN_COOKIE: &str = "access_token";
+
+/// A request that additionally carries a single cookie.
+#[derive(Debug)]
+struct CookieRequest {
+    inner: CraftedRequest,
+    cookie: Option<(String, String)>,
+}
+
+impl WebRequest for CookieRequest {
+    type Response = CraftedResponse;
+    type Error = CraftedError;
+
+    fn query(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.inner.query()
+    }
+
+    fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+        self.inner.urlbody()
+    }
+
+    fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+        self.inner.authheader()
+    }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<str>>, Self::Error> {
+        Ok(self
+            .cookie
+            .as_ref()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str().into()))
+    }
+}
+
+/// Checks sessions against a list of logged out owners.
+struct Sessions {
+    logged_out: Vec<String>,
+    available: bool,
+}
+
+impl SessionValidator for Sessions {
+    fn session_active(&mut self, grant: &Grant) -> Result<bool, ()> {
+        if !self.available {
+            return Err(());
+        }
+
+        Ok(!self.logged_out.contains(&grant.owner_id))
+    }
+}
+
+/// Accepts DPoP proofs of a single key.
+///
+/// The crafted request has no other headers, so the proof is passed as the `dpop` query parameter
+/// and consists only of the thumbprint of the key it was signed with.
+struct Proofs {
+    jkt: String,
+}
+
+impl ProofVerifier<CraftedRequest> for Proofs {
+    fn verify(&mut self, request: &mut CraftedRequest, _: &str, binding: &Binding) -> Result<bool, ()> {
+        let proof = request
+            .query
+            .as_ref()
+            .and_then(|query| query.get("dpop"))
+            .and_then(|values| values.first());
+
+        match binding {
+            Binding::Dpop { jkt } => Ok(proof == Some(jkt) && *jkt == self.jkt),
+            Binding::Certificate { .. } => Ok(false),
+        }
+    }
+}
+
 struct ResourceSetup {
     issuer: TokenMap<RandomGenerator>,
     authtoken: String,
     wrong_scope_token: String,
     small_scope_token: String,
+    empty_scope_token: String,
     resource_scope: [Scope; 1],
 }
 
@@ -58,11 +141,23 @@ impl ResourceSetup {
             })
             .unwrap();
 
+        let empty_scope_token = issuer
+            .issue(Grant {
+                client_id: EXAMPLE_CLIENT_ID.to_string(),
+                owner_id: EXAMPLE_OWNER_ID.to_string(),
+                redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+                scope: "".parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap();
+
         ResourceSetup {
             issuer,
             authtoken: authtoken.token,
             wrong_scope_token: wrong_scope_token.token,
             small_scope_token: small_scope_token.token,
+            empty_scope_token: empty_scope_token.token,
             resource_scope: ["needed legit".parse().unwrap()],
         }
     }
@@ -80,6 +175,33 @@ impl ResourceSetup {
             Err(_) => (),
         }
     }
+
+    fn test_token_info(&mut self, request: CraftedRequest) -> (Status, TokenInfo) {
+        let response = resource_flow(&mut self.issuer, &self.resource_scope)
+            .to_token_info(request)
+            .unwrap_or_else(|ohno| panic!("Expected a response instead of {:?}", ohno));
+        match response.body {
+            Some(Body::Json(json)) => (response.status, serde_json::from_str(&json).unwrap()),
+            other => panic!("Expected a json body instead of {:?}", other),
+        }
+    }
+
+    fn test_cookie_access(&mut self, request: CookieRequest) -> Result<(), Status> {
+        let mut flow = resource_flow(&mut self.issuer, &self.resource_scope);
+        flow.token_cookie(Some(TOKEN_COOKIE));
+        match flow.execute(request) {
+            Ok(_) => Ok(()),
+            Err(Ok(response)) => Err(response.status),
+            Err(Err(ohno)) => panic!("Expected a response instead of {:?}", ohno),
+        }
+    }
+}
+
+fn cookie_request(name: &str, value: &str) -> CookieRequest {
+    CookieRequest {
+        inner: CraftedRequest::default(),
+        cookie: Some((name.to_string(), value.to_string())),
+    }
 }
 
 #[test]
@@ -164,6 +286,42 @@ fn resource_scope_too_small() {
     setup.test_access_error(scope_too_small);
 }
 
+#[test]
+fn resource_empty_scope_required() {
+    let mut setup = ResourceSetup::new();
+    setup.resource_scope = ["".parse().unwrap()];
+    // A token without any scope suffices for a resource without requirements
+    let empty_scope = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &setup.empty_scope_token),
+    };
+
+    setup.test_access_success(empty_scope);
+}
+
+#[test]
+fn resource_empty_scope_insufficient() {
+    let mut setup = ResourceSetup::new();
+    // A token without any scope never suffices for a resource with requirements
+    let empty_scope = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &setup.empty_scope_token),
+    };
+
+    match resource_flow(&mut setup.issuer, &setup.resource_scope).execute(empty_scope) {
+        Err(Ok(response)) => {
+            let authenticate = response
+                .www_authenticate
+                .expect("Should have an authenticate header");
+            assert!(authenticate.contains("insufficient_scope"), "{}", authenticate);
+        }
+        Err(Err(ohno)) => panic!("Expected a response instead of {:?}", ohno),
+        Ok(grant) => panic!("Expected an error instead of {:?}", grant),
+    }
+}
+
 #[test]
 fn resource_wrong_scope() {
     let mut setup = ResourceSetup::new();
@@ -176,3 +334,524 @@ fn resource_wrong_scope() {
 
     setup.test_access_error(wrong_scope);
 }
+
+#[test]
+fn resource_expired_token_unauthorized() {
+    let mut setup = ResourceSetup::new();
+    let expired = setup
+        .issuer
+        .issue(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: "needed legit".parse().unwrap(),
+            until: Utc::now() - Duration::hours(1),
+            extensions: Extensions::new(),
+        })
+        .unwrap();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &expired.token),
+    };
+
+    match resource_flow(&mut setup.issuer, &setup.resource_scope).execute(request) {
+        Err(Ok(response)) => {
+            assert_eq!(response.status, Status::Unauthorized);
+            let authenticate = response.www_authenticate.unwrap();
+            assert!(authenticate.contains("invalid_token"), "{}", authenticate);
+        }
+        other => panic!("Expected an error response instead of {:?}", other),
+    }
+}
+
+#[test]
+fn resource_insufficient_scope_forbidden() {
+    let mut setup = ResourceSetup::new();
+    let request = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &setup.small_scope_token),
+    };
+
+    match resource_flow(&mut setup.issuer, &setup.resource_scope).execute(request) {
+        Err(Ok(response)) => {
+            assert_eq!(response.status, Status::Forbidden);
+            let authenticate = response.www_authenticate.unwrap();
+            assert!(authenticate.contains("insufficient_scope"), "{}", authenticate);
+        }
+        other => panic!("Expected an error response instead of {:?}", other),
+    }
+}
+
+/// Requires the scope of the resource setup and explains denied requests.
+struct Explained(Vec<Scope>);
+
+impl Scopes<CraftedRequest> for Explained {
+    fn scopes(&mut self, _: &mut CraftedRequest) -> &[Scope] {
+        &self.0
+    }
+
+    fn deny_reason(&mut self, _: &mut CraftedRequest) -> Option<String> {
+        Some("Photos require the \"needed\" scope".to_string())
+    }
+}
+
+#[test]
+fn resource_insufficient_scope_reason() {
+    let mut setup = ResourceSetup::new();
+    let wrong_scope = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &setup.wrong_scope_token),
+    };
+
+    let mut flow = Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer: &mut setup.issuer,
+        scopes: Explained(setup.resource_scope.to_vec()),
+        solicitor: Vacant,
+        response: Vacant,
+    }
+    .resource_flow();
+
+    match flow.execute(wrong_scope) {
+        Err(Ok(response)) => {
+            assert_eq!(response.status, Status::Forbidden);
+            // Quotes in the description are dropped, they would end the attribute.
+            assert_eq!(
+                response.www_authenticate.as_deref(),
+                Some(
+                    "Bearer error=\"insufficient_scope\",scope=\"legit needed\",\
+                     error_description=\"Photos require the needed scope\""
+                )
+            );
+        }
+        other => panic!("Expected an error response instead of {:?}", other),
+    }
+
+    // An invalid token is not explained by the scopes.
+    let invalid = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer NotAToken".to_string()),
+    };
+    match flow.execute(invalid) {
+        Err(Ok(response)) => {
+            let authenticate = response.www_authenticate.unwrap();
+            assert!(!authenticate.contains("error_description"), "{}", authenticate);
+        }
+        other => panic!("Expected an error response instead of {:?}", other),
+    }
+}
+
+#[test]
+fn resource_sliding_expiry() {
+    use crate::primitives::issuer::Issuer;
+
+    let mut setup = ResourceSetup::new();
+    setup.issuer.sliding_expiry(Duration::hours(4));
+    let token = setup
+        .issuer
+        .issue(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: "needed legit".parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions: Extensions::new(),
+        })
+        .unwrap()
+        .token;
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: None,
+        auth: Some("Bearer ".to_string() + &token),
+    };
+
+    let mut flow = resource_flow(&mut setup.issuer, &setup.resource_scope);
+    flow.sliding_expiry(Some(Duration::hours(2)));
+    let grant = flow.execute(request).expect("Should allow access");
+
+    assert!(grant.until > Utc::now() + Duration::minutes(90));
+    let stored = setup.issuer.recover_token(&token).unwrap().unwrap();
+    assert_eq!(stored.until, grant.until);
+}
+
+#[test]
+fn resource_cookie_success() {
+    let mut setup = ResourceSetup::new();
+    let request = cookie_request(TOKEN_COOKIE, &setup.authtoken);
+
+    if let Err(status) = setup.test_cookie_access(request) {
+        panic!("Expected access instead of {:?}", status);
+    }
+}
+
+#[test]
+fn resource_cookie_not_enabled() {
+    let mut setup = ResourceSetup::new();
+    let request = cookie_request(TOKEN_COOKIE, &setup.authtoken);
+
+    match resource_flow(&mut setup.issuer, &setup.resource_scope).execute(request) {
+        Ok(_) => panic!("Cookie must not be accepted unless enabled"),
+        Err(Ok(response)) => assert_eq!(response.status, Status::Unauthorized),
+        Err(Err(ohno)) => panic!("Expected a response instead of {:?}", ohno),
+    }
+}
+
+#[test]
+fn resource_cookie_missing() {
+    let mut setup = ResourceSetup::new();
+    let request = cookie_request("other_cookie", &setup.authtoken);
+
+    match setup.test_cookie_access(request) {
+        Ok(()) => panic!("Expected an error for a missing cookie"),
+        Err(status) => assert_eq!(status, Status::Unauthorized),
+    }
+}
+
+#[test]
+fn resource_cookie_invalid_token() {
+    let mut setup = ResourceSetup::new();
+    let request = cookie_request(TOKEN_COOKIE, "ThisisnotavalidtokenTooLong");
+
+    match setup.test_cookie_access(request) {
+        Ok(()) => panic!("Expected an error for an invalid cookie"),
+        Err(status) => assert_eq!(status, Status::Unauthorized),
+    }
+}
+
+#[test]
+fn resource_cookie_and_header() {
+    let mut setup = ResourceSetup::new();
+    // Presenting the token by more than one method is not allowed.
+    let mut request = cookie_request(TOKEN_COOKIE, &setup.authtoken);
+    request.inner.auth = Some("Bearer ".to_string() + &setup.authtoken);
+
+    match setup.test_cookie_access(request) {
+        Ok(()) => panic!("Expected an error for ambiguous authorization"),
+        Err(status) => assert_eq!(status, Status::Unauthorized),
+    }
+}
+
+#[test]
+fn resource_token_info() {
+    let mut setup = ResourceSetup::new();
+    let request = CraftedRequest {
+        auth: Some("Bearer ".to_string() + &setup.authtoken),
+        ..CraftedRequest::default()
+    };
+
+    let (status, info) = setup.test_token_info(request);
+    assert_eq!(status, Status::Ok);
+    assert!(info.active);
+    assert_eq!(info.sub.as_deref(), Some(EXAMPLE_OWNER_ID));
+    assert_eq!(info.client_id.as_deref(), Some(EXAMPLE_CLIENT_ID));
+    let scope: Scope = info.scope.unwrap().parse().unwrap();
+    assert_eq!(scope, "legit needed andmore".parse().unwrap());
+    assert!(info.exp.unwrap() > Utc::now().timestamp());
+}
+
+#[test]
+fn resource_token_info_inactive() {
+    let mut setup = ResourceSetup::new();
+    let request = CraftedRequest {
+        auth: Some("Bearer ThisisnotavalidtokenTooLong".to_string()),
+        ..CraftedRequest::default()
+    };
+
+    let (status, info) = setup.test_token_info(request);
+    assert_eq!(status, Status::Unauthorized);
+    assert_eq!(info, TokenInfo::inactive());
+
+    let (status, info) = setup.test_token_info(CraftedRequest::default());
+    assert_eq!(status, Status::Unauthorized);
+    assert_eq!(info, TokenInfo::inactive());
+}
+
+/// Issue a self-contained token for the owner, which can not be revoked by the issuer.
+fn signed_token(issuer: &mut TokenSigner, owner_id: &str) -> CraftedRequest {
+    let token = issuer
+        .issue(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: owner_id.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: "needed legit".parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions: Extensions::new(),
+        })
+        .unwrap()
+        .token;
+
+    CraftedRequest {
+        auth: Some("Bearer ".to_string() + &token),
+        ..CraftedRequest::default()
+    }
+}
+
+type SessionEndpoint<'a> =
+    Hooked<Generic<Vacant, Vacant, &'a mut dyn Issuer, Vacant, &'a [Scope], Vacant>, Hooks<Sessions>>;
+
+fn session_flow<'a>(
+    issuer: &'a mut dyn Issuer, scopes: &'a [Scope], logged_out: &[&str], available: bool,
+) -> ResourceFlow<SessionEndpoint<'a>, CraftedRequest> {
+    let sessions = Sessions {
+        logged_out: logged_out.iter().map(ToString::to_string).collect(),
+        available,
+    };
+    let endpoint = Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer,
+        scopes,
+        solicitor: Vacant,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().session_validator(sessions));
+
+    ResourceFlow::prepare(endpoint).unwrap_or_else(|_| panic!("Endpoint has issuer and scopes"))
+}
+
+#[test]
+fn resource_session_active() {
+    let mut issuer = TokenSigner::ephemeral();
+    let scopes = ["needed".parse().unwrap()];
+    let request = signed_token(&mut issuer, EXAMPLE_OWNER_ID);
+
+    let grant = session_flow(&mut issuer, &scopes, &["SomeoneElse"], true)
+        .execute(request)
+        .expect("Should allow access");
+    assert_eq!(grant.owner_id, EXAMPLE_OWNER_ID);
+}
+
+#[test]
+fn resource_session_revoked() {
+    let mut issuer = TokenSigner::ephemeral();
+    let scopes = ["needed".parse().unwrap()];
+    let request = signed_token(&mut issuer, EXAMPLE_OWNER_ID);
+    let token = &request.auth.as_deref().unwrap()["Bearer ".len()..];
+
+    // The token itself is still valid.
+    assert!(issuer.recover_token(token).unwrap().is_some());
+
+    let response = match session_flow(&mut issuer, &scopes, &[EXAMPLE_OWNER_ID], true).execute(request) {
+        Err(Ok(response)) => response,
+        other => panic!("Expected an error response instead of {:?}", other),
+    };
+    assert_eq!(response.status, Status::Unauthorized);
+    let authenticate = response.www_authenticate.expect("Should ask for authentication");
+    assert!(
+        authenticate.contains("error=\"invalid_token\""),
+        "{}",
+        authenticate
+    );
+}
+
+#[test]
+fn resource_session_revoked_token_info() {
+    let mut issuer = TokenSigner::ephemeral();
+    let scopes = ["needed".parse().unwrap()];
+    let request = signed_token(&mut issuer, EXAMPLE_OWNER_ID);
+
+    let response = session_flow(&mut issuer, &scopes, &[EXAMPLE_OWNER_ID], true)
+        .to_token_info(request)
+        .unwrap_or_else(|ohno| panic!("Expected a response instead of {:?}", ohno));
+    assert_eq!(response.status, Status::Unauthorized);
+    match response.body {
+        Some(Body::Json(json)) => {
+            assert_eq!(
+                serde_json::from_str::<TokenInfo>(&json).unwrap(),
+                TokenInfo::inactive()
+            )
+        }
+        other => panic!("Expected a json body instead of {:?}", other),
+    }
+}
+
+#[test]
+fn resource_session_unavailable() {
+    let mut issuer = TokenSigner::ephemeral();
+    let scopes = ["needed".parse().unwrap()];
+    let request = signed_token(&mut issuer, EXAMPLE_OWNER_ID);
+
+    // The session store can not be reached.
+    match session_flow(&mut issuer, &scopes, &[], false).execute(request) {
+        Err(Err(_)) => (),
+        other => panic!("Expected the flow to fail instead of {:?}", other),
+    }
+}
+
+const DPOP_JKT: &str = "0ZcOCORZNYy-DWpqq30jZyJGHTN0d2HglBV3uiguA4I";
+
+/// Issue a token bound to the DPoP key of the client.
+fn dpop_token(issuer: &mut TokenMap<RandomGenerator>) -> String {
+    let mut extensions = Extensions::new();
+    Binding::Dpop {
+        jkt: DPOP_JKT.to_string(),
+    }
+    .attach(&mut extensions);
+
+    let token = issuer
+        .issue(Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: "needed legit".parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions,
+        })
+        .unwrap();
+
+    assert_eq!(token.token_type, TokenType::DPoP);
+    token.token
+}
+
+fn dpop_request(auth: String, proof: Option<&str>) -> CraftedRequest {
+    CraftedRequest {
+        query: proof.map(|proof| vec![("dpop", proof)].iter().to_single_value_query()),
+        urlbody: None,
+        auth: Some(auth),
+    }
+}
+
+type ProofEndpoint<'a> = Hooked<
+    Generic<Vacant, Vacant, &'a mut dyn Issuer, Vacant, &'a [Scope], Vacant>,
+    Hooks<Vacant, Proofs>,
+>;
+
+fn proof_flow<'a>(
+    issuer: &'a mut dyn Issuer, scopes: &'a [Scope],
+) -> ResourceFlow<ProofEndpoint<'a>, CraftedRequest> {
+    let proofs = Proofs {
+        jkt: DPOP_JKT.to_string(),
+    };
+    let endpoint = Generic {
+        registrar: Vacant,
+        authorizer: Vacant,
+        issuer,
+        scopes,
+        solicitor: Vacant,
+        response: Vacant,
+    }
+    .with_hooks(Hooks::new().proof_verifier(proofs));
+
+    ResourceFlow::prepare(endpoint).unwrap_or_else(|_| panic!("Endpoint has issuer and scopes"))
+}
+
+fn assert_invalid_token<E: std::fmt::Debug>(result: Result<Grant, Result<CraftedResponse, E>>) {
+    let response = match result {
+        Err(Ok(response)) => response,
+        other => panic!("Expected an error response instead of {:?}", other),
+    };
+    assert_eq!(response.status, Status::Unauthorized);
+    let authenticate = response.www_authenticate.expect("Should ask for authentication");
+    assert!(
+        authenticate.contains("error=\"invalid_token\""),
+        "{}",
+        authenticate
+    );
+}
+
+#[test]
+fn resource_dpop_with_proof() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let scopes = ["needed".parse().unwrap()];
+    let token = dpop_token(&mut issuer);
+
+    let request = dpop_request("DPoP ".to_string() + &token, Some(DPOP_JKT));
+    let grant = proof_flow(&mut issuer, &scopes)
+        .execute(request)
+        .expect("Should allow access");
+    assert_eq!(Binding::token_type_of(&grant), TokenType::DPoP);
+}
+
+#[test]
+fn resource_dpop_as_bearer() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let scopes = ["needed".parse().unwrap()];
+    let token = dpop_token(&mut issuer);
+
+    // Even with a valid proof, the bound token must not be accepted as a bearer token.
+    let request = dpop_request("Bearer ".to_string() + &token, Some(DPOP_JKT));
+    assert_invalid_token(proof_flow(&mut issuer, &scopes).execute(request));
+}
+
+#[test]
+fn resource_dpop_invalid_proof() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let scopes = ["needed".parse().unwrap()];
+    let token = dpop_token(&mut issuer);
+
+    let request = dpop_request("DPoP ".to_string() + &token, None);
+    assert_invalid_token(proof_flow(&mut issuer, &scopes).execute(request));
+
+    let request = dpop_request("DPoP ".to_string() + &token, Some("SomeOtherKey"));
+    assert_invalid_token(proof_flow(&mut issuer, &scopes).execute(request));
+}
+
+#[test]
+fn resource_dpop_without_verifier() {
+    let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    let scopes = ["needed".parse().unwrap()];
+    let token = dpop_token(&mut issuer);
+
+    let request = dpop_request("DPoP ".to_string() + &token, Some(DPOP_JKT));
+    match resource_flow(&mut issuer, &scopes).execute(request) {
+        Err(Ok(response)) => assert_eq!(response.status, Status::Unauthorized),
+        other => panic!("Expected an error response instead of {:?}", other),
+    }
+}
+
+#[test]
+fn resource_bearer_as_dpop() {
+    let mut setup = ResourceSetup::new();
+    let scopes = ["needed".parse().unwrap()];
+
+    // An unbound token is not accepted with the DPoP scheme.
+    let request = dpop_request("DPoP ".to_string() + &setup.authtoken, Some(DPOP_JKT));
+    assert_invalid_token(proof_flow(&mut setup.issuer, &scopes).execute(request));
+}
+
+fn bearer_of(auth: Option<&str>) -> Option<String> {
+    let mut request = CraftedRequest {
+        auth: auth.map(str::to_owned),
+        ..CraftedRequest::default()
+    };
+    bearer_token(&mut request).expect("Crafted requests have no errors")
+}
+
+#[test]
+fn bearer_token_valid() {
+    assert_eq!(bearer_of(Some("Bearer Token")), Some("Token".to_owned()));
+    assert_eq!(bearer_of(Some("bearer Token")), Some("Token".to_owned()));
+    assert_eq!(bearer_of(Some("BEARER Token")), Some("Token".to_owned()));
+    assert_eq!(bearer_of(Some("  Bearer   Token  ")), Some("Token".to_owned()));
+}
+
+#[test]
+fn bearer_token_malformed() {
+    assert_eq!(bearer_of(None), None);
+    assert_eq!(bearer_of(Some("")), None);
+    assert_eq!(bearer_of(Some("Token")), None);
+    assert_eq!(bearer_of(Some("Bearer")), None);
+    assert_eq!(bearer_of(Some("Bearer   ")), None);
+    assert_eq!(bearer_of(Some("BearerToken")), None);
+    assert_eq!(bearer_of(Some("Bearer Token Other")), None);
+    assert_eq!(bearer_of(Some("Basic dXNlcjpwYXNz")), None);
+    assert_eq!(bearer_of(Some("DPoP Token")), None);
+}
+
+#[test]
+fn cookie_value_pairs() {
+    assert_eq!(cookie_value("token=abc", "token"), Some("abc"));
+    assert_eq!(cookie_value("a=1;token=abc; b=2", "token"), Some("abc"));
+    assert_eq!(cookie_value("token=\"abc\"", "token"), Some("abc"));
+    assert_eq!(cookie_value("token=", "token"), Some(""));
+    assert_eq!(cookie_value("Token=abc", "token"), None);
+    assert_eq!(cookie_value("mytoken=abc", "token"), None);
+    assert_eq!(cookie_value("token", "token"), None);
+    assert_eq!(cookie_value("", "token"), None);
+}
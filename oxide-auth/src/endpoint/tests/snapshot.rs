@@ -0,0 +1,177 @@
+use crate::primitives::authorizer::{AuthMap, AuthMapSnapshot, Authorizer};
+use crate::primitives::issuer::{IssuedToken, Issuer, TokenMap, TokenMapSnapshot};
+use crate::primitives::generator::RandomGenerator;
+use crate::primitives::grant::{Grant, Extensions, Value};
+use crate::primitives::registrar::{Client, ClientMap, ClientMapSnapshot, RegisteredUrl};
+
+use crate::frontends::simple::endpoint::{access_token_flow, refresh_flow, resource_flow};
+
+use base64::{self, Engine};
+use base64::engine::general_purpose::STANDARD;
+use chrono::{Utc, Duration};
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use super::{CraftedRequest, Status, ToSingleValueQuery};
+use super::defaults::*;
+
+/// The state of the endpoint, as it would be persisted by a server before it shuts down.
+#[derive(Serialize, Deserialize)]
+struct EndpointState {
+    registrar: ClientMapSnapshot,
+    authorizer: AuthMapSnapshot,
+    issuer: TokenMapSnapshot,
+}
+
+struct SnapshotSetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<RandomGenerator>,
+    issuer: TokenMap<RandomGenerator>,
+    authtoken: String,
+    issued: IssuedToken,
+    basic_authorization: String,
+}
+
+impl SnapshotSetup {
+    fn new() -> Self {
+        let mut registrar = ClientMap::new();
+        let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+        let mut issuer = TokenMap::new(RandomGenerator::new(16));
+
+        registrar.register_client(Client::confidential(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+            EXAMPLE_PASSPHRASE.as_bytes(),
+        ));
+
+        let mut extensions = Extensions::new();
+        extensions.set_raw("private".into(), Value::private(Some("secret".into())));
+        let grant = Grant {
+            client_id: EXAMPLE_CLIENT_ID.to_string(),
+            owner_id: EXAMPLE_OWNER_ID.to_string(),
+            redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+            scope: EXAMPLE_SCOPE.parse().unwrap(),
+            until: Utc::now() + Duration::hours(1),
+            extensions,
+        };
+
+        let authtoken = authorizer.authorize(grant.clone()).unwrap();
+        let issued = issuer.issue(grant).unwrap();
+
+        let basic_authorization =
+            STANDARD.encode(format!("{}:{}", EXAMPLE_CLIENT_ID, EXAMPLE_PASSPHRASE));
+        let basic_authorization = format!("Basic {}", basic_authorization);
+
+        SnapshotSetup {
+            registrar,
+            authorizer,
+            issuer,
+            authtoken,
+            issued,
+            basic_authorization,
+        }
+    }
+
+    /// Serialize the state and load it into fresh instances, like a restarted server would.
+    fn restart(&mut self) {
+        let state = EndpointState {
+            registrar: self.registrar.snapshot(),
+            authorizer: self.authorizer.snapshot(),
+            issuer: self.issuer.snapshot(),
+        };
+
+        let serialized = serde_json::to_string(&state).expect("State should be serializable");
+        assert!(!serialized.contains(EXAMPLE_PASSPHRASE));
+        let state: EndpointState = serde_json::from_str(&serialized).expect("State should deserialize");
+
+        self.registrar = ClientMap::new();
+        self.authorizer = AuthMap::new(RandomGenerator::new(16));
+        self.issuer = TokenMap::new(RandomGenerator::new(16));
+
+        self.registrar.restore(state.registrar);
+        self.authorizer.restore(state.authorizer);
+        self.issuer.restore(state.issuer);
+    }
+
+    fn access_resource(&mut self, token: &str) -> Result<Grant, ()> {
+        let request = CraftedRequest {
+            query: None,
+            urlbody: None,
+            auth: Some(format!("Bearer {}", token)),
+        };
+
+        resource_flow(&mut self.issuer, &[EXAMPLE_SCOPE.parse().unwrap()])
+            .execute(request)
+            .map_err(|_| ())
+    }
+}
+
+#[test]
+fn snapshot_restores_code() {
+    let mut setup = SnapshotSetup::new();
+    setup.restart();
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "authorization_code"),
+                ("code", &setup.authtoken),
+                ("redirect_uri", EXAMPLE_REDIRECT_URI),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(setup.basic_authorization.clone()),
+    };
+
+    let response = access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+        .execute(request)
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+}
+
+#[test]
+fn snapshot_restores_tokens() {
+    let mut setup = SnapshotSetup::new();
+    let before = setup.access_resource(&setup.issued.token.clone()).unwrap();
+    setup.restart();
+
+    let after = setup.access_resource(&setup.issued.token.clone()).unwrap();
+    assert_eq!(before, after);
+
+    let request = CraftedRequest {
+        query: None,
+        urlbody: Some(
+            vec![
+                ("grant_type", "refresh_token"),
+                ("refresh_token", setup.issued.refresh.as_ref().unwrap()),
+            ]
+            .iter()
+            .to_single_value_query(),
+        ),
+        auth: Some(setup.basic_authorization.clone()),
+    };
+
+    let response = refresh_flow(&setup.registrar, &mut setup.issuer)
+        .execute(request)
+        .expect("Expected non-error response");
+    assert_eq!(response.status, Status::Ok);
+    // The refresh replaced the restored access token.
+    assert!(setup.access_resource(&setup.issued.token.clone()).is_err());
+}
+
+#[test]
+fn snapshot_keeps_revocation() {
+    let mut setup = SnapshotSetup::new();
+    setup.issuer.revoke(&setup.issued.token);
+    setup.restart();
+
+    assert!(setup.access_resource(&setup.issued.token.clone()).is_err());
+    assert!(setup
+        .issuer
+        .recover_refresh(setup.issued.refresh.as_ref().unwrap())
+        .unwrap()
+        .is_some());
+}
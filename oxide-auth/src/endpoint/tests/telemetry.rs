@@ -0,0 +1,133 @@
+use crate::primitives::authorizer::{AuthMap, Authorizer};
+use crate::primitives::generator::TagGrant;
+use crate::primitives::grant::{Extensions, Grant};
+use crate::primitives::issuer::TokenMap;
+use crate::primitives::registrar::{Client, ClientMap, RegisteredUrl};
+
+use crate::frontends::simple::endpoint::access_token_flow;
+
+use chrono::{Duration, Utc};
+use tracing_test::traced_test;
+
+use super::{CraftedRequest, Status, TestGenerator, ToSingleValueQuery};
+use super::defaults::*;
+
+/// A generator whose backing service is unavailable.
+struct FailingGenerator;
+
+impl TagGrant for FailingGenerator {
+    fn tag(&mut self, _: u64, _: &Grant) -> Result<String, ()> {
+        Err(())
+    }
+}
+
+struct TelemetrySetup {
+    registrar: ClientMap,
+    authorizer: AuthMap<TestGenerator>,
+    issuer: TokenMap<Box<dyn TagGrant>>,
+    code: String,
+}
+
+impl TelemetrySetup {
+    fn new() -> Self {
+        Self::with_generator(Box::new(TestGenerator("AccessToken".to_string())))
+    }
+
+    fn with_generator(generator: Box<dyn TagGrant>) -> Self {
+        let mut registrar = ClientMap::new();
+        registrar.register_client(Client::public(
+            EXAMPLE_CLIENT_ID,
+            RegisteredUrl::Semantic(EXAMPLE_REDIRECT_URI.parse().unwrap()),
+            EXAMPLE_SCOPE.parse().unwrap(),
+        ));
+
+        let mut authorizer = AuthMap::new(TestGenerator("AuthToken".to_string()));
+        let code = authorizer
+            .authorize(Grant {
+                client_id: EXAMPLE_CLIENT_ID.to_string(),
+                owner_id: EXAMPLE_OWNER_ID.to_string(),
+                redirect_uri: EXAMPLE_REDIRECT_URI.parse().unwrap(),
+                scope: EXAMPLE_SCOPE.parse().unwrap(),
+                until: Utc::now() + Duration::hours(1),
+                extensions: Extensions::new(),
+            })
+            .unwrap();
+
+        TelemetrySetup {
+            registrar,
+            authorizer,
+            issuer: TokenMap::new(generator),
+            code,
+        }
+    }
+
+    fn request(&self, redirect_uri: &str) -> CraftedRequest {
+        CraftedRequest {
+            query: None,
+            urlbody: Some(
+                [
+                    ("grant_type", "authorization_code"),
+                    ("client_id", EXAMPLE_CLIENT_ID),
+                    ("code", &self.code),
+                    ("redirect_uri", redirect_uri),
+                ]
+                .iter()
+                .to_single_value_query(),
+            ),
+            auth: None,
+        }
+    }
+
+    fn exchange(&mut self, redirect_uri: &str) -> Status {
+        let request = self.request(redirect_uri);
+        access_token_flow(&self.registrar, &mut self.authorizer, &mut self.issuer)
+            .execute(request)
+            .expect("Should not error")
+            .status
+    }
+}
+
+#[test]
+#[traced_test]
+fn telemetry_invalid_grant() {
+    let mut setup = TelemetrySetup::new();
+    // The code was issued for a different redirect uri.
+    assert_eq!(setup.exchange("https://client.example/other"), Status::BadRequest);
+
+    // Denied requests are routine and not logged as errors.
+    assert!(!logs_contain("ERROR"));
+    assert!(logs_contain("flow denied"));
+    assert!(logs_contain("error=\"invalid_grant\""));
+    assert!(logs_contain("flow=\"access_token\""));
+    assert!(logs_contain(&format!("client_id=\"{}\"", EXAMPLE_CLIENT_ID)));
+    assert!(!logs_contain(&setup.code));
+}
+
+#[test]
+#[traced_test]
+fn telemetry_no_token_in_logs() {
+    let mut setup = TelemetrySetup::new();
+    assert_eq!(setup.exchange(EXAMPLE_REDIRECT_URI), Status::Ok);
+
+    assert!(!logs_contain("ERROR"));
+    assert!(logs_contain("flow succeeded"));
+    assert!(logs_contain("token_id="));
+    assert!(!logs_contain("AccessToken"));
+    assert!(!logs_contain(&setup.code));
+}
+
+#[test]
+#[traced_test]
+fn telemetry_server_error() {
+    let mut setup = TelemetrySetup::with_generator(Box::new(FailingGenerator));
+    let request = setup.request(EXAMPLE_REDIRECT_URI);
+    assert!(
+        access_token_flow(&setup.registrar, &mut setup.authorizer, &mut setup.issuer)
+            .execute(request)
+            .is_err()
+    );
+
+    assert!(logs_contain("ERROR"));
+    assert!(logs_contain("flow failed"));
+    assert!(logs_contain("error=\"server_error\""));
+}
@@ -15,6 +15,7 @@
 //! * `iron`: `oxide-auth-iron`
 //! * `rouille`: `oxide-auth-rouille`
 //! * `rocket`: `oxide-auth-rocket`
+//! * `warp`: `oxide-auth-warp`
 //!
 //! ## Guide to implementing a custom front-end
 //!
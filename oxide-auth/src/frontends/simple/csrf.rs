@@ -0,0 +1,142 @@
+//! Anti-CSRF tokens for consent forms.
+//!
+//! The authorization flow preserves the `state` of the client but the consent form presented by an
+//! `OwnerSolicitor` needs protection of its own. Without it, a malicious site could make the
+//! browser of a logged-in owner post an approval for a request that the owner never saw. A
+//! [`ConsentCsrf`] signs a token for the session of the owner and the specific request, which is
+//! embedded in the form and checked when it is submitted.
+//!
+//! ```
+//! # use oxide_auth::endpoint::{OwnerConsent, Solicitation};
+//! # use oxide_auth::frontends::simple::csrf::ConsentCsrf;
+//! # use oxide_auth::frontends::simple::endpoint::FnSolicitor;
+//! # use oxide_auth::frontends::simple::request::{Body, Request, Response};
+//! let csrf = ConsentCsrf::ephemeral();
+//! // The session of the logged-in owner, usually taken from a cookie.
+//! let session = "session-of-the-owner";
+//!
+//! // When rendering the form, include the token as a hidden field.
+//! let form = FnSolicitor(|_: &mut Request, solicitation: Solicitation| {
+//!     let token = csrf.token(session, &solicitation).unwrap();
+//!     let mut response = Response::default();
+//!     response.body = Some(Body::Text(format!(
+//!         r#"<form method="post"><input type="hidden" name="{}" value="{}"></form>"#,
+//!         ConsentCsrf::FIELD,
+//!         token,
+//!     )));
+//!     OwnerConsent::InProgress(response)
+//! });
+//!
+//! // When the form is posted, only accept it with a valid token.
+//! let submit = FnSolicitor(|request: &mut Request, solicitation: Solicitation| {
+//!     match csrf.check(request, session, &solicitation) {
+//!         Ok(()) => OwnerConsent::<Response>::Authorized("owner".to_string()),
+//!         Err(()) => OwnerConsent::Denied,
+//!     }
+//! });
+//! # let _ = (form, submit);
+//! ```
+//!
+//! [`ConsentCsrf`]: struct.ConsentCsrf.html
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+
+use crate::endpoint::{Solicitation, WebRequest};
+use crate::primitives::generator::Assertion;
+
+/// Signs and validates anti-CSRF tokens for consent forms.
+///
+/// A token is bound to the session of the owner, the client, its redirect uri, the requested scope
+/// and the state. It can thus not be used by an attacker to confirm a request of their own in the
+/// session of somebody else, nor be moved to another request. Tokens expire after ten minutes by
+/// default, see [`valid_for`].
+///
+/// [`valid_for`]: #method.valid_for
+pub struct ConsentCsrf {
+    assertion: Assertion,
+    valid_for: Duration,
+}
+
+impl ConsentCsrf {
+    /// The form field from which [`check`] reads the token.
+    ///
+    /// [`check`]: #method.check
+    pub const FIELD: &'static str = "csrf_token";
+
+    /// Sign tokens with a custom assertion.
+    ///
+    /// Use the same key on all instances of a server that may render or receive the form.
+    pub fn new(assertion: Assertion) -> Self {
+        ConsentCsrf {
+            assertion,
+            valid_for: Duration::minutes(10),
+        }
+    }
+
+    /// Sign tokens with a key that is only valid for the program execution.
+    pub fn ephemeral() -> Self {
+        ConsentCsrf::new(Assertion::ephemeral())
+    }
+
+    /// Set the duration for which a token is accepted after it was created.
+    pub fn valid_for(&mut self, duration: Duration) {
+        self.valid_for = duration;
+    }
+
+    /// Create a token to embed in the consent form.
+    ///
+    /// The session should identify the logged-in owner, for example by a session cookie, and must
+    /// not be known to other sites. Fails if the assertion can not sign, for example if it only has
+    /// a public key.
+    pub fn token(&self, session: &str, solicitation: &Solicitation) -> Result<String, ()> {
+        let expiry = (Utc::now() + self.valid_for).timestamp();
+        let signature = self.signature(expiry, session, solicitation)?;
+        Ok(format!("{}.{}", expiry, URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// Validate a token from a submitted consent form.
+    pub fn validate(&self, session: &str, solicitation: &Solicitation, token: &str) -> Result<(), ()> {
+        let (expiry, signature) = token.split_once('.').ok_or(())?;
+        let expiry: i64 = expiry.parse().map_err(|_| ())?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ())?;
+
+        if expiry < Utc::now().timestamp() {
+            return Err(());
+        }
+
+        let data = Self::signed_data(expiry, session, solicitation)?;
+        self.assertion.verify(&data, &signature)
+    }
+
+    /// Validate the token in the [`FIELD`] of the urlencoded body of a request.
+    ///
+    /// Requests without a body, without the field or with more than one token are rejected.
+    ///
+    /// [`FIELD`]: #associatedconstant.FIELD
+    pub fn check<W: WebRequest>(
+        &self, request: &mut W, session: &str, solicitation: &Solicitation,
+    ) -> Result<(), ()> {
+        let body = request.urlbody().map_err(|_| ())?;
+        let token = body.unique_value(Self::FIELD).ok_or(())?;
+        self.validate(session, solicitation, &token)
+    }
+
+    fn signature(&self, expiry: i64, session: &str, solicitation: &Solicitation) -> Result<Vec<u8>, ()> {
+        let data = Self::signed_data(expiry, session, solicitation)?;
+        self.assertion.signature(&data)
+    }
+
+    fn signed_data(expiry: i64, session: &str, solicitation: &Solicitation) -> Result<Vec<u8>, ()> {
+        let grant = solicitation.pre_grant();
+        let bound = (
+            "csrf",
+            expiry,
+            session,
+            grant.client_id.as_str(),
+            grant.redirect_uri.as_str(),
+            grant.scope.to_string(),
+            solicitation.state(),
+        );
+        rmp_serde::to_vec(&bound).map_err(|_| ())
+    }
+}
@@ -8,16 +8,27 @@
 //! [`Endpoint`]: ../../endpoint/trait.Endpoint.html
 
 use crate::primitives::authorizer::Authorizer;
-use crate::primitives::issuer::Issuer;
-use crate::primitives::registrar::Registrar;
+use crate::primitives::binding::Binding;
+use crate::primitives::clock::Clock;
+use crate::primitives::consent::ConsentStore;
+use crate::primitives::generator::{Assertion, RandomGenerator};
+use crate::primitives::issuer::{Issuer, TokenMap, TokenSigner};
+use crate::primitives::registrar::{ClientTrust, Registrar};
 use crate::primitives::scope::Scope;
 
 use crate::endpoint::{AccessTokenFlow, AuthorizationFlow, ResourceFlow, RefreshFlow, ClientCredentialsFlow};
-use crate::endpoint::{Endpoint, Extension, OAuthError, PreGrant, Template, Scopes};
-use crate::endpoint::{OwnerConsent, OwnerSolicitor, Solicitation};
-use crate::endpoint::WebRequest;
+use crate::endpoint::{
+    Endpoint, ErrorMapping, Extension, FlowError, FlowErrorDetail, OAuthError, PreGrant, Template,
+    Scopes, ScopeDescriptions,
+};
+use crate::endpoint::{OwnerConsent, OwnerSolicitor, ProofVerifier, SessionValidator, Solicitation};
+use crate::endpoint::{MetadataFlow, Metrics, ServerMetadata, WebRequest};
+#[cfg(feature = "ed25519")]
+use crate::endpoint::JwkSet;
 
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Errors either caused by the underlying web types or the library.
 #[derive(Debug)]
@@ -112,9 +123,100 @@ pub struct Generic<R, A, I, S = Vacant, C = Vacant, L = Vacant> {
     pub scopes: C,
 
     /// Creates responses, or `Vacant` if `Default::default` is applicable.
+    ///
+    /// Any `FnMut() -> Response` works as well, and an [`FnResponse`] tailors the response to its
+    /// template.
     pub response: L,
 }
 
+/// The optional hooks of a [`Hooked`] endpoint.
+///
+/// Each hook corresponds to the method of the same name of [`Endpoint`]. Hooks that are `Vacant`
+/// or `None` are not used, the wrapped endpoint then decides as if there were no hooks.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate oxide_auth;
+/// use oxide_auth::frontends::simple::endpoint::Hooks;
+/// use oxide_auth::primitives::consent::ConsentMap;
+///
+/// let hooks = Hooks::new()
+///     .consent_store(ConsentMap::new())
+///     .parameter_limit(32)
+///     .opaque_errors(true);
+/// ```
+///
+/// [`Hooked`]: struct.Hooked.html
+/// [`Endpoint`]: ../../../endpoint/trait.Endpoint.html
+pub struct Hooks<V = Vacant, P = Vacant, K = Vacant, T = Vacant> {
+    /// Checks the sessions of valid tokens, or `Vacant` to accept every valid token.
+    pub session_validator: V,
+
+    /// Verifies the proof of possession of bound tokens, or `None` to deny them.
+    pub proof_verifier: Option<P>,
+
+    /// Remembers the consent of owners, or `Vacant` to ask the owner every time.
+    pub consent_store: K,
+
+    /// The source of the current time, or `Vacant` to use the system time.
+    pub clock: T,
+
+    /// Descriptions of scope tokens presented to the owner solicitor.
+    pub scope_descriptions: Option<ScopeDescriptions>,
+
+    /// Replacements for the error codes reported to clients, applied in `on_flow_error`.
+    pub error_mapping: Option<ErrorMapping>,
+
+    /// Answer failed primitives with a generic response instead of an error.
+    pub opaque_errors: bool,
+
+    /// The maximum number of query or body parameters accepted in a request.
+    pub parameter_limit: Option<usize>,
+
+    /// Whether failed primitives are only temporarily unavailable, and when to retry.
+    pub temporarily_unavailable: Option<Arc<dyn Fn() -> Option<Duration> + Send + Sync>>,
+}
+
+/// Fluent construction of a [`Generic`] endpoint.
+///
+/// All primitives that are not set are [`Vacant`]. The scopes protected by the endpoint are
+/// collected into a list, which is empty by default and then denies access to all resources. After
+/// configuring [`Hooks`], the built endpoint is wrapped in a [`Hooked`] that provides them.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate oxide_auth;
+/// use oxide_auth::frontends::simple::endpoint::EndpointBuilder;
+/// use oxide_auth::primitives::{
+///     authorizer::AuthMap,
+///     generator::RandomGenerator,
+///     registrar::ClientMap,
+/// };
+///
+/// let endpoint = EndpointBuilder::new()
+///     .registrar(ClientMap::new())
+///     .authorizer(AuthMap::new(RandomGenerator::new(16)))
+///     .with_random_issuer(16)
+///     .scope("default".parse().unwrap())
+///     .build();
+/// ```
+///
+/// [`Generic`]: struct.Generic.html
+/// [`Vacant`]: struct.Vacant.html
+/// [`Hooks`]: struct.Hooks.html
+/// [`Hooked`]: struct.Hooked.html
+pub struct EndpointBuilder<R = Vacant, A = Vacant, I = Vacant, S = Vacant, L = Vacant, H = Vacant> {
+    registrar: R,
+    authorizer: A,
+    issuer: I,
+    solicitor: S,
+    scopes: Vec<Scope>,
+    response: L,
+    hooks: H,
+}
+
 /// A simple wrapper around an Endpoint to change it's error type into anything `Into`-able.
 pub struct ErrorInto<E, Error>(E, PhantomData<Error>);
 
@@ -125,6 +227,45 @@ impl<E, Error> ErrorInto<E, Error> {
     }
 }
 
+/// A wrapper around an Endpoint serving the documents describing the server.
+///
+/// Provides the metadata to a [`MetadataFlow`] and, with the `ed25519` feature, the key set to a
+/// [`JwksFlow`]. All other primitives and options are those of the wrapped endpoint, so a single
+/// endpoint can be routed the well-known paths in addition to its other flows.
+///
+/// [`MetadataFlow`]: ../../endpoint/struct.MetadataFlow.html
+/// [`JwksFlow`]: ../../endpoint/struct.JwksFlow.html
+pub struct WellKnown<E> {
+    inner: E,
+    metadata: ServerMetadata,
+    #[cfg(feature = "ed25519")]
+    jwks: Option<JwkSet>,
+}
+
+/// A wrapper around an Endpoint counting the outcomes of its flows.
+///
+/// Provides the [`Metrics`] to all flows, all other primitives and options are those of the
+/// wrapped endpoint. Endpoints that are not wrapped record no metrics at all.
+///
+/// [`Metrics`]: ../../endpoint/trait.Metrics.html
+pub struct Metered<E, M> {
+    inner: E,
+    metrics: M,
+}
+
+/// A wrapper around an Endpoint providing its optional hooks.
+///
+/// The configured [`Hooks`] take precedence, all other hooks and all primitives are those of the
+/// wrapped endpoint. Usually created with [`Generic::with_hooks`] or an [`EndpointBuilder`].
+///
+/// [`Hooks`]: struct.Hooks.html
+/// [`Generic::with_hooks`]: struct.Generic.html#method.with_hooks
+/// [`EndpointBuilder`]: struct.EndpointBuilder.html
+pub struct Hooked<E, H = Hooks> {
+    inner: E,
+    hooks: H,
+}
+
 /// Marker struct if some primitive is not provided.
 ///
 /// Used in place of other primitives when those are not provided. The exact semantics depend on
@@ -149,6 +290,15 @@ impl<E, Error> ErrorInto<E, Error> {
 /// optional reference trait for all solicitors in one way but in a different way for the `None`
 /// solicitor.
 ///
+/// ## ProofVerifier
+///
+/// A verifier rejecting all proofs, so that bound tokens never grant access. Like the solicitor,
+/// this can not be expressed as an optional reference trait.
+///
+/// ## SessionValidator, ConsentStore, Clock
+///
+/// The hook is not used, as if the endpoint did not provide it.
+///
 /// ## Scopes
 ///
 /// Returns an empty list of scopes, effictively denying all requests since at least one scope
@@ -161,6 +311,53 @@ pub struct Vacant;
 /// A simple wrapper for functions and lambdas to be used as solicitors.
 pub struct FnSolicitor<F>(pub F);
 
+/// A simple wrapper for functions and lambdas to be used as response creators.
+///
+/// Other than a plain `fn() -> Response`, the function gets to see the request and the template
+/// of the response. It can tailor the prototype response to the kind of response, for example by
+/// the `status` of the template, and may keep state between calls.
+///
+/// ## Example
+///
+/// ```
+/// # extern crate oxide_auth;
+/// use oxide_auth::endpoint::{ResponseStatus, Template, WebRequest};
+/// use oxide_auth::frontends::simple::endpoint::{FnResponse, ResponseCreator};
+///
+/// #[derive(Default)]
+/// struct Response {
+///     cache_control: Option<&'static str>,
+/// }
+///
+/// fn prototype<W>(_: &mut W, kind: Template) -> Response {
+///     match kind.status() {
+///         ResponseStatus::Ok => Response { cache_control: Some("private") },
+///         _ => Response::default(),
+///     }
+/// }
+///
+/// # fn creator<W: WebRequest<Response = Response>>() -> impl ResponseCreator<W> {
+/// FnResponse(prototype::<W>)
+/// # }
+/// ```
+pub struct FnResponse<F>(pub F);
+
+/// Choose a solicitor depending on how far the server trusts the client.
+///
+/// First-party clients are usually allowed to skip the consent page, the first solicitor could for
+/// example authorize the currently logged in owner right away. All other clients are handed to the
+/// second solicitor, which presents the consent page to the owner. The trust in a client is
+/// determined by the registrar, see [`Client::with_trust`].
+///
+/// [`Client::with_trust`]: ../../../primitives/registrar/struct.Client.html#method.with_trust
+pub struct TieredSolicitor<F, T> {
+    /// The solicitor for first-party clients.
+    pub first_party: F,
+
+    /// The solicitor for third-party clients.
+    pub third_party: T,
+}
+
 /// Use a predetermined grant and owner as solicitor.
 ///
 /// Convenience wrapper when the owner and her/his consent to a grant can be identified without
@@ -238,6 +435,36 @@ pub trait OptIssuer {
     fn opt_mut(&mut self) -> Option<&mut dyn Issuer>;
 }
 
+/// Like `AsMut<SessionValidator +'_>` but in a way that is expressible.
+///
+/// You are not supposed to need to implement this. See [`OptRegistrar`] for the reasoning.
+///
+/// [`OptRegistrar`]: trait.OptRegistrar.html
+pub trait OptSessionValidator {
+    /// Reference this mutably as a `SessionValidator` or `Option::None`.
+    fn opt_mut(&mut self) -> Option<&mut dyn SessionValidator>;
+}
+
+/// Like `AsMut<ConsentStore +'_>` but in a way that is expressible.
+///
+/// You are not supposed to need to implement this. See [`OptRegistrar`] for the reasoning.
+///
+/// [`OptRegistrar`]: trait.OptRegistrar.html
+pub trait OptConsentStore {
+    /// Reference this mutably as a `ConsentStore` or `Option::None`.
+    fn opt_mut(&mut self) -> Option<&mut dyn ConsentStore>;
+}
+
+/// Like `AsRef<Clock +'_>` but in a way that is expressible.
+///
+/// You are not supposed to need to implement this. See [`OptRegistrar`] for the reasoning.
+///
+/// [`OptRegistrar`]: trait.OptRegistrar.html
+pub trait OptClock {
+    /// Reference this as a `Clock` or `Option::None`.
+    fn opt_ref(&self) -> Option<&dyn Clock>;
+}
+
 /// Independent component responsible for instantiating responses.
 pub trait ResponseCreator<W: WebRequest> {
     /// Will only be called at most once per flow execution.
@@ -448,6 +675,25 @@ impl<R, A, I, O, C, L> Generic<R, A, I, O, C, L> {
         }
     }
 
+    /// Change the used response creator.
+    pub fn with_response<N>(self, new_response: N) -> Generic<R, A, I, O, C, N> {
+        Generic {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: new_response,
+        }
+    }
+
+    /// Provide the optional hooks, usually [`Hooks`].
+    ///
+    /// [`Hooks`]: struct.Hooks.html
+    pub fn with_hooks<H>(self, hooks: H) -> Hooked<Self, H> {
+        Hooked::new(self, hooks)
+    }
+
     /// Create an authorization flow.
     ///
     /// Opposed to `AuthorizationFlow::prepare` this statically ensures that the construction
@@ -496,6 +742,25 @@ impl<R, A, I, O, C, L> Generic<R, A, I, O, C, L> {
         }
     }
 
+    /// Create a client credentials flow.
+    ///
+    /// Opposed to `ClientCredentialsFlow::prepare` this statically ensures that the construction
+    /// succeeds. The owner solicitor of this endpoint is never consulted, the client is treated as
+    /// its own resource owner and no refresh token is issued unless explicitly allowed.
+    pub fn client_credentials_flow<W: WebRequest>(self) -> ClientCredentialsFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+        R: Registrar,
+        I: Issuer,
+    {
+        let mut flow = match ClientCredentialsFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        };
+        flow.solicit_owner(false);
+        flow
+    }
+
     /// Create a resource access flow.
     ///
     /// Opposed to `ResourceFlow::prepare` this statically ensures that the construction succeeds.
@@ -521,181 +786,1088 @@ impl<R, A, I, O, C, L> Generic<R, A, I, O, C, L> {
     }
 }
 
-impl<W: WebRequest> Error<W> {
-    /// Convert into a single error type.
-    ///
-    /// Note that the additional information whether the error occurred in the web components or
-    /// during the flow needs to be implicitely contained in the types. Otherwise, this information
-    /// is lost and you should use or provide a `From<Error<W>>` implementation instead. This
-    /// method is still useful for frontends providing a standard error type that interacts with
-    /// their web server library.
-    pub fn pack<P>(self) -> P
-    where
-        OAuthError: Into<P>,
-        W::Error: Into<P>,
-    {
-        match self {
-            Error::Web(err) => err.into(),
-            Error::OAuth(oauth) => oauth.into(),
+impl Hooks {
+    /// Start without any hooks.
+    pub fn new() -> Self {
+        Hooks {
+            session_validator: Vacant,
+            proof_verifier: None,
+            consent_store: Vacant,
+            clock: Vacant,
+            scope_descriptions: None,
+            error_mapping: None,
+            opaque_errors: false,
+            parameter_limit: None,
+            temporarily_unavailable: None,
         }
     }
 }
 
-impl<E, Error, W> Endpoint<W> for ErrorInto<E, Error>
-where
-    E: Endpoint<W>,
-    E::Error: Into<Error>,
-    W: WebRequest,
-{
-    type Error = Error;
-
-    fn registrar(&self) -> Option<&dyn Registrar> {
-        self.0.registrar()
-    }
-
-    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
-        self.0.authorizer_mut()
-    }
-
-    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
-        self.0.issuer_mut()
-    }
-
-    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
-        self.0.owner_solicitor()
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks::new()
     }
+}
 
-    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
-        self.0.scopes()
+impl<V, P, K, T> Hooks<V, P, K, T> {
+    /// Check the sessions of valid tokens in the resource flow.
+    pub fn session_validator<N>(self, session_validator: N) -> Hooks<N, P, K, T> {
+        Hooks {
+            session_validator,
+            proof_verifier: self.proof_verifier,
+            consent_store: self.consent_store,
+            clock: self.clock,
+            scope_descriptions: self.scope_descriptions,
+            error_mapping: self.error_mapping,
+            opaque_errors: self.opaque_errors,
+            parameter_limit: self.parameter_limit,
+            temporarily_unavailable: self.temporarily_unavailable,
+        }
     }
 
-    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
-        self.0.response(request, kind).map_err(Into::into)
+    /// Verify the proof of possession accompanying bound tokens in the resource flow.
+    pub fn proof_verifier<N>(self, proof_verifier: N) -> Hooks<V, N, K, T> {
+        Hooks {
+            session_validator: self.session_validator,
+            proof_verifier: Some(proof_verifier),
+            consent_store: self.consent_store,
+            clock: self.clock,
+            scope_descriptions: self.scope_descriptions,
+            error_mapping: self.error_mapping,
+            opaque_errors: self.opaque_errors,
+            parameter_limit: self.parameter_limit,
+            temporarily_unavailable: self.temporarily_unavailable,
+        }
     }
 
-    fn error(&mut self, err: OAuthError) -> Self::Error {
-        self.0.error(err).into()
+    /// Remember the consent of owners.
+    pub fn consent_store<N>(self, consent_store: N) -> Hooks<V, P, N, T> {
+        Hooks {
+            session_validator: self.session_validator,
+            proof_verifier: self.proof_verifier,
+            consent_store,
+            clock: self.clock,
+            scope_descriptions: self.scope_descriptions,
+            error_mapping: self.error_mapping,
+            opaque_errors: self.opaque_errors,
+            parameter_limit: self.parameter_limit,
+            temporarily_unavailable: self.temporarily_unavailable,
+        }
     }
 
-    fn web_error(&mut self, err: W::Error) -> Self::Error {
-        self.0.web_error(err).into()
+    /// Take the current time from a clock, usually the one of the primitives.
+    pub fn clock<N>(self, clock: N) -> Hooks<V, P, K, N> {
+        Hooks {
+            session_validator: self.session_validator,
+            proof_verifier: self.proof_verifier,
+            consent_store: self.consent_store,
+            clock,
+            scope_descriptions: self.scope_descriptions,
+            error_mapping: self.error_mapping,
+            opaque_errors: self.opaque_errors,
+            parameter_limit: self.parameter_limit,
+            temporarily_unavailable: self.temporarily_unavailable,
+        }
     }
 
-    fn extension(&mut self) -> Option<&mut dyn Extension> {
-        self.0.extension()
+    /// Describe scope tokens to the owner solicitor.
+    pub fn scope_descriptions(self, scope_descriptions: ScopeDescriptions) -> Self {
+        Hooks {
+            scope_descriptions: Some(scope_descriptions),
+            ..self
+        }
     }
-}
-
-impl<W, R, A, I, O, C, L> Endpoint<W> for Generic<R, A, I, O, C, L>
-where
-    W: WebRequest,
-    R: OptRegistrar,
-    A: OptAuthorizer,
-    I: OptIssuer,
-    O: OwnerSolicitor<W>,
-    C: Scopes<W>,
-    L: ResponseCreator<W>,
-{
-    type Error = Error<W>;
 
-    fn registrar(&self) -> Option<&dyn Registrar> {
-        self.registrar.opt_ref()
+    /// Replace the error codes reported to clients.
+    pub fn error_mapping(self, error_mapping: ErrorMapping) -> Self {
+        Hooks {
+            error_mapping: Some(error_mapping),
+            ..self
+        }
     }
 
-    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
-        self.authorizer.opt_mut()
+    /// Answer failed primitives with a generic response instead of an error.
+    pub fn opaque_errors(self, opaque_errors: bool) -> Self {
+        Hooks {
+            opaque_errors,
+            ..self
+        }
     }
 
-    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
-        self.issuer.opt_mut()
+    /// Reject requests with more query or body parameters than the limit.
+    pub fn parameter_limit(self, parameter_limit: usize) -> Self {
+        Hooks {
+            parameter_limit: Some(parameter_limit),
+            ..self
+        }
     }
 
-    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
-        Some(&mut self.solicitor)
+    /// Report failed primitives as transient while the check returns a duration.
+    ///
+    /// The check is called after a primitive failed and returns when clients should retry, or
+    /// `None` if the failure is permanent. Primitives backed by remote storage usually share their
+    /// connection state with it, so that the endpoint answers with 503 while the storage is
+    /// unreachable.
+    ///
+    /// ```
+    /// # extern crate oxide_auth;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::time::Duration;
+    /// use oxide_auth::frontends::simple::endpoint::Hooks;
+    ///
+    /// // Cleared by the primitives when their database connection is lost.
+    /// let connected = Arc::new(AtomicBool::new(true));
+    /// let check = connected.clone();
+    /// let hooks = Hooks::new().temporarily_unavailable(move || {
+    ///     if check.load(Ordering::Relaxed) {
+    ///         None
+    ///     } else {
+    ///         Some(Duration::from_secs(30))
+    ///     }
+    /// });
+    /// ```
+    pub fn temporarily_unavailable<F>(self, check: F) -> Self
+    where
+        F: Fn() -> Option<Duration> + Send + Sync + 'static,
+    {
+        Hooks {
+            temporarily_unavailable: Some(Arc::new(check)),
+            ..self
+        }
     }
+}
 
-    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
-        Some(&mut self.scopes)
+impl<E> WellKnown<E> {
+    /// Serve the metadata in addition to the flows of the endpoint.
+    pub fn new(endpoint: E, metadata: ServerMetadata) -> Self {
+        WellKnown {
+            inner: endpoint,
+            metadata,
+            #[cfg(feature = "ed25519")]
+            jwks: None,
+        }
     }
 
-    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
-        Ok(self.response.create(request, kind))
+    /// Also serve the key set, usually the one of the `TokenSigner` of the endpoint.
+    #[cfg(feature = "ed25519")]
+    pub fn with_jwks(self, jwks: JwkSet) -> Self {
+        WellKnown {
+            jwks: Some(jwks),
+            ..self
+        }
     }
 
-    fn error(&mut self, err: OAuthError) -> Error<W> {
-        Error::OAuth(err)
+    /// A mutable reference to the served metadata.
+    pub fn metadata_mut(&mut self) -> &mut ServerMetadata {
+        &mut self.metadata
     }
 
-    fn web_error(&mut self, err: W::Error) -> Error<W> {
-        Error::Web(err)
+    /// Create a metadata flow.
+    ///
+    /// Opposed to `MetadataFlow::prepare` this statically ensures that the construction succeeds.
+    pub fn metadata_flow<W: WebRequest>(self) -> MetadataFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+    {
+        match MetadataFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        }
     }
 }
 
-impl<T: Registrar> OptRegistrar for T {
-    fn opt_ref(&self) -> Option<&dyn Registrar> {
-        Some(self)
+impl<E, M: Metrics> Metered<E, M> {
+    /// Record the outcomes of the flows of the endpoint.
+    ///
+    /// Pass an `Arc` of the metrics, such as an `Arc<MetricCounters>`, to read them while the
+    /// endpoint is in use.
+    pub fn new(endpoint: E, metrics: M) -> Self {
+        Metered {
+            inner: endpoint,
+            metrics,
+        }
     }
-}
 
-impl<T: Authorizer> OptAuthorizer for T {
-    fn opt_mut(&mut self) -> Option<&mut dyn Authorizer> {
-        Some(self)
+    /// A reference to the recorded metrics.
+    pub fn metrics(&self) -> &M {
+        &self.metrics
     }
-}
 
-impl<T: Issuer> OptIssuer for T {
-    fn opt_mut(&mut self) -> Option<&mut dyn Issuer> {
-        Some(self)
+    /// Unwrap the endpoint, dropping the metrics.
+    pub fn into_inner(self) -> E {
+        self.inner
     }
 }
 
-impl OptRegistrar for Vacant {
-    fn opt_ref(&self) -> Option<&dyn Registrar> {
-        Option::None
+impl<E, H> Hooked<E, H> {
+    /// Provide the hooks to the endpoint.
+    pub fn new(endpoint: E, hooks: H) -> Self {
+        Hooked {
+            inner: endpoint,
+            hooks,
+        }
     }
-}
 
-impl OptAuthorizer for Vacant {
-    fn opt_mut(&mut self) -> Option<&mut dyn Authorizer> {
-        Option::None
+    /// A reference to the wrapped endpoint.
+    pub fn inner(&self) -> &E {
+        &self.inner
     }
-}
 
-impl OptIssuer for Vacant {
-    fn opt_mut(&mut self) -> Option<&mut dyn Issuer> {
-        Option::None
+    /// A mutable reference to the wrapped endpoint.
+    pub fn inner_mut(&mut self) -> &mut E {
+        &mut self.inner
     }
-}
 
-impl<W: WebRequest> OwnerSolicitor<W> for Vacant {
-    fn check_consent(&mut self, _: &mut W, _: Solicitation) -> OwnerConsent<W::Response> {
-        OwnerConsent::Denied
+    /// A reference to the hooks.
+    pub fn hooks(&self) -> &H {
+        &self.hooks
     }
-}
 
-impl<W: WebRequest> Scopes<W> for Vacant {
-    fn scopes(&mut self, _: &mut W) -> &[Scope] {
-        const NO_SCOPES: [Scope; 0] = [];
-        &NO_SCOPES
+    /// A mutable reference to the hooks.
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
     }
-}
 
-impl<W, F> OwnerSolicitor<W> for FnSolicitor<F>
-where
-    W: WebRequest,
-    F: FnMut(&mut W, Solicitation) -> OwnerConsent<W::Response>,
-{
-    fn check_consent(
-        &mut self, request: &mut W, solicitation: Solicitation,
-    ) -> OwnerConsent<W::Response> {
-        (self.0)(request, solicitation)
+    /// Unwrap the endpoint, dropping the hooks.
+    pub fn into_inner(self) -> E {
+        self.inner
     }
 }
 
-impl<W: WebRequest> OwnerSolicitor<W> for ApprovedGrant {
-    /// Approve if the grant matches *exactly*.
+impl<R, A, I, O, C, L, H> Hooked<Generic<R, A, I, O, C, L>, H> {
+    /// Create an authorization flow.
     ///
-    /// That is, `client_id`, `redirect_uri`, and `scope` of the pre-grant are all equivalent. In
+    /// Opposed to `AuthorizationFlow::prepare` this statically ensures that the construction
+    /// succeeds.
+    pub fn authorization_flow<W: WebRequest>(self) -> AuthorizationFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+        R: Registrar,
+        A: Authorizer,
+    {
+        match AuthorizationFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Create an access token flow.
+    ///
+    /// Opposed to `AccessTokenFlow::prepare` this statically ensures that the construction
+    /// succeeds.
+    pub fn access_token_flow<W: WebRequest>(self) -> AccessTokenFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+        R: Registrar,
+        A: Authorizer,
+        I: Issuer,
+    {
+        match AccessTokenFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Create a token refresh flow.
+    ///
+    /// Opposed to `RefreshFlow::prepare` this statically ensures that the construction succeeds.
+    pub fn refresh_flow<W: WebRequest>(self) -> RefreshFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+        R: Registrar,
+        I: Issuer,
+    {
+        match RefreshFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Create a client credentials flow.
+    ///
+    /// Opposed to `ClientCredentialsFlow::prepare` this statically ensures that the construction
+    /// succeeds. As with `Generic`, the owner solicitor is never consulted.
+    pub fn client_credentials_flow<W: WebRequest>(self) -> ClientCredentialsFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+        R: Registrar,
+        I: Issuer,
+    {
+        let mut flow = match ClientCredentialsFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        };
+        flow.solicit_owner(false);
+        flow
+    }
+
+    /// Create a resource access flow.
+    ///
+    /// Opposed to `ResourceFlow::prepare` this statically ensures that the construction succeeds.
+    pub fn resource_flow<W: WebRequest>(self) -> ResourceFlow<Self, W>
+    where
+        Self: Endpoint<W>,
+        I: Issuer,
+    {
+        match ResourceFlow::prepare(self) {
+            Ok(flow) => flow,
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+impl EndpointBuilder {
+    /// Start with an endpoint without any primitives.
+    pub fn new() -> Self {
+        EndpointBuilder {
+            registrar: Vacant,
+            authorizer: Vacant,
+            issuer: Vacant,
+            solicitor: Vacant,
+            scopes: Vec::new(),
+            response: Vacant,
+            hooks: Vacant,
+        }
+    }
+}
+
+impl Default for EndpointBuilder {
+    fn default() -> Self {
+        EndpointBuilder::new()
+    }
+}
+
+impl<R, A, I, S, L, H> EndpointBuilder<R, A, I, S, L, H> {
+    /// Use a registrar.
+    pub fn registrar<N>(self, registrar: N) -> EndpointBuilder<N, A, I, S, L, H> {
+        EndpointBuilder {
+            registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: self.response,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Use an authorizer.
+    pub fn authorizer<N>(self, authorizer: N) -> EndpointBuilder<R, N, I, S, L, H> {
+        EndpointBuilder {
+            registrar: self.registrar,
+            authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: self.response,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Use an issuer.
+    pub fn issuer<N>(self, issuer: N) -> EndpointBuilder<R, A, N, S, L, H> {
+        EndpointBuilder {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: self.response,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Use an in-memory issuer with random tokens of `len` bytes.
+    ///
+    /// This is a [`TokenMap`] with a [`RandomGenerator`].
+    ///
+    /// [`TokenMap`]: ../../../primitives/issuer/struct.TokenMap.html
+    /// [`RandomGenerator`]: ../../../primitives/generator/struct.RandomGenerator.html
+    pub fn with_random_issuer(
+        self, len: usize,
+    ) -> EndpointBuilder<R, A, TokenMap<RandomGenerator>, S, L, H> {
+        self.issuer(TokenMap::new(RandomGenerator::new(len)))
+    }
+
+    /// Use an issuer signing its tokens with the key.
+    ///
+    /// This is a [`TokenSigner`], whose tokens can not be revoked.
+    ///
+    /// [`TokenSigner`]: ../../../primitives/issuer/struct.TokenSigner.html
+    pub fn with_signer(self, key: Assertion) -> EndpointBuilder<R, A, TokenSigner, S, L, H> {
+        self.issuer(TokenSigner::new(key))
+    }
+
+    /// Use a solicitor to determine owner consent.
+    pub fn solicitor<N>(self, solicitor: N) -> EndpointBuilder<R, A, I, N, L, H> {
+        EndpointBuilder {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor,
+            scopes: self.scopes,
+            response: self.response,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Add a scope of which a token must fulfill at least one to access resources.
+    pub fn scope(mut self, scope: Scope) -> Self {
+        self.scopes.push(scope);
+        self
+    }
+
+    /// Use a creator of responses.
+    pub fn response<N>(self, response: N) -> EndpointBuilder<R, A, I, S, N, H> {
+        EndpointBuilder {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response,
+            hooks: self.hooks,
+        }
+    }
+
+    /// Provide hooks, usually [`Hooks`], to the built endpoint.
+    ///
+    /// [`Hooks`]: struct.Hooks.html
+    pub fn hooks<N>(self, hooks: N) -> EndpointBuilder<R, A, I, S, L, N> {
+        EndpointBuilder {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: self.response,
+            hooks,
+        }
+    }
+}
+
+impl<R, A, I, S, L> EndpointBuilder<R, A, I, S, L> {
+    /// Finish the endpoint.
+    pub fn build(self) -> Generic<R, A, I, S, Vec<Scope>, L> {
+        Generic {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: self.response,
+        }
+    }
+}
+
+impl<R, A, I, S, L, V, P, K, T> EndpointBuilder<R, A, I, S, L, Hooks<V, P, K, T>> {
+    /// Finish the endpoint, providing the hooks.
+    #[allow(clippy::type_complexity)]
+    pub fn build(self) -> Hooked<Generic<R, A, I, S, Vec<Scope>, L>, Hooks<V, P, K, T>> {
+        let generic = Generic {
+            registrar: self.registrar,
+            authorizer: self.authorizer,
+            issuer: self.issuer,
+            solicitor: self.solicitor,
+            scopes: self.scopes,
+            response: self.response,
+        };
+        generic.with_hooks(self.hooks)
+    }
+}
+
+impl<W: WebRequest> Error<W> {
+    /// Convert into a single error type.
+    ///
+    /// Note that the additional information whether the error occurred in the web components or
+    /// during the flow needs to be implicitely contained in the types. Otherwise, this information
+    /// is lost and you should use or provide a `From<Error<W>>` implementation instead. This
+    /// method is still useful for frontends providing a standard error type that interacts with
+    /// their web server library.
+    pub fn pack<P>(self) -> P
+    where
+        OAuthError: Into<P>,
+        W::Error: Into<P>,
+    {
+        match self {
+            Error::Web(err) => err.into(),
+            Error::OAuth(oauth) => oauth.into(),
+        }
+    }
+}
+
+impl<E, Error, W> Endpoint<W> for ErrorInto<E, Error>
+where
+    E: Endpoint<W>,
+    E::Error: Into<Error>,
+    W: WebRequest,
+{
+    type Error = Error;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.0.registrar()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.0.authorizer_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.0.issuer_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
+        self.0.owner_solicitor()
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
+        self.0.scopes()
+    }
+
+    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
+        self.0.response(request, kind).map_err(Into::into)
+    }
+
+    fn error(&mut self, err: OAuthError) -> Self::Error {
+        self.0.error(err).into()
+    }
+
+    fn web_error(&mut self, err: W::Error) -> Self::Error {
+        self.0.web_error(err).into()
+    }
+
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        self.0.extension()
+    }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        self.0.session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<W>> {
+        self.0.proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.0.scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.0.on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.0.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        self.0.temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.0.parameter_limit()
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        self.0.server_metadata()
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        self.0.jwks()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        self.0.consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        self.0.metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        self.0.clock()
+    }
+}
+
+impl<E, W> Endpoint<W> for WellKnown<E>
+where
+    E: Endpoint<W>,
+    W: WebRequest,
+{
+    type Error = E::Error;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.inner.registrar()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.inner.authorizer_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.inner.issuer_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
+        self.inner.owner_solicitor()
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
+        self.inner.scopes()
+    }
+
+    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
+        self.inner.response(request, kind)
+    }
+
+    fn error(&mut self, err: OAuthError) -> Self::Error {
+        self.inner.error(err)
+    }
+
+    fn web_error(&mut self, err: W::Error) -> Self::Error {
+        self.inner.web_error(err)
+    }
+
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        self.inner.extension()
+    }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        self.inner.session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<W>> {
+        self.inner.proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.inner.scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.inner.on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.inner.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        self.inner.temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.inner.parameter_limit()
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        Some(&self.metadata)
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        self.jwks.as_ref()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        self.inner.consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        self.inner.metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        self.inner.clock()
+    }
+}
+
+impl<E, M, W> Endpoint<W> for Metered<E, M>
+where
+    E: Endpoint<W>,
+    M: Metrics,
+    W: WebRequest,
+{
+    type Error = E::Error;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.inner.registrar()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.inner.authorizer_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.inner.issuer_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
+        self.inner.owner_solicitor()
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
+        self.inner.scopes()
+    }
+
+    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
+        self.inner.response(request, kind)
+    }
+
+    fn error(&mut self, err: OAuthError) -> Self::Error {
+        self.inner.error(err)
+    }
+
+    fn web_error(&mut self, err: W::Error) -> Self::Error {
+        self.inner.web_error(err)
+    }
+
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        self.inner.extension()
+    }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        self.inner.session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<W>> {
+        self.inner.proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.inner.scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.inner.on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.inner.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        self.inner.temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.inner.parameter_limit()
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        self.inner.server_metadata()
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        self.inner.jwks()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        self.inner.consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        Some(&self.metrics)
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        self.inner.clock()
+    }
+}
+
+impl<W, R, A, I, O, C, L> Endpoint<W> for Generic<R, A, I, O, C, L>
+where
+    W: WebRequest,
+    R: OptRegistrar,
+    A: OptAuthorizer,
+    I: OptIssuer,
+    O: OwnerSolicitor<W>,
+    C: Scopes<W>,
+    L: ResponseCreator<W>,
+{
+    type Error = Error<W>;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.registrar.opt_ref()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.authorizer.opt_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.issuer.opt_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
+        Some(&mut self.solicitor)
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
+        Some(&mut self.scopes)
+    }
+
+    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
+        Ok(self.response.create(request, kind))
+    }
+
+    fn error(&mut self, err: OAuthError) -> Error<W> {
+        Error::OAuth(err)
+    }
+
+    fn web_error(&mut self, err: W::Error) -> Error<W> {
+        Error::Web(err)
+    }
+}
+
+impl<E, W, V, P, K, T> Endpoint<W> for Hooked<E, Hooks<V, P, K, T>>
+where
+    E: Endpoint<W>,
+    W: WebRequest,
+    V: OptSessionValidator,
+    P: ProofVerifier<W>,
+    K: OptConsentStore,
+    T: OptClock,
+{
+    type Error = E::Error;
+
+    fn registrar(&self) -> Option<&dyn Registrar> {
+        self.inner.registrar()
+    }
+
+    fn authorizer_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        self.inner.authorizer_mut()
+    }
+
+    fn issuer_mut(&mut self) -> Option<&mut dyn Issuer> {
+        self.inner.issuer_mut()
+    }
+
+    fn owner_solicitor(&mut self) -> Option<&mut dyn OwnerSolicitor<W>> {
+        self.inner.owner_solicitor()
+    }
+
+    fn scopes(&mut self) -> Option<&mut dyn Scopes<W>> {
+        self.inner.scopes()
+    }
+
+    fn response(&mut self, request: &mut W, kind: Template) -> Result<W::Response, Self::Error> {
+        self.inner.response(request, kind)
+    }
+
+    fn error(&mut self, err: OAuthError) -> Self::Error {
+        self.inner.error(err)
+    }
+
+    fn web_error(&mut self, err: W::Error) -> Self::Error {
+        self.inner.web_error(err)
+    }
+
+    fn extension(&mut self) -> Option<&mut dyn Extension> {
+        self.inner.extension()
+    }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        match self.hooks.session_validator.opt_mut() {
+            Some(validator) => Some(validator),
+            None => self.inner.session_validator(),
+        }
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<W>> {
+        match &mut self.hooks.proof_verifier {
+            Some(verifier) => Some(verifier),
+            None => self.inner.proof_verifier(),
+        }
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.hooks
+            .scope_descriptions
+            .as_ref()
+            .or_else(|| self.inner.scope_descriptions())
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.inner.on_flow_error(error, detail);
+        if let Some(mapping) = &self.hooks.error_mapping {
+            mapping.apply(detail);
+        }
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.hooks.opaque_errors || self.inner.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        match &self.hooks.temporarily_unavailable {
+            Some(check) => check().or_else(|| self.inner.temporarily_unavailable()),
+            None => self.inner.temporarily_unavailable(),
+        }
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.hooks
+            .parameter_limit
+            .or_else(|| self.inner.parameter_limit())
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        self.inner.server_metadata()
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        self.inner.jwks()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        match self.hooks.consent_store.opt_mut() {
+            Some(store) => Some(store),
+            None => self.inner.consent_store(),
+        }
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        self.inner.metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        self.hooks.clock.opt_ref().or_else(|| self.inner.clock())
+    }
+}
+
+impl<T: Registrar> OptRegistrar for T {
+    fn opt_ref(&self) -> Option<&dyn Registrar> {
+        Some(self)
+    }
+}
+
+impl<T: Authorizer> OptAuthorizer for T {
+    fn opt_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        Some(self)
+    }
+}
+
+impl<T: Issuer> OptIssuer for T {
+    fn opt_mut(&mut self) -> Option<&mut dyn Issuer> {
+        Some(self)
+    }
+}
+
+impl OptRegistrar for Vacant {
+    fn opt_ref(&self) -> Option<&dyn Registrar> {
+        Option::None
+    }
+}
+
+impl OptAuthorizer for Vacant {
+    fn opt_mut(&mut self) -> Option<&mut dyn Authorizer> {
+        Option::None
+    }
+}
+
+impl OptIssuer for Vacant {
+    fn opt_mut(&mut self) -> Option<&mut dyn Issuer> {
+        Option::None
+    }
+}
+
+impl<T: SessionValidator> OptSessionValidator for T {
+    fn opt_mut(&mut self) -> Option<&mut dyn SessionValidator> {
+        Some(self)
+    }
+}
+
+impl<T: ConsentStore> OptConsentStore for T {
+    fn opt_mut(&mut self) -> Option<&mut dyn ConsentStore> {
+        Some(self)
+    }
+}
+
+impl<T: Clock> OptClock for T {
+    fn opt_ref(&self) -> Option<&dyn Clock> {
+        Some(self)
+    }
+}
+
+impl OptSessionValidator for Vacant {
+    fn opt_mut(&mut self) -> Option<&mut dyn SessionValidator> {
+        Option::None
+    }
+}
+
+impl OptConsentStore for Vacant {
+    fn opt_mut(&mut self) -> Option<&mut dyn ConsentStore> {
+        Option::None
+    }
+}
+
+impl OptClock for Vacant {
+    fn opt_ref(&self) -> Option<&dyn Clock> {
+        Option::None
+    }
+}
+
+impl<W: WebRequest> OwnerSolicitor<W> for Vacant {
+    fn check_consent(&mut self, _: &mut W, _: Solicitation) -> OwnerConsent<W::Response> {
+        OwnerConsent::Denied
+    }
+}
+
+impl<W: WebRequest> ProofVerifier<W> for Vacant {
+    fn verify(&mut self, _: &mut W, _: &str, _: &Binding) -> Result<bool, ()> {
+        Ok(false)
+    }
+}
+
+impl<W: WebRequest> Scopes<W> for Vacant {
+    fn scopes(&mut self, _: &mut W) -> &[Scope] {
+        const NO_SCOPES: [Scope; 0] = [];
+        &NO_SCOPES
+    }
+}
+
+impl<W, F> OwnerSolicitor<W> for FnSolicitor<F>
+where
+    W: WebRequest,
+    F: FnMut(&mut W, Solicitation) -> OwnerConsent<W::Response>,
+{
+    fn check_consent(
+        &mut self, request: &mut W, solicitation: Solicitation,
+    ) -> OwnerConsent<W::Response> {
+        (self.0)(request, solicitation)
+    }
+}
+
+impl<W, F, T> OwnerSolicitor<W> for TieredSolicitor<F, T>
+where
+    W: WebRequest,
+    F: OwnerSolicitor<W>,
+    T: OwnerSolicitor<W>,
+{
+    fn check_consent(
+        &mut self, request: &mut W, solicitation: Solicitation,
+    ) -> OwnerConsent<W::Response> {
+        match solicitation.client_trust() {
+            ClientTrust::FirstParty => self.first_party.check_consent(request, solicitation),
+            ClientTrust::ThirdParty => self.third_party.check_consent(request, solicitation),
+        }
+    }
+
+    fn authenticated_owner(&mut self, request: &mut W) -> Option<String> {
+        // First-party clients usually skip the consent page anyways.
+        self.third_party.authenticated_owner(request)
+    }
+}
+
+impl<W: WebRequest> OwnerSolicitor<W> for ApprovedGrant {
+    /// Approve if the grant matches *exactly*.
+    ///
+    /// That is, `client_id`, `redirect_uri`, and `scope` of the pre-grant are all equivalent. In
     /// particular, the requested scope must match exactly not only be a subset of the approved
     /// scope.
     fn check_consent(&mut self, _: &mut W, solicitation: Solicitation) -> OwnerConsent<W::Response> {
@@ -724,3 +1896,13 @@ where
         self()
     }
 }
+
+impl<W, F> ResponseCreator<W> for FnResponse<F>
+where
+    W: WebRequest,
+    F: FnMut(&mut W, Template) -> W::Response,
+{
+    fn create(&mut self, request: &mut W, kind: Template) -> W::Response {
+        (self.0)(request, kind)
+    }
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use super::{AuthorizationAddon, AuthorizationRequest, AccessTokenAddon, AccessTokenRequest};
+use super::{ClientCredentialsAddon, ClientCredentialsRequest, ResourceAddon, ResourceRequest};
+use super::{Addon, AddonList, AddonResult, Value};
+use crate::primitives::grant::{Grant, GrantExtension};
+
+/// Restricts tokens to the resource servers they were requested for.
+///
+/// Clients indicate the resource server with the `resource` parameter of RFC 8707, or the
+/// `audience` parameter used by some deployments, in the authorization request or the client
+/// credentials request. The authorization server only grants audiences from its configured set
+/// and stores the audience in the grant. A token request may repeat the audience of its code but
+/// can not change it.
+///
+/// A resource server configures the audiences it answers for and denies tokens carrying any other
+/// audience with `invalid_token`. Tokens that were requested without an audience are not
+/// restricted and accepted by every resource server.
+///
+/// ```
+/// # use oxide_auth::frontends::simple::extensions::{AddonList, AudienceAddon};
+/// // On the authorization server.
+/// let audience = AudienceAddon::new(vec!["https://a.example", "https://b.example"]);
+/// let mut addons = AddonList::new();
+/// addons.push_code(audience.clone());
+/// addons.push_client_credentials(audience);
+///
+/// // On resource server A.
+/// let mut addons = AddonList::new();
+/// addons.push_resource(AudienceAddon::new(vec!["https://a.example"]));
+/// ```
+#[derive(Clone, Debug)]
+pub struct AudienceAddon {
+    audiences: HashSet<String>,
+}
+
+impl AudienceAddon {
+    /// Create the addon with the set of valid audiences.
+    pub fn new<I, S>(audiences: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        AudienceAddon {
+            audiences: audiences.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Check if the audience is one of the configured audiences.
+    pub fn accepts(&self, audience: &str) -> bool {
+        self.audiences.contains(audience)
+    }
+
+    /// The audience stored in a grant, if its tokens are restricted to one.
+    pub fn audience(grant: &Grant) -> Option<&str> {
+        grant.extensions.public_value("audience")
+    }
+
+    fn grant(&self, requested: Option<Cow<str>>) -> AddonResult {
+        match requested {
+            None => AddonResult::Ok,
+            Some(audience) if self.accepts(&audience) => {
+                AddonResult::Data(Value::public(Some(audience.into_owned())))
+            }
+            Some(_) => AddonResult::Err,
+        }
+    }
+}
+
+impl GrantExtension for AudienceAddon {
+    fn identifier(&self) -> &'static str {
+        "audience"
+    }
+}
+
+impl AuthorizationAddon for AudienceAddon {
+    fn execute(&self, request: &dyn AuthorizationRequest) -> AddonResult {
+        let requested = request
+            .extension("resource")
+            .or_else(|| request.extension("audience"));
+        self.grant(requested)
+    }
+}
+
+impl AccessTokenAddon for AudienceAddon {
+    fn execute(&self, request: &dyn AccessTokenRequest, code_data: Option<Value>) -> AddonResult {
+        let requested = request
+            .extension("resource")
+            .or_else(|| request.extension("audience"));
+
+        match (code_data, requested) {
+            (None, requested) => self.grant(requested),
+            (Some(data), None) => AddonResult::Data(data),
+            (Some(data), Some(requested)) => {
+                if data.public_value() == Ok(Some(requested.as_ref())) {
+                    AddonResult::Data(data)
+                } else {
+                    AddonResult::Err
+                }
+            }
+        }
+    }
+}
+
+impl ClientCredentialsAddon for AudienceAddon {
+    fn execute(&self, request: &dyn ClientCredentialsRequest) -> AddonResult {
+        let requested = request
+            .extension("resource")
+            .or_else(|| request.extension("audience"));
+        self.grant(requested)
+    }
+}
+
+/// Registers the addon on the authorization server, for the code grant and client credentials.
+///
+/// Resource servers check the audience with `AddonList::push_resource` instead.
+impl Addon for AudienceAddon {
+    fn register(self, addons: &mut AddonList) {
+        addons.push_code(self.clone());
+        addons.push_client_credentials(self)
+    }
+}
+
+impl ResourceAddon for AudienceAddon {
+    fn execute(&self, _: &dyn ResourceRequest, data: Option<Value>) -> AddonResult {
+        let data = match data {
+            None => return AddonResult::Ok,
+            Some(data) => data,
+        };
+
+        match data.public_value() {
+            Ok(Some(audience)) if self.accepts(audience) => AddonResult::Ok,
+            _ => AddonResult::Err,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use super::{AccessTokenAddon, AccessTokenRequest};
+use super::{Addon, AddonList, AddonResult, Value};
+use crate::endpoint::{ProofVerifier, WebRequest};
+use crate::primitives::binding::Binding;
+
+pub use crate::code_grant::extensions::DpopValidator;
+
+impl AccessTokenAddon for DpopValidator {
+    fn execute(&self, request: &dyn AccessTokenRequest, _: Option<Value>) -> AddonResult {
+        match self.bind(request.dpop_proof()) {
+            Ok(Some(binding)) => AddonResult::Data(binding.value()),
+            Ok(None) => AddonResult::Ok,
+            Err(_) => AddonResult::Err,
+        }
+    }
+}
+
+impl Addon for DpopValidator {
+    fn register(self, addons: &mut AddonList) {
+        addons.push_access_token(self)
+    }
+}
+
+impl<R: WebRequest> ProofVerifier<R> for DpopValidator {
+    fn verify(&mut self, request: &mut R, token: &str, binding: &Binding) -> Result<bool, ()> {
+        let jkt = match binding {
+            Binding::Dpop { jkt } => jkt,
+            _ => return Ok(false),
+        };
+
+        // A malformed header is no proof either.
+        let proof = match request.header("DPoP") {
+            Ok(Some(proof)) => proof,
+            Ok(None) | Err(_) => return Ok(false),
+        };
+
+        Ok(DpopValidator::verify(self, &proof, token, jkt).is_ok())
+    }
+}
@@ -1,8 +1,19 @@
-use crate::endpoint::{Endpoint, Extension, OAuthError, OwnerSolicitor, Scopes, Template, WebRequest};
+use crate::endpoint::{
+    Endpoint, Extension, OAuthError, OwnerSolicitor, ProofVerifier, Scopes, SessionValidator, Template,
+    WebRequest,
+};
+use crate::endpoint::{FlowError, FlowErrorDetail, Metrics, ServerMetadata};
+#[cfg(feature = "ed25519")]
+use crate::endpoint::JwkSet;
+use crate::primitives::scope::ScopeDescriptions;
 use crate::primitives::authorizer::Authorizer;
+use crate::primitives::clock::Clock;
+use crate::primitives::consent::ConsentStore;
 use crate::primitives::issuer::Issuer;
 use crate::primitives::registrar::Registrar;
 
+use std::time::Duration;
+
 use super::AddonList;
 
 /// An inner endpoint with simple extensions.
@@ -95,4 +106,53 @@ where
     fn extension(&mut self) -> Option<&mut dyn Extension> {
         Some(&mut self.addons)
     }
+
+    fn session_validator(&mut self) -> Option<&mut dyn SessionValidator> {
+        self.inner.session_validator()
+    }
+
+    fn proof_verifier(&mut self) -> Option<&mut dyn ProofVerifier<Request>> {
+        self.inner.proof_verifier()
+    }
+
+    fn scope_descriptions(&self) -> Option<&ScopeDescriptions> {
+        self.inner.scope_descriptions()
+    }
+
+    fn on_flow_error(&mut self, error: FlowError, detail: &mut FlowErrorDetail<'_>) {
+        self.inner.on_flow_error(error, detail)
+    }
+
+    fn opaque_errors(&self) -> bool {
+        self.inner.opaque_errors()
+    }
+
+    fn temporarily_unavailable(&mut self) -> Option<Duration> {
+        self.inner.temporarily_unavailable()
+    }
+
+    fn parameter_limit(&self) -> Option<usize> {
+        self.inner.parameter_limit()
+    }
+
+    fn server_metadata(&self) -> Option<&ServerMetadata> {
+        self.inner.server_metadata()
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn jwks(&self) -> Option<&JwkSet> {
+        self.inner.jwks()
+    }
+
+    fn consent_store(&mut self) -> Option<&mut dyn ConsentStore> {
+        self.inner.consent_store()
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        self.inner.metrics()
+    }
+
+    fn clock(&self) -> Option<&dyn Clock> {
+        self.inner.clock()
+    }
 }
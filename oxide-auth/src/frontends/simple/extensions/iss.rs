@@ -0,0 +1,21 @@
+use super::{AccessTokenAddon, AccessTokenRequest};
+use super::{Addon, AddonList, AddonResult, Value};
+
+pub use crate::code_grant::extensions::IssuerIdentifier;
+
+impl AccessTokenAddon for IssuerIdentifier {
+    fn execute(&self, request: &dyn AccessTokenRequest, _: Option<Value>) -> AddonResult {
+        let iss = request.extension("iss");
+
+        match self.verify(iss) {
+            Ok(_) => AddonResult::Ok,
+            Err(_) => AddonResult::Err,
+        }
+    }
+}
+
+impl Addon for IssuerIdentifier {
+    fn register(self, addons: &mut AddonList) {
+        addons.push_access_token(self)
+    }
+}
@@ -1,12 +1,17 @@
 use std::fmt;
 use std::sync::Arc;
 
-use super::{AuthorizationAddon, AccessTokenAddon, AddonResult, ClientCredentialsAddon};
+use super::{
+    Addon, AuthorizationAddon, AccessTokenAddon, AddonResult, ClientCredentialsAddon, RefreshAddon,
+    ResourceAddon,
+};
 use crate::code_grant::accesstoken::{Extension as AccessTokenExtension, Request};
 use crate::code_grant::authorization::{Extension as AuthorizationExtension, Request as AuthRequest};
 use crate::code_grant::client_credentials::{
     Extension as ClientCredentialsExtension, Request as ClientCredentialsRequest,
 };
+use crate::code_grant::refresh::{Extension as RefreshExtension, Request as RefreshRequest};
+use crate::code_grant::resource::{Extension as ResourceExtension, Request as ResourceRequest};
 use crate::endpoint::Extension;
 use crate::primitives::grant::{Extensions, GrantExtension};
 
@@ -27,6 +32,14 @@ pub struct AddonList {
     /// Extension to be applied on get token. This field is `pub` for `oxide-auth-async` be able to
     /// implement async version of some traits.
     pub client_credentials: Vec<Arc<dyn ClientCredentialsAddon + Send + Sync + 'static>>,
+
+    /// Extension to be applied on refresh. This field is `pub` for `oxide-auth-async` be able to
+    /// implement async version of some traits.
+    pub refresh: Vec<Arc<dyn RefreshAddon + Send + Sync + 'static>>,
+
+    /// Extension to be applied on resource access. This field is `pub` for `oxide-auth-async` be
+    /// able to implement async version of some traits.
+    pub resource: Vec<Arc<dyn ResourceAddon + Send + Sync + 'static>>,
 }
 
 impl AddonList {
@@ -36,9 +49,52 @@ impl AddonList {
             authorization: vec![],
             access_token: vec![],
             client_credentials: vec![],
+            refresh: vec![],
+            resource: vec![],
         }
     }
 
+    /// Add an addon to all the flows it takes part in, as a builder.
+    ///
+    /// ```
+    /// # use oxide_auth::frontends::simple::extensions::{AddonList, AudienceAddon, Pkce};
+    /// let addons = AddonList::new()
+    ///     .with(Pkce::required())
+    ///     .with(AudienceAddon::new(vec!["https://a.example"]));
+    /// assert_eq!(addons.identifiers(), ["pkce", "audience"]);
+    /// ```
+    pub fn with<A: Addon>(mut self, addon: A) -> Self {
+        self.push(addon);
+        self
+    }
+
+    /// Add an addon to all the flows it takes part in.
+    pub fn push<A: Addon>(&mut self, addon: A) {
+        addon.register(self)
+    }
+
+    /// The identifiers of all registered addons, in the order they were first added.
+    ///
+    /// These are the keys under which the addons store their data in the extensions of a grant.
+    pub fn identifiers(&self) -> Vec<&'static str> {
+        let all = self
+            .authorization
+            .iter()
+            .map(|addon| addon.identifier())
+            .chain(self.access_token.iter().map(|addon| addon.identifier()))
+            .chain(self.client_credentials.iter().map(|addon| addon.identifier()))
+            .chain(self.refresh.iter().map(|addon| addon.identifier()))
+            .chain(self.resource.iter().map(|addon| addon.identifier()));
+
+        let mut identifiers = Vec::new();
+        for identifier in all {
+            if !identifiers.contains(&identifier) {
+                identifiers.push(identifier);
+            }
+        }
+        identifiers
+    }
+
     /// Add an addon that only applies to authorization.
     pub fn push_authorization<A>(&mut self, addon: A)
     where
@@ -63,6 +119,22 @@ impl AddonList {
         self.client_credentials.push(Arc::new(addon))
     }
 
+    /// Add an addon that only applies to refresh.
+    pub fn push_refresh<A>(&mut self, addon: A)
+    where
+        A: RefreshAddon + Send + Sync + 'static,
+    {
+        self.refresh.push(Arc::new(addon))
+    }
+
+    /// Add an addon that only applies to resource access.
+    pub fn push_resource<A>(&mut self, addon: A)
+    where
+        A: ResourceAddon + Send + Sync + 'static,
+    {
+        self.resource.push(Arc::new(addon))
+    }
+
     /// Add an addon that applies to the whole code grant flow.
     ///
     /// The addon gets added both the authorization and access token addons.
@@ -94,6 +166,14 @@ impl Extension for AddonList {
     fn client_credentials(&mut self) -> Option<&mut dyn ClientCredentialsExtension> {
         Some(self)
     }
+
+    fn refresh(&mut self) -> Option<&mut dyn RefreshExtension> {
+        Some(self)
+    }
+
+    fn resource(&mut self) -> Option<&mut dyn ResourceExtension> {
+        Some(self)
+    }
 }
 
 impl Extension for &mut AddonList {
@@ -108,6 +188,14 @@ impl Extension for &mut AddonList {
     fn client_credentials(&mut self) -> Option<&mut dyn ClientCredentialsExtension> {
         Some(self)
     }
+
+    fn refresh(&mut self) -> Option<&mut dyn RefreshExtension> {
+        Some(self)
+    }
+
+    fn resource(&mut self) -> Option<&mut dyn ResourceExtension> {
+        Some(self)
+    }
 }
 
 impl AccessTokenExtension for AddonList {
@@ -185,6 +273,55 @@ impl ClientCredentialsExtension for &mut AddonList {
     }
 }
 
+impl RefreshExtension for AddonList {
+    fn extend(&mut self, request: &dyn RefreshRequest, mut data: Extensions) -> Result<Extensions, ()> {
+        for ext in self.refresh.iter() {
+            let ext_data = data.remove(ext);
+            let result = ext.execute(request, ext_data.clone());
+
+            match result {
+                // Keep the previous data, as well as that of extensions without an addon.
+                AddonResult::Ok => {
+                    if let Some(ext_data) = ext_data {
+                        data.set(ext, ext_data)
+                    }
+                }
+                AddonResult::Data(new_data) => data.set(ext, new_data),
+                AddonResult::Err => return Err(()),
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+impl RefreshExtension for &mut AddonList {
+    fn extend(&mut self, request: &dyn RefreshRequest, data: Extensions) -> Result<Extensions, ()> {
+        RefreshExtension::extend(*self, request, data)
+    }
+}
+
+impl ResourceExtension for AddonList {
+    fn check(&mut self, request: &dyn ResourceRequest, data: &Extensions) -> Result<(), ()> {
+        for ext in self.resource.iter() {
+            let ext_data = data.get(ext.identifier()).cloned();
+
+            match ext.execute(request, ext_data) {
+                AddonResult::Ok | AddonResult::Data(_) => (),
+                AddonResult::Err => return Err(()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ResourceExtension for &mut AddonList {
+    fn check(&mut self, request: &dyn ResourceRequest, data: &Extensions) -> Result<(), ()> {
+        ResourceExtension::check(*self, request, data)
+    }
+}
+
 impl fmt::Debug for AddonList {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use std::slice::Iter;
@@ -200,6 +337,8 @@ impl fmt::Debug for AddonList {
             .field("authorization", &ExtIter(self.authorization.iter()))
             .field("access_token", &ExtIter(self.access_token.iter()))
             .field("client_credentials", &ExtIter(self.client_credentials.iter()))
+            .field("refresh", &ExtIter(self.refresh.iter()))
+            .field("resource", &ExtIter(self.resource.iter()))
             .finish()
     }
 }
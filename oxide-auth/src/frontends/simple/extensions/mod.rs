@@ -4,8 +4,14 @@
 pub use crate::code_grant::authorization::Request as AuthorizationRequest;
 pub use crate::code_grant::accesstoken::Request as AccessTokenRequest;
 pub use crate::code_grant::client_credentials::Request as ClientCredentialsRequest;
+pub use crate::code_grant::refresh::Request as RefreshRequest;
+pub use crate::code_grant::resource::Request as ResourceRequest;
 
+mod audience;
+mod dpop;
 mod extended;
+mod iss;
+mod mtls;
 mod pkce;
 mod list;
 
@@ -13,7 +19,11 @@ use std::borrow::{Cow, ToOwned};
 use std::rc::Rc;
 use std::sync::Arc;
 
+pub use self::audience::AudienceAddon;
+pub use self::dpop::DpopValidator;
 pub use self::extended::Extended;
+pub use self::iss::IssuerIdentifier;
+pub use self::mtls::CertificateBinding;
 pub use self::pkce::Pkce;
 pub use self::list::AddonList;
 use crate::primitives::grant::{GrantExtension, Value};
@@ -62,6 +72,41 @@ pub trait ClientCredentialsAddon: GrantExtension {
     fn execute(&self, request: &dyn ClientCredentialsRequest) -> AddonResult;
 }
 
+/// An extension reacting to a refresh request.
+pub trait RefreshAddon: GrantExtension {
+    /// Recompute the data of a grant that is being refreshed.
+    ///
+    /// The data currently stored for this extension in the grant is provided as a parameter.
+    /// Returning `Ok` keeps it unchanged while `Data` replaces it in the refreshed grant, for
+    /// example to update claims that may have changed since the grant was issued. An `Err` rejects
+    /// the refresh.
+    fn execute(&self, request: &dyn RefreshRequest, data: Option<Value>) -> AddonResult;
+}
+
+/// An extension checking a token presented to a resource.
+pub trait ResourceAddon: GrantExtension {
+    /// Check the data of the grant of an otherwise valid token.
+    ///
+    /// The data stored for this extension in the grant is provided as a parameter. An `Err`
+    /// denies access with an `invalid_token` error, while both `Ok` and `Data` allow it. The grant
+    /// can not be changed by this addon.
+    fn execute(&self, request: &dyn ResourceRequest, data: Option<Value>) -> AddonResult;
+}
+
+/// An addon that knows which flows it takes part in.
+///
+/// Registering it with [`AddonList::with`] or [`AddonList::push`] adds it to all of them at once,
+/// instead of calling the matching `push_*` method for each flow. The data it produces is stored
+/// in the grant under its [`identifier`], where it can be read again with
+/// [`Extensions::value_of`].
+///
+/// [`identifier`]: GrantExtension::identifier
+/// [`Extensions::value_of`]: crate::primitives::grant::Extensions::value_of
+pub trait Addon: GrantExtension {
+    /// Add the addon to each flow of the list it applies to.
+    fn register(self, addons: &mut AddonList);
+}
+
 impl<'a, T: AuthorizationAddon + ?Sized> AuthorizationAddon for &'a T {
     fn execute(&self, request: &dyn AuthorizationRequest) -> AddonResult {
         (**self).execute(request)
@@ -160,3 +205,69 @@ impl<T: ClientCredentialsAddon + ?Sized> ClientCredentialsAddon for Rc<T> {
         (**self).execute(request)
     }
 }
+
+impl<'a, T: RefreshAddon + ?Sized> RefreshAddon for &'a T {
+    fn execute(&self, request: &dyn RefreshRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<'a, T: RefreshAddon + ?Sized> RefreshAddon for Cow<'a, T>
+where
+    T: Clone + ToOwned,
+{
+    fn execute(&self, request: &dyn RefreshRequest, data: Option<Value>) -> AddonResult {
+        self.as_ref().execute(request, data)
+    }
+}
+
+impl<T: RefreshAddon + ?Sized> RefreshAddon for Box<T> {
+    fn execute(&self, request: &dyn RefreshRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<T: RefreshAddon + ?Sized> RefreshAddon for Arc<T> {
+    fn execute(&self, request: &dyn RefreshRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<T: RefreshAddon + ?Sized> RefreshAddon for Rc<T> {
+    fn execute(&self, request: &dyn RefreshRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<'a, T: ResourceAddon + ?Sized> ResourceAddon for &'a T {
+    fn execute(&self, request: &dyn ResourceRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<'a, T: ResourceAddon + ?Sized> ResourceAddon for Cow<'a, T>
+where
+    T: Clone + ToOwned,
+{
+    fn execute(&self, request: &dyn ResourceRequest, data: Option<Value>) -> AddonResult {
+        self.as_ref().execute(request, data)
+    }
+}
+
+impl<T: ResourceAddon + ?Sized> ResourceAddon for Box<T> {
+    fn execute(&self, request: &dyn ResourceRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<T: ResourceAddon + ?Sized> ResourceAddon for Arc<T> {
+    fn execute(&self, request: &dyn ResourceRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
+
+impl<T: ResourceAddon + ?Sized> ResourceAddon for Rc<T> {
+    fn execute(&self, request: &dyn ResourceRequest, data: Option<Value>) -> AddonResult {
+        (**self).execute(request, data)
+    }
+}
@@ -0,0 +1,39 @@
+use super::{AccessTokenAddon, AccessTokenRequest};
+use super::{Addon, AddonList, AddonResult, Value};
+use crate::endpoint::{ProofVerifier, WebRequest};
+use crate::primitives::binding::Binding;
+
+pub use crate::code_grant::extensions::CertificateBinding;
+
+impl AccessTokenAddon for CertificateBinding {
+    fn execute(&self, request: &dyn AccessTokenRequest, _: Option<Value>) -> AddonResult {
+        match self.bind(request.client_certificate().as_deref()) {
+            Ok(Some(binding)) => AddonResult::Data(binding.value()),
+            Ok(None) => AddonResult::Ok,
+            Err(_) => AddonResult::Err,
+        }
+    }
+}
+
+impl Addon for CertificateBinding {
+    fn register(self, addons: &mut AddonList) {
+        addons.push_access_token(self)
+    }
+}
+
+impl<R: WebRequest> ProofVerifier<R> for CertificateBinding {
+    fn verify(&mut self, request: &mut R, _: &str, binding: &Binding) -> Result<bool, ()> {
+        let x5t_s256 = match binding {
+            Binding::Certificate { x5t_s256 } => x5t_s256,
+            _ => return Ok(false),
+        };
+
+        // A malformed certificate is no proof either.
+        let certificate = match request.client_certificate() {
+            Ok(Some(certificate)) => certificate,
+            Ok(None) | Err(_) => return Ok(false),
+        };
+
+        Ok(CertificateBinding::verify(self, &certificate, x5t_s256))
+    }
+}
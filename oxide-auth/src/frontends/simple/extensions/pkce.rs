@@ -1,5 +1,5 @@
 use super::{AuthorizationAddon, AuthorizationRequest, AccessTokenAddon, AccessTokenRequest};
-use super::{AddonResult, Value};
+use super::{Addon, AddonList, AddonResult, Value};
 
 pub use crate::code_grant::extensions::Pkce;
 
@@ -28,3 +28,9 @@ impl AccessTokenAddon for Pkce {
         }
     }
 }
+
+impl Addon for Pkce {
+    fn register(self, addons: &mut AddonList) {
+        addons.push_code(self)
+    }
+}
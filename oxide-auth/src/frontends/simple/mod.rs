@@ -7,6 +7,8 @@
 //!
 //! [`Endpoint`]: ../../endpoint/trait.Endpoint.html
 //! [`WebRequest`]: ../../endpoint/trait.Endpoint.html
+pub mod csrf;
+
 pub mod endpoint;
 
 pub mod extensions;
@@ -5,6 +5,7 @@ use crate::endpoint::{QueryParameter, WebRequest, WebResponse};
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::time::Duration;
 
 use url::Url;
 
@@ -32,13 +33,16 @@ pub struct Response {
 
     /// Indicates how the client should have authenticated.
     ///
-    /// Only set with `Unauthorized` status.
+    /// Only set with `Unauthorized` or `Forbidden` status.
     pub www_authenticate: Option<String>,
 
     /// Encoded body of the response.
     ///
     /// One variant for each possible encoding type.
     pub body: Option<Body>,
+
+    /// Whether caches must not store the response, as for all token responses.
+    pub no_store: bool,
 }
 
 /// An enum containing the necessary HTTP status codes.
@@ -55,6 +59,12 @@ pub enum Status {
 
     /// Http status code 401.
     Unauthorized,
+
+    /// Http status code 403.
+    Forbidden,
+
+    /// Http status code 500.
+    InternalServerError,
 }
 
 /// Models the necessary body contents.
@@ -165,6 +175,22 @@ impl WebResponse for Response {
         Ok(())
     }
 
+    /// Set the response status to 403 and add a `WWW-Authenticate` header.
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.status = Status::Forbidden;
+        self.location = None;
+        self.www_authenticate = Some(header_value.to_owned());
+        Ok(())
+    }
+
+    /// Set the response status to 500.
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.status = Status::InternalServerError;
+        self.location = None;
+        self.www_authenticate = None;
+        Ok(())
+    }
+
     /// A pure text response with no special media type set.
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.body = Some(Body::Text(text.to_owned()));
@@ -176,6 +202,12 @@ impl WebResponse for Response {
         self.body = Some(Body::Json(data.to_owned()));
         Ok(())
     }
+
+    /// Forbid caches from storing the response.
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.no_store = true;
+        Ok(())
+    }
 }
 
 impl NoError {
@@ -211,6 +243,22 @@ where
     fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
         self.0.authheader().map_err(&mut self.1)
     }
+
+    fn cookie(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        self.0.cookie(name).map_err(&mut self.1)
+    }
+
+    fn header(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+        self.0.header(name).map_err(&mut self.1)
+    }
+
+    fn client_certificate(&mut self) -> Result<Option<Cow<'_, [u8]>>, Self::Error> {
+        self.0.client_certificate().map_err(&mut self.1)
+    }
+
+    fn raw_body(&self) -> Option<&[u8]> {
+        self.0.raw_body()
+    }
 }
 
 impl<W: WebResponse, F, T> WebResponse for MapErr<W, F, T>
@@ -238,6 +286,26 @@ where
         self.0.unauthorized(header_value).map_err(&mut self.1)
     }
 
+    /// Set the response status to 403 and add a `WWW-Authenticate` header.
+    fn forbidden(&mut self, header_value: &str) -> Result<(), Self::Error> {
+        self.0.forbidden(header_value).map_err(&mut self.1)
+    }
+
+    /// Set the response status to 500.
+    fn server_error(&mut self) -> Result<(), Self::Error> {
+        self.0.server_error().map_err(&mut self.1)
+    }
+
+    /// Set the response status to 429 with an optional `Retry-After` header.
+    fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.0.too_many_requests(retry_after).map_err(&mut self.1)
+    }
+
+    /// Set the response status to 503 with an optional `Retry-After` header.
+    fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+        self.0.service_unavailable(retry_after).map_err(&mut self.1)
+    }
+
     /// A pure text response with no special media type set.
     fn body_text(&mut self, text: &str) -> Result<(), Self::Error> {
         self.0.body_text(text).map_err(&mut self.1)
@@ -247,4 +315,112 @@ where
     fn body_json(&mut self, data: &str) -> Result<(), Self::Error> {
         self.0.body_json(data).map_err(&mut self.1)
     }
+
+    /// Forbid caches from storing the response.
+    fn no_store(&mut self) -> Result<(), Self::Error> {
+        self.0.no_store().map_err(&mut self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response remembering the last status method called on it.
+    #[derive(Default)]
+    struct Recorder {
+        status: Option<(u16, Option<Duration>)>,
+    }
+
+    impl WebResponse for Recorder {
+        type Error = NoError;
+
+        fn ok(&mut self) -> Result<(), Self::Error> {
+            self.status = Some((200, None));
+            Ok(())
+        }
+
+        fn redirect(&mut self, _: Url) -> Result<(), Self::Error> {
+            self.status = Some((302, None));
+            Ok(())
+        }
+
+        fn client_error(&mut self) -> Result<(), Self::Error> {
+            self.status = Some((400, None));
+            Ok(())
+        }
+
+        fn unauthorized(&mut self, _: &str) -> Result<(), Self::Error> {
+            self.status = Some((401, None));
+            Ok(())
+        }
+
+        fn too_many_requests(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+            self.status = Some((429, retry_after));
+            Ok(())
+        }
+
+        fn service_unavailable(&mut self, retry_after: Option<Duration>) -> Result<(), Self::Error> {
+            self.status = Some((503, retry_after));
+            Ok(())
+        }
+
+        fn body_text(&mut self, _: &str) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn body_json(&mut self, _: &str) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn mapped(recorder: Recorder) -> MapErr<Recorder, fn(NoError) -> (), ()> {
+        MapErr::response(recorder, NoError::into)
+    }
+
+    /// A request answering every header with its own name.
+    struct Echo;
+
+    impl WebRequest for Echo {
+        type Error = NoError;
+        type Response = Recorder;
+
+        fn query(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+            unimplemented!()
+        }
+
+        fn urlbody(&mut self) -> Result<Cow<dyn QueryParameter + 'static>, Self::Error> {
+            unimplemented!()
+        }
+
+        fn authheader(&mut self) -> Result<Option<Cow<str>>, Self::Error> {
+            Ok(None)
+        }
+
+        fn header(&mut self, name: &str) -> Result<Option<Cow<'_, str>>, Self::Error> {
+            Ok(Some(Cow::Owned(name.to_owned())))
+        }
+    }
+
+    #[test]
+    fn map_err_header() {
+        let mut request = MapErr::request(Echo, NoError::into::<()>);
+        assert_eq!(request.header("DPoP").unwrap().as_deref(), Some("DPoP"));
+    }
+
+    #[test]
+    fn map_err_too_many_requests() {
+        let mut response = mapped(Recorder::default());
+        let retry_after = Some(Duration::from_secs(30));
+        response.too_many_requests(retry_after).unwrap();
+        assert_eq!(response.into_inner().status, Some((429, retry_after)));
+    }
+
+    #[test]
+    fn map_err_service_unavailable() {
+        let mut response = mapped(Recorder::default());
+        let retry_after = Some(Duration::from_secs(60));
+        response.service_unavailable(retry_after).unwrap();
+        assert_eq!(response.into_inner().status, Some((503, retry_after)));
+    }
 }
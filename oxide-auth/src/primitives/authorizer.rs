@@ -6,10 +6,16 @@
 //! side request, it will then check the given parameters to determine the authorization of such
 //! clients.
 use std::collections::HashMap;
-use std::sync::{MutexGuard, RwLockWriteGuard};
+use std::sync::{Arc, MutexGuard, RwLockWriteGuard};
 
-use super::grant::Grant;
-use super::generator::TagGrant;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use super::Time;
+use super::clock::{Clock, SystemClock};
+use super::grant::{Grant, Value};
+use super::generator::{Assertion, TagGrant};
+use crate::code_grant::extensions::Pkce;
 
 /// Authorizers create and manage authorization codes.
 ///
@@ -19,8 +25,17 @@ pub trait Authorizer {
     fn authorize(&mut self, _: Grant) -> Result<String, ()>;
 
     /// Retrieve the parameters associated with a token, invalidating the code in the process. In
-    /// particular, a code should not be usable twice (there is no stateless implementation of an
-    /// authorizer for this reason).
+    /// particular, a code should not be usable twice (there is no purely stateless implementation
+    /// of an authorizer for this reason). Implementations with storage shared by several instances,
+    /// such as a database, must check and invalidate the code atomically.
+    ///
+    /// How the grant is recovered is up to the implementation, the flows only rely on this method.
+    /// An [`AuthMap`] looks the code up in its storage while a [`CodeSigner`] verifies the
+    /// signature of a self-contained code. Codes that are unknown, malformed or carry an invalid
+    /// signature all yield `Ok(None)`.
+    ///
+    /// [`AuthMap`]: struct.AuthMap.html
+    /// [`CodeSigner`]: struct.CodeSigner.html
     fn extract(&mut self, token: &str) -> Result<Option<Grant>, ()>;
 }
 
@@ -29,10 +44,65 @@ pub trait Authorizer {
 /// This authorizer saves a mapping of generated strings to their associated grants. The generator
 /// is itself trait based and can be chosen during construction. It is assumed to not be possible
 /// for two different grants to generate the same token in the issuer.
+///
+/// Extracting a code removes it from the map in a single step. A map shared between threads must
+/// be wrapped in a `Mutex` or `RwLock` to be modified at all, so of several concurrent requests
+/// redeeming the same code exactly one recovers the grant while all others find no grant.
 pub struct AuthMap<I: TagGrant = Box<dyn TagGrant + Send + Sync + 'static>> {
     tagger: I,
     usage: u64,
     tokens: HashMap<String, Grant>,
+    expiry: HashMap<String, Time>,
+    code_ttl: Option<Duration>,
+    redeemed: HashMap<String, Redeemed>,
+    reuse_listener: Option<ReuseListener>,
+    clock: Arc<dyn Clock + Send + Sync>,
+}
+
+type ReuseListener = Arc<dyn Fn(&CodeReuse) + Send + Sync>;
+
+/// A code that was extracted, remembered to detect its reuse.
+struct Redeemed {
+    client_id: String,
+    owner_id: String,
+    until: Time,
+}
+
+/// An authorization code presented again after it had already been redeemed.
+///
+/// Only the client and an attacker who intercepted the code know it, so the tokens issued for the
+/// first redemption may be in the wrong hands. [RFC 6749, Section 4.1.2] recommends revoking all of
+/// them. The event identifies the grant by its client and owner and never contains the code. See
+/// [`AuthMap::on_code_reuse`].
+///
+/// [RFC 6749, Section 4.1.2]: https://www.rfc-editor.org/rfc/rfc6749#section-4.1.2
+/// [`AuthMap::on_code_reuse`]: struct.AuthMap.html#method.on_code_reuse
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeReuse {
+    /// The client the code was issued to.
+    pub client_id: String,
+
+    /// The resource owner who authorized the grant.
+    pub owner_id: String,
+
+    /// When the reuse was observed, according to the clock of the authorizer.
+    pub time: Time,
+}
+
+/// The serializable state of an `AuthMap`.
+///
+/// Created by [`AuthMap::snapshot`] and consumed by [`AuthMap::restore`]. This contains the
+/// outstanding authorization codes in plain and must be kept as confidential as the codes
+/// themselves.
+///
+/// [`AuthMap::snapshot`]: struct.AuthMap.html#method.snapshot
+/// [`AuthMap::restore`]: struct.AuthMap.html#method.restore
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthMapSnapshot {
+    usage: u64,
+    codes: HashMap<String, Grant>,
+    #[serde(default)]
+    expiry: HashMap<String, Time>,
 }
 
 impl<I: TagGrant> AuthMap<I> {
@@ -47,10 +117,215 @@ impl<I: TagGrant> AuthMap<I> {
             tagger,
             usage: 0,
             tokens: HashMap::new(),
+            expiry: HashMap::new(),
+            code_ttl: None,
+            redeemed: HashMap::new(),
+            reuse_listener: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use a different clock to determine the expiry of codes, instead of the system time.
+    ///
+    /// This is mainly useful in tests, to advance time deterministically with a [`ManualClock`].
+    ///
+    /// [`ManualClock`]: ../clock/struct.ManualClock.html
+    pub fn with_clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Let codes expire after the specified duration, independent of their grant.
+    ///
+    /// The grant of a code usually lives as long as the access token issued for it, while the
+    /// code itself should be redeemed within a few minutes. A code extracted after its ttl has
+    /// passed, according to the clock, yields no grant and is rejected as an `invalid_grant` by
+    /// the access token flow. The grant of a code redeemed in time keeps its expiry. This only
+    /// affects codes created afterwards.
+    pub fn code_ttl(&mut self, ttl: Duration) {
+        self.code_ttl = Some(ttl);
+    }
+
+    /// Let codes expire only with their grant.
+    pub fn code_ttl_default(&mut self) {
+        self.code_ttl = None;
+    }
+
+    /// Report codes which are redeemed a second time to a listener.
+    ///
+    /// Extracted codes are remembered until they expire, they are not part of snapshots. When one
+    /// of them is extracted again, no grant is returned as before and the listener is called with a
+    /// [`CodeReuse`]. It should revoke the tokens the owner granted to the client, for example with
+    /// `TokenMap::revoke_client`, but must not block. The listener is called while the authorizer
+    /// is borrowed by the flow, so it should forward the event instead of locking the issuer of the
+    /// same endpoint itself.
+    ///
+    /// ```
+    /// # use oxide_auth::primitives::authorizer::{AuthMap, Authorizer};
+    /// # use oxide_auth::primitives::generator::RandomGenerator;
+    /// # use oxide_auth::primitives::grant::{Extensions, Grant};
+    /// # use chrono::{Duration, Utc};
+    /// use std::sync::mpsc;
+    ///
+    /// let (sender, reused) = mpsc::channel();
+    /// let sender = std::sync::Mutex::new(sender);
+    /// let mut authorizer = AuthMap::new(RandomGenerator::new(16));
+    /// authorizer.on_code_reuse(move |reuse| {
+    ///     let _ = sender.lock().unwrap().send(reuse.clone());
+    /// });
+    /// # let grant = Grant {
+    /// #     client_id: "client".into(),
+    /// #     owner_id: "owner".into(),
+    /// #     redirect_uri: "https://client.example/endpoint".parse().unwrap(),
+    /// #     scope: "default".parse().unwrap(),
+    /// #     until: Utc::now() + Duration::minutes(10),
+    /// #     extensions: Extensions::new(),
+    /// # };
+    ///
+    /// let code = authorizer.authorize(grant).unwrap();
+    /// assert!(authorizer.extract(&code).unwrap().is_some());
+    /// assert!(authorizer.extract(&code).unwrap().is_none());
+    ///
+    /// let reuse = reused.recv().unwrap();
+    /// assert_eq!(reuse.owner_id, "owner");
+    /// // Revoke the tokens of the grant, e.g. `issuer.revoke_client(&reuse.owner_id, &reuse.client_id)`.
+    /// ```
+    ///
+    /// [`CodeReuse`]: struct.CodeReuse.html
+    pub fn on_code_reuse<F>(&mut self, listener: F)
+    where
+        F: Fn(&CodeReuse) + Send + Sync + 'static,
+    {
+        self.reuse_listener = Some(Arc::new(listener));
+    }
+
+    /// Delete all codes which have expired according to the clock.
+    ///
+    /// Expired codes are rejected by the flows regardless but would otherwise occupy memory until
+    /// they are extracted. Redeemed codes remembered to detect their reuse are forgotten as well.
+    pub fn purge_expired(&mut self) {
+        let now = self.clock.now();
+        let expiry = &self.expiry;
+        self.tokens.retain(|code, grant| {
+            grant.until >= now && expiry.get(code).is_none_or(|until| *until >= now)
+        });
+        let tokens = &self.tokens;
+        self.expiry.retain(|code, _| tokens.contains_key(code));
+        self.redeemed.retain(|_, redeemed| redeemed.until >= now);
+    }
+
+    /// Capture all outstanding authorization codes, for example to persist them across restarts.
+    pub fn snapshot(&self) -> AuthMapSnapshot {
+        AuthMapSnapshot {
+            usage: self.usage,
+            codes: self.tokens.clone(),
+            expiry: self.expiry.clone(),
+        }
+    }
+
+    /// Replace all authorization codes with those of a snapshot.
+    ///
+    /// Codes from the snapshot can afterwards be extracted as if they had been created by this
+    /// instance.
+    pub fn restore(&mut self, snapshot: AuthMapSnapshot) {
+        self.usage = snapshot.usage;
+        self.tokens = snapshot.codes;
+        self.expiry = snapshot.expiry;
+    }
+
+    /// Remove a code, returning its grant unless the code has outlived its ttl.
+    fn take(&mut self, code: &str) -> Option<Grant> {
+        let grant = self.tokens.remove(code)?;
+        match self.expiry.remove(code) {
+            Some(until) if until < self.clock.now() => None,
+            _ => Some(grant),
         }
     }
 }
 
+/// Signs grants into self-contained authorization codes.
+///
+/// The code itself carries the grant and its signature, so nothing needs to be stored when it is
+/// issued. This allows an authorization endpoint to hand out codes that another instance sharing
+/// the same key redeems. Only redeemed codes are remembered, until they expire, to ensure that
+/// each code is used at most once. Since the redeemed codes are kept per instance, all requests to
+/// the token endpoint must still be served by a single instance.
+///
+/// Codes are opaque to the client but not encrypted, the grant should not contain any secrets.
+/// Grants with private extensions can not be signed and fail to be authorized, with the exception
+/// of `S256` PKCE challenges when enabled by [`embed_pkce`]. The expiry of the grant is kept with a
+/// precision of whole seconds.
+///
+/// Together with a [`TokenSigner`] this makes an authorization server without any storage of
+/// codes and tokens possible. The tradeoff is in the single use of codes: a code redeemed at one
+/// instance is only remembered by that instance, another instance accepts it again until it
+/// expires. Deployments relying on single use across instances need to share a denylist of
+/// redeemed codes. Embedding the PKCE challenge limits such a replay to the client holding the
+/// verifier, and short code lifetimes limit the window in which it is possible.
+///
+/// [`embed_pkce`]: #method.embed_pkce
+/// [`TokenSigner`]: ../issuer/struct.TokenSigner.html
+pub struct CodeSigner {
+    signer: Assertion,
+    counter: u64,
+    redeemed: HashMap<Vec<u8>, Time>,
+    clock: Arc<dyn Clock + Send + Sync>,
+    embed_pkce: bool,
+}
+
+impl CodeSigner {
+    /// Construct a signer of codes from a key.
+    ///
+    /// The key must not be shared with a `TokenSigner`. While the usages are distinguished in the
+    /// signed content, separate keys make it impossible to confuse codes and tokens.
+    pub fn new(signer: Assertion) -> Self {
+        CodeSigner {
+            signer,
+            counter: 0,
+            redeemed: HashMap::new(),
+            clock: Arc::new(SystemClock),
+            embed_pkce: false,
+        }
+    }
+
+    /// Construct a signer with a randomly generated key.
+    ///
+    /// Codes issued by this signer can only be redeemed by the same instance.
+    pub fn ephemeral() -> Self {
+        CodeSigner::new(Assertion::ephemeral())
+    }
+
+    /// Use a different clock to determine the expiry of codes, instead of the system time.
+    ///
+    /// This is mainly useful in tests, to advance time deterministically with a [`ManualClock`].
+    ///
+    /// [`ManualClock`]: ../clock/struct.ManualClock.html
+    pub fn with_clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Embed the PKCE challenge of grants in the signed codes.
+    ///
+    /// The challenge is verified against the verifier of the token request without any storage on
+    /// the server. Only `S256` challenges can be embedded since the client is able to read the
+    /// code, authorizing a grant with a `plain` challenge fails. All signers redeeming the codes
+    /// need this option as well, others do not recover the challenge from the code.
+    pub fn embed_pkce(mut self) -> Self {
+        self.embed_pkce = true;
+        self
+    }
+
+    /// Forget all redeemed codes which have expired according to the clock.
+    ///
+    /// Expired codes are rejected by the flows regardless, so they no longer need to be remembered
+    /// to prevent their reuse.
+    pub fn purge_expired(&mut self) {
+        let now = self.clock.now();
+        self.redeemed.retain(|_, until| *until >= now);
+    }
+}
+
 impl<'a, A: Authorizer + ?Sized> Authorizer for &'a mut A {
     fn authorize(&mut self, grant: Grant) -> Result<String, ()> {
         (**self).authorize(grant)
@@ -99,13 +374,86 @@ impl<I: TagGrant> Authorizer for AuthMap<I> {
         // second.
         let next_usage = self.usage.wrapping_add(1);
         let token = self.tagger.tag(next_usage - 1, &grant)?;
+        match self.code_ttl {
+            Some(ttl) => self.expiry.insert(token.clone(), self.clock.now() + ttl),
+            None => self.expiry.remove(&token),
+        };
         self.tokens.insert(token.clone(), grant);
         self.usage = next_usage;
         Ok(token)
     }
 
     fn extract<'a>(&mut self, grant: &'a str) -> Result<Option<Grant>, ()> {
-        Ok(self.tokens.remove(grant))
+        let listener = match &self.reuse_listener {
+            None => return Ok(self.take(grant)),
+            Some(listener) => listener,
+        };
+
+        if let Some(redeemed) = self.redeemed.get(grant) {
+            listener(&CodeReuse {
+                client_id: redeemed.client_id.clone(),
+                owner_id: redeemed.owner_id.clone(),
+                time: self.clock.now(),
+            });
+            return Ok(None);
+        }
+
+        let extracted = self.take(grant);
+        if let Some(extracted) = &extracted {
+            let redeemed = Redeemed {
+                client_id: extracted.client_id.clone(),
+                owner_id: extracted.owner_id.clone(),
+                until: extracted.until,
+            };
+            self.redeemed.insert(grant.to_owned(), redeemed);
+        }
+
+        Ok(extracted)
+    }
+}
+
+impl Authorizer for CodeSigner {
+    fn authorize(&mut self, mut grant: Grant) -> Result<String, ()> {
+        if self.embed_pkce {
+            let pkce = Pkce::optional();
+            if let Some(challenge) = grant.extensions.remove(&pkce) {
+                match challenge.into_private_value() {
+                    Ok(Some(challenge)) if Pkce::is_hashed(&challenge) => {
+                        grant.extensions.set(&pkce, Value::public(Some(challenge)))
+                    }
+                    _ => return Err(()),
+                }
+            }
+        }
+
+        let code = self.signer.tag("code").sign(self.counter, &grant)?;
+        self.counter = self.counter.wrapping_add(1);
+        Ok(code)
+    }
+
+    fn extract(&mut self, code: &str) -> Result<Option<Grant>, ()> {
+        let (mut grant, signature) = match self.signer.tag("code").extract_signed(code) {
+            Ok(signed) => signed,
+            Err(()) => return Ok(None),
+        };
+
+        if self.redeemed.contains_key(&signature) {
+            return Ok(None);
+        }
+
+        self.redeemed.insert(signature, grant.until);
+
+        if self.embed_pkce {
+            let pkce = Pkce::optional();
+            if let Some(challenge) = grant.extensions.remove(&pkce) {
+                match challenge.into_public_value() {
+                    Ok(challenge) => grant.extensions.set(&pkce, Value::private(challenge)),
+                    Err(()) => return Ok(None),
+                }
+            }
+        }
+
+        Ok(Some(grant))
     }
 }
 
@@ -113,7 +461,8 @@ impl<I: TagGrant> Authorizer for AuthMap<I> {
 /// Tests for authorizer implementations, including those provided here.
 pub mod tests {
     use super::*;
-    use chrono::Utc;
+    use chrono::{Duration, TimeZone, Utc};
+    use crate::primitives::clock::{Clock, ManualClock};
     use crate::primitives::grant::Extensions;
     use crate::primitives::generator::{Assertion, AssertionKind, RandomGenerator};
 
@@ -170,6 +519,203 @@ pub mod tests {
         simple_test_suite(&mut storage);
     }
 
+    #[test]
+    fn signer_rejects_foreign_codes() {
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "default".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: Utc::now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        };
+
+        let mut signer = CodeSigner::ephemeral();
+        let code = CodeSigner::ephemeral().authorize(grant.clone()).unwrap();
+        assert_eq!(signer.extract(&code), Ok(None));
+        assert_eq!(signer.extract("not a code"), Ok(None));
+
+        // A token signed with the same key for another usage is not a code.
+        let assertion = Assertion::ephemeral();
+        let token = assertion.tag("token").sign(0, &grant).unwrap();
+        let mut signer = CodeSigner::new(assertion);
+        assert_eq!(signer.extract(&token), Ok(None));
+    }
+
+    #[test]
+    fn signer_purge_keeps_single_use() {
+        // Signed codes carry their expiry in whole seconds.
+        let clock = ManualClock::new(Utc.timestamp_opt(Utc::now().timestamp(), 0).unwrap());
+        let mut signer = CodeSigner::ephemeral().with_clock(clock.clone());
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "default".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: clock.now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        };
+
+        let code = signer.authorize(grant.clone()).unwrap();
+        assert_eq!(signer.extract(&code), Ok(Some(grant)));
+
+        // Still remembered while the code is valid.
+        signer.purge_expired();
+        assert_eq!(signer.extract(&code), Ok(None));
+
+        clock.advance(Duration::minutes(11));
+        signer.purge_expired();
+        assert!(signer.redeemed.is_empty());
+    }
+
+    #[test]
+    fn code_reuse_listener() {
+        use std::sync::Mutex;
+
+        let clock = ManualClock::new(Utc::now());
+        let reused = Arc::new(Mutex::new(Vec::new()));
+        let mut storage = AuthMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        let listener = reused.clone();
+        storage.on_code_reuse(move |reuse| listener.lock().unwrap().push(reuse.clone()));
+
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "default".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: clock.now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        };
+
+        let code = storage.authorize(grant.clone()).unwrap();
+        assert_eq!(storage.extract(&code), Ok(Some(grant)));
+        assert!(reused.lock().unwrap().is_empty());
+
+        // Unknown codes are not a reuse.
+        assert_eq!(storage.extract("not a code"), Ok(None));
+        assert!(reused.lock().unwrap().is_empty());
+
+        assert_eq!(storage.extract(&code), Ok(None));
+        assert_eq!(
+            *reused.lock().unwrap(),
+            vec![CodeReuse {
+                client_id: "Client".to_string(),
+                owner_id: "Owner".to_string(),
+                time: clock.now(),
+            }]
+        );
+
+        // Still remembered while the code is valid.
+        storage.purge_expired();
+        assert_eq!(storage.extract(&code), Ok(None));
+        assert_eq!(reused.lock().unwrap().len(), 2);
+
+        clock.advance(Duration::minutes(11));
+        storage.purge_expired();
+        assert!(storage.redeemed.is_empty());
+    }
+
+    #[test]
+    fn purge_expired_codes() {
+        let clock = ManualClock::new(Utc::now());
+        let mut storage = AuthMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "default".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: clock.now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        };
+
+        let expiring = storage.authorize(grant.clone()).unwrap();
+        let lasting = storage
+            .authorize(Grant {
+                until: clock.now() + Duration::hours(1),
+                ..grant
+            })
+            .unwrap();
+
+        storage.purge_expired();
+        clock.advance(Duration::minutes(11));
+        storage.purge_expired();
+
+        assert_eq!(storage.extract(&expiring), Ok(None));
+        assert!(storage.extract(&lasting).unwrap().is_some());
+    }
+
+    #[test]
+    fn code_ttl_expires_codes() {
+        let clock = ManualClock::new(Utc::now());
+        let mut storage = AuthMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        storage.code_ttl(Duration::minutes(1));
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "default".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: clock.now() + Duration::hours(1),
+            extensions: Extensions::new(),
+        };
+
+        let stale = storage.authorize(grant.clone()).unwrap();
+        let purged = storage.authorize(grant.clone()).unwrap();
+        clock.advance(Duration::minutes(2));
+        let fresh = storage.authorize(grant.clone()).unwrap();
+
+        storage.purge_expired();
+        assert!(!storage.tokens.contains_key(&purged));
+        assert_eq!(storage.extract(&stale), Ok(None));
+        // The grant keeps its own, longer expiry.
+        assert_eq!(storage.extract(&fresh), Ok(Some(grant.clone())));
+
+        let mut restored = AuthMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        let kept = storage.authorize(grant).unwrap();
+        restored.restore(storage.snapshot());
+        clock.advance(Duration::minutes(2));
+        assert_eq!(restored.extract(&kept), Ok(None));
+    }
+
+    #[test]
+    fn concurrent_extract_single_use() {
+        use std::sync::{Barrier, Mutex};
+        use std::thread;
+
+        let grant = Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "One two three scopes".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: Utc::now() + Duration::minutes(10),
+            extensions: Extensions::new(),
+        };
+
+        for _ in 0..100 {
+            let mut storage = AuthMap::new(RandomGenerator::new(16));
+            let code = storage.authorize(grant.clone()).unwrap();
+            let storage = Arc::new(Mutex::new(storage));
+            let barrier = Arc::new(Barrier::new(2));
+
+            let racers: Vec<_> = (0..2)
+                .map(|_| {
+                    let storage = storage.clone();
+                    let barrier = barrier.clone();
+                    let code = code.clone();
+                    thread::spawn(move || {
+                        barrier.wait();
+                        storage.lock().unwrap().extract(&code).unwrap()
+                    })
+                })
+                .collect();
+
+            let winners = racers
+                .into_iter()
+                .filter_map(|racer| racer.join().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(winners, std::slice::from_ref(&grant));
+        }
+    }
+
     #[test]
     #[should_panic]
     fn bad_generator() {
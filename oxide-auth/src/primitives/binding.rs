@@ -0,0 +1,152 @@
+//! Binds tokens to a key held by their client.
+//!
+//! A bound token is only accepted together with a proof that the presenting client is in
+//! possession of the key, so that a leaked token alone can not be used by a third party. Two such
+//! mechanisms are standardized: DPoP ([RFC 9449]) where the client signs a proof for each request
+//! with its own key, and mutual TLS ([RFC 8705]) where the token is bound to the client
+//! certificate of the connection.
+//!
+//! The binding is stored as a public extension of the grant, so that it is also carried by
+//! self-contained tokens. Issuers derive the [`TokenType`] of their tokens from it and the
+//! resource flow requires the matching authorization scheme and proof.
+//!
+//! ```
+//! # use chrono::Utc;
+//! # use oxide_auth::primitives::grant::{Extensions, Grant};
+//! use oxide_auth::primitives::binding::Binding;
+//! use oxide_auth::primitives::issuer::TokenType;
+//!
+//! # let mut grant = Grant {
+//! #     owner_id: "owner".into(),
+//! #     client_id: "client".into(),
+//! #     scope: "default".parse().unwrap(),
+//! #     redirect_uri: "https://client.example/endpoint".parse().unwrap(),
+//! #     until: Utc::now(),
+//! #     extensions: Extensions::new(),
+//! # };
+//! Binding::Dpop { jkt: "0ZcOCORZNYy-DWpqq30jZyJGHTN0d2HglBV3uiguA4I".into() }
+//!     .attach(&mut grant.extensions);
+//!
+//! assert!(matches!(Binding::of(&grant), Some(Binding::Dpop { .. })));
+//! assert_eq!(Binding::token_type_of(&grant), TokenType::DPoP);
+//! ```
+//!
+//! [RFC 9449]: https://tools.ietf.org/html/rfc9449
+//! [RFC 8705]: https://tools.ietf.org/html/rfc8705
+//! [`TokenType`]: ../issuer/enum.TokenType.html
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+
+use super::grant::{Extensions, Grant, GrantExtension, Value};
+use super::issuer::TokenType;
+
+/// The key to which a token is bound, the confirmation claim `cnf` of [RFC 7800].
+///
+/// [RFC 7800]: https://tools.ietf.org/html/rfc7800
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Binding {
+    /// Bound to a DPoP key of the client.
+    ///
+    /// The tokens have the type `DPoP` and must be presented with the `DPoP` authorization scheme
+    /// together with a proof signed by this key.
+    Dpop {
+        /// The JWK SHA-256 thumbprint of the public key.
+        jkt: String,
+    },
+
+    /// Bound to the certificate the client used to authenticate its TLS connection.
+    ///
+    /// The tokens remain of type `Bearer` but are only accepted on connections authenticated with
+    /// the same certificate.
+    Certificate {
+        /// The SHA-256 thumbprint of the DER encoded certificate.
+        x5t_s256: String,
+    },
+}
+
+const IDENTIFIER: &str = "cnf";
+const DPOP_PREFIX: &str = "jkt:";
+const CERTIFICATE_PREFIX: &str = "x5t#S256:";
+
+impl Binding {
+    /// Get the binding attached to a grant, if it has one.
+    ///
+    /// Extension data that does not encode a binding is ignored.
+    pub fn of(grant: &Grant) -> Option<Binding> {
+        let (_, value) = grant
+            .extensions
+            .public()
+            .find(|&(identifier, _)| identifier == IDENTIFIER)?;
+        Binding::decode(value?)
+    }
+
+    /// The binding to a certificate, given in its DER encoding.
+    ///
+    /// The thumbprint is the base64url encoded SHA-256 hash of the certificate, the `x5t#S256`
+    /// confirmation method of [RFC 8705].
+    ///
+    /// [RFC 8705]: https://tools.ietf.org/html/rfc8705#section-3.1
+    pub fn certificate(der: &[u8]) -> Binding {
+        Binding::Certificate {
+            x5t_s256: URL_SAFE_NO_PAD.encode(Sha256::digest(der)),
+        }
+    }
+
+    /// Bind the tokens of a grant to this key.
+    ///
+    /// This is usually done in an `Extension` of the access token flow, after the proof of the
+    /// token request was checked.
+    pub fn attach(&self, extensions: &mut Extensions) {
+        extensions.set(self, self.value());
+    }
+
+    /// The extension data encoding this binding.
+    pub(crate) fn value(&self) -> Value {
+        Value::public(Some(self.encode()))
+    }
+
+    /// The type of the tokens bound to this key.
+    pub fn token_type(&self) -> TokenType {
+        match self {
+            Binding::Dpop { .. } => TokenType::DPoP,
+            Binding::Certificate { .. } => TokenType::Bearer,
+        }
+    }
+
+    /// The type of the tokens issued for a grant.
+    pub fn token_type_of(grant: &Grant) -> TokenType {
+        Binding::token_type_or(grant, TokenType::Bearer)
+    }
+
+    /// The type of the tokens issued for a grant, which is `unbound` for grants without binding.
+    pub fn token_type_or(grant: &Grant, unbound: TokenType) -> TokenType {
+        Binding::of(grant)
+            .map(|binding| binding.token_type())
+            .unwrap_or(unbound)
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            Binding::Dpop { jkt } => format!("{}{}", DPOP_PREFIX, jkt),
+            Binding::Certificate { x5t_s256 } => format!("{}{}", CERTIFICATE_PREFIX, x5t_s256),
+        }
+    }
+
+    fn decode(value: &str) -> Option<Binding> {
+        if let Some(jkt) = value.strip_prefix(DPOP_PREFIX) {
+            Some(Binding::Dpop { jkt: jkt.to_owned() })
+        } else {
+            value
+                .strip_prefix(CERTIFICATE_PREFIX)
+                .map(|x5t_s256| Binding::Certificate {
+                    x5t_s256: x5t_s256.to_owned(),
+                })
+        }
+    }
+}
+
+impl GrantExtension for Binding {
+    fn identifier(&self) -> &'static str {
+        IDENTIFIER
+    }
+}
@@ -0,0 +1,88 @@
+//! Sources of the current time for primitives.
+//!
+//! Primitives read the current time to determine the expiry of their grants. By default this is
+//! the system time but a different clock can be injected, for example a [`ManualClock`] to test
+//! the expiry of tokens without waiting for it.
+//!
+//! ```
+//! # use oxide_auth::primitives::clock::{Clock, ManualClock};
+//! use chrono::{Duration, Utc};
+//!
+//! let clock = ManualClock::new(Utc::now());
+//! let start = clock.now();
+//! clock.advance(Duration::hours(1));
+//! assert_eq!(clock.now(), start + Duration::hours(1));
+//! ```
+//!
+//! [`ManualClock`]: struct.ManualClock.html
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// A source of the current time.
+pub trait Clock {
+    /// The current point in time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system time, used by all primitives by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+/// A clock that only moves when told to.
+///
+/// Clones share the same time, so a clone kept by a test can advance the clock of a primitive.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl ManualClock {
+    /// Create a clock stopped at the given time.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Move the clock forward, or backward for negative durations.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+
+    /// Set the clock to a specific time.
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.now.lock().unwrap() = time;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &'_ C {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
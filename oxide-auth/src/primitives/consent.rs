@@ -0,0 +1,109 @@
+//! Remembers the consent of resource owners to the grants of clients.
+//!
+//! An endpoint with a [`ConsentStore`] authorizes a returning owner right away when they have
+//! already consented to the same client and scope, instead of asking them through the
+//! `OwnerSolicitor` again. The owner of a request is identified by the solicitor, see
+//! [`OwnerSolicitor::authenticated_owner`].
+//!
+//! [`ConsentStore`]: trait.ConsentStore.html
+//! [`OwnerSolicitor::authenticated_owner`]: ../../endpoint/trait.OwnerSolicitor.html#method.authenticated_owner
+use std::collections::HashMap;
+
+use super::scope::Scope;
+
+/// Stores the scopes each owner has consented to for each client.
+pub trait ConsentStore {
+    /// Record that the owner has consented to grant the scope to the client.
+    fn remember(&mut self, owner_id: &str, client_id: &str, scope: &Scope);
+
+    /// Check whether the owner has consented to grant at least the scope to the client.
+    fn has_consent(&self, owner_id: &str, client_id: &str, scope: &Scope) -> bool;
+}
+
+/// Keeps the consent of owners in memory.
+///
+/// Consenting to a client several times accumulates the scopes, so that a later request for any
+/// part of them is authorized without asking again.
+#[derive(Clone, Debug, Default)]
+pub struct ConsentMap {
+    consents: HashMap<(String, String), Scope>,
+}
+
+impl ConsentMap {
+    /// Create an empty store, no owner has consented to anything.
+    pub fn new() -> Self {
+        ConsentMap::default()
+    }
+
+    /// Forget the consent of the owner to the client, they will be asked again.
+    pub fn revoke(&mut self, owner_id: &str, client_id: &str) {
+        self.consents
+            .remove(&(owner_id.to_string(), client_id.to_string()));
+    }
+}
+
+impl ConsentStore for ConsentMap {
+    fn remember(&mut self, owner_id: &str, client_id: &str, scope: &Scope) {
+        let key = (owner_id.to_string(), client_id.to_string());
+        let remembered = match self.consents.get(&key) {
+            Some(previous) => previous.union(scope),
+            None => scope.clone(),
+        };
+        self.consents.insert(key, remembered);
+    }
+
+    fn has_consent(&self, owner_id: &str, client_id: &str, scope: &Scope) -> bool {
+        let key = (owner_id.to_string(), client_id.to_string());
+        match self.consents.get(&key) {
+            Some(consented) => consented.priviledged_to(scope),
+            None => false,
+        }
+    }
+}
+
+impl<S: ConsentStore + ?Sized> ConsentStore for &mut S {
+    fn remember(&mut self, owner_id: &str, client_id: &str, scope: &Scope) {
+        (**self).remember(owner_id, client_id, scope)
+    }
+
+    fn has_consent(&self, owner_id: &str, client_id: &str, scope: &Scope) -> bool {
+        (**self).has_consent(owner_id, client_id, scope)
+    }
+}
+
+impl<S: ConsentStore + ?Sized> ConsentStore for Box<S> {
+    fn remember(&mut self, owner_id: &str, client_id: &str, scope: &Scope) {
+        (**self).remember(owner_id, client_id, scope)
+    }
+
+    fn has_consent(&self, owner_id: &str, client_id: &str, scope: &Scope) -> bool {
+        (**self).has_consent(owner_id, client_id, scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consent_accumulates() {
+        let read: Scope = "read".parse().unwrap();
+        let write: Scope = "write".parse().unwrap();
+        let both: Scope = "read write".parse().unwrap();
+
+        let mut consents = ConsentMap::new();
+        assert!(!consents.has_consent("Owner", "Client", &read));
+
+        consents.remember("Owner", "Client", &read);
+        assert!(consents.has_consent("Owner", "Client", &read));
+        assert!(!consents.has_consent("Owner", "Client", &both));
+        assert!(!consents.has_consent("Owner", "Other", &read));
+        assert!(!consents.has_consent("Someone", "Client", &read));
+
+        consents.remember("Owner", "Client", &write);
+        assert!(consents.has_consent("Owner", "Client", &both));
+
+        consents.revoke("Owner", "Client");
+        assert!(!consents.has_consent("Owner", "Client", &read));
+    }
+}
@@ -9,6 +9,8 @@
 //!     - `Assertion` cryptographically verifies the integrity of a token, trading security without
 //!     persistent storage for the loss of revocability. It is thus unfit for some backends, which
 //!     is not currently expressed in the type system or with traits.
+//!     - `Encryption`, with the `chacha20poly1305` feature, additionally keeps the content of the
+//!     token confidential. The same loss of revocability applies.
 
 use super::grant::{Value, Extensions, Grant};
 use super::{Url, Time};
@@ -19,8 +21,8 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use base64::Engine;
-use base64::engine::general_purpose::STANDARD;
-use hmac::{digest::CtOutput, Mac, Hmac};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use hmac::{Mac, Hmac};
 use rand::{rngs::OsRng, RngCore, thread_rng};
 use serde::{Deserialize, Serialize};
 use rmp_serde;
@@ -50,17 +52,36 @@ pub trait TagGrant {
 ///
 /// Each byte is chosen randomly from the basic `rand::thread_rng`. This generator will always
 /// succeed.
+///
+/// The length always counts the random bytes before they are encoded, so the entropy of a token
+/// does not depend on its alphabet.
 pub struct RandomGenerator {
     random: OsRng,
     len: usize,
+    url_safe: bool,
 }
 
 impl RandomGenerator {
     /// Generates tokens with a specific byte length.
+    ///
+    /// Tokens are encoded with the standard base64 alphabet, as in previous versions, and may
+    /// contain `+`, `/` and `=` padding. Use `url_safe` for tokens placed in urls.
     pub fn new(length: usize) -> RandomGenerator {
         RandomGenerator {
             random: OsRng {},
             len: length,
+            url_safe: false,
+        }
+    }
+
+    /// Generates tokens with a specific byte length, encoded with the url-safe base64 alphabet.
+    ///
+    /// Tokens contain only ascii letters, digits, `-` and `_` and are not padded.
+    pub fn url_safe(length: usize) -> RandomGenerator {
+        RandomGenerator {
+            random: OsRng {},
+            len: length,
+            url_safe: true,
         }
     }
 
@@ -70,7 +91,11 @@ impl RandomGenerator {
         rnd.try_fill_bytes(result.as_mut_slice())
             .expect("Failed to generate random token");
 
-        STANDARD.encode(result)
+        if self.url_safe {
+            URL_SAFE_NO_PAD.encode(result)
+        } else {
+            STANDARD.encode(result)
+        }
     }
 }
 
@@ -83,8 +108,23 @@ impl RandomGenerator {
 /// The actual generator is given by a `TaggedAssertion` from `Assertion::tag` which enables
 /// signing the same grant for different uses, i.e. separating authorization from bearer grants and
 /// refresh tokens.
+///
+/// With the `ed25519` feature, tokens can also be signed with an asymmetric key. An assertion
+/// constructed from only the public key, see [`Assertion::ed25519_verifier`], can then verify and
+/// recover grants without being able to sign new ones.
+///
+/// [`Assertion::ed25519_verifier`]: #method.ed25519_verifier
 pub struct Assertion {
-    hasher: Hmac<sha2::Sha256>,
+    signer: Signer,
+}
+
+enum Signer {
+    Hmac(Hmac<sha2::Sha256>),
+    #[cfg(feature = "ed25519")]
+    Ed25519 {
+        signing: Option<Box<ed25519_dalek::SigningKey>>,
+        verifying: ed25519_dalek::VerifyingKey,
+    },
 }
 
 /// The cryptographic suite ensuring integrity of tokens.
@@ -95,6 +135,14 @@ pub enum AssertionKind {
     /// [HMAC]: https://tools.ietf.org/html/rfc2104
     /// [SHA256]: https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
     HmacSha256,
+
+    /// Uses [Ed25519 (RFC 8032)][Ed25519] signatures.
+    ///
+    /// The key is the 32 byte secret key. Requires the `ed25519` feature.
+    ///
+    /// [Ed25519]: https://tools.ietf.org/html/rfc8032
+    #[cfg(feature = "ed25519")]
+    Ed25519,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -138,23 +186,94 @@ impl Assertion {
     /// If future suites are added where this is not possible, this function may panic when supplied
     /// with an incorrect key length.
     ///
-    /// Currently, the implementation lacks the ability to really make use of another hasing mechanism than
-    /// hmac + sha256.
+    /// ## Panics
+    ///
+    /// For `AssertionKind::Ed25519` when the key is not exactly 32 bytes long.
     pub fn new(kind: AssertionKind, key: &[u8]) -> Self {
         match kind {
             AssertionKind::HmacSha256 => Assertion {
-                hasher: Hmac::<sha2::Sha256>::new_from_slice(key).unwrap(),
+                signer: Signer::Hmac(Hmac::<sha2::Sha256>::new_from_slice(key).unwrap()),
+            },
+            #[cfg(feature = "ed25519")]
+            AssertionKind::Ed25519 => {
+                use std::convert::TryInto;
+                let key: &[u8; 32] = key.try_into().expect("Ed25519 keys are 32 bytes long");
+                Assertion::ed25519(key)
+            }
+        }
+    }
+
+    /// Construct an assertion signing with an Ed25519 secret key.
+    ///
+    /// Share the result of [`public_key`] with resource servers so they can verify tokens with
+    /// [`ed25519_verifier`].
+    ///
+    /// [`public_key`]: #method.public_key
+    /// [`ed25519_verifier`]: #method.ed25519_verifier
+    #[cfg(feature = "ed25519")]
+    pub fn ed25519(private_key: &[u8; 32]) -> Self {
+        let signing = ed25519_dalek::SigningKey::from_bytes(private_key);
+        Assertion {
+            signer: Signer::Ed25519 {
+                verifying: signing.verifying_key(),
+                signing: Some(Box::new(signing)),
             },
         }
     }
 
+    /// Construct an assertion that can only verify tokens signed with an Ed25519 key.
+    ///
+    /// Extracting grants works as usual but any attempt to sign a grant fails. Returns an error if
+    /// the bytes are not a valid public key.
+    #[cfg(feature = "ed25519")]
+    pub fn ed25519_verifier(public_key: &[u8; 32]) -> Result<Self, ()> {
+        let verifying = ed25519_dalek::VerifyingKey::from_bytes(public_key).map_err(|_| ())?;
+        Ok(Assertion {
+            signer: Signer::Ed25519 {
+                signing: None,
+                verifying,
+            },
+        })
+    }
+
+    /// The public key verifying the tokens, if this assertion uses an asymmetric signature.
+    #[cfg(feature = "ed25519")]
+    pub fn public_key(&self) -> Option<[u8; 32]> {
+        match &self.signer {
+            Signer::Ed25519 { verifying, .. } => Some(verifying.to_bytes()),
+            Signer::Hmac(_) => None,
+        }
+    }
+
+    /// An identifier of the verifying key, if this assertion uses an asymmetric signature.
+    ///
+    /// This is the JWK thumbprint of the public key (RFC 7638), so it is the same for the signing
+    /// assertion and for all verifiers constructed from its public key.
+    pub fn key_id(&self) -> Option<String> {
+        match &self.signer {
+            Signer::Hmac(_) => None,
+            #[cfg(feature = "ed25519")]
+            Signer::Ed25519 { verifying, .. } => {
+                use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+                use sha2::Digest;
+
+                // The required members of the key in lexicographic order and without whitespace.
+                let canonical = format!(
+                    r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+                    URL_SAFE_NO_PAD.encode(verifying.as_bytes())
+                );
+                Some(URL_SAFE_NO_PAD.encode(sha2::Sha256::digest(canonical.as_bytes())))
+            }
+        }
+    }
+
     /// Construct an assertion instance whose tokens are only valid for the program execution.
     pub fn ephemeral() -> Self {
         // TODO Extract KeySize from currently selected hasher
         let mut rand_bytes: [u8; 32] = [0; 32];
         thread_rng().fill_bytes(&mut rand_bytes);
         Assertion {
-            hasher: Hmac::<sha2::Sha256>::new_from_slice(&rand_bytes).unwrap(),
+            signer: Signer::Hmac(Hmac::<sha2::Sha256>::new_from_slice(&rand_bytes).unwrap()),
         }
     }
 
@@ -163,38 +282,69 @@ impl Assertion {
         TaggedAssertion(self, tag)
     }
 
-    fn extract<'a>(&self, token: &'a str) -> Result<(Grant, String), ()> {
+    fn extract<'a>(&self, token: &'a str) -> Result<(Grant, String, Vec<u8>), ()> {
         let decoded = STANDARD.decode(token).map_err(|_| ())?;
         let assertion: AssertGrant = rmp_serde::from_slice(&decoded).map_err(|_| ())?;
-
-        let mut hasher = self.hasher.clone();
-        hasher.update(&assertion.0);
-        hasher.verify_slice(assertion.1.as_slice()).map_err(|_| ())?;
+        self.verify(&assertion.0, &assertion.1)?;
 
         let (_, serde_grant, tag): (u64, SerdeAssertionGrant, String) =
             rmp_serde::from_slice(&assertion.0).map_err(|_| ())?;
 
-        Ok((serde_grant.grant(), tag))
+        Ok((serde_grant.grant(), tag, assertion.1))
+    }
+
+    /// The name of the signature algorithm in a JSON Web Signature, see RFC 7518.
+    pub(crate) fn jws_algorithm(&self) -> &'static str {
+        match &self.signer {
+            Signer::Hmac(_) => "HS256",
+            #[cfg(feature = "ed25519")]
+            Signer::Ed25519 { .. } => "EdDSA",
+        }
+    }
+
+    pub(crate) fn signature(&self, data: &[u8]) -> Result<Vec<u8>, ()> {
+        match &self.signer {
+            Signer::Hmac(hasher) => {
+                let mut hasher = hasher.clone();
+                hasher.update(data);
+                Ok(hasher.finalize().into_bytes().to_vec())
+            }
+            #[cfg(feature = "ed25519")]
+            Signer::Ed25519 { signing, .. } => {
+                use ed25519_dalek::Signer as _;
+                let signing = signing.as_ref().ok_or(())?;
+                Ok(signing.sign(data).to_bytes().to_vec())
+            }
+        }
     }
 
-    fn signature(&self, data: &[u8]) -> CtOutput<hmac::Hmac<sha2::Sha256>> {
-        let mut hasher = self.hasher.clone();
-        hasher.update(data);
-        hasher.finalize()
+    pub(crate) fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), ()> {
+        match &self.signer {
+            Signer::Hmac(hasher) => {
+                let mut hasher = hasher.clone();
+                hasher.update(data);
+                hasher.verify_slice(signature).map_err(|_| ())
+            }
+            #[cfg(feature = "ed25519")]
+            Signer::Ed25519 { verifying, .. } => {
+                let signature = ed25519_dalek::Signature::from_slice(signature).map_err(|_| ())?;
+                verifying.verify_strict(data, &signature).map_err(|_| ())
+            }
+        }
     }
 
     fn counted_signature(&self, counter: u64, grant: &Grant) -> Result<String, ()> {
         let serde_grant = SerdeAssertionGrant::try_from(grant)?;
         let tosign = rmp_serde::to_vec(&(serde_grant, counter)).unwrap();
-        let signature = self.signature(&tosign);
-        Ok(STANDARD.encode(signature.into_bytes()))
+        let signature = self.signature(&tosign)?;
+        Ok(STANDARD.encode(signature))
     }
 
     fn generate_tagged(&self, counter: u64, grant: &Grant, tag: &str) -> Result<String, ()> {
         let serde_grant = SerdeAssertionGrant::try_from(grant)?;
         let tosign = rmp_serde::to_vec(&(counter, serde_grant, tag)).unwrap();
-        let signature = self.signature(&tosign);
-        let assert = AssertGrant(tosign, signature.into_bytes().to_vec());
+        let signature = self.signature(&tosign)?;
+        let assert = AssertGrant(tosign, signature);
 
         Ok(STANDARD.encode(rmp_serde::to_vec(&assert).unwrap()))
     }
@@ -217,9 +367,184 @@ impl<'a> TaggedAssertion<'a> {
     /// Result in an Err if either the signature is invalid or if the tag does not match the
     /// expected usage tag given to this assertion.
     pub fn extract<'b>(&self, token: &'b str) -> Result<Grant, ()> {
-        self.0
-            .extract(token)
-            .and_then(|(token, tag)| if tag == self.1 { Ok(token) } else { Err(()) })
+        self.extract_signed(token).map(|(grant, _)| grant)
+    }
+
+    /// Retrieve the underlying grant together with the signature of the token.
+    ///
+    /// The encoding of a token is not unique but its signature is, so the signature identifies
+    /// tokens that must only be used once.
+    pub(crate) fn extract_signed(&self, token: &str) -> Result<(Grant, Vec<u8>), ()> {
+        let (grant, tag, signature) = self.0.extract(token)?;
+        if tag == self.1 {
+            Ok((grant, signature))
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// Generates tokens by encrypting their grant with a secret key.
+///
+/// Unlike an [`Assertion`], whose tokens can be decoded by anyone holding them, the grant of these
+/// tokens is only readable with the key. Owner, client, scope and all extensions stay confidential
+/// and tokens can thus also carry private extensions. The authenticated encryption additionally
+/// protects the integrity of the grant, so it can not be altered either.
+///
+/// The key is symmetric, every party recovering tokens is able to create new ones. As with signed
+/// tokens, issued tokens can not be revoked without keeping additional state.
+///
+/// Requires the `chacha20poly1305` feature. Tokens are encrypted with `XChaCha20Poly1305` under a
+/// random nonce, binding the usage tag as associated data.
+///
+/// [`Assertion`]: struct.Assertion.html
+#[cfg(feature = "chacha20poly1305")]
+pub struct Encryption {
+    cipher: chacha20poly1305::XChaCha20Poly1305,
+}
+
+/// Binds a tag to the encrypted data, the token is only recovered for the same tag.
+#[cfg(feature = "chacha20poly1305")]
+pub struct TaggedEncryption<'a>(&'a Encryption, &'a str);
+
+/// Reasons for an [`Encryption`] to fail sealing or opening a token.
+///
+/// [`Encryption`]: struct.Encryption.html
+#[cfg(feature = "chacha20poly1305")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// The grant could not be encoded or the cipher refused to encrypt it.
+    Seal,
+
+    /// The token was not produced with this key and tag, or it was altered.
+    ///
+    /// Malformed tokens are reported the same way, to not reveal which check failed.
+    Invalid,
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl std::fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EncryptionError::Seal => f.write_str("the grant could not be encrypted"),
+            EncryptionError::Invalid => f.write_str("the token is not valid for this encryption"),
+        }
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl std::error::Error for EncryptionError {}
+
+#[cfg(feature = "chacha20poly1305")]
+#[derive(Serialize, Deserialize)]
+struct SerdeEncryptedGrant {
+    grant: SerdeAssertionGrant,
+
+    /// The private extensions, which the encryption keeps confidential.
+    private_extensions: HashMap<String, Option<String>>,
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl Encryption {
+    /// The length of the nonce prepended to each token.
+    const NONCE_LEN: usize = 24;
+
+    /// Construct an encryption from a 32 byte secret key.
+    ///
+    /// Security notice: Never use a password as the key. Generate it with a utility such as
+    /// `openssl rand` instead and store it away securely.
+    pub fn new(key: &[u8; 32]) -> Self {
+        use chacha20poly1305::KeyInit;
+        Encryption {
+            cipher: chacha20poly1305::XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    /// Construct an encryption whose tokens are only valid for the program execution.
+    pub fn ephemeral() -> Self {
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        Encryption::new(&key)
+    }
+
+    /// Get a reference to generator for the given tag.
+    pub fn tag<'a>(&'a self, tag: &'a str) -> TaggedEncryption<'a> {
+        TaggedEncryption(self, tag)
+    }
+
+    fn seal(&self, grant: &Grant, tag: &str) -> Result<String, EncryptionError> {
+        use chacha20poly1305::aead::{Aead, Payload};
+
+        let private_extensions = grant
+            .extensions
+            .private()
+            .map(|(name, content)| (name.to_string(), content.map(str::to_string)))
+            .collect();
+        let plain = SerdeEncryptedGrant {
+            grant: SerdeAssertionGrant::public_part(grant),
+            private_extensions,
+        };
+        let msg = rmp_serde::to_vec(&plain).map_err(|_| EncryptionError::Seal)?;
+
+        let mut nonce = [0; Self::NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: &msg,
+            aad: tag.as_bytes(),
+        };
+        let sealed = self
+            .cipher
+            .encrypt((&nonce).into(), payload)
+            .map_err(|_| EncryptionError::Seal)?;
+
+        let mut token = nonce.to_vec();
+        token.extend_from_slice(&sealed);
+        Ok(STANDARD.encode(token))
+    }
+
+    fn open(&self, token: &str, tag: &str) -> Result<Grant, EncryptionError> {
+        use chacha20poly1305::aead::{Aead, Payload};
+
+        let decoded = STANDARD.decode(token).map_err(|_| EncryptionError::Invalid)?;
+        if decoded.len() < Self::NONCE_LEN {
+            return Err(EncryptionError::Invalid);
+        }
+
+        let (nonce, sealed) = decoded.split_at(Self::NONCE_LEN);
+        let payload = Payload {
+            msg: sealed,
+            aad: tag.as_bytes(),
+        };
+        let msg = self
+            .cipher
+            .decrypt(nonce.into(), payload)
+            .map_err(|_| EncryptionError::Invalid)?;
+        let plain: SerdeEncryptedGrant =
+            rmp_serde::from_slice(&msg).map_err(|_| EncryptionError::Invalid)?;
+
+        let mut grant = plain.grant.grant();
+        for (name, content) in plain.private_extensions {
+            grant.extensions.set_raw(name, Value::private(content));
+        }
+        Ok(grant)
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl<'a> TaggedEncryption<'a> {
+    /// Encrypt the grant for this usage.
+    ///
+    /// Each call produces a different token, even for the same grant.
+    pub fn seal(&self, grant: &Grant) -> Result<String, EncryptionError> {
+        self.0.seal(grant, self.1)
+    }
+
+    /// Inverse operation of seal, retrieve the underlying grant.
+    ///
+    /// Results in an Err if the token was not encrypted with the same key and for the same usage
+    /// tag, or if it was altered.
+    pub fn open(&self, token: &str) -> Result<Grant, EncryptionError> {
+        self.0.open(token, self.1)
     }
 }
 
@@ -283,6 +608,20 @@ impl TagGrant for Arc<Assertion> {
     }
 }
 
+#[cfg(feature = "chacha20poly1305")]
+impl TagGrant for Encryption {
+    fn tag(&mut self, _: u64, grant: &Grant) -> Result<String, ()> {
+        self.seal(grant, "").map_err(|_| ())
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl<'a> TagGrant for &'a Encryption {
+    fn tag(&mut self, _: u64, grant: &Grant) -> Result<String, ()> {
+        self.seal(grant, "").map_err(|_| ())
+    }
+}
+
 mod scope_serde {
     use crate::primitives::scope::Scope;
 
@@ -334,24 +673,29 @@ mod time_serde {
 
 impl SerdeAssertionGrant {
     fn try_from(grant: &Grant) -> Result<Self, ()> {
-        let mut public_extensions: HashMap<String, Option<String>> = HashMap::new();
-
         if grant.extensions.private().any(|_| true) {
             return Err(());
         }
 
+        Ok(SerdeAssertionGrant::public_part(grant))
+    }
+
+    /// The grant without its private extensions.
+    fn public_part(grant: &Grant) -> Self {
+        let mut public_extensions: HashMap<String, Option<String>> = HashMap::new();
+
         for (name, content) in grant.extensions.public() {
             public_extensions.insert(name.to_string(), content.map(str::to_string));
         }
 
-        Ok(SerdeAssertionGrant {
+        SerdeAssertionGrant {
             owner_id: grant.owner_id.clone(),
             client_id: grant.client_id.clone(),
             scope: grant.scope.clone(),
             redirect_uri: grant.redirect_uri.clone(),
             until: grant.until,
             public_extensions,
-        })
+        }
     }
 
     fn grant(self) -> Grant {
@@ -382,4 +726,97 @@ mod tests {
         let fake_key = [0u8; 16];
         let _ = uses(Assertion::new(AssertionKind::HmacSha256, &fake_key));
     }
+
+    #[test]
+    fn random_url_safe() {
+        let generator = RandomGenerator::url_safe(32);
+        for _ in 0..64 {
+            let token = generator.generate();
+            assert!(
+                token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                "{}",
+                token
+            );
+            assert_eq!(URL_SAFE_NO_PAD.decode(&token).unwrap().len(), 32);
+        }
+
+        let token = RandomGenerator::new(32).generate();
+        assert_eq!(STANDARD.decode(&token).unwrap().len(), 32);
+    }
+
+    #[cfg(any(feature = "ed25519", feature = "chacha20poly1305"))]
+    fn test_grant() -> Grant {
+        let mut extensions = Extensions::new();
+        extensions.set_raw("public".into(), Value::public(Some("content".into())));
+        Grant {
+            owner_id: "Owner".to_string(),
+            client_id: "Client".to_string(),
+            scope: "One two three scopes".parse().unwrap(),
+            redirect_uri: "https://example.com/redirect_me".parse().unwrap(),
+            until: chrono::TimeZone::timestamp_opt(&chrono::Utc, 1_700_000_000, 0).unwrap(),
+            extensions,
+        }
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn ed25519_roundtrip() {
+        let private_key = [7u8; 32];
+        let signer = Assertion::new(AssertionKind::Ed25519, &private_key);
+        let public_key = signer.public_key().unwrap();
+        let verifier = Assertion::ed25519_verifier(&public_key).unwrap();
+
+        let grant = test_grant();
+        let token = signer.tag("authorization").sign(0, &grant).unwrap();
+
+        assert_eq!(signer.tag("authorization").extract(&token), Ok(grant.clone()));
+        assert_eq!(verifier.tag("authorization").extract(&token), Ok(grant.clone()));
+        // The tag is still checked.
+        assert!(verifier.tag("refresh").extract(&token).is_err());
+        // A verifier can not sign grants.
+        assert!(verifier.tag("authorization").sign(1, &grant).is_err());
+        assert!(TagGrant::tag(&mut &verifier, 1, &grant).is_err());
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn ed25519_wrong_key() {
+        let signer = Assertion::ed25519(&[7u8; 32]);
+        let other = Assertion::ed25519(&[8u8; 32]);
+        let verifier = Assertion::ed25519_verifier(&other.public_key().unwrap()).unwrap();
+        let hmac = Assertion::new(AssertionKind::HmacSha256, &[7u8; 32]);
+
+        let grant = test_grant();
+        let token = signer.tag("authorization").sign(0, &grant).unwrap();
+        assert!(verifier.tag("authorization").extract(&token).is_err());
+        assert!(hmac.tag("authorization").extract(&token).is_err());
+
+        let hmac_token = hmac.tag("authorization").sign(0, &grant).unwrap();
+        assert!(signer.tag("authorization").extract(&hmac_token).is_err());
+    }
+
+    #[cfg(feature = "chacha20poly1305")]
+    #[test]
+    fn encryption_errors() {
+        let encryption = Encryption::new(&[7u8; 32]);
+        let other = Encryption::new(&[8u8; 32]);
+
+        let grant = test_grant();
+        let token = encryption.tag("authorization").seal(&grant).unwrap();
+        assert_eq!(encryption.tag("authorization").open(&token), Ok(grant));
+        assert_eq!(
+            encryption.tag("refresh").open(&token),
+            Err(EncryptionError::Invalid)
+        );
+        assert_eq!(
+            other.tag("authorization").open(&token),
+            Err(EncryptionError::Invalid)
+        );
+        assert_eq!(
+            encryption.tag("authorization").open("not a token"),
+            Err(EncryptionError::Invalid)
+        );
+    }
 }
@@ -8,6 +8,8 @@ use std::collections::hash_map::Iter;
 use std::rc::Rc;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
 /// Provides a name registry for extensions.
 pub trait GrantExtension {
     /// An unique identifier distinguishing this extension type for parsing and storing.
@@ -24,7 +26,7 @@ pub trait GrantExtension {
 ///
 /// Some extensions have semantics where the presence alone is the stored data, so storing data
 /// is optional and storing no data is distinct from not attaching any extension instance at all.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Value {
     /// An extension that the token owner is allowed to read and interpret.
     Public(Option<String>),
@@ -39,7 +41,19 @@ pub enum Value {
 ///
 /// This also serves as a clean interface for both frontend and backend to reliably and
 /// conveniently manipulate or query the stored data sets.
-#[derive(Clone, Default, Debug, PartialEq, Eq)]
+///
+/// ## Which values reach the access token
+///
+/// Extension data of an authorization request is stored with the authorization code. When the
+/// code is redeemed, only the values returned by the access token extension are attached to the
+/// access token. With an `AddonList` this is the `AddonResult::Data` of each `AccessTokenAddon`,
+/// which receives the value of its own identifier from the code. All other values are dropped.
+/// A refresh keeps the values of the refreshed grant unless a refresh addon replaces them.
+///
+/// The grant returned by the resource flow carries the values of the access token, so a handler
+/// can read them with `public_value` and `private_value`. Note that a `TokenSigner` can not
+/// issue tokens for grants with private extensions, their content would not be confidential.
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Extensions {
     extensions: HashMap<String, Value>,
 }
@@ -48,7 +62,7 @@ pub struct Extensions {
 ///
 /// This can be stored in a database without worrying about lifetimes or shared across thread
 /// boundaries. A reference to this can be converted to a purely referential `GrantRef`.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Grant {
     /// Identifies the owner of the resource.
     pub owner_id: String,
@@ -159,6 +173,34 @@ impl Extensions {
         self.extensions.remove(extension.identifier())
     }
 
+    /// Inspect the stored data of an extension by its identifier.
+    ///
+    /// Unlike `remove`, this leaves the data in place.
+    pub fn get(&self, identifier: &str) -> Option<&Value> {
+        self.extensions.get(identifier)
+    }
+
+    /// Inspect the stored data of an extension instance.
+    ///
+    /// This looks up the data under the identifier of the extension, the same key `set` uses.
+    pub fn value_of(&self, extension: &dyn GrantExtension) -> Option<&Value> {
+        self.get(extension.identifier())
+    }
+
+    /// The content of a public extension.
+    ///
+    /// Returns `None` if the extension is not present, is private, or consists only of the key.
+    pub fn public_value(&self, identifier: &str) -> Option<&str> {
+        self.get(identifier)?.public_value().ok()?
+    }
+
+    /// The content of a private extension.
+    ///
+    /// Returns `None` if the extension is not present, is public, or consists only of the key.
+    pub fn private_value(&self, identifier: &str) -> Option<&str> {
+        self.get(identifier)?.private_value().ok()?
+    }
+
     /// Iterate of the public extensions whose presence and content is not secret.
     pub fn public(&self) -> PublicExtensions {
         PublicExtensions {
@@ -288,4 +330,24 @@ mod tests {
         );
         assert_eq!(extensions.private().count(), 2);
     }
+
+    #[test]
+    fn lookup() {
+        let mut extensions = Extensions::new();
+        extensions.set_raw("pub".into(), Value::Public(Some("content".into())));
+        extensions.set_raw("pub_none".into(), Value::Public(None));
+        extensions.set_raw("priv".into(), Value::Private(Some("private".into())));
+
+        assert_eq!(extensions.public_value("pub"), Some("content"));
+        assert_eq!(extensions.public_value("pub_none"), None);
+        assert_eq!(extensions.public_value("priv"), None);
+        assert_eq!(extensions.public_value("missing"), None);
+
+        assert_eq!(extensions.private_value("priv"), Some("private"));
+        assert_eq!(extensions.private_value("pub"), None);
+
+        // Presence without content is still visible.
+        assert_eq!(extensions.get("pub_none"), Some(&Value::Public(None)));
+        assert_eq!(extensions.get("missing"), None);
+    }
 }
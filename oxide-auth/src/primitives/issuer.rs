@@ -3,16 +3,24 @@
 //! Internally similar to the authorization module, tokens generated here live longer and can be
 //! renewed. There exist two fundamental implementation as well, one utilizing in memory hash maps
 //! while the other uses cryptographic signing.
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, MutexGuard, RwLockWriteGuard};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use chrono::{Duration, Utc};
+use chrono::{Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
 use crate::{endpoint::PreGrant, code_grant::accesstoken::BearerToken};
 use super::Time;
+use super::clock::{Clock, SystemClock};
+use super::subject::{PublicSubject, SubjectEncoder};
+use super::binding::Binding;
 use super::grant::Grant;
+use super::scope::Scope;
 use super::generator::{TagGrant, TaggedAssertion, Assertion};
+#[cfg(feature = "chacha20poly1305")]
+use super::generator::{Encryption, TaggedEncryption};
+use super::jwt::JwtProfile;
 
 /// Issuers create bearer tokens.
 ///
@@ -24,7 +32,30 @@ pub trait Issuer {
     /// Create a token authorizing the request parameters
     fn issue(&mut self, grant: Grant) -> Result<IssuedToken, ()>;
 
+    /// Create a token, choosing whether it gets a refresh token and for how long that is valid.
+    ///
+    /// The default implementation issues a token as `issue` does. If no refresh token should be
+    /// issued, it is dropped from the result. A lifetime for the refresh token can not be enforced
+    /// on other issuers, so this fails unless the issuer overrides this method. `TokenMap` does.
+    fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        match refresh {
+            RefreshIssuance::Default => self.issue(grant),
+            RefreshIssuance::Never => {
+                let issued = self.issue(grant)?;
+                Ok(IssuedToken {
+                    refresh: None,
+                    refresh_until: None,
+                    ..issued
+                })
+            }
+            RefreshIssuance::ValidFor(_) => Err(()),
+        }
+    }
+
     /// Refresh a token.
+    ///
+    /// Stateful issuers should invalidate the access token previously issued for the grant, as
+    /// `TokenMap` does. Signed tokens can not be revoked and stay valid until they expire.
     fn refresh(&mut self, _refresh: &str, _grant: Grant) -> Result<RefreshedToken, ()>;
 
     /// Get the values corresponding to a bearer token
@@ -32,6 +63,78 @@ pub trait Issuer {
 
     /// Get the values corresponding to a refresh token
     fn recover_refresh<'a>(&'a self, _: &'a str) -> Result<Option<Grant>, ()>;
+
+    /// Extend the expiry of an access token in place, for sliding sessions.
+    ///
+    /// The issuer may limit the expiry to an earlier time but should never shorten it. Returns the
+    /// expiry of the token after the update. Issuers that can not change tokens once issued, such
+    /// as signers, return an error which is also the default implementation.
+    fn touch(&mut self, _token: &str, _until: Time) -> Result<Time, ()> {
+        Err(())
+    }
+
+    /// Revoke an access or a refresh token before it expires.
+    ///
+    /// Revoking a token that is not known succeeds, it is invalid in any case. Issuers that can
+    /// not revoke their tokens, such as signers, return an error which is also the default
+    /// implementation.
+    fn revoke_token(&mut self, _token: &str) -> Result<(), ()> {
+        Err(())
+    }
+}
+
+/// Enumerates the grants an issuer holds, for the management of authorized clients.
+///
+/// Only issuers which keep their tokens can implement this meaningfully. A consent management
+/// page would show an owner the result of [`authorized_clients`] and offer to revoke the grants
+/// of each client.
+///
+/// [`authorized_clients`]: #method.authorized_clients
+pub trait GrantQuery {
+    /// Summaries of all grants of the owner with a valid access or refresh token.
+    ///
+    /// There is one summary for each grant, so a client authorized several times appears several
+    /// times. Issuers which can not enumerate their tokens return an empty list.
+    fn grants_for_owner(&self, owner_id: &str) -> Vec<GrantSummary>;
+
+    /// The clients the owner has authorized, each with the scope of all its grants combined.
+    ///
+    /// The expiry of each summary is the latest of its grants. Clients are sorted by their id.
+    fn authorized_clients(&self, owner_id: &str) -> Vec<GrantSummary> {
+        let mut clients: Vec<GrantSummary> = Vec::new();
+        for grant in self.grants_for_owner(owner_id) {
+            match clients
+                .iter_mut()
+                .find(|client| client.client_id == grant.client_id)
+            {
+                Some(client) => {
+                    client.scope = client.scope.union(&grant.scope);
+                    client.until = client.until.max(grant.until);
+                }
+                None => clients.push(grant),
+            }
+        }
+
+        clients.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        clients
+    }
+}
+
+/// The client and scope of a grant, as listed by a `GrantQuery`.
+///
+/// The summary never contains the tokens themselves, so it can be shown to the owner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrantSummary {
+    /// The client holding the grant.
+    pub client_id: String,
+
+    /// The scope granted to the client.
+    pub scope: Scope,
+
+    /// The expiry of the access token of the grant.
+    ///
+    /// A grant with a refresh token may be listed after its access token has expired.
+    pub until: Time,
 }
 
 /// Token parameters returned to a client.
@@ -51,6 +154,24 @@ pub struct IssuedToken {
 
     /// The type of the token.
     pub token_type: TokenType,
+
+    /// Expiration timestamp of the refresh token, if the issuer limits its lifetime.
+    pub refresh_until: Option<Time>,
+}
+
+/// Whether a refresh token is issued along with an access token, see `Issuer::issue_with`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshIssuance {
+    /// Follow the configuration of the issuer.
+    Default,
+
+    /// Issue only an access token, as for the client credentials grant.
+    Never,
+
+    /// Issue a refresh token that expires after the duration, independent of the access token.
+    ///
+    /// The tokens obtained by exchanging it keep this lifetime.
+    ValidFor(Duration),
 }
 
 /// The type of token, describing proper usage.
@@ -63,12 +184,38 @@ pub struct IssuedToken {
 /// In other context (RFC 8693) the explicitly non-access-token kind `N_A` also exists but this is
 /// not a possible response.
 #[non_exhaustive]
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TokenType {
     /// A bearer token used on its own in an Authorization header.
     ///
     /// For this variant and its usage see RFC 6750.
     Bearer,
+
+    /// A token bound to a key of the client, used with the `DPoP` authorization scheme.
+    ///
+    /// Each request must be accompanied by a proof of possession of the key, see RFC 9449 and
+    /// the [`Binding`] of the grant.
+    ///
+    /// [`Binding`]: ../binding/enum.Binding.html
+    DPoP,
+
+    /// A token of another type, advertised and presented with the given name.
+    ///
+    /// Such tokens are not bound to any key and are otherwise used like bearer tokens. Issuers
+    /// advertise the type once configured with it, for example with `TokenMap::token_type`. The
+    /// resource flow then needs to accept the scheme with `ResourceFlow::token_type`.
+    Custom(&'static str),
+}
+
+impl TokenType {
+    /// The name of the type in the `token_type` parameter of a token response.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenType::Bearer => "bearer",
+            TokenType::DPoP => "DPoP",
+            TokenType::Custom(name) => name,
+        }
+    }
 }
 
 /// Refresh token information returned to a client.
@@ -90,6 +237,45 @@ pub struct RefreshedToken {
 
     /// The type of the new access token.
     pub token_type: TokenType,
+
+    /// Expiration timestamp of the new refresh token, if the issuer limits its lifetime.
+    pub refresh_until: Option<Time>,
+}
+
+/// A change of a refresh token observed by a `TokenMap`.
+///
+/// Events identify the grant by its client and owner and never contain the tokens themselves, so
+/// they can be forwarded to external monitoring. See [`TokenMap::on_refresh_event`].
+///
+/// [`TokenMap::on_refresh_event`]: struct.TokenMap.html#method.on_refresh_event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefreshEvent {
+    /// What happened to the refresh token.
+    pub kind: RefreshEventKind,
+
+    /// The client the refresh token was issued to.
+    pub client_id: String,
+
+    /// The resource owner who authorized the grant.
+    pub owner_id: String,
+
+    /// When the event was observed, according to the clock of the issuer.
+    pub time: Time,
+}
+
+/// The kind of a `RefreshEvent`.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshEventKind {
+    /// The refresh token was exchanged for a new access and refresh token.
+    Rotated,
+
+    /// A refresh token was presented again after it had already been exchanged.
+    ///
+    /// Only the client and the holder of a stolen copy know the old token, so this indicates that
+    /// one of them is compromised. Consider revoking the tokens of the grant and asking the owner
+    /// to authorize the client again.
+    Reused,
 }
 
 /// Keeps track of access and refresh tokens by a hash-map.
@@ -97,12 +283,50 @@ pub struct RefreshedToken {
 /// The generator is itself trait based and can be chosen during construction. It is assumed to not
 /// be possible (or at least very unlikely during their overlapping lifetime) for two different
 /// grants to generate the same token in the grant tagger.
+///
+/// Refreshing a grant always revokes its previous access token together with the used refresh
+/// token, so only the newest access token of each grant is accepted by the resource flow.
 pub struct TokenMap<G: TagGrant = Box<dyn TagGrant + Send + Sync + 'static>> {
     duration: Option<Duration>,
+    refresh_duration: Option<Duration>,
+    reuse_window: Duration,
+    max_lifetime: Option<Duration>,
+    scope_lifetimes: HashMap<String, Duration>,
+    token_type: TokenType,
     generator: G,
     usage: u64,
     access: HashMap<Arc<str>, Arc<Token>>,
     refresh: HashMap<Arc<str>, Arc<Token>>,
+    retired: HashMap<Arc<str>, Retired>,
+    refresh_listener: Option<RefreshListener>,
+    clock: Arc<dyn Clock + Send + Sync>,
+    owner_limit: Option<usize>,
+    client_limit: Option<usize>,
+    holders: Holders,
+}
+
+type RefreshListener = Arc<dyn Fn(&RefreshEvent) + Send + Sync>;
+
+/// The access and refresh token of each entry held by an owner or a client.
+///
+/// Ordered by the usage counter when the entry was issued or refreshed, so that the least recent
+/// one can be evicted without scanning all tokens.
+type Held = BTreeMap<(u64, Arc<str>), Option<Arc<str>>>;
+
+/// Indexes the entries of the access and refresh maps by owner and by client.
+#[derive(Default)]
+struct Holders {
+    owners: HashMap<String, Held>,
+    clients: HashMap<String, Held>,
+}
+
+/// A refresh token that was exchanged, remembered to detect its reuse.
+struct Retired {
+    client_id: String,
+    owner_id: String,
+
+    /// When the token is forgotten, at the end of its lifetime or of the reuse window.
+    until: Time,
 }
 
 struct Token {
@@ -114,6 +338,60 @@ struct Token {
 
     /// The grant that was originally granted.
     grant: Grant,
+
+    /// The latest expiry this token can be extended to.
+    ceiling: Option<Time>,
+
+    /// The expiry of the refresh token, if it is limited.
+    refresh_until: Option<Time>,
+
+    /// The lifetime of refresh tokens for this grant, if it differs from the issuer's.
+    refresh_lifetime: Option<Duration>,
+
+    /// The usage counter when the token was last issued or refreshed, orders tokens for eviction.
+    issued: u64,
+}
+
+/// The serializable state of a `TokenMap`.
+///
+/// Created by [`TokenMap::snapshot`] and consumed by [`TokenMap::restore`]. This contains all
+/// access and refresh tokens in plain and must be kept as confidential as the tokens themselves.
+///
+/// [`TokenMap::snapshot`]: struct.TokenMap.html#method.snapshot
+/// [`TokenMap::restore`]: struct.TokenMap.html#method.restore
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenMapSnapshot {
+    usage: u64,
+    tokens: Vec<TokenSnapshot>,
+    /// Exchanged refresh tokens, remembered to detect their reuse.
+    #[serde(default)]
+    retired: Vec<RetiredSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RetiredSnapshot {
+    refresh: String,
+    client_id: String,
+    owner_id: String,
+    until: Time,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TokenSnapshot {
+    access: String,
+    /// The access token was revoked while the refresh token is still valid.
+    access_revoked: bool,
+    refresh: Option<String>,
+    grant: Grant,
+    #[serde(default)]
+    ceiling: Option<Time>,
+    #[serde(default)]
+    refresh_until: Option<Time>,
+    /// The lifetime of refresh tokens for this grant in seconds, if it differs from the issuer's.
+    #[serde(default)]
+    refresh_lifetime: Option<i64>,
+    #[serde(default)]
+    issued: u64,
 }
 
 impl<G: TagGrant> TokenMap<G> {
@@ -121,13 +399,34 @@ impl<G: TagGrant> TokenMap<G> {
     pub fn new(generator: G) -> Self {
         Self {
             duration: None,
+            refresh_duration: None,
+            reuse_window: Duration::days(30),
+            max_lifetime: None,
+            scope_lifetimes: HashMap::new(),
+            token_type: TokenType::Bearer,
             generator,
             usage: 0,
             access: HashMap::new(),
             refresh: HashMap::new(),
+            retired: HashMap::new(),
+            refresh_listener: None,
+            clock: Arc::new(SystemClock),
+            owner_limit: None,
+            client_limit: None,
+            holders: Holders::default(),
         }
     }
 
+    /// Use a different clock to determine the expiry of tokens, instead of the system time.
+    ///
+    /// This is mainly useful in tests, to advance time deterministically with a [`ManualClock`].
+    ///
+    /// [`ManualClock`]: ../clock/struct.ManualClock.html
+    pub fn with_clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
     /// Set the validity of all issued grants to the specified duration.
     pub fn valid_for(&mut self, duration: Duration) {
         self.duration = Some(duration);
@@ -138,6 +437,115 @@ impl<G: TagGrant> TokenMap<G> {
         self.duration = None;
     }
 
+    /// Advertise issued tokens with another type than `bearer`.
+    ///
+    /// This only changes the type of tokens whose grant is not bound to a key, bound tokens keep
+    /// the type of their binding. The resource flow must accept the same type.
+    pub fn token_type(&mut self, token_type: TokenType) {
+        self.token_type = token_type;
+    }
+
+    /// Let refresh tokens expire after the specified duration.
+    ///
+    /// The lifetime starts anew whenever a refresh token is exchanged for a new one. By default,
+    /// refresh tokens stay valid until they are used or revoked.
+    pub fn refresh_valid_for(&mut self, duration: Duration) {
+        self.refresh_duration = Some(duration);
+    }
+
+    /// Report the rotation and the reuse of refresh tokens to a listener.
+    ///
+    /// The listener is called with a [`RefreshEvent`] whenever a refresh token is exchanged and
+    /// whenever an already exchanged refresh token is presented again. It may for example send
+    /// the event to a channel, but should not block. To detect reuse, exchanged refresh tokens are
+    /// remembered in memory until `purge_expired` is called after their lifetime set with
+    /// `refresh_valid_for` or the window set with `detect_reuse_for` is over, whichever ends first.
+    /// They are part of snapshots, so reuse is still detected after restoring one.
+    ///
+    /// ```
+    /// # use oxide_auth::primitives::issuer::{Issuer, RefreshEventKind, TokenMap};
+    /// # use oxide_auth::primitives::generator::RandomGenerator;
+    /// # use oxide_auth::primitives::grant::{Extensions, Grant};
+    /// # use chrono::{Duration, Utc};
+    /// use std::sync::mpsc;
+    ///
+    /// let (sender, events) = mpsc::channel();
+    /// let sender = std::sync::Mutex::new(sender);
+    /// let mut issuer = TokenMap::new(RandomGenerator::new(16));
+    /// issuer.on_refresh_event(move |event| {
+    ///     let _ = sender.lock().unwrap().send(event.clone());
+    /// });
+    /// # let grant = Grant {
+    /// #     client_id: "client".into(),
+    /// #     owner_id: "owner".into(),
+    /// #     redirect_uri: "https://client.example/endpoint".parse().unwrap(),
+    /// #     scope: "default".parse().unwrap(),
+    /// #     until: Utc::now() + Duration::hours(1),
+    /// #     extensions: Extensions::new(),
+    /// # };
+    ///
+    /// let refresh = issuer.issue(grant.clone()).unwrap().refresh.unwrap();
+    /// issuer.refresh(&refresh, grant).unwrap();
+    /// assert!(issuer.recover_refresh(&refresh).unwrap().is_none());
+    ///
+    /// assert_eq!(events.recv().unwrap().kind, RefreshEventKind::Rotated);
+    /// let reused = events.recv().unwrap();
+    /// assert_eq!(reused.kind, RefreshEventKind::Reused);
+    /// assert_eq!(reused.owner_id, "owner");
+    /// ```
+    ///
+    /// [`RefreshEvent`]: struct.RefreshEvent.html
+    pub fn on_refresh_event<F>(&mut self, listener: F)
+    where
+        F: Fn(&RefreshEvent) + Send + Sync + 'static,
+    {
+        self.refresh_listener = Some(Arc::new(listener));
+    }
+
+    /// Remember exchanged refresh tokens for at most this duration to detect their reuse.
+    ///
+    /// Only used with a listener set by `on_refresh_event`. Afterwards `purge_expired` forgets the
+    /// token and presenting it again is no longer reported. The default is 30 days, bounding the
+    /// memory used by exchanged tokens when refresh tokens do not expire.
+    pub fn detect_reuse_for(&mut self, window: Duration) {
+        self.reuse_window = window;
+    }
+
+    /// Allow extending access tokens with `touch`, up to the maximum lifetime after issuing.
+    ///
+    /// This only applies to tokens issued or refreshed afterwards. By default, tokens can not be
+    /// extended.
+    pub fn sliding_expiry(&mut self, max_lifetime: Duration) {
+        self.max_lifetime = Some(max_lifetime);
+    }
+
+    /// Limit the lifetime of all tokens granting the scope token.
+    ///
+    /// A token granting several limited scope tokens expires after the shortest of their
+    /// lifetimes. This also applies to refreshed tokens and bounds their sliding expiry.
+    pub fn scope_lifetime<S: Into<String>>(&mut self, scope: S, lifetime: Duration) {
+        self.scope_lifetimes.insert(scope.into(), lifetime);
+    }
+
+    /// Keep at most `limit` tokens for each resource owner.
+    ///
+    /// Issuing another token to an owner who already holds the maximum number of tokens first
+    /// deletes their least recently issued or refreshed one, together with its refresh token. This
+    /// bounds the memory a misbehaving client can claim by requesting tokens over and over. A
+    /// limit of `1` allows a single session per owner, a limit of `0` is treated as `1`. By
+    /// default the number of tokens is unlimited.
+    pub fn max_tokens_per_owner(&mut self, limit: usize) {
+        self.owner_limit = Some(limit);
+    }
+
+    /// Keep at most `limit` tokens for each client, across all of its resource owners.
+    ///
+    /// Evicts the least recently issued or refreshed token of the client like
+    /// `max_tokens_per_owner`. By default the number of tokens is unlimited.
+    pub fn max_tokens_per_client(&mut self, limit: usize) {
+        self.client_limit = Some(limit);
+    }
+
     /// Unconditionally delete grant associated with the token.
     ///
     /// This is the main advantage over signing tokens. By keeping internal state of allowed
@@ -145,43 +553,343 @@ impl<G: TagGrant> TokenMap<G> {
     /// naturally. There is no differentiation between access and refresh tokens since these should
     /// have a marginal probability of colliding.
     pub fn revoke(&mut self, token: &str) {
-        self.access.remove(token);
-        self.refresh.remove(token);
+        let access = self.access.remove(token);
+        let refresh = self.refresh.remove(token);
+        for revoked in access.iter().chain(&refresh) {
+            self.release_unheld(revoked);
+        }
+    }
+
+    /// Delete all access and refresh tokens the owner has granted to the client.
+    ///
+    /// This withdraws the authorization of a client listed by [`GrantQuery::authorized_clients`].
+    ///
+    /// [`GrantQuery::authorized_clients`]: trait.GrantQuery.html#method.authorized_clients
+    pub fn revoke_client(&mut self, owner_id: &str, client_id: &str) {
+        let granted =
+            |token: &Token| token.grant.owner_id == owner_id && token.grant.client_id == client_id;
+        self.retain(|token| !granted(token), |token| !granted(token));
+    }
+
+    /// Delete all access and refresh tokens which have expired according to the clock.
+    ///
+    /// Refresh tokens stay valid after their access token has expired, they are only deleted once
+    /// their own lifetime set with `refresh_valid_for` is over. Exchanged refresh tokens remembered
+    /// to detect their reuse are deleted as well, see `detect_reuse_for`.
+    pub fn purge_expired(&mut self) {
+        let now = self.clock.now();
+        self.retain(
+            |token| token.grant.until >= now,
+            |token| !token.refresh_expired(now),
+        );
+        self.retired.retain(|_, retired| retired.until >= now);
     }
 
     /// Directly associate token with grant.
     ///
     /// No checks on the validity of the grant are performed but the expiration time of the grant
-    /// is modified (if a `duration` was previously set).
+    /// is modified (if a `duration` or the lifetime of one of its scopes was previously set).
     pub fn import_grant(&mut self, token: String, mut grant: Grant) {
         self.set_duration(&mut grant);
+        self.make_room(&grant);
+        let ceiling = self.ceiling(&grant);
         let key: Arc<str> = Arc::from(token);
-        let token = Token::from_access(key.clone(), grant);
+        let token = Token::from_access(key.clone(), grant, ceiling, self.usage);
+        self.holders.hold(&token);
         self.access.insert(key, Arc::new(token));
+        // Keep the usage in steps of two, as if a refresh token had been generated as well.
+        self.usage = self.usage.wrapping_add(2);
+    }
+
+    /// Capture all access and refresh tokens, for example to persist them across restarts.
+    ///
+    /// Exchanged refresh tokens remembered to detect their reuse are captured as well.
+    pub fn snapshot(&self) -> TokenMapSnapshot {
+        let access = self.access.values().map(|token| (token, false));
+        // Refresh tokens whose access token was revoked on its own are still valid.
+        let refresh_only = self
+            .refresh
+            .values()
+            .filter(|token| !self.access.contains_key(&token.access))
+            .map(|token| (token, true));
+
+        let tokens = access
+            .chain(refresh_only)
+            .map(|(token, access_revoked)| TokenSnapshot {
+                access: token.access.to_string(),
+                access_revoked,
+                refresh: token.refresh.as_ref().map(ToString::to_string),
+                grant: token.grant.clone(),
+                ceiling: token.ceiling,
+                refresh_until: token.refresh_until,
+                refresh_lifetime: token.refresh_lifetime.map(|lifetime| lifetime.num_seconds()),
+                issued: token.issued,
+            })
+            .collect();
+
+        let retired = self
+            .retired
+            .iter()
+            .map(|(refresh, retired)| RetiredSnapshot {
+                refresh: refresh.to_string(),
+                client_id: retired.client_id.clone(),
+                owner_id: retired.owner_id.clone(),
+                until: retired.until,
+            })
+            .collect();
+
+        TokenMapSnapshot {
+            usage: self.usage,
+            tokens,
+            retired,
+        }
+    }
+
+    /// Replace all access and refresh tokens with those of a snapshot.
+    ///
+    /// The tokens keep the expiration time they were issued with, regardless of the currently
+    /// configured duration.
+    pub fn restore(&mut self, snapshot: TokenMapSnapshot) {
+        self.usage = snapshot.usage;
+        self.access.clear();
+        self.refresh.clear();
+        self.holders = Holders::default();
+        self.retired = snapshot
+            .retired
+            .into_iter()
+            .map(|retired| {
+                let entry = Retired {
+                    client_id: retired.client_id,
+                    owner_id: retired.owner_id,
+                    until: retired.until,
+                };
+                (Arc::from(retired.refresh), entry)
+            })
+            .collect();
+
+        for token in snapshot.tokens {
+            let access: Arc<str> = Arc::from(token.access);
+            let refresh: Option<Arc<str>> = token.refresh.map(Arc::from);
+            let entry = Arc::new(Token {
+                access: access.clone(),
+                refresh: refresh.clone(),
+                grant: token.grant,
+                ceiling: token.ceiling,
+                refresh_until: token.refresh_until,
+                refresh_lifetime: token.refresh_lifetime.map(Duration::seconds),
+                issued: token.issued,
+            });
+
+            self.holders.hold(&entry);
+            if let Some(refresh) = refresh {
+                self.refresh.insert(refresh, entry.clone());
+            }
+            if !token.access_revoked {
+                self.access.insert(access, entry);
+            }
+        }
     }
 
     fn set_duration(&self, grant: &mut Grant) {
         if let Some(duration) = &self.duration {
-            grant.until = Utc::now() + *duration;
+            grant.until = self.clock.now() + *duration;
+        }
+
+        if let Some(lifetime) = scope_lifetime(&self.scope_lifetimes, grant) {
+            grant.until = grant.until.min(self.clock.now() + lifetime);
+        }
+    }
+
+    fn ceiling(&self, grant: &Grant) -> Option<Time> {
+        let now = self.clock.now();
+        let ceiling = now + self.max_lifetime?;
+        // Sliding expiry must not extend a token beyond the lifetime of its scopes.
+        match scope_lifetime(&self.scope_lifetimes, grant) {
+            Some(lifetime) => Some(ceiling.min(now + lifetime)),
+            None => Some(ceiling),
+        }
+    }
+
+    /// Evict tokens of the owner and client of the grant until another one fits their limits.
+    fn make_room(&mut self, grant: &Grant) {
+        if let Some(limit) = self.owner_limit {
+            while let Some(oldest) = Holders::excess(&self.holders.owners, &grant.owner_id, limit) {
+                self.evict(oldest);
+            }
+        }
+
+        if let Some(limit) = self.client_limit {
+            while let Some(oldest) = Holders::excess(&self.holders.clients, &grant.client_id, limit) {
+                self.evict(oldest);
+            }
+        }
+    }
+
+    /// Delete the access and refresh token of an entry.
+    fn evict(&mut self, (access, refresh): (Arc<str>, Option<Arc<str>>)) {
+        let access = self.access.remove(&access);
+        let refresh = refresh.and_then(|refresh| self.refresh.remove(&refresh));
+        if let Some(evicted) = access.or(refresh) {
+            self.holders.release(&evicted);
+        }
+    }
+
+    /// Keep only the matching access and refresh tokens.
+    fn retain(
+        &mut self, mut access: impl FnMut(&Token) -> bool, mut refresh: impl FnMut(&Token) -> bool,
+    ) {
+        let mut dropped = Vec::new();
+        self.access.retain(|_, token| {
+            let keep = access(token);
+            if !keep {
+                dropped.push(token.clone());
+            }
+            keep
+        });
+        self.refresh.retain(|_, token| {
+            let keep = refresh(token);
+            if !keep {
+                dropped.push(token.clone());
+            }
+            keep
+        });
+
+        for token in dropped {
+            self.release_unheld(&token);
+        }
+    }
+
+    /// Remove an entry from the indexes once neither its access nor its refresh token remains.
+    fn release_unheld(&mut self, token: &Token) {
+        let access = self.access.contains_key(&token.access);
+        let refresh = match &token.refresh {
+            Some(refresh) => self.refresh.contains_key(refresh),
+            None => false,
+        };
+
+        if !access && !refresh {
+            self.holders.release(token);
+        }
+    }
+
+    fn issue_unrefreshable(&mut self, mut grant: Grant) -> Result<IssuedToken, ()> {
+        self.set_duration(&mut grant);
+        let access = self.generator.tag(self.usage, &grant)?;
+        debug_assert!(
+            !access.is_empty(),
+            "An empty access token was generated, this is horribly insecure."
+        );
+        self.make_room(&grant);
+
+        let until = grant.until;
+        let token_type = Binding::token_type_or(&grant, self.token_type);
+        let ceiling = self.ceiling(&grant);
+        let access_key: Arc<str> = Arc::from(access.clone());
+        let token = Token::from_access(access_key.clone(), grant, ceiling, self.usage);
+
+        self.holders.hold(&token);
+        self.access.insert(access_key, Arc::new(token));
+        // Keep the usage in steps of two, as if a refresh token had been generated as well.
+        self.usage = self.usage.wrapping_add(2);
+        Ok(IssuedToken {
+            token: access,
+            refresh: None,
+            until,
+            token_type,
+            refresh_until: None,
+        })
+    }
+
+    fn refresh_until(&self, lifetime: Option<Duration>) -> Option<Time> {
+        lifetime
+            .or(self.refresh_duration)
+            .map(|duration| self.clock.now() + duration)
+    }
+
+    fn refresh_event(&self, kind: RefreshEventKind, client_id: &str, owner_id: &str) {
+        if let Some(listener) = &self.refresh_listener {
+            listener(&RefreshEvent {
+                kind,
+                client_id: client_id.to_string(),
+                owner_id: owner_id.to_string(),
+                time: self.clock.now(),
+            });
         }
     }
 }
 
 impl Token {
-    fn from_access(access: Arc<str>, grant: Grant) -> Self {
+    fn from_access(access: Arc<str>, grant: Grant, ceiling: Option<Time>, issued: u64) -> Self {
         Token {
             access,
             refresh: None,
             grant,
+            ceiling,
+            refresh_until: None,
+            refresh_lifetime: None,
+            issued,
         }
     }
 
-    fn from_refresh(access: Arc<str>, refresh: Arc<str>, grant: Grant) -> Self {
+    fn from_refresh(
+        access: Arc<str>, refresh: Arc<str>, grant: Grant, ceiling: Option<Time>,
+        refresh_until: Option<Time>, refresh_lifetime: Option<Duration>, issued: u64,
+    ) -> Self {
         Token {
             access,
             refresh: Some(refresh),
             grant,
+            ceiling,
+            refresh_until,
+            refresh_lifetime,
+            issued,
+        }
+    }
+
+    fn refresh_expired(&self, now: Time) -> bool {
+        matches!(self.refresh_until, Some(until) if until < now)
+    }
+}
+
+impl Holders {
+    fn hold(&mut self, token: &Token) {
+        let key = (token.issued, token.access.clone());
+        for index in [
+            self.owners.entry(token.grant.owner_id.clone()).or_default(),
+            self.clients.entry(token.grant.client_id.clone()).or_default(),
+        ] {
+            index.insert(key.clone(), token.refresh.clone());
+        }
+    }
+
+    fn release(&mut self, token: &Token) {
+        let key = (token.issued, token.access.clone());
+        Self::remove(&mut self.owners, &token.grant.owner_id, &key);
+        Self::remove(&mut self.clients, &token.grant.client_id, &key);
+    }
+
+    fn remove(index: &mut HashMap<String, Held>, id: &str, key: &(u64, Arc<str>)) {
+        if let Some(held) = index.get_mut(id) {
+            held.remove(key);
+            if held.is_empty() {
+                index.remove(id);
+            }
+        }
+    }
+
+    /// The least recent entry of the owner or client if it holds at least `limit` entries.
+    ///
+    /// A limit of `0` is treated as `1`.
+    fn excess(
+        index: &HashMap<String, Held>, id: &str, limit: usize,
+    ) -> Option<(Arc<str>, Option<Arc<str>>)> {
+        let held = index.get(id)?;
+        if held.len() < limit.max(1) {
+            return None;
         }
+
+        let ((_, access), refresh) = held.iter().next()?;
+        Some((access.clone(), refresh.clone()))
     }
 }
 
@@ -225,6 +933,7 @@ impl IssuedToken {
             refresh: None,
             until,
             token_type: TokenType::Bearer,
+            refresh_until: None,
         }
     }
 
@@ -242,7 +951,17 @@ impl IssuedToken {
 }
 
 impl<G: TagGrant> Issuer for TokenMap<G> {
-    fn issue(&mut self, mut grant: Grant) -> Result<IssuedToken, ()> {
+    fn issue(&mut self, grant: Grant) -> Result<IssuedToken, ()> {
+        self.issue_with(grant, RefreshIssuance::Default)
+    }
+
+    fn issue_with(&mut self, mut grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        let refresh_lifetime = match refresh {
+            RefreshIssuance::Default => None,
+            RefreshIssuance::Never => return self.issue_unrefreshable(grant),
+            RefreshIssuance::ValidFor(lifetime) => Some(lifetime),
+        };
+
         self.set_duration(&mut grant);
         // The (usage, grant) tuple needs to be unique. Since this wraps after 2^63 operations, we
         // expect the validity time of the grant to have changed by then. This works when you don't
@@ -264,10 +983,23 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
             (access, refresh)
         };
 
+        self.make_room(&grant);
         let until = grant.until;
+        let token_type = Binding::token_type_or(&grant, self.token_type);
+        let ceiling = self.ceiling(&grant);
+        let refresh_until = self.refresh_until(refresh_lifetime);
         let access_key: Arc<str> = Arc::from(access.clone());
         let refresh_key: Arc<str> = Arc::from(refresh.clone());
-        let token = Token::from_refresh(access_key.clone(), refresh_key.clone(), grant);
+        let token = Token::from_refresh(
+            access_key.clone(),
+            refresh_key.clone(),
+            grant,
+            ceiling,
+            refresh_until,
+            refresh_lifetime,
+            self.usage,
+        );
+        self.holders.hold(&token);
         let token = Arc::new(token);
 
         self.access.insert(access_key, token.clone());
@@ -277,7 +1009,8 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
             token: access,
             refresh: Some(refresh),
             until,
-            token_type: TokenType::Bearer,
+            token_type,
+            refresh_until,
         })
     }
 
@@ -292,6 +1025,9 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
         assert!(Arc::ptr_eq(token.refresh.as_ref().unwrap(), &refresh_key));
         self.set_duration(&mut grant);
         let until = grant.until;
+        let token_type = Binding::token_type_or(&grant, self.token_type);
+        let ceiling = self.ceiling(&grant);
+        let refresh_until = self.refresh_until(token.refresh_lifetime);
 
         let tag = self.usage;
         let new_access = self.generator.tag(tag, &grant)?;
@@ -306,6 +1042,24 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
             assert!(Arc::ptr_eq(&token, &atoken));
         }
 
+        self.refresh_event(
+            RefreshEventKind::Rotated,
+            &token.grant.client_id,
+            &token.grant.owner_id,
+        );
+        if self.refresh_listener.is_some() {
+            let forgotten = self.clock.now() + self.reuse_window;
+            let retired = Retired {
+                client_id: token.grant.client_id.clone(),
+                owner_id: token.grant.owner_id.clone(),
+                until: token
+                    .refresh_until
+                    .map_or(forgotten, |until| until.min(forgotten)),
+            };
+            self.retired.insert(refresh_key, retired);
+        }
+
+        self.holders.release(&token);
         {
             // Should now be the only `Arc` pointing to this.
             let mut_token = Arc::get_mut(&mut token)
@@ -314,7 +1068,11 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
             mut_token.access = new_access_key.clone();
             mut_token.refresh = Some(new_refresh_key.clone());
             mut_token.grant = grant;
+            mut_token.ceiling = ceiling;
+            mut_token.refresh_until = refresh_until;
+            mut_token.issued = self.usage;
         }
+        self.holders.hold(&token);
 
         self.access.insert(new_access_key, token.clone());
         self.refresh.insert(new_refresh_key, token);
@@ -324,7 +1082,8 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
             token: new_access,
             refresh: Some(new_refresh),
             until,
-            token_type: TokenType::Bearer,
+            token_type,
+            refresh_until,
         })
     }
 
@@ -333,20 +1092,125 @@ impl<G: TagGrant> Issuer for TokenMap<G> {
     }
 
     fn recover_refresh<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
-        Ok(self.refresh.get(token).map(|token| token.grant.clone()))
+        if let Some(retired) = self.retired.get(token) {
+            self.refresh_event(RefreshEventKind::Reused, &retired.client_id, &retired.owner_id);
+            return Ok(None);
+        }
+
+        let now = self.clock.now();
+        Ok(self
+            .refresh
+            .get(token)
+            .filter(|token| !token.refresh_expired(now))
+            .map(|token| token.grant.clone()))
+    }
+
+    fn touch(&mut self, token: &str, until: Time) -> Result<Time, ()> {
+        let old = self.access.get(token).ok_or(())?;
+        let ceiling = old.ceiling.ok_or(())?;
+        if old.grant.until < self.clock.now() {
+            // Expired tokens are not revived.
+            return Err(());
+        }
+
+        let until = until.min(ceiling).max(old.grant.until);
+        let mut grant = old.grant.clone();
+        grant.until = until;
+
+        // The entry is shared with the refresh token, replace it in both places.
+        let new = Arc::new(Token {
+            access: old.access.clone(),
+            refresh: old.refresh.clone(),
+            grant,
+            ceiling: old.ceiling,
+            refresh_until: old.refresh_until,
+            refresh_lifetime: old.refresh_lifetime,
+            issued: old.issued,
+        });
+
+        if let Some(refresh) = &new.refresh {
+            self.refresh.insert(refresh.clone(), new.clone());
+        }
+        self.access.insert(new.access.clone(), new);
+        Ok(until)
+    }
+
+    fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        // Revoking a refresh token also revokes the access token of its grant, RFC 7009.
+        let revoked = match self.refresh.remove(token) {
+            Some(revoked) => {
+                self.access.remove(&*revoked.access);
+                Some(revoked)
+            }
+            None => self.access.remove(token),
+        };
+
+        if let Some(revoked) = revoked {
+            self.release_unheld(&revoked);
+        }
+        Ok(())
+    }
+}
+
+impl<G: TagGrant> GrantQuery for TokenMap<G> {
+    fn grants_for_owner(&self, owner_id: &str) -> Vec<GrantSummary> {
+        let now = self.clock.now();
+        let access = self.access.values().filter(|token| token.grant.until >= now);
+        let refresh = self.refresh.values().filter(|token| !token.refresh_expired(now));
+
+        // Both maps share the entry of a grant which has an access and a refresh token.
+        let mut seen = HashSet::new();
+        access
+            .chain(refresh)
+            .filter(|token| token.grant.owner_id == owner_id)
+            .filter(|token| seen.insert(token.access.clone()))
+            .map(|token| GrantSummary {
+                client_id: token.grant.client_id.clone(),
+                scope: token.grant.scope.clone(),
+                until: token.grant.until,
+            })
+            .collect()
     }
 }
 
+/// The shortest lifetime configured for any scope token of the grant.
+fn scope_lifetime(lifetimes: &HashMap<String, Duration>, grant: &Grant) -> Option<Duration> {
+    grant
+        .scope
+        .iter()
+        .filter_map(|token| lifetimes.get(token))
+        .min()
+        .copied()
+}
+
 /// Signs grants instead of storing them.
 ///
 /// Although this token instance allows preservation of memory it also implies that tokens, once
 /// issued, are impossible to revoke.
+///
+/// By default, access tokens use an opaque encoding. A signer constructed with
+/// [`TokenSigner::jwt`] instead issues JWT access tokens which third parties can validate.
+///
+/// Tokens are always signed with a single active key. To rotate it without invalidating the
+/// outstanding tokens, replace it with [`with_signing_key`] and keep the retired key around with
+/// [`add_verification_key`] until all its tokens have expired.
+///
+/// [`TokenSigner::jwt`]: #method.jwt
+/// [`with_signing_key`]: #method.with_signing_key
+/// [`add_verification_key`]: #method.add_verification_key
 pub struct TokenSigner {
     duration: Option<Duration>,
+    scope_lifetimes: HashMap<String, Duration>,
+    token_type: TokenType,
     signer: Assertion,
+    key_id: Option<String>,
+    verification: Vec<(String, Assertion)>,
     // FIXME: make this an AtomicU64 once stable.
     counter: AtomicUsize,
     have_refresh: bool,
+    jwt: Option<JwtProfile>,
+    subject: Arc<dyn SubjectEncoder + Send + Sync>,
+    clock: Arc<dyn Clock + Send + Sync>,
 }
 
 impl TokenSigner {
@@ -357,40 +1221,154 @@ impl TokenSigner {
     pub fn new(secret: Assertion) -> TokenSigner {
         TokenSigner {
             duration: None,
+            scope_lifetimes: HashMap::new(),
+            token_type: TokenType::Bearer,
+            key_id: secret.key_id(),
             signer: secret,
+            verification: Vec::new(),
             counter: AtomicUsize::new(0),
             have_refresh: false,
+            jwt: None,
+            subject: Arc::new(PublicSubject),
+            clock: Arc::new(SystemClock),
         }
     }
 
-    /// Construct a signing instance whose tokens only live for the program execution.
+    /// Use a different clock for the expiry and issuing time of tokens, instead of the system time.
     ///
-    /// Useful for rapid prototyping where tokens need not be stored in a persistent database and
-    /// can be invalidated at any time. This interface is provided with simplicity in mind, using
-    /// the default system random generator (`ring::rand::SystemRandom`).
-    pub fn ephemeral() -> TokenSigner {
-        TokenSigner::new(Assertion::ephemeral())
+    /// This is mainly useful in tests, to advance time deterministically with a [`ManualClock`].
+    ///
+    /// [`ManualClock`]: ../clock/struct.ManualClock.html
+    pub fn with_clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
     }
 
-    /// Set the validity of all issued grants to the specified duration.
+    /// Sign all new tokens with another key, identified by `kid`.
     ///
-    /// This only affects tokens issued after this call. The default duration is 1 (ONE) hour for
-    /// tokens issued for the authorization code grant method. For many users this may seem to
-    /// short but should be secure-by-default. You may want to increase the duration, or instead
-    /// use long lived refresh token instead (although you currently need to handle refresh tokens
-    /// yourself, coming soonish).
-    pub fn valid_for(&mut self, duration: Duration) {
-        self.duration = Some(duration);
+    /// JWT access tokens name their key in the `kid` header. Tokens signed by the previous key are
+    /// no longer recovered unless it is also added with [`add_verification_key`]. For `Ed25519`
+    /// keys, use the [`key_id`] of the assertion so that the key set published by [`jwks`] agrees
+    /// with the header of the tokens.
+    ///
+    /// [`add_verification_key`]: #method.add_verification_key
+    /// [`key_id`]: ../generator/struct.Assertion.html#method.key_id
+    /// [`jwks`]: #method.jwks
+    pub fn with_signing_key<K: Into<String>>(mut self, kid: K, key: Assertion) -> Self {
+        self.signer = key;
+        self.key_id = Some(kid.into());
+        self
     }
 
-    /// Set all grants to be valid for their default duration.
+    /// Also recover tokens signed by the key identified by `kid`, without signing new ones.
     ///
-    /// This only affects tokens issued after this call. The default duration is 1 (ONE) hour for
-    /// tokens issued for the authorization code grant method.
-    pub fn valid_for_default(&mut self) {
+    /// This is usually a key retired by [`with_signing_key`]. A token naming its key in a JWT
+    /// header is only checked against the key of that id, while opaque tokens are checked against
+    /// each key in turn. Adding another key with the same `kid` replaces the previous one.
+    ///
+    /// [`with_signing_key`]: #method.with_signing_key
+    pub fn add_verification_key<K: Into<String>>(&mut self, kid: K, key: Assertion) {
+        let kid = kid.into();
+        self.verification.retain(|(existing, _)| *existing != kid);
+        self.verification.push((kid, key));
+    }
+
+    /// Derive the `sub` claim of JWT access tokens with an encoder, instead of the owner id.
+    ///
+    /// A [`PairwiseSubject`] hides which tokens of different clients belong to the same owner.
+    /// Recovering such a token yields a grant whose `owner_id` is the encoded subject, as it is
+    /// seen by any other resource server. Opaque tokens are not affected.
+    ///
+    /// [`PairwiseSubject`]: ../subject/struct.PairwiseSubject.html
+    pub fn with_subject_encoder<S: SubjectEncoder + Send + Sync + 'static>(
+        mut self, subject: S,
+    ) -> Self {
+        self.subject = Arc::new(subject);
+        self
+    }
+
+    /// Construct a signing instance issuing access tokens as JWTs, following [RFC 9068].
+    ///
+    /// Tokens have the `at+jwt` type and contain the `iss`, `sub` (the owner, unless changed with
+    /// [`with_subject_encoder`]), `aud`, `exp`, `iat`, `jti`, `client_id` and `scope` claims. The
+    /// `exp` claim has a precision of seconds, so the expiry of each grant is truncated to whole
+    /// seconds. The signature algorithm is `HS256` for an `AssertionKind::HmacSha256` assertion
+    /// and `EdDSA` for `Ed25519`.
+    ///
+    /// Tokens are only recovered if their `iss` is `issuer` and their `aud` contains `audience`.
+    /// Grants with private extensions can not be encoded and fail to issue. Refresh tokens, if
+    /// enabled, keep the opaque encoding since they are only ever read by this signer.
+    ///
+    /// [RFC 9068]: https://tools.ietf.org/html/rfc9068
+    /// [`with_subject_encoder`]: #method.with_subject_encoder
+    pub fn jwt<I, A>(secret: Assertion, issuer: I, audience: A) -> TokenSigner
+    where
+        I: Into<String>,
+        A: Into<String>,
+    {
+        TokenSigner {
+            jwt: Some(JwtProfile::new(issuer.into(), audience.into())),
+            ..TokenSigner::new(secret)
+        }
+    }
+
+    /// The public key set verifying the access tokens of this signer.
+    ///
+    /// This contains the active key and all verification keys, except those which are symmetric.
+    #[cfg(feature = "ed25519")]
+    pub fn jwks(&self) -> crate::endpoint::JwkSet {
+        let mut jwks = crate::endpoint::JwkSet::new();
+        for (_, key) in self.verifying_keys() {
+            // Only fails for symmetric keys which must not be published anyways.
+            let _ = jwks.push(key);
+        }
+        jwks
+    }
+
+    /// Construct a signing instance whose tokens only live for the program execution.
+    ///
+    /// Useful for rapid prototyping where tokens need not be stored in a persistent database and
+    /// can be invalidated at any time. This interface is provided with simplicity in mind, using
+    /// the default system random generator (`ring::rand::SystemRandom`).
+    pub fn ephemeral() -> TokenSigner {
+        TokenSigner::new(Assertion::ephemeral())
+    }
+
+    /// Set the validity of all issued grants to the specified duration.
+    ///
+    /// This only affects tokens issued after this call. The default duration is 1 (ONE) hour for
+    /// tokens issued for the authorization code grant method. For many users this may seem to
+    /// short but should be secure-by-default. You may want to increase the duration, or instead
+    /// use long lived refresh token instead (although you currently need to handle refresh tokens
+    /// yourself, coming soonish).
+    pub fn valid_for(&mut self, duration: Duration) {
+        self.duration = Some(duration);
+    }
+
+    /// Set all grants to be valid for their default duration.
+    ///
+    /// This only affects tokens issued after this call. The default duration is 1 (ONE) hour for
+    /// tokens issued for the authorization code grant method.
+    pub fn valid_for_default(&mut self) {
         self.duration = None;
     }
 
+    /// Limit the lifetime of all tokens granting the scope token.
+    ///
+    /// A token granting several limited scope tokens expires after the shortest of their
+    /// lifetimes, even if the configured duration is longer.
+    pub fn scope_lifetime<S: Into<String>>(&mut self, scope: S, lifetime: Duration) {
+        self.scope_lifetimes.insert(scope.into(), lifetime);
+    }
+
+    /// Advertise issued tokens with another type than `bearer`.
+    ///
+    /// This only changes the type of tokens whose grant is not bound to a key, bound tokens keep
+    /// the type of their binding. The resource flow must accept the same type.
+    pub fn token_type(&mut self, token_type: TokenType) {
+        self.token_type = token_type;
+    }
+
     /// Determine whether to generate refresh tokens.
     ///
     /// By default, this option is *off*. Since the `TokenSigner` can on its own not revoke any
@@ -413,32 +1391,57 @@ impl TokenSigner {
         let first_ctr = self.next_counter() as u64;
         let second_ctr = self.next_counter() as u64;
 
-        let token = self.as_token().sign(first_ctr, grant)?;
+        let token = self.sign_token(first_ctr, grant)?;
         let refresh = self.as_refresh().sign(second_ctr, grant)?;
 
         Ok(IssuedToken {
             token,
             refresh: Some(refresh),
             until: grant.until,
-            token_type: TokenType::Bearer,
+            token_type: Binding::token_type_or(grant, self.token_type),
+            refresh_until: None,
         })
     }
 
     fn unrefreshable_token(&self, grant: &Grant) -> Result<IssuedToken, ()> {
         let counter = self.next_counter() as u64;
 
-        let token = self.as_token().sign(counter, grant)?;
+        let token = self.sign_token(counter, grant)?;
+
+        Ok(IssuedToken {
+            token_type: Binding::token_type_or(grant, self.token_type),
+            ..IssuedToken::without_refresh(token, grant.until)
+        })
+    }
 
-        Ok(IssuedToken::without_refresh(token, grant.until))
+    fn sign_token(&self, counter: u64, grant: &Grant) -> Result<String, ()> {
+        match &self.jwt {
+            Some(jwt) => {
+                let sub = self.subject.encode(&grant.owner_id, &grant.client_id);
+                let kid = self.key_id.as_deref();
+                jwt.encode(&self.signer, kid, grant, sub, self.clock.now())
+            }
+            None => self.as_token().sign(counter, grant),
+        }
     }
 
-    fn as_token(&self) -> TaggedAssertion {
+    fn as_token(&self) -> TaggedAssertion<'_> {
         self.signer.tag("token")
     }
 
-    fn as_refresh(&self) -> TaggedAssertion {
+    fn as_refresh(&self) -> TaggedAssertion<'_> {
         self.signer.tag("refresh")
     }
+
+    /// The active key followed by all verification keys, each with its identifier.
+    fn verifying_keys(&self) -> impl Iterator<Item = (Option<&str>, &Assertion)> {
+        let active = (self.key_id.as_deref(), &self.signer);
+        let retired = self
+            .verification
+            .iter()
+            .map(|(kid, key)| (Some(kid.as_str()), key));
+        std::iter::once(active).chain(retired)
+    }
 }
 
 impl<'s, I: Issuer + ?Sized> Issuer for &'s mut I {
@@ -446,6 +1449,10 @@ impl<'s, I: Issuer + ?Sized> Issuer for &'s mut I {
         (**self).issue(grant)
     }
 
+    fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        (**self).issue_with(grant, refresh)
+    }
+
     fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
         (**self).refresh(token, grant)
     }
@@ -457,6 +1464,14 @@ impl<'s, I: Issuer + ?Sized> Issuer for &'s mut I {
     fn recover_refresh<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
         (**self).recover_refresh(token)
     }
+
+    fn touch(&mut self, token: &str, until: Time) -> Result<Time, ()> {
+        (**self).touch(token, until)
+    }
+
+    fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        (**self).revoke_token(token)
+    }
 }
 
 impl<I: Issuer + ?Sized> Issuer for Box<I> {
@@ -464,6 +1479,10 @@ impl<I: Issuer + ?Sized> Issuer for Box<I> {
         (**self).issue(grant)
     }
 
+    fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        (**self).issue_with(grant, refresh)
+    }
+
     fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
         (**self).refresh(token, grant)
     }
@@ -475,6 +1494,14 @@ impl<I: Issuer + ?Sized> Issuer for Box<I> {
     fn recover_refresh<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
         (**self).recover_refresh(token)
     }
+
+    fn touch(&mut self, token: &str, until: Time) -> Result<Time, ()> {
+        (**self).touch(token, until)
+    }
+
+    fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        (**self).revoke_token(token)
+    }
 }
 
 impl<'s, I: Issuer + ?Sized> Issuer for MutexGuard<'s, I> {
@@ -482,6 +1509,10 @@ impl<'s, I: Issuer + ?Sized> Issuer for MutexGuard<'s, I> {
         (**self).issue(grant)
     }
 
+    fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        (**self).issue_with(grant, refresh)
+    }
+
     fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
         (**self).refresh(token, grant)
     }
@@ -493,6 +1524,14 @@ impl<'s, I: Issuer + ?Sized> Issuer for MutexGuard<'s, I> {
     fn recover_refresh<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
         (**self).recover_refresh(token)
     }
+
+    fn touch(&mut self, token: &str, until: Time) -> Result<Time, ()> {
+        (**self).touch(token, until)
+    }
+
+    fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        (**self).revoke_token(token)
+    }
 }
 
 impl<'s, I: Issuer + ?Sized> Issuer for RwLockWriteGuard<'s, I> {
@@ -500,6 +1539,10 @@ impl<'s, I: Issuer + ?Sized> Issuer for RwLockWriteGuard<'s, I> {
         (**self).issue(grant)
     }
 
+    fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        (**self).issue_with(grant, refresh)
+    }
+
     fn refresh(&mut self, token: &str, grant: Grant) -> Result<RefreshedToken, ()> {
         (**self).refresh(token, grant)
     }
@@ -511,6 +1554,32 @@ impl<'s, I: Issuer + ?Sized> Issuer for RwLockWriteGuard<'s, I> {
     fn recover_refresh<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
         (**self).recover_refresh(token)
     }
+
+    fn touch(&mut self, token: &str, until: Time) -> Result<Time, ()> {
+        (**self).touch(token, until)
+    }
+
+    fn revoke_token(&mut self, token: &str) -> Result<(), ()> {
+        (**self).revoke_token(token)
+    }
+}
+
+impl<Q: GrantQuery + ?Sized> GrantQuery for &Q {
+    fn grants_for_owner(&self, owner_id: &str) -> Vec<GrantSummary> {
+        (**self).grants_for_owner(owner_id)
+    }
+}
+
+impl<Q: GrantQuery + ?Sized> GrantQuery for &mut Q {
+    fn grants_for_owner(&self, owner_id: &str) -> Vec<GrantSummary> {
+        (**self).grants_for_owner(owner_id)
+    }
+}
+
+impl<Q: GrantQuery + ?Sized> GrantQuery for Box<Q> {
+    fn grants_for_owner(&self, owner_id: &str) -> Vec<GrantSummary> {
+        (**self).grants_for_owner(owner_id)
+    }
 }
 
 impl Issuer for TokenSigner {
@@ -518,6 +1587,10 @@ impl Issuer for TokenSigner {
         (&mut &*self).issue(grant)
     }
 
+    fn issue_with(&mut self, grant: Grant, refresh: RefreshIssuance) -> Result<IssuedToken, ()> {
+        (&mut &*self).issue_with(grant, refresh)
+    }
+
     fn refresh(&mut self, _refresh: &str, _grant: Grant) -> Result<RefreshedToken, ()> {
         Err(())
     }
@@ -534,7 +1607,16 @@ impl Issuer for TokenSigner {
 impl<'a> Issuer for &'a TokenSigner {
     fn issue(&mut self, mut grant: Grant) -> Result<IssuedToken, ()> {
         if let Some(duration) = &self.duration {
-            grant.until = Utc::now() + *duration;
+            grant.until = self.clock.now() + *duration;
+        }
+
+        if let Some(lifetime) = scope_lifetime(&self.scope_lifetimes, &grant) {
+            grant.until = grant.until.min(self.clock.now() + lifetime);
+        }
+
+        if self.jwt.is_some() {
+            // The `exp` claim is in seconds, the `expires_in` of the response must agree with it.
+            grant.until = Utc.timestamp_opt(grant.until.timestamp(), 0).single().ok_or(())?;
         }
 
         if self.have_refresh {
@@ -549,7 +1631,13 @@ impl<'a> Issuer for &'a TokenSigner {
     }
 
     fn recover_token<'t>(&'t self, token: &'t str) -> Result<Option<Grant>, ()> {
-        Ok(self.as_token().extract(token).ok())
+        // Decoding a JWT with a key of another id fails before verifying its signature.
+        let grant = self.verifying_keys().find_map(|(kid, key)| match &self.jwt {
+            Some(jwt) => jwt.decode(key, kid, token).ok(),
+            None => key.tag("token").extract(token).ok(),
+        });
+
+        Ok(grant)
     }
 
     fn recover_refresh<'t>(&'t self, token: &'t str) -> Result<Option<Grant>, ()> {
@@ -557,7 +1645,167 @@ impl<'a> Issuer for &'a TokenSigner {
             return Ok(None);
         }
 
-        Ok(self.as_refresh().extract(token).ok())
+        let grant = self
+            .verifying_keys()
+            .find_map(|(_, key)| key.tag("refresh").extract(token).ok());
+
+        Ok(grant)
+    }
+}
+
+/// Signed tokens are not stored, so there are never any grants to list.
+impl GrantQuery for TokenSigner {
+    fn grants_for_owner(&self, _: &str) -> Vec<GrantSummary> {
+        Vec::new()
+    }
+}
+
+/// Encrypts grants instead of storing them.
+///
+/// A drop-in replacement for a [`TokenSigner`] whose tokens reveal nothing about their grant. The
+/// tokens of a signer can be decoded by anyone holding them, exposing the owner, the client and
+/// the scope, while these are only readable with the [`Encryption`] key. Grants may also carry
+/// private extensions.
+///
+/// Encryption does not help with revocation. As with signed tokens, once issued, tokens are valid
+/// until they expire unless additional state is kept elsewhere. Keep their lifetime short.
+///
+/// Requires the `chacha20poly1305` feature.
+///
+/// [`TokenSigner`]: struct.TokenSigner.html
+/// [`Encryption`]: ../generator/struct.Encryption.html
+#[cfg(feature = "chacha20poly1305")]
+pub struct TokenEncryptor {
+    duration: Option<Duration>,
+    token_type: TokenType,
+    encryption: Encryption,
+    have_refresh: bool,
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl TokenEncryptor {
+    /// Construct an encrypting instance from a secret key.
+    pub fn new(encryption: Encryption) -> TokenEncryptor {
+        TokenEncryptor {
+            duration: None,
+            token_type: TokenType::Bearer,
+            encryption,
+            have_refresh: false,
+        }
+    }
+
+    /// Construct an encrypting instance whose tokens only live for the program execution.
+    pub fn ephemeral() -> TokenEncryptor {
+        TokenEncryptor::new(Encryption::ephemeral())
+    }
+
+    /// Set the validity of all issued grants to the specified duration.
+    ///
+    /// This only affects tokens issued after this call.
+    pub fn valid_for(&mut self, duration: Duration) {
+        self.duration = Some(duration);
+    }
+
+    /// Set all grants to be valid for their default duration.
+    ///
+    /// This only affects tokens issued after this call.
+    pub fn valid_for_default(&mut self) {
+        self.duration = None;
+    }
+
+    /// Advertise issued tokens with another type than `bearer`.
+    ///
+    /// This only changes the type of tokens whose grant is not bound to a key, bound tokens keep
+    /// the type of their binding. The resource flow must accept the same type.
+    pub fn token_type(&mut self, token_type: TokenType) {
+        self.token_type = token_type;
+    }
+
+    /// Determine whether to generate refresh tokens.
+    ///
+    /// By default, this option is *off*. The same considerations as for the [`TokenSigner`] apply,
+    /// refresh tokens can not be revoked either.
+    ///
+    /// [`TokenSigner`]: struct.TokenSigner.html#method.generate_refresh_tokens
+    pub fn generate_refresh_tokens(&mut self, refresh: bool) {
+        self.have_refresh = refresh;
+    }
+
+    fn as_token(&self) -> TaggedEncryption<'_> {
+        self.encryption.tag("token")
+    }
+
+    fn as_refresh(&self) -> TaggedEncryption<'_> {
+        self.encryption.tag("refresh")
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl Issuer for TokenEncryptor {
+    fn issue(&mut self, grant: Grant) -> Result<IssuedToken, ()> {
+        (&mut &*self).issue(grant)
+    }
+
+    fn refresh(&mut self, _refresh: &str, _grant: Grant) -> Result<RefreshedToken, ()> {
+        Err(())
+    }
+
+    fn recover_token<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
+        (&&*self).recover_token(token)
+    }
+
+    fn recover_refresh<'a>(&'a self, token: &'a str) -> Result<Option<Grant>, ()> {
+        (&&*self).recover_refresh(token)
+    }
+}
+
+#[cfg(feature = "chacha20poly1305")]
+impl<'a> Issuer for &'a TokenEncryptor {
+    fn issue(&mut self, mut grant: Grant) -> Result<IssuedToken, ()> {
+        if let Some(duration) = &self.duration {
+            grant.until = Utc::now() + *duration;
+        }
+
+        // The expiry is encoded in seconds, the `expires_in` of the response must agree with it.
+        grant.until = Utc.timestamp_opt(grant.until.timestamp(), 0).single().ok_or(())?;
+
+        let token = self.as_token().seal(&grant).map_err(|_| ())?;
+        let refresh = match self.have_refresh {
+            true => Some(self.as_refresh().seal(&grant).map_err(|_| ())?),
+            false => None,
+        };
+
+        Ok(IssuedToken {
+            token,
+            refresh,
+            until: grant.until,
+            token_type: Binding::token_type_or(&grant, self.token_type),
+            refresh_until: None,
+        })
+    }
+
+    fn refresh(&mut self, _refresh: &str, _grant: Grant) -> Result<RefreshedToken, ()> {
+        Err(())
+    }
+
+    fn recover_token<'t>(&'t self, token: &'t str) -> Result<Option<Grant>, ()> {
+        Ok(self.as_token().open(token).ok())
+    }
+
+    fn recover_refresh<'t>(&'t self, token: &'t str) -> Result<Option<Grant>, ()> {
+        if !self.have_refresh {
+            return Ok(None);
+        }
+
+        Ok(self.as_refresh().open(token).ok())
+    }
+}
+
+/// Encrypted tokens are not stored, so there are never any grants to list.
+#[cfg(feature = "chacha20poly1305")]
+impl GrantQuery for TokenEncryptor {
+    fn grants_for_owner(&self, _: &str) -> Vec<GrantSummary> {
+        Vec::new()
     }
 }
 
@@ -565,9 +1813,13 @@ impl<'a> Issuer for &'a TokenSigner {
 /// Tests for issuer implementations, including those provided here.
 pub mod tests {
     use super::*;
-    use crate::primitives::grant::Extensions;
-    use crate::primitives::generator::RandomGenerator;
+    use crate::primitives::grant::{Extensions, Value};
+    use crate::primitives::generator::{AssertionKind, RandomGenerator};
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
     use chrono::{Duration, Utc};
+    use crate::primitives::clock::{Clock, ManualClock};
+    use crate::primitives::subject::PairwiseSubject;
 
     fn grant_template() -> Grant {
         Grant {
@@ -626,6 +1878,341 @@ pub mod tests {
         assert!(!token.refreshable());
     }
 
+    #[cfg(feature = "chacha20poly1305")]
+    #[test]
+    fn encryptor_test_suite() {
+        let mut encryptor = TokenEncryptor::ephemeral();
+        encryptor.generate_refresh_tokens(true);
+        simple_test_suite(&mut encryptor);
+    }
+
+    #[cfg(feature = "chacha20poly1305")]
+    #[test]
+    fn encryptor_roundtrip() {
+        use crate::primitives::generator::Encryption;
+
+        let key = [7u8; 32];
+        let mut encryptor = TokenEncryptor::new(Encryption::new(&key));
+        encryptor.generate_refresh_tokens(true);
+
+        let mut grant = grant_template();
+        grant
+            .extensions
+            .set_raw("public".into(), Value::public(Some("visible".into())));
+        grant
+            .extensions
+            .set_raw("private".into(), Value::private(Some("hidden".into())));
+        let issued = encryptor.issue(grant.clone()).unwrap();
+        grant.until = issued.until;
+
+        // Another instance with the same key, for example on a resource server.
+        let other = TokenEncryptor::new(Encryption::new(&key));
+        assert_eq!(other.recover_token(&issued.token), Ok(Some(grant.clone())));
+        assert_eq!(encryptor.recover_token(&issued.token), Ok(Some(grant.clone())));
+
+        let refresh = issued.refresh.unwrap();
+        assert_eq!(encryptor.recover_refresh(&refresh), Ok(Some(grant)));
+        // Access and refresh tokens can not be used in place of each other.
+        assert_eq!(encryptor.recover_token(&refresh), Ok(None));
+        assert_eq!(encryptor.recover_refresh(&issued.token), Ok(None));
+    }
+
+    #[cfg(feature = "chacha20poly1305")]
+    #[test]
+    fn encryptor_confidential() {
+        use crate::primitives::generator::Encryption;
+        use base64::engine::general_purpose::STANDARD;
+
+        let mut encryptor = TokenEncryptor::new(Encryption::new(&[7u8; 32]));
+        let grant = grant_template();
+        let issued = encryptor.issue(grant.clone()).unwrap();
+
+        // Neither the grant nor any of its parts can be read from the token.
+        let raw = STANDARD.decode(&issued.token).unwrap();
+        for part in [&grant.owner_id, &grant.client_id, "default", "example.com"] {
+            assert!(!raw.windows(part.len()).any(|window| window == part.as_bytes()));
+        }
+
+        // The signed token of the same grant does reveal them.
+        let signed = TokenSigner::ephemeral().issue(grant).unwrap();
+        let raw = STANDARD.decode(&signed.token).unwrap();
+        assert!(raw.windows(5).any(|window| window == b"Owner"));
+
+        // Not recovered with another key, nor when altered.
+        let other = TokenEncryptor::new(Encryption::new(&[8u8; 32]));
+        assert_eq!(other.recover_token(&issued.token), Ok(None));
+        let mut altered = STANDARD.decode(&issued.token).unwrap();
+        *altered.last_mut().unwrap() ^= 1;
+        assert_eq!(encryptor.recover_token(&STANDARD.encode(altered)), Ok(None));
+        assert_eq!(encryptor.recover_token("not a token"), Ok(None));
+        assert_eq!(encryptor.recover_token(""), Ok(None));
+    }
+
+    fn jwt_signer(audience: &str) -> TokenSigner {
+        let assertion = Assertion::new(AssertionKind::HmacSha256, b"0123456789abcdef0123456789abcdef");
+        TokenSigner::jwt(assertion, "https://auth.example", audience)
+    }
+
+    fn jwt_part(token: &str, index: usize) -> serde_json::Value {
+        let part = token.split('.').nth(index).unwrap();
+        let part = URL_SAFE_NO_PAD.decode(part).unwrap();
+        serde_json::from_slice(&part).unwrap()
+    }
+
+    #[test]
+    fn jwt_test_suite() {
+        let mut signer = jwt_signer("https://api.example");
+        signer.generate_refresh_tokens(true);
+        simple_test_suite(&mut signer);
+    }
+
+    #[test]
+    fn jwt_claims() {
+        let mut signer = jwt_signer("https://api.example");
+        let mut grant = grant_template();
+        grant
+            .extensions
+            .set_raw("public".into(), Value::public(Some("content".into())));
+        let issued = signer.issue(grant.clone()).unwrap();
+
+        let header = jwt_part(&issued.token, 0);
+        assert_eq!(header["alg"], "HS256");
+        assert_eq!(header["typ"], "at+jwt");
+
+        let claims = jwt_part(&issued.token, 1);
+        assert_eq!(claims["iss"], "https://auth.example");
+        assert_eq!(claims["sub"], "Owner");
+        assert_eq!(claims["aud"], "https://api.example");
+        assert_eq!(claims["client_id"], "Client");
+        assert_eq!(claims["scope"], "default");
+        assert!(claims["jti"].is_string());
+        assert!(claims["iat"].is_i64());
+        assert_eq!(claims["exp"], issued.until.timestamp());
+
+        // The response lifetime agrees with the `exp` claim.
+        let exp = Utc.timestamp_opt(claims["exp"].as_i64().unwrap(), 0).unwrap();
+        assert_eq!(issued.until, exp);
+        let response = BearerToken(issued.clone(), grant.scope.clone()).to_json();
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let expires_in = response["expires_in"].as_i64().unwrap();
+        let remaining = exp.signed_duration_since(Utc::now()).num_seconds();
+        assert!(remaining <= expires_in && expires_in <= remaining + 1);
+
+        let recovered = signer.recover_token(&issued.token).unwrap().unwrap();
+        grant.until = exp;
+        assert_eq!(recovered, grant);
+    }
+
+    #[test]
+    fn jwt_pairwise_subject() {
+        let mut signer = jwt_signer("https://api.example")
+            .with_subject_encoder(PairwiseSubject::new(b"pairwise secret"));
+        let subject = |signer: &mut TokenSigner, client: &str| {
+            let mut grant = grant_template();
+            grant.client_id = client.to_string();
+            let token = signer.issue(grant).unwrap().token;
+            jwt_part(&token, 1)["sub"].as_str().unwrap().to_string()
+        };
+
+        let first = subject(&mut signer, "Client");
+        assert_ne!(first, "Owner");
+        assert_eq!(first, subject(&mut signer, "Client"));
+        assert_ne!(first, subject(&mut signer, "OtherClient"));
+
+        // Recovered grants name the owner by the same subject as the token.
+        let token = signer.issue(grant_template()).unwrap().token;
+        let recovered = signer.recover_token(&token).unwrap().unwrap();
+        assert_eq!(recovered.owner_id, first);
+
+        // A different key yields unrelated subjects.
+        let mut other = jwt_signer("https://api.example")
+            .with_subject_encoder(PairwiseSubject::new(b"another secret"));
+        assert_ne!(first, subject(&mut other, "Client"));
+    }
+
+    #[test]
+    fn jwt_rejected() {
+        let mut signer = jwt_signer("https://api.example");
+        let token = signer.issue(grant_template()).unwrap().token;
+
+        let other_audience = jwt_signer("https://other.example");
+        assert_eq!(other_audience.recover_token(&token), Ok(None));
+
+        let other_issuer = TokenSigner::jwt(
+            Assertion::new(AssertionKind::HmacSha256, b"0123456789abcdef0123456789abcdef"),
+            "https://mixup.example",
+            "https://api.example",
+        );
+        assert_eq!(other_issuer.recover_token(&token), Ok(None));
+
+        let other_key = TokenSigner::jwt(
+            Assertion::new(AssertionKind::HmacSha256, b"fedcba9876543210fedcba9876543210"),
+            "https://auth.example",
+            "https://api.example",
+        );
+        assert_eq!(other_key.recover_token(&token), Ok(None));
+
+        let opaque = TokenSigner::new(Assertion::new(
+            AssertionKind::HmacSha256,
+            b"0123456789abcdef0123456789abcdef",
+        ));
+        assert_eq!(opaque.recover_token(&token), Ok(None));
+
+        let mut private = grant_template();
+        private.extensions.set_raw("private".into(), Value::private(None));
+        assert!(signer.issue(private).is_err());
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn jwt_ed25519_key_id() {
+        let mut signer = TokenSigner::jwt(
+            Assertion::ed25519(&[1; 32]),
+            "https://auth.example",
+            "https://api.example",
+        );
+        let issued = signer.issue(grant_template()).unwrap();
+
+        let header = jwt_part(&issued.token, 0);
+        assert_eq!(header["alg"], "EdDSA");
+        let jwks = signer.jwks();
+        assert_eq!(jwks.keys().len(), 1);
+        assert_eq!(header["kid"], jwks.keys()[0].kid());
+
+        // Resource servers only need the public key.
+        let public_key = signer.signer.public_key().unwrap();
+        let verifier = TokenSigner::jwt(
+            Assertion::ed25519_verifier(&public_key).unwrap(),
+            "https://auth.example",
+            "https://api.example",
+        );
+        assert!(verifier.recover_token(&issued.token).unwrap().is_some());
+
+        let other = TokenSigner::jwt(
+            Assertion::ed25519(&[2; 32]),
+            "https://auth.example",
+            "https://api.example",
+        );
+        assert_eq!(other.recover_token(&issued.token), Ok(None));
+    }
+
+    fn owner_grant(owner_id: &str, client_id: &str, scope: &str) -> Grant {
+        Grant {
+            owner_id: owner_id.to_string(),
+            client_id: client_id.to_string(),
+            scope: scope.parse().unwrap(),
+            ..grant_template()
+        }
+    }
+
+    #[test]
+    fn random_grants_for_owner() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        let first = token_map.issue(owner_grant("Owner", "Client", "read")).unwrap();
+        token_map.issue(owner_grant("Owner", "Client", "write")).unwrap();
+        let other = token_map.issue(owner_grant("Owner", "Other", "read")).unwrap();
+        token_map
+            .issue(owner_grant("Someone", "Client", "admin"))
+            .unwrap();
+
+        assert_eq!(token_map.grants_for_owner("Owner").len(), 3);
+        let clients = token_map.authorized_clients("Owner");
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].client_id, "Client");
+        assert_eq!(clients[0].scope, "read write".parse().unwrap());
+        assert_eq!(clients[1].client_id, "Other");
+        assert_eq!(clients[1].scope, "read".parse().unwrap());
+
+        // The grant remains listed as long as its refresh token is valid.
+        token_map.revoke(&other.token);
+        assert_eq!(token_map.authorized_clients("Owner").len(), 2);
+        token_map.revoke(other.refresh.as_ref().unwrap());
+        let clients = token_map.authorized_clients("Owner");
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].client_id, "Client");
+
+        token_map.revoke_client("Owner", "Client");
+        assert!(token_map.grants_for_owner("Owner").is_empty());
+        assert_eq!(token_map.recover_token(&first.token), Ok(None));
+        assert_eq!(token_map.grants_for_owner("Someone").len(), 1);
+    }
+
+    #[test]
+    fn random_grants_for_owner_expired() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.valid_for(Duration::minutes(10));
+        token_map.refresh_valid_for(Duration::hours(1));
+        token_map.issue(owner_grant("Owner", "Client", "read")).unwrap();
+
+        clock.advance(Duration::minutes(30));
+        assert_eq!(token_map.grants_for_owner("Owner").len(), 1);
+        clock.advance(Duration::hours(1));
+        assert!(token_map.grants_for_owner("Owner").is_empty());
+    }
+
+    #[test]
+    fn signer_grants_for_owner() {
+        let mut signer = TokenSigner::ephemeral();
+        signer.issue(grant_template()).unwrap();
+        assert!(signer.grants_for_owner("Owner").is_empty());
+    }
+
+    fn hmac_key(byte: u8) -> Assertion {
+        Assertion::new(AssertionKind::HmacSha256, &[byte; 32])
+    }
+
+    #[test]
+    fn signer_key_rotation() {
+        let signer = TokenSigner::ephemeral().with_signing_key("old", hmac_key(1));
+        let old = (&signer).issue(grant_template()).unwrap();
+
+        let mut signer = signer.with_signing_key("new", hmac_key(2));
+        assert_eq!(signer.recover_token(&old.token), Ok(None));
+
+        signer.add_verification_key("old", hmac_key(1));
+        assert!(signer.recover_token(&old.token).unwrap().is_some());
+        let new = signer.issue(grant_template()).unwrap();
+        assert!(signer.recover_token(&new.token).unwrap().is_some());
+
+        // A verification key does not sign new tokens.
+        let retired = TokenSigner::new(hmac_key(1));
+        assert_eq!(retired.recover_token(&new.token), Ok(None));
+
+        let unknown = TokenSigner::new(hmac_key(3)).issue(grant_template()).unwrap();
+        assert_eq!(signer.recover_token(&unknown.token), Ok(None));
+    }
+
+    #[test]
+    fn jwt_key_rotation() {
+        let jwt = |kid: &str, key: Assertion| {
+            TokenSigner::jwt(
+                Assertion::ephemeral(),
+                "https://auth.example",
+                "https://api.example",
+            )
+            .with_signing_key(kid, key)
+        };
+
+        let mut signer = jwt("old", hmac_key(1));
+        let old = signer.issue(grant_template()).unwrap();
+        assert_eq!(jwt_part(&old.token, 0)["kid"], "old");
+
+        let mut signer = signer.with_signing_key("new", hmac_key(2));
+        signer.add_verification_key("old", hmac_key(1));
+        assert!(signer.recover_token(&old.token).unwrap().is_some());
+        let new = signer.issue(grant_template()).unwrap();
+        assert_eq!(jwt_part(&new.token, 0)["kid"], "new");
+        assert!(signer.recover_token(&new.token).unwrap().is_some());
+
+        // The key is selected by its id, another key under the same id must not verify.
+        let forged = jwt("old", hmac_key(3)).issue(grant_template()).unwrap();
+        assert_eq!(signer.recover_token(&forged.token), Ok(None));
+
+        let unknown = jwt("unknown", hmac_key(1)).issue(grant_template()).unwrap();
+        assert_eq!(signer.recover_token(&unknown.token), Ok(None));
+    }
+
     #[test]
     fn random_test_suite() {
         let mut token_map = TokenMap::new(RandomGenerator::new(16));
@@ -658,6 +2245,515 @@ pub mod tests {
         assert!(refresh != new_refresh);
     }
 
+    fn dpop_grant() -> Grant {
+        let mut grant = grant_template();
+        Binding::Dpop {
+            jkt: "thumbprint".into(),
+        }
+        .attach(&mut grant.extensions);
+        grant
+    }
+
+    #[test]
+    fn random_dpop_token_type() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        let issued = token_map.issue(dpop_grant()).unwrap();
+        assert_eq!(issued.token_type, TokenType::DPoP);
+
+        let refresh = issued.refresh.unwrap();
+        let refreshed = token_map.refresh(&refresh, dpop_grant()).unwrap();
+        assert_eq!(refreshed.token_type, TokenType::DPoP);
+
+        let unbound = token_map.issue(grant_template()).unwrap();
+        assert_eq!(unbound.token_type, TokenType::Bearer);
+    }
+
+    #[test]
+    fn custom_token_type() {
+        const EXAMPLE: TokenType = TokenType::Custom("Example");
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.token_type(EXAMPLE);
+        assert_eq!(token_map.issue(grant_template()).unwrap().token_type, EXAMPLE);
+        // Bound tokens keep the type of their binding.
+        let issued = token_map.issue(dpop_grant()).unwrap();
+        assert_eq!(issued.token_type, TokenType::DPoP);
+
+        let mut signer = TokenSigner::ephemeral();
+        signer.token_type(EXAMPLE);
+        assert_eq!(signer.issue(grant_template()).unwrap().token_type, EXAMPLE);
+        assert_eq!(EXAMPLE.as_str(), "Example");
+    }
+
+    #[test]
+    fn signer_dpop_token_type() {
+        let mut signer = TokenSigner::ephemeral();
+        let issued = signer.issue(dpop_grant()).unwrap();
+        assert_eq!(issued.token_type, TokenType::DPoP);
+
+        // The binding is carried by the self-contained token.
+        let recovered = signer.recover_token(&issued.token).unwrap().unwrap();
+        assert_eq!(
+            Binding::of(&recovered),
+            Some(Binding::Dpop {
+                jkt: "thumbprint".into()
+            })
+        );
+    }
+
+    #[test]
+    fn random_sliding_expiry() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.valid_for(Duration::minutes(10));
+        token_map.sliding_expiry(Duration::hours(1));
+
+        let issued = token_map.issue(grant_template()).unwrap();
+        let refresh = issued.refresh.unwrap();
+
+        // Within the limit the token is extended, also as seen by its refresh token.
+        let until = Utc::now() + Duration::minutes(30);
+        assert_eq!(token_map.touch(&issued.token, until), Ok(until));
+        let grant = token_map.recover_token(&issued.token).unwrap().unwrap();
+        assert_eq!(grant.until, until);
+        let grant = token_map.recover_refresh(&refresh).unwrap().unwrap();
+        assert_eq!(grant.until, until);
+
+        // Never shortened.
+        let earlier = Utc::now() + Duration::minutes(5);
+        assert_eq!(token_map.touch(&issued.token, earlier), Ok(until));
+
+        // The absolute ceiling can not be exceeded.
+        let beyond = Utc::now() + Duration::hours(2);
+        let capped = token_map.touch(&issued.token, beyond).unwrap();
+        assert!(capped < beyond);
+        assert!(capped <= Utc::now() + Duration::hours(1));
+        assert!(capped > until);
+
+        // The refresh token still rotates the extended token.
+        token_map.refresh(&refresh, grant_template()).unwrap();
+        assert_eq!(token_map.recover_token(&issued.token), Ok(None));
+        assert!(token_map.touch(&issued.token, until).is_err());
+    }
+
+    #[test]
+    fn random_expiry_with_clock() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.valid_for(Duration::minutes(10));
+        token_map.sliding_expiry(Duration::minutes(30));
+
+        let issued = token_map.issue(grant_template()).unwrap();
+        let refresh = issued.refresh.unwrap();
+        assert_eq!(issued.until, clock.now() + Duration::minutes(10));
+
+        // The ceiling is measured from the clock as well.
+        let capped = token_map.touch(&issued.token, clock.now() + Duration::hours(1));
+        assert_eq!(capped, Ok(clock.now() + Duration::minutes(30)));
+
+        clock.advance(Duration::minutes(31));
+        let grant = token_map.recover_token(&issued.token).unwrap().unwrap();
+        assert!(grant.until < clock.now());
+        assert!(token_map.touch(&issued.token, clock.now()).is_err());
+
+        token_map.purge_expired();
+        assert_eq!(token_map.recover_token(&issued.token), Ok(None));
+        assert!(token_map.recover_refresh(&refresh).unwrap().is_some());
+    }
+
+    #[test]
+    fn random_refresh_expiry() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.valid_for(Duration::minutes(10));
+        token_map.refresh_valid_for(Duration::days(1));
+
+        let issued = token_map.issue(grant_template()).unwrap();
+        assert_eq!(issued.refresh_until, Some(clock.now() + Duration::days(1)));
+
+        // Exchanging the refresh token starts its lifetime anew.
+        clock.advance(Duration::hours(12));
+        let refresh = issued.refresh.unwrap();
+        let refreshed = token_map.refresh(&refresh, grant_template()).unwrap();
+        assert_eq!(refreshed.refresh_until, Some(clock.now() + Duration::days(1)));
+
+        let refresh = refreshed.refresh.unwrap();
+        clock.advance(Duration::hours(23));
+        assert!(token_map.recover_refresh(&refresh).unwrap().is_some());
+
+        clock.advance(Duration::hours(2));
+        assert_eq!(token_map.recover_refresh(&refresh), Ok(None));
+
+        // The expiry is kept in snapshots.
+        let mut restored = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        restored.restore(token_map.snapshot());
+        assert_eq!(restored.recover_refresh(&refresh), Ok(None));
+
+        token_map.purge_expired();
+        assert!(token_map.snapshot().tokens.is_empty());
+    }
+
+    #[test]
+    fn random_issue_without_refresh() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.refresh_valid_for(Duration::days(1));
+
+        let issued = token_map
+            .issue_with(grant_template(), RefreshIssuance::Never)
+            .unwrap();
+        assert!(!issued.refreshable());
+        assert_eq!(issued.refresh_until, None);
+        assert!(token_map.recover_token(&issued.token).unwrap().is_some());
+
+        // Tokens issued afterwards are still distinct and refreshable by default.
+        let other = token_map.issue(grant_template()).unwrap();
+        assert_ne!(issued.token, other.token);
+        assert_ne!(Some(&issued.token), other.refresh.as_ref());
+        assert!(other.refreshable());
+
+        let snapshot = token_map.snapshot();
+        let mut restored = TokenMap::new(RandomGenerator::new(16));
+        restored.restore(snapshot);
+        assert!(restored.recover_token(&issued.token).unwrap().is_some());
+    }
+
+    #[test]
+    fn random_refresh_lifetime() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.valid_for(Duration::minutes(10));
+        token_map.refresh_valid_for(Duration::days(1));
+
+        let issued = token_map
+            .issue_with(grant_template(), RefreshIssuance::ValidFor(Duration::days(30)))
+            .unwrap();
+        assert_eq!(issued.until, clock.now() + Duration::minutes(10));
+        assert_eq!(issued.refresh_until, Some(clock.now() + Duration::days(30)));
+
+        // The lifetime is kept for the refresh tokens obtained by exchanging it.
+        clock.advance(Duration::days(2));
+        let refresh = issued.refresh.unwrap();
+        let refreshed = token_map.refresh(&refresh, grant_template()).unwrap();
+        assert_eq!(refreshed.refresh_until, Some(clock.now() + Duration::days(30)));
+
+        // Also when restored from a snapshot.
+        let mut restored = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        restored.refresh_valid_for(Duration::days(1));
+        restored.restore(token_map.snapshot());
+        let refresh = refreshed.refresh.unwrap();
+        let refreshed = restored.refresh(&refresh, grant_template()).unwrap();
+        assert_eq!(refreshed.refresh_until, Some(clock.now() + Duration::days(30)));
+
+        // Other tokens keep the lifetime of the issuer.
+        let issued = token_map.issue(grant_template()).unwrap();
+        assert_eq!(issued.refresh_until, Some(clock.now() + Duration::days(1)));
+    }
+
+    #[test]
+    fn signer_issue_with() {
+        let mut signer = TokenSigner::ephemeral();
+        signer.generate_refresh_tokens(true);
+
+        let issued = signer
+            .issue_with(grant_template(), RefreshIssuance::Default)
+            .unwrap();
+        assert!(issued.refreshable());
+
+        let issued = signer
+            .issue_with(grant_template(), RefreshIssuance::Never)
+            .unwrap();
+        assert!(!issued.refreshable());
+        assert!(signer.recover_token(&issued.token).unwrap().is_some());
+
+        // Signed refresh tokens can not expire on their own.
+        assert!(signer
+            .issue_with(grant_template(), RefreshIssuance::ValidFor(Duration::days(30)))
+            .is_err());
+    }
+
+    #[test]
+    fn random_refresh_events() {
+        use std::sync::Mutex;
+
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.refresh_valid_for(Duration::days(1));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener = events.clone();
+        token_map.on_refresh_event(move |event| listener.lock().unwrap().push(event.clone()));
+
+        let issued = token_map.issue(grant_template()).unwrap();
+        let first = issued.refresh.unwrap();
+        let refreshed = token_map.refresh(&first, grant_template()).unwrap();
+        let second = refreshed.refresh.unwrap();
+
+        let event = |kind| RefreshEvent {
+            kind,
+            client_id: "Client".to_string(),
+            owner_id: "Owner".to_string(),
+            time: clock.now(),
+        };
+        assert_eq!(*events.lock().unwrap(), [event(RefreshEventKind::Rotated)]);
+
+        // Presenting the exchanged token again is reported, the current one is not.
+        assert_eq!(token_map.recover_refresh(&first), Ok(None));
+        assert!(token_map.recover_refresh(&second).unwrap().is_some());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [event(RefreshEventKind::Rotated), event(RefreshEventKind::Reused)]
+        );
+
+        // Unknown tokens are not reuse.
+        assert_eq!(token_map.recover_refresh("unknown"), Ok(None));
+        assert_eq!(events.lock().unwrap().len(), 2);
+
+        // Reuse is still detected after restoring a snapshot.
+        let mut restored = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        let listener = events.clone();
+        restored.on_refresh_event(move |event| listener.lock().unwrap().push(event.clone()));
+        restored.restore(token_map.snapshot());
+        assert_eq!(restored.recover_refresh(&first), Ok(None));
+        assert!(restored.recover_refresh(&second).unwrap().is_some());
+        assert_eq!(events.lock().unwrap()[2..], [event(RefreshEventKind::Reused)]);
+        events.lock().unwrap().truncate(2);
+
+        // Exchanged tokens are forgotten with the end of their lifetime.
+        clock.advance(Duration::days(2));
+        token_map.purge_expired();
+        assert_eq!(token_map.recover_refresh(&first), Ok(None));
+        assert_eq!(events.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn random_retired_purged() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.on_refresh_event(|_| ());
+
+        // Refresh tokens do not expire by default, the retired ones are still forgotten.
+        let mut refresh = token_map.issue(grant_template()).unwrap().refresh.unwrap();
+        for _ in 0..3 {
+            refresh = token_map
+                .refresh(&refresh, grant_template())
+                .unwrap()
+                .refresh
+                .unwrap();
+        }
+        assert_eq!(token_map.retired.len(), 3);
+
+        clock.advance(Duration::days(29));
+        token_map.purge_expired();
+        assert_eq!(token_map.retired.len(), 3);
+        assert_eq!(token_map.snapshot().retired.len(), 3);
+
+        clock.advance(Duration::days(2));
+        token_map.purge_expired();
+        assert!(token_map.retired.is_empty());
+        assert!(token_map.snapshot().retired.is_empty());
+        // The current refresh token is not affected.
+        assert!(token_map.recover_refresh(&refresh).unwrap().is_some());
+    }
+
+    #[test]
+    fn random_reuse_window() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.on_refresh_event(|_| ());
+        token_map.detect_reuse_for(Duration::hours(1));
+
+        let first = token_map.issue(grant_template()).unwrap().refresh.unwrap();
+        token_map.refresh(&first, grant_template()).unwrap();
+        assert_eq!(token_map.retired.len(), 1);
+
+        clock.advance(Duration::hours(2));
+        token_map.purge_expired();
+        assert!(token_map.retired.is_empty());
+    }
+
+    #[test]
+    fn signer_expiry_with_clock() {
+        let clock = ManualClock::new(Utc::now() - Duration::days(1));
+        let mut signer = jwt_signer("resource").with_clock(clock.clone());
+        signer.valid_for(Duration::minutes(10));
+
+        let issued = signer.issue(grant_template()).unwrap();
+        let grant = signer.recover_token(&issued.token).unwrap().unwrap();
+        assert!(grant.until < Utc::now());
+
+        let claims = jwt_part(&issued.token, 1);
+        assert_eq!(claims["iat"].as_i64(), Some(clock.now().timestamp()));
+        assert_eq!(claims["exp"].as_i64(), Some(issued.until.timestamp()));
+    }
+
+    #[test]
+    fn random_scope_lifetime() {
+        let clock = ManualClock::new(Utc::now());
+        let mut token_map = TokenMap::new(RandomGenerator::new(16)).with_clock(clock.clone());
+        token_map.valid_for(Duration::hours(1));
+        token_map.sliding_expiry(Duration::hours(2));
+        token_map.scope_lifetime("payments", Duration::minutes(5));
+        token_map.scope_lifetime("profile", Duration::minutes(30));
+
+        // Unrestricted scopes keep the configured duration.
+        let issued = token_map.issue(grant_template()).unwrap();
+        assert_eq!(issued.until, clock.now() + Duration::hours(1));
+
+        let mut grant = grant_template();
+        grant.scope = "default profile payments".parse().unwrap();
+        let issued = token_map.issue(grant.clone()).unwrap();
+        assert_eq!(issued.until, clock.now() + Duration::minutes(5));
+
+        // Sliding expiry does not extend beyond the scope lifetime.
+        let touched = token_map.touch(&issued.token, clock.now() + Duration::hours(1));
+        assert_eq!(touched, Ok(clock.now() + Duration::minutes(5)));
+
+        clock.advance(Duration::minutes(10));
+        let refresh = issued.refresh.unwrap();
+        let refreshed = token_map.refresh(&refresh, grant).unwrap();
+        assert_eq!(refreshed.until, clock.now() + Duration::minutes(5));
+    }
+
+    #[test]
+    fn signer_scope_lifetime() {
+        let clock = ManualClock::new(Utc::now());
+        let mut signer = jwt_signer("resource").with_clock(clock.clone());
+        signer.valid_for(Duration::hours(1));
+        signer.scope_lifetime("payments", Duration::minutes(5));
+
+        let mut grant = grant_template();
+        grant.scope = "default payments".parse().unwrap();
+        let issued = signer.issue(grant).unwrap();
+        let expected = clock.now() + Duration::minutes(5);
+        assert_eq!(issued.until.timestamp(), expected.timestamp());
+
+        let claims = jwt_part(&issued.token, 1);
+        assert_eq!(claims["exp"].as_i64(), Some(expected.timestamp()));
+    }
+
+    #[test]
+    fn revoke_token() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        let access = token_map.issue(grant_template()).unwrap();
+        let refresh = token_map.issue(grant_template()).unwrap();
+
+        // Revoking an access token keeps its refresh token.
+        assert_eq!(token_map.revoke_token(&access.token), Ok(()));
+        assert_eq!(token_map.recover_token(&access.token), Ok(None));
+        assert!(token_map
+            .recover_refresh(access.refresh.as_ref().unwrap())
+            .unwrap()
+            .is_some());
+
+        // Revoking a refresh token takes the access token along.
+        assert_eq!(token_map.revoke_token(refresh.refresh.as_ref().unwrap()), Ok(()));
+        assert_eq!(token_map.recover_token(&refresh.token), Ok(None));
+        assert_eq!(token_map.revoke_token("unknown"), Ok(()));
+
+        let mut signer = TokenSigner::ephemeral();
+        let issued = signer.issue(grant_template()).unwrap();
+        assert!(signer.revoke_token(&issued.token).is_err());
+    }
+
+    #[test]
+    fn max_tokens_per_owner_evicts_oldest() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.max_tokens_per_owner(2);
+
+        let first = token_map.issue(grant_template()).unwrap();
+        let second = token_map.issue(grant_template()).unwrap();
+        // Refreshing makes the first token the most recent one.
+        let first = token_map
+            .refresh(first.refresh.as_ref().unwrap(), grant_template())
+            .unwrap();
+        let third = token_map.issue(grant_template()).unwrap();
+
+        assert_eq!(token_map.recover_token(&second.token), Ok(None));
+        assert_eq!(
+            token_map.recover_refresh(second.refresh.as_ref().unwrap()),
+            Ok(None)
+        );
+        assert!(token_map.recover_token(&first.token).unwrap().is_some());
+        assert!(token_map.recover_token(&third.token).unwrap().is_some());
+
+        // Other owners are not affected.
+        let mut other = grant_template();
+        other.owner_id = "Other".to_string();
+        let other = token_map.issue(other).unwrap();
+        assert!(token_map.recover_token(&other.token).unwrap().is_some());
+        assert!(token_map.recover_token(&first.token).unwrap().is_some());
+    }
+
+    #[test]
+    fn max_tokens_per_client_evicts_oldest() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.max_tokens_per_client(1);
+
+        let first = token_map.issue(grant_template()).unwrap();
+        let mut other = grant_template();
+        other.owner_id = "Other".to_string();
+        let second = token_map.issue(other).unwrap();
+
+        assert_eq!(token_map.recover_token(&first.token), Ok(None));
+        assert_eq!(
+            token_map.recover_refresh(first.refresh.as_ref().unwrap()),
+            Ok(None)
+        );
+        assert!(token_map.recover_token(&second.token).unwrap().is_some());
+        assert!(token_map
+            .recover_refresh(second.refresh.as_ref().unwrap())
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn max_tokens_evicts_imported_in_order() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.max_tokens_per_owner(2);
+
+        token_map.import_grant("first".to_string(), grant_template());
+        token_map.import_grant("second".to_string(), grant_template());
+        token_map.import_grant("third".to_string(), grant_template());
+
+        assert_eq!(token_map.recover_token("first"), Ok(None));
+        assert!(token_map.recover_token("second").unwrap().is_some());
+        assert!(token_map.recover_token("third").unwrap().is_some());
+    }
+
+    #[test]
+    fn max_tokens_ignores_revoked() {
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        token_map.max_tokens_per_client(2);
+
+        let first = token_map.issue(grant_template()).unwrap();
+        let second = token_map.issue(grant_template()).unwrap();
+        token_map.revoke_token(first.refresh.as_ref().unwrap()).unwrap();
+        // The access token alone still holds a place until it is revoked as well.
+        token_map.revoke(&second.token);
+        let third = token_map.issue(grant_template()).unwrap();
+
+        assert!(token_map
+            .recover_refresh(second.refresh.as_ref().unwrap())
+            .unwrap()
+            .is_some());
+        assert!(token_map.recover_token(&third.token).unwrap().is_some());
+
+        token_map.revoke(second.refresh.as_ref().unwrap());
+        let fourth = token_map.issue(grant_template()).unwrap();
+        assert!(token_map.recover_token(&third.token).unwrap().is_some());
+        assert!(token_map.recover_token(&fourth.token).unwrap().is_some());
+    }
+
+    #[test]
+    fn touch_unsupported() {
+        // Not enabled on the token map.
+        let mut token_map = TokenMap::new(RandomGenerator::new(16));
+        let issued = token_map.issue(grant_template()).unwrap();
+        assert!(token_map.touch(&issued.token, Utc::now()).is_err());
+
+        // Signed tokens can not be changed.
+        let mut signer = TokenSigner::ephemeral();
+        let issued = signer.issue(grant_template()).unwrap();
+        assert!(signer.touch(&issued.token, Utc::now()).is_err());
+    }
+
     #[test]
     #[should_panic]
     fn bad_generator() {
@@ -0,0 +1,254 @@
+//! Encoding of grants as JWT access tokens, see RFC 9068, and verification of JWT client
+//! assertions, see RFC 7523.
+use std::collections::HashMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{Duration, TimeZone, Utc};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::Time;
+use super::generator::Assertion;
+use super::grant::{Extensions, Grant, Value};
+
+/// The media type of access tokens, in its shortened form.
+const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+
+/// The issuer and audience written into and expected from each token.
+pub(crate) struct JwtProfile {
+    issuer: String,
+    audience: String,
+}
+
+/// The claims of a verified client assertion needed to detect its replay.
+pub(crate) struct ClientAssertion {
+    /// The unique identifier of the assertion.
+    pub(crate) jti: String,
+    /// The expiry of the assertion, it need not be remembered afterwards.
+    pub(crate) until: Time,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    iat: i64,
+    jti: String,
+    client_id: String,
+    scope: String,
+    /// Not a registered claim but required to restore the grant.
+    redirect_uri: String,
+    /// Public extensions of the grant, private extensions can not be part of the token.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    extensions: HashMap<String, Option<String>>,
+}
+
+#[derive(Deserialize)]
+struct AssertionClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    #[serde(default)]
+    nbf: Option<i64>,
+    jti: String,
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::Single(aud) => aud == audience,
+            Audience::Multiple(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+impl JwtProfile {
+    pub(crate) fn new(issuer: String, audience: String) -> Self {
+        JwtProfile { issuer, audience }
+    }
+
+    /// Sign the grant as a JWT for the subject `sub`, issued at `iat`, naming the key `kid`.
+    ///
+    /// Fails if the grant has private extensions or the assertion can not sign.
+    pub(crate) fn encode(
+        &self, assertion: &Assertion, kid: Option<&str>, grant: &Grant, sub: String, iat: Time,
+    ) -> Result<String, ()> {
+        if grant.extensions.private().any(|_| true) {
+            return Err(());
+        }
+
+        let header = Header {
+            alg: assertion.jws_algorithm().to_string(),
+            typ: Some(ACCESS_TOKEN_TYPE.to_string()),
+            kid: kid.map(str::to_string),
+        };
+
+        let mut jti = [0; 16];
+        thread_rng().fill_bytes(&mut jti);
+
+        let claims = Claims {
+            iss: self.issuer.clone(),
+            sub,
+            aud: Audience::Single(self.audience.clone()),
+            exp: grant.until.timestamp(),
+            iat: iat.timestamp(),
+            jti: URL_SAFE_NO_PAD.encode(jti),
+            client_id: grant.client_id.clone(),
+            scope: grant.scope.to_string(),
+            redirect_uri: grant.redirect_uri.to_string(),
+            extensions: grant
+                .extensions
+                .public()
+                .map(|(name, content)| (name.to_string(), content.map(str::to_string)))
+                .collect(),
+        };
+
+        let header = serde_json::to_vec(&header).map_err(|_| ())?;
+        let claims = serde_json::to_vec(&claims).map_err(|_| ())?;
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let signature = assertion.signature(signing_input.as_bytes())?;
+
+        Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// Verify a JWT and restore the grant it was created from.
+    ///
+    /// The algorithm, type, issuer and audience must match those of this profile. A token naming
+    /// a key other than `kid` is rejected before its signature is verified. The expiry is not
+    /// checked here but kept in the grant, like any other issuer does.
+    pub(crate) fn decode(
+        &self, assertion: &Assertion, kid: Option<&str>, token: &str,
+    ) -> Result<Grant, ()> {
+        let (signing_input, signature) = token.rsplit_once('.').ok_or(())?;
+        let (header, claims) = signing_input.split_once('.').ok_or(())?;
+
+        let header = URL_SAFE_NO_PAD.decode(header).map_err(|_| ())?;
+        let header: Header = serde_json::from_slice(&header).map_err(|_| ())?;
+        if header.alg != assertion.jws_algorithm() {
+            return Err(());
+        }
+
+        match header.typ {
+            Some(typ) if Self::is_access_token_type(&typ) => (),
+            _ => return Err(()),
+        }
+
+        // A token naming another key was definitely not signed by this one.
+        if header.kid.is_some() && header.kid.as_deref() != kid {
+            return Err(());
+        }
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ())?;
+        assertion.verify(signing_input.as_bytes(), &signature)?;
+
+        let claims = URL_SAFE_NO_PAD.decode(claims).map_err(|_| ())?;
+        let claims: Claims = serde_json::from_slice(&claims).map_err(|_| ())?;
+
+        if claims.iss != self.issuer {
+            return Err(());
+        }
+
+        if !claims.aud.contains(&self.audience) {
+            return Err(());
+        }
+
+        let mut extensions = Extensions::new();
+        for (name, content) in claims.extensions {
+            extensions.set_raw(name, Value::public(content));
+        }
+
+        Ok(Grant {
+            owner_id: claims.sub,
+            client_id: claims.client_id,
+            scope: claims.scope.parse().map_err(|_| ())?,
+            redirect_uri: claims.redirect_uri.parse().map_err(|_| ())?,
+            until: Utc.timestamp_opt(claims.exp, 0).single().ok_or(())?,
+            extensions,
+        })
+    }
+
+    fn is_access_token_type(typ: &str) -> bool {
+        // The `application/` prefix may be omitted and media types are case-insensitive.
+        let typ = typ.to_ascii_lowercase();
+        typ == ACCESS_TOKEN_TYPE || typ.strip_prefix("application/") == Some(ACCESS_TOKEN_TYPE)
+    }
+}
+
+impl ClientAssertion {
+    /// Assertions must not be valid for longer, this bounds the time their `jti` is remembered.
+    const MAX_LIFETIME: i64 = 3600;
+
+    /// The client an assertion claims to authenticate, without verifying it.
+    pub(crate) fn subject(token: &str) -> Option<String> {
+        let mut parts = token.split('.');
+        let claims = parts.nth(1)?;
+        let claims = URL_SAFE_NO_PAD.decode(claims).ok()?;
+        let claims: AssertionClaims = serde_json::from_slice(&claims).ok()?;
+        Some(claims.sub)
+    }
+
+    /// Verify the assertion of a client for the audience, at time `now`.
+    ///
+    /// Issuer and subject must both be the client. The assertion must not be expired and not be
+    /// valid for more than an hour.
+    pub(crate) fn verify(
+        key: &Assertion, token: &str, client_id: &str, audience: &str, now: Time,
+    ) -> Result<Self, ()> {
+        let (signing_input, signature) = token.rsplit_once('.').ok_or(())?;
+        let (header, claims) = signing_input.split_once('.').ok_or(())?;
+
+        let header = URL_SAFE_NO_PAD.decode(header).map_err(|_| ())?;
+        let header: Header = serde_json::from_slice(&header).map_err(|_| ())?;
+        if header.alg != key.jws_algorithm() {
+            return Err(());
+        }
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ())?;
+        key.verify(signing_input.as_bytes(), &signature)?;
+
+        let claims = URL_SAFE_NO_PAD.decode(claims).map_err(|_| ())?;
+        let claims: AssertionClaims = serde_json::from_slice(&claims).map_err(|_| ())?;
+
+        if claims.iss != client_id || claims.sub != client_id || !claims.aud.contains(audience) {
+            return Err(());
+        }
+
+        let until = Utc.timestamp_opt(claims.exp, 0).single().ok_or(())?;
+        if until <= now || until > now + Duration::seconds(Self::MAX_LIFETIME) {
+            return Err(());
+        }
+
+        if matches!(claims.nbf, Some(nbf) if nbf > now.timestamp()) {
+            return Err(());
+        }
+
+        Ok(ClientAssertion {
+            jti: claims.jti,
+            until,
+        })
+    }
+}
@@ -37,19 +37,24 @@ use chrono::Utc;
 use url::Url;
 
 pub mod authorizer;
+pub mod binding;
+pub mod clock;
+pub mod consent;
 pub mod generator;
 pub mod grant;
 pub mod issuer;
+pub(crate) mod jwt;
 pub mod registrar;
 pub mod scope;
+pub mod subject;
 
 type Time = DateTime<Utc>;
 
 /// Commonly used primitives for frontends and backends.
 pub mod prelude {
-    pub use super::authorizer::{Authorizer, AuthMap};
-    pub use super::issuer::{IssuedToken, Issuer, TokenMap, TokenSigner};
+    pub use super::authorizer::{Authorizer, AuthMap, CodeSigner};
+    pub use super::issuer::{GrantQuery, IssuedToken, Issuer, TokenMap, TokenSigner};
     pub use super::generator::{Assertion, TagGrant, RandomGenerator};
-    pub use super::registrar::{Registrar, Client, ClientUrl, ClientMap, PreGrant};
+    pub use super::registrar::{Registrar, ChainRegistrar, Client, ClientUrl, ClientMap, PreGrant};
     pub use super::scope::Scope;
 }
@@ -3,21 +3,30 @@
 //! It will govern their redirect urls and allowed scopes to request tokens for. When an oauth
 //! request turns up, it is the registrars duty to verify the requested scope and redirect url for
 //! consistency in the permissions granted and urls registered.
-use super::scope::Scope;
+use super::binding::Binding;
+use super::generator::{Assertion, AssertionKind};
+use super::jwt::ClientAssertion;
+use super::scope::{ParseScopeErr, Scope};
+use super::Time;
 
 use std::borrow::Cow;
 use std::cmp;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::convert::TryFrom;
 use std::fmt;
 use std::iter::{Extend, FromIterator};
 use std::rc::Rc;
-use std::sync::{Arc, MutexGuard, RwLockWriteGuard};
+use std::sync::{Arc, Mutex, MutexGuard, RwLockWriteGuard};
 
 use argon2::{self, Config};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
 use once_cell::sync::Lazy;
 use rand::{RngCore, thread_rng};
 use serde::{Deserialize, Serialize};
-use url::{Url, ParseError as ParseUrlError};
+use url::{Host, Url, ParseError as ParseUrlError};
 
 /// Registrars provie a way to interact with clients.
 ///
@@ -40,10 +49,60 @@ pub trait Registrar {
     ///
     /// Another common strategy is to set a default scope or return the intersection with another
     /// scope.
+    ///
+    /// Negotiation happens before the resource owner is asked for consent. The owner may then
+    /// narrow the negotiated scope further, see `OwnerConsent::AuthorizedScope`, but never extend
+    /// it, so the negotiated scope is an upper bound for the issued grant.
     fn negotiate(&self, client: BoundClient, scope: Option<Scope>) -> Result<PreGrant, RegistrarError>;
 
     /// Try to login as client with some authentication.
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError>;
+
+    /// Try to login as client with a JWT assertion, see [RFC 7523].
+    ///
+    /// The assertion was sent as `client_assertion` with the `client_assertion_type`
+    /// `urn:ietf:params:oauth:client-assertion-type:jwt-bearer`. It must be signed with a key of
+    /// the client, name the client as issuer and subject and the token endpoint as audience. An
+    /// assertion must only be accepted once. Rejecting all assertions is the default
+    /// implementation.
+    ///
+    /// [RFC 7523]: https://tools.ietf.org/html/rfc7523
+    fn check_assertion(&self, _client_id: &str, _assertion: &str) -> Result<(), RegistrarError> {
+        Err(RegistrarError::Unspecified)
+    }
+
+    /// Try to login as client with the certificate of its TLS connection, see [RFC 8705].
+    ///
+    /// The `certificate` is the DER encoding of the certificate the client presented, the
+    /// frontend must have checked that the client possesses its private key. Only called when no
+    /// other credentials were sent with the request. A registrar supporting `tls_client_auth`
+    /// validates the certificate chain against its trust anchors and compares the subject with the
+    /// registered one, for `self_signed_tls_client_auth` it compares the certificate with those
+    /// registered for the client. The default implementation ignores the certificate and
+    /// authenticates the client without credentials, as `check(client_id, None)`.
+    ///
+    /// [RFC 8705]: https://tools.ietf.org/html/rfc8705#section-2
+    fn check_certificate(&self, client_id: &str, _certificate: &[u8]) -> Result<(), RegistrarError> {
+        self.check(client_id, None)
+    }
+
+    /// Determine how far the server trusts a client.
+    ///
+    /// This is consulted before the resource owner is asked for consent and handed to the owner
+    /// solicitor. Returning `ClientTrust::ThirdParty` is the default implementation.
+    fn client_trust(&self, _client_id: &str) -> ClientTrust {
+        ClientTrust::ThirdParty
+    }
+
+    /// Check that the client may use a grant type.
+    ///
+    /// Consulted by the authorization flow for the code and implicit grant and by the flows of
+    /// the token endpoint, after the client was authenticated. A client which is not permitted
+    /// to use the grant is answered with `unauthorized_client`. Permitting all grant types is the
+    /// default implementation.
+    fn check_grant_type(&self, _client_id: &str, _grant_type: GrantType) -> Result<(), RegistrarError> {
+        Ok(())
+    }
 }
 
 /// An url that has been registered.
@@ -238,6 +297,11 @@ pub struct Client {
     additional_redirect_uris: Vec<RegisteredUrl>,
     default_scope: Scope,
     client_type: ClientType,
+    trust: ClientTrust,
+    loopback_port_variance: bool,
+    assertion_key: Option<ClientKey>,
+    certificate: Option<String>,
+    grant_types: Option<Vec<GrantType>>,
 }
 
 /// A client whose credentials have been wrapped by a password policy.
@@ -264,6 +328,80 @@ pub struct EncodedClient {
 
     /// The authentication data.
     pub encoded_client: ClientType,
+
+    /// How far the server trusts the client.
+    #[serde(default)]
+    pub trust: ClientTrust,
+
+    /// Whether redirect uris on a loopback host match regardless of their port.
+    #[serde(default)]
+    pub loopback_port_variance: bool,
+
+    /// The key verifying assertions the client authenticates with.
+    #[serde(default)]
+    pub assertion_key: Option<ClientKey>,
+
+    /// The `x5t#S256` thumbprint of the certificate the client authenticates with.
+    #[serde(default)]
+    pub certificate: Option<String>,
+
+    /// The grant types the client may use, all of them if this is `None`.
+    #[serde(default)]
+    pub grant_types: Option<Vec<GrantType>>,
+}
+
+/// A key of a client, verifying the JWT assertions it authenticates with.
+///
+/// See [`Client::with_assertion_key`]. Assertions must be signed with the algorithm matching the
+/// key, `HS256` for a shared secret and `EdDSA` for an Ed25519 key.
+///
+/// [`Client::with_assertion_key`]: struct.Client.html#method.with_assertion_key
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ClientKey {
+    /// A secret shared with the client, the `client_secret_jwt` method.
+    HmacSha256(Vec<u8>),
+
+    /// The public Ed25519 key of the client, the `private_key_jwt` method.
+    ///
+    /// Verifying requires the `ed25519` feature, without it all assertions are rejected.
+    Ed25519([u8; 32]),
+}
+
+/// How far the server trusts a client, used to decide whether owners need to consent to a grant.
+///
+/// A first-party client is operated by the same party as the authorization server, for example its
+/// own web frontend. Asking the owner to confirm its grants would add little to no security.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ClientTrust {
+    /// A client operated by the server itself.
+    FirstParty,
+
+    /// Any other client, which needs the explicit consent of the owner.
+    #[default]
+    ThirdParty,
+}
+
+/// A way for a client to obtain a grant, see [`Client::with_grant_types`].
+///
+/// Serialized as the `grant_type` value of RFC 6749 or, for the implicit grant, as `implicit`.
+///
+/// [`Client::with_grant_types`]: struct.Client.html#method.with_grant_types
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum GrantType {
+    /// Redeeming an authorization code at the token endpoint.
+    AuthorizationCode,
+
+    /// Receiving an access token directly from the authorization endpoint.
+    Implicit,
+
+    /// Requesting a token for the client itself, with its own credentials.
+    ClientCredentials,
+
+    /// Refreshing a previously issued token.
+    RefreshToken,
 }
 
 /// Recombines an `EncodedClient` and a  `PasswordPolicy` to check authentication.
@@ -290,6 +428,31 @@ pub enum ClientType {
 pub struct ClientMap {
     clients: HashMap<String, EncodedClient>,
     password_policy: Option<Box<dyn PasswordPolicy>>,
+    assertion_audience: Option<String>,
+    /// Identifiers of accepted assertions by client, until they expire.
+    assertion_ids: Mutex<HashMap<(String, String), Time>>,
+}
+
+/// The serializable state of a `ClientMap`.
+///
+/// Created by [`ClientMap::snapshot`] and consumed by [`ClientMap::restore`]. Client secrets are
+/// only contained in the form encoded by the password policy, so the map restoring the snapshot
+/// must use the same policy as the one which registered the clients.
+///
+/// [`ClientMap::snapshot`]: struct.ClientMap.html#method.snapshot
+/// [`ClientMap::restore`]: struct.ClientMap.html#method.restore
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientMapSnapshot {
+    clients: Vec<EncodedClient>,
+}
+
+impl fmt::Debug for ClientKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ClientKey::HmacSha256(_) => write!(f, "<hmac-sha256>"),
+            ClientKey::Ed25519(key) => write!(f, "Ed25519({})", URL_SAFE_NO_PAD.encode(key)),
+        }
+    }
 }
 
 impl fmt::Debug for ClientType {
@@ -493,6 +656,12 @@ impl core::str::FromStr for IgnoreLocalPortUrl {
 
 impl Client {
     /// Create a public client.
+    ///
+    /// The `Scope` type guarantees a syntactically valid default scope. When the scope is read
+    /// from configuration, prefer [`Client::try_public`] which additionally rejects duplicate
+    /// tokens and an empty default scope.
+    ///
+    /// [`Client::try_public`]: #method.try_public
     pub fn public(client_id: &str, redirect_uri: RegisteredUrl, default_scope: Scope) -> Client {
         Client {
             client_id: client_id.to_string(),
@@ -500,10 +669,31 @@ impl Client {
             additional_redirect_uris: vec![],
             default_scope,
             client_type: ClientType::Public,
+            trust: ClientTrust::ThirdParty,
+            loopback_port_variance: false,
+            assertion_key: None,
+            certificate: None,
+            grant_types: None,
         }
     }
 
+    /// Create a public client, validating the default scope.
+    ///
+    /// The scope is parsed with [`Scope::parse_strict`].
+    ///
+    /// [`Scope::parse_strict`]: ../scope/struct.Scope.html#method.parse_strict
+    pub fn try_public(
+        client_id: &str, redirect_uri: RegisteredUrl, default_scope: &str,
+    ) -> Result<Client, ParseScopeErr> {
+        let default_scope = Scope::parse_strict(default_scope)?;
+        Ok(Client::public(client_id, redirect_uri, default_scope))
+    }
+
     /// Create a confidential client.
+    ///
+    /// See [`Client::try_confidential`] to validate a default scope read from configuration.
+    ///
+    /// [`Client::try_confidential`]: #method.try_confidential
     pub fn confidential(
         client_id: &str, redirect_uri: RegisteredUrl, default_scope: Scope, passphrase: &[u8],
     ) -> Client {
@@ -515,15 +705,94 @@ impl Client {
             client_type: ClientType::Confidential {
                 passdata: passphrase.to_owned(),
             },
+            trust: ClientTrust::ThirdParty,
+            loopback_port_variance: false,
+            assertion_key: None,
+            certificate: None,
+            grant_types: None,
         }
     }
 
+    /// Create a confidential client, validating the default scope.
+    ///
+    /// The scope is parsed with [`Scope::parse_strict`].
+    ///
+    /// [`Scope::parse_strict`]: ../scope/struct.Scope.html#method.parse_strict
+    pub fn try_confidential(
+        client_id: &str, redirect_uri: RegisteredUrl, default_scope: &str, passphrase: &[u8],
+    ) -> Result<Client, ParseScopeErr> {
+        let default_scope = Scope::parse_strict(default_scope)?;
+        Ok(Client::confidential(
+            client_id,
+            redirect_uri,
+            default_scope,
+            passphrase,
+        ))
+    }
+
     /// Add additional redirect uris.
     pub fn with_additional_redirect_uris(mut self, uris: Vec<RegisteredUrl>) -> Self {
         self.additional_redirect_uris = uris;
         self
     }
 
+    /// Set how far the server trusts the client.
+    ///
+    /// Clients are third-party clients by default.
+    pub fn with_trust(mut self, trust: ClientTrust) -> Self {
+        self.trust = trust;
+        self
+    }
+
+    /// Accept any port in redirect uris on a registered loopback host.
+    ///
+    /// Native applications listen on a port assigned by the operating system at runtime, see
+    /// [RFC 8252, Section 7.3]. Redirect uris on `127.0.0.1`, `[::1]` or `localhost` then match
+    /// a registered uri when scheme, host and path are equal, independent of the port. Matching of
+    /// all other hosts is unaffected.
+    ///
+    /// [RFC 8252, Section 7.3]: https://www.rfc-editor.org/rfc/rfc8252#section-7.3
+    pub fn allow_loopback_port_variance(mut self) -> Self {
+        self.loopback_port_variance = true;
+        self
+    }
+
+    /// Authenticate the client with JWT assertions verified by this key, see [RFC 7523].
+    ///
+    /// Once a key is registered, a public client can no longer authenticate without credentials.
+    /// A confidential client may use either its passphrase or an assertion.
+    ///
+    /// [RFC 7523]: https://tools.ietf.org/html/rfc7523
+    pub fn with_assertion_key(mut self, key: ClientKey) -> Self {
+        self.assertion_key = Some(key);
+        self
+    }
+
+    /// Authenticate the client with the certificate of its TLS connection, see [RFC 8705].
+    ///
+    /// The certificate is identified by its `x5t#S256` thumbprint, see [`Binding::certificate`].
+    /// This is the `self_signed_tls_client_auth` method, or `tls_client_auth` with the certificate
+    /// pinned instead of validated against a certificate authority. Once a certificate is
+    /// registered, a public client can no longer authenticate without credentials.
+    ///
+    /// [RFC 8705]: https://tools.ietf.org/html/rfc8705#section-2.2
+    /// [`Binding::certificate`]: ../binding/enum.Binding.html#method.certificate
+    pub fn with_certificate_thumbprint(mut self, x5t_s256: String) -> Self {
+        self.certificate = Some(x5t_s256);
+        self
+    }
+
+    /// Restrict the grant types the client may use.
+    ///
+    /// Clients may use all grant types by default. Registrars reject any other grant type with
+    /// `unauthorized_client`, for example a single page application restricted to
+    /// `GrantType::AuthorizationCode` and `GrantType::RefreshToken` can not request tokens for
+    /// itself with `client_credentials`.
+    pub fn with_grant_types(mut self, grant_types: Vec<GrantType>) -> Self {
+        self.grant_types = Some(grant_types);
+        self
+    }
+
     /// Obscure the clients authentication data.
     ///
     /// This could apply a one-way function to the passphrase using an adequate password hashing
@@ -543,10 +812,102 @@ impl Client {
             additional_redirect_uris: self.additional_redirect_uris,
             default_scope: self.default_scope,
             encoded_client,
+            trust: self.trust,
+            loopback_port_variance: self.loopback_port_variance,
+            assertion_key: self.assertion_key,
+            certificate: self.certificate,
+            grant_types: self.grant_types,
         }
     }
 }
 
+impl ClientKey {
+    /// Parse a public key of a client from its JSON Web Key, see [RFC 8037].
+    ///
+    /// Only Ed25519 keys, of key type `OKP`, are supported.
+    ///
+    /// [RFC 8037]: https://tools.ietf.org/html/rfc8037
+    pub fn from_jwk(jwk: &str) -> Result<Self, ()> {
+        #[derive(Deserialize)]
+        struct Jwk {
+            kty: String,
+            crv: String,
+            x: String,
+        }
+
+        let jwk: Jwk = serde_json::from_str(jwk).map_err(|_| ())?;
+        if jwk.kty != "OKP" || jwk.crv != "Ed25519" {
+            return Err(());
+        }
+
+        let x = URL_SAFE_NO_PAD.decode(jwk.x).map_err(|_| ())?;
+        let key = <[u8; 32]>::try_from(x.as_slice()).map_err(|_| ())?;
+        Ok(ClientKey::Ed25519(key))
+    }
+
+    fn verifier(&self) -> Result<Assertion, ()> {
+        match self {
+            ClientKey::HmacSha256(secret) => Ok(Assertion::new(AssertionKind::HmacSha256, secret)),
+            #[cfg(feature = "ed25519")]
+            ClientKey::Ed25519(key) => Assertion::ed25519_verifier(key),
+            #[cfg(not(feature = "ed25519"))]
+            ClientKey::Ed25519(_) => Err(()),
+        }
+    }
+}
+
+impl EncodedClient {
+    /// All redirect uris registered for this client, starting with the default one.
+    pub fn redirect_uris(&self) -> impl Iterator<Item = &RegisteredUrl> {
+        std::iter::once(&self.redirect_uri).chain(self.additional_redirect_uris.iter())
+    }
+
+    /// Find the registered redirect uri a request bound to, by exact matching.
+    ///
+    /// When the request contained no redirect uri the client may only be bound to a default if it
+    /// registered exactly one, as required by the rfc. Otherwise the request is ambiguous and no
+    /// uri is returned.
+    pub fn bound_redirect_uri(&self, requested: Option<&ExactUrl>) -> Option<&RegisteredUrl> {
+        match requested {
+            None if self.additional_redirect_uris.is_empty() => Some(&self.redirect_uri),
+            None => None,
+            Some(url) => self.redirect_uris().find(|registered| {
+                *registered == url || (self.loopback_port_variance && loopback_port_eq(registered, url))
+            }),
+        }
+    }
+
+    /// Whether the client may use a grant type.
+    pub fn allows_grant_type(&self, grant_type: GrantType) -> bool {
+        self.grant_types
+            .as_ref()
+            .map_or(true, |grant_types| grant_types.contains(&grant_type))
+    }
+}
+
+/// Compare two urls on the same loopback host, disregarding their port.
+fn loopback_port_eq(registered: &RegisteredUrl, requested: &ExactUrl) -> bool {
+    fn is_loopback(url: &Url) -> bool {
+        match url.host() {
+            Some(Host::Domain(domain)) => domain == "localhost",
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        }
+    }
+
+    let mut registered = registered.to_url();
+    let mut requested = requested.to_url();
+    if !is_loopback(&registered) || !is_loopback(&requested) {
+        return false;
+    }
+
+    // Setting the port only fails for urls without a host, which are not loopback urls.
+    let _ = registered.set_port(None);
+    let _ = requested.set_port(None);
+    registered == requested
+}
+
 impl<'a> RegisteredClient<'a> {
     /// Binds a client and a policy reference together.
     ///
@@ -559,9 +920,13 @@ impl<'a> RegisteredClient<'a> {
     /// Try to authenticate with the client and passphrase. This check will success if either the
     /// client is public and no passphrase was provided or if the client is confidential and the
     /// passphrase matches.
+    ///
+    /// A public client with an assertion key or a certificate must not authenticate without
+    /// credentials.
     pub fn check_authentication(&self, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
+        let credentialed = self.client.assertion_key.is_some() || self.client.certificate.is_some();
         match (passphrase, &self.client.encoded_client) {
-            (None, &ClientType::Public) => Ok(()),
+            (None, &ClientType::Public) if !credentialed => Ok(()),
             (Some(provided), &ClientType::Confidential { passdata: ref stored }) => {
                 self.policy.check(&self.client.client_id, provided, stored)
             }
@@ -583,7 +948,14 @@ impl cmp::PartialOrd<Self> for PreGrant {
 
 /// Determines how passphrases are stored and checked.
 ///
-/// The provided library implementation is based on `Argon2`.
+/// The default library implementation is based on `Argon2`. With the `pbkdf2` feature, a policy
+/// based on `PBKDF2-HMAC-SHA256` is available as well. Custom implementations can be configured
+/// with [`ClientMap::set_password_policy`].
+///
+/// Clients must be checked with the same policy that stored their passphrase, the stored formats
+/// are not compatible with each other.
+///
+/// [`ClientMap::set_password_policy`]: struct.ClientMap.html#method.set_password_policy
 pub trait PasswordPolicy: Send + Sync {
     /// Transform the passphrase so it can be stored in the confidential client.
     fn store(&self, client_id: &str, passphrase: &[u8]) -> Vec<u8>;
@@ -626,6 +998,101 @@ impl PasswordPolicy for Argon2 {
     }
 }
 
+/// Store passwords using `PBKDF2-HMAC-SHA256` to derive the stored value.
+///
+/// The passphrase is salted with random bytes and the client id. The stored value contains the
+/// number of iterations, so that it can be increased later without invalidating existing clients.
+#[cfg(feature = "pbkdf2")]
+#[derive(Clone, Debug)]
+pub struct Pbkdf2 {
+    iterations: u32,
+}
+
+#[cfg(feature = "pbkdf2")]
+impl Pbkdf2 {
+    /// The iteration count recommended by OWASP for `PBKDF2-HMAC-SHA256`.
+    pub const DEFAULT_ITERATIONS: u32 = 600_000;
+
+    const PREFIX: &'static str = "$pbkdf2-sha256$";
+    const SALT_LEN: usize = 16;
+    const HASH_LEN: usize = 32;
+
+    /// Use a custom number of iterations for newly stored passphrases.
+    pub fn with_iterations(iterations: u32) -> Self {
+        Pbkdf2 { iterations }
+    }
+
+    fn derive(client_id: &str, passphrase: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+        let mut salted = salt.to_vec();
+        salted.extend_from_slice(client_id.as_bytes());
+        let mut output = vec![0; Self::HASH_LEN];
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase, &salted, iterations, &mut output);
+        output
+    }
+}
+
+#[cfg(feature = "pbkdf2")]
+impl Default for Pbkdf2 {
+    fn default() -> Self {
+        Pbkdf2::with_iterations(Self::DEFAULT_ITERATIONS)
+    }
+}
+
+#[cfg(feature = "pbkdf2")]
+impl PasswordPolicy for Pbkdf2 {
+    fn store(&self, client_id: &str, passphrase: &[u8]) -> Vec<u8> {
+        use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+
+        let mut salt = vec![0; Self::SALT_LEN];
+        thread_rng()
+            .try_fill_bytes(salt.as_mut_slice())
+            .expect("Failed to generate password salt");
+
+        let hash = Self::derive(client_id, passphrase, &salt, self.iterations);
+        format!(
+            "{}i={}${}${}",
+            Self::PREFIX,
+            self.iterations,
+            STANDARD_NO_PAD.encode(&salt),
+            STANDARD_NO_PAD.encode(&hash)
+        )
+        .into_bytes()
+    }
+
+    fn check(&self, client_id: &str, passphrase: &[u8], stored: &[u8]) -> Result<(), RegistrarError> {
+        use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+        use subtle::ConstantTimeEq;
+
+        let stored = std::str::from_utf8(stored).map_err(|_| RegistrarError::PrimitiveError)?;
+        let mut parts = stored
+            .strip_prefix(Self::PREFIX)
+            .ok_or(RegistrarError::PrimitiveError)?
+            .split('$');
+
+        let (iterations, salt, hash) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(iterations), Some(salt), Some(hash), None) => (iterations, salt, hash),
+            _ => return Err(RegistrarError::PrimitiveError),
+        };
+
+        let iterations = iterations
+            .strip_prefix("i=")
+            .and_then(|count| count.parse().ok())
+            .ok_or(RegistrarError::PrimitiveError)?;
+        let salt = STANDARD_NO_PAD
+            .decode(salt)
+            .map_err(|_| RegistrarError::PrimitiveError)?;
+        let hash = STANDARD_NO_PAD
+            .decode(hash)
+            .map_err(|_| RegistrarError::PrimitiveError)?;
+
+        let derived = Self::derive(client_id, passphrase, &salt, iterations);
+        match bool::from(derived.ct_eq(&hash)) {
+            true => Ok(()),
+            false => Err(RegistrarError::Unspecified),
+        }
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 //                             Standard Implementations of Registrars                            //
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -650,6 +1117,32 @@ impl ClientMap {
         self.password_policy = Some(Box::new(new_policy))
     }
 
+    /// Accept client assertions addressed to this audience.
+    ///
+    /// This should be the url of the token endpoint. Without an audience all assertions are
+    /// rejected.
+    pub fn set_assertion_audience<S: Into<String>>(&mut self, audience: S) {
+        self.assertion_audience = Some(audience.into())
+    }
+
+    /// Capture all registered clients, for example to persist them across restarts.
+    pub fn snapshot(&self) -> ClientMapSnapshot {
+        ClientMapSnapshot {
+            clients: self.clients.values().cloned().collect(),
+        }
+    }
+
+    /// Replace all registered clients with those of a snapshot.
+    ///
+    /// The password policy is not part of the snapshot and stays unchanged.
+    pub fn restore(&mut self, snapshot: ClientMapSnapshot) {
+        self.clients = snapshot
+            .clients
+            .into_iter()
+            .map(|client| (client.client_id.clone(), client))
+            .collect();
+    }
+
     // This is not an instance method because it needs to borrow the box but register needs &mut
     fn current_policy<'a>(policy: &'a Option<Box<dyn PasswordPolicy>>) -> &'a dyn PasswordPolicy {
         policy
@@ -679,6 +1172,195 @@ impl FromIterator<Client> for ClientMap {
     }
 }
 
+/// A malformed client configuration, see [`ClientMap::from_toml`].
+///
+/// [`ClientMap::from_toml`]: struct.ClientMap.html#method.from_toml
+#[cfg(feature = "config")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClientConfigError {
+    /// The document could not be deserialized into a list of clients.
+    Syntax(String),
+
+    /// A redirect uri of the client is not a valid url.
+    RedirectUri {
+        /// The id of the client.
+        client_id: String,
+        /// The malformed uri.
+        uri: String,
+        /// The reason the uri was rejected.
+        error: ParseUrlError,
+    },
+
+    /// The scope of the client is malformed or empty.
+    Scope {
+        /// The id of the client.
+        client_id: String,
+        /// The reason the scope was rejected.
+        error: ParseScopeErr,
+    },
+
+    /// The same client id appeared more than once.
+    DuplicateClient(String),
+}
+
+/// The clients of a configuration, the `clients` table array of the document.
+#[cfg(feature = "config")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientsConfig {
+    #[serde(default)]
+    clients: Vec<ClientConfig>,
+}
+
+#[cfg(feature = "config")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClientConfig {
+    client_id: String,
+    redirect_uri: String,
+    #[serde(default)]
+    additional_redirect_uris: Vec<String>,
+    scope: String,
+    /// The passphrase of a confidential client, public clients have none.
+    #[serde(default)]
+    secret: Option<String>,
+    #[serde(default)]
+    trust: ClientTrust,
+    /// The permitted grant types, all of them when missing.
+    #[serde(default)]
+    grant_types: Option<Vec<GrantType>>,
+}
+
+#[cfg(feature = "config")]
+impl ClientMap {
+    /// Create a map with the clients defined in a toml document.
+    ///
+    /// Each client is an entry of the `clients` table array. A client with a `secret` is
+    /// confidential, all others are public. A client restricted with `grant_types` may only use
+    /// those, see [`Client::with_grant_types`]. Redirect uris are matched exactly and the scope is
+    /// parsed with [`Scope::parse_strict`]. Secrets are stored with the default password policy.
+    ///
+    /// ```
+    /// # use oxide_auth::primitives::registrar::ClientMap;
+    /// let clients = ClientMap::from_toml(r#"
+    ///     [[clients]]
+    ///     client_id = "LocalClient"
+    ///     redirect_uri = "http://localhost:8021/endpoint"
+    ///     additional_redirect_uris = ["http://localhost:8021/other"]
+    ///     scope = "default-scope"
+    ///     secret = "SecretSecret"
+    ///     trust = "FirstParty"
+    ///
+    ///     [[clients]]
+    ///     client_id = "PublicClient"
+    ///     redirect_uri = "http://localhost:8021/public"
+    ///     scope = "default-scope"
+    ///     grant_types = ["authorization_code", "refresh_token"]
+    /// "#).expect("Valid configuration");
+    /// ```
+    ///
+    /// [`Scope::parse_strict`]: ../scope/struct.Scope.html#method.parse_strict
+    /// [`Client::with_grant_types`]: struct.Client.html#method.with_grant_types
+    pub fn from_toml(config: &str) -> Result<ClientMap, ClientConfigError> {
+        let config: ClientsConfig =
+            toml::from_str(config).map_err(|err| ClientConfigError::Syntax(err.to_string()))?;
+        Self::from_config(config)
+    }
+
+    /// Create a map with the clients defined in a json document.
+    ///
+    /// The document is an object with a `clients` array, each entry having the same fields as in
+    /// [`ClientMap::from_toml`].
+    ///
+    /// [`ClientMap::from_toml`]: #method.from_toml
+    pub fn from_json(config: &str) -> Result<ClientMap, ClientConfigError> {
+        let config: ClientsConfig =
+            serde_json::from_str(config).map_err(|err| ClientConfigError::Syntax(err.to_string()))?;
+        Self::from_config(config)
+    }
+
+    fn from_config(config: ClientsConfig) -> Result<ClientMap, ClientConfigError> {
+        let mut map = ClientMap::new();
+        for client in config.clients {
+            if map.clients.contains_key(&client.client_id) {
+                return Err(ClientConfigError::DuplicateClient(client.client_id));
+            }
+
+            let client = client.into_client()?;
+            map.register_client(client);
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(feature = "config")]
+impl ClientConfig {
+    fn into_client(self) -> Result<Client, ClientConfigError> {
+        let client_id = self.client_id;
+        let redirect_uri = |uri: String| match ExactUrl::new(uri.clone()) {
+            Ok(url) => Ok(RegisteredUrl::Exact(url)),
+            Err(error) => Err(ClientConfigError::RedirectUri {
+                client_id: client_id.clone(),
+                uri,
+                error,
+            }),
+        };
+
+        let default_uri = redirect_uri(self.redirect_uri)?;
+        let additional_uris = self
+            .additional_redirect_uris
+            .into_iter()
+            .map(redirect_uri)
+            .collect::<Result<Vec<_>, _>>()?;
+        let scope = Scope::parse_strict(&self.scope).map_err(|error| ClientConfigError::Scope {
+            client_id: client_id.clone(),
+            error,
+        })?;
+
+        let client = match self.secret {
+            Some(secret) => Client::confidential(&client_id, default_uri, scope, secret.as_bytes()),
+            None => Client::public(&client_id, default_uri, scope),
+        };
+
+        let client = client
+            .with_additional_redirect_uris(additional_uris)
+            .with_trust(self.trust);
+        Ok(match self.grant_types {
+            Some(grant_types) => client.with_grant_types(grant_types),
+            None => client,
+        })
+    }
+}
+
+#[cfg(feature = "config")]
+impl fmt::Display for ClientConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ClientConfigError::Syntax(err) => write!(fmt, "Malformed client configuration: {}", err),
+            ClientConfigError::RedirectUri {
+                client_id,
+                uri,
+                error,
+            } => write!(
+                fmt,
+                "Invalid redirect uri {:?} of client {}: {}",
+                uri, client_id, error
+            ),
+            ClientConfigError::Scope { client_id, error } => {
+                write!(fmt, "Invalid scope of client {}: {}", client_id, error)
+            }
+            ClientConfigError::DuplicateClient(client_id) => {
+                write!(fmt, "Client {} is defined more than once", client_id)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl std::error::Error for ClientConfigError {}
+
 impl<'s, R: Registrar + ?Sized> Registrar for &'s R {
     fn bound_redirect<'a>(&self, bound: ClientUrl<'a>) -> Result<BoundClient<'a>, RegistrarError> {
         (**self).bound_redirect(bound)
@@ -691,6 +1373,22 @@ impl<'s, R: Registrar + ?Sized> Registrar for &'s R {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl<'s, R: Registrar + ?Sized> Registrar for &'s mut R {
@@ -705,6 +1403,22 @@ impl<'s, R: Registrar + ?Sized> Registrar for &'s mut R {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl<R: Registrar + ?Sized> Registrar for Box<R> {
@@ -719,6 +1433,22 @@ impl<R: Registrar + ?Sized> Registrar for Box<R> {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl<R: Registrar + ?Sized> Registrar for Rc<R> {
@@ -733,6 +1463,22 @@ impl<R: Registrar + ?Sized> Registrar for Rc<R> {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl<R: Registrar + ?Sized> Registrar for Arc<R> {
@@ -747,6 +1493,22 @@ impl<R: Registrar + ?Sized> Registrar for Arc<R> {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl<'s, R: Registrar + ?Sized + 's> Registrar for MutexGuard<'s, R> {
@@ -761,6 +1523,22 @@ impl<'s, R: Registrar + ?Sized + 's> Registrar for MutexGuard<'s, R> {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl<'s, R: Registrar + ?Sized + 's> Registrar for RwLockWriteGuard<'s, R> {
@@ -775,6 +1553,22 @@ impl<'s, R: Registrar + ?Sized + 's> Registrar for RwLockWriteGuard<'s, R> {
     fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
         (**self).check(client_id, passphrase)
     }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        (**self).check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        (**self).check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        (**self).client_trust(client_id)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        (**self).check_grant_type(client_id, grant_type)
+    }
 }
 
 impl Registrar for ClientMap {
@@ -785,20 +1579,13 @@ impl Registrar for ClientMap {
         };
 
         // Perform exact matching as motivated in the rfc
-        let registered_url = match bound.redirect_uri {
-            None => client.redirect_uri.clone(),
-            Some(url) => {
-                let original = std::iter::once(&client.redirect_uri);
-                let alternatives = client.additional_redirect_uris.iter();
-                if original
-                    .chain(alternatives)
-                    .any(|registered| *registered == *url.as_ref())
-                {
-                    RegisteredUrl::Exact((*url).clone())
-                } else {
-                    return Err(RegistrarError::Unspecified);
-                }
-            }
+        let registered_url = match client.bound_redirect_uri(bound.redirect_uri.as_deref()) {
+            None => return Err(RegistrarError::Unspecified),
+            Some(registered) => match bound.redirect_uri {
+                // Keep the requested url, it may differ from the registered one in its local port.
+                Some(url) => RegisteredUrl::Exact(url.into_owned()),
+                None => registered.clone(),
+            },
         };
 
         Ok(BoundClient {
@@ -832,6 +1619,167 @@ impl Registrar for ClientMap {
 
         Ok(())
     }
+
+    /// Verify the assertion with the key of the client and remember its `jti` until it expires.
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        let audience = self
+            .assertion_audience
+            .as_deref()
+            .ok_or(RegistrarError::Unspecified)?;
+        let key = self
+            .clients
+            .get(client_id)
+            .and_then(|client| client.assertion_key.as_ref())
+            .ok_or(RegistrarError::Unspecified)?;
+        let verifier = key.verifier().map_err(|_| RegistrarError::Unspecified)?;
+
+        let now = Utc::now();
+        let assertion = ClientAssertion::verify(&verifier, assertion, client_id, audience, now)
+            .map_err(|_| RegistrarError::Unspecified)?;
+
+        let mut assertion_ids = self
+            .assertion_ids
+            .lock()
+            .map_err(|_| RegistrarError::PrimitiveError)?;
+        assertion_ids.retain(|_, until| *until > now);
+        match assertion_ids.entry((client_id.to_string(), assertion.jti)) {
+            Entry::Occupied(_) => Err(RegistrarError::Unspecified),
+            Entry::Vacant(vacant) => {
+                vacant.insert(assertion.until);
+                Ok(())
+            }
+        }
+    }
+
+    /// Compare the certificate with the one registered for the client.
+    ///
+    /// Clients without a registered certificate are authenticated as if no credentials were sent.
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        let client = self.clients.get(client_id).ok_or(RegistrarError::Unspecified)?;
+        let registered = match &client.certificate {
+            None => return self.check(client_id, None),
+            Some(registered) => registered,
+        };
+
+        match Binding::certificate(certificate) {
+            Binding::Certificate { x5t_s256 } if x5t_s256 == *registered => Ok(()),
+            _ => Err(RegistrarError::Unspecified),
+        }
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        self.clients
+            .get(client_id)
+            .map_or(ClientTrust::ThirdParty, |client| client.trust)
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        match self.clients.get(client_id) {
+            Some(client) if client.allows_grant_type(grant_type) => Ok(()),
+            _ => Err(RegistrarError::Unspecified),
+        }
+    }
+}
+
+/// Combines several registrars, such as static clients and those of a database.
+///
+/// Each client is handled by the first registrar that knows it, in the order they were added. A
+/// registrar knows a client if it binds the client to its default redirect uri. That registrar
+/// alone decides on the redirect uri, the scope and the authentication of the client, later
+/// registrars are not asked even if they know a client with the same id. Clients unknown to all
+/// registrars are rejected.
+///
+/// ```
+/// # use oxide_auth::primitives::registrar::{ChainRegistrar, Client, ClientMap};
+/// # use std::sync::Arc;
+/// let builtin: ClientMap = vec![Client::public(
+///     "builtin",
+///     "https://builtin.example/endpoint".parse::<url::Url>().unwrap().into(),
+///     "default".parse().unwrap(),
+/// )]
+/// .into_iter()
+/// .collect();
+/// let registered = Arc::new(ClientMap::new());
+///
+/// let mut registrar = ChainRegistrar::new();
+/// registrar.push(builtin);
+/// registrar.push(registered.clone());
+/// ```
+#[derive(Default)]
+pub struct ChainRegistrar {
+    registrars: Vec<Box<dyn Registrar + Send + Sync>>,
+}
+
+impl ChainRegistrar {
+    /// Create a chain without any registrars, rejecting all clients.
+    pub fn new() -> Self {
+        ChainRegistrar::default()
+    }
+
+    /// Add a registrar, asked after all registrars added before.
+    pub fn push<R>(&mut self, registrar: R)
+    where
+        R: Registrar + Send + Sync + 'static,
+    {
+        self.registrars.push(Box::new(registrar))
+    }
+
+    /// Find the first registrar that knows the client.
+    ///
+    /// A registrar failing for other reasons stops the search, a later registrar must not handle a
+    /// client only because the one responsible for it is unavailable.
+    fn registrar_of(&self, client_id: &str) -> Result<&dyn Registrar, RegistrarError> {
+        for registrar in &self.registrars {
+            let probe = ClientUrl {
+                client_id: Cow::Borrowed(client_id),
+                redirect_uri: None,
+            };
+
+            match registrar.bound_redirect(probe) {
+                Ok(_) => return Ok(&**registrar),
+                Err(RegistrarError::Unspecified) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(RegistrarError::Unspecified)
+    }
+}
+
+impl Registrar for ChainRegistrar {
+    fn bound_redirect<'a>(&self, bound: ClientUrl<'a>) -> Result<BoundClient<'a>, RegistrarError> {
+        self.registrar_of(&bound.client_id)?.bound_redirect(bound)
+    }
+
+    fn negotiate(&self, bound: BoundClient, scope: Option<Scope>) -> Result<PreGrant, RegistrarError> {
+        self.registrar_of(&bound.client_id)?.negotiate(bound, scope)
+    }
+
+    fn check(&self, client_id: &str, passphrase: Option<&[u8]>) -> Result<(), RegistrarError> {
+        self.registrar_of(client_id)?.check(client_id, passphrase)
+    }
+
+    fn check_assertion(&self, client_id: &str, assertion: &str) -> Result<(), RegistrarError> {
+        self.registrar_of(client_id)?
+            .check_assertion(client_id, assertion)
+    }
+
+    fn check_certificate(&self, client_id: &str, certificate: &[u8]) -> Result<(), RegistrarError> {
+        self.registrar_of(client_id)?
+            .check_certificate(client_id, certificate)
+    }
+
+    fn client_trust(&self, client_id: &str) -> ClientTrust {
+        self.registrar_of(client_id)
+            .map_or(ClientTrust::ThirdParty, |registrar| {
+                registrar.client_trust(client_id)
+            })
+    }
+
+    fn check_grant_type(&self, client_id: &str, grant_type: GrantType) -> Result<(), RegistrarError> {
+        self.registrar_of(client_id)?
+            .check_grant_type(client_id, grant_type)
+    }
 }
 
 #[cfg(test)]
@@ -923,6 +1871,330 @@ mod tests {
         assert!(client.check_authentication(Some(b"")).is_err());
     }
 
+    #[test]
+    fn validated_default_scope() {
+        let url: RegisteredUrl = "https://example.com".parse::<Url>().unwrap().into();
+
+        let client = Client::try_public("ClientId", url.clone(), "profile  email profile:read")
+            .expect("Valid scope was rejected");
+        let client = client.encode(&Argon2::default());
+        assert_eq!(client.default_scope.to_string(), "email profile profile:read");
+
+        assert!(matches!(
+            Client::try_public("ClientId", url.clone(), "email \"quoted\""),
+            Err(ParseScopeErr::InvalidCharacter('"'))
+        ));
+        assert!(matches!(
+            Client::try_public("ClientId", url.clone(), "email profile email"),
+            Err(ParseScopeErr::DuplicateToken(ref token)) if token == "email"
+        ));
+        assert!(matches!(
+            Client::try_confidential("ClientId", url.clone(), " ", b"passphrase"),
+            Err(ParseScopeErr::Empty)
+        ));
+        assert!(Client::try_confidential("ClientId", url, "email", b"passphrase").is_ok());
+    }
+
+    /// Stores the passphrase without any hashing, only for testing.
+    struct Plaintext;
+
+    impl PasswordPolicy for Plaintext {
+        fn store(&self, _: &str, passphrase: &[u8]) -> Vec<u8> {
+            passphrase.to_vec()
+        }
+
+        fn check(&self, _: &str, passphrase: &[u8], stored: &[u8]) -> Result<(), RegistrarError> {
+            match passphrase == stored {
+                true => Ok(()),
+                false => Err(RegistrarError::Unspecified),
+            }
+        }
+    }
+
+    fn policy_roundtrip(store: &dyn PasswordPolicy, check: &dyn PasswordPolicy) -> bool {
+        let pass = b"AB3fAj6GJpdxmEVeNCyPoA==";
+        let client = Client::confidential(
+            "ClientId",
+            "https://example.com".parse::<Url>().unwrap().into(),
+            "default".parse().unwrap(),
+            pass,
+        )
+        .encode(store);
+        RegisteredClient::new(&client, check)
+            .check_authentication(Some(pass))
+            .is_ok()
+    }
+
+    #[test]
+    fn custom_password_policy() {
+        let mut client_map = ClientMap::new();
+        client_map.set_password_policy(Plaintext);
+        let pass = b"AB3fAj6GJpdxmEVeNCyPoA==";
+        client_map.register_client(Client::confidential(
+            "ClientId",
+            "https://example.com".parse::<Url>().unwrap().into(),
+            "default".parse().unwrap(),
+            pass,
+        ));
+
+        assert!(client_map.check("ClientId", Some(pass)).is_ok());
+        assert!(client_map.check("ClientId", Some(b"not the passphrase")).is_err());
+
+        // The client was not stored with the new policy.
+        client_map.set_password_policy(Argon2::default());
+        assert!(client_map.check("ClientId", Some(pass)).is_err());
+    }
+
+    #[test]
+    fn mismatched_password_policy() {
+        assert!(policy_roundtrip(&Argon2::default(), &Argon2::default()));
+        assert!(policy_roundtrip(&Plaintext, &Plaintext));
+        assert!(!policy_roundtrip(&Argon2::default(), &Plaintext));
+        assert!(!policy_roundtrip(&Plaintext, &Argon2::default()));
+    }
+
+    #[cfg(feature = "pbkdf2")]
+    #[test]
+    fn pbkdf2_password_policy() {
+        let policy = Pbkdf2::default();
+        let pass = b"AB3fAj6GJpdxmEVeNCyPoA==";
+        let client = Client::confidential(
+            "ClientId",
+            "https://example.com".parse::<Url>().unwrap().into(),
+            "default".parse().unwrap(),
+            pass,
+        )
+        .encode(&policy);
+        let client = RegisteredClient::new(&client, &policy);
+        assert!(client.check_authentication(None).is_err());
+        assert!(client.check_authentication(Some(pass)).is_ok());
+        assert!(client.check_authentication(Some(b"not the passphrase")).is_err());
+        assert!(client.check_authentication(Some(b"")).is_err());
+
+        // Stored iterations are used, not the configured ones.
+        assert!(policy_roundtrip(&Pbkdf2::with_iterations(1000), &policy));
+        assert!(!policy_roundtrip(&Pbkdf2::default(), &Argon2::default()));
+        assert!(!policy_roundtrip(&Argon2::default(), &Pbkdf2::default()));
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn client_map_from_config() {
+        let toml = r#"
+            [[clients]]
+            client_id = "PrivateClient"
+            redirect_uri = "https://example.com/callback"
+            additional_redirect_uris = ["https://example.com/other"]
+            scope = "default"
+            secret = "AB3fAj6GJpdxmEVeNCyPoA=="
+
+            [[clients]]
+            client_id = "PublicClient"
+            redirect_uri = "https://example.com/public"
+            scope = "default extra"
+            trust = "FirstParty"
+            grant_types = ["authorization_code", "refresh_token"]
+        "#;
+
+        let json = r#"{ "clients": [
+            { "client_id": "PrivateClient", "redirect_uri": "https://example.com/callback",
+              "additional_redirect_uris": ["https://example.com/other"], "scope": "default",
+              "secret": "AB3fAj6GJpdxmEVeNCyPoA==" },
+            { "client_id": "PublicClient", "redirect_uri": "https://example.com/public",
+              "scope": "default extra", "trust": "FirstParty",
+              "grant_types": ["authorization_code", "refresh_token"] }
+        ] }"#;
+
+        for registrar in [
+            ClientMap::from_toml(toml).unwrap(),
+            ClientMap::from_json(json).unwrap(),
+        ] {
+            let other = ExactUrl::new("https://example.com/other".into()).unwrap();
+            let bound = registrar
+                .bound_redirect(ClientUrl {
+                    client_id: Cow::from("PrivateClient"),
+                    redirect_uri: Some(Cow::Borrowed(&other)),
+                })
+                .expect("Additional redirect uri should be registered");
+            let pre_grant = registrar.negotiate(bound, None).unwrap();
+            assert_eq!(pre_grant.scope, "default".parse().unwrap());
+
+            assert!(registrar
+                .check("PrivateClient", Some(b"AB3fAj6GJpdxmEVeNCyPoA=="))
+                .is_ok());
+            assert!(registrar.check("PrivateClient", None).is_err());
+            assert!(registrar.check("PublicClient", None).is_ok());
+            assert_eq!(registrar.client_trust("PublicClient"), ClientTrust::FirstParty);
+            assert_eq!(registrar.client_trust("PrivateClient"), ClientTrust::ThirdParty);
+
+            assert!(registrar
+                .check_grant_type("PublicClient", GrantType::RefreshToken)
+                .is_ok());
+            assert!(registrar
+                .check_grant_type("PublicClient", GrantType::ClientCredentials)
+                .is_err());
+            assert!(registrar
+                .check_grant_type("PrivateClient", GrantType::ClientCredentials)
+                .is_ok());
+            assert!(registrar
+                .check_grant_type("UnknownClient", GrantType::AuthorizationCode)
+                .is_err());
+        }
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn client_map_from_malformed_config() {
+        let invalid_uri = r#"
+            [[clients]]
+            client_id = "Client"
+            redirect_uri = "/callback"
+            scope = "default"
+        "#;
+        match ClientMap::from_toml(invalid_uri) {
+            Err(ClientConfigError::RedirectUri { client_id, uri, .. }) => {
+                assert_eq!(client_id, "Client");
+                assert_eq!(uri, "/callback");
+            }
+            other => panic!("Expected invalid redirect uri, got {:?}", other.err()),
+        }
+
+        let empty_scope = r#"{ "clients": [
+            { "client_id": "Client", "redirect_uri": "https://example.com", "scope": "" }
+        ] }"#;
+        assert!(matches!(
+            ClientMap::from_json(empty_scope),
+            Err(ClientConfigError::Scope { .. })
+        ));
+
+        let duplicate = r#"
+            [[clients]]
+            client_id = "Client"
+            redirect_uri = "https://example.com/a"
+            scope = "default"
+
+            [[clients]]
+            client_id = "Client"
+            redirect_uri = "https://example.com/b"
+            scope = "default"
+        "#;
+        assert!(matches!(
+            ClientMap::from_toml(duplicate),
+            Err(ClientConfigError::DuplicateClient(ref id)) if id == "Client"
+        ));
+
+        let unknown_field = r#"
+            [[clients]]
+            client_id = "Client"
+            redirect_uri = "https://example.com"
+            scope = "default"
+            secert = "typo"
+        "#;
+        assert!(matches!(
+            ClientMap::from_toml(unknown_field),
+            Err(ClientConfigError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn chain_registrar() {
+        let first: ClientMap = vec![Client::public(
+            "FirstClient",
+            "https://first.example".parse::<Url>().unwrap().into(),
+            "first".parse().unwrap(),
+        )]
+        .into_iter()
+        .collect();
+        let second: ClientMap = vec![
+            Client::confidential(
+                "SecondClient",
+                "https://second.example".parse::<Url>().unwrap().into(),
+                "second".parse().unwrap(),
+                b"SecondSecret",
+            ),
+            // Shadowed by the client of the first registrar.
+            Client::confidential(
+                "FirstClient",
+                "https://second.example".parse::<Url>().unwrap().into(),
+                "second".parse().unwrap(),
+                b"SecondSecret",
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut registrar = ChainRegistrar::new();
+        registrar.push(first);
+        registrar.push(second);
+
+        let requested = ExactUrl::new("https://second.example".into()).unwrap();
+        let bound = registrar
+            .bound_redirect(ClientUrl {
+                client_id: Cow::from("SecondClient"),
+                redirect_uri: Some(Cow::Borrowed(&requested)),
+            })
+            .expect("Client of the second registrar should be bound");
+        let pre_grant = registrar.negotiate(bound, None).unwrap();
+        assert_eq!(pre_grant.scope, "second".parse().unwrap());
+        assert!(registrar.check("SecondClient", Some(b"SecondSecret")).is_ok());
+        assert!(registrar.check("SecondClient", Some(b"wrong")).is_err());
+
+        // Only the first registrar knowing the client is asked.
+        assert!(registrar.check("FirstClient", None).is_ok());
+        assert!(registrar.check("FirstClient", Some(b"SecondSecret")).is_err());
+        assert!(registrar
+            .bound_redirect(ClientUrl {
+                client_id: Cow::from("FirstClient"),
+                redirect_uri: Some(Cow::Borrowed(&requested)),
+            })
+            .is_err());
+
+        // Unknown to all registrars.
+        assert!(registrar.check("UnknownClient", None).is_err());
+        assert!(registrar
+            .bound_redirect(ClientUrl {
+                client_id: Cow::from("UnknownClient"),
+                redirect_uri: None,
+            })
+            .is_err());
+        assert_eq!(registrar.client_trust("UnknownClient"), ClientTrust::ThirdParty);
+    }
+
+    #[test]
+    fn chain_registrar_stops_at_failure() {
+        struct Unavailable;
+
+        impl Registrar for Unavailable {
+            fn bound_redirect<'a>(&self, _: ClientUrl<'a>) -> Result<BoundClient<'a>, RegistrarError> {
+                Err(RegistrarError::PrimitiveError)
+            }
+
+            fn negotiate(&self, _: BoundClient, _: Option<Scope>) -> Result<PreGrant, RegistrarError> {
+                Err(RegistrarError::PrimitiveError)
+            }
+
+            fn check(&self, _: &str, _: Option<&[u8]>) -> Result<(), RegistrarError> {
+                Err(RegistrarError::PrimitiveError)
+            }
+        }
+
+        let fallback: ClientMap = vec![Client::public(
+            "Client",
+            "https://example.com".parse::<Url>().unwrap().into(),
+            "default".parse().unwrap(),
+        )]
+        .into_iter()
+        .collect();
+
+        let mut registrar = ChainRegistrar::new();
+        registrar.push(Unavailable);
+        registrar.push(fallback);
+        assert!(matches!(
+            registrar.check("Client", None),
+            Err(RegistrarError::PrimitiveError)
+        ));
+    }
+
     #[test]
     fn with_additional_redirect_uris() {
         let client_id = "ClientId";
@@ -970,6 +2242,32 @@ mod tests {
                 redirect_uri: Some(Cow::Borrowed(&"https://example.com:1234/foo".parse().unwrap()))
             })
             .is_err());
+
+        // Ambiguous without an explicit redirect uri.
+        assert!(client_map
+            .bound_redirect(ClientUrl {
+                client_id: Cow::from(client_id),
+                redirect_uri: None,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn single_redirect_uri_default() {
+        let client_id = "ClientId";
+        let redirect_uri: Url = "https://example.com/foo".parse().unwrap();
+        let default_scope = "default".parse().unwrap();
+        let client = Client::public(client_id, redirect_uri.clone().into(), default_scope);
+        let mut client_map = ClientMap::new();
+        client_map.register_client(client);
+
+        let bound = client_map
+            .bound_redirect(ClientUrl {
+                client_id: Cow::from(client_id),
+                redirect_uri: None,
+            })
+            .unwrap();
+        assert_eq!(bound.redirect_uri.as_ref(), &redirect_uri);
     }
 
     #[test]
@@ -1019,6 +2317,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn loopback_port_variance() {
+        let bound = |client_map: &ClientMap, client_id: &str, url: &str| {
+            client_map
+                .bound_redirect(ClientUrl {
+                    client_id: Cow::from(client_id),
+                    redirect_uri: Some(Cow::Owned(url.parse().unwrap())),
+                })
+                .map(|bound| bound.redirect_uri.into_owned())
+        };
+
+        let mut client_map = ClientMap::new();
+        for (client_id, url) in &[
+            ("ipv4", "http://127.0.0.1/cb"),
+            ("ipv6", "http://[::1]:8080/cb"),
+            ("localhost", "http://localhost/cb"),
+        ] {
+            let client = Client::public(
+                client_id,
+                ExactUrl::new(url.to_string()).unwrap().into(),
+                "default".parse().unwrap(),
+            )
+            .allow_loopback_port_variance();
+            client_map.register_client(client);
+        }
+        client_map.register_client(
+            Client::public(
+                "https",
+                ExactUrl::new("https://example.com:8443/cb".into())
+                    .unwrap()
+                    .into(),
+                "default".parse().unwrap(),
+            )
+            .allow_loopback_port_variance(),
+        );
+        client_map.register_client(Client::public(
+            "strict",
+            ExactUrl::new("http://127.0.0.1/cb".into()).unwrap().into(),
+            "default".parse().unwrap(),
+        ));
+
+        // The requested port is kept for the redirect.
+        for (client_id, url) in &[
+            ("ipv4", "http://127.0.0.1:51004/cb"),
+            ("ipv4", "http://127.0.0.1/cb"),
+            ("ipv6", "http://[::1]:51004/cb"),
+            ("ipv6", "http://[::1]/cb"),
+            ("localhost", "http://localhost:51004/cb"),
+        ] {
+            assert_eq!(
+                bound(&client_map, client_id, url).unwrap(),
+                RegisteredUrl::Exact(url.parse().unwrap())
+            );
+        }
+
+        for (client_id, url) in &[
+            // Scheme, host and path must still match.
+            ("ipv4", "https://127.0.0.1:51004/cb"),
+            ("ipv4", "http://127.0.0.2:51004/cb"),
+            ("ipv4", "http://localhost:51004/cb"),
+            ("ipv4", "http://127.0.0.1:51004/other"),
+            ("ipv6", "http://127.0.0.1:51004/cb"),
+            // Other hosts are matched exactly.
+            ("https", "https://example.com/cb"),
+            ("https", "https://example.com:443/cb"),
+            // The option is opt-in.
+            ("strict", "http://127.0.0.1:51004/cb"),
+        ] {
+            assert!(
+                bound(&client_map, client_id, url).is_err(),
+                "{} bound {}",
+                client_id,
+                url
+            );
+        }
+    }
+
     #[test]
     fn client_map() {
         let mut client_map = ClientMap::new();
@@ -1,7 +1,8 @@
 //! Defines the Scope type and parsing/formatting according to the rfc.
 use std::{cmp, fmt, str, error};
+use std::convert::TryFrom;
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use serde::{Deserialize, Serialize};
 
 /// Scope of a given grant or resource, a set of scope-tokens separated by spaces.
@@ -44,9 +45,12 @@ use serde::{Deserialize, Serialize};
 ///
 /// In particular, the characters '\x22' (`"`) and '\x5c' (`\`)  are not allowed.
 ///
+/// Parsing normalizes the scope. Repeated scope-tokens are only kept once and the order in which
+/// they were given has no meaning, so `read read write` and `write read` are the same scope. Both
+/// iteration and formatting yield the tokens in lexicographic order.
 #[derive(Clone, PartialEq, Eq)]
 pub struct Scope {
-    tokens: HashSet<String>,
+    tokens: BTreeSet<String>,
 }
 
 impl Serialize for Scope {
@@ -87,14 +91,297 @@ impl Scope {
 
     /// Determines if a resouce protected by this scope should allow access to a token with the
     /// grant on the right side. This operation is equivalent to comparison via `<=`.
+    ///
+    /// An empty grant carries no privileges. It allows access only to a resource requiring the
+    /// empty scope, while a resource requiring the empty scope allows access to any grant.
     pub fn allow_access(&self, rhs: &Scope) -> bool {
         self <= rhs
     }
 
-    /// Create an iterator over the individual scopes.
+    /// Check if this scope contains no scope tokens at all.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The scope containing the tokens of both scopes.
+    pub(crate) fn union(&self, rhs: &Scope) -> Scope {
+        Scope {
+            tokens: self.tokens.union(&rhs.tokens).cloned().collect(),
+        }
+    }
+
+    /// Create an iterator over the individual scopes, in lexicographic order.
     pub fn iter(&self) -> impl Iterator<Item = &str> {
         self.tokens.iter().map(AsRef::as_ref)
     }
+
+    /// Parse a scope, additionally rejecting duplicate tokens and an empty scope.
+    ///
+    /// Parsing through `FromStr` is lenient, as is appropriate for scopes requested by clients.
+    /// Scopes configured by the server itself, such as the default scope of a registered client,
+    /// should instead be parsed with this method so that mistakes are noticed early.
+    ///
+    /// ```
+    /// # use oxide_auth::primitives::scope::Scope;
+    /// assert!(Scope::parse_strict("email profile").is_ok());
+    /// assert!(Scope::parse_strict("email email").is_err());
+    /// assert!(Scope::parse_strict("").is_err());
+    /// ```
+    pub fn parse_strict(string: &str) -> Result<Scope, ParseScopeErr> {
+        let scope: Scope = string.parse()?;
+
+        let count = string.split(' ').filter(|s| !s.is_empty()).count();
+        if count != scope.tokens.len() {
+            let mut seen = HashSet::new();
+            let duplicate = string
+                .split(' ')
+                .filter(|s| !s.is_empty())
+                .find(|token| !seen.insert(*token))
+                .unwrap_or_default();
+            return Err(ParseScopeErr::DuplicateToken(duplicate.to_string()));
+        }
+
+        if scope.tokens.is_empty() {
+            return Err(ParseScopeErr::Empty);
+        }
+
+        Ok(scope)
+    }
+
+    /// Create a scope from individual scope-tokens, without parsing a string.
+    ///
+    /// Each token is validated on its own, so a token containing a space is rejected instead of
+    /// being split, as is an empty token. Repeated tokens are only kept once.
+    ///
+    /// ```
+    /// # use oxide_auth::primitives::scope::Scope;
+    /// let scope = Scope::from_tokens(["read", "write"]).unwrap();
+    /// assert_eq!(scope, "read write".parse().unwrap());
+    ///
+    /// assert!(Scope::from_tokens(["read write"]).is_err());
+    /// ```
+    pub fn from_tokens<I>(tokens: I) -> Result<Scope, ParseScopeErr>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut builder = Scope::builder();
+        for token in tokens {
+            builder = builder.token(token.as_ref());
+        }
+        builder.build()
+    }
+
+    /// Start building a scope from individual scope-tokens.
+    ///
+    /// ```
+    /// # use oxide_auth::primitives::scope::Scope;
+    /// let scope = Scope::builder()
+    ///     .token("read")
+    ///     .token("write")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(scope.to_string(), "read write");
+    /// ```
+    pub fn builder() -> ScopeBuilder {
+        ScopeBuilder {
+            tokens: Ok(BTreeSet::new()),
+        }
+    }
+
+    fn validate_token(token: &str) -> Result<(), ParseScopeErr> {
+        if token.is_empty() {
+            return Err(ParseScopeErr::EmptyToken);
+        }
+
+        match token
+            .chars()
+            .find(|&ch| ch == ' ' || Scope::invalid_scope_char(ch))
+        {
+            Some(ch) => Err(ParseScopeErr::InvalidCharacter(ch)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Builds a `Scope` from individual scope-tokens, see `Scope::builder`.
+///
+/// The first invalid token is reported when the scope is built.
+#[derive(Debug)]
+pub struct ScopeBuilder {
+    tokens: Result<BTreeSet<String>, ParseScopeErr>,
+}
+
+impl ScopeBuilder {
+    /// Add a scope-token.
+    pub fn token<T: Into<String>>(mut self, token: T) -> Self {
+        let token = token.into();
+        if let Ok(tokens) = &mut self.tokens {
+            match Scope::validate_token(&token) {
+                Ok(()) => {
+                    tokens.insert(token);
+                }
+                Err(err) => self.tokens = Err(err),
+            }
+        }
+        self
+    }
+
+    /// Finish the scope, failing if any of the tokens was invalid.
+    ///
+    /// A scope without any tokens is valid, it is the empty scope.
+    pub fn build(self) -> Result<Scope, ParseScopeErr> {
+        self.tokens.map(|tokens| Scope { tokens })
+    }
+}
+
+impl<'a> TryFrom<&'a [&'a str]> for Scope {
+    type Error = ParseScopeErr;
+
+    fn try_from(tokens: &'a [&'a str]) -> Result<Scope, ParseScopeErr> {
+        Scope::from_tokens(tokens)
+    }
+}
+
+/// A grant scope whose tokens form a hierarchy, where each token implies the tokens nested below it.
+///
+/// Tokens are split into segments at a separator, `:` by default. A token then also grants every
+/// token that extends it by further segments, while the comparison of `Scope` itself only accepts
+/// exactly matching tokens. This is opt-in since it changes the meaning of existing tokens.
+///
+/// ```
+/// # use oxide_auth::primitives::scope::{HierarchicalScope, Scope};
+/// let grant = HierarchicalScope::new("read:users".parse().unwrap());
+///
+/// assert!(grant.priviledged_to(&"read:users:profile".parse().unwrap()));
+/// assert!(grant.priviledged_to(&"read:users".parse().unwrap()));
+/// assert!(!grant.priviledged_to(&"read:user".parse().unwrap()));
+/// assert!(!grant.priviledged_to(&"read".parse().unwrap()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct HierarchicalScope {
+    scope: Scope,
+    separator: char,
+}
+
+impl HierarchicalScope {
+    /// Interpret the tokens of a scope as segments separated by `:`.
+    pub fn new(scope: Scope) -> Self {
+        Self::with_separator(scope, ':')
+    }
+
+    /// Interpret the tokens of a scope as segments separated by a custom character.
+    pub fn with_separator(scope: Scope, separator: char) -> Self {
+        HierarchicalScope { scope, separator }
+    }
+
+    /// The underlying scope.
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    /// Determines if some token of this scope is the token itself or one of its ancestors.
+    pub fn grants(&self, token: &str) -> bool {
+        self.scope
+            .iter()
+            .any(|granted| match token.strip_prefix(granted) {
+                Some("") => true,
+                Some(rest) => rest.starts_with(self.separator),
+                None => false,
+            })
+    }
+
+    /// Determines if this scope has enough privileges to access some resource requiring the scope
+    /// on the right side, when each token also grants all tokens nested below it.
+    pub fn priviledged_to(&self, rhs: &Scope) -> bool {
+        rhs.iter().all(|token| self.grants(token))
+    }
+}
+
+/// Human readable descriptions of scope tokens, to be shown on a consent page.
+///
+/// Descriptions can be registered for all clients or for a single client, where the latter take
+/// precedence. Scope tokens without any description are described by the raw token itself.
+///
+/// ```
+/// # use oxide_auth::primitives::scope::ScopeDescriptions;
+/// let mut descriptions = ScopeDescriptions::new();
+/// descriptions.insert("profile:read", "Read your profile");
+/// descriptions.insert_for_client("LocalClient", "profile:read", "Show your name in the chat");
+///
+/// assert_eq!(descriptions.describe("OtherClient", "profile:read"), "Read your profile");
+/// assert_eq!(descriptions.describe("LocalClient", "profile:read"), "Show your name in the chat");
+/// assert_eq!(descriptions.describe("OtherClient", "email"), "email");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ScopeDescriptions {
+    common: HashMap<String, String>,
+    clients: HashMap<String, HashMap<String, String>>,
+}
+
+/// A single scope token together with its description.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopeDescription {
+    /// The raw scope token.
+    pub token: String,
+
+    /// The description of the token, or the token itself if none was registered.
+    pub description: String,
+}
+
+impl ScopeDescriptions {
+    /// Create an empty registry, describing every token by itself.
+    pub fn new() -> Self {
+        ScopeDescriptions::default()
+    }
+
+    /// Set the description of a scope token for all clients.
+    pub fn insert<T, D>(&mut self, token: T, description: D)
+    where
+        T: Into<String>,
+        D: Into<String>,
+    {
+        self.common.insert(token.into(), description.into());
+    }
+
+    /// Set the description of a scope token for a single client.
+    ///
+    /// This overrides any description registered for all clients.
+    pub fn insert_for_client<C, T, D>(&mut self, client_id: C, token: T, description: D)
+    where
+        C: Into<String>,
+        T: Into<String>,
+        D: Into<String>,
+    {
+        self.clients
+            .entry(client_id.into())
+            .or_default()
+            .insert(token.into(), description.into());
+    }
+
+    /// Describe a single scope token requested by a client.
+    ///
+    /// Falls back to the raw token if no description has been registered.
+    pub fn describe<'a>(&'a self, client_id: &str, token: &'a str) -> &'a str {
+        self.clients
+            .get(client_id)
+            .and_then(|client| client.get(token))
+            .or_else(|| self.common.get(token))
+            .map_or(token, String::as_str)
+    }
+
+    /// Describe all tokens of a scope requested by a client, ordered by token.
+    pub fn describe_scope(&self, client_id: &str, scope: &Scope) -> Vec<ScopeDescription> {
+        let mut described: Vec<_> = scope
+            .iter()
+            .map(|token| ScopeDescription {
+                token: token.to_owned(),
+                description: self.describe(client_id, token).to_owned(),
+            })
+            .collect();
+        described.sort_by(|a, b| a.token.cmp(&b.token));
+        described
+    }
 }
 
 /// Error returned from parsing a scope as encoded in an authorization token request.
@@ -110,6 +397,22 @@ pub enum ParseScopeErr {
     ///
     /// In particular, the characters '\x22' (`"`) and '\x5c' (`\`)  are not allowed.
     InvalidCharacter(char),
+
+    /// A scope-token appeared more than once.
+    ///
+    /// Only returned by `Scope::parse_strict`.
+    DuplicateToken(String),
+
+    /// The scope contained no scope-token at all.
+    ///
+    /// Only returned by `Scope::parse_strict`.
+    Empty,
+
+    /// A scope-token was empty.
+    ///
+    /// Only returned when a scope is created from individual tokens, such as with
+    /// `Scope::from_tokens`.
+    EmptyToken,
 }
 
 impl error::Error for ParseScopeErr {}
@@ -134,6 +437,11 @@ impl fmt::Display for ParseScopeErr {
             ParseScopeErr::InvalidCharacter(chr) => {
                 write!(fmt, "Encountered invalid character in scope: {}", chr)
             }
+            ParseScopeErr::DuplicateToken(token) => {
+                write!(fmt, "Encountered duplicate token in scope: {}", token)
+            }
+            ParseScopeErr::Empty => write!(fmt, "Encountered empty scope"),
+            ParseScopeErr::EmptyToken => write!(fmt, "Encountered empty token in scope"),
         }
     }
 }
@@ -144,15 +452,11 @@ impl fmt::Debug for Scope {
     }
 }
 
+/// Formats the scope-tokens in lexicographic order, so that equal scopes have equal output.
 impl fmt::Display for Scope {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let output = self
-            .tokens
-            .iter()
-            .map(String::as_str)
-            .collect::<Vec<_>>()
-            .join(" ");
-        fmt.write_str(&output)
+        let tokens = self.tokens.iter().map(String::as_str).collect::<Vec<_>>();
+        fmt.write_str(&tokens.join(" "))
     }
 }
 
@@ -190,6 +494,12 @@ mod tests {
         assert_eq!(scope, from_string);
     }
 
+    #[test]
+    fn test_normalized_display() {
+        let scope = "profile email  email profile:read".parse::<Scope>().unwrap();
+        assert_eq!(scope.to_string(), "email profile profile:read");
+    }
+
     #[test]
     fn test_compare() {
         let scope_base = "cap1 cap2".parse::<Scope>().unwrap();
@@ -218,6 +528,32 @@ mod tests {
         assert!(!scope_uncmp.allow_access(&scope_base));
     }
 
+    #[test]
+    fn test_hierarchical() {
+        let grant = HierarchicalScope::new("read:users write:posts:draft".parse().unwrap());
+
+        assert!(grant.priviledged_to(&"read:users".parse().unwrap()));
+        assert!(grant.priviledged_to(&"read:users:profile".parse().unwrap()));
+        assert!(grant.priviledged_to(&"read:users:profile:email write:posts:draft:1".parse().unwrap()));
+
+        // Only whole segments are matched.
+        assert!(!grant.priviledged_to(&"read:user".parse().unwrap()));
+        assert!(!grant.priviledged_to(&"read:usersettings".parse().unwrap()));
+        // Parents are not implied by their children.
+        assert!(!grant.priviledged_to(&"read".parse().unwrap()));
+        assert!(!grant.priviledged_to(&"write:posts".parse().unwrap()));
+        // All tokens must be granted.
+        assert!(!grant.priviledged_to(&"read:users:profile write:posts".parse().unwrap()));
+
+        let dotted = HierarchicalScope::with_separator("read.users".parse().unwrap(), '.');
+        assert!(dotted.priviledged_to(&"read.users.profile".parse().unwrap()));
+        assert!(!dotted.priviledged_to(&"read.users:profile".parse().unwrap()));
+
+        // The default comparison still requires exact tokens.
+        let exact = "read:users".parse::<Scope>().unwrap();
+        assert!(!exact.priviledged_to(&"read:users:profile".parse().unwrap()));
+    }
+
     #[test]
     fn test_iterating() {
         let scope = "cap1 cap2 cap3".parse::<Scope>().unwrap();
@@ -226,6 +562,100 @@ mod tests {
         assert!(all.contains(&"cap1"));
         assert!(all.contains(&"cap2"));
         assert!(all.contains(&"cap3"));
+
+        let shuffled = "cap3 cap1 cap2 cap1".parse::<Scope>().unwrap();
+        assert_eq!(shuffled.iter().collect::<Vec<_>>(), ["cap1", "cap2", "cap3"]);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let duplicated = "read read write".parse::<Scope>().unwrap();
+        let plain = "read write".parse::<Scope>().unwrap();
+        let reordered = "write read".parse::<Scope>().unwrap();
+
+        assert_eq!(duplicated, plain);
+        assert_eq!(duplicated, reordered);
+        assert_eq!(duplicated.partial_cmp(&plain), Some(cmp::Ordering::Equal));
+        assert_eq!(duplicated.to_string(), "read write");
+
+        let read = "read read".parse::<Scope>().unwrap();
+        assert!(read.allow_access(&duplicated));
+        assert!(duplicated.priviledged_to(&read));
+        assert!(!duplicated.allow_access(&read));
+
+        let other = "read admin".parse::<Scope>().unwrap();
+        assert_eq!(duplicated.partial_cmp(&other), None);
+    }
+
+    #[test]
+    fn test_descriptions() {
+        let mut descriptions = ScopeDescriptions::new();
+        descriptions.insert("cap1", "First capability");
+        descriptions.insert("cap2", "Second capability");
+        descriptions.insert_for_client("client", "cap2", "Special capability");
+
+        let scope = "cap3 cap2 cap1".parse::<Scope>().unwrap();
+        let described = descriptions.describe_scope("client", &scope);
+        let pairs = described
+            .iter()
+            .map(|desc| (desc.token.as_str(), desc.description.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            [
+                ("cap1", "First capability"),
+                ("cap2", "Special capability"),
+                ("cap3", "cap3")
+            ]
+        );
+
+        assert_eq!(descriptions.describe("other", "cap2"), "Second capability");
+    }
+
+    #[test]
+    fn test_from_tokens() {
+        let scope = "email profile".parse::<Scope>().unwrap();
+        assert_eq!(Scope::from_tokens(["profile", "email"]).unwrap(), scope);
+        assert_eq!(Scope::from_tokens(["email", "profile", "email"]).unwrap(), scope);
+        assert_eq!(Scope::try_from(&["email", "profile"][..]).unwrap(), scope);
+        assert_eq!(
+            Scope::builder()
+                .token("email")
+                .token(String::from("profile"))
+                .build()
+                .unwrap(),
+            scope
+        );
+        assert!(Scope::from_tokens(Vec::<String>::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_from_invalid_tokens() {
+        assert!(matches!(
+            Scope::from_tokens(["read write"]),
+            Err(ParseScopeErr::InvalidCharacter(' '))
+        ));
+        assert!(matches!(
+            Scope::from_tokens(["read", "wr\\ite"]),
+            Err(ParseScopeErr::InvalidCharacter('\\'))
+        ));
+        assert!(matches!(
+            Scope::try_from(&["read", "\x22"][..]),
+            Err(ParseScopeErr::InvalidCharacter('"'))
+        ));
+        assert!(matches!(
+            Scope::from_tokens(["read\n"]),
+            Err(ParseScopeErr::InvalidCharacter('\n'))
+        ));
+        assert!(matches!(
+            Scope::builder().token("read").token("").build(),
+            Err(ParseScopeErr::EmptyToken)
+        ));
+        // The first invalid token is reported.
+        assert!(matches!(
+            Scope::builder().token("a\tb").token("").token("c d").build(),
+            Err(ParseScopeErr::InvalidCharacter('\t'))
+        ));
     }
 
     #[test]
@@ -0,0 +1,84 @@
+//! Derivation of the subject identifier written into tokens.
+//!
+//! JWT access tokens name the resource owner in their `sub` claim. By default this is the
+//! `owner_id` of the grant, which lets any two clients correlate the tokens of the same owner. A
+//! [`PairwiseSubject`] instead derives a different, opaque identifier for each client.
+//!
+//! ```
+//! # use oxide_auth::primitives::subject::{PairwiseSubject, SubjectEncoder};
+//! let encoder = PairwiseSubject::new(b"a secret only known to the issuer");
+//! let first = encoder.encode("Owner", "FirstClient");
+//! assert_eq!(first, encoder.encode("Owner", "FirstClient"));
+//! assert_ne!(first, encoder.encode("Owner", "SecondClient"));
+//! ```
+//!
+//! [`PairwiseSubject`]: struct.PairwiseSubject.html
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Derives the subject identifier of a token from its owner and client.
+pub trait SubjectEncoder {
+    /// The subject presented to the client `client_id` for the owner `owner_id`.
+    fn encode(&self, owner_id: &str, client_id: &str) -> String;
+}
+
+/// Use the owner id as the subject, used by all primitives by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PublicSubject;
+
+/// Pairwise subject identifiers, keyed with a secret.
+///
+/// The subject is the HMAC-SHA256 of the client and owner id, encoded as unpadded base64url. The
+/// same owner thus has a stable subject towards each client but the subjects of different clients
+/// can not be linked without the key.
+#[derive(Clone)]
+pub struct PairwiseSubject {
+    hasher: Hmac<Sha256>,
+}
+
+impl SubjectEncoder for PublicSubject {
+    fn encode(&self, owner_id: &str, _: &str) -> String {
+        owner_id.to_string()
+    }
+}
+
+impl PairwiseSubject {
+    /// Derive subjects with the given secret key.
+    pub fn new(key: &[u8]) -> Self {
+        PairwiseSubject {
+            // Hmac accepts keys of any length.
+            hasher: Hmac::new_from_slice(key).unwrap(),
+        }
+    }
+}
+
+impl SubjectEncoder for PairwiseSubject {
+    fn encode(&self, owner_id: &str, client_id: &str) -> String {
+        let mut hasher = self.hasher.clone();
+        // Length prefix the client so that no two pairs hash the same input.
+        hasher.update(&(client_id.len() as u64).to_be_bytes());
+        hasher.update(client_id.as_bytes());
+        hasher.update(owner_id.as_bytes());
+        URL_SAFE_NO_PAD.encode(hasher.finalize().into_bytes())
+    }
+}
+
+impl<S: SubjectEncoder + ?Sized> SubjectEncoder for &'_ S {
+    fn encode(&self, owner_id: &str, client_id: &str) -> String {
+        (**self).encode(owner_id, client_id)
+    }
+}
+
+impl<S: SubjectEncoder + ?Sized> SubjectEncoder for Box<S> {
+    fn encode(&self, owner_id: &str, client_id: &str) -> String {
+        (**self).encode(owner_id, client_id)
+    }
+}
+
+impl<S: SubjectEncoder + ?Sized> SubjectEncoder for std::sync::Arc<S> {
+    fn encode(&self, owner_id: &str, client_id: &str) -> String {
+        (**self).encode(owner_id, client_id)
+    }
+}